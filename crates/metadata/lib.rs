@@ -299,6 +299,46 @@ impl Metadata {
         cargo_args
     }
 
+    /// Whether the `rustc-args` or `rustdoc-args` opt into nightly-only behaviour.
+    ///
+    /// That's `--cfg docsrs`, which crates use to gate unstable doc attributes like
+    /// `#![feature(doc_cfg)]`, and `-Z` flags. `-Z unstable-options` is ignored, docs.rs passes
+    /// it to every build.
+    pub fn uses_nightly_features(&self) -> bool {
+        [&self.rustc_args, &self.rustdoc_args].iter().any(|args| {
+            let flags = args.iter().map(String::as_str);
+            let values = args.iter().map(String::as_str).skip(1).chain(Some(""));
+            flags.zip(values).any(|(flag, value)| match flag {
+                "--cfg" => value == "docsrs",
+                "--cfg=docsrs" => true,
+                "-Z" => value != "unstable-options",
+                _ => flag.starts_with("-Z") && flag != "-Zunstable-options",
+            })
+        })
+    }
+
+    /// The `--cfg` flags passed to `rustc` and `rustdoc`, like `docsrs`, without duplicates.
+    pub fn cfgs(&self) -> Vec<String> {
+        let mut cfgs: Vec<String> = Vec::new();
+        for args in [&self.rustc_args, &self.rustdoc_args] {
+            let mut args = args.iter();
+            while let Some(arg) = args.next() {
+                let cfg = match arg.strip_prefix("--cfg=") {
+                    Some(cfg) => cfg,
+                    None if arg == "--cfg" => match args.next() {
+                        Some(cfg) => cfg,
+                        None => break,
+                    },
+                    None => continue,
+                };
+                if !cfgs.iter().any(|existing| existing == cfg) {
+                    cfgs.push(cfg.to_owned());
+                }
+            }
+        }
+        cfgs
+    }
+
     /// Return the environment variables that should be set when building this crate.
     pub fn environment_variables(&self) -> HashMap<&'static str, String> {
         let mut map = HashMap::new();
@@ -476,6 +516,57 @@ mod test_parsing {
         assert!(metadata.default_target.is_some());
     }
 
+    #[test]
+    fn test_nightly_features() {
+        let uses_nightly_features = |docs_rs: &str| {
+            Metadata::from_str(&format!("[package.metadata.docs.rs]\n{}", docs_rs))
+                .unwrap()
+                .uses_nightly_features()
+        };
+
+        assert!(!uses_nightly_features(""));
+        assert!(!uses_nightly_features(
+            r#"rustdoc-args = ["--cfg", "feature=\"x\""]"#
+        ));
+        assert!(!uses_nightly_features(
+            r#"rustdoc-args = ["-Zunstable-options"]"#
+        ));
+        assert!(uses_nightly_features(
+            r#"rustdoc-args = ["--cfg", "docsrs"]"#
+        ));
+        assert!(uses_nightly_features(r#"rustdoc-args = ["--cfg=docsrs"]"#));
+        assert!(uses_nightly_features(r#"rustc-args = ["--cfg", "docsrs"]"#));
+        assert!(uses_nightly_features(
+            r#"rustdoc-args = ["-Z", "rustdoc-map", "--example-rustdoc-arg"]"#
+        ));
+        assert!(uses_nightly_features(r#"rustdoc-args = ["-Zrustdoc-map"]"#));
+    }
+
+    #[test]
+    fn test_cfgs() {
+        let cfgs = |docs_rs: &str| {
+            Metadata::from_str(&format!("[package.metadata.docs.rs]\n{}", docs_rs))
+                .unwrap()
+                .cfgs()
+        };
+
+        assert!(cfgs("").is_empty());
+        assert_eq!(
+            cfgs(
+                r#"rustdoc-args = ["--cfg", "docsrs", "--cfg=feature=\"x\"", "-Zunstable-options"]"#
+            ),
+            vec!["docsrs".to_owned(), r#"feature="x""#.to_owned()]
+        );
+        assert_eq!(
+            cfgs(
+                r#"rustc-args = ["--cfg", "docsrs"]
+                rustdoc-args = ["--cfg", "docsrs", "--cfg", "other"]"#
+            ),
+            vec!["docsrs".to_owned(), "other".to_owned()]
+        );
+        assert!(cfgs(r#"rustdoc-args = ["--cfg"]"#).is_empty());
+    }
+
     #[test]
     fn test_proc_macro() {
         let manifest = r#"
@@ -17,6 +17,7 @@ use docs_rs::{
     Storage,
 };
 use once_cell::sync::OnceCell;
+use opentelemetry::{sdk::Resource, KeyValue};
 use tokio::runtime::{Builder, Runtime};
 use tracing_log::LogTracer;
 use tracing_subscriber::{filter::Directive, prelude::*, EnvFilter};
@@ -26,8 +27,47 @@ fn main() {
     // through rustwide.
     rustwide::logging::init_with(LogTracer::new());
 
+    let command = CommandLine::parse();
+    let result = run(command);
+
+    if let Err(err) = result {
+        let mut msg = format!("Error: {}", err);
+        for cause in err.chain() {
+            write!(msg, "\n\nCaused by:\n    {}", cause).unwrap();
+        }
+        eprintln!("{}", msg);
+
+        let backtrace = err.backtrace().to_string();
+        if !backtrace.is_empty() {
+            eprintln!("\nStack backtrace:\n{}", backtrace);
+        }
+
+        std::process::exit(1);
+    }
+}
+
+/// Sets up tracing with the loaded config and runs `command`.
+fn run(command: CommandLine) -> Result<()> {
+    let ctx = BinContext::new();
+    let config = ctx.config()?;
+
+    // Exporting spans to an OpenTelemetry collector is opt-in, since it adds
+    // overhead to every request.
+    let (_otlp_runtime, otlp_layer) = match &config.otlp_endpoint {
+        Some(endpoint) => {
+            let (runtime, tracer) = opentelemetry_tracer(endpoint.clone())
+                .context("could not set up the OTLP exporter")?;
+            (
+                Some(runtime),
+                Some(tracing_opentelemetry::layer().with_tracer(tracer)),
+            )
+        }
+        None => (None, None),
+    };
+
     let tracing_registry = tracing_subscriber::registry()
         .with(tracing_subscriber::fmt::layer())
+        .with(otlp_layer)
         .with(
             EnvFilter::builder()
                 .with_default_directive(Directive::from_str("docs_rs=info").unwrap())
@@ -51,24 +91,44 @@ fn main() {
         None
     };
 
-    if let Err(err) = CommandLine::parse().handle_args() {
-        let mut msg = format!("Error: {}", err);
-        for cause in err.chain() {
-            write!(msg, "\n\nCaused by:\n    {}", cause).unwrap();
-        }
-        eprintln!("{}", msg);
+    let result = command.handle_args(ctx);
 
-        let backtrace = err.backtrace().to_string();
-        if !backtrace.is_empty() {
-            eprintln!("\nStack backtrace:\n{}", backtrace);
-        }
+    // flush all spans that weren't exported yet, before the runtime of the exporter is dropped
+    opentelemetry::global::shutdown_tracer_provider();
 
-        // we need to drop the sentry guard here so all unsent
-        // errors are sent to sentry before
-        // process::exit kills everything.
-        drop(_sentry_guard);
-        std::process::exit(1);
-    }
+    // dropping the sentry guard when returning sends all unsent errors to sentry before
+    // process::exit kills everything.
+    result
+}
+
+/// Creates a tracer exporting all spans to the OpenTelemetry collector at `endpoint`.
+///
+/// The batch exporter runs on its own tokio runtime, which has to be kept alive
+/// as long as spans should be exported.
+fn opentelemetry_tracer(endpoint: String) -> Result<(Runtime, opentelemetry::sdk::trace::Tracer)> {
+    let runtime = Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("otlp-exporter")
+        .enable_all()
+        .build()?;
+
+    let tracer =
+        {
+            let _guard = runtime.enter();
+            opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(
+                    opentelemetry_otlp::new_exporter()
+                        .tonic()
+                        .with_endpoint(endpoint),
+                )
+                .with_trace_config(opentelemetry::sdk::trace::config().with_resource(
+                    Resource::new(vec![KeyValue::new("service.name", "docs.rs")]),
+                ))
+                .install_batch(opentelemetry::runtime::Tokio)?
+        };
+
+    Ok((runtime, tracer))
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
@@ -133,9 +193,7 @@ enum CommandLine {
 }
 
 impl CommandLine {
-    fn handle_args(self) -> Result<()> {
-        let ctx = BinContext::new();
-
+    fn handle_args(self, ctx: BinContext) -> Result<()> {
         match self {
             Self::Build {
                 skip_if_exists,
@@ -188,6 +246,9 @@ enum QueueSubcommand {
             default_value = "5"
         )]
         build_priority: i32,
+        /// Scoped registry the crate is published to, one of `DOCSRS_SCOPED_REGISTRIES`
+        #[arg(long = "registry")]
+        registry: Option<String>,
     },
 
     /// Interactions with build queue priorities
@@ -204,6 +265,7 @@ impl QueueSubcommand {
                 crate_name,
                 crate_version,
                 build_priority,
+                registry: None,
             } => ctx.build_queue()?.add_crate(
                 &crate_name,
                 &crate_version,
@@ -211,6 +273,18 @@ impl QueueSubcommand {
                 ctx.config()?.registry_url.as_deref(),
             )?,
 
+            Self::Add {
+                crate_name,
+                crate_version,
+                build_priority,
+                registry: Some(registry),
+            } => ctx.build_queue()?.add_scoped_crate(
+                &registry,
+                &crate_name,
+                &crate_version,
+                build_priority,
+            )?,
+
             Self::DefaultPriority { subcommand } => subcommand.handle_args(ctx)?,
         }
         Ok(())
@@ -393,6 +467,28 @@ enum DatabaseSubcommand {
         name: String,
     },
 
+    /// Recompress existing build logs in storage with gzip
+    RecompressBuildLogs {
+        /// Only process builds with an id greater than this, to resume an earlier run
+        #[arg(long)]
+        start_after: Option<i32>,
+
+        /// How many builds to fetch from the database at once
+        #[arg(long, default_value = "1000")]
+        batch_size: i64,
+    },
+
+    /// Recompress the HTML pages in existing rustdoc and source archives with zstd
+    RecompressArchives {
+        /// Only process releases with an id greater than this, to resume an earlier run
+        #[arg(long)]
+        start_after: Option<i32>,
+
+        /// How many releases to fetch from the database at once
+        #[arg(long, default_value = "100")]
+        batch_size: i64,
+    },
+
     AddDirectory {
         /// Path of file or directory
         #[arg(name = "DIRECTORY")]
@@ -411,6 +507,12 @@ enum DatabaseSubcommand {
         command: BlacklistSubcommand,
     },
 
+    /// Release channel operations
+    Channel {
+        #[command(subcommand)]
+        command: ChannelSubcommand,
+    },
+
     /// Compares the database with the index and resolves inconsistencies
     #[cfg(feature = "consistency_check")]
     Synchronize {
@@ -446,6 +548,34 @@ impl DatabaseSubcommand {
                 )?;
             }
 
+            Self::RecompressBuildLogs {
+                start_after,
+                batch_size,
+            } => {
+                let recompressed = db::recompress_build_logs(
+                    &mut *ctx.conn()?,
+                    &*ctx.storage()?,
+                    start_after,
+                    batch_size,
+                )
+                .context("failed to recompress build logs")?;
+                println!("recompressed {} build logs", recompressed);
+            }
+
+            Self::RecompressArchives {
+                start_after,
+                batch_size,
+            } => {
+                let recompressed = db::recompress_archives(
+                    &mut *ctx.conn()?,
+                    &*ctx.storage()?,
+                    start_after,
+                    batch_size,
+                )
+                .context("failed to recompress archives")?;
+                println!("recompressed {} archives", recompressed);
+            }
+
             Self::AddDirectory { directory } => {
                 add_path_into_database(&*ctx.storage()?, &ctx.config()?.prefix, directory)
                     .context("Failed to add directory into database")?;
@@ -466,6 +596,7 @@ impl DatabaseSubcommand {
             )
             .context("failed to delete the crate")?,
             Self::Blacklist { command } => command.handle_args(ctx)?,
+            Self::Channel { command } => command.handle_args(ctx)?,
 
             #[cfg(feature = "consistency_check")]
             Self::Synchronize { dry_run } => {
@@ -517,6 +648,81 @@ impl BlacklistSubcommand {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+enum ChannelSubcommand {
+    /// List all release channels
+    List,
+
+    /// Point a release channel of a crate to a version
+    Set {
+        /// Crate name
+        #[arg(name = "CRATE_NAME")]
+        crate_name: String,
+
+        /// Channel name, e.g. `stable` or `lts`
+        #[arg(name = "CHANNEL")]
+        channel: String,
+
+        /// The version the channel points to
+        #[arg(name = "VERSION")]
+        version: String,
+    },
+
+    /// Remove a release channel of a crate
+    Remove {
+        /// Crate name
+        #[arg(name = "CRATE_NAME")]
+        crate_name: String,
+
+        /// Channel name
+        #[arg(name = "CHANNEL")]
+        channel: String,
+    },
+}
+
+impl ChannelSubcommand {
+    fn handle_args(self, ctx: BinContext) -> Result<()> {
+        let conn = &mut *ctx.conn()?;
+        match self {
+            Self::List => {
+                let channels =
+                    db::channels::list_channels(conn).context("failed to list channels")?;
+
+                for (crate_name, channel, version) in channels {
+                    println!("{} {} -> {}", crate_name, channel, version);
+                }
+            }
+
+            Self::Set {
+                crate_name,
+                channel,
+                version,
+            } => db::channels::set_channel(
+                conn,
+                &*ctx.config()?,
+                &*ctx.cdn()?,
+                &crate_name,
+                &channel,
+                &version,
+            )
+            .context("failed to set channel")?,
+
+            Self::Remove {
+                crate_name,
+                channel,
+            } => db::channels::remove_channel(
+                conn,
+                &*ctx.config()?,
+                &*ctx.cdn()?,
+                &crate_name,
+                &channel,
+            )
+            .context("failed to remove channel")?,
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
 enum DeleteSubcommand {
     /// Delete a whole crate
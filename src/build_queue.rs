@@ -3,7 +3,9 @@ use crate::db::{delete_crate, Pool};
 use crate::docbuilder::PackageKind;
 use crate::error::Result;
 use crate::storage::Storage;
-use crate::utils::{get_config, get_crate_priority, report_error, set_config, ConfigName};
+use crate::utils::{
+    get_config, get_crate_priority, report_error, scoped_registry, set_config, ConfigName,
+};
 use crate::{Config, Index, Metrics, RustwideBuilder};
 use anyhow::Context;
 
@@ -85,13 +87,117 @@ impl BuildQueue {
              ON CONFLICT (name, version) DO UPDATE
                 SET priority = EXCLUDED.priority,
                     registry = EXCLUDED.registry,
-                    attempt = 0
+                    attempt = 0,
+                    -- crates which failed too often are queued again from now
+                    queued_at = CASE
+                        WHEN queue.attempt >= $5 THEN EXCLUDED.queued_at
+                        ELSE queue.queued_at
+                    END
             ;",
-            &[&name, &version, &priority, &registry],
+            &[&name, &version, &priority, &registry, &self.max_attempts],
         )?;
         Ok(())
     }
 
+    /// Queues a release of a crate from one of the scoped registries, stored under its scoped
+    /// name, see [`crate::utils::scoped_registry`].
+    pub fn add_scoped_crate(
+        &self,
+        registry: &str,
+        name: &str,
+        version: &str,
+        priority: i32,
+    ) -> Result<()> {
+        let index_url = self
+            .config
+            .scoped_registry_url(registry)
+            .with_context(|| format!("unknown scoped registry {}", registry))?;
+        self.add_crate(
+            &scoped_registry::scoped_name(registry, name),
+            version,
+            priority,
+            Some(index_url),
+        )
+    }
+
+    /// Adds a manually requested rebuild to the queue, recording who triggered it.
+    ///
+    /// Returns the position of the rebuild in the queue, or `None` when the release is already
+    /// queued or being built. Releases that failed too often are queued again.
+    pub(crate) fn add_rebuild(
+        &self,
+        name: &str,
+        version: &str,
+        priority: i32,
+        triggered_by: &str,
+    ) -> Result<Option<usize>> {
+        let mut conn = self.db.get()?;
+        let id: i32 = match conn.query_opt(
+            "INSERT INTO queue (name, version, priority, triggered_by)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (name, version) DO UPDATE
+                SET priority = EXCLUDED.priority,
+                    triggered_by = EXCLUDED.triggered_by,
+                    attempt = 0,
+                    queued_at = EXCLUDED.queued_at
+                WHERE queue.attempt >= $5
+             RETURNING id",
+            &[
+                &name,
+                &version,
+                &priority,
+                &triggered_by,
+                &self.max_attempts,
+            ],
+        )? {
+            Some(row) => row.get(0),
+            None => return Ok(None),
+        };
+
+        let position: i64 = conn
+            .query_one(
+                "SELECT COUNT(*)
+                 FROM queue
+                 WHERE
+                    attempt < $1 AND
+                    (priority, attempt, id) <= (SELECT priority, attempt, id FROM queue WHERE id = $2)",
+                &[&self.max_attempts, &id],
+            )?
+            .get(0);
+        Ok(Some(position as usize))
+    }
+
+    /// Returns a version of the crate waiting in the queue or being built, with its position in
+    /// the queue. Only `version` is looked up when given.
+    pub(crate) fn queued_position(
+        &self,
+        name: &str,
+        version: Option<&str>,
+    ) -> Result<Option<(String, usize)>> {
+        Ok(self
+            .db
+            .get()?
+            .query_opt(
+                "SELECT
+                    queued.version,
+                    (SELECT COUNT(*)
+                     FROM queue
+                     WHERE
+                        attempt < $1 AND
+                        (priority, attempt, id) <= (queued.priority, queued.attempt, queued.id)
+                    ) AS position
+                 FROM queue AS queued
+                 WHERE
+                    queued.name = $2 AND
+                    ($3::TEXT IS NULL OR queued.version = $3) AND
+                    queued.attempt < $1
+                 ORDER BY queued.priority, queued.attempt, queued.id
+                 LIMIT 1",
+                &[&self.max_attempts, &name, &version],
+            )?
+            .map(|row| (row.get(0), row.get::<_, i64>(1) as usize)))
+    }
+
     pub(crate) fn pending_count(&self) -> Result<usize> {
         Ok(self.pending_count_by_priority()?.values().sum::<usize>())
     }
@@ -121,6 +227,35 @@ impl BuildQueue {
             .collect())
     }
 
+    /// Time since the oldest crate waiting in the queue was added, `None` for an empty queue.
+    pub(crate) fn oldest_pending_age(&self) -> Result<Option<Duration>> {
+        let seconds: Option<f64> = self
+            .db
+            .get()?
+            .query_one(
+                "SELECT EXTRACT(EPOCH FROM NOW() - MIN(queued_at))::FLOAT8
+                 FROM queue
+                 WHERE attempt < $1",
+                &[&self.max_attempts],
+            )?
+            .get(0);
+        Ok(seconds.map(|seconds| Duration::from_secs_f64(seconds.max(0.0))))
+    }
+
+    /// Number of build servers which finished a build during the last hour.
+    pub(crate) fn active_builders(&self) -> Result<i64> {
+        Ok(self
+            .db
+            .get()?
+            .query_one(
+                "SELECT COUNT(DISTINCT build_server)
+                 FROM builds
+                 WHERE build_time > NOW() - INTERVAL '1 hour'",
+                &[],
+            )?
+            .get(0))
+    }
+
     pub(crate) fn failed_count(&self) -> Result<usize> {
         let res = self.db.get()?.query(
             "SELECT COUNT(*) FROM queue WHERE attempt >= $1;",
@@ -191,7 +326,7 @@ impl BuildQueue {
             )
         });
         self.metrics.total_builds.inc();
-        if let Err(err) = cdn::invalidate_crate(&self.config, &self.cdn, &to_process.name) {
+        if let Err(err) = cdn::invalidate_crate(&self.config, &*self.cdn, &to_process.name) {
             report_error(&err);
         }
 
@@ -298,6 +433,25 @@ fn set_yanked(conn: &mut postgres::Client, name: &str, version: &str, yanked: bo
 
 /// Index methods.
 impl BuildQueue {
+    /// Queues a release published to the index with the priority of its crate, unless the crate
+    /// is excluded by the configuration. Returns whether the release was queued.
+    fn add_published_release(
+        &self,
+        conn: &mut postgres::Client,
+        name: &str,
+        version: &str,
+        registry: Option<&str>,
+    ) -> Result<bool> {
+        if let Some(reason) = self.config.crate_excluded_reason(name) {
+            info!("skipping {}-{}: {}", name, version, reason);
+            return Ok(false);
+        }
+
+        let priority = get_crate_priority(conn, name)?;
+        self.add_crate(name, version, priority, registry)?;
+        Ok(true)
+    }
+
     /// Updates registry index repository and adds new crates into build queue.
     ///
     /// Returns the number of crates added
@@ -321,20 +475,18 @@ impl BuildQueue {
                     ),
                     Err(err) => report_error(&err),
                 }
-                if let Err(err) = cdn::invalidate_crate(&self.config, &self.cdn, krate) {
+                if let Err(err) = cdn::invalidate_crate(&self.config, &*self.cdn, krate) {
                     report_error(&err);
                 }
                 continue;
             }
 
             if let Some(release) = change.added() {
-                let priority = get_crate_priority(&mut conn, &release.name)?;
-
                 match self
-                    .add_crate(
+                    .add_published_release(
+                        &mut conn,
                         &release.name,
                         &release.version,
-                        priority,
                         index.repository_url(),
                     )
                     .with_context(|| {
@@ -343,13 +495,14 @@ impl BuildQueue {
                             release.name, release.version
                         )
                     }) {
-                    Ok(()) => {
+                    Ok(true) => {
                         debug!(
                             "{}-{} added into build queue",
                             release.name, release.version
                         );
                         crates_added += 1;
                     }
+                    Ok(false) => {}
                     Err(err) => report_error(&err),
                 }
             }
@@ -366,7 +519,7 @@ impl BuildQueue {
                     yanked.is_some(),
                 );
 
-                if let Err(err) = cdn::invalidate_crate(&self.config, &self.cdn, &release.name) {
+                if let Err(err) = cdn::invalidate_crate(&self.config, &*self.cdn, &release.name) {
                     report_error(&err);
                 }
             }
@@ -494,6 +647,39 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_add_scoped_crate() {
+        crate::test::wrapper(|env| {
+            env.override_config(|config| {
+                config.scoped_registries =
+                    vec!["internal=https://git.example.com/index".parse().unwrap()];
+            });
+            let queue = env.build_queue();
+
+            queue.add_crate("foo", "0.1.0", 0, None)?;
+            queue.add_scoped_crate("internal", "foo", "0.1.0", 0)?;
+            assert!(queue.add_scoped_crate("other", "foo", "0.1.0", 0).is_err());
+
+            let queued: Vec<_> = queue
+                .queued_crates()?
+                .into_iter()
+                .map(|krate| (krate.name, krate.registry))
+                .collect();
+            assert_eq!(
+                queued,
+                [
+                    ("foo".into(), None),
+                    (
+                        "foo@internal".into(),
+                        Some("https://git.example.com/index".into())
+                    ),
+                ]
+            );
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_add_and_process_crates() {
         const MAX_ATTEMPTS: u16 = 3;
@@ -780,6 +966,63 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_crate_allow_and_deny_lists() {
+        crate::test::wrapper(|env| {
+            env.override_config(|config| {
+                config.crate_allow_list = vec!["foo".into(), "Bar".into()];
+                config.crate_deny_list = vec!["bar".into()];
+            });
+            let queue = env.build_queue();
+            let mut conn = env.db().conn();
+
+            assert!(queue.add_published_release(&mut conn, "foo", "1.0.0", None)?);
+            assert!(!queue.add_published_release(&mut conn, "bar", "1.0.0", None)?);
+            assert!(!queue.add_published_release(&mut conn, "baz", "1.0.0", None)?);
+
+            let queued = queue.queued_crates()?;
+            assert_eq!(
+                queued
+                    .iter()
+                    .map(|c| (c.name.as_str(), c.version.as_str()))
+                    .collect::<Vec<_>>(),
+                vec![("foo", "1.0.0")]
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_queued_position() {
+        crate::test::wrapper(|env| {
+            let queue = env.build_queue();
+
+            queue.add_crate("bar", "1.0.0", 0, None)?;
+            queue.add_crate("foo", "1.0.0", 5, None)?;
+            queue.add_crate("foo", "2.0.0", -10, None)?;
+
+            assert_eq!(
+                queue.queued_position("foo", None)?,
+                Some(("2.0.0".into(), 1))
+            );
+            assert_eq!(
+                queue.queued_position("foo", Some("1.0.0"))?,
+                Some(("1.0.0".into(), 3))
+            );
+            assert_eq!(queue.queued_position("foo", Some("3.0.0"))?, None);
+            assert_eq!(queue.queued_position("baz", None)?, None);
+
+            queue.process_next_crate(|_| Ok(()))?;
+            assert_eq!(
+                queue.queued_position("foo", None)?,
+                Some(("1.0.0".into(), 2))
+            );
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn test_last_seen_reference_in_db() {
         crate::test::wrapper(|env| {
@@ -49,17 +49,57 @@ impl CdnBackend {
             CdnKind::Dummy => Self::Dummy(Arc::new(Mutex::new(Vec::new()))),
         }
     }
+
+    async fn cloudfront_invalidation(
+        client: &Client,
+        distribution_id: &str,
+        caller_reference: &str,
+        path_patterns: &[&str],
+    ) -> Result<(), Error> {
+        let path_patterns: Vec<_> = path_patterns.iter().cloned().map(String::from).collect();
+
+        client
+            .create_invalidation()
+            .distribution_id(distribution_id)
+            .invalidation_batch(
+                InvalidationBatch::builder()
+                    .paths(
+                        Paths::builder()
+                            .quantity(path_patterns.len().try_into().unwrap())
+                            .set_items(Some(path_patterns))
+                            .build(),
+                    )
+                    .caller_reference(caller_reference)
+                    .build(),
+            )
+            .send()
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Sends invalidation requests to a CDN, so the backend can be replaced in tests.
+pub(crate) trait PurgeBackend: Send + Sync {
     /// create a Front invalidation request for a list of path patterns.
     /// patterns can be
     /// * `/filename.ext` (a specific path)
     /// * `/directory-path/file-name.*` (delete these files, all extensions)
-    /// * `/directory-path/*` (invalidate all of the files in a directory, without subdirectories)
-    /// * `/directory-path*` (recursive directory delete, including subdirectories)
+    /// * `/directory-path/*` (invalidate all of the files in a directory, including subdirectories)
+    /// * `/directory-path*` (like above, but also every path starting with `directory-path`)
     /// see https://docs.aws.amazon.com/AmazonCloudFront/latest/DeveloperGuide/Invalidation.html#invalidation-specifying-objects
     ///
     /// Returns the caller reference that can be used to query the status of this
     /// invalidation request.
-    pub(crate) fn create_invalidation(
+    fn create_invalidation(
+        &self,
+        distribution_id: &str,
+        path_patterns: &[&str],
+    ) -> Result<Uuid, Error>;
+}
+
+impl PurgeBackend for CdnBackend {
+    fn create_invalidation(
         &self,
         distribution_id: &str,
         path_patterns: &[&str],
@@ -93,37 +133,9 @@ impl CdnBackend {
 
         Ok(caller_reference)
     }
-
-    async fn cloudfront_invalidation(
-        client: &Client,
-        distribution_id: &str,
-        caller_reference: &str,
-        path_patterns: &[&str],
-    ) -> Result<(), Error> {
-        let path_patterns: Vec<_> = path_patterns.iter().cloned().map(String::from).collect();
-
-        client
-            .create_invalidation()
-            .distribution_id(distribution_id)
-            .invalidation_batch(
-                InvalidationBatch::builder()
-                    .paths(
-                        Paths::builder()
-                            .quantity(path_patterns.len().try_into().unwrap())
-                            .set_items(Some(path_patterns))
-                            .build(),
-                    )
-                    .caller_reference(caller_reference)
-                    .build(),
-            )
-            .send()
-            .await?;
-
-        Ok(())
-    }
 }
 
-pub(crate) fn invalidate_crate(config: &Config, cdn: &CdnBackend, name: &str) -> Result<()> {
+pub(crate) fn invalidate_crate(config: &Config, cdn: &dyn PurgeBackend, name: &str) -> Result<()> {
     if let Some(distribution_id) = config.cloudfront_distribution_id_web.as_ref() {
         cdn.create_invalidation(
             distribution_id,
@@ -139,6 +151,46 @@ pub(crate) fn invalidate_crate(config: &Config, cdn: &CdnBackend, name: &str) ->
     Ok(())
 }
 
+/// Invalidates the pages of a single release, including the `latest` pages which might show it.
+/// The pages below a version are invalidated with `/{version}/*`, since `/{version}*` would also
+/// match other versions starting with it, like pre-releases of the same version.
+///
+/// Returns the invalidated path patterns of all configured distributions.
+pub(crate) fn invalidate_release(
+    config: &Config,
+    cdn: &dyn PurgeBackend,
+    name: &str,
+    version: &str,
+) -> Result<Vec<String>> {
+    let mut invalidated = Vec::new();
+    if let Some(distribution_id) = config.cloudfront_distribution_id_web.as_ref() {
+        let paths: Vec<_> = [
+            format!("/{}/{}", name, version),
+            format!("/{}/latest", name),
+            format!("/crate/{}/{}", name, version),
+            format!("/crate/{}/latest", name),
+        ]
+        .into_iter()
+        .flat_map(|path| [format!("{}/*", path), path])
+        .collect();
+        cdn.create_invalidation(
+            distribution_id,
+            &paths.iter().map(String::as_str).collect::<Vec<_>>(),
+        )
+        .context("error creating web CDN invalidation")?;
+        invalidated.extend(paths);
+    }
+    if let Some(distribution_id) = config.cloudfront_distribution_id_static.as_ref() {
+        // the static distribution only serves files, there's no page at the version itself
+        let path = format!("/rustdoc/{}/{}/*", name, version);
+        cdn.create_invalidation(distribution_id, &[&path])
+            .context("error creating static CDN invalidation")?;
+        invalidated.push(path);
+    }
+
+    Ok(invalidated)
+}
+
 #[derive(Debug, Clone, Serialize, PartialEq, Eq)]
 pub(crate) struct CrateInvalidation {
     pub name: String,
@@ -185,6 +237,30 @@ mod tests {
     use aws_smithy_http::body::SdkBody;
     use chrono::{Duration, Timelike};
 
+    /// Records the invalidations it receives, or rejects all of them
+    #[derive(Default)]
+    struct MockPurgeBackend {
+        invalidations: Mutex<Vec<(String, Vec<String>)>>,
+        fail: bool,
+    }
+
+    impl PurgeBackend for MockPurgeBackend {
+        fn create_invalidation(
+            &self,
+            distribution_id: &str,
+            path_patterns: &[&str],
+        ) -> Result<Uuid, Error> {
+            if self.fail {
+                anyhow::bail!("invalidation rejected");
+            }
+            self.invalidations.lock().unwrap().push((
+                distribution_id.to_owned(),
+                path_patterns.iter().map(|p| (*p).to_owned()).collect(),
+            ));
+            Ok(Uuid::new_v4())
+        }
+    }
+
     #[test]
     fn create_cloudfront() {
         wrapper(|env| {
@@ -222,7 +298,7 @@ mod tests {
                 config.cloudfront_distribution_id_web = Some("distribution_id_web".into());
                 config.cloudfront_distribution_id_static = Some("distribution_id_static".into());
             });
-            invalidate_crate(&env.config(), &env.cdn(), "krate")?;
+            invalidate_crate(&env.config(), &*env.cdn(), "krate")?;
 
             assert!(matches!(*env.cdn(), CdnBackend::Dummy(_)));
             if let CdnBackend::Dummy(ref invalidation_requests) = *env.cdn() {
@@ -240,6 +316,70 @@ mod tests {
         });
     }
 
+    #[test]
+    fn invalidate_a_release() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.cloudfront_distribution_id_web = Some("distribution_id_web".into());
+                config.cloudfront_distribution_id_static = Some("distribution_id_static".into());
+            });
+            let cdn = MockPurgeBackend::default();
+
+            let paths = invalidate_release(&env.config(), &cdn, "krate", "1.0.0")?;
+
+            let invalidations = cdn.invalidations.lock().unwrap();
+            assert_eq!(
+                *invalidations,
+                [
+                    (
+                        "distribution_id_web".into(),
+                        vec![
+                            "/krate/1.0.0/*".to_owned(),
+                            "/krate/1.0.0".into(),
+                            "/krate/latest/*".into(),
+                            "/krate/latest".into(),
+                            "/crate/krate/1.0.0/*".into(),
+                            "/crate/krate/1.0.0".into(),
+                            "/crate/krate/latest/*".into(),
+                            "/crate/krate/latest".into(),
+                        ]
+                    ),
+                    (
+                        "distribution_id_static".into(),
+                        vec!["/rustdoc/krate/1.0.0/*".to_owned()]
+                    ),
+                ]
+            );
+            assert_eq!(
+                paths,
+                invalidations
+                    .iter()
+                    .flat_map(|(_, paths)| paths.clone())
+                    .collect::<Vec<_>>()
+            );
+            // other versions starting with the purged one stay cached
+            assert!(!paths.iter().any(|path| path.ends_with("1.0.0*")));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn invalidate_a_release_fails() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.cloudfront_distribution_id_web = Some("distribution_id_web".into());
+            });
+            let cdn = MockPurgeBackend {
+                fail: true,
+                ..Default::default()
+            };
+
+            assert!(invalidate_release(&env.config(), &cdn, "krate", "1.0.0").is_err());
+            assert!(invalidate_crate(&env.config(), &cdn, "krate").is_err());
+            Ok(())
+        })
+    }
+
     async fn get_mock_config() -> aws_sdk_cloudfront::Config {
         let cfg = aws_config::from_env()
             .region(Region::new("eu-central-1"))
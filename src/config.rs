@@ -1,5 +1,6 @@
-use crate::{cdn::CdnKind, storage::StorageKind};
+use crate::{cdn::CdnKind, storage::StorageKind, utils::sanitize_snippet};
 use anyhow::{anyhow, bail, Context, Result};
+use ipnet::IpNet;
 use std::env::VarError;
 use std::error::Error;
 use std::path::PathBuf;
@@ -20,7 +21,18 @@ pub struct Config {
     // Storage params
     pub(crate) storage_backend: StorageKind,
 
-    // AWS SDK configuration
+    // Read-only storage reads fall back to when the primary storage fails, like a replica of
+    // the bucket in another region. Paths missing in the primary storage aren't looked up there.
+    pub(crate) storage_fallback_backend: Option<StorageKind>,
+    pub(crate) s3_fallback_bucket: Option<String>,
+    pub(crate) s3_fallback_region: Option<String>,
+
+    // Retries of storage reads failing with transient errors, and the milliseconds before the
+    // first retry. The backoff doubles with every further retry, up to a second.
+    pub(crate) storage_read_retries: u32,
+    pub(crate) storage_read_retry_backoff: u64,
+
+    // AWS SDK configuration, storage reads aren't retried by the SDK
     pub(crate) aws_sdk_max_retries: u32,
 
     // S3 params
@@ -65,6 +77,80 @@ pub struct Config {
     // Content Security Policy
     pub(crate) csp_report_only: bool,
 
+    // Scheme and host the site is publicly served from, used for absolute links
+    pub(crate) public_base_url: String,
+    // Use the `X-Forwarded-Host` and `X-Forwarded-Proto` headers for absolute links.
+    // Only enable this when the server runs behind a proxy that sets them.
+    pub(crate) trust_forwarded_headers: bool,
+    // Networks of the proxies in front of the server, whose `X-Forwarded-For` entries are
+    // trusted when determining the client IP.
+    pub(crate) trusted_proxies: Vec<IpNet>,
+
+    // Tokens allowed to trigger rebuilds through the web server, as `name:token` pairs.
+    // The name is recorded with each rebuild, without tokens rebuilds are disabled.
+    // How many rebuilds each token can queue per hour.
+    pub(crate) rebuild_tokens: Vec<NamedToken>,
+    pub(crate) rebuilds_per_hour: u32,
+    // Tokens allowed to create short links, in the same format as the rebuild tokens.
+    pub(crate) short_link_tokens: Vec<NamedToken>,
+    // Tokens allowed to review the reports of broken docs, in the same format as the rebuild
+    // tokens. How many reports a single client IP can send per hour.
+    pub(crate) report_tokens: Vec<NamedToken>,
+    pub(crate) reports_per_hour: u32,
+    // Tokens allowed to upload documentation previews, in the same format as the rebuild tokens.
+    // Seconds until previews expire, and the largest uploaded zip file in bytes.
+    pub(crate) preview_tokens: Vec<NamedToken>,
+    pub(crate) preview_ttl: u64,
+    pub(crate) max_preview_size: usize,
+    // Tokens allowed to purge the CDN caches of a release, in the same format as the rebuild
+    // tokens.
+    pub(crate) cdn_purge_tokens: Vec<NamedToken>,
+
+    // Origins allowed to call the JSON API from browsers, like `https://example.com`.
+    // Without any, only the public endpoints allowing every origin can be called.
+    pub(crate) cors_allowed_origins: Vec<String>,
+
+    // Send `Server-Timing` headers with the time spent on the database, storage and rendering
+    // for documentation and crate pages.
+    pub(crate) server_timing: bool,
+
+    // Export tracing spans to the OpenTelemetry collector at this endpoint, like
+    // `http://localhost:4317`.
+    pub otlp_endpoint: Option<String>,
+
+    // Send `Link` headers preloading the static files of documentation pages. Not every proxy
+    // passes these on correctly.
+    pub(crate) early_hints: bool,
+
+    // Keep search engines from indexing the documentation of pre-releases and yanked releases,
+    // and leave them out of the sitemaps.
+    pub(crate) noindex_unstable_versions: bool,
+
+    // Redirect `/crate/:name` to the documentation of the latest release instead of its details
+    // page, when it was built successfully.
+    pub(crate) crate_details_redirect_to_docs: bool,
+
+    // Serve the `index.html` of directories in the static files, for self-hosted instances
+    // shipping additional pages.
+    pub(crate) static_directory_index: bool,
+
+    // HTML snippets shown above and below the documentation on rustdoc pages, for the branding
+    // of self-hosted instances. Scripts and event handlers are removed from them.
+    pub(crate) rustdoc_header_html: Option<String>,
+    pub(crate) rustdoc_footer_html: Option<String>,
+
+    // Serve the registered routes at `/-/routes.json` to debug routing, never enable this in
+    // production.
+    pub(crate) expose_route_table: bool,
+
+    // Seconds in-flight requests get to complete after the web server received `SIGTERM`.
+    pub(crate) shutdown_grace_period: u64,
+
+    // Limits for requests to endpoints changing data, like `POST` or `DELETE`: the largest
+    // request body in bytes, and the seconds a handler may take.
+    pub(crate) max_request_body_size: usize,
+    pub(crate) write_request_timeout: u64,
+
     // Cache-Control header, for versioned URLs.
     // If both are absent, don't generate the header. If only one is present,
     // generate just that directive. Values are in seconds.
@@ -80,6 +166,8 @@ pub struct Config {
 
     // Build params
     pub(crate) build_attempts: u16,
+    // How often the delivery of a build notification webhook is attempted before giving up
+    pub(crate) webhook_max_attempts: u16,
     pub(crate) rustwide_workspace: PathBuf,
     pub(crate) inside_docker: bool,
     pub(crate) docker_image: Option<String>,
@@ -87,6 +175,16 @@ pub struct Config {
     pub(crate) build_cpu_limit: Option<u32>,
     pub(crate) include_default_targets: bool,
     pub(crate) disable_memory_limit: bool,
+    // Build and serve an mdBook from the `book/` directory of a crate, next to the API docs
+    pub(crate) enable_books: bool,
+    // Names of the crates whose releases are queued from the index, for self-hosted instances
+    // mirroring a subset of the crates. Without an allow list every crate not on the deny list
+    // is documented.
+    pub(crate) crate_allow_list: Vec<String>,
+    pub(crate) crate_deny_list: Vec<String>,
+    // Alternate registries whose crates are served under `/registry/<name>/...` next to the
+    // crates of the default registry, as `name=index-url` pairs.
+    pub(crate) scoped_registries: Vec<ScopedRegistry>,
 }
 
 impl Config {
@@ -115,6 +213,7 @@ impl Config {
 
         Ok(Self {
             build_attempts: env("DOCSRS_BUILD_ATTEMPTS", 5)?,
+            webhook_max_attempts: env("DOCSRS_WEBHOOK_MAX_ATTEMPTS", 8)?,
 
             registry_index_path: env("REGISTRY_INDEX_PATH", prefix.join("crates.io-index"))?,
             registry_url: maybe_env("REGISTRY_URL")?,
@@ -125,7 +224,12 @@ impl Config {
             min_pool_idle: env("DOCSRS_MIN_POOL_IDLE", 10)?,
 
             storage_backend: env("DOCSRS_STORAGE_BACKEND", StorageKind::Database)?,
+            storage_fallback_backend: maybe_env("DOCSRS_STORAGE_FALLBACK_BACKEND")?,
+            s3_fallback_bucket: maybe_env("DOCSRS_S3_FALLBACK_BUCKET")?,
+            s3_fallback_region: maybe_env("DOCSRS_S3_FALLBACK_REGION")?,
 
+            storage_read_retries: env("DOCSRS_STORAGE_READ_RETRIES", 2)?,
+            storage_read_retry_backoff: env("DOCSRS_STORAGE_READ_RETRY_BACKOFF", 100)?,
             aws_sdk_max_retries: env("DOCSRS_AWS_SDK_MAX_RETRIES", 6)?,
 
             s3_bucket: env("DOCSRS_S3_BUCKET", "rust-docs-rs".to_string())?,
@@ -158,6 +262,34 @@ impl Config {
 
             csp_report_only: env("DOCSRS_CSP_REPORT_ONLY", false)?,
 
+            public_base_url: env("DOCSRS_PUBLIC_BASE_URL", "https://docs.rs".to_string())?
+                .trim_end_matches('/')
+                .to_string(),
+            trust_forwarded_headers: env("DOCSRS_TRUST_FORWARDED_HEADERS", false)?,
+            trusted_proxies: env_list("DOCSRS_TRUSTED_PROXIES")?,
+            rebuild_tokens: env_list("DOCSRS_REBUILD_TOKENS")?,
+            rebuilds_per_hour: env("DOCSRS_REBUILDS_PER_HOUR", 100)?,
+            short_link_tokens: env_list("DOCSRS_SHORT_LINK_TOKENS")?,
+            report_tokens: env_list("DOCSRS_REPORT_TOKENS")?,
+            reports_per_hour: env("DOCSRS_REPORTS_PER_HOUR", 5)?,
+            preview_tokens: env_list("DOCSRS_PREVIEW_TOKENS")?,
+            preview_ttl: env("DOCSRS_PREVIEW_TTL", 7 * 24 * 60 * 60)?,
+            max_preview_size: env("DOCSRS_MAX_PREVIEW_SIZE", 100 * 1024 * 1024)?,
+            cdn_purge_tokens: env_list("DOCSRS_CDN_PURGE_TOKENS")?,
+            cors_allowed_origins: env_list("DOCSRS_CORS_ALLOWED_ORIGINS")?,
+            server_timing: env("DOCSRS_SERVER_TIMING", false)?,
+            otlp_endpoint: maybe_env("DOCSRS_OTLP_ENDPOINT")?,
+            early_hints: env("DOCSRS_EARLY_HINTS", false)?,
+            noindex_unstable_versions: env("DOCSRS_NOINDEX_UNSTABLE_VERSIONS", true)?,
+            crate_details_redirect_to_docs: env("DOCSRS_CRATE_DETAILS_REDIRECT_TO_DOCS", false)?,
+            static_directory_index: env("DOCSRS_STATIC_DIRECTORY_INDEX", false)?,
+            rustdoc_header_html: maybe_snippet_env("DOCSRS_RUSTDOC_HEADER_HTML")?,
+            rustdoc_footer_html: maybe_snippet_env("DOCSRS_RUSTDOC_FOOTER_HTML")?,
+            expose_route_table: env("DOCSRS_EXPOSE_ROUTE_TABLE", false)?,
+            shutdown_grace_period: env("DOCSRS_SHUTDOWN_GRACE_PERIOD", 30)?,
+            max_request_body_size: env("DOCSRS_MAX_REQUEST_BODY_SIZE", 64 * 1024)?,
+            write_request_timeout: env("DOCSRS_WRITE_REQUEST_TIMEOUT", 30)?,
+
             cache_control_stale_while_revalidate: maybe_env(
                 "CACHE_CONTROL_STALE_WHILE_REVALIDATE",
             )?,
@@ -180,8 +312,90 @@ impl Config {
             build_cpu_limit: maybe_env("DOCSRS_BUILD_CPU_LIMIT")?,
             include_default_targets: env("DOCSRS_INCLUDE_DEFAULT_TARGETS", true)?,
             disable_memory_limit: env("DOCSRS_DISABLE_MEMORY_LIMIT", false)?,
+            enable_books: env("DOCSRS_ENABLE_BOOKS", false)?,
+            crate_allow_list: env_list("DOCSRS_CRATE_ALLOW_LIST")?,
+            crate_deny_list: env_list("DOCSRS_CRATE_DENY_LIST")?,
+            scoped_registries: env_list("DOCSRS_SCOPED_REGISTRIES")?,
         })
     }
+
+    /// Returns why releases of the crate aren't queued from the index, or `None` when they are.
+    /// Names are compared case-insensitively, like crates.io does.
+    pub(crate) fn crate_excluded_reason(&self, name: &str) -> Option<&'static str> {
+        let listed = |list: &[String]| list.iter().any(|entry| entry.eq_ignore_ascii_case(name));
+
+        if listed(&self.crate_deny_list) {
+            Some("the crate is on the deny list")
+        } else if !self.crate_allow_list.is_empty() && !listed(&self.crate_allow_list) {
+            Some("the crate is not on the allow list")
+        } else {
+            None
+        }
+    }
+
+    /// Returns the index URL of the scoped registry `name`, `None` when it isn't configured.
+    pub(crate) fn scoped_registry_url(&self, name: &str) -> Option<&str> {
+        self.scoped_registries
+            .iter()
+            .find(|registry| registry.name == name)
+            .map(|registry| registry.index_url.as_str())
+    }
+}
+
+/// A bearer token for internal endpoints, named so actions can be attributed to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct NamedToken {
+    pub(crate) name: String,
+    pub(crate) token: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("tokens have to be given as `name:token`")]
+pub(crate) struct InvalidNamedToken;
+
+impl FromStr for NamedToken {
+    type Err = InvalidNamedToken;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((name, token)) if !name.is_empty() && !token.is_empty() => Ok(NamedToken {
+                name: name.to_string(),
+                token: token.to_string(),
+            }),
+            _ => Err(InvalidNamedToken),
+        }
+    }
+}
+
+/// An alternate registry whose crates are served under `/registry/<name>/...`, see
+/// [`crate::utils::scoped_registry`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ScopedRegistry {
+    pub(crate) name: String,
+    pub(crate) index_url: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("scoped registries have to be given as `name=index-url`, with names made of ASCII letters, digits, `-` and `_`")]
+pub(crate) struct InvalidScopedRegistry;
+
+impl FromStr for ScopedRegistry {
+    type Err = InvalidScopedRegistry;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((name, index_url))
+                if crate::utils::scoped_registry::is_valid_registry_name(name)
+                    && !index_url.is_empty() =>
+            {
+                Ok(ScopedRegistry {
+                    name: name.to_string(),
+                    index_url: index_url.to_string(),
+                })
+            }
+            _ => Err(InvalidScopedRegistry),
+        }
+    }
 }
 
 fn env<T>(var: &str, default: T) -> Result<T>
@@ -192,6 +406,32 @@ where
     Ok(maybe_env(var)?.unwrap_or(default))
 }
 
+/// Parses a comma separated list, an unset variable is an empty list.
+fn env_list<T>(var: &str) -> Result<Vec<T>>
+where
+    T: FromStr,
+    T::Err: Error + Send + Sync + 'static,
+{
+    maybe_env::<String>(var)?
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            item.parse::<T>()
+                .with_context(|| format!("failed to parse configuration variable {}", var))
+        })
+        .collect()
+}
+
+/// An HTML snippet shown on pages, sanitized with [`sanitize_snippet`]. Empty snippets are
+/// treated like unset ones.
+fn maybe_snippet_env(var: &str) -> Result<Option<String>> {
+    Ok(maybe_env::<String>(var)?
+        .filter(|html| !html.trim().is_empty())
+        .map(|html| sanitize_snippet(&html)))
+}
+
 fn require_env<T>(var: &str) -> Result<T>
 where
     T: FromStr,
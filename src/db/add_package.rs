@@ -1,11 +1,11 @@
 use crate::{
     db::types::Feature,
-    docbuilder::{BuildResult, DocCoverage},
+    docbuilder::{BuildResult, DocCoverage, DocSizes},
     error::Result,
     index::api::{CrateData, CrateOwner, ReleaseData},
     storage::CompressionAlgorithm,
     utils::MetadataPackage,
-    web::crate_details::CrateDetails,
+    web::{changelog::find_changelog, crate_details::CrateDetails},
 };
 use anyhow::{anyhow, Context};
 use postgres::Client;
@@ -58,12 +58,14 @@ pub(crate) fn add_package_into_database(
             keywords, have_examples, downloads, files,
             doc_targets, is_library, doc_rustc_version,
             documentation_url, default_target, features,
-            repository_id, archive_storage
+            repository_id, archive_storage, rust_version,
+            has_changelog, license_file
          )
          VALUES (
             $1,  $2,  $3,  $4,  $5,  $6,  $7,  $8,  $9,
             $10, $11, $12, $13, $14, $15, $16, $17, $18,
-            $19, $20, $21, $22, $23, $24, $25, $26, $27 
+            $19, $20, $21, $22, $23, $24, $25, $26, $27,
+            $28, $29, $30
          )
          ON CONFLICT (crate_id, version) DO UPDATE
             SET release_time = $3,
@@ -90,7 +92,10 @@ pub(crate) fn add_package_into_database(
                 default_target = $24,
                 features = $25,
                 repository_id = $26,
-                archive_storage = $27
+                archive_storage = $27,
+                rust_version = $28,
+                has_changelog = $29,
+                license_file = $30
          RETURNING id",
         &[
             &crate_id,
@@ -120,12 +125,16 @@ pub(crate) fn add_package_into_database(
             &features,
             &repository_id,
             &archive_storage,
+            &metadata_pkg.rust_version,
+            &find_changelog(&source_files).is_some(),
+            &metadata_pkg.license_file,
         ],
     )?;
 
     let release_id: i32 = rows[0].get(0);
 
     add_keywords_into_database(conn, metadata_pkg, release_id)?;
+    add_categories_into_database(conn, metadata_pkg, release_id)?;
     add_compression_into_database(conn, compression_algorithms.into_iter(), release_id)?;
 
     let crate_details = CrateDetails::new(
@@ -178,6 +187,69 @@ pub(crate) fn add_doc_coverage(
     Ok(rows[0].get(0))
 }
 
+pub(crate) fn add_doc_sizes_into_database(
+    conn: &mut Client,
+    release_id: i32,
+    doc_sizes: DocSizes,
+) -> Result<()> {
+    debug!("Adding doc sizes into database");
+    conn.execute(
+        "INSERT INTO doc_sizes (release_id, documentation, sources, search_index)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (release_id) DO UPDATE
+                SET
+                    documentation = $2,
+                    sources = $3,
+                    search_index = $4",
+        &[
+            &release_id,
+            &doc_sizes.documentation,
+            &doc_sizes.sources,
+            &doc_sizes.search_index,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Marks a release as having an mdBook stored next to its documentation
+pub(crate) fn add_book_into_database(conn: &mut Client, release_id: i32) -> Result<()> {
+    debug!("Marking release as having a book");
+    conn.execute(
+        "UPDATE releases SET has_book = TRUE WHERE id = $1",
+        &[&release_id],
+    )?;
+    Ok(())
+}
+
+/// Records whether the docs of a release were built using nightly-only rustdoc features.
+pub(crate) fn add_nightly_features_into_database(
+    conn: &mut Client,
+    release_id: i32,
+    nightly_features: bool,
+) -> Result<()> {
+    debug!("Adding nightly features into database");
+    conn.execute(
+        "UPDATE releases SET nightly_features = $2 WHERE id = $1",
+        &[&release_id, &nightly_features],
+    )?;
+    Ok(())
+}
+
+/// Records the static files the documentation pages of a release load right away, which are sent
+/// as preload links with them.
+pub(crate) fn add_preload_assets_into_database(
+    conn: &mut Client,
+    release_id: i32,
+    preload_assets: &[String],
+) -> Result<()> {
+    debug!("Adding preload assets into database");
+    conn.execute(
+        "UPDATE releases SET preload_assets = $2 WHERE id = $1",
+        &[&release_id, &preload_assets],
+    )?;
+    Ok(())
+}
+
 /// Adds a build into database
 pub(crate) fn add_build_into_database(
     conn: &mut Client,
@@ -186,8 +258,11 @@ pub(crate) fn add_build_into_database(
 ) -> Result<i32> {
     debug!("Adding build into database");
     let rows = conn.query(
-        "INSERT INTO builds (rid, rustc_version, docsrs_version, build_status, build_server)
-        VALUES ($1, $2, $3, $4, $5)
+        "INSERT INTO builds (
+            rid, rustc_version, docsrs_version, build_status, build_server, command,
+            failure_category, cargo_version, target, cfgs
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
         RETURNING id",
         &[
             &release_id,
@@ -195,6 +270,11 @@ pub(crate) fn add_build_into_database(
             &res.docsrs_version,
             &res.successful,
             &hostname::get()?.to_str().unwrap_or(""),
+            &res.command,
+            &res.failure.map(|failure| failure.to_string()),
+            &res.cargo_version,
+            &res.target,
+            &res.cfgs,
         ],
     )?;
     Ok(rows[0].get(0))
@@ -296,6 +376,45 @@ fn get_rustdoc(pkg: &MetadataPackage, source_dir: &Path) -> Result<Option<String
     }
 }
 
+/// Unstable rustdoc features crates enable in their library root, usually behind
+/// `#![cfg_attr(docsrs, feature(...))]`
+const UNSTABLE_DOC_FEATURES: &[&str] = &[
+    "doc_auto_cfg",
+    "doc_cfg",
+    "doc_cfg_hide",
+    "doc_masked",
+    "doc_notable_trait",
+];
+
+/// Checks whether the library root enables one of the [`UNSTABLE_DOC_FEATURES`].
+pub(crate) fn uses_unstable_doc_features(pkg: &MetadataPackage, source_dir: &Path) -> Result<bool> {
+    let src_path = match pkg
+        .targets
+        .get(0)
+        .and_then(|target| target.src_path.as_ref())
+    {
+        Some(src_path) => source_dir.join(src_path),
+        None => return Ok(false),
+    };
+
+    for line in BufReader::new(fs::File::open(src_path)?).lines() {
+        let line = line?;
+        let line = line.trim_start();
+        if !line.starts_with("#![") {
+            continue;
+        }
+        if let Some((_, features)) = line.split_once("feature(") {
+            if features
+                .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .any(|feature| UNSTABLE_DOC_FEATURES.contains(&feature))
+            {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
 /// Reads rustdoc from library
 fn read_rust_doc(file_path: &Path) -> Result<Option<String>> {
     let reader = fs::File::open(file_path).map(BufReader::new)?;
@@ -369,6 +488,37 @@ fn add_keywords_into_database(
     Ok(())
 }
 
+/// Adds the crates.io categories of the release into the database, replacing the ones of a
+/// previous build of the same release.
+fn add_categories_into_database(
+    conn: &mut Client,
+    pkg: &MetadataPackage,
+    release_id: i32,
+) -> Result<()> {
+    conn.execute("DELETE FROM category_rels WHERE rid = $1", &[&release_id])?;
+    if pkg.categories.is_empty() {
+        return Ok(());
+    }
+
+    conn.execute(
+        "INSERT INTO categories (slug)
+        SELECT * FROM UNNEST($1::TEXT[])
+        ON CONFLICT DO NOTHING;",
+        &[&pkg.categories],
+    )?;
+
+    conn.execute(
+        "INSERT INTO category_rels (rid, cid)
+        SELECT $1 as rid, id as cid
+        FROM categories
+        WHERE slug = ANY($2)
+        ON CONFLICT DO NOTHING;",
+        &[&release_id, &pkg.categories],
+    )?;
+
+    Ok(())
+}
+
 pub fn update_crate_data_in_database(
     conn: &mut Client,
     name: &str,
@@ -457,6 +607,46 @@ mod test {
     use crate::test::*;
     use crate::utils::MetadataPackage;
 
+    #[test]
+    fn unstable_doc_features() -> Result<()> {
+        let source_dir = tempfile::tempdir()?;
+        let pkg = MetadataPackage {
+            targets: vec![crate::utils::Target::dummy_lib(
+                "dummy".into(),
+                Some("src/lib.rs".into()),
+            )],
+            ..Default::default()
+        };
+        fs::create_dir(source_dir.path().join("src"))?;
+
+        for (lib_rs, expected) in [
+            ("//! docs\n#![cfg_attr(docsrs, feature(doc_cfg))]\n", true),
+            (
+                "#![cfg_attr(docsrs, feature(never_type, doc_auto_cfg))]",
+                true,
+            ),
+            ("  #![feature(doc_notable_trait)]", true),
+            ("#![cfg_attr(docsrs, feature(never_type))]", false),
+            (
+                "//! uses feature(doc_cfg) on docs.rs\n#![deny(missing_docs)]",
+                false,
+            ),
+            ("#![cfg_attr(docsrs, feature(doc_cfg_extra))]", false),
+        ] {
+            fs::write(source_dir.path().join("src/lib.rs"), lib_rs)?;
+            assert_eq!(
+                uses_unstable_doc_features(&pkg, source_dir.path())?,
+                expected,
+                "{}",
+                lib_rs
+            );
+        }
+
+        let pkg = MetadataPackage::default();
+        assert!(!uses_unstable_doc_features(&pkg, source_dir.path())?);
+        Ok(())
+    }
+
     #[test]
     fn new_keywords() {
         wrapper(|env| {
@@ -570,6 +760,43 @@ mod test {
         })
     }
 
+    #[test]
+    fn updated_categories() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("dummy")
+                .version("0.13.0")
+                .categories(vec!["parsing".into(), "encoding".into()])
+                .create()?;
+
+            // rebuilding the same release replaces its categories
+            let release_id = env
+                .fake_release()
+                .name("dummy")
+                .version("0.13.0")
+                .categories(vec!["parsing".into(), "no-std".into()])
+                .create()?;
+
+            let mut conn = env.db().conn();
+            let categories = conn
+                .query(
+                    "SELECT categories.slug
+                    FROM categories
+                    INNER JOIN category_rels ON categories.id = category_rels.cid
+                    WHERE category_rels.rid = $1
+                    ORDER BY categories.slug",
+                    &[&release_id],
+                )?
+                .into_iter()
+                .map(|row| row.get::<_, String>(0))
+                .collect::<Vec<_>>();
+
+            assert_eq!(categories, vec!["no-std", "parsing"]);
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn new_owners() {
         wrapper(|env| {
@@ -0,0 +1,132 @@
+use crate::{
+    error::Result,
+    storage::{rustdoc_archive_path, source_archive_path, CompressionAlgorithm, PathNotFoundError},
+    Storage,
+};
+use postgres::Client;
+use tracing::{info, warn};
+
+/// Recompress the rustdoc and source archives in storage whose HTML pages are not
+/// zstd-compressed yet.
+///
+/// The releases are processed in batches ordered by their id, starting after `start_after`.
+/// Every archive is re-stored on its own, so this can run in the background while the site is
+/// live, and can be resumed from the last logged release id.
+///
+/// Returns the number of recompressed archives.
+pub fn recompress_archives(
+    conn: &mut Client,
+    storage: &Storage,
+    start_after: Option<i32>,
+    batch_size: i64,
+) -> Result<usize> {
+    let mut last_id = start_after.unwrap_or(0);
+    let mut recompressed = 0;
+
+    loop {
+        let rows = conn.query(
+            "SELECT releases.id, crates.name, releases.version, releases.rustdoc_status
+             FROM releases
+             INNER JOIN crates ON crates.id = releases.crate_id
+             WHERE releases.id > $1 AND releases.archive_storage
+             ORDER BY releases.id
+             LIMIT $2",
+            &[&last_id, &batch_size],
+        )?;
+        if rows.is_empty() {
+            break;
+        }
+
+        for row in &rows {
+            let release_id: i32 = row.get("id");
+            let name: String = row.get("name");
+            let version: String = row.get("version");
+
+            let mut archives = vec![source_archive_path(&name, &version)];
+            if row.get("rustdoc_status") {
+                archives.push(rustdoc_archive_path(&name, &version));
+            }
+            for archive in archives {
+                match storage.recompress_archive(&archive) {
+                    Ok(true) => {
+                        conn.execute(
+                            "INSERT INTO compression_rels (release, algorithm)
+                             VALUES ($1, $2)
+                             ON CONFLICT DO NOTHING",
+                            &[&release_id, &(CompressionAlgorithm::Zstd as i32)],
+                        )?;
+                        recompressed += 1;
+                    }
+                    Ok(false) => {}
+                    Err(err) if err.downcast_ref::<PathNotFoundError>().is_some() => {}
+                    Err(err) => warn!("failed to recompress {}: {:?}", archive, err),
+                }
+            }
+            last_id = release_id;
+        }
+
+        info!(
+            "recompressed {} archives so far, last processed release id: {}",
+            recompressed, last_id
+        );
+    }
+
+    Ok(recompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::wrapper;
+    use std::io::Write;
+
+    #[test]
+    fn recompress_legacy_archives() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .archive_storage(true)
+                .create()?;
+            let storage = env.storage();
+            let archive = rustdoc_archive_path("foo", "0.1.0");
+            let sources = source_archive_path("foo", "0.1.0");
+
+            // legacy archives compressed every file with bzip2
+            for path in [&archive, &sources] {
+                let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+                zip.start_file(
+                    "foo/index.html",
+                    zip::write::FileOptions::default()
+                        .compression_method(zip::CompressionMethod::Bzip2),
+                )?;
+                zip.write_all(b"<html>docs</html>")?;
+                storage.store_archive(path, zip.finish()?.into_inner())?;
+            }
+
+            let mut conn = env.db().conn();
+            conn.execute("DELETE FROM compression_rels", &[])?;
+
+            assert_eq!(recompress_archives(&mut *conn, &storage, None, 10)?, 2);
+            for path in [&archive, &sources] {
+                assert_eq!(
+                    storage
+                        .get_from_archive(path, "foo/index.html", std::usize::MAX, None)?
+                        .content,
+                    b"<html>docs</html>"
+                );
+            }
+            let algorithms: Vec<i32> = conn
+                .query("SELECT algorithm FROM compression_rels", &[])?
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+            assert_eq!(algorithms, [CompressionAlgorithm::Zstd as i32]);
+
+            // running it a second time doesn't touch the recompressed archive
+            assert_eq!(recompress_archives(&mut *conn, &storage, None, 10)?, 0);
+
+            Ok(())
+        })
+    }
+}
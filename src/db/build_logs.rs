@@ -0,0 +1,105 @@
+use crate::{error::Result, storage::PathNotFoundError, Storage};
+use postgres::Client;
+use tracing::{info, warn};
+
+/// Recompress the build logs in storage that are not stored gzip-compressed yet.
+///
+/// The builds are processed in batches ordered by their id, starting after `start_after`.
+/// Since every build log is re-stored on its own, this can safely run in the background
+/// while the site is live, and can be resumed from the last logged build id.
+///
+/// Returns the number of recompressed logs.
+pub fn recompress_build_logs(
+    conn: &mut Client,
+    storage: &Storage,
+    start_after: Option<i32>,
+    batch_size: i64,
+) -> Result<usize> {
+    let mut last_id = start_after.unwrap_or(0);
+    let mut recompressed = 0;
+
+    loop {
+        let rows = conn.query(
+            "SELECT builds.id, releases.default_target
+             FROM builds
+             INNER JOIN releases ON releases.id = builds.rid
+             WHERE builds.id > $1 AND builds.output IS NULL
+             ORDER BY builds.id
+             LIMIT $2",
+            &[&last_id, &batch_size],
+        )?;
+        if rows.is_empty() {
+            break;
+        }
+
+        for row in &rows {
+            let build_id: i32 = row.get("id");
+            let target: String = row.get("default_target");
+
+            match storage.recompress_build_log(build_id, &target) {
+                Ok(true) => recompressed += 1,
+                Ok(false) => {}
+                Err(err) if err.downcast_ref::<PathNotFoundError>().is_some() => {}
+                Err(err) => warn!(
+                    "failed to recompress build log for build {}: {:?}",
+                    build_id, err
+                ),
+            }
+            last_id = build_id;
+        }
+
+        info!(
+            "recompressed {} build logs so far, last processed build id: {}",
+            recompressed, last_id
+        );
+    }
+
+    Ok(recompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::CompressionAlgorithm;
+    use crate::test::{wrapper, FakeBuild};
+
+    #[test]
+    fn recompress_legacy_build_logs() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .builds(vec![FakeBuild::default().no_s3_build_log()])
+                .create()?;
+
+            let build_id: i32 = env
+                .db()
+                .conn()
+                .query_one("SELECT id FROM builds", &[])?
+                .get(0);
+            let storage = env.storage();
+            let target = "x86_64-unknown-linux-gnu";
+
+            // legacy logs were stored with the default compression
+            storage.store_one(
+                crate::storage::build_log_path(build_id, target),
+                "A build log",
+            )?;
+            assert_ne!(CompressionAlgorithm::default(), CompressionAlgorithm::Gzip);
+
+            assert_eq!(
+                recompress_build_logs(&mut *env.db().conn(), &storage, None, 10)?,
+                1
+            );
+            assert_eq!(storage.get_build_log(build_id, target)?, "A build log");
+
+            // running it a second time doesn't touch the already compressed log
+            assert_eq!(
+                recompress_build_logs(&mut *env.db().conn(), &storage, None, 10)?,
+                0
+            );
+
+            Ok(())
+        })
+    }
+}
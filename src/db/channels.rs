@@ -0,0 +1,241 @@
+//! Release channels like `stable` or `lts`, aliases for a version of a crate configured by the
+//! operators of self-hosted instances. URLs with a channel instead of a version redirect to the
+//! version the channel points to. Changing a channel invalidates the cached pages of its crate.
+
+use crate::{
+    cdn::{self, CdnBackend},
+    error::Result,
+    Config,
+};
+use anyhow::Context as _;
+use postgres::Client;
+use semver::{Version, VersionReq};
+
+/// Version names with a special meaning which can't be used for channels
+const RESERVED_NAMES: &[&str] = &["latest", "latest-prerelease", "newest"];
+
+#[derive(Debug, thiserror::Error)]
+enum ChannelError {
+    #[error("{0} can't be used as channel, it would be read as a version or version requirement")]
+    InvalidChannel(String),
+
+    #[error("{0} is not a valid version")]
+    InvalidVersion(String),
+
+    #[error("crate {0} has no channel {1}")]
+    ChannelNotFound(String, String),
+}
+
+/// Returns the version `channel` of crate `name` points to, if the channel exists.
+pub(crate) fn channel_version(
+    conn: &mut Client,
+    name: &str,
+    channel: &str,
+) -> Result<Option<String>> {
+    Ok(conn
+        .query_opt(
+            "SELECT version FROM version_channels WHERE crate_name = $1 AND channel = $2;",
+            &[&name, &channel],
+        )?
+        .map(|row| row.get(0)))
+}
+
+/// Returns all channels as crate name, channel and version, sorted by crate name and channel.
+pub fn list_channels(conn: &mut Client) -> Result<Vec<(String, String, String)>> {
+    let rows = conn.query(
+        "SELECT crate_name, channel, version
+         FROM version_channels
+         ORDER BY crate_name, channel;",
+        &[],
+    )?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.get(0), row.get(1), row.get(2)))
+        .collect())
+}
+
+/// Points a channel of a crate to a version, replacing an existing channel with the same name.
+pub fn set_channel(
+    conn: &mut Client,
+    config: &Config,
+    cdn: &CdnBackend,
+    name: &str,
+    channel: &str,
+    version: &str,
+) -> Result<()> {
+    if channel.is_empty()
+        || channel.contains('/')
+        || RESERVED_NAMES.contains(&channel)
+        || VersionReq::parse(channel).is_ok()
+    {
+        return Err(ChannelError::InvalidChannel(channel.into()).into());
+    }
+    if Version::parse(version).is_err() {
+        return Err(ChannelError::InvalidVersion(version.into()).into());
+    }
+
+    conn.execute(
+        "INSERT INTO version_channels (crate_name, channel, version)
+         VALUES ($1, $2, $3)
+         ON CONFLICT (crate_name, channel) DO UPDATE SET version = EXCLUDED.version;",
+        &[&name, &channel, &version],
+    )?;
+
+    // the channel might have pointed to another version before
+    cdn::invalidate_crate(config, cdn, name).context("error invalidating the crate")
+}
+
+/// Removes a channel of a crate.
+pub fn remove_channel(
+    conn: &mut Client,
+    config: &Config,
+    cdn: &CdnBackend,
+    name: &str,
+    channel: &str,
+) -> Result<()> {
+    let removed = conn.execute(
+        "DELETE FROM version_channels WHERE crate_name = $1 AND channel = $2;",
+        &[&name, &channel],
+    )?;
+    if removed == 0 {
+        return Err(ChannelError::ChannelNotFound(name.into(), channel.into()).into());
+    }
+
+    cdn::invalidate_crate(config, cdn, name).context("error invalidating the crate")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_and_remove_channels() {
+        crate::test::wrapper(|env| {
+            let db = env.db();
+
+            set_channel(
+                &mut db.conn(),
+                &env.config(),
+                &env.cdn(),
+                "foo",
+                "stable",
+                "1.2.0",
+            )?;
+            set_channel(
+                &mut db.conn(),
+                &env.config(),
+                &env.cdn(),
+                "foo",
+                "lts",
+                "1.0.3",
+            )?;
+            set_channel(
+                &mut db.conn(),
+                &env.config(),
+                &env.cdn(),
+                "bar",
+                "stable",
+                "0.1.0",
+            )?;
+            // replaces the existing channel
+            set_channel(
+                &mut db.conn(),
+                &env.config(),
+                &env.cdn(),
+                "foo",
+                "stable",
+                "1.3.0",
+            )?;
+
+            assert_eq!(
+                list_channels(&mut db.conn())?,
+                [
+                    ("bar".into(), "stable".into(), "0.1.0".into()),
+                    ("foo".into(), "lts".into(), "1.0.3".into()),
+                    ("foo".into(), "stable".into(), "1.3.0".into()),
+                ]
+            );
+            assert_eq!(
+                channel_version(&mut db.conn(), "foo", "stable")?.as_deref(),
+                Some("1.3.0")
+            );
+
+            remove_channel(&mut db.conn(), &env.config(), &env.cdn(), "foo", "stable")?;
+            assert_eq!(channel_version(&mut db.conn(), "foo", "stable")?, None);
+            assert!(
+                remove_channel(&mut db.conn(), &env.config(), &env.cdn(), "foo", "stable").is_err()
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn changing_channels_invalidates_the_crate() {
+        crate::test::wrapper(|env| {
+            env.override_config(|config| {
+                config.cloudfront_distribution_id_web = Some("distribution_id_web".into());
+            });
+            let db = env.db();
+
+            set_channel(
+                &mut db.conn(),
+                &env.config(),
+                &env.cdn(),
+                "foo",
+                "stable",
+                "1.2.0",
+            )?;
+            remove_channel(&mut db.conn(), &env.config(), &env.cdn(), "foo", "stable")?;
+
+            assert!(matches!(*env.cdn(), CdnBackend::Dummy(_)));
+            if let CdnBackend::Dummy(ref invalidation_requests) = *env.cdn() {
+                let ir = invalidation_requests.lock().unwrap();
+                assert_eq!(
+                    ir.iter().filter(|(_, path)| path == "/foo*").count(),
+                    2,
+                    "{:?}",
+                    ir
+                );
+            }
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn invalid_channels_and_versions() {
+        crate::test::wrapper(|env| {
+            let db = env.db();
+
+            for channel in ["", "latest", "newest", "1", "^1.2", "*", "lts/1"] {
+                assert!(
+                    set_channel(
+                        &mut db.conn(),
+                        &env.config(),
+                        &env.cdn(),
+                        "foo",
+                        channel,
+                        "1.0.0"
+                    )
+                    .is_err(),
+                    "{}",
+                    channel
+                );
+            }
+            assert!(set_channel(
+                &mut db.conn(),
+                &env.config(),
+                &env.cdn(),
+                "foo",
+                "stable",
+                "1.0"
+            )
+            .is_err());
+            assert!(list_channels(&mut db.conn())?.is_empty());
+
+            Ok(())
+        });
+    }
+}
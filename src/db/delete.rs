@@ -1,5 +1,5 @@
 use crate::error::Result;
-use crate::storage::{rustdoc_archive_path, source_archive_path, Storage};
+use crate::storage::{book_archive_path, rustdoc_archive_path, source_archive_path, Storage};
 use crate::{Config, Context};
 use anyhow::Context as _;
 use postgres::Client;
@@ -7,7 +7,7 @@ use std::fs;
 
 /// List of directories in docs.rs's underlying storage (either the database or S3) containing a
 /// subdirectory named after the crate. Those subdirectories will be deleted.
-static LIBRARY_STORAGE_PATHS_TO_DELETE: &[&str] = &["rustdoc", "sources"];
+static LIBRARY_STORAGE_PATHS_TO_DELETE: &[&str] = &["rustdoc", "sources", "book"];
 static BINARY_STORAGE_PATHS_TO_DELETE: &[&str] = &["sources"];
 
 #[derive(Debug, thiserror::Error)]
@@ -71,6 +71,7 @@ pub fn delete_version(ctx: &dyn Context, name: &str, version: &str) -> Result<()
     let mut paths = vec![source_archive_path(name, version)];
     if is_library {
         paths.push(rustdoc_archive_path(name, version));
+        paths.push(book_archive_path(name, version));
     }
 
     for archive_filename in paths {
@@ -105,9 +106,12 @@ fn get_id(conn: &mut Client, name: &str) -> Result<i32> {
 // WARNING: these must be hard-coded and NEVER user input.
 const METADATA: &[(&str, &str)] = &[
     ("keyword_rels", "rid"),
+    ("category_rels", "rid"),
+    ("doc_reports", "rid"),
     ("builds", "rid"),
     ("compression_rels", "release"),
     ("doc_coverage", "release_id"),
+    ("doc_sizes", "release_id"),
 ];
 
 /// Returns whether this release was a library
@@ -120,12 +124,21 @@ fn delete_version_from_database(conn: &mut Client, name: &str, version: &str) ->
             &[&crate_id, &version],
         )?;
     }
-    let is_library: bool = transaction
-        .query_one(
-            "DELETE FROM releases WHERE crate_id = $1 AND version = $2 RETURNING is_library",
+    let row = transaction.query_one(
+        "DELETE FROM releases WHERE crate_id = $1 AND version = $2 RETURNING is_library, yanked",
+        &[&crate_id, &version],
+    )?;
+    let is_library: bool = row.get("is_library");
+
+    // remember yanked releases, so their URLs can be reported as gone instead of missing
+    if row.get::<_, bool>("yanked") {
+        transaction.execute(
+            "INSERT INTO purged_releases (crate_id, version)
+             VALUES ($1, $2)
+             ON CONFLICT DO NOTHING",
             &[&crate_id, &version],
-        )?
-        .get("is_library");
+        )?;
+    }
     transaction.execute(
         "UPDATE crates SET latest_version_id = (
             SELECT id FROM releases WHERE release_time = (
@@ -170,6 +183,11 @@ fn delete_crate_from_database(conn: &mut Client, name: &str, crate_id: i32) -> R
         )?;
     }
     transaction.execute("DELETE FROM owner_rels WHERE cid = $1;", &[&crate_id])?;
+    transaction.execute(
+        "DELETE FROM purged_releases WHERE crate_id = $1;",
+        &[&crate_id],
+    )?;
+    transaction.execute("DELETE FROM webhooks WHERE crate_id = $1;", &[&crate_id])?;
     let has_library = transaction
         .query_one(
             "SELECT BOOL_OR(releases.is_library) AS has_library FROM releases",
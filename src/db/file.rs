@@ -8,7 +8,7 @@
 //! However, postgres is still available for testing and backwards compatibility.
 
 use crate::error::Result;
-use crate::storage::{CompressionAlgorithm, CompressionAlgorithms, Storage};
+use crate::storage::{CompressionAlgorithms, Storage};
 
 use serde_json::Value;
 use std::path::{Path, PathBuf};
@@ -39,14 +39,14 @@ pub fn add_path_into_remote_archive<P: AsRef<Path>>(
     archive_path: &str,
     path: P,
     public_access: bool,
-) -> Result<(Value, CompressionAlgorithm)> {
-    let (file_list, algorithm) = storage.store_all_in_archive(archive_path, path.as_ref())?;
+) -> Result<(Value, CompressionAlgorithms)> {
+    let (file_list, algorithms) = storage.store_all_in_archive(archive_path, path.as_ref())?;
     if public_access {
         storage.set_public_access(archive_path, true)?;
     }
     Ok((
         file_list_to_json(file_list.into_iter().collect()),
-        algorithm,
+        algorithms,
     ))
 }
 
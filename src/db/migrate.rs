@@ -848,6 +848,208 @@ pub fn migrate(version: Option<Version>, conn: &mut Client) -> crate::error::Res
             "ALTER TABLE files ADD COLUMN public BOOL NOT NULL DEFAULT FALSE;",
             "ALTER TABLE files DROP COLUMN public;"
         ),
+        sql_migration!(
+            context, 36, "add prefix search index on normalized crate names",
+            "CREATE INDEX crates_normalized_name_prefix_idx
+                ON crates (normalize_crate_name(name) varchar_pattern_ops);",
+            "DROP INDEX crates_normalized_name_prefix_idx;"
+        ),
+        sql_migration!(
+            context, 37, "add flag for releases with an mdBook",
+            "ALTER TABLE releases ADD COLUMN has_book BOOL NOT NULL DEFAULT FALSE;",
+            "ALTER TABLE releases DROP COLUMN has_book;"
+        ),
+        sql_migration!(
+            context, 38, "add table of yanked releases whose documentation was deleted",
+            "CREATE TABLE purged_releases (
+                crate_id INT NOT NULL REFERENCES crates(id),
+                version VARCHAR(100) NOT NULL,
+                purged_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                PRIMARY KEY (crate_id, version)
+            );",
+            "DROP TABLE purged_releases;"
+        ),
+        sql_migration!(
+            context, 39, "record who triggered a queued build",
+            "ALTER TABLE queue ADD COLUMN triggered_by TEXT;",
+            "ALTER TABLE queue DROP COLUMN triggered_by;"
+        ),
+        sql_migration!(
+            context, 40, "add build notification webhooks and their pending deliveries",
+            "CREATE TABLE webhooks (
+                id SERIAL PRIMARY KEY,
+                crate_id INT NOT NULL REFERENCES crates(id),
+                url TEXT NOT NULL,
+                secret TEXT NOT NULL,
+                created_by TEXT NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                UNIQUE (crate_id, url)
+            );
+            CREATE TABLE webhook_deliveries (
+                id SERIAL PRIMARY KEY,
+                webhook_id INT NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+                payload TEXT NOT NULL,
+                attempts INT NOT NULL DEFAULT 0,
+                next_attempt TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                last_error TEXT,
+                dead_at TIMESTAMPTZ
+            );
+            CREATE INDEX webhook_deliveries_pending_idx
+                ON webhook_deliveries (next_attempt) WHERE dead_at IS NULL;",
+            "DROP TABLE webhook_deliveries, webhooks;"
+        ),
+        sql_migration!(
+            context, 41, "add short links redirecting to docs.rs urls",
+            "CREATE TABLE short_links (
+                token TEXT PRIMARY KEY,
+                target TEXT NOT NULL,
+                created_by TEXT NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                expires_at TIMESTAMPTZ
+            );",
+            "DROP TABLE short_links;"
+        ),
+        sql_migration!(
+            context, 42, "record whether releases were built with nightly features",
+            // NULL for releases built before this was recorded
+            "ALTER TABLE releases ADD COLUMN nightly_features BOOL;",
+            "ALTER TABLE releases DROP COLUMN nightly_features;"
+        ),
+        sql_migration!(
+            context, 43, "add crates.io categories of releases",
+            "CREATE TABLE categories (
+                id SERIAL PRIMARY KEY,
+                slug VARCHAR(255) NOT NULL UNIQUE
+            );
+            CREATE TABLE category_rels (
+                rid INT NOT NULL REFERENCES releases(id),
+                cid INT NOT NULL REFERENCES categories(id),
+                UNIQUE(rid, cid)
+            );",
+            "DROP TABLE category_rels, categories;"
+        ),
+        sql_migration!(
+            context, 44, "add reports of broken docs",
+            "CREATE TABLE doc_reports (
+                id SERIAL PRIMARY KEY,
+                rid INT NOT NULL REFERENCES releases(id),
+                reason TEXT NOT NULL,
+                path TEXT,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            );",
+            "DROP TABLE doc_reports;"
+        ),
+        sql_migration!(
+            context, 45, "add uploaded documentation previews",
+            "CREATE TABLE doc_previews (
+                token TEXT PRIMARY KEY,
+                created_by TEXT NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                expires_at TIMESTAMPTZ NOT NULL
+            );
+            CREATE INDEX doc_previews_expires_at_idx ON doc_previews (expires_at);",
+            "DROP TABLE doc_previews;"
+        ),
+        sql_migration!(
+            context, 46, "record the sizes of the documentation, sources and search index",
+            "CREATE TABLE doc_sizes (
+                release_id INT PRIMARY KEY REFERENCES releases(id),
+                documentation BIGINT NOT NULL,
+                sources BIGINT NOT NULL,
+                search_index BIGINT NOT NULL
+            );",
+            "DROP TABLE doc_sizes;"
+        ),
+        sql_migration!(
+            context, 47, "record the CDN purges requested through the web server",
+            "CREATE TABLE cdn_purges (
+                id SERIAL PRIMARY KEY,
+                name TEXT NOT NULL,
+                version TEXT NOT NULL,
+                paths TEXT[] NOT NULL,
+                requested_by TEXT NOT NULL,
+                requested_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                -- NULL when the purge succeeded
+                error TEXT
+            );",
+            "DROP TABLE cdn_purges;"
+        ),
+        sql_migration!(
+            context, 48, "record the cargo command of builds",
+            // NULL for builds before the command was recorded
+            "ALTER TABLE builds ADD COLUMN command TEXT;",
+            "ALTER TABLE builds DROP COLUMN command;"
+        ),
+        sql_migration!(
+            context, 49, "record the category of failed builds",
+            // NULL for successful builds and builds before the category was recorded
+            "ALTER TABLE builds ADD COLUMN failure_category TEXT;",
+            "ALTER TABLE builds DROP COLUMN failure_category;"
+        ),
+        sql_migration!(
+            context, 50, "record when crates were added to the build queue",
+            // crates already queued count as added during the migration
+            "ALTER TABLE queue ADD COLUMN queued_at TIMESTAMPTZ NOT NULL DEFAULT NOW();",
+            "ALTER TABLE queue DROP COLUMN queued_at;"
+        ),
+        sql_migration!(
+            context, 51, "add release channels",
+            "CREATE TABLE version_channels (
+                crate_name TEXT NOT NULL,
+                channel TEXT NOT NULL,
+                version TEXT NOT NULL,
+                PRIMARY KEY (crate_name, channel)
+            );",
+            "DROP TABLE version_channels;"
+        ),
+        sql_migration!(
+            context, 52, "record the minimum supported rust version of releases",
+            // NULL for releases without `rust-version` and releases built before it was recorded
+            "ALTER TABLE releases ADD COLUMN rust_version TEXT;",
+            "ALTER TABLE releases DROP COLUMN rust_version;"
+        ),
+        sql_migration!(
+            context, 53, "record the environment of builds",
+            // NULL for builds before the environment was recorded
+            "ALTER TABLE builds
+                ADD COLUMN cargo_version TEXT,
+                ADD COLUMN target TEXT,
+                ADD COLUMN cfgs TEXT[];",
+            "ALTER TABLE builds
+                DROP COLUMN cargo_version,
+                DROP COLUMN target,
+                DROP COLUMN cfgs;"
+        ),
+        sql_migration!(
+            context, 54, "record whether releases ship a changelog",
+            // the names of `web::changelog::CHANGELOG_FILES`, only files in the root of the
+            // source count
+            "ALTER TABLE releases ADD COLUMN has_changelog BOOL NOT NULL DEFAULT FALSE;
+            UPDATE releases SET has_changelog = TRUE
+            WHERE EXISTS (
+                SELECT 1
+                FROM json_array_elements(
+                    CASE WHEN json_typeof(releases.files) = 'array' THEN releases.files ELSE '[]' END
+                ) AS file
+                WHERE lower(file->>1) IN (
+                    'changelog.md', 'changes.md', 'history.md', 'releases.md', 'news.md',
+                    'changelog', 'changes'
+                )
+            );",
+            "ALTER TABLE releases DROP COLUMN has_changelog;"
+        ),
+        sql_migration!(
+            context, 55, "store the static files documentation pages load right away",
+            // releases built before this are served without preload links
+            "ALTER TABLE releases ADD COLUMN preload_assets TEXT[] NOT NULL DEFAULT '{}';",
+            "ALTER TABLE releases DROP COLUMN preload_assets;"
+        ),
+        sql_migration!(
+            context, 56, "record the license file of releases",
+            // NULL for releases without `license-file` and releases built before it was recorded
+            "ALTER TABLE releases ADD COLUMN license_file TEXT;",
+            "ALTER TABLE releases DROP COLUMN license_file;"
+        ),
 
     ];
 
@@ -2,15 +2,22 @@
 
 pub use self::add_package::update_crate_data_in_database;
 pub(crate) use self::add_package::{
-    add_build_into_database, add_doc_coverage, add_package_into_database,
+    add_book_into_database, add_build_into_database, add_doc_coverage, add_doc_sizes_into_database,
+    add_nightly_features_into_database, add_package_into_database,
+    add_preload_assets_into_database, uses_unstable_doc_features,
 };
+pub use self::archives::recompress_archives;
+pub use self::build_logs::recompress_build_logs;
 pub use self::delete::{delete_crate, delete_version};
 pub use self::file::{add_path_into_database, add_path_into_remote_archive};
 pub use self::migrate::migrate;
 pub use self::pool::{Pool, PoolClient, PoolError};
 
 mod add_package;
+mod archives;
 pub mod blacklist;
+mod build_logs;
+pub mod channels;
 mod delete;
 pub(crate) mod file;
 mod migrate;
@@ -3,7 +3,7 @@ use crate::Config;
 use postgres::{Client, NoTls};
 use r2d2_postgres::PostgresConnectionManager;
 use std::sync::Arc;
-use tracing::debug;
+use tracing::{debug, instrument};
 
 pub type PoolClient = r2d2::PooledConnection<PostgresConnectionManager<NoTls>>;
 
@@ -73,6 +73,7 @@ impl Pool {
         }
     }
 
+    #[instrument(skip_all)]
     pub fn get(&self) -> Result<PoolClient, PoolError> {
         match self.with_pool(|p| p.get()) {
             Ok(conn) => Ok(conn),
@@ -0,0 +1,191 @@
+use serde::{Serialize, Serializer};
+use std::{fmt, str::FromStr};
+
+/// The likely cause of a failed build, guessed from its build log.
+///
+/// `DocsTooLarge` is never guessed, it's set by the builder when the documentation exceeded
+/// the size limit and was rejected.
+///
+/// [`BuildFailure::as_str`] is the only place naming the categories, it's what is stored in
+/// `builds.failure_category`, serialized and shown in the templates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BuildFailure {
+    Timeout,
+    OutOfMemory,
+    MissingDependency,
+    CompilationError,
+    DocsTooLarge,
+    Other,
+}
+
+// The patterns are checked in order: a timeout or OOM kill usually leaves
+// unrelated compiler output behind, and a missing dependency often ends in
+// `could not compile` for the crate depending on it.
+const PATTERNS: &[(BuildFailure, &[&str])] = &[
+    (BuildFailure::Timeout, &["timed out after", "no output for"]),
+    (
+        BuildFailure::OutOfMemory,
+        &["ran out of memory", "memory allocation of"],
+    ),
+    (
+        BuildFailure::MissingDependency,
+        &[
+            "no matching package named",
+            "failed to select a version",
+            "could not find system library",
+            "was not found in the pkg-config search path",
+            "Unable to find libclang",
+        ],
+    ),
+    (
+        BuildFailure::CompilationError,
+        &["error[E", "could not compile", "could not document"],
+    ),
+];
+
+impl BuildFailure {
+    pub(crate) const ALL: [BuildFailure; 6] = [
+        BuildFailure::Timeout,
+        BuildFailure::OutOfMemory,
+        BuildFailure::MissingDependency,
+        BuildFailure::CompilationError,
+        BuildFailure::DocsTooLarge,
+        BuildFailure::Other,
+    ];
+
+    /// Categorize a failed build based on its build log.
+    pub(crate) fn categorize(log: &str) -> Self {
+        PATTERNS
+            .iter()
+            .find(|(_, patterns)| patterns.iter().any(|pattern| log.contains(pattern)))
+            .map(|(failure, _)| *failure)
+            .unwrap_or(BuildFailure::Other)
+    }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            BuildFailure::Timeout => "timeout",
+            BuildFailure::OutOfMemory => "out-of-memory",
+            BuildFailure::MissingDependency => "missing-dependency",
+            BuildFailure::CompilationError => "compilation-error",
+            BuildFailure::DocsTooLarge => "docs-too-large",
+            BuildFailure::Other => "other",
+        }
+    }
+
+    /// The category of a build from `builds.failure_category`. Failed builds from before the
+    /// categories were recorded are [`BuildFailure::Other`], successful builds have none.
+    pub(crate) fn from_column(category: Option<&str>, successful: bool) -> Option<Self> {
+        if successful {
+            return None;
+        }
+        Some(
+            category
+                .and_then(|category| category.parse().ok())
+                .unwrap_or(BuildFailure::Other),
+        )
+    }
+}
+
+impl fmt::Display for BuildFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for BuildFailure {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BuildFailure::ALL
+            .into_iter()
+            .find(|failure| failure.as_str() == s)
+            .ok_or_else(|| anyhow::anyhow!("unknown build failure category: {}", s))
+    }
+}
+
+impl Serialize for BuildFailure {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(
+        "[INFO] running `Command { std: \"docker\" \"start\" \"-a\" \"c0ffee\", kill_on_drop: false }`\n\
+         [INFO] [stderr]    Compiling syn v2.0.15\n\
+         [ERROR] command timed out after 900 seconds",
+        BuildFailure::Timeout
+    )]
+    #[test_case(
+        "[INFO] [stderr]    Compiling huge v0.1.0\n\
+         [INFO] [stderr] error: could not compile `huge`\n\
+         [ERROR] container ran out of memory",
+        BuildFailure::OutOfMemory
+    )]
+    #[test_case(
+        "[INFO] [stderr] memory allocation of 4294967296 bytes failed",
+        BuildFailure::OutOfMemory
+    )]
+    #[test_case(
+        "[INFO] [stderr] error: no matching package named `serde_derive2` found\n\
+         [INFO] [stderr] location searched: registry `crates-io`",
+        BuildFailure::MissingDependency
+    )]
+    #[test_case(
+        "[INFO] [stderr] warning: build failed, waiting for other jobs to finish...\n\
+         [INFO] [stderr]   The system library `gtk+-3.0` required by crate `gdk-sys` \
+         was not found.\n\
+         [INFO] [stderr]   Package gtk+-3.0 was not found in the pkg-config search path.\n\
+         [INFO] [stderr] error: could not compile `gdk-sys`",
+        BuildFailure::MissingDependency
+    )]
+    #[test_case(
+        "[INFO] [stderr]  Documenting foo v0.1.0 (/opt/rustwide/workdir)\n\
+         [INFO] [stderr] error[E0425]: cannot find value `bar` in this scope\n\
+         [INFO] [stderr] error: could not document `foo`",
+        BuildFailure::CompilationError
+    )]
+    #[test_case(
+        "[INFO] [stderr] error: could not compile `foo` due to previous error",
+        BuildFailure::CompilationError
+    )]
+    #[test_case("", BuildFailure::Other)]
+    #[test_case(
+        "[INFO] [stderr] error: failed to parse manifest at `/opt/rustwide/workdir/Cargo.toml`",
+        BuildFailure::Other
+    )]
+    fn categorize(log: &str, expected: BuildFailure) {
+        assert_eq!(BuildFailure::categorize(log), expected);
+    }
+
+    #[test]
+    fn roundtrip() {
+        for failure in BuildFailure::ALL {
+            assert_eq!(failure.as_str().parse::<BuildFailure>().unwrap(), failure);
+            assert_eq!(
+                serde_json::to_value(failure).unwrap(),
+                serde_json::Value::String(failure.to_string())
+            );
+        }
+        assert!("oom".parse::<BuildFailure>().is_err());
+    }
+
+    #[test]
+    fn from_column() {
+        assert_eq!(BuildFailure::from_column(None, true), None);
+        assert_eq!(
+            BuildFailure::from_column(Some("timeout"), false),
+            Some(BuildFailure::Timeout)
+        );
+        // failed before the categories were recorded
+        assert_eq!(
+            BuildFailure::from_column(None, false),
+            Some(BuildFailure::Other)
+        );
+    }
+}
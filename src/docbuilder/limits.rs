@@ -10,6 +10,8 @@ pub(crate) struct Limits {
     timeout: Duration,
     networking: bool,
     max_log_size: usize,
+    /// Documentation with more HTML than this is rejected instead of being served incomplete
+    max_doc_size: usize,
 }
 
 impl Default for Limits {
@@ -19,7 +21,8 @@ impl Default for Limits {
             timeout: Duration::from_secs(15 * 60), // 15 minutes
             targets: 10,
             networking: false,
-            max_log_size: 100 * 1024, // 100 KB
+            max_log_size: 100 * 1024,             // 100 KB
+            max_doc_size: 2 * 1024 * 1024 * 1024, // 2 GB
         }
     }
 }
@@ -67,6 +70,10 @@ impl Limits {
         self.max_log_size
     }
 
+    pub(crate) fn max_doc_size(&self) -> usize {
+        self.max_doc_size
+    }
+
     pub(crate) fn targets(&self) -> usize {
         self.targets
     }
@@ -1,7 +1,9 @@
 mod crates;
+mod failures;
 mod limits;
 mod rustwide_builder;
 
+pub(crate) use self::failures::BuildFailure;
 pub(crate) use self::limits::Limits;
-pub(crate) use self::rustwide_builder::{BuildResult, DocCoverage};
+pub(crate) use self::rustwide_builder::{BuildResult, DocCoverage, DocSizes};
 pub use self::rustwide_builder::{PackageKind, RustwideBuilder};
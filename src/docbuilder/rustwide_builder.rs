@@ -1,16 +1,21 @@
 use crate::db::file::add_path_into_database;
 use crate::db::{
-    add_build_into_database, add_doc_coverage, add_package_into_database,
-    add_path_into_remote_archive, update_crate_data_in_database, Pool,
+    add_book_into_database, add_build_into_database, add_doc_coverage, add_doc_sizes_into_database,
+    add_nightly_features_into_database, add_package_into_database, add_path_into_remote_archive,
+    add_preload_assets_into_database, update_crate_data_in_database, uses_unstable_doc_features,
+    Pool,
 };
-use crate::docbuilder::{crates::crates_from_path, Limits};
+use crate::docbuilder::{crates::crates_from_path, BuildFailure, Limits};
 use crate::error::Result;
 use crate::index::api::ReleaseData;
 use crate::repositories::RepositoryStatsUpdater;
-use crate::storage::{rustdoc_archive_path, source_archive_path};
+use crate::storage::{book_archive_path, get_file_list, rustdoc_archive_path, source_archive_path};
+use crate::utils::scoped_registry::split_scoped_name;
 use crate::utils::{
-    copy_dir_all, parse_rustc_version, queue_builder, set_config, CargoMetadata, ConfigName,
+    copy_dir_all, parse_rustc_version, queue_builder, report_error, set_config, CargoMetadata,
+    ConfigName,
 };
+use crate::webhooks;
 use crate::RUSTDOC_STATIC_STORAGE_PREFIX;
 use crate::{db::blacklist::is_blacklisted, utils::MetadataPackage};
 use crate::{Config, Context, Index, Metrics, Storage};
@@ -24,6 +29,7 @@ use rustwide::logging::{self, LogStorage};
 use rustwide::toolchain::ToolchainError;
 use rustwide::{AlternativeRegistry, Build, Crate, Toolchain, Workspace, WorkspaceBuilder};
 use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
 use std::path::Path;
 use std::sync::Arc;
 use tracing::{debug, info, warn};
@@ -47,6 +53,8 @@ pub struct RustwideBuilder {
     metrics: Arc<Metrics>,
     index: Arc<Index>,
     rustc_version: String,
+    /// The output of `cargo --version`, `None` for CI toolchains and when it couldn't be detected
+    cargo_version: Option<String>,
     repository_stats_updater: Arc<RepositoryStatsUpdater>,
     skip_build_if_exists: bool,
 }
@@ -94,6 +102,7 @@ impl RustwideBuilder {
             metrics: context.metrics()?,
             index: context.index()?,
             rustc_version: String::new(),
+            cargo_version: None,
             repository_stats_updater: context.repository_stats_updater()?,
             skip_build_if_exists: false,
         })
@@ -193,6 +202,13 @@ impl RustwideBuilder {
         }
 
         self.rustc_version = self.detect_rustc_version()?;
+        self.cargo_version = match self.detect_cargo_version() {
+            Ok(version) => Some(version),
+            Err(err) => {
+                warn!("failed to detect the cargo version: {}", err);
+                None
+            }
+        };
 
         let has_changed = old_version.as_deref() != Some(&self.rustc_version);
         if has_changed {
@@ -218,6 +234,21 @@ impl RustwideBuilder {
         }
     }
 
+    /// Return a string containing the output of `cargo --version`.
+    fn detect_cargo_version(&self) -> Result<String> {
+        let res = Command::new(&self.workspace, self.toolchain.cargo())
+            .args(&["--version"])
+            .log_output(false)
+            .run_capture()?;
+        let mut iter = res.stdout_lines().iter();
+        if let (Some(line), None) = (iter.next(), iter.next()) {
+            info!("found {}", line);
+            Ok(line.clone())
+        } else {
+            Err(anyhow!("invalid output returned by `cargo --version`",))
+        }
+    }
+
     pub fn add_essential_files(&mut self) -> Result<()> {
         let rustc_version = parse_rustc_version(&self.rustc_version)?;
 
@@ -362,10 +393,24 @@ impl RustwideBuilder {
         let mut build_dir = self.workspace.build_dir(&format!("{}-{}", name, version));
         build_dir.purge().map_err(FailureError::compat)?;
 
-        let krate = match kind {
-            PackageKind::Local(path) => Crate::local(path),
-            PackageKind::CratesIo => Crate::crates_io(name, version),
-            PackageKind::Registry(registry) => {
+        // crates of scoped registries are always fetched from their own registry
+        let (crate_name, scoped_registry) = split_scoped_name(name);
+        let scoped_registry_url = scoped_registry
+            .map(|registry| {
+                self.config
+                    .scoped_registry_url(registry)
+                    .map(str::to_owned)
+                    .ok_or_else(|| anyhow!("unknown scoped registry {}", registry))
+            })
+            .transpose()?;
+
+        let krate = match (kind, &scoped_registry_url) {
+            (_, Some(registry)) => {
+                Crate::registry(AlternativeRegistry::new(registry), crate_name, version)
+            }
+            (PackageKind::Local(path), None) => Crate::local(path),
+            (PackageKind::CratesIo, None) => Crate::crates_io(name, version),
+            (PackageKind::Registry(registry), None) => {
                 Crate::registry(AlternativeRegistry::new(registry), name, version)
             }
         };
@@ -416,6 +461,10 @@ impl RustwideBuilder {
                         )?;
                     }
 
+                    if scoped_registry.is_some() {
+                        res.cargo_metadata.rename_root(name);
+                    }
+
                     if res.result.successful {
                         if let Some(name) = res.cargo_metadata.root().library_name() {
                             let host_target = build.host_target_dir();
@@ -452,43 +501,109 @@ impl RustwideBuilder {
                                 &metadata,
                             )?;
                         }
-                        let (_, new_alg) = add_path_into_remote_archive(
+                    }
+
+                    let doc_sizes = DocSizes::measure(
+                        if has_docs {
+                            Some(local_storage.path())
+                        } else {
+                            None
+                        },
+                        &build.host_source_dir(),
+                    )?;
+
+                    // Serving only part of the documentation would leave broken links
+                    // everywhere, so documentation over the limit isn't stored at all.
+                    if has_docs && doc_sizes.documentation > limits.max_doc_size() as i64 {
+                        let message = format!(
+                            "documentation is {} bytes, more than the limit of {} bytes",
+                            doc_sizes.documentation,
+                            limits.max_doc_size()
+                        );
+                        warn!("{} {}: {}", name, version, message);
+                        res.build_log.push_str(&format!("\n[ERROR] {}\n", message));
+                        res.result.successful = false;
+                        res.result.failure = Some(BuildFailure::DocsTooLarge);
+                        successful_targets.clear();
+                        has_docs = false;
+                    }
+
+                    // all pages reference the same static files, the root page of the default
+                    // target stands in for them
+                    let preload_assets = if has_docs {
+                        let root_page = local_storage
+                            .path()
+                            .join(res.cargo_metadata.root().package_name())
+                            .join("index.html");
+                        match std::fs::read(&root_page)
+                            .map_err(Error::from)
+                            .and_then(|html| {
+                                crate::web::rustdoc_assets::preload_assets(&html)
+                                    .map_err(Error::from)
+                            }) {
+                            Ok(assets) => assets,
+                            Err(err) => {
+                                warn!("failed to parse {}: {:?}", root_page.display(), err);
+                                Vec::new()
+                            }
+                        }
+                    } else {
+                        Vec::new()
+                    };
+
+                    if has_docs {
+                        let (_, new_algs) = add_path_into_remote_archive(
                             &self.storage,
                             &rustdoc_archive_path(name, version),
                             local_storage.path(),
                             true,
                         )?;
-                        algs.insert(new_alg);
-                    };
+                        algs.extend(new_algs);
+                    }
+
+                    let has_book = has_docs
+                        && self.config.enable_books
+                        && self.build_book(name, version, build, &limits)?;
 
                     // Store the sources even if the build fails
                     debug!("adding sources into database");
                     let files_list = {
-                        let (files_list, new_alg) = add_path_into_remote_archive(
+                        let (files_list, new_algs) = add_path_into_remote_archive(
                             &self.storage,
                             &source_archive_path(name, version),
                             build.host_source_dir(),
                             false,
                         )?;
-                        algs.insert(new_alg);
+                        algs.extend(new_algs);
                         files_list
                     };
 
                     let has_examples = build.host_source_dir().join("examples").is_dir();
-                    if res.result.successful {
+                    let result = if res.result.successful {
                         self.metrics.successful_builds.inc();
+                        "successful"
                     } else if res.cargo_metadata.root().is_library() {
                         self.metrics.failed_builds.inc();
+                        "failed"
                     } else {
                         self.metrics.non_library_builds.inc();
-                    }
-
-                    let release_data = match self.index.api().get_release_data(name, version) {
-                        Ok(data) => data,
-                        Err(err) => {
-                            warn!("{:#?}", err);
-                            ReleaseData::default()
-                        }
+                        "non-library"
+                    };
+                    self.metrics
+                        .finished_builds
+                        .with_label_values(&[result])
+                        .inc();
+
+                    // the registry API only knows the crates of the default registry
+                    let release_data = match scoped_registry {
+                        Some(_) => ReleaseData::default(),
+                        None => match self.index.api().get_release_data(name, version) {
+                            Ok(data) => data,
+                            Err(err) => {
+                                warn!("{:#?}", err);
+                                ReleaseData::default()
+                            }
+                        },
                     };
 
                     let cargo_metadata = res.cargo_metadata.root();
@@ -513,17 +628,41 @@ impl RustwideBuilder {
                     if let Some(doc_coverage) = res.doc_coverage {
                         add_doc_coverage(&mut conn, release_id, doc_coverage)?;
                     }
+                    add_doc_sizes_into_database(&mut conn, release_id, doc_sizes)?;
+
+                    if has_book {
+                        add_book_into_database(&mut conn, release_id)?;
+                    }
+
+                    let nightly_features = metadata.uses_nightly_features()
+                        || uses_unstable_doc_features(cargo_metadata, &build.host_source_dir())
+                            .unwrap_or(false);
+                    add_nightly_features_into_database(&mut conn, release_id, nightly_features)?;
+                    add_preload_assets_into_database(&mut conn, release_id, &preload_assets)?;
 
                     let build_id = add_build_into_database(&mut conn, release_id, &res.result)?;
-                    let build_log_path = format!("build-logs/{}/{}.txt", build_id, default_target);
-                    self.storage.store_one(build_log_path, res.build_log)?;
+                    self.storage
+                        .store_build_log(build_id, default_target, res.build_log)?;
+
+                    if let Err(err) = webhooks::queue_build_notifications(
+                        &mut conn,
+                        &self.config,
+                        name,
+                        version,
+                        build_id,
+                        res.result.successful,
+                    ) {
+                        report_error(&err.context("failed to queue build notifications"));
+                    }
 
                     // Some crates.io crate data is mutable, so we proactively update it during a release
-                    match self.index.api().get_crate_data(name) {
-                        Ok(crate_data) => {
-                            update_crate_data_in_database(&mut conn, name, &crate_data)?
+                    if scoped_registry.is_none() {
+                        match self.index.api().get_crate_data(name) {
+                            Ok(crate_data) => {
+                                update_crate_data_in_database(&mut conn, name, &crate_data)?
+                            }
+                            Err(err) => warn!("{:#?}", err),
                         }
-                        Err(err) => warn!("{:#?}", err),
                     }
 
                     if res.result.successful {
@@ -629,6 +768,51 @@ impl RustwideBuilder {
         )
     }
 
+    /// Builds the mdBook in the `book/` directory of the crate, if there is one,
+    /// and uploads it next to the documentation.
+    ///
+    /// Returns whether a book was uploaded. A failing book build doesn't fail the release.
+    fn build_book(
+        &self,
+        name: &str,
+        version: &str,
+        build: &Build,
+        limits: &Limits,
+    ) -> Result<bool> {
+        if !build
+            .host_source_dir()
+            .join("book")
+            .join("book.toml")
+            .is_file()
+        {
+            return Ok(false);
+        }
+
+        debug!("building book for {} {}", name, version);
+        let built = build
+            .cmd("mdbook")
+            .args(&["build", "book", "--dest-dir", "/opt/rustwide/target/book"])
+            .timeout(Some(limits.timeout()))
+            .run();
+        if let Err(err) = built {
+            info!("error when trying to build the book: {}", err);
+            return Ok(false);
+        }
+
+        let book_dir = build.host_target_dir().join("book");
+        if !book_dir.is_dir() {
+            return Ok(false);
+        }
+
+        add_path_into_remote_archive(
+            &self.storage,
+            &book_archive_path(name, version),
+            &book_dir,
+            true,
+        )?;
+        Ok(true)
+    }
+
     fn execute_build(
         &self,
         target: &str,
@@ -652,6 +836,11 @@ impl RustwideBuilder {
             format!("-{}", parse_rustc_version(&self.rustc_version)?),
         ]);
 
+        let command = command_line(
+            metadata.environment_variables(),
+            &self.cargo_args(target, metadata, rustdoc_flags.clone()),
+        );
+
         let mut storage = LogStorage::new(log::LevelFilter::Info);
         storage.set_max_size(limits.max_log_size());
 
@@ -668,10 +857,19 @@ impl RustwideBuilder {
         };
 
         let successful = logging::capture(&storage, || {
-            self.prepare_command(build, target, metadata, limits, rustdoc_flags)
+            match self
+                .prepare_command(build, target, metadata, limits, rustdoc_flags)
                 .and_then(|command| command.run().map_err(Error::from))
-                .is_ok()
+            {
+                Ok(()) => true,
+                Err(err) => {
+                    // make timeouts and OOM kills visible in the build log
+                    log::error!("{}", err);
+                    false
+                }
+            }
         });
+        let build_log = storage.to_string();
 
         // For proc-macros, cargo will put the output in `target/doc`.
         // Move it to the target-specific directory for consistency with other builds.
@@ -695,10 +893,15 @@ impl RustwideBuilder {
                 rustc_version: self.rustc_version.clone(),
                 docsrs_version: format!("docsrs {}", crate::BUILD_VERSION),
                 successful,
+                command: Some(command),
+                failure: (!successful).then(|| BuildFailure::categorize(&build_log)),
+                cargo_version: self.cargo_version.clone(),
+                target: Some(target.to_string()),
+                cfgs: Some(metadata.cfgs()),
             },
             doc_coverage,
             cargo_metadata,
-            build_log: storage.to_string(),
+            build_log,
             target: target.to_string(),
         })
     }
@@ -709,7 +912,7 @@ impl RustwideBuilder {
         target: &str,
         metadata: &Metadata,
         limits: &Limits,
-        mut rustdoc_flags_extras: Vec<String>,
+        rustdoc_flags_extras: Vec<String>,
     ) -> Result<Command<'ws, 'pl>> {
         // If the explicit target is not a tier one target, we need to install it.
         if !docsrs_metadata::DEFAULT_TARGETS.contains(&target) {
@@ -719,6 +922,27 @@ impl RustwideBuilder {
                 .map_err(FailureError::compat)?;
         }
 
+        let cargo_args = self.cargo_args(target, metadata, rustdoc_flags_extras);
+
+        let mut command = build
+            .cargo()
+            .timeout(Some(limits.timeout()))
+            .no_output_timeout(None);
+
+        for (key, val) in metadata.environment_variables() {
+            command = command.env(key, val);
+        }
+
+        Ok(command.args(&cargo_args))
+    }
+
+    /// The arguments `cargo` is called with to document `target`.
+    fn cargo_args(
+        &self,
+        target: &str,
+        metadata: &Metadata,
+        mut rustdoc_flags_extras: Vec<String>,
+    ) -> Vec<String> {
         // Add docs.rs specific arguments
         let mut cargo_args = vec![
             "--offline".into(),
@@ -766,18 +990,7 @@ impl RustwideBuilder {
         ];
 
         rustdoc_flags_extras.extend(UNCONDITIONAL_ARGS.iter().map(|&s| s.to_owned()));
-        let cargo_args = metadata.cargo_args(&cargo_args, &rustdoc_flags_extras);
-
-        let mut command = build
-            .cargo()
-            .timeout(Some(limits.timeout()))
-            .no_output_timeout(None);
-
-        for (key, val) in metadata.environment_variables() {
-            command = command.env(key, val);
-        }
-
-        Ok(command.args(&cargo_args))
+        metadata.cargo_args(&cargo_args, &rustdoc_flags_extras)
     }
 
     fn copy_docs(
@@ -827,6 +1040,43 @@ impl RustwideBuilder {
     }
 }
 
+/// Parts of names of environment variables whose values are hidden in recorded commands.
+const SENSITIVE_ENV_VARS: &[&str] = &["TOKEN", "SECRET", "PASSWORD", "KEY", "CREDENTIAL"];
+
+/// Formats a `cargo` invocation as it could be pasted into a shell to reproduce a build, with
+/// the values of sensitive environment variables redacted.
+fn command_line<K: AsRef<str>, V: AsRef<str>>(
+    env: impl IntoIterator<Item = (K, V)>,
+    args: &[String],
+) -> String {
+    fn quote(arg: &str) -> String {
+        let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+        if !arg.is_empty() && arg.chars().all(is_safe) {
+            arg.to_owned()
+        } else {
+            format!("'{}'", arg.replace('\'', r"'\''"))
+        }
+    }
+
+    let mut env: Vec<_> = env.into_iter().collect();
+    env.sort_by(|(a, _), (b, _)| a.as_ref().cmp(b.as_ref()));
+
+    let mut parts = Vec::with_capacity(env.len() + args.len() + 1);
+    for (key, value) in &env {
+        let key = key.as_ref();
+        let upper = key.to_ascii_uppercase();
+        let value = if SENSITIVE_ENV_VARS.iter().any(|part| upper.contains(part)) {
+            "<redacted>".to_owned()
+        } else {
+            quote(value.as_ref())
+        };
+        parts.push(format!("{}={}", key, value));
+    }
+    parts.push("cargo".to_owned());
+    parts.extend(args.iter().map(|arg| quote(arg)));
+    parts.join(" ")
+}
+
 struct FullBuildResult {
     result: BuildResult,
     target: String,
@@ -849,10 +1099,55 @@ pub(crate) struct DocCoverage {
     pub(crate) items_with_examples: i32,
 }
 
+/// How many bytes the files of a release occupy, before they are compressed in the storage.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct DocSizes {
+    /// The HTML pages generated by rustdoc for all targets
+    pub(crate) documentation: i64,
+    /// The files in the source archive
+    pub(crate) sources: i64,
+    /// The search indexes generated by rustdoc for all targets
+    pub(crate) search_index: i64,
+}
+
+impl DocSizes {
+    /// Measures the rustdoc output in `doc_dir` when there is any, and the sources in
+    /// `source_dir`.
+    fn measure(doc_dir: Option<&Path>, source_dir: &Path) -> Result<DocSizes> {
+        let mut sizes = DocSizes::default();
+        if let Some(doc_dir) = doc_dir {
+            for path in get_file_list(doc_dir)? {
+                let size = std::fs::metadata(doc_dir.join(&path))?.len() as i64;
+                let file_name = path.file_name().and_then(OsStr::to_str).unwrap_or_default();
+                // `search-index.js`, or `search-index-<resource suffix>.js` in older rustdoc versions
+                if file_name.starts_with("search-index") && file_name.ends_with(".js") {
+                    sizes.search_index += size;
+                } else if file_name.ends_with(".html") {
+                    sizes.documentation += size;
+                }
+            }
+        }
+        for path in get_file_list(source_dir)? {
+            sizes.sources += std::fs::metadata(source_dir.join(path))?.len() as i64;
+        }
+        Ok(sizes)
+    }
+}
+
 pub(crate) struct BuildResult {
     pub(crate) rustc_version: String,
     pub(crate) docsrs_version: String,
     pub(crate) successful: bool,
+    /// The `cargo` command documenting the default target, `None` for builds that didn't run it
+    pub(crate) command: Option<String>,
+    /// The likely cause of the failure, `None` for successful builds
+    pub(crate) failure: Option<BuildFailure>,
+    /// The output of `cargo --version`, `None` when it wasn't detected
+    pub(crate) cargo_version: Option<String>,
+    /// The target the command documented, `None` for builds that didn't run it
+    pub(crate) target: Option<String>,
+    /// The `--cfg` flags of the build, `None` for builds that didn't run it
+    pub(crate) cfgs: Option<Vec<String>>,
 }
 
 #[cfg(test)]
@@ -861,6 +1156,25 @@ mod tests {
     use crate::test::{assert_redirect, assert_success, wrapper};
     use serde_json::Value;
 
+    #[test]
+    fn command_line_is_quoted_and_redacted() {
+        let args: Vec<String> = [
+            "rustdoc",
+            "--features",
+            "a b",
+            "--config",
+            r#"build.rustdocflags=["--cfg", "docsrs"]"#,
+            "it's",
+        ]
+        .iter()
+        .map(|&arg| arg.to_owned())
+        .collect();
+        assert_eq!(
+            command_line([("DOCS_RS", "1"), ("API_TOKEN", "hunter2")], &args),
+            r#"API_TOKEN=<redacted> DOCS_RS=1 cargo rustdoc --features 'a b' --config 'build.rustdocflags=["--cfg", "docsrs"]' 'it'\''s'"#
+        );
+    }
+
     #[test]
     #[ignore]
     fn test_build_crate() {
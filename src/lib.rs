@@ -21,12 +21,14 @@ mod docbuilder;
 mod error;
 pub mod index;
 mod metrics;
+mod previews;
 pub mod repositories;
 pub mod storage;
 #[cfg(test)]
 mod test;
 pub mod utils;
 mod web;
+mod webhooks;
 
 #[allow(dead_code)]
 mod target {
@@ -21,6 +21,7 @@ macro_rules! metrics {
                 $metric_vis $metric: $ty,
             )*
             pub(crate) recently_accessed_releases: RecentlyAccessedReleases,
+            pub(crate) recent_response_times: RecentResponseTimes,
         }
         impl $name {
             $vis fn new() -> Result<Self, prometheus::Error> {
@@ -38,6 +39,7 @@ macro_rules! metrics {
                 Ok(Self {
                     registry,
                     recently_accessed_releases: RecentlyAccessedReleases::new(),
+                    recent_response_times: RecentResponseTimes::new(),
                     $(
                         $(#[$meta])*
                         $metric,
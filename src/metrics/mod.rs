@@ -8,12 +8,15 @@ use crate::BuildQueue;
 use anyhow::Error;
 use dashmap::DashMap;
 use prometheus::proto::MetricFamily;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 
 load_metric_type!(IntGauge as single);
 load_metric_type!(IntCounter as single);
 load_metric_type!(IntCounterVec as vec);
 load_metric_type!(IntGaugeVec as vec);
+load_metric_type!(GaugeVec as vec);
 load_metric_type!(HistogramVec as vec);
 
 metrics! {
@@ -28,6 +31,10 @@ metrics! {
         queue_is_locked: IntGauge,
         /// queued crates by priority
         queued_crates_count_by_priority: IntGaugeVec["priority"],
+        /// Seconds since the oldest crate waiting in the build queue was added, 0 without any
+        queue_oldest_crate_age: IntGauge,
+        /// Number of build servers which finished a build during the last hour
+        active_builders: IntGauge,
 
         /// The number of idle database connections
         idle_db_connections: IntGauge,
@@ -49,6 +56,10 @@ metrics! {
         pub(crate) routes_visited: IntCounterVec["route"],
         /// The response times of various docs.rs routes
         pub(crate) response_time: HistogramVec["route"],
+        /// Number of responses sent with each cache policy
+        pub(crate) cache_policies: IntCounterVec["policy"],
+        /// Percentiles of the response times of various docs.rs routes in the last five minutes
+        response_time_percentiles: GaugeVec["route", "quantile"],
         /// The time it takes to render a rustdoc page
         pub(crate) rustdoc_rendering_times: HistogramVec["step"],
         /// The time it takes to render a rustdoc redirect page
@@ -69,9 +80,17 @@ metrics! {
         pub(crate) failed_builds: IntCounter,
         /// Number of builds that did not complete due to not being a library
         pub(crate) non_library_builds: IntCounter,
+        /// Number of finished builds by their result: successful, failed or non-library
+        pub(crate) finished_builds: IntCounterVec["result"],
 
         /// Number of files uploaded to the storage backend
         pub(crate) uploaded_files_total: IntCounter,
+        /// Number of storage reads retried after a transient error
+        pub(crate) storage_read_retries: IntCounter,
+        /// Number of storage reads repeated on the fallback storage after the primary one failed
+        pub(crate) storage_fallback_reads: IntCounter,
+        /// The time it takes to decompress a file read from the storage backend
+        pub(crate) storage_decompression_times: HistogramVec["algorithm"],
 
         /// The number of attempted files that failed due to a memory limit
         pub(crate) html_rewrite_ooms: IntCounter,
@@ -151,6 +170,85 @@ impl RecentlyAccessedReleases {
     }
 }
 
+/// How long response times are kept for [`RecentResponseTimes`]
+const RESPONSE_TIMES_WINDOW: Duration = Duration::from_secs(5 * 60);
+/// Maximum number of response times kept per route, older ones are dropped first
+const RESPONSE_TIMES_MAX_SAMPLES: usize = 1000;
+/// Maximum number of routes response times are kept for
+const RESPONSE_TIMES_MAX_ROUTES: usize = 1000;
+const RESPONSE_TIME_QUANTILES: &[(&str, f64)] = &[("0.5", 0.5), ("0.9", 0.9), ("0.99", 0.99)];
+
+/// Response times per route in a rolling window, exported as percentiles for dashboards that
+/// can't compute them from the `response_time` histogram.
+///
+/// Only the requests of the last five minutes are included, capped to the newest 1000 per route.
+/// The percentiles are computed when the metrics are gathered. Routes without requests in the
+/// window are removed from the export instead of keeping their last values, and everything is
+/// reset when the server restarts.
+#[derive(Debug, Default)]
+pub(crate) struct RecentResponseTimes {
+    routes: DashMap<String, VecDeque<(Instant, f64)>>,
+}
+
+impl RecentResponseTimes {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record(&self, route: &str, seconds: f64) {
+        let now = Instant::now();
+        if let Some(mut samples) = self.routes.get_mut(route) {
+            if samples.len() >= RESPONSE_TIMES_MAX_SAMPLES {
+                samples.pop_front();
+            }
+            samples.push_back((now, seconds));
+            return;
+        }
+
+        if self.routes.len() >= RESPONSE_TIMES_MAX_ROUTES {
+            // Avoid filling the map with routes of unmatched paths
+            return;
+        }
+        self.routes
+            .entry(route.to_owned())
+            .or_default()
+            .push_back((now, seconds));
+    }
+
+    pub(crate) fn gather(&self, metrics: &Metrics) {
+        let metric = &metrics.response_time_percentiles;
+        self.routes.retain(|route, samples| {
+            while let Some((recorded, _)) = samples.front() {
+                if recorded.elapsed() < RESPONSE_TIMES_WINDOW {
+                    break;
+                }
+                samples.pop_front();
+            }
+
+            let mut times: Vec<f64> = samples.iter().map(|(_, seconds)| *seconds).collect();
+            times.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            for (label, quantile) in RESPONSE_TIME_QUANTILES {
+                match percentile(&times, *quantile) {
+                    Some(value) => metric
+                        .with_label_values(&[route.as_str(), *label])
+                        .set(value),
+                    None => {
+                        let _ = metric.remove_label_values(&[route.as_str(), *label]);
+                    }
+                }
+            }
+
+            !samples.is_empty()
+        });
+    }
+}
+
+/// Nearest-rank percentile of the sorted `values`, `None` when there are none.
+fn percentile(values: &[f64], quantile: f64) -> Option<f64> {
+    let rank = (quantile * values.len() as f64).ceil() as usize;
+    values.get(rank.max(1) - 1).copied()
+}
+
 impl Metrics {
     pub(crate) fn gather(
         &self,
@@ -175,8 +273,15 @@ impl Metrics {
         }
 
         self.failed_crates_count.set(queue.failed_count()? as i64);
+        self.queue_oldest_crate_age.set(
+            queue
+                .oldest_pending_age()?
+                .map_or(0, |age| age.as_secs() as i64),
+        );
+        self.active_builders.set(queue.active_builders()?);
 
         self.recently_accessed_releases.gather(self);
+        self.recent_response_times.gather(self);
         self.gather_system_performance();
         Ok(self.registry.gather())
     }
@@ -195,3 +300,20 @@ impl Metrics {
             .set(process.stat().unwrap().num_threads);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::percentile;
+
+    #[test]
+    fn nearest_rank_percentile() {
+        let values: Vec<f64> = (1..=100).map(f64::from).collect();
+        assert_eq!(percentile(&values, 0.5), Some(50.0));
+        assert_eq!(percentile(&values, 0.9), Some(90.0));
+        assert_eq!(percentile(&values, 0.99), Some(99.0));
+
+        assert_eq!(percentile(&[0.2], 0.5), Some(0.2));
+        assert_eq!(percentile(&[0.2], 0.99), Some(0.2));
+        assert_eq!(percentile(&[], 0.5), None);
+    }
+}
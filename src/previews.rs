@@ -0,0 +1,160 @@
+//! Documentation previews, so maintainers can check their docs before publishing a release.
+//!
+//! A preview is a zip file of the rustdoc output, the contents of `target/doc`. It is stored as
+//! an archive under the `previews/` prefix and served at `/-/preview/:token/` until it expires
+//! after `DOCSRS_PREVIEW_TTL` seconds. Expired previews are deleted by the daemon.
+//!
+//! Previews aren't releases, so they never show up in the sitemap, search or crate lists.
+
+use crate::{
+    storage::{preview_archive_path, Storage},
+    Config,
+};
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Utc};
+use postgres::Client;
+use serde::Serialize;
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+use tracing::{debug, info};
+
+/// An uploaded preview, the token is the only way to find it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct Preview {
+    pub(crate) token: String,
+    pub(crate) expires_at: DateTime<Utc>,
+}
+
+/// Stores the rustdoc output in `zip_content` as a new preview, expiring after `ttl`. Gives up
+/// once `deadline` passed.
+pub(crate) fn create(
+    conn: &mut Client,
+    storage: &Storage,
+    zip_content: &[u8],
+    created_by: &str,
+    ttl: Duration,
+    deadline: Instant,
+) -> Result<Preview> {
+    let mut token = [0u8; 16];
+    getrandom::getrandom(&mut token).context("failed to generate a preview token")?;
+    let token = hex::encode(token);
+
+    storage.store_zip_in_archive(&preview_archive_path(&token), zip_content, deadline)?;
+    let expires_at = conn
+        .query_one(
+            "INSERT INTO doc_previews (token, created_by, expires_at)
+             VALUES ($1, $2, NOW() + make_interval(secs => $3))
+             RETURNING expires_at",
+            &[&token, &created_by, &(ttl.as_secs() as f64)],
+        )?
+        .get(0);
+
+    info!(%token, %created_by, %expires_at, "created documentation preview");
+    Ok(Preview { token, expires_at })
+}
+
+/// Whether the preview exists and didn't expire yet, expired ones might not be deleted yet.
+pub(crate) fn is_available(conn: &mut Client, token: &str) -> Result<bool> {
+    Ok(conn
+        .query_opt(
+            "SELECT 1 FROM doc_previews WHERE token = $1 AND expires_at > NOW()",
+            &[&token],
+        )?
+        .is_some())
+}
+
+/// Deletes the expired previews with their files, returns how many were deleted.
+pub(crate) fn delete_expired(
+    conn: &mut Client,
+    storage: &Storage,
+    config: &Config,
+) -> Result<usize> {
+    let expired = conn.query(
+        "SELECT token FROM doc_previews WHERE expires_at <= NOW()",
+        &[],
+    )?;
+
+    for row in &expired {
+        let token: &str = row.get(0);
+        let archive_path = preview_archive_path(token);
+        storage.delete_prefix(&archive_path)?;
+
+        // the index of the archive is also cached locally
+        let local_index_path = config
+            .local_archive_cache_path
+            .join(format!("{}.index", archive_path));
+        if local_index_path.exists() {
+            fs::remove_file(&local_index_path)
+                .with_context(|| format!("failed to remove local index {:?}", local_index_path))?;
+        }
+
+        conn.execute("DELETE FROM doc_previews WHERE token = $1", &[&token])?;
+        debug!(%token, "deleted expired documentation preview");
+    }
+
+    Ok(expired.len())
+}
+
+/// A zip file of `files` with stored entries, like the uploads of previews.
+#[cfg(test)]
+pub(crate) fn zip_file(files: &[(&str, &[u8])]) -> Vec<u8> {
+    use std::io::Write;
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for (path, content) in files {
+        zip.start_file(*path, options).unwrap();
+        zip.write_all(content).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{create, delete_expired, is_available, zip_file};
+    use crate::{storage::preview_archive_path, test::wrapper};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn expired_previews_are_deleted() {
+        wrapper(|env| {
+            let mut conn = env.db().conn();
+            let storage = env.storage();
+            let zip = zip_file(&[("foo/index.html", &b"<html>foo</html>"[..])]);
+
+            let kept = create(
+                &mut conn,
+                &storage,
+                &zip,
+                "admin",
+                Duration::from_secs(3600),
+                Instant::now() + Duration::from_secs(60),
+            )?;
+            let expired = create(
+                &mut conn,
+                &storage,
+                &zip,
+                "admin",
+                Duration::from_secs(3600),
+                Instant::now() + Duration::from_secs(60),
+            )?;
+            assert_ne!(kept.token, expired.token);
+            conn.execute(
+                "UPDATE doc_previews SET expires_at = NOW() - INTERVAL '1 minute' WHERE token = $1",
+                &[&expired.token],
+            )?;
+            assert!(!is_available(&mut conn, &expired.token)?);
+
+            assert_eq!(delete_expired(&mut conn, &storage, &env.config())?, 1);
+            assert!(!storage.exists(&preview_archive_path(&expired.token))?);
+            assert!(storage.exists(&preview_archive_path(&kept.token))?);
+            assert!(is_available(&mut conn, &kept.token)?);
+
+            assert_eq!(delete_expired(&mut conn, &storage, &env.config())?, 0);
+            Ok(())
+        })
+    }
+}
@@ -16,6 +16,10 @@ pub(crate) struct FileInfo {
     compression: CompressionAlgorithm,
 }
 
+/// Length of a zip local file header without the file name and extra field
+const LOCAL_FILE_HEADER_LEN: u64 = 30;
+const LOCAL_FILE_HEADER_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x03, 0x04];
+
 impl FileInfo {
     pub(crate) fn range(&self) -> FileRange {
         self.range.clone()
@@ -23,6 +27,34 @@ impl FileInfo {
     pub(crate) fn compression(&self) -> CompressionAlgorithm {
         self.compression
     }
+
+    /// The range of the local file header of `path` followed by its data. The archives we create
+    /// don't have extra fields in the local headers.
+    pub(crate) fn range_with_header(&self, path: &str) -> Option<FileRange> {
+        let start = self
+            .range
+            .start()
+            .checked_sub(LOCAL_FILE_HEADER_LEN + path.len() as u64)?;
+        Some(start..=*self.range.end())
+    }
+
+    /// Whether `content`, read from [`FileInfo::range_with_header`], starts with the local file
+    /// header of `path` compressed like this index entry says. It doesn't when the archive was
+    /// replaced after the index was read.
+    pub(crate) fn header_matches(&self, path: &str, content: &[u8]) -> bool {
+        let method: u16 = match self.compression {
+            CompressionAlgorithm::Bzip2 => 12,
+            CompressionAlgorithm::Zstd => 93,
+            CompressionAlgorithm::Gzip => return false,
+        };
+        let header_len = LOCAL_FILE_HEADER_LEN as usize;
+        content.len() >= header_len + path.len()
+            && content[..4] == LOCAL_FILE_HEADER_SIGNATURE
+            && content[8..10] == method.to_le_bytes()
+            && content[26..28] == (path.len() as u16).to_le_bytes()
+            && content[28..30] == [0, 0]
+            && content[header_len..header_len + path.len()] == *path.as_bytes()
+    }
 }
 
 #[derive(Serialize)]
@@ -47,6 +79,7 @@ pub(crate) fn create<R: io::Read + io::Seek, W: io::Write>(
                 range: FileRange::new(zf.data_start(), zf.data_start() + zf.compressed_size() - 1),
                 compression: match zf.compression() {
                     zip::CompressionMethod::Bzip2 => CompressionAlgorithm::Bzip2,
+                    zip::CompressionMethod::Zstd => CompressionAlgorithm::Zstd,
                     c => bail!("unsupported compression algorithm {} in zip-file", c),
                 },
             },
@@ -172,7 +205,7 @@ pub(crate) fn find_in_file<P: AsRef<Path>>(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Write;
+    use std::io::{Read, Seek, Write};
     use zip::write::FileOptions;
 
     #[test]
@@ -198,6 +231,14 @@ mod tests {
         assert_eq!(fi.range, FileRange::new(39, 459));
         assert_eq!(fi.compression, CompressionAlgorithm::Bzip2);
 
+        let range = fi.range_with_header("testfile1").unwrap();
+        assert_eq!(range, FileRange::new(0, 459));
+        tf.seek(io::SeekFrom::Start(*range.start())).unwrap();
+        let mut header = vec![0; 39];
+        tf.read_exact(&mut header).unwrap();
+        assert!(fi.header_matches("testfile1", &header));
+        assert!(!fi.header_matches("testfile2", &header));
+
         assert!(find_in_slice(&buf, "some_other_file").unwrap().is_none());
     }
 }
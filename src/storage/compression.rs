@@ -1,6 +1,7 @@
 use anyhow::Error;
 use bzip2::read::{BzDecoder, BzEncoder};
 use bzip2::Compression;
+use flate2::read::{GzDecoder, GzEncoder};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashSet,
@@ -56,6 +57,7 @@ enum_id! {
     pub enum CompressionAlgorithm {
         Zstd = 0,
         Bzip2 = 1,
+        Gzip = 2,
     }
 }
 
@@ -72,6 +74,13 @@ pub fn compress(content: impl Read, algorithm: CompressionAlgorithm) -> Result<V
         CompressionAlgorithm::Bzip2 => {
             let mut compressor = BzEncoder::new(content, Compression::best());
 
+            let mut data = vec![];
+            compressor.read_to_end(&mut data)?;
+            Ok(data)
+        }
+        CompressionAlgorithm::Gzip => {
+            let mut compressor = GzEncoder::new(content, flate2::Compression::best());
+
             let mut data = vec![];
             compressor.read_to_end(&mut data)?;
             Ok(data)
@@ -92,11 +101,60 @@ pub fn decompress(
         CompressionAlgorithm::Bzip2 => {
             io::copy(&mut BzDecoder::new(content), &mut buffer)?;
         }
+        CompressionAlgorithm::Gzip => {
+            io::copy(&mut GzDecoder::new(content), &mut buffer)?;
+        }
     }
 
     Ok(buffer.into_inner())
 }
 
+/// Decompresses `content` with `algorithm` while it's read, or passes it through without one.
+///
+/// Like [`decompress`], reading fails once more than `max_size` bytes came out.
+pub(crate) fn decompressing_reader<'a>(
+    content: impl Read + 'a,
+    algorithm: Option<CompressionAlgorithm>,
+    max_size: usize,
+) -> Result<impl Read + 'a, Error> {
+    let inner: Box<dyn Read + 'a> = match algorithm {
+        Some(CompressionAlgorithm::Zstd) => Box::new(zstd::stream::read::Decoder::new(content)?),
+        Some(CompressionAlgorithm::Bzip2) => Box::new(BzDecoder::new(content)),
+        Some(CompressionAlgorithm::Gzip) => Box::new(GzDecoder::new(content)),
+        None => Box::new(content),
+    };
+    Ok(SizedReader {
+        inner,
+        remaining: max_size,
+    })
+}
+
+/// Fails reads once more than the limit was read, the reading counterpart of
+/// [`SizedBuffer`](crate::utils::sized_buffer::SizedBuffer).
+struct SizedReader<R> {
+    inner: R,
+    remaining: usize,
+}
+
+impl<R: Read> Read for SizedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.remaining = self
+            .remaining
+            .checked_sub(read)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, crate::error::SizeLimitReached))?;
+        Ok(read)
+    }
+}
+
+/// Magic bytes every gzip stream starts with, see RFC 1952.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Returns whether `content` looks like a gzip stream.
+pub(crate) fn is_gzip(content: &[u8]) -> bool {
+    content.starts_with(&GZIP_MAGIC)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -115,6 +173,49 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_decompressing_reader() {
+        const MAX_SIZE: usize = 1024;
+        let content = &[b'A'; MAX_SIZE] as &[u8];
+
+        let mut algorithms: Vec<_> = CompressionAlgorithm::AVAILABLE
+            .iter()
+            .copied()
+            .map(Some)
+            .collect();
+        algorithms.push(None);
+        for alg in algorithms {
+            let compressed = match alg {
+                Some(alg) => compress(content, alg).unwrap(),
+                None => content.to_vec(),
+            };
+
+            let mut read = Vec::new();
+            decompressing_reader(compressed.as_slice(), alg, MAX_SIZE)
+                .unwrap()
+                .read_to_end(&mut read)
+                .unwrap();
+            assert_eq!(read, content);
+
+            let err = decompressing_reader(compressed.as_slice(), alg, MAX_SIZE - 1)
+                .unwrap()
+                .read_to_end(&mut Vec::new())
+                .unwrap_err();
+            assert!(err
+                .get_ref()
+                .and_then(|err| err.downcast_ref::<crate::error::SizeLimitReached>())
+                .is_some());
+        }
+    }
+
+    #[test]
+    fn test_gzip_magic() {
+        let data = compress("some build log".as_bytes(), CompressionAlgorithm::Gzip).unwrap();
+        assert!(is_gzip(&data));
+        assert!(!is_gzip(b"some build log"));
+        assert!(!is_gzip(b""));
+    }
+
     #[test]
     fn test_decompression_too_big() {
         const MAX_SIZE: usize = 1024;
@@ -1,4 +1,4 @@
-use super::{Blob, FileRange, StorageTransaction};
+use super::{Blob, FileMetadata, FileRange, StorageTransaction};
 use crate::db::Pool;
 use crate::error::Result;
 use crate::Metrics;
@@ -21,6 +21,31 @@ impl DatabaseBackend {
         Ok(conn.query(query, &[&path])?[0].get(0))
     }
 
+    pub(super) fn get_metadata(&self, path: &str) -> Result<FileMetadata> {
+        match self.pool.get()?.query_opt(
+            "SELECT LENGTH(content)::BIGINT, date_updated
+             FROM files
+             WHERE path = $1",
+            &[&path],
+        )? {
+            Some(row) => Ok(FileMetadata {
+                size: row.get::<_, i64>(0).try_into()?,
+                date_updated: row.get(1),
+            }),
+            None => Err(super::PathNotFoundError.into()),
+        }
+    }
+
+    /// Size of the `files` table on disk, including its indexes and TOAST data.
+    pub(super) fn approximate_size(&self) -> Result<u64> {
+        let size: i64 = self
+            .pool
+            .get()?
+            .query_one("SELECT pg_total_relation_size('files')", &[])?
+            .get(0);
+        Ok(size.try_into()?)
+    }
+
     pub(super) fn get_public_access(&self, path: &str) -> Result<bool> {
         match self.pool.get()?.query_opt(
             "SELECT public 
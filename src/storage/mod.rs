@@ -3,6 +3,8 @@ mod compression;
 mod database;
 mod s3;
 
+use self::compression::decompressing_reader;
+use self::compression::is_gzip;
 pub use self::compression::{compress, decompress, CompressionAlgorithm, CompressionAlgorithms};
 use self::database::DatabaseBackend;
 use self::s3::S3Backend;
@@ -19,18 +21,31 @@ use std::{
     io::{self, Write},
     ops::RangeInclusive,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 use tokio::runtime::Runtime;
+use tracing::{instrument, warn};
 
 const MAX_CONCURRENT_UPLOADS: usize = 1000;
 
-type FileRange = RangeInclusive<u64>;
+pub(crate) type FileRange = RangeInclusive<u64>;
 
 #[derive(Debug, thiserror::Error)]
 #[error("path not found")]
 pub(crate) struct PathNotFoundError;
 
+/// The cached index of an archive doesn't match the archive anymore, it was replaced since.
+#[derive(Debug, thiserror::Error)]
+#[error("stale archive index")]
+struct StaleArchiveIndexError;
+
+/// Attached as context to backend errors which might go away when retrying, like timeouts or
+/// server errors.
+#[derive(Debug, thiserror::Error)]
+#[error("transient storage error")]
+pub(crate) struct TransientStorageError;
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub(crate) struct Blob {
     pub(crate) path: String,
@@ -40,10 +55,23 @@ pub(crate) struct Blob {
     pub(crate) compression: Option<CompressionAlgorithm>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct FileMetadata {
+    /// The size of the file as it's stored, after compression
+    pub(crate) size: u64,
+    pub(crate) date_updated: DateTime<Utc>,
+}
+
 impl Blob {
     pub(crate) fn is_empty(&self) -> bool {
         self.mime == "application/x-empty"
     }
+
+    /// Reads the content, decompressing it while it's read, and fails once more than `max_size`
+    /// bytes were read.
+    pub(crate) fn decompressing_reader(&self, max_size: usize) -> Result<impl io::Read + '_> {
+        decompressing_reader(self.content.as_slice(), self.compression, max_size)
+    }
 }
 
 fn get_file_list_from_dir<P: AsRef<Path>>(path: P, files: &mut Vec<PathBuf>) -> Result<()> {
@@ -108,9 +136,44 @@ enum StorageBackend {
     S3(Box<S3Backend>),
 }
 
+/// The reads of a storage backend, which are all a fallback storage has to support.
+trait ReadBackend: Send + Sync {
+    fn exists(&self, path: &str) -> Result<bool>;
+    fn get(&self, path: &str, max_size: usize, range: Option<FileRange>) -> Result<Blob>;
+    fn get_metadata(&self, path: &str) -> Result<FileMetadata>;
+}
+
+impl ReadBackend for StorageBackend {
+    fn exists(&self, path: &str) -> Result<bool> {
+        match self {
+            StorageBackend::Database(db) => db.exists(path),
+            StorageBackend::S3(s3) => s3.exists(path),
+        }
+    }
+
+    fn get(&self, path: &str, max_size: usize, range: Option<FileRange>) -> Result<Blob> {
+        match self {
+            StorageBackend::Database(db) => db.get(path, max_size, range),
+            StorageBackend::S3(s3) => s3.get(path, max_size, range),
+        }
+    }
+
+    fn get_metadata(&self, path: &str) -> Result<FileMetadata> {
+        match self {
+            StorageBackend::Database(db) => db.get_metadata(path),
+            StorageBackend::S3(s3) => s3.get_metadata(path),
+        }
+    }
+}
+
 pub struct Storage {
     backend: StorageBackend,
+    /// Read-only storage used when reading from `backend` fails
+    fallback: Option<Box<dyn ReadBackend>>,
+    /// Skips `backend` while it keeps failing, only used when there is a fallback
+    circuit_breaker: CircuitBreaker,
     config: Arc<Config>,
+    metrics: Arc<Metrics>,
 }
 
 impl Storage {
@@ -124,19 +187,66 @@ impl Storage {
             config: config.clone(),
             backend: match config.storage_backend {
                 StorageKind::Database => {
-                    StorageBackend::Database(DatabaseBackend::new(pool, metrics))
-                }
-                StorageKind::S3 => {
-                    StorageBackend::S3(Box::new(S3Backend::new(metrics, &config, runtime)?))
+                    StorageBackend::Database(DatabaseBackend::new(pool.clone(), metrics.clone()))
                 }
+                StorageKind::S3 => StorageBackend::S3(Box::new(S3Backend::new(
+                    metrics.clone(),
+                    &config,
+                    runtime.clone(),
+                )?)),
             },
+            fallback: match config.storage_fallback_backend {
+                Some(StorageKind::Database) => Some(Box::new(StorageBackend::Database(
+                    DatabaseBackend::new(pool, metrics.clone()),
+                ))),
+                Some(StorageKind::S3) => Some(Box::new(StorageBackend::S3(Box::new(
+                    S3Backend::fallback(metrics.clone(), &config, runtime)?,
+                )))),
+                None => None,
+            },
+            circuit_breaker: CircuitBreaker::default(),
+            metrics,
         })
     }
 
+    /// Runs the read `f` on the primary backend, retrying transient errors, and once on the
+    /// fallback backend when it still failed, see [`read_with_fallback`]. Writes never go to the
+    /// fallback backend.
+    fn read<T>(&self, f: impl Fn(&dyn ReadBackend) -> Result<T>) -> Result<T> {
+        read_with_fallback(
+            &self.backend,
+            self.fallback.as_deref(),
+            &self.circuit_breaker,
+            |primary| {
+                retry_transient_errors(
+                    self.config.storage_read_retries,
+                    Duration::from_millis(self.config.storage_read_retry_backoff),
+                    &self.metrics,
+                    || f(primary),
+                )
+            },
+            &f,
+            &self.metrics,
+        )
+    }
+
+    #[instrument(skip(self))]
     pub(crate) fn exists(&self, path: &str) -> Result<bool> {
+        self.read(|backend| backend.exists(path))
+    }
+
+    /// The size and modification date of a stored file, without reading it.
+    #[instrument(skip(self))]
+    pub(crate) fn get_metadata(&self, path: &str) -> Result<FileMetadata> {
+        self.read(|backend| backend.get_metadata(path))
+    }
+
+    /// Returns roughly how many bytes the stored files use, or `None` when the backend can't
+    /// tell cheaply. S3 would require listing the whole bucket.
+    pub(crate) fn approximate_size(&self) -> Result<Option<u64>> {
         match &self.backend {
-            StorageBackend::Database(db) => db.exists(path),
-            StorageBackend::S3(s3) => s3.exists(path),
+            StorageBackend::Database(db) => db.approximate_size().map(Some),
+            StorageBackend::S3(_) => Ok(None),
         }
     }
 
@@ -162,29 +272,72 @@ impl Storage {
         }
     }
 
+    /// Decompresses a file read from the backend, recording how long it took.
+    fn decompress(
+        &self,
+        content: &[u8],
+        alg: CompressionAlgorithm,
+        max_size: usize,
+    ) -> Result<Vec<u8>> {
+        let start = Instant::now();
+        let content = decompress(content, alg, max_size)?;
+        self.metrics
+            .storage_decompression_times
+            .with_label_values(&[&alg.to_string()])
+            .observe(start.elapsed().as_secs_f64());
+        Ok(content)
+    }
+
+    #[instrument(skip(self, fetch_time))]
     pub(crate) fn fetch_rustdoc_file(
         &self,
         name: &str,
         version: &str,
         path: &str,
         archive_storage: bool,
-        fetch_time: &mut RenderingTimesRecorder,
+        fetch_time: Option<&mut RenderingTimesRecorder>,
     ) -> Result<Blob> {
         Ok(if archive_storage {
             self.get_from_archive(
                 &rustdoc_archive_path(name, version),
                 path,
                 self.max_file_size_for(path),
-                Some(fetch_time),
+                fetch_time,
             )?
         } else {
-            fetch_time.step("fetch from storage");
+            if let Some(fetch_time) = fetch_time {
+                fetch_time.step("fetch from storage");
+            }
             // Add rustdoc prefix, name and version to the path for accessing the file stored in the database
             let remote_path = format!("rustdoc/{}/{}/{}", name, version, path);
             self.get(&remote_path, self.max_file_size_for(path))?
         })
     }
 
+    /// Fetches a rustdoc file like [`Storage::fetch_rustdoc_file`], but still compressed, so it can
+    /// be decompressed while it's read with [`Blob::decompressing_reader`] instead of being held
+    /// in memory as a whole.
+    pub(crate) fn fetch_compressed_rustdoc_file(
+        &self,
+        name: &str,
+        version: &str,
+        path: &str,
+        archive_storage: bool,
+    ) -> Result<Blob> {
+        if archive_storage {
+            self.get_compressed_from_archive(
+                &rustdoc_archive_path(name, version),
+                path,
+                self.max_file_size_for(path),
+                None,
+            )
+        } else {
+            let remote_path = format!("rustdoc/{}/{}/{}", name, version, path);
+            self.read(|backend| backend.get(&remote_path, self.max_file_size_for(path), None))
+        }
+    }
+
+    #[instrument(skip(self))]
     pub(crate) fn fetch_source_file(
         &self,
         name: &str,
@@ -205,6 +358,24 @@ impl Storage {
         })
     }
 
+    pub(crate) fn fetch_book_file(&self, name: &str, version: &str, path: &str) -> Result<Blob> {
+        self.get_from_archive(
+            &book_archive_path(name, version),
+            path,
+            self.max_file_size_for(path),
+            None,
+        )
+    }
+
+    pub(crate) fn fetch_preview_file(&self, token: &str, path: &str) -> Result<Blob> {
+        self.get_from_archive(
+            &preview_archive_path(token),
+            path,
+            self.max_file_size_for(path),
+            None,
+        )
+    }
+
     pub(crate) fn rustdoc_file_exists(
         &self,
         name: &str,
@@ -221,6 +392,7 @@ impl Storage {
         })
     }
 
+    #[instrument(skip(self))]
     pub(crate) fn exists_in_archive(&self, archive_path: &str, path: &str) -> Result<bool> {
         match self.get_index_filename(archive_path) {
             Ok(index_filename) => Ok(archive_index::find_in_file(index_filename, path)?.is_some()),
@@ -234,34 +406,30 @@ impl Storage {
         }
     }
 
+    #[instrument(skip(self))]
     pub(crate) fn get(&self, path: &str, max_size: usize) -> Result<Blob> {
-        let mut blob = match &self.backend {
-            StorageBackend::Database(db) => db.get(path, max_size, None),
-            StorageBackend::S3(s3) => s3.get(path, max_size, None),
-        }?;
+        let mut blob = self.read(|backend| backend.get(path, max_size, None))?;
         if let Some(alg) = blob.compression {
-            blob.content = decompress(blob.content.as_slice(), alg, max_size)?;
+            blob.content = self.decompress(blob.content.as_slice(), alg, max_size)?;
             blob.compression = None;
         }
         Ok(blob)
     }
 
-    pub(super) fn get_range(
+    #[instrument(skip(self))]
+    pub(crate) fn get_range(
         &self,
         path: &str,
         max_size: usize,
         range: FileRange,
         compression: Option<CompressionAlgorithm>,
     ) -> Result<Blob> {
-        let mut blob = match &self.backend {
-            StorageBackend::Database(db) => db.get(path, max_size, Some(range)),
-            StorageBackend::S3(s3) => s3.get(path, max_size, Some(range)),
-        }?;
+        let mut blob = self.read(|backend| backend.get(path, max_size, Some(range.clone())))?;
         // `compression` represents the compression of the file-stream inside the archive.
         // We don't compress the whole archive, so the encoding of the archive's blob is irrelevant
         // here.
         if let Some(alg) = compression {
-            blob.content = decompress(blob.content.as_slice(), alg, max_size)?;
+            blob.content = self.decompress(blob.content.as_slice(), alg, max_size)?;
             blob.compression = None;
         }
         Ok(blob)
@@ -290,36 +458,97 @@ impl Storage {
         Ok(local_index_path)
     }
 
+    #[instrument(skip(self, fetch_time))]
     pub(crate) fn get_from_archive(
         &self,
         archive_path: &str,
         path: &str,
         max_size: usize,
-        mut fetch_time: Option<&mut RenderingTimesRecorder>,
+        fetch_time: Option<&mut RenderingTimesRecorder>,
     ) -> Result<Blob> {
-        if let Some(ref mut t) = fetch_time {
-            t.step("find path in index");
+        let mut blob =
+            self.get_compressed_from_archive(archive_path, path, max_size, fetch_time)?;
+        if let Some(alg) = blob.compression {
+            blob.content = self.decompress(&blob.content, alg, max_size)?;
+            blob.compression = None;
         }
-        let info = archive_index::find_in_file(self.get_index_filename(archive_path)?, path)?
-            .ok_or(PathNotFoundError)?;
+        Ok(blob)
+    }
 
-        if let Some(t) = fetch_time {
-            t.step("range request");
-        }
-        let blob = self.get_range(
+    /// Reads `path` from the archive like [`Storage::get_from_archive`], but leaves it compressed.
+    fn get_compressed_from_archive(
+        &self,
+        archive_path: &str,
+        path: &str,
+        max_size: usize,
+        mut fetch_time: Option<&mut RenderingTimesRecorder>,
+    ) -> Result<Blob> {
+        let index_filename = self.get_index_filename(archive_path)?;
+        let blob = match self.get_with_index(
             archive_path,
+            &index_filename,
+            path,
             max_size,
-            info.range(),
-            Some(info.compression()),
-        )?;
-        assert_eq!(blob.compression, None);
+            fetch_time.as_deref_mut(),
+        ) {
+            Err(err) if err.is::<StaleArchiveIndexError>() => {
+                // the archive was stored again since we cached its index, e.g. when it was
+                // recompressed, fetch the new index
+                warn!(%archive_path, "cached archive index is stale");
+                fs::remove_file(&index_filename)?;
+                let index_filename = self.get_index_filename(archive_path)?;
+                self.get_with_index(archive_path, &index_filename, path, max_size, fetch_time)?
+            }
+            result => result?,
+        };
 
         Ok(Blob {
             path: format!("{}/{}", archive_path, path),
             mime: detect_mime(path).into(),
             date_updated: blob.date_updated,
             content: blob.content,
-            compression: None,
+            compression: blob.compression,
+        })
+    }
+
+    /// Reads the still compressed `path` from the archive with the ranges in the local
+    /// `index_filename`.
+    ///
+    /// The local file header in front of the file is read too and has to match the index,
+    /// otherwise this fails with a [`StaleArchiveIndexError`].
+    fn get_with_index(
+        &self,
+        archive_path: &str,
+        index_filename: &Path,
+        path: &str,
+        max_size: usize,
+        mut fetch_time: Option<&mut RenderingTimesRecorder>,
+    ) -> Result<Blob> {
+        if let Some(ref mut t) = fetch_time {
+            t.step("find path in index");
+        }
+        let info = archive_index::find_in_file(index_filename, path)?.ok_or(PathNotFoundError)?;
+        let range = info.range_with_header(path).ok_or(StaleArchiveIndexError)?;
+        let header_len = (info.range().start() - range.start()) as usize;
+
+        if let Some(t) = fetch_time {
+            t.step("range request");
+        }
+        let blob = self.read(|backend| {
+            backend.get(
+                archive_path,
+                max_size.saturating_add(header_len),
+                Some(range.clone()),
+            )
+        })?;
+        if !info.header_matches(path, &blob.content) {
+            return Err(StaleArchiveIndexError.into());
+        }
+
+        Ok(Blob {
+            content: blob.content[header_len..].to_vec(),
+            compression: Some(info.compression()),
+            ..blob
         })
     }
 
@@ -327,7 +556,7 @@ impl Storage {
         &self,
         archive_path: &str,
         root_dir: &Path,
-    ) -> Result<(HashMap<PathBuf, String>, CompressionAlgorithm)> {
+    ) -> Result<(HashMap<PathBuf, String>, CompressionAlgorithms)> {
         let mut file_paths = HashMap::new();
 
         // We are only using the `zip` library to create the archives and the matching
@@ -339,23 +568,134 @@ impl Storage {
         // For decompression we are sharing the compression algorithms defined in
         // `storage::compression`. So every new algorithm to be used inside ZIP archives
         // also has to be added as supported algorithm for storage compression, together
-        // with a mapping in `storage::archive_index::Index::new_from_zip`.
-
-        let options =
-            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Bzip2);
+        // with a mapping in `storage::archive_index::create`.
+        let mut algs = HashSet::new();
 
         let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
         for file_path in get_file_list(root_dir)? {
             let mut file = fs::File::open(root_dir.join(&file_path))?;
 
-            zip.start_file(file_path.to_str().unwrap(), options)?;
+            let file_name = file_path.to_str().unwrap();
+            let (method, alg) = archive_compression_for(file_name);
+            zip.start_file(
+                file_name,
+                zip::write::FileOptions::default().compression_method(method),
+            )?;
             io::copy(&mut file, &mut zip)?;
+            algs.insert(alg);
 
             let mime = detect_mime(&file_path);
             file_paths.insert(file_path, mime.to_string());
         }
 
-        let mut zip_content = zip.finish()?.into_inner();
+        self.store_archive(archive_path, zip.finish()?.into_inner())?;
+
+        Ok((file_paths, algs))
+    }
+
+    /// Stores the files of an uploaded zip file as archive, compressed again with the algorithm
+    /// the archive index supports.
+    ///
+    /// Only stored, bzip2 and zstd compressed entries can be read, entries larger than the
+    /// `max_file_size` or outside of the archive root are rejected. Gives up once `deadline`
+    /// passed.
+    pub(crate) fn store_zip_in_archive(
+        &self,
+        archive_path: &str,
+        zip_content: &[u8],
+        deadline: Instant,
+    ) -> Result<()> {
+        let mut uploaded = zip::ZipArchive::new(io::Cursor::new(zip_content))?;
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        for i in 0..uploaded.len() {
+            ensure!(
+                Instant::now() < deadline,
+                "gave up recompressing {} after the deadline",
+                archive_path
+            );
+            let file = uploaded.by_index(i)?;
+            if file.is_dir() {
+                continue;
+            }
+            let file_path = file
+                .enclosed_name()
+                .and_then(|path| path.to_str())
+                .map(str::to_owned)
+                .ok_or_else(|| anyhow!("invalid path {:?} in zip file", file.name()))?;
+
+            let max_size = self.max_file_size_for(&file_path);
+            let (method, _) = archive_compression_for(&file_path);
+            zip.start_file(
+                &file_path,
+                zip::write::FileOptions::default().compression_method(method),
+            )?;
+            let copied = io::copy(&mut file.take(max_size as u64 + 1), &mut zip)?;
+            ensure!(
+                copied <= max_size as u64,
+                "{} is larger than the limit of {} bytes",
+                file_path,
+                max_size
+            );
+        }
+
+        ensure!(
+            Instant::now() < deadline,
+            "gave up recompressing {} after the deadline",
+            archive_path
+        );
+        self.store_archive(archive_path, zip.finish()?.into_inner())
+    }
+
+    /// Re-stores an archive with every entry compressed like [`archive_compression_for`] picks for
+    /// new archives, if any of them isn't. Entries that are compressed right already are copied
+    /// without decompressing them.
+    ///
+    /// Returns `true` if the archive was recompressed.
+    pub(crate) fn recompress_archive(&self, archive_path: &str) -> Result<bool> {
+        let content = self.get(archive_path, std::usize::MAX)?.content;
+        let mut archive = zip::ZipArchive::new(io::Cursor::new(content))?;
+
+        let mut outdated = false;
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            if file.compression() != archive_compression_for(file.name()).0 {
+                outdated = true;
+                break;
+            }
+        }
+        if !outdated {
+            return Ok(false);
+        }
+
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            let (method, _) = archive_compression_for(file.name());
+            if file.compression() == method {
+                zip.raw_copy_file(file)?;
+                continue;
+            }
+            drop(file);
+
+            let mut file = archive.by_index(i)?;
+            zip.start_file(
+                file.name().to_owned(),
+                zip::write::FileOptions::default().compression_method(method),
+            )?;
+            io::copy(&mut file, &mut zip)?;
+        }
+
+        // uploading the archive again would reset its visibility
+        let public = self.get_public_access(archive_path)?;
+        self.store_archive(archive_path, zip.finish()?.into_inner())?;
+        if public {
+            self.set_public_access(archive_path, true)?;
+        }
+        Ok(true)
+    }
+
+    /// Stores a zip file created with bzip2 or zstd compressed entries with its index.
+    pub(crate) fn store_archive(&self, archive_path: &str, mut zip_content: Vec<u8>) -> Result<()> {
         let mut index_content = vec![];
         archive_index::create(&mut io::Cursor::new(&mut zip_content), &mut index_content)?;
         let alg = CompressionAlgorithm::default();
@@ -394,10 +734,7 @@ impl Storage {
             ]
             .into_iter()
             .map(Ok),
-        )?;
-
-        let file_alg = CompressionAlgorithm::Bzip2;
-        Ok((file_paths, file_alg))
+        )
     }
 
     fn transaction<T, F>(&self, f: F) -> Result<T>
@@ -473,10 +810,18 @@ impl Storage {
         &self,
         path: impl Into<String>,
         content: impl Into<Vec<u8>>,
+    ) -> Result<CompressionAlgorithm> {
+        self.store_one_with_compression(path, content, CompressionAlgorithm::default())
+    }
+
+    fn store_one_with_compression(
+        &self,
+        path: impl Into<String>,
+        content: impl Into<Vec<u8>>,
+        alg: CompressionAlgorithm,
     ) -> Result<CompressionAlgorithm> {
         let path = path.into();
         let content = content.into();
-        let alg = CompressionAlgorithm::default();
         let content = compress(&*content, alg)?;
         let mime = detect_mime(&path).to_owned();
 
@@ -509,6 +854,54 @@ impl Storage {
         })
     }
 
+    /// Store a build log. Build logs compress very well, so they are always stored gzip-compressed.
+    pub(crate) fn store_build_log(
+        &self,
+        build_id: i32,
+        target: &str,
+        content: impl Into<Vec<u8>>,
+    ) -> Result<()> {
+        self.store_one_with_compression(
+            build_log_path(build_id, target),
+            content,
+            CompressionAlgorithm::Gzip,
+        )?;
+        Ok(())
+    }
+
+    /// Fetch a build log, transparently decompressing it.
+    ///
+    /// Older logs were either stored with the default compression or as plain
+    /// text, and some were uploaded gzip-compressed without the compression metadata.
+    /// The latter are detected by looking at the gzip magic bytes.
+    pub(crate) fn get_build_log(&self, build_id: i32, target: &str) -> Result<String> {
+        let max_size = self.config.max_file_size;
+        let mut blob = self.get(&build_log_path(build_id, target), max_size)?;
+        if is_gzip(&blob.content) {
+            blob.content = decompress(
+                blob.content.as_slice(),
+                CompressionAlgorithm::Gzip,
+                max_size,
+            )?;
+        }
+        Ok(String::from_utf8(blob.content)?)
+    }
+
+    /// Re-store a build log gzip-compressed if it isn't already.
+    ///
+    /// Returns `true` if the log was recompressed.
+    pub(crate) fn recompress_build_log(&self, build_id: i32, target: &str) -> Result<bool> {
+        let path = build_log_path(build_id, target);
+        let raw = self.backend.get(&path, self.config.max_file_size, None)?;
+        if raw.compression == Some(CompressionAlgorithm::Gzip) {
+            return Ok(false);
+        }
+
+        let content = self.get_build_log(build_id, target)?;
+        self.store_build_log(build_id, target, content)?;
+        Ok(true)
+    }
+
     pub(crate) fn delete_prefix(&self, prefix: &str) -> Result<()> {
         self.transaction(|trans| trans.delete_prefix(prefix))
     }
@@ -540,6 +933,147 @@ trait StorageTransaction {
     fn complete(self: Box<Self>) -> Result<()>;
 }
 
+/// Longest wait between two retries of a storage read, the waits block a thread of the blocking
+/// pool
+const MAX_READ_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// The wait before the retry after `attempt` failed reads, starting with `backoff` and doubling
+/// with every further retry, up to [`MAX_READ_RETRY_DELAY`].
+fn read_retry_delay(backoff: Duration, attempt: u32) -> Duration {
+    backoff
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_READ_RETRY_DELAY)
+}
+
+/// Calls `f` until it succeeds or fails with an error other than a [`TransientStorageError`],
+/// retrying at most `retries` times, waiting for [`read_retry_delay`] in between.
+///
+/// This is the only place storage reads are retried, the backends don't retry reads on their
+/// own.
+fn retry_transient_errors<T>(
+    retries: u32,
+    backoff: Duration,
+    metrics: &Metrics,
+    mut f: impl FnMut() -> Result<T>,
+) -> Result<T> {
+    let mut attempt = 0;
+    loop {
+        match f() {
+            Err(err)
+                if attempt < retries && err.downcast_ref::<TransientStorageError>().is_some() =>
+            {
+                let delay = read_retry_delay(backoff, attempt);
+                warn!(
+                    ?err,
+                    attempt,
+                    ?delay,
+                    "retrying storage read after a transient error"
+                );
+                metrics.storage_read_retries.inc();
+                std::thread::sleep(delay);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Reads with `read_primary` from the `primary` storage, and once with `read` from the
+/// `fallback` storage if there is one and `read_primary` failed, after all its retries. Missing
+/// paths are definitive, a read-only replica can't know about any more files than the primary
+/// storage.
+///
+/// While the `circuit_breaker` is open the primary storage is skipped and only the fallback is
+/// read. Without a fallback, the primary storage is always read.
+fn read_with_fallback<T>(
+    primary: &dyn ReadBackend,
+    fallback: Option<&dyn ReadBackend>,
+    circuit_breaker: &CircuitBreaker,
+    read_primary: impl FnOnce(&dyn ReadBackend) -> Result<T>,
+    read: impl FnOnce(&dyn ReadBackend) -> Result<T>,
+    metrics: &Metrics,
+) -> Result<T> {
+    let fallback = match fallback {
+        Some(fallback) => fallback,
+        None => return read_primary(primary),
+    };
+    if circuit_breaker.is_open() {
+        metrics.storage_fallback_reads.inc();
+        return read(fallback);
+    }
+
+    match read_primary(primary) {
+        Err(err) if err.downcast_ref::<PathNotFoundError>().is_none() => {
+            warn!(
+                ?err,
+                "storage read failed, reading from the fallback storage"
+            );
+            circuit_breaker.record_failure();
+            metrics.storage_fallback_reads.inc();
+            read(fallback)
+        }
+        result => {
+            circuit_breaker.record_success();
+            result
+        }
+    }
+}
+
+/// Number of reads in a row that have to fail before the [`CircuitBreaker`] opens
+const CIRCUIT_BREAKER_FAILURES: u32 = 5;
+/// How long an open [`CircuitBreaker`] skips the primary storage
+const CIRCUIT_BREAKER_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Skips the primary storage for [`CIRCUIT_BREAKER_COOLDOWN`] after
+/// [`CIRCUIT_BREAKER_FAILURES`] reads in a row failed, so reads don't wait for all retries of a
+/// storage that's down before reaching the fallback. The first read after the cooldown tries the
+/// primary storage again, and opens the breaker again right away when it still fails.
+#[derive(Debug, Default)]
+struct CircuitBreaker {
+    state: Mutex<CircuitBreakerState>,
+}
+
+#[derive(Debug, Default)]
+struct CircuitBreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    fn is_open(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state
+            .open_until
+            .map_or(false, |open_until| Instant::now() < open_until)
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.failures = state.failures.saturating_add(1);
+        if state.failures >= CIRCUIT_BREAKER_FAILURES {
+            warn!(
+                failures = state.failures,
+                "primary storage keeps failing, only reading from the fallback storage for a while"
+            );
+            state.open_until = Some(Instant::now() + CIRCUIT_BREAKER_COOLDOWN);
+        }
+    }
+
+    fn record_success(&self) {
+        *self.state.lock().unwrap() = CircuitBreakerState::default();
+    }
+}
+
+/// The compression of a file in a new archive. HTML pages are the bulk of the documentation and
+/// read on every request, zstd decompresses them a lot faster than bzip2.
+fn archive_compression_for(path: &str) -> (zip::CompressionMethod, CompressionAlgorithm) {
+    if path.ends_with(".html") {
+        (zip::CompressionMethod::Zstd, CompressionAlgorithm::Zstd)
+    } else {
+        (zip::CompressionMethod::Bzip2, CompressionAlgorithm::Bzip2)
+    }
+}
+
 fn detect_mime(file_path: impl AsRef<Path>) -> &'static str {
     let mime = mime_guess::from_path(file_path.as_ref())
         .first_raw()
@@ -566,6 +1100,18 @@ pub(crate) fn rustdoc_archive_path(name: &str, version: &str) -> String {
     format!("rustdoc/{0}/{1}.zip", name, version)
 }
 
+pub(crate) fn book_archive_path(name: &str, version: &str) -> String {
+    format!("book/{0}/{1}.zip", name, version)
+}
+
+pub(crate) fn preview_archive_path(token: &str) -> String {
+    format!("previews/{}.zip", token)
+}
+
+pub(crate) fn build_log_path(build_id: i32, target: &str) -> String {
+    format!("build-logs/{}/{}.txt", build_id, target)
+}
+
 pub(crate) fn source_archive_path(name: &str, version: &str) -> String {
     format!("sources/{0}/{1}.zip", name, version)
 }
@@ -605,6 +1151,229 @@ mod test {
         let detected_mime = detect_mime(Path::new(&path));
         assert_eq!(detected_mime, expected_mime);
     }
+
+    #[test]
+    fn retry_transient_storage_errors() {
+        let metrics = Metrics::new().unwrap();
+        let mut attempts = 0;
+        let result = retry_transient_errors(3, Duration::from_millis(1), &metrics, || {
+            attempts += 1;
+            if attempts <= 2 {
+                Err(anyhow!("timed out").context(TransientStorageError))
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(metrics.storage_read_retries.get(), 2);
+    }
+
+    #[test]
+    fn retry_transient_storage_errors_gives_up() {
+        let metrics = Metrics::new().unwrap();
+        let mut attempts = 0;
+        let result: Result<()> =
+            retry_transient_errors(2, Duration::from_millis(1), &metrics, || {
+                attempts += 1;
+                Err(anyhow!("timed out").context(TransientStorageError))
+            });
+        assert!(result
+            .unwrap_err()
+            .downcast_ref::<TransientStorageError>()
+            .is_some());
+        assert_eq!(attempts, 3);
+        assert_eq!(metrics.storage_read_retries.get(), 2);
+    }
+
+    #[test]
+    fn read_retry_delay_is_bounded() {
+        let backoff = Duration::from_millis(100);
+        assert_eq!(read_retry_delay(backoff, 0), backoff);
+        assert_eq!(read_retry_delay(backoff, 2), Duration::from_millis(400));
+        assert_eq!(read_retry_delay(backoff, 4), MAX_READ_RETRY_DELAY);
+        assert_eq!(read_retry_delay(backoff, u32::MAX), MAX_READ_RETRY_DELAY);
+    }
+
+    #[test]
+    fn dont_retry_missing_paths() {
+        let metrics = Metrics::new().unwrap();
+        let mut attempts = 0;
+        let result: Result<()> =
+            retry_transient_errors(2, Duration::from_millis(1), &metrics, || {
+                attempts += 1;
+                Err(PathNotFoundError.into())
+            });
+        assert!(result.unwrap_err().is::<PathNotFoundError>());
+        assert_eq!(attempts, 1);
+        assert_eq!(metrics.storage_read_retries.get(), 0);
+    }
+
+    /// A storage where every read of `exists` fails or finds the file
+    #[derive(Default)]
+    struct MockBackend {
+        reads: std::sync::atomic::AtomicUsize,
+        failing: bool,
+    }
+
+    impl MockBackend {
+        fn failing() -> Self {
+            MockBackend {
+                failing: true,
+                ..Default::default()
+            }
+        }
+
+        fn reads(&self) -> usize {
+            self.reads.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    impl ReadBackend for MockBackend {
+        fn exists(&self, _path: &str) -> Result<bool> {
+            self.reads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if self.failing {
+                Err(anyhow!("connection refused").context(TransientStorageError))
+            } else {
+                Ok(true)
+            }
+        }
+
+        fn get(&self, _path: &str, _max_size: usize, _range: Option<FileRange>) -> Result<Blob> {
+            unimplemented!()
+        }
+
+        fn get_metadata(&self, _path: &str) -> Result<FileMetadata> {
+            unimplemented!()
+        }
+    }
+
+    fn read_exists(
+        primary: &MockBackend,
+        fallback: Option<&MockBackend>,
+        circuit_breaker: &CircuitBreaker,
+        metrics: &Metrics,
+    ) -> Result<bool> {
+        read_with_fallback(
+            primary,
+            fallback.map(|fallback| fallback as &dyn ReadBackend),
+            circuit_breaker,
+            |primary| {
+                retry_transient_errors(2, Duration::from_millis(1), metrics, || {
+                    primary.exists("path")
+                })
+            },
+            |backend| backend.exists("path"),
+            metrics,
+        )
+    }
+
+    #[test]
+    fn fall_back_on_storage_errors() {
+        let metrics = Metrics::new().unwrap();
+        let (primary, fallback) = (MockBackend::failing(), MockBackend::default());
+        let circuit_breaker = CircuitBreaker::default();
+
+        assert!(read_exists(&primary, Some(&fallback), &circuit_breaker, &metrics).unwrap());
+        // the fallback is only read once, after all retries of the primary storage
+        assert_eq!(primary.reads(), 3);
+        assert_eq!(fallback.reads(), 1);
+        assert_eq!(metrics.storage_read_retries.get(), 2);
+        assert_eq!(metrics.storage_fallback_reads.get(), 1);
+
+        // without a fallback the error is returned
+        assert!(read_exists(&primary, None, &circuit_breaker, &metrics).is_err());
+        assert_eq!(metrics.storage_fallback_reads.get(), 1);
+    }
+
+    #[test]
+    fn dont_fall_back_on_missing_paths() {
+        let metrics = Metrics::new().unwrap();
+        let fallback = MockBackend::default();
+        let result: Result<()> = read_with_fallback(
+            &MockBackend::default(),
+            Some(&fallback),
+            &CircuitBreaker::default(),
+            |_| Err(PathNotFoundError.into()),
+            |_| panic!("the fallback storage must not be read"),
+            &metrics,
+        );
+        assert!(result.unwrap_err().is::<PathNotFoundError>());
+        assert_eq!(metrics.storage_fallback_reads.get(), 0);
+    }
+
+    #[test]
+    fn circuit_breaker_skips_failing_storage() {
+        let metrics = Metrics::new().unwrap();
+        let (primary, fallback) = (MockBackend::failing(), MockBackend::default());
+        let circuit_breaker = CircuitBreaker::default();
+
+        for _ in 0..CIRCUIT_BREAKER_FAILURES {
+            assert!(!circuit_breaker.is_open());
+            assert!(read_exists(&primary, Some(&fallback), &circuit_breaker, &metrics).unwrap());
+        }
+        assert!(circuit_breaker.is_open());
+        let primary_reads = primary.reads();
+
+        // the primary storage isn't read anymore while the breaker is open
+        assert!(read_exists(&primary, Some(&fallback), &circuit_breaker, &metrics).unwrap());
+        assert_eq!(primary.reads(), primary_reads);
+        assert_eq!(fallback.reads(), CIRCUIT_BREAKER_FAILURES as usize + 1);
+
+        // successful reads close it again
+        circuit_breaker.state.lock().unwrap().open_until = Some(Instant::now());
+        assert!(!circuit_breaker.is_open());
+        let primary = MockBackend::default();
+        assert!(read_exists(&primary, Some(&fallback), &circuit_breaker, &metrics).unwrap());
+        assert_eq!(primary.reads(), 1);
+        assert_eq!(circuit_breaker.state.lock().unwrap().failures, 0);
+
+        // without a fallback the breaker doesn't matter
+        circuit_breaker.state.lock().unwrap().open_until =
+            Some(Instant::now() + CIRCUIT_BREAKER_COOLDOWN);
+        assert!(read_exists(&primary, None, &circuit_breaker, &metrics).unwrap());
+        assert_eq!(primary.reads(), 2);
+    }
+
+    #[test]
+    fn read_from_fallback_storage() {
+        crate::test::wrapper(|env| {
+            env.override_config(|config| {
+                // an S3 primary storage that can't be reached
+                config.storage_backend = StorageKind::S3;
+                config.s3_endpoint = Some("http://127.0.0.1:1".into());
+                config.s3_bucket_is_temporary = false;
+                config.aws_sdk_max_retries = 1;
+                config.storage_read_retries = 0;
+                config.storage_fallback_backend = Some(StorageKind::Database);
+            });
+            env.db().conn().execute(
+                "INSERT INTO files (path, mime, content)
+                 VALUES ('path/to/file.txt', 'text/plain', 'Hello world!')",
+                &[],
+            )?;
+            let storage = env.storage();
+
+            let blob = storage.get("path/to/file.txt", usize::MAX)?;
+            assert_eq!(blob.mime, "text/plain");
+            assert_eq!(blob.content, b"Hello world!");
+            assert!(storage.exists("path/to/file.txt")?);
+            assert!(!storage.exists("path/to/missing.txt")?);
+            assert_eq!(env.metrics().storage_fallback_reads.get(), 3);
+
+            // writes only go to the primary storage
+            assert!(storage
+                .store_blobs(vec![Blob {
+                    path: "path/to/other.txt".into(),
+                    mime: "text/plain".into(),
+                    date_updated: Utc::now(),
+                    content: b"Hello world!".to_vec(),
+                    compression: None,
+                }])
+                .is_err());
+            assert!(!storage.exists("path/to/other.txt")?);
+            Ok(())
+        });
+    }
 }
 
 /// Backend tests are a set of tests executed on all the supported storage backends. They ensure
@@ -698,6 +1467,30 @@ mod backend_tests {
         Ok(())
     }
 
+    fn test_get_metadata(storage: &Storage) -> Result<()> {
+        storage.store_blobs(vec![Blob {
+            path: "foo/bar.txt".into(),
+            mime: "text/plain".into(),
+            date_updated: Utc::now(),
+            compression: None,
+            content: b"test content\n".to_vec(),
+        }])?;
+
+        let metadata = storage.get_metadata("foo/bar.txt")?;
+        assert_eq!(metadata.size, 13);
+        assert_eq!(
+            metadata.date_updated,
+            storage.get("foo/bar.txt", 13)?.date_updated
+        );
+        assert!(storage
+            .get_metadata("foo/baz.txt")
+            .unwrap_err()
+            .downcast_ref::<PathNotFoundError>()
+            .is_some());
+
+        Ok(())
+    }
+
     fn test_get_range(storage: &Storage) -> Result<()> {
         let blob = Blob {
             path: "foo/bar.txt".into(),
@@ -811,7 +1604,7 @@ mod backend_tests {
         let dir = tempfile::Builder::new()
             .prefix("docs.rs-upload-archive-test")
             .tempdir()?;
-        let files = ["Cargo.toml", "src/main.rs"];
+        let files = ["Cargo.toml", "src/main.rs", "index.html"];
         for &file in &files {
             let path = dir.path().join(file);
             if let Some(parent) = path.parent() {
@@ -827,13 +1620,16 @@ mod backend_tests {
 
         assert!(!local_index_location.exists());
 
-        let (stored_files, compression_alg) =
+        let (stored_files, compression_algs) =
             storage.store_all_in_archive("folder/test.zip", dir.path())?;
 
         assert!(local_index_location.exists());
         assert!(storage.exists("folder/test.zip.index")?);
 
-        assert_eq!(compression_alg, CompressionAlgorithm::Bzip2);
+        let mut expected_algs = HashSet::new();
+        expected_algs.insert(CompressionAlgorithm::Bzip2);
+        expected_algs.insert(CompressionAlgorithm::Zstd);
+        assert_eq!(compression_algs, expected_algs);
         assert_eq!(stored_files.len(), files.len());
         for name in &files {
             let name = Path::new(name);
@@ -871,11 +1667,91 @@ mod backend_tests {
         assert_eq!(file.mime, "text/rust");
         assert_eq!(file.path, "folder/test.zip/src/main.rs");
 
+        // HTML pages are compressed with zstd
+        let info = archive_index::find_in_file(&local_index_location, "index.html")?.unwrap();
+        assert_eq!(info.compression(), CompressionAlgorithm::Zstd);
+        let file =
+            storage.get_from_archive("folder/test.zip", "index.html", std::usize::MAX, None)?;
+        assert_eq!(file.content, b"data");
+        assert_eq!(file.mime, "text/html");
+
         assert_eq!(2, metrics.uploaded_files_total.get());
 
         Ok(())
     }
 
+    fn test_recompress_archive(storage: &Storage) -> Result<()> {
+        // archives created before HTML pages were compressed with zstd
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        for (name, content) in [
+            ("index.html", &b"<html>docs</html>"[..]),
+            ("main.js", &b"script"[..]),
+        ] {
+            zip.start_file(
+                name,
+                zip::write::FileOptions::default()
+                    .compression_method(zip::CompressionMethod::Bzip2),
+            )?;
+            zip.write_all(content)?;
+        }
+        storage.store_archive("folder/legacy.zip", zip.finish()?.into_inner())?;
+        storage.set_public_access("folder/legacy.zip", true)?;
+
+        assert!(storage.recompress_archive("folder/legacy.zip")?);
+        assert!(storage.get_public_access("folder/legacy.zip")?);
+
+        let index = storage.get_index_filename("folder/legacy.zip")?;
+        for (name, alg) in [
+            ("index.html", CompressionAlgorithm::Zstd),
+            ("main.js", CompressionAlgorithm::Bzip2),
+        ] {
+            let info = archive_index::find_in_file(&index, name)?.unwrap();
+            assert_eq!(info.compression(), alg, "{}", name);
+        }
+        let file =
+            storage.get_from_archive("folder/legacy.zip", "index.html", std::usize::MAX, None)?;
+        assert_eq!(file.content, b"<html>docs</html>");
+        let file =
+            storage.get_from_archive("folder/legacy.zip", "main.js", std::usize::MAX, None)?;
+        assert_eq!(file.content, b"script");
+
+        // archives that are up to date are left alone
+        assert!(!storage.recompress_archive("folder/legacy.zip")?);
+        Ok(())
+    }
+
+    fn test_stale_archive_index(storage: &Storage) -> Result<()> {
+        let archive = |files: &[(&str, &[u8])]| -> Result<Vec<u8>> {
+            let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+            for (name, content) in files {
+                zip.start_file(
+                    *name,
+                    zip::write::FileOptions::default()
+                        .compression_method(zip::CompressionMethod::Bzip2),
+                )?;
+                zip.write_all(content)?;
+            }
+            Ok(zip.finish()?.into_inner())
+        };
+
+        storage.store_archive("folder/stale.zip", archive(&[("main.js", b"old")])?)?;
+        let index = storage.get_index_filename("folder/stale.zip")?;
+        let old_index = fs::read(&index)?;
+
+        // another server stored the archive again, with the file at a different offset
+        storage.store_archive(
+            "folder/stale.zip",
+            archive(&[("main.css", b"style"), ("main.js", b"new")])?,
+        )?;
+        fs::write(&index, old_index)?;
+
+        let file =
+            storage.get_from_archive("folder/stale.zip", "main.js", std::usize::MAX, None)?;
+        assert_eq!(file.content, b"new");
+        assert!(archive_index::find_in_file(&index, "main.css")?.is_some());
+        Ok(())
+    }
+
     fn test_store_all(storage: &Storage, metrics: &Metrics) -> Result<()> {
         let dir = tempfile::Builder::new()
             .prefix("docs.rs-upload-test")
@@ -1075,12 +1951,15 @@ mod backend_tests {
             test_exists,
             test_get_object,
             test_get_range,
+            test_get_metadata,
             test_get_too_big,
             test_delete_prefix,
             test_delete_prefix_without_matches,
             test_delete_percent,
             test_exists_without_remote_archive,
             test_set_public,
+            test_recompress_archive,
+            test_stale_archive_index,
         }
 
         tests_with_metrics {
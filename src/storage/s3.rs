@@ -1,4 +1,4 @@
-use super::{Blob, FileRange, StorageTransaction};
+use super::{Blob, FileMetadata, FileRange, StorageTransaction};
 use crate::{Config, Metrics};
 use anyhow::{Context, Error};
 use aws_sdk_s3::{
@@ -21,8 +21,27 @@ use tracing::{error, warn};
 const PUBLIC_ACCESS_TAG: &str = "static-cloudfront-access";
 const PUBLIC_ACCESS_VALUE: &str = "allow";
 
+/// Marks the errors of a read worth retrying as [`super::TransientStorageError`], the reads
+/// through `read_client` are only retried by `Storage`.
+fn read_error<E>(err: SdkError<E>) -> Error
+where
+    SdkError<E>: std::error::Error + Send + Sync + 'static,
+{
+    match err {
+        SdkError::ServiceError { ref raw, .. } if raw.http().status().is_server_error() => {
+            Error::from(err).context(super::TransientStorageError)
+        }
+        SdkError::TimeoutError(_)
+        | SdkError::DispatchFailure(_)
+        | SdkError::ResponseError { .. } => Error::from(err).context(super::TransientStorageError),
+        err => Error::from(err),
+    }
+}
+
 pub(super) struct S3Backend {
     client: Client,
+    /// Client without retries for the reads of `ReadBackend`
+    read_client: Client,
     runtime: Arc<Runtime>,
     bucket: String,
     metrics: Arc<Metrics>,
@@ -35,48 +54,92 @@ impl S3Backend {
         metrics: Arc<Metrics>,
         config: &Config,
         runtime: Arc<Runtime>,
+    ) -> Result<Self, Error> {
+        Self::with_bucket(
+            metrics,
+            config,
+            runtime,
+            &config.s3_bucket,
+            &config.s3_region,
+        )
+    }
+
+    /// The read-only replica configured as fallback storage, see
+    /// `Config::storage_fallback_backend`.
+    pub(super) fn fallback(
+        metrics: Arc<Metrics>,
+        config: &Config,
+        runtime: Arc<Runtime>,
+    ) -> Result<Self, Error> {
+        let bucket = config
+            .s3_fallback_bucket
+            .as_deref()
+            .context("the S3 fallback storage requires DOCSRS_S3_FALLBACK_BUCKET")?;
+        let region = config
+            .s3_fallback_region
+            .as_deref()
+            .unwrap_or(&config.s3_region);
+        Self::with_bucket(metrics, config, runtime, bucket, region)
+    }
+
+    fn with_bucket(
+        metrics: Arc<Metrics>,
+        config: &Config,
+        runtime: Arc<Runtime>,
+        bucket: &str,
+        region: &str,
     ) -> Result<Self, Error> {
         let shared_config = runtime.block_on(aws_config::load_from_env());
-        let mut config_builder = aws_sdk_s3::config::Builder::from(&shared_config)
-            .retry_config(RetryConfig::standard().with_max_attempts(config.aws_sdk_max_retries))
-            .region(Region::new(config.s3_region.clone()));
-
-        if let Some(ref endpoint) = config.s3_endpoint {
-            config_builder = config_builder.endpoint_resolver(Endpoint::immutable(
-                endpoint
-                    .parse::<http::Uri>()
-                    .context("got invalid URI as S3 endpoint")?,
-            ));
-        }
+        let endpoint = config
+            .s3_endpoint
+            .as_deref()
+            .map(|endpoint| endpoint.parse::<http::Uri>())
+            .transpose()
+            .context("got invalid URI as S3 endpoint")?;
+        let build_client = |retry_config: RetryConfig| {
+            let mut config_builder = aws_sdk_s3::config::Builder::from(&shared_config)
+                .retry_config(retry_config)
+                .region(Region::new(region.to_owned()));
+            if let Some(endpoint) = &endpoint {
+                config_builder =
+                    config_builder.endpoint_resolver(Endpoint::immutable(endpoint.clone()));
+            }
+            Client::from_conf(config_builder.build())
+        };
 
-        let client = Client::from_conf(config_builder.build());
+        let client =
+            build_client(RetryConfig::standard().with_max_attempts(config.aws_sdk_max_retries));
+        // reads are retried by `Storage` with `DOCSRS_STORAGE_READ_RETRIES`, retrying them in the
+        // SDK too would multiply the attempts
+        let read_client = build_client(RetryConfig::disabled());
 
         #[cfg(test)]
         {
             // Create the temporary S3 bucket during tests.
-            if config.s3_bucket_is_temporary {
+            if config.s3_bucket_is_temporary && bucket == config.s3_bucket {
                 if cfg!(not(test)) {
                     panic!("safeguard to prevent creating temporary buckets outside of tests");
                 }
 
-                runtime.block_on(client.create_bucket().bucket(&config.s3_bucket).send())?;
+                runtime.block_on(client.create_bucket().bucket(bucket).send())?;
             }
         }
 
         Ok(Self {
             client,
+            read_client,
             runtime,
             metrics,
-            bucket: config.s3_bucket.clone(),
+            bucket: bucket.to_owned(),
             #[cfg(test)]
-            temporary: config.s3_bucket_is_temporary,
+            temporary: config.s3_bucket_is_temporary && bucket == config.s3_bucket,
         })
     }
 
     pub(super) fn exists(&self, path: &str) -> Result<bool, Error> {
         self.runtime.block_on(async {
             match self
-                .client
+                .read_client
                 .head_object()
                 .bucket(&self.bucket)
                 .key(path)
@@ -90,7 +153,36 @@ impl S3Backend {
                 {
                     Ok(false)
                 }
-                Err(other) => Err(other.into()),
+                Err(other) => Err(read_error(other)),
+            }
+        })
+    }
+
+    pub(super) fn get_metadata(&self, path: &str) -> Result<FileMetadata, Error> {
+        self.runtime.block_on(async {
+            match self
+                .read_client
+                .head_object()
+                .bucket(&self.bucket)
+                .key(path)
+                .send()
+                .await
+            {
+                Ok(res) => Ok(FileMetadata {
+                    size: res.content_length().try_into()?,
+                    // see `get` for the missing modification dates
+                    date_updated: res
+                        .last_modified()
+                        .map(|dt| dt.to_chrono_utc())
+                        .unwrap_or_else(Utc::now),
+                }),
+                Err(SdkError::ServiceError { err, raw })
+                    if (matches!(err.kind, s3_error::HeadObjectErrorKind::NotFound(_))
+                        || raw.http().status() == http::StatusCode::NOT_FOUND) =>
+                {
+                    Err(super::PathNotFoundError.into())
+                }
+                Err(other) => Err(read_error(other)),
             }
         })
     }
@@ -168,7 +260,7 @@ impl S3Backend {
     ) -> Result<Blob, Error> {
         self.runtime.block_on(async {
             let res = self
-                .client
+                .read_client
                 .get_object()
                 .bucket(&self.bucket)
                 .key(path)
@@ -181,7 +273,7 @@ impl S3Backend {
                     {
                         super::PathNotFoundError.into()
                     }
-                    err => Error::from(err),
+                    err => read_error(err),
                 })
                 .await?;
 
@@ -190,7 +282,12 @@ impl S3Backend {
 
             let mut body = res.body;
 
-            while let Some(data) = body.next().await.transpose()? {
+            while let Some(data) = body
+                .next()
+                .await
+                .transpose()
+                .context(super::TransientStorageError)?
+            {
                 content.write_all(data.as_ref())?;
             }
 
@@ -1,14 +1,14 @@
 use super::TestDatabase;
 
-use crate::docbuilder::{BuildResult, DocCoverage};
+use crate::docbuilder::{BuildFailure, BuildResult, DocCoverage, DocSizes};
 use crate::error::Result;
 use crate::index::api::{CrateData, CrateOwner, ReleaseData};
-use crate::storage::{rustdoc_archive_path, source_archive_path, Storage};
+use crate::storage::{book_archive_path, rustdoc_archive_path, source_archive_path, Storage};
 use crate::utils::{Dependency, MetadataPackage, Target};
 use anyhow::Context;
 use chrono::{DateTime, Utc};
 use postgres::Client;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tracing::debug;
 
@@ -22,6 +22,8 @@ pub(crate) struct FakeRelease<'a> {
     source_files: Vec<(&'a str, &'a [u8])>,
     /// name, content
     rustdoc_files: Vec<(&'a str, &'a [u8])>,
+    /// name, content
+    book_files: Vec<(&'a str, &'a [u8])>,
     doc_targets: Vec<String>,
     default_target: Option<&'a str>,
     registry_crate_data: CrateData,
@@ -33,6 +35,8 @@ pub(crate) struct FakeRelease<'a> {
     readme: Option<&'a str>,
     github_stats: Option<FakeGithubStats>,
     doc_coverage: Option<DocCoverage>,
+    doc_sizes: Option<DocSizes>,
+    nightly_features: Option<bool>,
 }
 
 pub(crate) struct FakeBuild {
@@ -55,6 +59,7 @@ impl<'a> FakeRelease<'a> {
                 name: "fake-package".into(),
                 version: "1.0.0".into(),
                 license: Some("MIT".into()),
+                license_file: None,
                 repository: Some("https://git.example.com".into()),
                 homepage: Some("https://www.example.com".into()),
                 description: Some("Fake package".into()),
@@ -69,6 +74,7 @@ impl<'a> FakeRelease<'a> {
                 targets: vec![Target::dummy_lib("fake_package".into(), None)],
                 readme: None,
                 keywords: vec!["fake".into(), "package".into()],
+                categories: Vec::new(),
                 features: [
                     ("default".into(), vec!["feature1".into(), "feature3".into()]),
                     ("feature1".into(), Vec::new()),
@@ -78,10 +84,12 @@ impl<'a> FakeRelease<'a> {
                 .iter()
                 .cloned()
                 .collect::<HashMap<String, Vec<String>>>(),
+                rust_version: None,
             },
             builds: vec![],
             source_files: Vec::new(),
             rustdoc_files: Vec::new(),
+            book_files: Vec::new(),
             doc_targets: Vec::new(),
             default_target: None,
             registry_crate_data: CrateData { owners: Vec::new() },
@@ -95,6 +103,8 @@ impl<'a> FakeRelease<'a> {
             readme: None,
             github_stats: None,
             doc_coverage: None,
+            doc_sizes: None,
+            nightly_features: None,
             archive_storage: false,
         }
     }
@@ -116,11 +126,28 @@ impl<'a> FakeRelease<'a> {
         self
     }
 
+    /// Publishes the release to a scoped registry, call it after `name`.
+    pub(crate) fn registry(mut self, registry: &str) -> Self {
+        self.package.name =
+            crate::utils::scoped_registry::scoped_name(registry, &self.package.name);
+        self
+    }
+
     pub(crate) fn version(mut self, new: &str) -> Self {
         self.package.version = new.into();
         self
     }
 
+    pub(crate) fn license(mut self, license: Option<&str>) -> Self {
+        self.package.license = license.map(Into::into);
+        self
+    }
+
+    pub(crate) fn license_file(mut self, license_file: &str) -> Self {
+        self.package.license_file = Some(license_file.into());
+        self
+    }
+
     pub(crate) fn repo(mut self, repo: impl Into<String>) -> Self {
         self.package.repository = Some(repo.into());
         self
@@ -168,6 +195,11 @@ impl<'a> FakeRelease<'a> {
         self
     }
 
+    pub(crate) fn book_file(mut self, path: &'a str, data: &'a [u8]) -> Self {
+        self.book_files.push((path, data));
+        self
+    }
+
     pub(crate) fn source_file(mut self, path: &'a str, data: &'a [u8]) -> Self {
         self.source_files.push((path, data));
         self
@@ -194,11 +226,32 @@ impl<'a> FakeRelease<'a> {
         self
     }
 
+    pub(crate) fn add_bin_target(mut self, name: &str) -> Self {
+        self.package.targets.push(Target::dummy_bin(name.into()));
+        self
+    }
+
+    /// Whether the build produced docs, `binary(true)` unsets it.
+    pub(crate) fn has_docs(mut self, has_docs: bool) -> Self {
+        self.has_docs = has_docs;
+        self
+    }
+
+    pub(crate) fn rust_version(mut self, rust_version: &str) -> Self {
+        self.package.rust_version = Some(rust_version.into());
+        self
+    }
+
     pub(crate) fn keywords(mut self, keywords: Vec<String>) -> Self {
         self.package.keywords = keywords;
         self
     }
 
+    pub(crate) fn categories(mut self, categories: Vec<String>) -> Self {
+        self.package.categories = categories;
+        self
+    }
+
     pub(crate) fn add_platform<S: Into<String>>(mut self, platform: S) -> Self {
         let platform = platform.into();
         let name = self.package.targets[0].name.clone();
@@ -226,11 +279,52 @@ impl<'a> FakeRelease<'a> {
         }
     }
 
+    pub(crate) fn doc_sizes(self, doc_sizes: DocSizes) -> Self {
+        Self {
+            doc_sizes: Some(doc_sizes),
+            ..self
+        }
+    }
+
+    pub(crate) fn nightly_features(self, nightly_features: bool) -> Self {
+        Self {
+            nightly_features: Some(nightly_features),
+            ..self
+        }
+    }
+
     pub(crate) fn features(mut self, features: HashMap<String, Vec<String>>) -> Self {
         self.package.features = features;
         self
     }
 
+    /// Adds an optional dependency, which gets an implicit feature
+    pub(crate) fn optional_dependency(mut self, name: &str) -> Self {
+        self.package.dependencies.push(Dependency {
+            name: name.into(),
+            req: "^1.0.0".into(),
+            kind: None,
+            rename: None,
+            optional: true,
+        });
+        self
+    }
+
+    /// Replaces the dependencies of the package with `(name, req, kind)` entries
+    pub(crate) fn dependencies(mut self, dependencies: &[(&str, &str, Option<&str>)]) -> Self {
+        self.package.dependencies = dependencies
+            .iter()
+            .map(|&(name, req, kind)| Dependency {
+                name: name.into(),
+                req: req.into(),
+                kind: kind.map(Into::into),
+                rename: None,
+                optional: false,
+            })
+            .collect();
+        self
+    }
+
     pub(crate) fn github_stats(
         mut self,
         repo: impl Into<String>,
@@ -327,15 +421,12 @@ impl<'a> FakeRelease<'a> {
                     }
                 };
                 debug!("store in archive: {:?}", archive);
-                let (files_list, new_alg) = crate::db::add_path_into_remote_archive(
+                crate::db::add_path_into_remote_archive(
                     &storage,
                     &archive,
                     source_directory,
                     public,
-                )?;
-                let mut hm = HashSet::new();
-                hm.insert(new_alg);
-                Ok((files_list, hm))
+                )
             } else {
                 let prefix = match kind {
                     FileKind::Rustdoc => "rustdoc",
@@ -355,17 +446,33 @@ impl<'a> FakeRelease<'a> {
         let (source_meta, algs) = upload_files(FileKind::Sources, source_tmp.path())?;
         debug!("added source files {}", source_meta);
 
+        if !self.book_files.is_empty() {
+            let book_tmp = create_temp_dir();
+            store_files_into(&self.book_files, book_tmp.path())?;
+            crate::db::add_path_into_remote_archive(
+                &storage,
+                &book_archive_path(&package.name, &package.version),
+                book_tmp.path(),
+                true,
+            )?;
+            debug!("added book files");
+        }
+
         // If the test didn't add custom builds, inject a default one
         if self.builds.is_empty() {
             self.builds.push(FakeBuild::default());
         }
         let last_build_result = &self.builds.last().unwrap().result;
 
+        let mut preload_assets = Vec::new();
         if last_build_result.successful {
             let index = [&package.name, "index.html"].join("/");
             if package.is_library() && !rustdoc_files.iter().any(|(path, _)| path == &index) {
                 rustdoc_files.push((&index, DEFAULT_CONTENT));
             }
+            if let Some((_, html)) = rustdoc_files.iter().find(|(path, _)| path == &index) {
+                preload_assets = crate::web::rustdoc_assets::preload_assets(html)?;
+            }
 
             let rustdoc_tmp = create_temp_dir();
             let rustdoc_path = rustdoc_tmp.path();
@@ -374,8 +481,11 @@ impl<'a> FakeRelease<'a> {
             store_files_into(&rustdoc_files, rustdoc_path)?;
             debug!("added rustdoc files");
 
-            for target in &package.targets[1..] {
-                let platform = target.src_path.as_ref().unwrap();
+            // the other targets with a `src_path` are added by `add_platform`
+            for platform in package.targets[1..]
+                .iter()
+                .filter_map(|target| target.src_path.as_ref())
+            {
                 let platform_dir = rustdoc_path.join(platform);
                 fs::create_dir(&platform_dir)?;
 
@@ -428,6 +538,20 @@ impl<'a> FakeRelease<'a> {
         if let Some(coverage) = self.doc_coverage {
             crate::db::add_doc_coverage(&mut db.conn(), release_id, coverage)?;
         }
+        if let Some(doc_sizes) = self.doc_sizes {
+            crate::db::add_doc_sizes_into_database(&mut db.conn(), release_id, doc_sizes)?;
+        }
+        if let Some(nightly_features) = self.nightly_features {
+            crate::db::add_nightly_features_into_database(
+                &mut db.conn(),
+                release_id,
+                nightly_features,
+            )?;
+        }
+        if !self.book_files.is_empty() {
+            crate::db::add_book_into_database(&mut db.conn(), release_id)?;
+        }
+        crate::db::add_preload_assets_into_database(&mut db.conn(), release_id, &preload_assets)?;
 
         Ok(release_id)
     }
@@ -506,6 +630,28 @@ impl FakeBuild {
         }
     }
 
+    pub(crate) fn command(self, command: impl Into<String>) -> Self {
+        Self {
+            result: BuildResult {
+                command: Some(command.into()),
+                ..self.result
+            },
+            ..self
+        }
+    }
+
+    pub(crate) fn environment(self, cargo_version: &str, target: &str, cfgs: &[&str]) -> Self {
+        Self {
+            result: BuildResult {
+                cargo_version: Some(cargo_version.into()),
+                target: Some(target.into()),
+                cfgs: Some(cfgs.iter().map(|&cfg| cfg.to_owned()).collect()),
+                ..self.result
+            },
+            ..self
+        }
+    }
+
     pub(crate) fn successful(self, successful: bool) -> Self {
         Self {
             result: BuildResult {
@@ -516,6 +662,17 @@ impl FakeBuild {
         }
     }
 
+    pub(crate) fn failure(self, failure: BuildFailure) -> Self {
+        Self {
+            result: BuildResult {
+                successful: false,
+                failure: Some(failure),
+                ..self.result
+            },
+            ..self
+        }
+    }
+
     fn create(
         &self,
         conn: &mut Client,
@@ -540,8 +697,7 @@ impl FakeBuild {
         }
 
         if let Some(s3_build_log) = self.s3_build_log.as_deref() {
-            let path = format!("build-logs/{}/{}.txt", build_id, default_target);
-            storage.store_one(path, s3_build_log)?;
+            storage.store_build_log(build_id, default_target, s3_build_log)?;
         }
 
         Ok(())
@@ -558,6 +714,11 @@ impl Default for FakeBuild {
                 rustc_version: "rustc 2.0.0-nightly (000000000 1970-01-01)".into(),
                 docsrs_version: "docs.rs 1.0.0 (000000000 1970-01-01)".into(),
                 successful: true,
+                command: None,
+                failure: None,
+                cargo_version: None,
+                target: None,
+                cfgs: None,
             },
         }
     }
@@ -7,7 +7,8 @@ use crate::error::Result;
 use crate::repositories::RepositoryStatsUpdater;
 use crate::storage::{Storage, StorageKind};
 use crate::web::{
-    build_axum_app, build_strangler_service, cache, page::TemplateData, start_iron_server,
+    build_axum_app, build_strangler_service, cache, page::TemplateData, routes::build_routes,
+    scoped_urls::serve_scoped_urls, serve_with_graceful_shutdown, start_iron_server,
 };
 use crate::{BuildQueue, Config, Context, Index, Metrics};
 use anyhow::Context as _;
@@ -563,8 +564,10 @@ impl TestFrontend {
         let template_data =
             Arc::new(TemplateData::new(&mut context.pool().unwrap().get().unwrap()).unwrap());
 
+        let (axum_router, iron_routes) = build_routes();
+
         debug!("starting iron server");
-        let iron_server = start_iron_server(context, template_data.clone(), Some(1))
+        let iron_server = start_iron_server(context, template_data.clone(), iron_routes, Some(1))
             .expect("could not start iron server");
 
         debug!("binding local TCP port for axum");
@@ -577,28 +580,31 @@ impl TestFrontend {
         let (tx, rx) = tokio::sync::oneshot::channel::<()>();
 
         debug!("building axum app");
-        let axum_app = build_axum_app(context, template_data).expect("could not build axum app");
+        let axum_app =
+            build_axum_app(context, template_data, axum_router).expect("could not build axum app");
+
+        let config = context.config().unwrap();
+        let grace_period = Duration::from_secs(config.shutdown_grace_period);
 
         let handle = thread::spawn({
             let runtime = context.runtime().unwrap();
             move || {
-                runtime.block_on(async {
-                    axum::Server::from_tcp(axum_listener)
-                        .unwrap()
-                        .serve(
-                            axum_app
-                                .fallback_service(
-                                    build_strangler_service(iron_server.socket)
-                                        .expect("could not build strangler service"),
-                                )
-                                .into_make_service(),
-                        )
-                        .with_graceful_shutdown(async {
+                runtime
+                    .block_on(serve_with_graceful_shutdown(
+                        axum_listener,
+                        serve_scoped_urls(
+                            axum_app.fallback_service(
+                                build_strangler_service(iron_server.socket)
+                                    .expect("could not build strangler service"),
+                            ),
+                            &config,
+                        ),
+                        async {
                             rx.await.ok();
-                        })
-                        .await
-                        .expect("error from axum server")
-                })
+                        },
+                        grace_period,
+                    ))
+                    .expect("error from axum server")
             }
         });
 
@@ -653,6 +659,24 @@ impl TestFrontend {
         self.client.request(Method::GET, url)
     }
 
+    pub(crate) fn post(&self, url: &str) -> RequestBuilder {
+        let url = self.build_url(url);
+        debug!("posting {url}");
+        self.client.request(Method::POST, url)
+    }
+
+    pub(crate) fn delete(&self, url: &str) -> RequestBuilder {
+        let url = self.build_url(url);
+        debug!("deleting {url}");
+        self.client.request(Method::DELETE, url)
+    }
+
+    pub(crate) fn options(&self, url: &str) -> RequestBuilder {
+        let url = self.build_url(url);
+        debug!("sending options for {url}");
+        self.client.request(Method::OPTIONS, url)
+    }
+
     pub(crate) fn get_no_redirect(&self, url: &str) -> RequestBuilder {
         let url = self.build_url(url);
         debug!("getting {url} (no redirects)");
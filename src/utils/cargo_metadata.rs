@@ -41,6 +41,12 @@ impl CargoMetadata {
     pub(crate) fn root(&self) -> &Package {
         &self.root
     }
+
+    /// Stores the root package under another name, the scoped name for crates of scoped
+    /// registries.
+    pub(crate) fn rename_root(&mut self, name: &str) {
+        self.root.name = name.into();
+    }
 }
 
 #[derive(Deserialize, Serialize, Default)]
@@ -49,6 +55,10 @@ pub(crate) struct Package {
     pub(crate) name: String,
     pub(crate) version: String,
     pub(crate) license: Option<String>,
+    /// The `package.license-file` path, relative to the root of the package
+    // older versions of cargo don't include this in the metadata
+    #[serde(default)]
+    pub(crate) license_file: Option<String>,
     pub(crate) repository: Option<String>,
     pub(crate) homepage: Option<String>,
     pub(crate) description: Option<String>,
@@ -57,7 +67,14 @@ pub(crate) struct Package {
     pub(crate) targets: Vec<Target>,
     pub(crate) readme: Option<String>,
     pub(crate) keywords: Vec<String>,
+    // older versions of cargo don't include this in the metadata
+    #[serde(default)]
+    pub(crate) categories: Vec<String>,
     pub(crate) features: HashMap<String, Vec<String>>,
+    /// The minimum supported Rust version from `package.rust-version`
+    // older versions of cargo don't include this in the metadata
+    #[serde(default)]
+    pub(crate) rust_version: Option<String>,
 }
 
 impl Package {
@@ -105,6 +122,15 @@ impl Target {
             src_path,
         }
     }
+
+    #[cfg(test)]
+    pub(crate) fn dummy_bin(name: String) -> Self {
+        Target {
+            name,
+            crate_types: vec!["bin".into()],
+            src_path: None,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize)]
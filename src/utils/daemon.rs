@@ -3,9 +3,10 @@
 //! This daemon will start web server, track new packages and build them
 
 use crate::{
+    previews,
     utils::{queue_builder, report_error},
     web::start_web_server,
-    BuildQueue, Config, Context, Index, RustwideBuilder,
+    webhooks, BuildQueue, Config, Context, Index, RustwideBuilder,
 };
 use anyhow::{anyhow, Context as _, Error};
 use std::sync::Arc;
@@ -95,6 +96,35 @@ pub fn start_background_repository_stats_updater(context: &dyn Context) -> Resul
     Ok(())
 }
 
+fn start_webhook_deliveries(context: &dyn Context) -> Result<(), Error> {
+    let pool = context.pool()?;
+    let config = context.config()?;
+    cron("webhook deliveries", Duration::from_secs(60), move || {
+        let mut conn = pool.get()?;
+        let delivered = webhooks::deliver_pending(&mut conn, &config)?;
+        if delivered > 0 {
+            debug!(delivered, "delivered webhooks");
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+fn start_preview_expiry(context: &dyn Context) -> Result<(), Error> {
+    let pool = context.pool()?;
+    let storage = context.storage()?;
+    let config = context.config()?;
+    cron("preview expiry", Duration::from_secs(60 * 60), move || {
+        let mut conn = pool.get()?;
+        let deleted = previews::delete_expired(&mut conn, &storage, &config)?;
+        if deleted > 0 {
+            info!(deleted, "deleted expired documentation previews");
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
 pub fn start_daemon<C: Context + Send + Sync + 'static>(
     context: C,
     enable_registry_watcher: bool,
@@ -125,6 +155,8 @@ pub fn start_daemon<C: Context + Send + Sync + 'static>(
         .unwrap();
 
     start_background_repository_stats_updater(&*context)?;
+    start_webhook_deliveries(&*context)?;
+    start_preview_expiry(&*context)?;
 
     // NOTE: if a error occurred earlier in `start_daemon`, the server will _not_ be joined -
     // instead it will get killed when the process exits.
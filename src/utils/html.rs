@@ -9,11 +9,21 @@ use tera::Context;
 /// render the `rustdoc/` templates with the `html`.
 /// The output is an HTML page which has not yet been UTF-8 validated.
 /// In practice, the output should always be valid UTF-8.
+///
+/// The `custom_header` and `custom_footer` snippets of self-hosted instances are added above and
+/// below the documentation, pages stay unchanged without them.
+///
+/// A `theme` is set on the `<html>` element and returned to the rustdoc scripts reading the stored
+/// theme, so it only applies to this page and the stored theme of the user stays the same. It has
+/// to be one of the known rustdoc themes.
 pub(crate) fn rewrite_lol(
     html: &[u8],
     max_allowed_memory_usage: usize,
     ctx: Context,
     templates: &TemplateData,
+    custom_header: Option<&str>,
+    custom_footer: Option<&str>,
+    theme: Option<&str>,
 ) -> Result<Vec<u8>, RewritingError> {
     use lol_html::html_content::{ContentType, Element};
     use lol_html::{HtmlRewriter, MemorySettings, Settings};
@@ -23,6 +33,9 @@ pub(crate) fn rewrite_lol(
     let tera_vendored_css = templates.render("rustdoc/vendored.html", &ctx).unwrap();
     let tera_body = templates.render("rustdoc/body.html", &ctx).unwrap();
     let tera_rustdoc_topbar = templates.render("rustdoc/topbar.html", &ctx).unwrap();
+    let tera_yanked_banner = templates
+        .render("rustdoc/yanked_banner.html", &ctx)
+        .unwrap();
 
     // Before: <body> ... rustdoc content ... </body>
     // After:
@@ -52,6 +65,20 @@ pub(crate) fn rewrite_lol(
         rustdoc_body_class.before(r#"<body class="rustdoc-page">"#, ContentType::Html);
         // Insert the topbar outside of the rustdoc div
         rustdoc_body_class.before(&tera_rustdoc_topbar, ContentType::Html);
+        // Followed by the warning for yanked releases, empty for all others
+        rustdoc_body_class.before(&tera_yanked_banner, ContentType::Html);
+        if let Some(header) = custom_header {
+            rustdoc_body_class.before(
+                &format!(r#"<div id="docsrs-custom-header">{}</div>"#, header),
+                ContentType::Html,
+            );
+        }
+        if let Some(footer) = custom_footer {
+            rustdoc_body_class.after(
+                &format!(r#"<div id="docsrs-custom-footer">{}</div>"#, footer),
+                ContentType::Html,
+            );
+        }
         // Finalize body with </body>
         rustdoc_body_class.after("</body>", ContentType::Html);
 
@@ -60,8 +87,32 @@ pub(crate) fn rewrite_lol(
 
     let settings = Settings {
         element_content_handlers: vec![
+            element!("html", |html: &mut Element| {
+                if let Some(theme) = theme {
+                    html.set_attribute("data-theme", theme)?;
+                }
+                Ok(())
+            }),
             // Append `style.css` stylesheet after all head elements.
             element!("head", |head: &mut Element| {
+                if let Some(theme) = theme {
+                    head.prepend(
+                        &format!(
+                            "<script type=\"text/javascript\">try {{ \
+                             var getItem = Storage.prototype.getItem; \
+                             Storage.prototype.getItem = function(key) {{ \
+                             if (this === window.localStorage) {{ \
+                             if (key === 'rustdoc-theme') return '{}'; \
+                             if (key === 'rustdoc-use-system-theme') return 'false'; \
+                             }} \
+                             return getItem.call(this, key); \
+                             }}; \
+                             }} catch (e) {{}}</script>",
+                            theme
+                        ),
+                        ContentType::Html,
+                    );
+                }
                 head.append(&tera_head, ContentType::Html);
                 Ok(())
             }),
@@ -106,9 +157,119 @@ pub(crate) fn rewrite_lol(
     Ok(buffer)
 }
 
+/// Sanitizes a custom HTML snippet, like `DOCSRS_RUSTDOC_HEADER_HTML`, with an allow list of
+/// elements, attributes and URL schemes, so it can't run code or change the page outside of the
+/// snippet.
+///
+/// Besides the defaults of [`ammonia`], `class` attributes are kept so the snippet can be styled.
+pub(crate) fn sanitize_snippet(html: &str) -> String {
+    ammonia::Builder::default()
+        .add_generic_attributes(&["class"])
+        .link_rel(None)
+        .clean(html)
+        .to_string()
+}
+
 #[cfg(test)]
 mod test {
+    use super::sanitize_snippet;
     use crate::test::wrapper;
+    use test_case::test_case;
+
+    #[test_case(
+        r#"<a href="https://example.com" class="brand">Example</a>"#,
+        r#"<a href="https://example.com" class="brand">Example</a>"#
+    )]
+    #[test_case(r#"<b>hi</b><script>alert(1)</script>"#, "<b>hi</b>")]
+    #[test_case(
+        r#"<img src="logo.png" onerror="alert(1)">"#,
+        r#"<img src="logo.png">"#
+    )]
+    #[test_case(r#"<a href=" JavaScript:alert(1)">x</a>"#, "<a>x</a>")]
+    #[test_case(r#"<a href="&#106;avascript:alert(1)">x</a>"#, "<a>x</a>")]
+    #[test_case("<style>body { display: none }</style><p>x</p><!-- y -->", "<p>x</p>")]
+    fn sanitize_custom_snippets(html: &str, expected: &str) {
+        assert_eq!(sanitize_snippet(html), expected);
+    }
+
+    #[test]
+    fn no_custom_header_and_footer_by_default() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("testing")
+                .version("0.1.0")
+                .create()?;
+
+            let output = env
+                .frontend()
+                .get("/testing/0.1.0/testing/")
+                .send()?
+                .text()?;
+            assert!(!output.contains("docsrs-custom-header"));
+            assert!(!output.contains("docsrs-custom-footer"));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn custom_header_and_footer() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.rustdoc_header_html = Some(r#"<p class="brand">Internal docs</p>"#.into());
+                config.rustdoc_footer_html = Some("<p>Hosted by example</p>".into());
+            });
+            env.fake_release()
+                .name("testing")
+                .version("0.1.0")
+                .create()?;
+
+            let output = env
+                .frontend()
+                .get("/testing/0.1.0/testing/")
+                .send()?
+                .text()?;
+            let header = output
+                .find(r#"<div id="docsrs-custom-header"><p class="brand">Internal docs</p></div>"#)
+                .expect("missing header");
+            let docs = output.find(r#"id="rustdoc_body_wrapper""#).unwrap();
+            let footer = output
+                .find(r#"<div id="docsrs-custom-footer"><p>Hosted by example</p></div>"#)
+                .expect("missing footer");
+            assert!(header < docs && docs < footer);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn theme_from_query() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("testing")
+                .version("0.1.0")
+                .create()?;
+            let web = env.frontend();
+
+            let output = web
+                .get("/testing/0.1.0/testing/?theme=dark")
+                .send()?
+                .text()?;
+            assert!(output.contains(r#"<html data-theme="dark">"#));
+            assert!(output.contains("if (key === 'rustdoc-theme') return 'dark';"));
+            // the theme of the user isn't changed
+            assert!(!output.contains("setItem"));
+
+            for query in ["", "?theme=unknown", "?theme=%27dark"] {
+                let output = web
+                    .get(&format!("/testing/0.1.0/testing/{}", query))
+                    .send()?
+                    .text()?;
+                assert!(output.contains("<html>"), "{}", query);
+                assert!(!output.contains("'rustdoc-theme') return"), "{}", query);
+            }
+            Ok(())
+        });
+    }
 
     #[test]
     fn rewriting_only_injects_css_once() {
@@ -3,10 +3,12 @@
 pub(crate) use self::cargo_metadata::{CargoMetadata, Package as MetadataPackage};
 pub(crate) use self::copy::copy_dir_all;
 pub use self::daemon::{start_daemon, watch_registry};
-pub(crate) use self::html::rewrite_lol;
+pub(crate) use self::html::{rewrite_lol, sanitize_snippet};
 pub use self::queue::{get_crate_priority, remove_crate_priority, set_crate_priority};
 pub use self::queue_builder::queue_builder;
-pub(crate) use self::rustc_version::{get_correct_docsrs_style_file, parse_rustc_version};
+pub(crate) use self::rustc_version::{
+    get_correct_docsrs_style_file, parse_rustc_date, parse_rustc_version,
+};
 
 #[cfg(test)]
 pub(crate) use self::cargo_metadata::{Dependency, Target};
@@ -20,6 +22,7 @@ mod html;
 mod queue;
 pub(crate) mod queue_builder;
 mod rustc_version;
+pub(crate) mod scoped_registry;
 use anyhow::{Context as _, Result};
 use postgres::Client;
 use serde::de::DeserializeOwned;
@@ -107,7 +110,10 @@ where
     F: FnOnce() -> Result<R> + Send + 'static,
     R: Send + 'static,
 {
-    tokio::task::spawn_blocking(f)
+    // run the closure in the current span, so spans created in the blocking
+    // task keep their parent.
+    let span = tracing::Span::current();
+    tokio::task::spawn_blocking(move || span.in_scope(f))
         .await
         .context("failed to join thread")?
 }
@@ -0,0 +1,56 @@
+//! Crates of scoped registries, the alternate registries configured with
+//! `DOCSRS_SCOPED_REGISTRIES` whose crates are served under `/registry/:registry/...`.
+//!
+//! Their releases are stored as `<name>@<registry>` in the database, the storage and the build
+//! queue. `@` can't appear in crate names, so crates with the same name from different
+//! registries never collide, and crates of the default registry keep their plain names.
+
+/// Separates the crate name from the registry in scoped names
+const SEPARATOR: char = '@';
+
+/// The name a crate of the scoped registry `registry` is stored under.
+pub(crate) fn scoped_name(registry: &str, name: &str) -> String {
+    format!("{}{}{}", name, SEPARATOR, registry)
+}
+
+/// Splits a stored name into the crate name and its scoped registry, which is `None` for
+/// crates of the default registry.
+pub(crate) fn split_scoped_name(name: &str) -> (&str, Option<&str>) {
+    match name.split_once(SEPARATOR) {
+        Some((name, registry)) => (name, Some(registry)),
+        None => (name, None),
+    }
+}
+
+/// Whether `registry` can be used as name of a scoped registry, it's part of URLs and
+/// storage paths.
+pub(crate) fn is_valid_registry_name(registry: &str) -> bool {
+    !registry.is_empty()
+        && registry
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scoped_names() {
+        assert_eq!(scoped_name("internal", "foo-bar"), "foo-bar@internal");
+        assert_eq!(
+            split_scoped_name("foo-bar@internal"),
+            ("foo-bar", Some("internal"))
+        );
+        assert_eq!(split_scoped_name("foo-bar"), ("foo-bar", None));
+    }
+
+    #[test]
+    fn registry_names() {
+        assert!(is_valid_registry_name("internal"));
+        assert!(is_valid_registry_name("my_company-2"));
+        assert!(!is_valid_registry_name(""));
+        assert!(!is_valid_registry_name("a/b"));
+        assert!(!is_valid_registry_name("a@b"));
+    }
+}
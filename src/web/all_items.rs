@@ -0,0 +1,520 @@
+//! Serves the items rustdoc lists on the `all.html` page of a release as JSON, a flat inventory
+//! that is simpler to use than the search index.
+
+use crate::{
+    db::Pool,
+    utils::{report_error, spawn_blocking},
+    web::{
+        cache::CachePolicy,
+        error::{AxumNope, JsonAxumResult},
+        match_version_axum, MatchSemver, RustdocRelease,
+    },
+    Storage,
+};
+use anyhow::{anyhow, Context as _, Result};
+use axum::{
+    body::{Bytes, StreamBody},
+    extract::{Extension, Path},
+    http::header::CONTENT_TYPE,
+    response::IntoResponse,
+};
+use futures_util::stream;
+use lol_html::{element, errors::RewritingError, text};
+use serde::Serialize;
+use std::{cell::RefCell, collections::HashMap, io, sync::Arc};
+use tokio::sync::mpsc;
+
+/// Size of the chunks the items are streamed in, and `all.html` is parsed in
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// How stable an item is, as rustdoc marks it on the page of its module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Stability {
+    Stable,
+    Unstable,
+    Deprecated,
+}
+
+/// An item as it's linked on `all.html`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Link {
+    /// The kind of the item as rustdoc names its pages, like `struct` or `macro`
+    kind: String,
+    /// The path of the item, starting with the name of the crate
+    path: String,
+    /// The page of the item, relative to the directory of the crate
+    href: String,
+}
+
+/// An item listed on `all.html`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Item {
+    kind: String,
+    path: String,
+    /// The URL of the documentation of the item
+    url: String,
+    stability: Stability,
+}
+
+/// Parses the items linked on an `all.html` page, in the order rustdoc lists them, handing each
+/// to `on_item` as soon as it's complete.
+///
+/// `all.html` doesn't contain the stability of items, see [`parse_module_stability`] for that.
+fn parse_all_items(
+    html: &[u8],
+    target_name: &str,
+    mut on_item: impl FnMut(Link) -> Result<()>,
+) -> Result<()> {
+    use lol_html::{html_content::Element, html_content::TextChunk, HtmlRewriter, Settings};
+
+    let links = RefCell::new(Vec::new());
+    let start_item = |link: &mut Element| {
+        // links look like `module/struct.Name.html`, the path is the text of the link
+        let href = link.get_attribute("href").unwrap_or_default();
+        let kind = href
+            .rsplit('/')
+            .next()
+            .and_then(|file| file.split_once('.'))
+            .map_or("", |(kind, _)| kind)
+            .to_owned();
+        links.borrow_mut().push(Link {
+            kind,
+            path: format!("{}::", target_name),
+            href,
+        });
+        Ok(())
+    };
+    let item_path = |text: &mut TextChunk| {
+        if let Some(link) = links.borrow_mut().last_mut() {
+            link.path.push_str(text.as_str().trim());
+        }
+        Ok(())
+    };
+
+    let settings = Settings {
+        element_content_handlers: vec![
+            element!("ul.all-items a", start_item),
+            text!("ul.all-items a", item_path),
+            // rustdoc before 1.59 had a class named after the kind instead
+            element!("ul.docblock a", start_item),
+            text!("ul.docblock a", item_path),
+        ],
+        ..Settings::default()
+    };
+    let mut rewriter = HtmlRewriter::new(settings, |_: &[u8]| {});
+    for chunk in html.chunks(CHUNK_SIZE) {
+        rewriter.write(chunk)?;
+        // the text of the last link might continue in the next chunk
+        let complete = links.borrow().len().saturating_sub(1);
+        let complete: Vec<Link> = links.borrow_mut().drain(..complete).collect();
+        for link in complete {
+            on_item(link)?;
+        }
+    }
+    rewriter.end()?;
+    let rest = links.take();
+    for link in rest {
+        on_item(link)?;
+    }
+    Ok(())
+}
+
+/// Parses the stability of the items listed on the page of a module, keyed by their `href`.
+/// Items without a mark are left out, they are stable.
+fn parse_module_stability(html: &[u8]) -> Result<HashMap<String, Stability>, RewritingError> {
+    use lol_html::{html_content::Element, HtmlRewriter, Settings};
+
+    let stability = RefCell::new(HashMap::new());
+    let last_item = RefCell::new(None);
+    let item = |link: &mut Element| {
+        *last_item.borrow_mut() = link.get_attribute("href");
+        Ok(())
+    };
+    let mark = |span: &mut Element| {
+        let class = span.get_attribute("class").unwrap_or_default();
+        let mark = if class.split_whitespace().any(|class| class == "deprecated") {
+            Stability::Deprecated
+        } else if class.split_whitespace().any(|class| class == "unstable") {
+            Stability::Unstable
+        } else {
+            return Ok(());
+        };
+        if let Some(href) = last_item.borrow().clone() {
+            let mut stability = stability.borrow_mut();
+            let current = stability.entry(href).or_insert(mark);
+            // deprecation is more important to users than the item being unstable
+            if mark == Stability::Deprecated {
+                *current = mark;
+            }
+        }
+        Ok(())
+    };
+
+    let settings = Settings {
+        element_content_handlers: vec![
+            element!(".item-name > a", item),
+            element!(".item-name .stab", mark),
+            // rustdoc before 1.69
+            element!(".item-left > a", item),
+            element!(".item-left .stab", mark),
+            // rustdoc before 1.56
+            element!("tr.module-item > td > a", item),
+            element!("tr.module-item .stab", mark),
+        ],
+        ..Settings::default()
+    };
+    let mut rewriter = HtmlRewriter::new(settings, |_: &[u8]| {});
+    rewriter.write(html)?;
+    rewriter.end()?;
+
+    Ok(stability.into_inner())
+}
+
+/// Looks up the stability of items on the pages of their modules, fetching each page once.
+struct ModuleStability<'a> {
+    release: &'a RustdocRelease,
+    storage: &'a Storage,
+    modules: HashMap<String, HashMap<String, Stability>>,
+}
+
+impl ModuleStability<'_> {
+    fn of(&mut self, href: &str) -> Result<Stability> {
+        let (module, file) = href.rsplit_once('/').unwrap_or(("", href));
+        if !self.modules.contains_key(module) {
+            let path = if module.is_empty() {
+                format!("{}/index.html", self.release.target_name)
+            } else {
+                format!("{}/{}/index.html", self.release.target_name, module)
+            };
+            let stability = match self.release.fetch(self.storage, &path) {
+                Ok(blob) => parse_module_stability(&blob.content)
+                    .with_context(|| format!("failed to parse {}", path))?,
+                // some items, like the ones of private modules, aren't listed anywhere else
+                Err(err) if matches!(err.downcast_ref(), Some(AxumNope::ResourceNotFound)) => {
+                    HashMap::new()
+                }
+                Err(err) => return Err(err),
+            };
+            self.modules.insert(module.to_owned(), stability);
+        }
+        Ok(self.modules[module]
+            .get(file)
+            .copied()
+            .unwrap_or(Stability::Stable))
+    }
+}
+
+/// Writes the items of an `all.html` page as a JSON array, handing it to `send` in chunks of
+/// about [`CHUNK_SIZE`] bytes as the page is parsed.
+fn write_items(
+    all_html: &[u8],
+    target_name: &str,
+    base_url: &str,
+    mut stability: impl FnMut(&str) -> Result<Stability>,
+    mut send: impl FnMut(Bytes) -> Result<()>,
+) -> Result<()> {
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+    chunk.push(b'[');
+    let mut first = true;
+    parse_all_items(all_html, target_name, |link| {
+        let item = Item {
+            stability: stability(&link.href)?,
+            url: format!("{}/{}", base_url, link.href),
+            kind: link.kind,
+            path: link.path,
+        };
+        if !first {
+            chunk.push(b',');
+        }
+        first = false;
+        serde_json::to_writer(&mut chunk, &item)?;
+        if chunk.len() >= CHUNK_SIZE {
+            send(Bytes::from(std::mem::replace(
+                &mut chunk,
+                Vec::with_capacity(CHUNK_SIZE),
+            )))?;
+        }
+        Ok(())
+    })?;
+    chunk.push(b']');
+    send(Bytes::from(chunk))
+}
+
+/// Serves the items of the default target of a release, as listed on its `all.html` page, with
+/// their stability from the pages of their modules.
+///
+/// The items are streamed while `all.html` is parsed and the module pages are fetched, the
+/// inventory of large crates is bigger than their pages. Errors after the response started
+/// abort it, leaving the JSON incomplete.
+pub(crate) async fn all_items_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+    Extension(storage): Extension<Arc<Storage>>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let version = match match_version_axum(&pool, &name, Some(&req_version))
+        .await?
+        .assume_exact()?
+    {
+        MatchSemver::Exact((version, _)) | MatchSemver::Latest((version, _)) => version,
+        MatchSemver::Semver((version, _)) => {
+            return Ok(super::axum_cached_redirect(
+                &format!("/crate/{}/{}/all.json", name, version),
+                CachePolicy::ForeverInCdn,
+            )?
+            .into_response());
+        }
+    };
+
+    let (release, all_html) = spawn_blocking({
+        let storage = storage.clone();
+        move || {
+            let mut conn = pool.get()?;
+            let release = RustdocRelease::load(&mut conn, &name, &version)?;
+            let all_html = release.fetch(&storage, &format!("{}/all.html", release.target_name))?;
+            Ok((release, all_html.content))
+        }
+    })
+    .await?;
+
+    // only a few chunks are prepared ahead of what the client received
+    let (sender, receiver) = mpsc::channel(2);
+    tokio::task::spawn_blocking(move || {
+        let base_url = format!(
+            "/{}/{}/{}",
+            release.name, release.version, release.target_name
+        );
+        let mut modules = ModuleStability {
+            release: &release,
+            storage: &storage,
+            modules: HashMap::new(),
+        };
+        let result = write_items(
+            &all_html,
+            &release.target_name,
+            &base_url,
+            |href| modules.of(href),
+            |chunk| {
+                sender
+                    .blocking_send(Ok(chunk))
+                    .map_err(|_| anyhow!("the client went away"))
+            },
+        );
+        if let Err(err) = result {
+            if !sender.is_closed() {
+                report_error(&err.context(format!(
+                    "failed to list the items of {} {}",
+                    release.name, release.version
+                )));
+                let _ = sender.blocking_send(Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "failed to list the items",
+                )));
+            }
+        }
+    });
+    let chunks = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+
+    Ok((
+        Extension(CachePolicy::ForeverInCdn),
+        [(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())],
+        StreamBody::new(chunks),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_all_items, parse_module_stability, write_items, Link, Stability, CHUNK_SIZE,
+    };
+    use crate::{
+        test::{assert_cache_control, assert_redirect_cached, wrapper},
+        web::cache::CachePolicy,
+    };
+    use reqwest::StatusCode;
+    use serde_json::json;
+    use test_case::test_case;
+
+    const ALL_HTML: &str = r#"<!DOCTYPE html><html><body><main>
+<h1 class="fqn">List of all items</h1>
+<h3 id="structs">Structs</h3>
+<ul class="all-items"><li><a href="struct.Foo.html">Foo</a></li><li><a href="bar/struct.Baz.html">bar::Baz</a></li></ul>
+<h3 id="macros">Macros</h3>
+<ul class="all-items"><li><a href="macro.quux.html">quux</a></li></ul>
+</main></body></html>"#;
+
+    /// `all.html` of rustdoc before 1.59
+    const OLD_ALL_HTML: &str = r#"<h3 id="Functions">Functions</h3>
+<ul class="functions docblock"><li><a href="fn.run.html">run</a></li></ul>"#;
+
+    /// The page of the root module, with the marks of rustdoc since 1.69
+    const INDEX_HTML: &str = r#"<ul class="item-table">
+<li><div class="item-name"><a class="mod" href="bar/index.html" title="mod foo::bar">bar</a></div></li>
+<li><div class="item-name"><a class="struct" href="struct.Foo.html" title="struct foo::Foo">Foo</a><span class="stab deprecated" title="">Deprecated</span></div><div class="desc docblock-short">See <a href="macro.quux.html">quux</a></div></li>
+<li><div class="item-name"><a class="macro" href="macro.quux.html" title="macro foo::quux">quux</a></div></li>
+</ul>"#;
+
+    /// The page of a module, with the marks of rustdoc before 1.56
+    const BAR_INDEX_HTML: &str = r#"<table>
+<tr class="module-item"><td><a class="struct" href="struct.Baz.html" title="struct foo::bar::Baz">Baz</a></td><td class="docblock-short"><span class="stab unstable">Experimental</span><span class="stab portability">Available on <strong>crate feature <code>baz</code></strong> only.</span><p>A baz</p></td></tr>
+</table>"#;
+
+    fn link(kind: &str, path: &str, href: &str) -> Link {
+        Link {
+            kind: kind.into(),
+            path: path.into(),
+            href: href.into(),
+        }
+    }
+
+    fn parse(html: &str) -> Vec<Link> {
+        let mut links = Vec::new();
+        parse_all_items(html.as_bytes(), "foo", |link| {
+            links.push(link);
+            Ok(())
+        })
+        .unwrap();
+        links
+    }
+
+    #[test]
+    fn parse_items() {
+        assert_eq!(
+            parse(ALL_HTML),
+            vec![
+                link("struct", "foo::Foo", "struct.Foo.html"),
+                link("struct", "foo::bar::Baz", "bar/struct.Baz.html"),
+                link("macro", "foo::quux", "macro.quux.html"),
+            ]
+        );
+        assert_eq!(
+            parse(OLD_ALL_HTML),
+            vec![link("fn", "foo::run", "fn.run.html")]
+        );
+    }
+
+    #[test]
+    fn parse_stability() {
+        assert_eq!(
+            parse_module_stability(INDEX_HTML.as_bytes()).unwrap(),
+            [("struct.Foo.html".to_owned(), Stability::Deprecated)].into()
+        );
+        assert_eq!(
+            parse_module_stability(BAR_INDEX_HTML.as_bytes()).unwrap(),
+            [("struct.Baz.html".to_owned(), Stability::Unstable)].into()
+        );
+    }
+
+    #[test_case(0, false; "empty")]
+    #[test_case(1, false; "single item")]
+    #[test_case(CHUNK_SIZE / 10, true; "multiple chunks")]
+    fn chunks_are_valid_json(count: usize, multiple_chunks: bool) {
+        let html = format!(
+            r#"<ul class="all-items">{}</ul>"#,
+            r#"<li><a href="fn.run.html">run</a></li>"#.repeat(count)
+        );
+        let mut chunks = Vec::new();
+        write_items(
+            html.as_bytes(),
+            "foo",
+            "/foo/0.1.0/foo",
+            |_| Ok(Stability::Stable),
+            |chunk| {
+                chunks.push(chunk);
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(chunks.len() > 1, multiple_chunks);
+
+        let parsed: serde_json::Value = serde_json::from_slice(&chunks.concat()).unwrap();
+        let item = json!({
+            "kind": "fn",
+            "path": "foo::run",
+            "url": "/foo/0.1.0/foo/fn.run.html",
+            "stability": "stable",
+        });
+        assert_eq!(parsed, json!(vec![item; count]));
+    }
+
+    #[test_case(true; "archive storage")]
+    #[test_case(false; "file storage")]
+    fn all_items_json(archive_storage: bool) {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .archive_storage(archive_storage)
+                .rustdoc_file_with("foo/all.html", ALL_HTML.as_bytes())
+                .rustdoc_file_with("foo/index.html", INDEX_HTML.as_bytes())
+                .rustdoc_file_with("foo/bar/index.html", BAR_INDEX_HTML.as_bytes())
+                .create()?;
+            let web = env.frontend();
+
+            let response = web.get("/crate/foo/latest/all.json").send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_cache_control(&response, CachePolicy::ForeverInCdn, &env.config());
+            assert_eq!(response.headers()["content-type"], "application/json");
+            let value: serde_json::Value = response.json()?;
+            assert_eq!(
+                value,
+                json!([
+                    {
+                        "kind": "struct",
+                        "path": "foo::Foo",
+                        "url": "/foo/0.1.0/foo/struct.Foo.html",
+                        "stability": "deprecated",
+                    },
+                    {
+                        "kind": "struct",
+                        "path": "foo::bar::Baz",
+                        "url": "/foo/0.1.0/foo/bar/struct.Baz.html",
+                        "stability": "unstable",
+                    },
+                    {
+                        "kind": "macro",
+                        "path": "foo::quux",
+                        "url": "/foo/0.1.0/foo/macro.quux.html",
+                        "stability": "stable",
+                    },
+                ])
+            );
+
+            assert_redirect_cached(
+                "/crate/foo/0.1/all.json",
+                "/crate/foo/0.1.0/all.json",
+                CachePolicy::ForeverInCdn,
+                web,
+                &env.config(),
+            )?;
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn all_items_json_not_found() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            env.fake_release()
+                .name("bar")
+                .version("0.1.0")
+                .build_result_failed()
+                .create()?;
+            let web = env.frontend();
+
+            for url in [
+                "/crate/foo/0.1.0/all.json",
+                "/crate/bar/0.1.0/all.json",
+                "/crate/foo/0.2.0/all.json",
+            ] {
+                let response = web.get(url).send()?;
+                assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", url);
+                assert_eq!(response.headers()["content-type"], "application/json");
+            }
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,66 @@
+//! Bearer token authentication of the endpoints that change data.
+
+use crate::{
+    config::NamedToken,
+    web::error::{AxumNope, JsonAxumNope},
+    Config,
+};
+use axum::{
+    http::{header::AUTHORIZATION, HeaderMap, Request as AxumHttpRequest},
+    middleware::Next,
+    response::{IntoResponse, Response as AxumResponse},
+};
+use std::sync::Arc;
+
+/// The token given in an `Authorization: Bearer <token>` header.
+pub(crate) fn bearer_token(headers: &HeaderMap) -> Result<&str, AxumNope> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(AxumNope::Unauthorized)
+}
+
+/// Checks the bearer token against the configured `tokens`, returning the name of the matching
+/// one so actions can be attributed to it.
+pub(crate) fn authenticate_named_token(
+    headers: &HeaderMap,
+    tokens: &[NamedToken],
+) -> Result<String, AxumNope> {
+    let token = bearer_token(headers)?;
+    tokens
+        .iter()
+        .find(|named_token| constant_time_eq(named_token.token.as_bytes(), token.as_bytes()))
+        .map(|named_token| named_token.name.clone())
+        .ok_or(AxumNope::Unauthorized)
+}
+
+/// The name of the token a request was authenticated with by [`require_named_token`].
+#[derive(Debug, Clone)]
+pub(crate) struct TokenName(pub(crate) String);
+
+/// Authenticates the request with one of the `tokens` of the config before its body is read,
+/// the handler gets the name of the token as `Extension<TokenName>`.
+pub(crate) async fn require_named_token<B>(
+    mut request: AxumHttpRequest<B>,
+    next: Next<B>,
+    tokens: fn(&Config) -> &[NamedToken],
+) -> AxumResponse {
+    let config = request
+        .extensions()
+        .get::<Arc<Config>>()
+        .cloned()
+        .expect("missing config extension in request");
+    match authenticate_named_token(request.headers(), tokens(&config)) {
+        Ok(name) => {
+            request.extensions_mut().insert(TokenName(name));
+            next.run(request).await
+        }
+        Err(err) => JsonAxumNope(err).into_response(),
+    }
+}
+
+/// Compares the whole input, so the time taken doesn't tell how much of a token matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
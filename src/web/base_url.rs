@@ -0,0 +1,255 @@
+//! Resolution of the public base URL used in generated absolute links.
+
+use super::error::AxumNope;
+use crate::Config;
+use anyhow::anyhow;
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use std::sync::Arc;
+
+/// The scheme and host docs.rs is publicly served from, without a trailing slash.
+///
+/// This is the configured `DOCSRS_PUBLIC_BASE_URL`, unless `DOCSRS_TRUST_FORWARDED_HEADERS`
+/// is set and the request came through a proxy setting `X-Forwarded-Host` and
+/// `X-Forwarded-Proto`. Responses then vary on both headers, see the cache middleware.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BaseUrl(pub(crate) String);
+
+impl BaseUrl {
+    pub(crate) fn resolve(
+        config: &Config,
+        forwarded_host: Option<&str>,
+        forwarded_proto: Option<&str>,
+    ) -> Self {
+        if !config.trust_forwarded_headers {
+            return BaseUrl(config.public_base_url.clone());
+        }
+
+        // proxies can append their own values, the first one is the one the client sent to
+        let first_value = |value: &str| value.split(',').next().unwrap_or("").trim().to_owned();
+
+        let host = forwarded_host.map(first_value).filter(|host| {
+            !host.is_empty()
+                && host
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':' | '[' | ']'))
+        });
+        let host = match host {
+            Some(host) => host,
+            None => return BaseUrl(config.public_base_url.clone()),
+        };
+
+        let scheme = forwarded_proto
+            .map(first_value)
+            .filter(|proto| proto == "http" || proto == "https")
+            .unwrap_or_else(|| {
+                // fall back to the scheme of the configured URL
+                config
+                    .public_base_url
+                    .split_once("://")
+                    .map_or("https", |(scheme, _)| scheme)
+                    .to_owned()
+            });
+
+        BaseUrl(format!("{}://{}", scheme, host))
+    }
+
+    pub(crate) fn from_iron(req: &iron::Request) -> Self {
+        let header = |name: &str| {
+            req.headers
+                .get_raw(name)
+                .and_then(|values| values.get(0))
+                .and_then(|value| std::str::from_utf8(value).ok())
+        };
+
+        let config = req
+            .extensions
+            .get::<Config>()
+            .expect("missing Config from the request extensions");
+
+        Self::resolve(
+            config,
+            header("x-forwarded-host"),
+            header("x-forwarded-proto"),
+        )
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for BaseUrl
+where
+    S: Send + Sync,
+{
+    type Rejection = AxumNope;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let config = parts
+            .extensions
+            .get::<Arc<Config>>()
+            .ok_or_else(|| AxumNope::InternalError(anyhow!("missing config extension")))?;
+
+        let header = |name: &str| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        };
+
+        Ok(Self::resolve(
+            config,
+            header("x-forwarded-host"),
+            header("x-forwarded-proto"),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BaseUrl;
+    use crate::test::{wrapper, TestEnvironment};
+
+    #[test]
+    fn forwarded_headers_are_ignored_by_default() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.public_base_url = "https://docs.example.com".into();
+            });
+            let config = env.config();
+
+            assert_eq!(
+                BaseUrl::resolve(&config, Some("evil.example.com"), Some("http")),
+                BaseUrl("https://docs.example.com".into())
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn trusted_forwarded_headers() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.public_base_url = "https://docs.example.com".into();
+                config.trust_forwarded_headers = true;
+            });
+            let config = env.config();
+
+            assert_eq!(
+                BaseUrl::resolve(&config, Some("mirror.example.com, proxy"), Some("http")),
+                BaseUrl("http://mirror.example.com".into())
+            );
+            assert_eq!(
+                BaseUrl::resolve(&config, Some("mirror.example.com:8080"), None),
+                BaseUrl("https://mirror.example.com:8080".into())
+            );
+            assert_eq!(
+                BaseUrl::resolve(&config, Some("mirror.example.com/\"><"), Some("https")),
+                BaseUrl("https://docs.example.com".into())
+            );
+            assert_eq!(
+                BaseUrl::resolve(&config, None, Some("http")),
+                BaseUrl("https://docs.example.com".into())
+            );
+            Ok(())
+        })
+    }
+
+    fn vary_headers(env: &TestEnvironment, path: &str) -> anyhow::Result<Vec<String>> {
+        Ok(env
+            .frontend()
+            .get(path)
+            .send()?
+            .headers()
+            .get_all("vary")
+            .iter()
+            .map(|value| value.to_str().unwrap().to_owned())
+            .collect())
+    }
+
+    #[test]
+    fn responses_dont_vary_on_untrusted_forwarded_headers() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+
+            // rendered by axum and by iron
+            for path in &["/sitemap.xml", "/foo/0.1.0/foo/"] {
+                assert!(
+                    !vary_headers(env, path)?
+                        .iter()
+                        .any(|vary| vary.contains("X-Forwarded-Host")),
+                    "{} varies on forwarded headers that aren't trusted",
+                    path
+                );
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn responses_vary_on_trusted_forwarded_headers() {
+        wrapper(|env| {
+            env.override_config(|config| config.trust_forwarded_headers = true);
+            env.fake_release().name("foo").version("0.1.0").create()?;
+
+            // rendered by axum and by iron
+            for path in &["/sitemap.xml", "/foo/0.1.0/foo/"] {
+                assert!(
+                    vary_headers(env, path)?
+                        .iter()
+                        .any(|vary| vary == "X-Forwarded-Host, X-Forwarded-Proto"),
+                    "{} doesn't vary on the forwarded headers",
+                    path
+                );
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn generated_absolute_urls_use_the_configured_host() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.public_base_url = "https://docs.example.org".into();
+            });
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .source_file("src/lib.rs", b"")
+                .create()?;
+            let web = env.frontend();
+
+            for path in &[
+                "/sitemap.xml",
+                "/-/sitemap/f/sitemap.xml",
+                "/releases/feed",
+                "/-/static/opensearch.xml",
+                "/crate/foo/0.1.0",
+                "/crate/foo/0.1.0/builds",
+                "/crate/foo/0.1.0/features",
+                "/crate/foo/0.1.0/source/",
+                "/foo/0.1.0/foo/",
+            ] {
+                let resp = web.get(path).send()?;
+                assert!(resp.status().is_success(), "{} failed", path);
+
+                let link = resp
+                    .headers()
+                    .get("link")
+                    .map(|link| link.to_str().unwrap().to_owned())
+                    .unwrap_or_default();
+                let body = resp.text()?;
+                assert!(
+                    body.contains("https://docs.example.org/")
+                        || link.contains("https://docs.example.org/"),
+                    "{} doesn't link to the configured host",
+                    path
+                );
+                assert!(
+                    !body.contains("https://docs.rs/") && !link.contains("https://docs.rs/"),
+                    "{} links to the default host",
+                    path
+                );
+            }
+
+            Ok(())
+        })
+    }
+}
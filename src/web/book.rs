@@ -0,0 +1,183 @@
+use super::{cache::CachePolicy, error::AxumNope, file::File, match_version_axum, MatchSemver};
+use crate::{
+    db::Pool,
+    storage::{PathNotFoundError, Storage},
+    utils::spawn_blocking,
+    web::error::AxumResult,
+    Config,
+};
+use axum::{
+    extract::{Extension, Path},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::instrument;
+
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct BookHandlerParams {
+    name: String,
+    version: String,
+    #[serde(default)]
+    path: String,
+}
+
+/// Serves the mdBook a crate ships next to its API documentation.
+///
+/// The book is built from the `book/` directory of the crate and stored in its own archive,
+/// see `book_archive_path`.
+#[instrument(skip(pool, storage, config))]
+pub(crate) async fn book_handler(
+    Path(BookHandlerParams {
+        mut name,
+        version,
+        path,
+    }): Path<BookHandlerParams>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(config): Extension<Arc<Config>>,
+    Extension(pool): Extension<Pool>,
+) -> AxumResult<impl IntoResponse> {
+    if !config.enable_books {
+        return Err(AxumNope::ResourceNotFound);
+    }
+
+    let v = match_version_axum(&pool, &name, Some(&version)).await?;
+    if let Some(new_name) = &v.corrected_name {
+        name = new_name.to_string();
+    }
+    let (version, is_latest_url) = match v.version {
+        MatchSemver::Latest((version, _)) => (version, true),
+        MatchSemver::Exact((version, _)) => (version, false),
+        MatchSemver::Semver((version, _)) => {
+            return Ok(super::axum_cached_redirect(
+                &format!("/crate/{}/{}/book/{}", name, version, path),
+                CachePolicy::ForeverInCdn,
+            )?
+            .into_response());
+        }
+    };
+
+    let mut path = path;
+    if path.is_empty() || path.ends_with('/') {
+        path.push_str("index.html");
+    }
+
+    let blob = spawn_blocking(move || {
+        let has_book: bool = pool
+            .get()?
+            .query_opt(
+                "SELECT releases.has_book
+                 FROM releases
+                 INNER JOIN crates ON crates.id = releases.crate_id
+                 WHERE crates.name = $1 AND releases.version = $2",
+                &[&name, &version],
+            )?
+            .map_or(false, |row| row.get(0));
+        if !has_book {
+            return Err(AxumNope::ResourceNotFound.into());
+        }
+
+        match storage.fetch_book_file(&name, &version, &path) {
+            Ok(blob) => Ok(blob),
+            Err(err) if err.downcast_ref::<PathNotFoundError>().is_some() => {
+                Err(AxumNope::ResourceNotFound.into())
+            }
+            Err(err) => Err(err),
+        }
+    })
+    .await?;
+
+    let mut response = File(blob).into_response();
+    // the book is replaced when the crate is rebuilt, so it can't be cached forever in the browser
+    response.extensions_mut().insert(if is_latest_url {
+        CachePolicy::ForeverInCdn
+    } else {
+        CachePolicy::ForeverInCdnAndStaleInBrowser
+    });
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test::{assert_cache_control, assert_redirect, wrapper};
+    use crate::web::cache::CachePolicy;
+
+    #[test]
+    fn serves_book() {
+        wrapper(|env| {
+            env.override_config(|config| config.enable_books = true);
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .book_file("index.html", b"<html>the book</html>")
+                .book_file("chapter/one.html", b"<html>chapter one</html>")
+                .create()?;
+            let web = env.frontend();
+
+            let resp = web.get("/crate/foo/0.1.0/book/").send()?;
+            assert!(resp.status().is_success());
+            assert_cache_control(
+                &resp,
+                CachePolicy::ForeverInCdnAndStaleInBrowser,
+                &env.config(),
+            );
+            assert_eq!(resp.text()?, "<html>the book</html>");
+
+            let resp = web.get("/crate/foo/latest/book/chapter/one.html").send()?;
+            assert!(resp.status().is_success());
+            assert_cache_control(&resp, CachePolicy::ForeverInCdn, &env.config());
+            assert_eq!(resp.text()?, "<html>chapter one</html>");
+
+            assert_redirect(
+                "/crate/foo/0.1/book/chapter/one.html",
+                "/crate/foo/0.1.0/book/chapter/one.html",
+                web,
+            )?;
+            assert_eq!(
+                web.get("/crate/foo/0.1.0/book/missing.html")
+                    .send()?
+                    .status(),
+                404
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn book_not_found_without_book() {
+        wrapper(|env| {
+            env.override_config(|config| config.enable_books = true);
+            env.fake_release().name("foo").version("0.1.0").create()?;
+
+            assert_eq!(
+                env.frontend()
+                    .get("/crate/foo/0.1.0/book/")
+                    .send()?
+                    .status(),
+                404
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn book_not_found_when_disabled() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .book_file("index.html", b"<html>the book</html>")
+                .create()?;
+
+            assert_eq!(
+                env.frontend()
+                    .get("/crate/foo/0.1.0/book/")
+                    .send()?
+                    .status(),
+                404
+            );
+            Ok(())
+        })
+    }
+}
@@ -1,17 +1,23 @@
 use crate::{
     db::Pool,
+    docbuilder::BuildFailure,
     impl_axum_webpage,
     utils::spawn_blocking,
     web::{
-        error::{AxumNope, AxumResult},
-        file::File,
+        base_url::BaseUrl,
+        cache::CachePolicy,
+        crate_details::Sizes,
+        error::{AxumNope, AxumResult, JsonAxumResult},
+        output_format::{OutputFormat, RequestedFormat},
         MetaData,
     },
-    Config, Storage,
+    Storage,
 };
 use axum::{
     extract::{Extension, Path},
-    response::IntoResponse,
+    http::header::ACCESS_CONTROL_ALLOW_ORIGIN,
+    response::{IntoResponse, Response as AxumResponse},
+    Json,
 };
 use chrono::{DateTime, Utc};
 use serde::Serialize;
@@ -25,37 +31,184 @@ pub(crate) struct BuildDetails {
     build_status: bool,
     build_time: DateTime<Utc>,
     output: String,
+    /// The `cargo` command documenting the default target, `None` for builds before it was
+    /// recorded
+    command: Option<String>,
+    /// The likely cause of a failed build, `None` for successful builds
+    failure_category: Option<BuildFailure>,
+    /// Sizes of the release, from its newest build
+    sizes: Sizes,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 struct BuildDetailsPage {
     metadata: MetaData,
     build_details: BuildDetails,
+    /// Build ids never change, so this keeps pointing to the same build after a rebuild
+    canonical_url: String,
+    /// Whether the documentation was rejected for exceeding the size limit
+    docs_too_large: bool,
 }
 
 impl_axum_webpage! {
     BuildDetailsPage = "crate/build_details.html",
+    canonical_url = |page| Some(page.canonical_url.clone()),
+}
+
+/// Loads a build of a release and its log, which is either stored in the database for older
+/// builds, or in the storage.
+fn load_build(
+    conn: &mut postgres::Client,
+    storage: &Storage,
+    id: i32,
+    name: &str,
+    version: &str,
+) -> anyhow::Result<(postgres::Row, String)> {
+    let row = conn
+        .query_opt(
+            "SELECT
+                 builds.rustc_version,
+                 builds.docsrs_version,
+                 builds.build_status,
+                 builds.build_time,
+                 builds.output,
+                 builds.command,
+                 builds.failure_category,
+                 releases.default_target,
+                 doc_sizes.documentation,
+                 doc_sizes.sources,
+                 doc_sizes.search_index
+             FROM builds
+             INNER JOIN releases ON releases.id = builds.rid
+             INNER JOIN crates ON releases.crate_id = crates.id
+             LEFT JOIN doc_sizes ON doc_sizes.release_id = releases.id
+             WHERE builds.id = $1 AND crates.name = $2 AND releases.version = $3",
+            &[&id, &name, &version],
+        )?
+        .ok_or(AxumNope::BuildNotFound)?;
+
+    let output = if let Some(output) = row.get("output") {
+        output
+    } else {
+        let target: String = row.get("default_target");
+        storage.get_build_log(id, &target)?
+    };
+    Ok((row, output))
 }
 
 pub(crate) async fn build_details_handler(
     Path((name, version, id)): Path<(String, String, String)>,
     Extension(pool): Extension<Pool>,
-    Extension(config): Extension<Arc<Config>>,
     Extension(storage): Extension<Arc<Storage>>,
-) -> AxumResult<impl IntoResponse> {
+    BaseUrl(base_url): BaseUrl,
+    RequestedFormat(format): RequestedFormat,
+) -> AxumResult<AxumResponse> {
+    if id == "latest" {
+        let latest_id: i32 = spawn_blocking({
+            let (name, version) = (name.clone(), version.clone());
+            move || {
+                let mut conn = pool.get()?;
+                Ok(conn
+                    .query_opt(
+                        "SELECT builds.id
+                         FROM builds
+                         INNER JOIN releases ON releases.id = builds.rid
+                         INNER JOIN crates ON releases.crate_id = crates.id
+                         WHERE crates.name = $1 AND releases.version = $2
+                         ORDER BY builds.build_time DESC, builds.id DESC
+                         LIMIT 1",
+                        &[&name, &version],
+                    )?
+                    .ok_or(AxumNope::BuildNotFound)?
+                    .get(0))
+            }
+        })
+        .await?;
+
+        // the latest build changes when the release is rebuilt
+        return Ok(super::axum_cached_redirect(
+            &format!("/crate/{}/{}/builds/{}", name, version, latest_id),
+            CachePolicy::NoCaching,
+        )?
+        .into_response());
+    }
+
+    let id: i32 = id.parse().map_err(|_| AxumNope::BuildNotFound)?;
+
+    let (row, output, metadata) = spawn_blocking({
+        let (name, version) = (name.clone(), version.clone());
+        move || {
+            let mut conn = pool.get()?;
+            let (row, output) = load_build(&mut conn, &storage, id, &name, &version)?;
+            Ok((
+                row,
+                output,
+                MetaData::from_crate(&mut conn, &name, &version, &version)?,
+            ))
+        }
+    })
+    .await?;
+
+    let build_details = BuildDetails {
+        id,
+        rustc_version: row.get("rustc_version"),
+        docsrs_version: row.get("docsrs_version"),
+        build_status: row.get("build_status"),
+        build_time: row.get("build_time"),
+        output,
+        command: row.get("command"),
+        failure_category: BuildFailure::from_column(
+            row.get("failure_category"),
+            row.get("build_status"),
+        ),
+        sizes: Sizes::from_row(&row),
+    };
+    if format == OutputFormat::Json {
+        return Ok((Extension(CachePolicy::NoCaching), Json(build_details)).into_response());
+    }
+
+    Ok(BuildDetailsPage {
+        metadata,
+        docs_too_large: build_details.failure_category == Some(BuildFailure::DocsTooLarge),
+        build_details,
+        canonical_url: format!("{}/crate/{}/{}/builds/{}", base_url, name, version, id),
+    }
+    .into_response())
+}
+
+/// The toolchain and configuration of a build, to reproduce it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct BuildEnvironment {
+    id: i32,
+    rustc_version: String,
+    docsrs_version: String,
+    /// The output of `cargo --version`, `None` for builds before it was recorded
+    cargo_version: Option<String>,
+    /// The target that was documented, `None` for builds before it was recorded
+    target: Option<String>,
+    /// The `--cfg` flags passed to `rustc` and `rustdoc`, `None` for builds before they were
+    /// recorded
+    cfgs: Option<Vec<String>>,
+    build_time: DateTime<Utc>,
+}
+
+pub(crate) async fn build_environment_json_handler(
+    Path((name, version, id)): Path<(String, String, String)>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
     let id: i32 = id.parse().map_err(|_| AxumNope::BuildNotFound)?;
 
-    let (row, output, metadata) = spawn_blocking(move || {
+    let environment = spawn_blocking(move || {
         let mut conn = pool.get()?;
         let row = conn
             .query_opt(
                 "SELECT
                      builds.rustc_version,
                      builds.docsrs_version,
-                     builds.build_status,
-                     builds.build_time,
-                     builds.output,
-                     releases.default_target
+                     builds.cargo_version,
+                     builds.target,
+                     builds.cfgs,
+                     builds.build_time
                  FROM builds
                  INNER JOIN releases ON releases.id = builds.rid
                  INNER JOIN crates ON releases.crate_id = crates.id
@@ -63,41 +216,32 @@ pub(crate) async fn build_details_handler(
                 &[&id, &name, &version],
             )?
             .ok_or(AxumNope::BuildNotFound)?;
-
-        let output = if let Some(output) = row.get("output") {
-            output
-        } else {
-            let target: String = row.get("default_target");
-            let path = format!("build-logs/{}/{}.txt", id, target);
-            let file = File::from_path(&storage, &path, &config)?;
-            String::from_utf8(file.0.content)?
-        };
-
-        Ok((
-            row,
-            output,
-            MetaData::from_crate(&mut conn, &name, &version, &version)?,
-        ))
-    })
-    .await?;
-
-    Ok(BuildDetailsPage {
-        metadata,
-        build_details: BuildDetails {
+        Ok(BuildEnvironment {
             id,
             rustc_version: row.get("rustc_version"),
             docsrs_version: row.get("docsrs_version"),
-            build_status: row.get("build_status"),
+            cargo_version: row.get("cargo_version"),
+            target: row.get("target"),
+            cfgs: row.get("cfgs"),
             build_time: row.get("build_time"),
-            output,
-        },
-    }
-    .into_response())
+        })
+    })
+    .await?;
+
+    Ok((
+        Extension(CachePolicy::NoStoreMustRevalidate),
+        [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+        Json(environment),
+    ))
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::test::{wrapper, FakeBuild};
+    use crate::docbuilder::DocSizes;
+    use crate::storage::{build_log_path, compress, Blob, CompressionAlgorithm};
+    use crate::test::{assert_cache_control, assert_redirect_cached, wrapper, FakeBuild};
+    use crate::web::cache::CachePolicy;
+    use chrono::{TimeZone, Utc};
     use kuchiki::traits::TendrilSink;
     use test_case::test_case;
 
@@ -133,6 +277,119 @@ mod tests {
         });
     }
 
+    #[test]
+    fn doc_sizes() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .doc_sizes(DocSizes {
+                    documentation: 2_500_000,
+                    sources: 2048,
+                    search_index: 512,
+                })
+                .create()?;
+            env.fake_release().name("bar").version("0.1.0").create()?;
+
+            let sizes = |name: &str| -> Result<Vec<(String, String)>, anyhow::Error> {
+                let page = kuchiki::parse_html().one(
+                    env.frontend()
+                        .get(&format!("/crate/{}/0.1.0/builds", name))
+                        .send()?
+                        .text()?,
+                );
+                let node = page.select("ul > li a.release").unwrap().next().unwrap();
+                let url = node.attributes.borrow().get("href").unwrap().to_owned();
+
+                let page = kuchiki::parse_html().one(env.frontend().get(&url).send()?.text()?);
+                Ok(page
+                    .select("table.doc-sizes td")
+                    .unwrap()
+                    .map(|td| {
+                        let title = td.attributes.borrow().get("title").unwrap_or("").to_owned();
+                        (title, td.text_contents())
+                    })
+                    .collect())
+            };
+
+            assert_eq!(
+                sizes("foo")?,
+                [
+                    ("2500000 bytes".to_owned(), "2.38 MB".to_owned()),
+                    ("2048 bytes".to_owned(), "2 KB".to_owned()),
+                    ("512 bytes".to_owned(), "512 B".to_owned()),
+                ]
+            );
+            assert!(sizes("bar")?
+                .iter()
+                .all(|(title, text)| title.is_empty() && text == "unknown"));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn build_command() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .builds(vec![
+                    FakeBuild::default().command("DOCS_RS=1 cargo rustdoc --lib --features 'a b'")
+                ])
+                .create()?;
+            env.fake_release().name("bar").version("0.1.0").create()?;
+            let build_id = |name: &str| -> Result<i32, anyhow::Error> {
+                Ok(env
+                    .db()
+                    .conn()
+                    .query_one(
+                        "SELECT builds.id
+                         FROM builds
+                         INNER JOIN releases ON releases.id = builds.rid
+                         INNER JOIN crates ON crates.id = releases.crate_id
+                         WHERE crates.name = $1",
+                        &[&name],
+                    )?
+                    .get(0))
+            };
+            let web = env.frontend();
+
+            let url = format!("/crate/foo/0.1.0/builds/{}", build_id("foo")?);
+            let page = kuchiki::parse_html().one(web.get(&url).send()?.text()?);
+            assert_eq!(
+                page.select_first("[data-id=build-command]")
+                    .unwrap()
+                    .text_contents(),
+                "DOCS_RS=1 cargo rustdoc --lib --features 'a b'"
+            );
+            let value: serde_json::Value =
+                web.get(&format!("{}?format=json", url)).send()?.json()?;
+            assert_eq!(
+                value["command"],
+                "DOCS_RS=1 cargo rustdoc --lib --features 'a b'"
+            );
+
+            // builds before the command was recorded
+            let url = format!("/crate/bar/0.1.0/builds/{}", build_id("bar")?);
+            let page = kuchiki::parse_html().one(web.get(&url).send()?.text()?);
+            assert_eq!(
+                page.select_first("[data-id=build-command]")
+                    .unwrap()
+                    .text_contents(),
+                "not recorded"
+            );
+            let value: serde_json::Value = web
+                .get(&url)
+                .header("accept", "application/json")
+                .send()?
+                .json()?;
+            assert!(value["command"].is_null());
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn s3_build_logs() {
         wrapper(|env| {
@@ -163,6 +420,49 @@ mod tests {
         });
     }
 
+    #[test_case(None ; "plain text")]
+    #[test_case(Some(CompressionAlgorithm::Gzip) ; "gzip without metadata")]
+    fn legacy_s3_build_logs(compression: Option<CompressionAlgorithm>) {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .builds(vec![FakeBuild::default().no_s3_build_log()])
+                .create()?;
+
+            let build_id: i32 = env
+                .db()
+                .conn()
+                .query_one("SELECT id FROM builds", &[])?
+                .get(0);
+
+            let mut content = b"A build log".to_vec();
+            if let Some(alg) = compression {
+                content = compress(content.as_slice(), alg)?;
+            }
+            env.storage().store_blobs(vec![Blob {
+                path: build_log_path(build_id, "x86_64-unknown-linux-gnu"),
+                mime: "text/plain".into(),
+                date_updated: Utc::now(),
+                content,
+                compression: None,
+            }])?;
+
+            let page = kuchiki::parse_html().one(
+                env.frontend()
+                    .get(&format!("/crate/foo/0.1.0/builds/{}", build_id))
+                    .send()?
+                    .text()?,
+            );
+
+            let log = page.select("pre").unwrap().next().unwrap().text_contents();
+
+            assert!(log.contains("A build log"));
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn both_build_logs() {
         wrapper(|env| {
@@ -212,4 +512,164 @@ mod tests {
             Ok(())
         });
     }
+
+    #[test]
+    fn build_of_other_release() {
+        wrapper(|env| {
+            let release_id = env.fake_release().name("foo").version("0.1.0").create()?;
+            env.fake_release().name("foo").version("0.2.0").create()?;
+            env.fake_release().name("bar").version("0.1.0").create()?;
+
+            let id: i32 = env
+                .db()
+                .conn()
+                .query_one("SELECT id FROM builds WHERE rid = $1", &[&release_id])?
+                .get(0);
+            let web = env.frontend();
+
+            for path in [
+                format!("/crate/foo/0.2.0/builds/{}", id),
+                format!("/crate/bar/0.1.0/builds/{}", id),
+            ] {
+                let res = web.get(&path).send()?;
+                assert_eq!(res.status(), 404);
+                assert!(res.text()?.contains("no such build"));
+            }
+
+            let res = web.get(&format!("/crate/foo/0.1.0/builds/{}", id)).send()?;
+            assert!(res.status().is_success());
+            let link = res.headers()["link"].to_str()?;
+            assert!(link.ends_with(&format!(
+                r#"/crate/foo/0.1.0/builds/{}>; rel="canonical""#,
+                id
+            )));
+            assert!(res.text()?.contains("copy-build-permalink"));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn latest_build() {
+        wrapper(|env| {
+            let release_id = env
+                .fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .builds(vec![
+                    FakeBuild::default()
+                        .build_time(Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap()),
+                    FakeBuild::default()
+                        .build_time(Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap()),
+                ])
+                .create()?;
+            env.fake_release().name("bar").version("0.1.0").create()?;
+
+            let id: i32 = env
+                .db()
+                .conn()
+                .query_one(
+                    "SELECT id FROM builds WHERE rid = $1 ORDER BY build_time DESC LIMIT 1",
+                    &[&release_id],
+                )?
+                .get(0);
+            let web = env.frontend();
+
+            assert_redirect_cached(
+                "/crate/foo/0.1.0/builds/latest",
+                &format!("/crate/foo/0.1.0/builds/{}", id),
+                CachePolicy::NoCaching,
+                web,
+                &env.config(),
+            )?;
+
+            assert_eq!(
+                web.get("/crate/foo/0.2.0/builds/latest").send()?.status(),
+                404
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn build_environment() {
+        wrapper(|env| {
+            let release_id = env
+                .fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .builds(vec![
+                    // built before the environment was recorded
+                    FakeBuild::default()
+                        .build_time(Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap()),
+                    FakeBuild::default()
+                        .build_time(Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap())
+                        .environment(
+                            "cargo 2.0.0-nightly (000000000 1970-01-01)",
+                            "x86_64-unknown-linux-gnu",
+                            &["docsrs"],
+                        ),
+                ])
+                .create()?;
+            env.fake_release().name("foo").version("0.2.0").create()?;
+
+            let ids: Vec<i32> = env
+                .db()
+                .conn()
+                .query(
+                    "SELECT id FROM builds WHERE rid = $1 ORDER BY build_time",
+                    &[&release_id],
+                )?
+                .into_iter()
+                .map(|row| row.get(0))
+                .collect();
+            let web = env.frontend();
+
+            let fetch = |id: i32| -> anyhow::Result<serde_json::Value> {
+                let response = web
+                    .get(&format!("/crate/foo/0.1.0/builds/{}/environment.json", id))
+                    .send()?;
+                assert_eq!(response.status(), 200);
+                assert_cache_control(&response, CachePolicy::NoStoreMustRevalidate, &env.config());
+                Ok(response.json()?)
+            };
+
+            assert_eq!(
+                fetch(ids[1])?,
+                serde_json::json!({
+                    "id": ids[1],
+                    "rustc_version": "rustc 2.0.0-nightly (000000000 1970-01-01)",
+                    "docsrs_version": "docs.rs 1.0.0 (000000000 1970-01-01)",
+                    "cargo_version": "cargo 2.0.0-nightly (000000000 1970-01-01)",
+                    "target": "x86_64-unknown-linux-gnu",
+                    "cfgs": ["docsrs"],
+                    "build_time": "2021-01-01T00:00:00Z",
+                })
+            );
+            assert_eq!(
+                fetch(ids[0])?,
+                serde_json::json!({
+                    "id": ids[0],
+                    "rustc_version": "rustc 2.0.0-nightly (000000000 1970-01-01)",
+                    "docsrs_version": "docs.rs 1.0.0 (000000000 1970-01-01)",
+                    "cargo_version": null,
+                    "target": null,
+                    "cfgs": null,
+                    "build_time": "2020-01-01T00:00:00Z",
+                })
+            );
+
+            // the build has to belong to the release
+            for path in [
+                format!("/crate/foo/0.2.0/builds/{}/environment.json", ids[0]),
+                format!("/crate/bar/0.1.0/builds/{}/environment.json", ids[0]),
+                "/crate/foo/0.1.0/builds/invalid/environment.json".to_owned(),
+            ] {
+                assert_eq!(web.get(&path).send()?.status(), 404, "{}", path);
+            }
+
+            Ok(())
+        });
+    }
 }
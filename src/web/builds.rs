@@ -1,20 +1,39 @@
 use super::{cache::CachePolicy, MatchSemver};
 use crate::{
     db::Pool,
-    docbuilder::Limits,
+    docbuilder::{BuildFailure, Limits},
     impl_axum_webpage,
-    utils::spawn_blocking,
-    web::{error::AxumResult, match_version_axum, MetaData},
+    utils::{get_config, parse_rustc_date, spawn_blocking, ConfigName},
+    web::{
+        auth::authenticate_named_token,
+        base_url::BaseUrl,
+        error::{AxumNope, AxumResult, JsonAxumResult},
+        match_version_axum,
+        output_format::{OutputFormat, RequestedFormat},
+        rate_limit::RateLimiter,
+        MetaData,
+    },
+    BuildQueue, Config,
 };
 use anyhow::Result;
 use axum::{
-    extract::{Extension, Path},
-    http::header::ACCESS_CONTROL_ALLOW_ORIGIN,
+    extract::{Extension, Path, Query},
+    http::{header::ACCESS_CONTROL_ALLOW_ORIGIN, HeaderMap, StatusCode},
     response::IntoResponse,
     Json,
 };
-use chrono::{DateTime, Utc};
-use serde::Serialize;
+use chrono::{DateTime, NaiveDate, Utc};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, sync::Arc, time::Instant};
+use tracing::info;
+
+/// Priority of rebuilds triggered through the web server, ahead of crates built after a release
+const REBUILD_PRIORITY: i32 = -10;
+/// Default and maximum number of releases returned by `/-/stale-builds.json`
+pub(super) const STALE_BUILDS_LIMIT: i64 = 1000;
+/// Number of releases on each page of the build matrix
+const MATRIX_VERSIONS: usize = 10;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub(crate) struct Build {
@@ -23,6 +42,8 @@ pub(crate) struct Build {
     docsrs_version: String,
     build_status: bool,
     build_time: DateTime<Utc>,
+    /// The likely cause of a failed build, `None` for successful builds
+    failure_category: Option<BuildFailure>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
@@ -40,6 +61,8 @@ impl_axum_webpage! {
 pub(crate) async fn build_list_handler(
     Path((name, req_version)): Path<(String, String)>,
     Extension(pool): Extension<Pool>,
+    BaseUrl(base_url): BaseUrl,
+    RequestedFormat(format): RequestedFormat,
 ) -> AxumResult<impl IntoResponse> {
     let (version, version_or_latest) = match match_version_axum(&pool, &name, Some(&req_version))
         .await?
@@ -70,11 +93,20 @@ pub(crate) async fn build_list_handler(
     })
     .await?;
 
+    if format == OutputFormat::Json {
+        return Ok((
+            Extension(CachePolicy::NoStoreMustRevalidate),
+            [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+            Json(builds),
+        )
+            .into_response());
+    }
+
     Ok(BuildsPage {
         metadata,
         builds,
         limits,
-        canonical_url: format!("https://docs.rs/crate/{}/latest/builds", name),
+        canonical_url: format!("{}/crate/{}/latest/builds", base_url, name),
     }
     .into_response())
 }
@@ -113,6 +145,96 @@ pub(crate) async fn build_list_json_handler(
         .into_response())
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct BuildTarget {
+    target: String,
+    successful: bool,
+    default: bool,
+}
+
+pub(crate) async fn build_targets_json_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let version = match match_version_axum(&pool, &name, Some(&req_version))
+        .await?
+        .assume_exact()?
+    {
+        MatchSemver::Exact((version, _)) | MatchSemver::Latest((version, _)) => version,
+        MatchSemver::Semver((version, _)) => {
+            return Ok(super::axum_cached_redirect(
+                &format!("/crate/{}/{}/targets.json", name, version),
+                CachePolicy::ForeverInCdn,
+            )?
+            .into_response());
+        }
+    };
+
+    let targets = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        get_build_targets(&mut conn, &name, &version)
+    })
+    .await?
+    .ok_or(AxumNope::VersionNotFound)?;
+
+    Ok((
+        Extension(CachePolicy::NoStoreMustRevalidate),
+        [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+        Json(targets),
+    )
+        .into_response())
+}
+
+/// Returns all targets documentation was built for, or `None` if the version was never built.
+///
+/// Only the default target is recorded when a build fails, so every other target in the
+/// list was built successfully.
+fn get_build_targets(
+    conn: &mut postgres::Client,
+    name: &str,
+    version: &str,
+) -> Result<Option<Vec<BuildTarget>>> {
+    let row = match conn.query_opt(
+        "SELECT releases.default_target,
+                releases.doc_targets
+         FROM releases
+         INNER JOIN crates ON releases.crate_id = crates.id
+         WHERE
+            crates.name = $1 AND
+            releases.version = $2 AND
+            EXISTS (SELECT 1 FROM builds WHERE builds.rid = releases.id)",
+        &[&name, &version],
+    )? {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+
+    Ok(Some(build_targets(
+        row.get("default_target"),
+        MetaData::parse_doc_targets(row.get("doc_targets")),
+    )))
+}
+
+/// The targets of a built release, see [`get_build_targets`].
+fn build_targets(default_target: String, doc_targets: Vec<String>) -> Vec<BuildTarget> {
+    let mut targets = vec![BuildTarget {
+        successful: doc_targets.contains(&default_target),
+        target: default_target.clone(),
+        default: true,
+    }];
+    targets.extend(
+        doc_targets
+            .into_iter()
+            .filter(|target| *target != default_target)
+            .map(|target| BuildTarget {
+                target,
+                successful: true,
+                default: false,
+            }),
+    );
+    targets
+}
+
 fn get_builds(conn: &mut postgres::Client, name: &str, version: &str) -> Result<Vec<Build>> {
     Ok(conn
         .query(
@@ -125,7 +247,8 @@ fn get_builds(conn: &mut postgres::Client, name: &str, version: &str) -> Result<
                 builds.rustc_version,
                 builds.docsrs_version,
                 builds.build_status,
-                builds.build_time
+                builds.build_time,
+                builds.failure_category
              FROM builds
              INNER JOIN releases ON releases.id = builds.rid
              INNER JOIN crates ON releases.crate_id = crates.id
@@ -140,19 +263,479 @@ fn get_builds(conn: &mut postgres::Client, name: &str, version: &str) -> Result<
             docsrs_version: row.get("docsrs_version"),
             build_status: row.get("build_status"),
             build_time: row.get("build_time"),
+            failure_category: BuildFailure::from_column(
+                row.get("failure_category"),
+                row.get("build_status"),
+            ),
         })
         .collect())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum TargetStatus {
+    Passed,
+    Failed,
+    NotBuilt,
+}
+
+/// A release of a crate with the targets it was built for, `None` if it was never built.
+#[derive(Debug, Clone)]
+struct MatrixRelease {
+    version: Version,
+    targets: Option<Vec<BuildTarget>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct BuildMatrixRow {
+    version: String,
+    /// The status of each target in [`BuildMatrix::targets`], in the same order
+    statuses: Vec<TargetStatus>,
+}
+
+/// The build status of the targets of a page of releases, newest releases first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct BuildMatrix {
+    /// Every target any release on the page was built for
+    targets: Vec<String>,
+    versions: Vec<BuildMatrixRow>,
+    page: usize,
+    /// `None` on the last page
+    next_page: Option<usize>,
+}
+
+impl BuildMatrix {
+    /// Only the default target is recorded for failed builds, so other targets of a release are
+    /// either built successfully or not at all, see [`get_build_targets`].
+    fn new(mut releases: Vec<MatrixRelease>, page: usize, per_page: usize) -> Self {
+        releases.sort_by(|a, b| b.version.cmp(&a.version));
+        let has_next_page = releases.len() > page * per_page;
+        let releases: Vec<_> = releases
+            .into_iter()
+            .skip((page - 1) * per_page)
+            .take(per_page)
+            .collect();
+
+        let targets: BTreeSet<&str> = releases
+            .iter()
+            .flat_map(|release| release.targets.iter().flatten())
+            .map(|target| target.target.as_str())
+            .collect();
+
+        let versions = releases
+            .iter()
+            .map(|release| BuildMatrixRow {
+                version: release.version.to_string(),
+                statuses: targets
+                    .iter()
+                    .map(|target| {
+                        match release
+                            .targets
+                            .iter()
+                            .flatten()
+                            .find(|built| built.target == *target)
+                        {
+                            Some(built) if built.successful => TargetStatus::Passed,
+                            Some(_) => TargetStatus::Failed,
+                            None => TargetStatus::NotBuilt,
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        BuildMatrix {
+            targets: targets.into_iter().map(str::to_owned).collect(),
+            versions,
+            page,
+            next_page: if has_next_page { Some(page + 1) } else { None },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct BuildMatrixPage {
+    metadata: MetaData,
+    matrix: BuildMatrix,
+}
+
+impl_axum_webpage! {
+    BuildMatrixPage = "crate/build_matrix.html",
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct BuildMatrixParams {
+    page: Option<usize>,
+}
+
+/// Shows which targets the releases of a crate were built for, so failures on some platforms
+/// stand out. Lists [`MATRIX_VERSIONS`] releases per page.
+pub(crate) async fn build_matrix_handler(
+    Path(name): Path<String>,
+    Query(params): Query<BuildMatrixParams>,
+    Extension(pool): Extension<Pool>,
+    RequestedFormat(format): RequestedFormat,
+) -> AxumResult<impl IntoResponse> {
+    let page = params.page.unwrap_or(1);
+    if page == 0 {
+        return Err(AxumNope::BadRequest("`page` starts at 1".into()));
+    }
+    // the releases of the page are counted with `page * MATRIX_VERSIONS`
+    if page.checked_mul(MATRIX_VERSIONS).is_none() {
+        return Err(AxumNope::BadRequest("`page` is too large".into()));
+    }
+
+    let (matrix, metadata) = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let releases: Vec<_> = conn
+            .query(
+                "SELECT releases.version,
+                    releases.default_target,
+                    releases.doc_targets,
+                    EXISTS (SELECT 1 FROM builds WHERE builds.rid = releases.id) AS built
+                 FROM releases
+                 INNER JOIN crates ON releases.crate_id = crates.id
+                 WHERE crates.name = $1",
+                &[&name],
+            )?
+            .into_iter()
+            .filter_map(|row| {
+                let version = Version::parse(row.get("version")).ok()?;
+                let built: bool = row.get("built");
+                Some(MatrixRelease {
+                    version,
+                    targets: built.then(|| {
+                        build_targets(
+                            row.get("default_target"),
+                            MetaData::parse_doc_targets(row.get("doc_targets")),
+                        )
+                    }),
+                })
+            })
+            .collect();
+        // the navigation always shows the newest release, like on the other crate pages
+        let newest = match releases.iter().map(|release| &release.version).max() {
+            Some(newest) => newest.to_string(),
+            None => return Err(AxumNope::CrateNotFound.into()),
+        };
+        let metadata = MetaData::from_crate(&mut conn, &name, &newest, "latest")?;
+
+        Ok((BuildMatrix::new(releases, page, MATRIX_VERSIONS), metadata))
+    })
+    .await?;
+
+    Ok(match format {
+        OutputFormat::Json => (
+            Extension(CachePolicy::NoStoreMustRevalidate),
+            [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+            Json(matrix),
+        )
+            .into_response(),
+        OutputFormat::Html => BuildMatrixPage { metadata, matrix }.into_response(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct QueuedRebuild {
+    /// Position in the build queue, starting at 1
+    position: usize,
+}
+
+/// Queues a rebuild of a release, authenticated with one of the `DOCSRS_REBUILD_TOKENS` given as
+/// bearer token. Each token can queue `DOCSRS_REBUILDS_PER_HOUR` rebuilds.
+pub(crate) async fn rebuild_handler(
+    Path((name, version)): Path<(String, String)>,
+    headers: HeaderMap,
+    Extension(config): Extension<Arc<Config>>,
+    Extension(build_queue): Extension<Arc<BuildQueue>>,
+    Extension(pool): Extension<Pool>,
+    Extension(rate_limiter): Extension<Arc<RateLimiter<String>>>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let triggered_by = authenticate_named_token(&headers, &config.rebuild_tokens)?;
+    if !rate_limiter.check(
+        triggered_by.clone(),
+        config.rebuilds_per_hour,
+        Instant::now(),
+    ) {
+        return Err(AxumNope::TooManyRequests.into());
+    }
+
+    let position = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        // only exact versions can be rebuilt, a semver requirement is too easy to get wrong here
+        let release = conn.query_opt(
+            "SELECT 1
+             FROM releases
+             INNER JOIN crates ON releases.crate_id = crates.id
+             WHERE crates.name = $1 AND releases.version = $2",
+            &[&name, &version],
+        )?;
+        if release.is_none() {
+            return Err(AxumNope::VersionNotFound.into());
+        }
+
+        let position = build_queue
+            .add_rebuild(&name, &version, REBUILD_PRIORITY, &triggered_by)?
+            .ok_or_else(|| {
+                AxumNope::Conflict(format!(
+                    "{} {} is already queued or being built",
+                    name, version
+                ))
+            })?;
+        info!(%name, %version, %triggered_by, position, "queued rebuild");
+        Ok(position)
+    })
+    .await?;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Extension(CachePolicy::NoCaching),
+        Json(QueuedRebuild { position }),
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct StaleBuild {
+    name: String,
+    version: String,
+    rustc_version: String,
+    build_time: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct StaleBuilds {
+    /// The toolchain docs.rs is currently building with, `None` before the first build
+    current_rustc_version: Option<String>,
+    releases: Vec<StaleBuild>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct StaleBuildsParams {
+    limit: Option<i64>,
+}
+
+/// Returns the releases whose latest build used a nightly older than `current`, oldest builds
+/// first.
+///
+/// Toolchains are compared by the date in their `rustc --version` string, builds whose version
+/// can't be parsed are never reported.
+fn get_stale_builds(
+    conn: &mut postgres::Client,
+    current: NaiveDate,
+    limit: i64,
+) -> Result<Vec<StaleBuild>> {
+    Ok(conn
+        .query(
+            r"SELECT crates.name,
+                releases.version,
+                latest_builds.rustc_version,
+                latest_builds.build_time
+             FROM (
+                 SELECT DISTINCT ON (rid) rid, rustc_version, build_time
+                 FROM builds
+                 ORDER BY rid, build_time DESC, id DESC
+             ) AS latest_builds
+             INNER JOIN releases ON releases.id = latest_builds.rid
+             INNER JOIN crates ON releases.crate_id = crates.id
+             WHERE SUBSTRING(latest_builds.rustc_version FROM ' (\d{4}-\d{2}-\d{2})\)$')::DATE < $1
+             ORDER BY latest_builds.build_time, crates.name, releases.version
+             LIMIT $2",
+            &[&current, &limit],
+        )?
+        .iter()
+        .map(|row| StaleBuild {
+            name: row.get("name"),
+            version: row.get("version"),
+            rustc_version: row.get("rustc_version"),
+            build_time: row.get("build_time"),
+        })
+        .collect())
+}
+
+/// Lists releases built with an older toolchain than the current one, so they can be queued
+/// for rebuilds. Authenticated with one of the `DOCSRS_REBUILD_TOKENS` given as bearer token.
+pub(crate) async fn stale_builds_json_handler(
+    headers: HeaderMap,
+    Query(params): Query<StaleBuildsParams>,
+    Extension(config): Extension<Arc<Config>>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
+    authenticate_named_token(&headers, &config.rebuild_tokens)?;
+
+    let limit = params.limit.unwrap_or(STALE_BUILDS_LIMIT);
+    if !(1..=STALE_BUILDS_LIMIT).contains(&limit) {
+        return Err(AxumNope::BadRequest(format!(
+            "`limit` has to be between 1 and {}",
+            STALE_BUILDS_LIMIT
+        ))
+        .into());
+    }
+
+    let stale_builds = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let current_rustc_version = get_config::<String>(&mut conn, ConfigName::RustcVersion)?;
+        let releases = match &current_rustc_version {
+            Some(current) => get_stale_builds(&mut conn, parse_rustc_date(current)?, limit)?,
+            None => Vec::new(),
+        };
+        Ok(StaleBuilds {
+            current_rustc_version,
+            releases,
+        })
+    })
+    .await?;
+
+    Ok((Extension(CachePolicy::NoCaching), Json(stale_builds)))
+}
+
 #[cfg(test)]
 mod tests {
+    use super::{build_targets, BuildMatrix, MatrixRelease, TargetStatus};
     use crate::{
         test::{assert_cache_control, wrapper, FakeBuild},
+        utils::{set_config, ConfigName},
         web::cache::CachePolicy,
     };
     use chrono::{DateTime, Duration, Utc};
     use kuchiki::traits::TendrilSink;
     use reqwest::StatusCode;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn build_matrix_pages() {
+        let releases: Vec<_> = ["0.9.0", "0.10.0", "0.2.0", "1.0.0-rc.1", "0.1.0"]
+            .into_iter()
+            .map(|version| MatrixRelease {
+                version: version.parse().unwrap(),
+                targets: Some(build_targets(
+                    "x86_64-unknown-linux-gnu".into(),
+                    if version == "0.1.0" {
+                        vec!["i686-pc-windows-msvc".into()]
+                    } else {
+                        vec!["x86_64-unknown-linux-gnu".into()]
+                    },
+                )),
+            })
+            .collect();
+
+        let versions = |matrix: &BuildMatrix| -> Vec<String> {
+            matrix
+                .versions
+                .iter()
+                .map(|row| row.version.clone())
+                .collect()
+        };
+
+        let first = BuildMatrix::new(releases.clone(), 1, 2);
+        assert_eq!(versions(&first), ["1.0.0-rc.1", "0.10.0"]);
+        assert_eq!(first.targets, ["x86_64-unknown-linux-gnu"]);
+        assert_eq!(first.next_page, Some(2));
+
+        let last = BuildMatrix::new(releases.clone(), 3, 2);
+        assert_eq!(versions(&last), ["0.1.0"]);
+        // only the targets of releases on the page are listed
+        assert_eq!(
+            last.targets,
+            ["i686-pc-windows-msvc", "x86_64-unknown-linux-gnu"]
+        );
+        assert_eq!(
+            last.versions[0].statuses,
+            [TargetStatus::Passed, TargetStatus::Failed]
+        );
+        assert_eq!(last.next_page, None);
+
+        assert!(BuildMatrix::new(releases, 4, 2).versions.is_empty());
+    }
+
+    #[test]
+    fn build_matrix() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .default_target("x86_64-unknown-linux-gnu")
+                .add_target("x86_64-pc-windows-msvc")
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.2.0")
+                .default_target("x86_64-unknown-linux-gnu")
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.10.0")
+                .build_result_failed()
+                .create()?;
+            // releases are only added after their first build
+            let never_built = env.fake_release().name("foo").version("0.3.0").create()?;
+            env.db()
+                .conn()
+                .execute("DELETE FROM builds WHERE rid = $1", &[&never_built])?;
+            let web = env.frontend();
+
+            let matrix: Value = web
+                .get("/crate/foo/builds/matrix?format=json")
+                .send()?
+                .json()?;
+            assert_eq!(
+                matrix,
+                json!({
+                    "targets": ["x86_64-pc-windows-msvc", "x86_64-unknown-linux-gnu"],
+                    "versions": [
+                        { "version": "0.10.0", "statuses": ["not_built", "failed"] },
+                        { "version": "0.3.0", "statuses": ["not_built", "not_built"] },
+                        { "version": "0.2.0", "statuses": ["not_built", "passed"] },
+                        { "version": "0.1.0", "statuses": ["passed", "passed"] },
+                    ],
+                    "page": 1,
+                    "next_page": null,
+                })
+            );
+
+            let response = web.get("/crate/foo/builds/matrix").send()?;
+            assert!(response.status().is_success());
+            let page = kuchiki::parse_html().one(response.text()?);
+            let rows: Vec<Vec<String>> = page
+                .select("table.build-matrix tbody tr")
+                .unwrap()
+                .map(|row| {
+                    row.as_node()
+                        .select("th, td")
+                        .unwrap()
+                        .map(|cell| {
+                            let attributes = cell.attributes.borrow();
+                            attributes
+                                .get("class")
+                                .map(str::to_owned)
+                                .unwrap_or_else(|| cell.text_contents().trim().to_owned())
+                        })
+                        .collect()
+                })
+                .collect();
+            assert_eq!(
+                rows,
+                [
+                    ["0.10.0", "not_built", "failed"],
+                    ["0.3.0", "not_built", "not_built"],
+                    ["0.2.0", "not_built", "passed"],
+                    ["0.1.0", "passed", "passed"],
+                ]
+            );
+
+            assert_eq!(web.get("/crate/bar/builds/matrix").send()?.status(), 404);
+            for page in [0, usize::MAX] {
+                assert_eq!(
+                    web.get(&format!("/crate/foo/builds/matrix?page={}", page))
+                        .send()?
+                        .status(),
+                    400,
+                    "{}",
+                    page
+                );
+            }
+            Ok(())
+        });
+    }
 
     #[test]
     fn build_list() {
@@ -195,6 +778,82 @@ mod tests {
         });
     }
 
+    #[test]
+    fn build_list_json_compression() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .builds(vec![FakeBuild::default()])
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.2.0")
+                .builds((0..50).map(|_| FakeBuild::default()).collect())
+                .create()?;
+            let web = env.frontend();
+
+            let response = web
+                .get("/crate/foo/0.2.0/builds.json")
+                .header("accept-encoding", "gzip")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["content-encoding"], "gzip");
+
+            // small responses aren't compressed
+            let response = web
+                .get("/crate/foo/0.1.0/builds.json")
+                .header("accept-encoding", "gzip")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(response.headers().get("content-encoding").is_none());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn build_list_accept_json() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+
+            let response = env
+                .frontend()
+                .get("/crate/foo/0.1.0/builds")
+                .header("accept", "application/json")
+                .send()?;
+            assert!(response.status().is_success());
+            assert_eq!(response.headers()["content-type"], "application/json");
+            assert!(response
+                .headers()
+                .get_all("vary")
+                .iter()
+                .any(|value| value == "Accept"));
+            let value: serde_json::Value = response.json()?;
+            assert_eq!(value.pointer("/0/build_status"), Some(&true.into()));
+
+            // browsers still get the page
+            let response = env
+                .frontend()
+                .get("/crate/foo/0.1.0/builds")
+                .header(
+                    "accept",
+                    "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+                )
+                .send()?;
+            assert!(response.status().is_success());
+            assert!(response.headers()["content-type"]
+                .to_str()?
+                .starts_with("text/html"));
+            assert!(response
+                .headers()
+                .get_all("vary")
+                .iter()
+                .any(|value| value == "Accept"));
+            Ok(())
+        });
+    }
+
     #[test]
     fn build_list_json() {
         wrapper(|env| {
@@ -277,6 +936,73 @@ mod tests {
         });
     }
 
+    #[test]
+    fn build_targets_json() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .default_target("x86_64-unknown-linux-gnu")
+                .add_target("i686-pc-windows-msvc")
+                .create()?;
+
+            let response = env.frontend().get("/crate/foo/0.1.0/targets.json").send()?;
+            assert_cache_control(&response, CachePolicy::NoStoreMustRevalidate, &env.config());
+            let value: serde_json::Value = serde_json::from_str(&response.text()?)?;
+
+            assert_eq!(
+                value,
+                serde_json::json!([
+                    {"target": "x86_64-unknown-linux-gnu", "successful": true, "default": true},
+                    {"target": "i686-pc-windows-msvc", "successful": true, "default": false},
+                ])
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn build_targets_json_failed_build() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .build_result_failed()
+                .create()?;
+
+            let response = env.frontend().get("/crate/foo/0.1.0/targets.json").send()?;
+            assert!(response.status().is_success());
+            let value: serde_json::Value = serde_json::from_str(&response.text()?)?;
+
+            assert_eq!(value.pointer("/0/successful"), Some(&false.into()));
+            assert_eq!(value.pointer("/0/default"), Some(&true.into()));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn build_targets_json_without_builds() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            env.db().conn().execute("DELETE FROM builds", &[])?;
+
+            let response = env.frontend().get("/crate/foo/0.1.0/targets.json").send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            let value: serde_json::Value = serde_json::from_str(&response.text()?)?;
+            assert_eq!(
+                value,
+                serde_json::json!({"error": {
+                    "code": "version_not_found",
+                    "message": "no such version for this crate",
+                }})
+            );
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn limits() {
         wrapper(|env| {
@@ -405,4 +1131,303 @@ mod tests {
             Ok(())
         });
     }
+
+    #[test]
+    fn rebuild() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.rebuild_tokens = vec!["admin:secret".parse().unwrap()];
+            });
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            env.fake_release().name("bar").version("0.2.0").create()?;
+            env.build_queue().add_crate("baz", "1.0.0", 0, None)?;
+            let web = env.frontend();
+
+            let response = web
+                .post("/crate/foo/0.1.0/rebuild")
+                .bearer_auth("secret")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+            assert_cache_control(&response, CachePolicy::NoCaching, &env.config());
+            // ahead of the regular build
+            assert_eq!(response.json::<serde_json::Value>()?["position"], 1);
+
+            let response = web
+                .post("/crate/bar/0.2.0/rebuild")
+                .bearer_auth("secret")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+            assert_eq!(response.json::<serde_json::Value>()?["position"], 2);
+
+            let triggered_by: Option<String> = env
+                .db()
+                .conn()
+                .query_one(
+                    "SELECT triggered_by FROM queue WHERE name = 'foo' AND version = '0.1.0'",
+                    &[],
+                )?
+                .get(0);
+            assert_eq!(triggered_by.as_deref(), Some("admin"));
+
+            // unknown releases can't be rebuilt
+            let response = web
+                .post("/crate/foo/0.3.0/rebuild")
+                .bearer_auth("secret")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn rebuilds_are_rate_limited_per_token() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.rebuild_tokens = vec![
+                    "admin:secret".parse().unwrap(),
+                    "bot:other-secret".parse().unwrap(),
+                ];
+                config.rebuilds_per_hour = 1;
+            });
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            env.fake_release().name("bar").version("0.2.0").create()?;
+            let web = env.frontend();
+
+            let rebuild = |path: &str, token: &str| web.post(path).bearer_auth(token).send();
+            assert_eq!(
+                rebuild("/crate/foo/0.1.0/rebuild", "secret")?.status(),
+                StatusCode::ACCEPTED
+            );
+            let response = rebuild("/crate/bar/0.2.0/rebuild", "secret")?;
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(
+                response.json::<serde_json::Value>()?["error"]["code"],
+                "too_many_requests"
+            );
+
+            // the quota is per token
+            assert_eq!(
+                rebuild("/crate/bar/0.2.0/rebuild", "other-secret")?.status(),
+                StatusCode::ACCEPTED
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn rebuild_rejects_duplicates() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.rebuild_tokens = vec!["admin:secret".parse().unwrap()];
+            });
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let web = env.frontend();
+
+            let rebuild = || {
+                web.post("/crate/foo/0.1.0/rebuild")
+                    .bearer_auth("secret")
+                    .send()
+            };
+            assert_eq!(rebuild()?.status(), StatusCode::ACCEPTED);
+
+            let response = rebuild()?;
+            assert_eq!(response.status(), StatusCode::CONFLICT);
+            assert!(response.json::<serde_json::Value>()?["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("already queued"));
+            assert_eq!(env.build_queue().pending_count()?, 1);
+
+            // releases that were queued after a publish are duplicates too
+            env.build_queue().add_crate("bar", "1.0.0", 0, None)?;
+            env.fake_release().name("bar").version("1.0.0").create()?;
+            assert_eq!(
+                web.post("/crate/bar/1.0.0/rebuild")
+                    .bearer_auth("secret")
+                    .send()?
+                    .status(),
+                StatusCode::CONFLICT
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn rebuild_requires_valid_token() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.rebuild_tokens = vec!["admin:secret".parse().unwrap()];
+            });
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let web = env.frontend();
+
+            assert_eq!(
+                web.post("/crate/foo/0.1.0/rebuild").send()?.status(),
+                StatusCode::UNAUTHORIZED
+            );
+            for auth in &["Bearer wrong", "Bearer secre", "secret", "Basic c2VjcmV0"] {
+                let response = web
+                    .post("/crate/foo/0.1.0/rebuild")
+                    .header("authorization", *auth)
+                    .send()?;
+                assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{}", auth);
+            }
+            assert_eq!(env.build_queue().pending_count()?, 0);
+
+            // GET isn't allowed
+            assert_eq!(
+                web.get("/crate/foo/0.1.0/rebuild").send()?.status(),
+                StatusCode::METHOD_NOT_ALLOWED
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn stale_builds() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.rebuild_tokens = vec!["admin:secret".parse().unwrap()];
+            });
+            set_config(
+                &mut env.db().conn(),
+                ConfigName::RustcVersion,
+                "rustc 1.70.0-nightly (f63ccaf25 2023-03-01)",
+            )?;
+
+            let now = Utc::now();
+            let fixtures = [
+                (
+                    "foo",
+                    "0.1.0",
+                    vec![("2023-01-01", now - Duration::days(10))],
+                ),
+                (
+                    "foo",
+                    "0.2.0",
+                    vec![("2023-03-01", now - Duration::days(5))],
+                ),
+                (
+                    "bar",
+                    "1.0.0",
+                    vec![
+                        ("2023-03-01", now - Duration::days(1)),
+                        ("2022-01-01", now - Duration::days(100)),
+                    ],
+                ),
+                (
+                    "baz",
+                    "1.0.0",
+                    vec![("2022-06-01", now - Duration::days(50))],
+                ),
+            ];
+            for (name, version, builds) in fixtures {
+                env.fake_release()
+                    .name(name)
+                    .version(version)
+                    .builds(
+                        builds
+                            .into_iter()
+                            .map(|(date, build_time)| {
+                                FakeBuild::default()
+                                    .rustc_version(format!(
+                                        "rustc 1.70.0-nightly (000000000 {})",
+                                        date
+                                    ))
+                                    .build_time(build_time)
+                            })
+                            .collect(),
+                    )
+                    .create()?;
+            }
+            env.fake_release()
+                .name("qux")
+                .version("1.0.0")
+                .builds(vec![FakeBuild::default().rustc_version("rustc unknown")])
+                .create()?;
+
+            let web = env.frontend();
+            let response = web.get("/-/stale-builds.json").send()?;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+            let response = web
+                .get("/-/stale-builds.json")
+                .bearer_auth("secret")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_cache_control(&response, CachePolicy::NoCaching, &env.config());
+            let value: serde_json::Value = response.json()?;
+            assert_eq!(
+                value["current_rustc_version"],
+                "rustc 1.70.0-nightly (f63ccaf25 2023-03-01)"
+            );
+            let releases: Vec<_> = value["releases"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|release| {
+                    (
+                        release["name"].as_str().unwrap(),
+                        release["version"].as_str().unwrap(),
+                        release["rustc_version"].as_str().unwrap(),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                releases,
+                [
+                    (
+                        "baz",
+                        "1.0.0",
+                        "rustc 1.70.0-nightly (000000000 2022-06-01)"
+                    ),
+                    (
+                        "foo",
+                        "0.1.0",
+                        "rustc 1.70.0-nightly (000000000 2023-01-01)"
+                    ),
+                ]
+            );
+
+            let value: serde_json::Value = web
+                .get("/-/stale-builds.json?limit=1")
+                .bearer_auth("secret")
+                .send()?
+                .json()?;
+            assert_eq!(value["releases"].as_array().unwrap().len(), 1);
+            assert_eq!(value["releases"][0]["name"], "baz");
+
+            let response = web
+                .get("/-/stale-builds.json?limit=0")
+                .bearer_auth("secret")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn stale_builds_without_current_toolchain() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.rebuild_tokens = vec!["admin:secret".parse().unwrap()];
+            });
+            env.fake_release().name("foo").version("0.1.0").create()?;
+
+            let value: serde_json::Value = env
+                .frontend()
+                .get("/-/stale-builds.json")
+                .bearer_auth("secret")
+                .send()?
+                .json()?;
+            assert_eq!(value["current_rustc_version"], serde_json::Value::Null);
+            assert_eq!(value["releases"], serde_json::json!([]));
+
+            Ok(())
+        });
+    }
 }
@@ -1,9 +1,12 @@
 use super::STATIC_FILE_CACHE_DURATION;
-use crate::config::Config;
+use crate::{config::Config, Metrics};
 use axum::{
     http::Request as AxumHttpRequest, middleware::Next, response::Response as AxumResponse,
 };
-use http::header::CACHE_CONTROL;
+use http::{
+    header::{CACHE_CONTROL, VARY},
+    HeaderValue,
+};
 use iron::{
     headers::{CacheControl, CacheDirective},
     AfterMiddleware, IronResult, Request, Response,
@@ -13,6 +16,15 @@ use std::sync::Arc;
 #[cfg(test)]
 pub const NO_CACHE: &str = "max-age=0";
 
+/// Seconds responses with [`CachePolicy::ShortInCdn`] are cached in the CDN.
+const SHORT_CDN_CACHE_DURATION: u32 = 5 * 60;
+/// Seconds responses with [`CachePolicy::ShortInCdn`] may be served stale while revalidating.
+const SHORT_STALE_WHILE_REVALIDATE: u32 = 60;
+
+/// Generated absolute links depend on these headers when `DOCSRS_TRUST_FORWARDED_HEADERS` is
+/// set, so cached pages can't be shared between hosts.
+const FORWARDED_HEADERS_VARY: &str = "X-Forwarded-Host, X-Forwarded-Proto";
+
 /// defines the wanted caching behaviour for a web response.
 pub enum CachePolicy {
     /// no browser or CDN caching.
@@ -39,9 +51,34 @@ pub enum CachePolicy {
     /// version from the origin server in the background.
     /// This helps building a PWA.
     ForeverInCdnAndStaleInBrowser,
+    /// cache for a few minutes in the CDN, but not in the browser.
+    /// Stale content may be served for a short while during revalidation.
+    /// Example: aggregate statistics or badges which change slowly, but can't
+    /// be purged after every build.
+    ShortInCdn,
 }
 
 impl CachePolicy {
+    /// The name of the variant, the label of the `cache_policies` metric
+    pub(crate) fn name(&self) -> &'static str {
+        match *self {
+            CachePolicy::NoCaching => "NoCaching",
+            CachePolicy::NoStoreMustRevalidate => "NoStoreMustRevalidate",
+            CachePolicy::ForeverInCdnAndBrowser => "ForeverInCdnAndBrowser",
+            CachePolicy::ForeverInCdn => "ForeverInCdn",
+            CachePolicy::ForeverInCdnAndStaleInBrowser => "ForeverInCdnAndStaleInBrowser",
+            CachePolicy::ShortInCdn => "ShortInCdn",
+        }
+    }
+
+    /// Counts a response sent with this policy.
+    fn record(&self, metrics: &Metrics) {
+        metrics
+            .cache_policies
+            .with_label_values(&[self.name()])
+            .inc();
+    }
+
     pub fn render(&self, config: &Config) -> Vec<CacheDirective> {
         match *self {
             CachePolicy::NoCaching => {
@@ -76,6 +113,16 @@ impl CachePolicy {
                 }
                 directives
             }
+            CachePolicy::ShortInCdn => {
+                vec![
+                    CacheDirective::MaxAge(0),
+                    CacheDirective::SMaxAge(SHORT_CDN_CACHE_DURATION),
+                    CacheDirective::Extension(
+                        "stale-while-revalidate".to_string(),
+                        Some(SHORT_STALE_WHILE_REVALIDATE.to_string()),
+                    ),
+                ]
+            }
         }
     }
 }
@@ -113,6 +160,11 @@ impl AfterMiddleware for CacheMiddleware {
         if !directives.is_empty() {
             res.headers.set(CacheControl(directives))
         }
+        if config.trust_forwarded_headers {
+            res.headers
+                .append_raw("Vary", FORWARDED_HEADERS_VARY.as_bytes().to_vec());
+        }
+        cache.record(extension!(req, Metrics));
         Ok(res)
     }
 }
@@ -123,6 +175,11 @@ pub(crate) async fn cache_middleware<B>(req: AxumHttpRequest<B>, next: Next<B>)
         .get::<Arc<Config>>()
         .cloned()
         .expect("missing config extension in request");
+    let metrics = req
+        .extensions()
+        .get::<Arc<Metrics>>()
+        .cloned()
+        .expect("missing metrics extension in request");
 
     let mut response = next.run(req).await;
 
@@ -148,6 +205,12 @@ pub(crate) async fn cache_middleware<B>(req: AxumHttpRequest<B>, next: Next<B>)
                 .expect("cache-control header could not be parsed"),
         );
     }
+    if config.trust_forwarded_headers {
+        response
+            .headers_mut()
+            .append(VARY, HeaderValue::from_static(FORWARDED_HEADERS_VARY));
+    }
+    cache.record(&metrics);
     response
 }
 
@@ -169,6 +232,10 @@ mod tests {
         CachePolicy::ForeverInCdnAndStaleInBrowser,
         "stale-while-revalidate=86400"
     )]
+    #[test_case(
+        CachePolicy::ShortInCdn,
+        "max-age=0, s-maxage=300, stale-while-revalidate=60"
+    )]
     fn render(cache: CachePolicy, expected: &str) {
         wrapper(|env| {
             assert_eq!(
@@ -207,4 +274,49 @@ mod tests {
             Ok(())
         });
     }
+
+    #[test]
+    fn responses_are_counted_by_policy() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .rustdoc_file("foo/index.html")
+                .create()?;
+            let web = env.frontend();
+            let metrics = env.metrics();
+            let count = |policy: CachePolicy| {
+                metrics
+                    .cache_policies
+                    .with_label_values(&[policy.name()])
+                    .get()
+            };
+            let stale_in_browser = count(CachePolicy::ForeverInCdnAndStaleInBrowser);
+            let must_revalidate = count(CachePolicy::NoStoreMustRevalidate);
+
+            // served by iron
+            for _ in 0..2 {
+                assert!(web.get("/foo/0.1.0/foo/").send()?.status().is_success());
+            }
+            // served by axum
+            assert!(web
+                .get("/crate/foo/0.1.0/coverage.json")
+                .send()?
+                .status()
+                .is_success());
+
+            assert_eq!(
+                count(CachePolicy::ForeverInCdnAndStaleInBrowser),
+                stale_in_browser + 2
+            );
+            assert_eq!(
+                count(CachePolicy::NoStoreMustRevalidate),
+                must_revalidate + 1
+            );
+
+            let page = web.get("/about/metrics").send()?.text()?;
+            assert!(page.contains(r#"docsrs_cache_policies{policy="NoStoreMustRevalidate"}"#));
+            Ok(())
+        });
+    }
 }
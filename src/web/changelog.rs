@@ -0,0 +1,205 @@
+//! Renders the changelog shipped in the source of a release.
+
+use crate::{
+    db::Pool,
+    impl_axum_webpage,
+    storage::PathNotFoundError,
+    utils::spawn_blocking,
+    web::{
+        cache::CachePolicy,
+        error::{AxumNope, AxumResult},
+        markdown, match_version_axum, MatchSemver, MetaData,
+    },
+    Storage,
+};
+use axum::{
+    extract::{Extension, Path},
+    response::{IntoResponse, Response as AxumResponse},
+};
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// File names recognized as changelog, in order of preference. They are only looked up in the
+/// root of the source and compared case-insensitively. Migration 54 set `releases.has_changelog`
+/// of existing releases with the same names.
+const CHANGELOG_FILES: &[&str] = &[
+    "CHANGELOG.md",
+    "CHANGES.md",
+    "HISTORY.md",
+    "RELEASES.md",
+    "NEWS.md",
+    "CHANGELOG",
+    "CHANGES",
+];
+
+/// Returns the path of the changelog in the source file list stored in `releases.files`.
+pub(crate) fn find_changelog(files: &Value) -> Option<String> {
+    let paths: Vec<&str> = files
+        .as_array()?
+        .iter()
+        .filter_map(|file| file.as_array()?.get(1)?.as_str())
+        .collect();
+
+    CHANGELOG_FILES.iter().find_map(|name| {
+        paths
+            .iter()
+            .find(|path| path.eq_ignore_ascii_case(name))
+            .map(|path| path.to_string())
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct ChangelogPage {
+    metadata: MetaData,
+    path: String,
+    #[serde(serialize_with = "markdown_to_html")]
+    changelog: String,
+}
+
+fn markdown_to_html<S>(markdown: &str, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    markdown::render(markdown).serialize(serializer)
+}
+
+impl_axum_webpage! {
+    ChangelogPage = "crate/changelog.html",
+    cache_policy = |_| CachePolicy::ForeverInCdn,
+    cpu_intensive_rendering = true,
+}
+
+pub(crate) async fn changelog_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+    Extension(storage): Extension<Arc<Storage>>,
+) -> AxumResult<AxumResponse> {
+    let (version, version_or_latest) = match match_version_axum(&pool, &name, Some(&req_version))
+        .await?
+        .assume_exact()?
+    {
+        MatchSemver::Exact((version, _)) => (version.clone(), version),
+        MatchSemver::Latest((version, _)) => (version, "latest".to_string()),
+        MatchSemver::Semver((version, _)) => {
+            return Ok(super::axum_cached_redirect(
+                &format!("/crate/{}/{}/changelog", name, version),
+                CachePolicy::ForeverInCdn,
+            )?
+            .into_response());
+        }
+    };
+
+    let page = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let row = conn
+            .query_opt(
+                "SELECT releases.files, releases.archive_storage
+                 FROM releases
+                 INNER JOIN crates ON releases.crate_id = crates.id
+                 WHERE crates.name = $1 AND releases.version = $2",
+                &[&name, &version],
+            )?
+            .ok_or(AxumNope::VersionNotFound)?;
+
+        let path = row
+            .get::<_, Option<Value>>("files")
+            .as_ref()
+            .and_then(find_changelog)
+            .ok_or(AxumNope::ResourceNotFound)?;
+
+        let blob =
+            match storage.fetch_source_file(&name, &version, &path, row.get("archive_storage")) {
+                Ok(blob) => blob,
+                Err(err) if err.is::<PathNotFoundError>() => {
+                    return Err(AxumNope::ResourceNotFound.into())
+                }
+                Err(err) => return Err(err),
+            };
+
+        Ok(ChangelogPage {
+            metadata: MetaData::from_crate(&mut conn, &name, &version, &version_or_latest)?,
+            path,
+            changelog: String::from_utf8_lossy(&blob.content).into_owned(),
+        })
+    })
+    .await?;
+
+    Ok(page.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_changelog;
+    use crate::test::{assert_cache_control, assert_redirect_cached, wrapper};
+    use crate::web::cache::CachePolicy;
+    use serde_json::json;
+    use test_case::test_case;
+
+    #[test_case(&["src/lib.rs", "CHANGELOG.md"], Some("CHANGELOG.md"))]
+    #[test_case(&["Changelog.md"], Some("Changelog.md"); "case insensitive")]
+    #[test_case(&["CHANGES.md", "CHANGELOG.md"], Some("CHANGELOG.md"); "preferred")]
+    #[test_case(&["docs/CHANGELOG.md", "README.md"], None; "not in root")]
+    #[test_case(&[], None; "empty")]
+    fn find_changelog_in_files(paths: &[&str], expected: Option<&str>) {
+        let files = json!(paths
+            .iter()
+            .map(|path| json!(["text/markdown", path]))
+            .collect::<Vec<_>>());
+        assert_eq!(find_changelog(&files).as_deref(), expected);
+    }
+
+    #[test]
+    fn changelog_page() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .source_file(
+                    "CHANGELOG.md",
+                    b"# Changelog\n\n## 0.1.0\n\n* *first* release\n\n<script>alert(1)</script>\n",
+                )
+                .create()?;
+            let web = env.frontend();
+
+            let response = web.get("/crate/foo/0.1.0/changelog").send()?;
+            assert!(response.status().is_success());
+            assert_cache_control(&response, CachePolicy::ForeverInCdn, &env.config());
+            let text = response.text()?;
+            assert!(text.contains("<h1>Changelog</h1>"));
+            assert!(text.contains("<em>first</em>"));
+            assert!(!text.contains("<script>alert(1)</script>"));
+
+            assert_redirect_cached(
+                "/crate/foo/0.1/changelog",
+                "/crate/foo/0.1.0/changelog",
+                CachePolicy::ForeverInCdn,
+                web,
+                &env.config(),
+            )?;
+
+            let details = web.get("/crate/foo/0.1.0").send()?.text()?;
+            assert!(details.contains(r#"href="/crate/foo/0.1.0/changelog""#));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn changelog_page_without_changelog() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .source_file("README.md", b"# foo")
+                .create()?;
+            let web = env.frontend();
+
+            assert_eq!(web.get("/crate/foo/0.1.0/changelog").send()?.status(), 404);
+            assert_eq!(web.get("/crate/bar/0.1.0/changelog").send()?.status(), 404);
+
+            let details = web.get("/crate/foo/0.1.0").send()?.text()?;
+            assert!(!details.contains("/changelog"));
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,197 @@
+//! Extraction of the IP address of the client, for metrics and rate limiting.
+
+use super::error::AxumNope;
+use crate::Config;
+use anyhow::anyhow;
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, Extensions, HeaderMap},
+};
+use ipnet::IpNet;
+use std::{
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+};
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// The IP address of the client that sent the request.
+///
+/// When the request came in through one of the configured `DOCSRS_TRUSTED_PROXIES`, this is the
+/// rightmost `X-Forwarded-For` entry that wasn't added by a trusted proxy. Entries left of it are
+/// controlled by the client and can't be trusted. Otherwise it's the address of the socket peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ClientIp(pub(crate) IpAddr);
+
+impl ClientIp {
+    pub(crate) fn resolve(trusted_proxies: &[IpNet], peer: IpAddr, headers: &HeaderMap) -> Self {
+        let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+
+        if !is_trusted(&peer) {
+            return ClientIp(peer);
+        }
+
+        // multiple headers are equivalent to a single comma separated one
+        let entries: Vec<&str> = headers
+            .get_all(X_FORWARDED_FOR)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+
+        let mut client = peer;
+        for entry in entries.iter().rev() {
+            match parse_forwarded_ip(entry) {
+                Some(ip) => {
+                    client = ip;
+                    if !is_trusted(&ip) {
+                        break;
+                    }
+                }
+                // everything left of a malformed entry is unreliable
+                None => break,
+            }
+        }
+        ClientIp(client)
+    }
+
+    /// Looks up the client IP using the connection info and config from the request extensions.
+    pub(crate) fn from_request_data(extensions: &Extensions, headers: &HeaderMap) -> Option<Self> {
+        let ConnectInfo(peer) = extensions.get::<ConnectInfo<SocketAddr>>()?;
+        let config = extensions.get::<Arc<Config>>()?;
+
+        Some(Self::resolve(&config.trusted_proxies, peer.ip(), headers))
+    }
+}
+
+/// Parses an `X-Forwarded-For` entry, which can be a plain IPv4 or IPv6 address, a bracketed
+/// IPv6 address, or either of them with a port.
+fn parse_forwarded_ip(entry: &str) -> Option<IpAddr> {
+    if let Ok(ip) = IpAddr::from_str(entry) {
+        return Some(ip);
+    }
+    if let Ok(addr) = SocketAddr::from_str(entry) {
+        return Some(addr.ip());
+    }
+    entry
+        .strip_prefix('[')
+        .and_then(|entry| entry.strip_suffix(']'))
+        .and_then(|ip| IpAddr::from_str(ip).ok())
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = AxumNope;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Self::from_request_data(&parts.extensions, &parts.headers).ok_or_else(|| {
+            AxumNope::InternalError(anyhow!("missing connection info or config extension"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_forwarded_ip, ClientIp};
+    use axum::http::{HeaderMap, HeaderValue};
+    use ipnet::IpNet;
+    use std::net::IpAddr;
+    use test_case::test_case;
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    fn trusted() -> Vec<IpNet> {
+        vec!["10.0.0.0/8".parse().unwrap(), "fd00::/8".parse().unwrap()]
+    }
+
+    fn resolve(peer: &str, forwarded_for: &[&str]) -> IpAddr {
+        let mut headers = HeaderMap::new();
+        for value in forwarded_for {
+            headers.append("x-forwarded-for", HeaderValue::from_str(value).unwrap());
+        }
+        ClientIp::resolve(&trusted(), ip(peer), &headers).0
+    }
+
+    #[test_case("192.0.2.1", "192.0.2.1")]
+    #[test_case("192.0.2.1:1234", "192.0.2.1")]
+    #[test_case("2001:db8::1", "2001:db8::1")]
+    #[test_case("[2001:db8::1]", "2001:db8::1")]
+    #[test_case("[2001:db8::1]:443", "2001:db8::1")]
+    fn parse_entries(entry: &str, expected: &str) {
+        assert_eq!(parse_forwarded_ip(entry), Some(ip(expected)));
+    }
+
+    #[test_case(""; "empty")]
+    #[test_case("unknown")]
+    #[test_case("[192.0.2.1"; "unclosed bracket")]
+    #[test_case("2001:db8::1]"; "unopened bracket")]
+    fn parse_invalid_entries(entry: &str) {
+        assert_eq!(parse_forwarded_ip(entry), None);
+    }
+
+    #[test]
+    fn untrusted_peer_ignores_header() {
+        assert_eq!(resolve("192.0.2.1", &["198.51.100.7"]), ip("192.0.2.1"));
+        assert_eq!(resolve("2001:db8::1", &["198.51.100.7"]), ip("2001:db8::1"));
+    }
+
+    #[test]
+    fn trusted_peer_without_header() {
+        assert_eq!(resolve("10.0.0.1", &[]), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn rightmost_untrusted_entry() {
+        assert_eq!(
+            resolve("10.0.0.1", &["198.51.100.7, 10.1.2.3"]),
+            ip("198.51.100.7")
+        );
+        assert_eq!(
+            resolve("fd00::1", &["[2001:db8::7]:1234, fd12::3"]),
+            ip("2001:db8::7")
+        );
+        // mixed address families, split across multiple headers
+        assert_eq!(
+            resolve("10.0.0.1", &["2001:db8::7", "fd00::2, 10.0.0.2"]),
+            ip("2001:db8::7")
+        );
+    }
+
+    #[test]
+    fn spoofed_prefix_is_ignored() {
+        // the client can send any `X-Forwarded-For` it likes, our proxy appends the real address
+        assert_eq!(
+            resolve("10.0.0.1", &["1.2.3.4, 2001:db8::dead, 198.51.100.7"]),
+            ip("198.51.100.7")
+        );
+        assert_eq!(
+            resolve("10.0.0.1", &["10.9.9.9, 198.51.100.7, 10.0.0.2"]),
+            ip("198.51.100.7")
+        );
+        assert_eq!(
+            resolve("10.0.0.1", &["garbage, 198.51.100.7"]),
+            ip("198.51.100.7")
+        );
+    }
+
+    #[test]
+    fn malformed_entry_stops_at_last_valid_address() {
+        assert_eq!(
+            resolve("10.0.0.1", &["198.51.100.7, garbage, 10.0.0.2"]),
+            ip("10.0.0.2")
+        );
+    }
+
+    #[test]
+    fn only_trusted_entries_uses_leftmost() {
+        assert_eq!(resolve("10.0.0.1", &["10.0.0.3, fd00::2"]), ip("10.0.0.3"));
+    }
+}
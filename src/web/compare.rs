@@ -0,0 +1,424 @@
+//! Comparison of two releases of a crate at `/crate/:name/compare?from=X&to=Y`.
+
+use super::MatchSemver;
+use crate::db::types::Feature;
+use crate::{
+    db::Pool,
+    impl_axum_webpage,
+    utils::spawn_blocking,
+    web::{
+        cache::CachePolicy,
+        error::{AxumNope, AxumResult},
+        match_version_axum,
+        output_format::{OutputFormat, RequestedFormat},
+        MetaData,
+    },
+};
+use anyhow::Result;
+use axum::{
+    extract::{Extension, Path, Query},
+    http::header::ACCESS_CONTROL_ALLOW_ORIGIN,
+    response::IntoResponse,
+    Json,
+};
+use postgres::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct CompareParams {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+/// A dependency whose requirement differs between the releases, `None` when it's missing in one
+/// of them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct DependencyChange {
+    name: String,
+    kind: String,
+    from: Option<String>,
+    to: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct FeatureChanges {
+    added: Vec<String>,
+    removed: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Comparison {
+    from: String,
+    to: String,
+    /// `None` when the dependencies of one of the releases weren't recorded
+    dependencies: Option<Vec<DependencyChange>>,
+    /// `None` when one of the releases was built before features were collected
+    features: Option<FeatureChanges>,
+    from_rustdoc_status: bool,
+    to_rustdoc_status: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct ComparePage {
+    metadata: MetaData,
+    comparison: Comparison,
+    is_exact: bool,
+}
+
+impl_axum_webpage! {
+    ComparePage = "crate/compare.html",
+    cache_policy = |page| if page.is_exact {
+        CachePolicy::ForeverInCdnAndStaleInBrowser
+    } else {
+        CachePolicy::ForeverInCdn
+    },
+}
+
+/// The parts of a release that are compared.
+struct ReleaseData {
+    dependencies: Option<Value>,
+    features: Option<Vec<Feature>>,
+    rustdoc_status: bool,
+}
+
+impl ReleaseData {
+    fn load(conn: &mut Client, name: &str, version: &str) -> Result<Self> {
+        let row = conn
+            .query_opt(
+                "SELECT releases.dependencies, releases.features, releases.rustdoc_status
+                 FROM releases
+                 INNER JOIN crates ON crates.id = releases.crate_id
+                 WHERE crates.name = $1 AND releases.version = $2",
+                &[&name, &version],
+            )?
+            .ok_or(AxumNope::VersionNotFound)?;
+
+        Ok(Self {
+            dependencies: row.get("dependencies"),
+            features: row.get("features"),
+            rustdoc_status: row.get("rustdoc_status"),
+        })
+    }
+
+    /// Requirements of the dependencies keyed by name and kind, `None` when they weren't recorded.
+    fn dependency_reqs(&self) -> Option<BTreeMap<(String, String), String>> {
+        let dependencies = self.dependencies.as_ref()?.as_array()?;
+        Some(
+            dependencies
+                .iter()
+                .filter_map(|dependency| {
+                    // dependencies are stored as `[name, req, kind]`, older releases don't have a kind
+                    let field = |idx: usize| dependency.get(idx).and_then(Value::as_str);
+                    let name = field(0)?.to_owned();
+                    let req = field(1)?.to_owned();
+                    let kind = field(2).unwrap_or("normal").to_owned();
+                    Some(((name, kind), req))
+                })
+                .collect(),
+        )
+    }
+
+    fn public_features(&self) -> Option<BTreeSet<&str>> {
+        Some(
+            self.features
+                .as_ref()?
+                .iter()
+                .filter(|feature| !feature.is_private())
+                .map(|feature| feature.name.as_str())
+                .collect(),
+        )
+    }
+}
+
+fn compare_dependencies(from: &ReleaseData, to: &ReleaseData) -> Option<Vec<DependencyChange>> {
+    let from = from.dependency_reqs()?;
+    let to = to.dependency_reqs()?;
+
+    let keys: BTreeSet<_> = from.keys().chain(to.keys()).collect();
+    Some(
+        keys.into_iter()
+            .filter_map(|key| {
+                let (from_req, to_req) = (from.get(key), to.get(key));
+                if from_req == to_req {
+                    return None;
+                }
+                Some(DependencyChange {
+                    name: key.0.clone(),
+                    kind: key.1.clone(),
+                    from: from_req.cloned(),
+                    to: to_req.cloned(),
+                })
+            })
+            .collect(),
+    )
+}
+
+fn compare_features(from: &ReleaseData, to: &ReleaseData) -> Option<FeatureChanges> {
+    let from = from.public_features()?;
+    let to = to.public_features()?;
+
+    Some(FeatureChanges {
+        added: to.difference(&from).map(|name| name.to_string()).collect(),
+        removed: from.difference(&to).map(|name| name.to_string()).collect(),
+    })
+}
+
+/// Resolves the `from` or `to` parameter, returning the version and whether it was given exactly.
+async fn resolve_version(
+    pool: &Pool,
+    name: &str,
+    version: Option<&str>,
+) -> AxumResult<(String, bool)> {
+    let version = version.ok_or_else(|| {
+        AxumNope::BadRequest("both `from` and `to` versions are required".to_owned())
+    })?;
+
+    Ok(
+        match match_version_axum(pool, name, Some(version))
+            .await?
+            .assume_exact()?
+        {
+            MatchSemver::Exact((version, _)) => (version, true),
+            MatchSemver::Latest((version, _)) | MatchSemver::Semver((version, _)) => {
+                (version, false)
+            }
+        },
+    )
+}
+
+pub(crate) async fn compare_releases_handler(
+    Path(name): Path<String>,
+    Query(params): Query<CompareParams>,
+    Extension(pool): Extension<Pool>,
+    RequestedFormat(format): RequestedFormat,
+) -> AxumResult<impl IntoResponse> {
+    let (from, from_exact) = resolve_version(&pool, &name, params.from.as_deref()).await?;
+    let (to, to_exact) = resolve_version(&pool, &name, params.to.as_deref()).await?;
+    // `latest` and semver requirements match other releases once a new version is published
+    let is_exact = from_exact && to_exact;
+
+    let (metadata, comparison) = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let from_data = ReleaseData::load(&mut conn, &name, &from)?;
+        let to_data = ReleaseData::load(&mut conn, &name, &to)?;
+        Ok((
+            MetaData::from_crate(&mut conn, &name, &to, &to)?,
+            Comparison {
+                dependencies: compare_dependencies(&from_data, &to_data),
+                features: compare_features(&from_data, &to_data),
+                from_rustdoc_status: from_data.rustdoc_status,
+                to_rustdoc_status: to_data.rustdoc_status,
+                from,
+                to,
+            },
+        ))
+    })
+    .await?;
+
+    if format == OutputFormat::Json {
+        return Ok((
+            Extension(if is_exact {
+                CachePolicy::ForeverInCdnAndStaleInBrowser
+            } else {
+                CachePolicy::ForeverInCdn
+            }),
+            [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+            Json(comparison),
+        )
+            .into_response());
+    }
+
+    Ok(ComparePage {
+        metadata,
+        comparison,
+        is_exact,
+    }
+    .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test::{assert_cache_control, wrapper};
+    use crate::web::cache::CachePolicy;
+    use kuchiki::traits::TendrilSink;
+    use reqwest::StatusCode;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn create_releases(env: &crate::test::TestEnvironment) -> anyhow::Result<()> {
+        env.fake_release()
+            .name("foo")
+            .version("0.1.0")
+            .dependencies(&[
+                ("serde", "^1.0.100", None),
+                ("log", "^0.4", None),
+                ("tempfile", "^3", Some("dev")),
+            ])
+            .features(HashMap::from([
+                ("default".to_owned(), vec!["std".to_owned()]),
+                ("std".to_owned(), Vec::new()),
+                ("_private".to_owned(), Vec::new()),
+            ]))
+            .create()?;
+        env.fake_release()
+            .name("foo")
+            .version("0.2.0")
+            .dependencies(&[
+                ("serde", "^1.0.150", None),
+                ("tracing", "^0.1", None),
+                ("tempfile", "^3", Some("dev")),
+            ])
+            .features(HashMap::from([
+                ("default".to_owned(), vec!["std".to_owned()]),
+                ("std".to_owned(), Vec::new()),
+                ("alloc".to_owned(), Vec::new()),
+            ]))
+            .build_result_failed()
+            .create()?;
+        Ok(())
+    }
+
+    #[test]
+    fn compare_json() {
+        wrapper(|env| {
+            create_releases(env)?;
+
+            let resp = env
+                .frontend()
+                .get("/crate/foo/compare?from=0.1.0&to=0.2.0&format=json")
+                .send()?;
+            assert!(resp.status().is_success());
+            assert_cache_control(
+                &resp,
+                CachePolicy::ForeverInCdnAndStaleInBrowser,
+                &env.config(),
+            );
+            let value: serde_json::Value = resp.json()?;
+            assert_eq!(
+                value,
+                json!({
+                    "from": "0.1.0",
+                    "to": "0.2.0",
+                    "dependencies": [
+                        { "name": "log", "kind": "normal", "from": "^0.4", "to": null },
+                        { "name": "serde", "kind": "normal", "from": "^1.0.100", "to": "^1.0.150" },
+                        { "name": "tracing", "kind": "normal", "from": null, "to": "^0.1" },
+                    ],
+                    "features": { "added": ["alloc"], "removed": [] },
+                    "from_rustdoc_status": true,
+                    "to_rustdoc_status": false,
+                })
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn compare_html() {
+        wrapper(|env| {
+            create_releases(env)?;
+
+            let resp = env
+                .frontend()
+                .get("/crate/foo/compare?from=0.1.0&to=latest")
+                .send()?;
+            assert!(resp.status().is_success());
+            assert_cache_control(&resp, CachePolicy::ForeverInCdn, &env.config());
+            let page = kuchiki::parse_html().one(resp.text()?);
+
+            let dependencies: Vec<_> = page
+                .select("#dependency-changes tbody tr")
+                .unwrap()
+                .map(|row| {
+                    row.text_contents()
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect();
+            assert_eq!(
+                dependencies,
+                [
+                    "log normal ^0.4 removed",
+                    "serde normal ^1.0.100 ^1.0.150",
+                    "tracing normal added ^0.1",
+                ]
+            );
+            assert_eq!(
+                page.select_first("#added-features")
+                    .unwrap()
+                    .text_contents()
+                    .trim(),
+                "alloc"
+            );
+            assert!(page.select_first("#docs-status-changed").is_ok());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn compare_unavailable_data() {
+        wrapper(|env| {
+            create_releases(env)?;
+            env.db().conn().execute(
+                "UPDATE releases SET features = NULL, dependencies = NULL WHERE version = '0.1.0'",
+                &[],
+            )?;
+            let web = env.frontend();
+
+            let value: serde_json::Value = web
+                .get("/crate/foo/compare?from=0.1.0&to=0.2.0&format=json")
+                .send()?
+                .json()?;
+            assert_eq!(value["dependencies"], serde_json::Value::Null);
+            assert_eq!(value["features"], serde_json::Value::Null);
+
+            let page = kuchiki::parse_html().one(
+                web.get("/crate/foo/compare?from=0.1.0&to=0.2.0")
+                    .send()?
+                    .text()?,
+            );
+            for id in ["#dependency-changes", "#feature-changes"] {
+                let text = page.select_first(id).unwrap().text_contents();
+                assert!(text.contains("n/a"), "{}: {}", id, text);
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn compare_invalid_params() {
+        wrapper(|env| {
+            create_releases(env)?;
+            let web = env.frontend();
+
+            for url in [
+                "/crate/foo/compare",
+                "/crate/foo/compare?from=0.1.0",
+                "/crate/foo/compare?to=0.2.0",
+            ] {
+                assert_eq!(
+                    web.get(url).send()?.status(),
+                    StatusCode::BAD_REQUEST,
+                    "{}",
+                    url
+                );
+            }
+            assert_eq!(
+                web.get("/crate/foo/compare?from=0.1.0&to=0.3.0")
+                    .send()?
+                    .status(),
+                StatusCode::NOT_FOUND
+            );
+            assert_eq!(
+                web.get("/crate/bar/compare?from=0.1.0&to=0.2.0")
+                    .send()?
+                    .status(),
+                StatusCode::NOT_FOUND
+            );
+            Ok(())
+        });
+    }
+}
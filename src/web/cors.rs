@@ -0,0 +1,200 @@
+//! CORS for the JSON API, so browser based tools on the `DOCSRS_CORS_ALLOWED_ORIGINS` can call
+//! it, including the endpoints that need a preflight request like the authenticated ones.
+//!
+//! Documentation is never served with CORS headers, pages only when they can also be fetched as
+//! JSON.
+
+use crate::Config;
+use axum::{
+    http::{
+        header::{
+            ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+            ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE,
+            ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+        },
+        HeaderValue, Method, Request as AxumHttpRequest, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response as AxumResponse},
+};
+use std::sync::Arc;
+
+const ALLOWED_METHODS: &str = "GET, POST, DELETE";
+const ALLOWED_HEADERS: &str = "authorization, content-type";
+/// Response headers besides the CORS-safelisted ones that scripts can read
+const EXPOSED_HEADERS: &str = "link, retry-after, server-timing";
+/// How long browsers can cache preflight responses, in seconds
+const PREFLIGHT_MAX_AGE: &str = "3600";
+
+/// Answers the preflight requests of JSON API routes, the CORS headers are added by
+/// [`cors_middleware`].
+pub(crate) async fn preflight_handler() -> impl IntoResponse {
+    StatusCode::NO_CONTENT
+}
+
+/// Adds the CORS headers to responses of JSON API routes when the `Origin` of the request is one
+/// of the `cors_allowed_origins`.
+///
+/// Responses to other origins are left alone, so public endpoints that allow every origin with
+/// `Access-Control-Allow-Origin: *` keep doing so.
+pub(crate) async fn cors_middleware<B>(request: AxumHttpRequest<B>, next: Next<B>) -> AxumResponse {
+    let config = request
+        .extensions()
+        .get::<Arc<Config>>()
+        .expect("missing config extension")
+        .clone();
+
+    let origin = request
+        .headers()
+        .get(ORIGIN)
+        .filter(|origin| {
+            config
+                .cors_allowed_origins
+                .iter()
+                .any(|allowed| origin.as_bytes() == allowed.as_bytes())
+        })
+        .cloned();
+    let is_preflight = request.method() == Method::OPTIONS
+        && request
+            .headers()
+            .contains_key(ACCESS_CONTROL_REQUEST_METHOD);
+
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    // the CORS headers depend on the origin
+    headers.append(VARY, HeaderValue::from_static("Origin"));
+
+    if let Some(origin) = origin {
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        headers.insert(
+            ACCESS_CONTROL_EXPOSE_HEADERS,
+            HeaderValue::from_static(EXPOSED_HEADERS),
+        );
+        if is_preflight {
+            headers.insert(
+                ACCESS_CONTROL_ALLOW_METHODS,
+                HeaderValue::from_static(ALLOWED_METHODS),
+            );
+            headers.insert(
+                ACCESS_CONTROL_ALLOW_HEADERS,
+                HeaderValue::from_static(ALLOWED_HEADERS),
+            );
+            headers.insert(
+                ACCESS_CONTROL_MAX_AGE,
+                HeaderValue::from_static(PREFLIGHT_MAX_AGE),
+            );
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test::wrapper;
+    use reqwest::StatusCode;
+
+    const ALLOWED: &str = "https://tools.example.com";
+
+    #[test]
+    fn allowed_origin() {
+        wrapper(|env| {
+            env.override_config(|config| config.cors_allowed_origins = vec![ALLOWED.into()]);
+            let web = env.frontend();
+
+            let response = web
+                .get("/about/stats.json")
+                .header("Origin", ALLOWED)
+                .send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["access-control-allow-origin"], ALLOWED);
+            assert!(response.headers()["access-control-expose-headers"]
+                .to_str()?
+                .contains("retry-after"));
+            assert!(response
+                .headers()
+                .get_all("vary")
+                .iter()
+                .any(|vary| vary == "Origin"));
+
+            let response = web
+                .options("/-/reports.json")
+                .header("Origin", ALLOWED)
+                .header("Access-Control-Request-Method", "GET")
+                .header("Access-Control-Request-Headers", "authorization")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::NO_CONTENT);
+            assert_eq!(response.headers()["access-control-allow-origin"], ALLOWED);
+            assert!(response.headers()["access-control-allow-headers"]
+                .to_str()?
+                .contains("authorization"));
+            assert!(response.headers()["access-control-allow-methods"]
+                .to_str()?
+                .contains("GET"));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn disallowed_origin() {
+        wrapper(|env| {
+            env.override_config(|config| config.cors_allowed_origins = vec![ALLOWED.into()]);
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let web = env.frontend();
+
+            for origin in ["https://evil.example.com", "https://tools.example.com.evil"] {
+                let response = web
+                    .get("/about/stats.json")
+                    .header("Origin", origin)
+                    .send()?;
+                assert_eq!(response.status(), StatusCode::OK);
+                assert!(response
+                    .headers()
+                    .get("access-control-allow-origin")
+                    .is_none());
+
+                let response = web
+                    .options("/-/reports.json")
+                    .header("Origin", origin)
+                    .header("Access-Control-Request-Method", "GET")
+                    .send()?;
+                assert!(response
+                    .headers()
+                    .get("access-control-allow-origin")
+                    .is_none());
+                assert!(response
+                    .headers()
+                    .get("access-control-allow-methods")
+                    .is_none());
+            }
+
+            // pages never get CORS headers
+            let response = web
+                .get("/crate/foo/0.1.0")
+                .header("Origin", ALLOWED)
+                .send()?;
+            assert!(response
+                .headers()
+                .get("access-control-allow-origin")
+                .is_none());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn no_origins_allowed_by_default() {
+        wrapper(|env| {
+            let response = env
+                .frontend()
+                .get("/about/stats.json")
+                .header("Origin", ALLOWED)
+                .send()?;
+            assert!(response
+                .headers()
+                .get("access-control-allow-origin")
+                .is_none());
+            Ok(())
+        })
+    }
+}
@@ -1,25 +1,39 @@
-use super::{markdown, match_version, MatchSemver, MetaData};
-use crate::utils::{get_correct_docsrs_style_file, report_error, spawn_blocking};
+use super::{markdown, match_version, match_version_axum, MatchSemver, MetaData};
+use crate::utils::{
+    get_correct_docsrs_style_file, report_error, scoped_registry::split_scoped_name, spawn_blocking,
+};
 use crate::{
-    db::Pool,
+    db::{types::Feature, Pool},
     impl_axum_webpage,
     repositories::RepositoryStatsUpdater,
     web::{
+        base_url::BaseUrl,
         cache::CachePolicy,
-        error::{AxumNope, AxumResult},
+        error::{AxumNope, AxumResult, JsonAxumResult, Nope},
+        metrics::ServerTiming,
+        output_format::{OutputFormat, RequestedFormat},
     },
+    Config,
 };
 use anyhow::anyhow;
 use axum::{
-    extract::{Extension, Path},
+    extract::{Extension, Path, Query},
+    http::{
+        header::{ACCESS_CONTROL_ALLOW_ORIGIN, LOCATION},
+        StatusCode,
+    },
     response::{IntoResponse, Response as AxumResponse},
+    Json,
 };
 use chrono::{DateTime, Utc};
 use postgres::GenericClient;
+use semver::{Version, VersionReq};
 use serde::Deserialize;
 use serde::{ser::Serializer, Serialize};
 use serde_json::Value;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Instant;
 
 // TODO: Add target name and versions
 
@@ -36,8 +50,12 @@ pub struct CrateDetails {
     rustdoc: Option<String>, // this is description_long in database
     release_time: DateTime<Utc>,
     build_status: bool,
+    /// Whether this release was yanked on crates.io
+    pub(crate) yanked: bool,
     last_successful_build: Option<String>,
-    rustdoc_status: bool,
+    /// Completion time of the newest build of this release
+    pub(crate) last_build_time: Option<DateTime<Utc>>,
+    pub(crate) rustdoc_status: bool,
     pub archive_storage: bool,
     repository_url: Option<String>,
     homepage_url: Option<String>,
@@ -50,10 +68,24 @@ pub struct CrateDetails {
     is_library: bool,
     license: Option<String>,
     pub(crate) documentation_url: Option<String>,
+    has_book: bool,
+    has_changelog: bool,
+    /// The static files the documentation pages load right away
+    pub(crate) preload_assets: Vec<String>,
     total_items: Option<i32>,
     documented_items: Option<i32>,
     total_items_needing_examples: Option<i32>,
     items_with_examples: Option<i32>,
+    /// Whether the docs were built using nightly-only rustdoc features like `--cfg docsrs`,
+    /// `None` for releases built before this was recorded
+    nightly_features: Option<bool>,
+    /// The minimum supported Rust version from `package.rust-version`, `None` when the manifest
+    /// doesn't specify it or the release was built before it was recorded
+    rust_version: Option<String>,
+    /// The public feature flags of this release, which can be selected for the `dependency`
+    features: Vec<String>,
+    /// The dependency on this release, ready to copy
+    dependency: DependencySnippet,
     /// Database id for this crate
     pub(crate) crate_id: i32,
     /// Database id for this release
@@ -69,6 +101,50 @@ struct RepositoryMetadata {
     icon: &'static str,
 }
 
+/// A dependency on a release with the features selected with `?enable=`, both as line for the
+/// `[dependencies]` of a `Cargo.toml` and as `cargo add` command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct DependencySnippet {
+    features: Vec<String>,
+    toml: String,
+    cargo_add: String,
+}
+
+impl DependencySnippet {
+    fn new(name: &str, version: &str, features: Vec<String>) -> Self {
+        let (name, registry) = split_scoped_name(name);
+        let mut fields = Vec::new();
+        let mut cargo_add = format!("cargo add {}@{}", name, version);
+
+        if !features.is_empty() {
+            let quoted: Vec<_> = features.iter().map(|f| format!("\"{}\"", f)).collect();
+            fields.push(format!("features = [{}]", quoted.join(", ")));
+            cargo_add += &format!(" --features {}", features.join(","));
+        }
+        if let Some(registry) = registry {
+            fields.push(format!("registry = \"{}\"", registry));
+            cargo_add += &format!(" --registry {}", registry);
+        }
+
+        let toml = if fields.is_empty() {
+            format!("{} = \"{}\"", name, version)
+        } else {
+            format!(
+                "{} = {{ version = \"{}\", {} }}",
+                name,
+                version,
+                fields.join(", ")
+            )
+        };
+
+        DependencySnippet {
+            features,
+            toml,
+            cargo_add,
+        }
+    }
+}
+
 fn optional_markdown<S>(markdown: &Option<String>, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -128,12 +204,23 @@ impl CrateDetails {
                 releases.doc_targets,
                 releases.license,
                 releases.documentation_url,
+                releases.has_book,
+                releases.has_changelog,
+                releases.preload_assets,
                 releases.default_target,
                 releases.doc_rustc_version,
+                releases.nightly_features,
+                releases.rust_version,
+                releases.features,
                 doc_coverage.total_items,
                 doc_coverage.documented_items,
                 doc_coverage.total_items_needing_examples,
-                doc_coverage.items_with_examples
+                doc_coverage.items_with_examples,
+                (
+                    SELECT MAX(builds.build_time)
+                    FROM builds
+                    WHERE builds.rid = releases.id
+                ) AS last_build_time
             FROM releases
             INNER JOIN crates ON releases.crate_id = crates.id
             LEFT JOIN doc_coverage ON doc_coverage.release_id = releases.id
@@ -178,9 +265,21 @@ impl CrateDetails {
             rustdoc_css_file: get_correct_docsrs_style_file(krate.get("doc_rustc_version"))?,
         };
 
+        let name: String = krate.get("name");
+        let version: String = krate.get("version");
+        let mut features: Vec<String> = krate
+            .get::<_, Option<Vec<Feature>>>("features")
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|feature| !feature.is_private() && feature.name != "default")
+            .map(|feature| feature.name)
+            .collect();
+        features.sort();
+
         let mut crate_details = CrateDetails {
-            name: krate.get("name"),
-            version: krate.get("version"),
+            dependency: DependencySnippet::new(&name, &version, Vec::new()),
+            name,
+            version,
             description: krate.get("description"),
             owners: Vec::new(),
             dependencies: krate.get("dependencies"),
@@ -188,7 +287,9 @@ impl CrateDetails {
             rustdoc: krate.get("description_long"),
             release_time: krate.get("release_time"),
             build_status: krate.get("build_status"),
+            yanked: krate.get("yanked"),
             last_successful_build: None,
+            last_build_time: krate.get("last_build_time"),
             rustdoc_status: krate.get("rustdoc_status"),
             archive_storage: krate.get("archive_storage"),
             repository_url: krate.get("repository_url"),
@@ -202,10 +303,16 @@ impl CrateDetails {
             is_library: krate.get("is_library"),
             license: krate.get("license"),
             documentation_url: krate.get("documentation_url"),
+            has_book: krate.get("has_book"),
+            has_changelog: krate.get("has_changelog"),
+            preload_assets: krate.get("preload_assets"),
             documented_items: krate.get("documented_items"),
             total_items: krate.get("total_items"),
             total_items_needing_examples: krate.get("total_items_needing_examples"),
             items_with_examples: krate.get("items_with_examples"),
+            nightly_features: krate.get("nightly_features"),
+            rust_version: krate.get("rust_version"),
+            features,
             crate_id,
             release_id,
         };
@@ -244,6 +351,15 @@ impl CrateDetails {
             .find(|release| release.version.pre.is_empty() && !release.yanked)
             .unwrap_or(&self.releases[0])
     }
+
+    /// Selects the features of the `dependency`, unknown features are ignored.
+    fn select_features(&mut self, selected: Vec<String>) {
+        let selected = selected
+            .into_iter()
+            .filter(|name| self.features.contains(name))
+            .collect();
+        self.dependency = DependencySnippet::new(&self.name, &self.version, selected);
+    }
 }
 
 /// Return all releases for a crate, sorted in descending order by semver
@@ -297,6 +413,7 @@ pub(crate) fn releases_for_crate(
 #[derive(Debug, Clone, PartialEq, Serialize)]
 struct CrateDetailsPage {
     details: CrateDetails,
+    base_url: String,
 }
 
 impl_axum_webpage! {
@@ -310,14 +427,46 @@ pub(crate) struct CrateDetailHandlerParams {
     version: Option<String>,
 }
 
-#[tracing::instrument]
+/// Shows the details page of a release.
+///
+/// `/crate/:name` without a version resolves like this:
+/// 1. with `DOCSRS_CRATE_DETAILS_REDIRECT_TO_DOCS` enabled and a successful build of the latest
+///    release, redirect to its documentation at `/:name/latest/:target_name/`,
+/// 2. otherwise redirect to the details of the latest release at `/crate/:name/latest`.
+///
+/// URLs with a version always show the details page, features selected with `?enable=` are
+/// added to its dependency snippet.
+#[tracing::instrument(skip(config))]
 pub(crate) async fn crate_details_handler(
     Path(params): Path<CrateDetailHandlerParams>,
     Extension(pool): Extension<Pool>,
+    Extension(config): Extension<Arc<Config>>,
     Extension(repository_stats_updater): Extension<Arc<RepositoryStatsUpdater>>,
+    BaseUrl(base_url): BaseUrl,
+    RequestedFormat(format): RequestedFormat,
+    Query(query): Query<Vec<(String, String)>>,
 ) -> AxumResult<AxumResponse> {
     // this handler must always called with a crate name
     if params.version.is_none() {
+        if config.crate_details_redirect_to_docs {
+            let matched = spawn_blocking({
+                let pool = pool.clone();
+                let name = params.name.clone();
+                move || {
+                    let mut conn = pool.get()?;
+                    Ok(match_version(&mut conn, &name, None)?)
+                }
+            })
+            .await?;
+            if matched.corrected_name.is_none() && matched.rustdoc_status {
+                return Ok(super::axum_cached_redirect(
+                    &format!("/{}/latest/{}/", params.name, matched.target_name),
+                    CachePolicy::ForeverInCdn,
+                )?
+                .into_response());
+            }
+        }
+
         return Ok(super::axum_cached_redirect(
             &format!("/crate/{}/latest", params.name),
             CachePolicy::ForeverInCdn,
@@ -325,6 +474,8 @@ pub(crate) async fn crate_details_handler(
         .into_response());
     }
 
+    let mut server_timing = ServerTiming::default();
+    let start = Instant::now();
     let found_version = spawn_blocking({
         let pool = pool.clone();
         let params = params.clone();
@@ -349,8 +500,10 @@ pub(crate) async fn crate_details_handler(
             .into_response());
         }
     };
+    server_timing.record("match version", start.elapsed());
 
-    let details = spawn_blocking(move || {
+    let start = Instant::now();
+    let mut details = spawn_blocking(move || {
         let mut conn = pool.get()?;
         CrateDetails::new(
             &mut *conn,
@@ -362,181 +515,959 @@ pub(crate) async fn crate_details_handler(
     })
     .await?
     .ok_or(AxumNope::VersionNotFound)?;
+    details.select_features(super::features::selected_features(&query));
+    server_timing.record("crate details", start.elapsed());
 
-    let mut res = CrateDetailsPage { details }.into_response();
+    let mut res = match format {
+        OutputFormat::Json => ([(ACCESS_CONTROL_ALLOW_ORIGIN, "*")], Json(details)).into_response(),
+        OutputFormat::Html => CrateDetailsPage { details, base_url }.into_response(),
+    };
     res.extensions_mut()
         .insert::<CachePolicy>(if is_latest_url {
             CachePolicy::ForeverInCdn
         } else {
             CachePolicy::ForeverInCdnAndStaleInBrowser
         });
+    res.extensions_mut().insert(server_timing);
     Ok(res.into_response())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::index::api::CrateOwner;
-    use crate::test::{
-        assert_cache_control, assert_redirect, assert_redirect_cached, wrapper, TestDatabase,
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct ReadmePage {
+    name: String,
+    version: String,
+    #[serde(serialize_with = "markdown_to_html")]
+    readme: String,
+}
+
+fn markdown_to_html<S>(markdown: &str, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    markdown::render(markdown).serialize(serializer)
+}
+
+impl_axum_webpage! {
+    ReadmePage = "crate/readme.html",
+    cache_policy = |_| CachePolicy::ForeverInCdn,
+    cpu_intensive_rendering = true,
+}
+
+/// Renders the README of a release as a standalone page, without the crate details around it.
+pub(crate) async fn readme_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+) -> AxumResult<AxumResponse> {
+    let version = match match_version_axum(&pool, &name, Some(&req_version))
+        .await?
+        .assume_exact()?
+    {
+        MatchSemver::Exact((version, _)) | MatchSemver::Latest((version, _)) => version,
+        MatchSemver::Semver((version, _)) => {
+            return Ok(super::axum_cached_redirect(
+                &format!("/crate/{}/{}/readme", name, version),
+                CachePolicy::ForeverInCdn,
+            )?
+            .into_response());
+        }
     };
-    use anyhow::{Context, Error};
-    use kuchiki::traits::TendrilSink;
-    use std::collections::HashMap;
 
-    fn assert_last_successful_build_equals(
-        db: &TestDatabase,
-        package: &str,
-        version: &str,
-        expected_last_successful_build: Option<&str>,
-    ) -> Result<(), Error> {
-        let details = CrateDetails::new(&mut *db.conn(), package, version, version, None)
-            .with_context(|| anyhow::anyhow!("could not fetch crate details"))?
-            .unwrap();
+    let readme: String = spawn_blocking({
+        let name = name.clone();
+        let version = version.clone();
+        move || {
+            let mut conn = pool.get()?;
+            Ok(conn
+                .query_opt(
+                    "SELECT releases.readme
+                     FROM releases
+                     INNER JOIN crates ON releases.crate_id = crates.id
+                     WHERE crates.name = $1 AND releases.version = $2",
+                    &[&name, &version],
+                )?
+                .and_then(|row| row.get("readme")))
+        }
+    })
+    .await?
+    .ok_or(AxumNope::ResourceNotFound)?;
 
-        assert_eq!(
-            details.last_successful_build,
-            expected_last_successful_build.map(|s| s.to_string()),
-        );
-        Ok(())
+    Ok(ReadmePage {
+        name,
+        version,
+        readme,
     }
+    .into_response())
+}
 
-    #[test]
-    fn test_last_successful_build_when_last_releases_failed_or_yanked() {
-        wrapper(|env| {
-            let db = env.db();
+/// Documentation coverage of a release, all fields are `null` for releases
+/// built before the coverage was recorded.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct Coverage {
+    total_items: Option<i32>,
+    documented_items: Option<i32>,
+    total_items_needing_examples: Option<i32>,
+    items_with_examples: Option<i32>,
+    /// Percentage of documented items
+    percentage: Option<f32>,
+}
 
-            env.fake_release().name("foo").version("0.0.1").create()?;
-            env.fake_release().name("foo").version("0.0.2").create()?;
-            env.fake_release()
-                .name("foo")
-                .version("0.0.3")
-                .build_result_failed()
-                .create()?;
-            env.fake_release()
-                .name("foo")
-                .version("0.0.4")
-                .yanked(true)
-                .create()?;
-            env.fake_release()
-                .name("foo")
-                .version("0.0.5")
-                .build_result_failed()
-                .yanked(true)
-                .create()?;
+pub(crate) async fn coverage_json_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let version = match match_version_axum(&pool, &name, Some(&req_version))
+        .await?
+        .assume_exact()?
+    {
+        MatchSemver::Exact((version, _)) | MatchSemver::Latest((version, _)) => version,
+        MatchSemver::Semver((version, _)) => {
+            return Ok(super::axum_cached_redirect(
+                &format!("/crate/{}/{}/coverage.json", name, version),
+                CachePolicy::ForeverInCdn,
+            )?
+            .into_response());
+        }
+    };
 
-            assert_last_successful_build_equals(db, "foo", "0.0.1", None)?;
-            assert_last_successful_build_equals(db, "foo", "0.0.2", None)?;
-            assert_last_successful_build_equals(db, "foo", "0.0.3", Some("0.0.2"))?;
-            assert_last_successful_build_equals(db, "foo", "0.0.4", None)?;
-            assert_last_successful_build_equals(db, "foo", "0.0.5", Some("0.0.2"))?;
-            Ok(())
-        });
-    }
+    let coverage = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let row = conn
+            .query_opt(
+                "SELECT
+                    doc_coverage.total_items,
+                    doc_coverage.documented_items,
+                    doc_coverage.total_items_needing_examples,
+                    doc_coverage.items_with_examples
+                 FROM releases
+                 INNER JOIN crates ON releases.crate_id = crates.id
+                 LEFT JOIN doc_coverage ON doc_coverage.release_id = releases.id
+                 WHERE crates.name = $1 AND releases.version = $2",
+                &[&name, &version],
+            )?
+            .ok_or(AxumNope::VersionNotFound)?;
+
+        let total_items: Option<i32> = row.get("total_items");
+        let documented_items: Option<i32> = row.get("documented_items");
+        Ok(Coverage {
+            total_items,
+            documented_items,
+            total_items_needing_examples: row.get("total_items_needing_examples"),
+            items_with_examples: row.get("items_with_examples"),
+            percentage: match (documented_items, total_items) {
+                (Some(documented), Some(total)) if total > 0 => {
+                    Some(documented as f32 * 100.0 / total as f32)
+                }
+                _ => None,
+            },
+        })
+    })
+    .await?;
 
-    #[test]
-    fn test_last_successful_build_when_all_releases_failed_or_yanked() {
-        wrapper(|env| {
-            let db = env.db();
+    Ok((
+        Extension(CachePolicy::NoStoreMustRevalidate),
+        [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+        Json(coverage),
+    )
+        .into_response())
+}
 
-            env.fake_release()
-                .name("foo")
-                .version("0.0.1")
-                .build_result_failed()
-                .create()?;
-            env.fake_release()
-                .name("foo")
-                .version("0.0.2")
-                .build_result_failed()
-                .create()?;
-            env.fake_release()
-                .name("foo")
-                .version("0.0.3")
-                .yanked(true)
-                .create()?;
+/// How many bytes the files of a release occupy before they are compressed, all fields are `null`
+/// for releases built before the sizes were recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub(crate) struct Sizes {
+    /// The HTML pages generated by rustdoc for all targets
+    documentation: Option<i64>,
+    /// The files in the source archive
+    sources: Option<i64>,
+    /// The search indexes generated by rustdoc for all targets
+    search_index: Option<i64>,
+}
 
-            assert_last_successful_build_equals(db, "foo", "0.0.1", None)?;
-            assert_last_successful_build_equals(db, "foo", "0.0.2", None)?;
-            assert_last_successful_build_equals(db, "foo", "0.0.3", None)?;
-            Ok(())
-        });
+impl Sizes {
+    /// Reads the columns of `doc_sizes` joined to a release, which are `NULL` when there is no
+    /// row for it.
+    pub(crate) fn from_row(row: &postgres::Row) -> Self {
+        Self {
+            documentation: row.get("documentation"),
+            sources: row.get("sources"),
+            search_index: row.get("search_index"),
+        }
     }
+}
 
-    #[test]
-    fn test_last_successful_build_with_intermittent_releases_failed_or_yanked() {
-        wrapper(|env| {
-            let db = env.db();
+/// The sizes of the documentation of a release as JSON, see [`Sizes`].
+pub(crate) async fn sizes_json_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let version = match match_version_axum(&pool, &name, Some(&req_version))
+        .await?
+        .assume_exact()?
+    {
+        MatchSemver::Exact((version, _)) | MatchSemver::Latest((version, _)) => version,
+        MatchSemver::Semver((version, _)) => {
+            return Ok(super::axum_cached_redirect(
+                &format!("/crate/{}/{}/sizes.json", name, version),
+                CachePolicy::ForeverInCdn,
+            )?
+            .into_response());
+        }
+    };
 
-            env.fake_release().name("foo").version("0.0.1").create()?;
-            env.fake_release()
-                .name("foo")
-                .version("0.0.2")
-                .build_result_failed()
-                .create()?;
-            env.fake_release()
-                .name("foo")
-                .version("0.0.3")
-                .yanked(true)
-                .create()?;
-            env.fake_release().name("foo").version("0.0.4").create()?;
+    let sizes = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let row = conn
+            .query_opt(
+                "SELECT
+                    doc_sizes.documentation,
+                    doc_sizes.sources,
+                    doc_sizes.search_index
+                 FROM releases
+                 INNER JOIN crates ON releases.crate_id = crates.id
+                 LEFT JOIN doc_sizes ON doc_sizes.release_id = releases.id
+                 WHERE crates.name = $1 AND releases.version = $2",
+                &[&name, &version],
+            )?
+            .ok_or(AxumNope::VersionNotFound)?;
+        Ok(Sizes::from_row(&row))
+    })
+    .await?;
 
-            assert_last_successful_build_equals(db, "foo", "0.0.1", None)?;
-            assert_last_successful_build_equals(db, "foo", "0.0.2", Some("0.0.4"))?;
-            assert_last_successful_build_equals(db, "foo", "0.0.3", None)?;
-            assert_last_successful_build_equals(db, "foo", "0.0.4", None)?;
-            Ok(())
-        });
-    }
+    Ok((
+        Extension(CachePolicy::NoStoreMustRevalidate),
+        [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+        Json(sizes),
+    )
+        .into_response())
+}
 
-    #[test]
-    fn test_releases_should_be_sorted() {
-        wrapper(|env| {
-            let db = env.db();
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Msrv {
+    name: String,
+    version: String,
+    /// `None` when the manifest doesn't specify `rust-version`
+    rust_version: Option<String>,
+}
 
-            // Add new releases of 'foo' out-of-order since CrateDetails should sort them descending
-            env.fake_release().name("foo").version("0.1.0").create()?;
-            env.fake_release().name("foo").version("0.1.1").create()?;
-            env.fake_release()
-                .name("foo")
-                .version("0.3.0")
-                .build_result_failed()
-                .create()?;
-            env.fake_release().name("foo").version("1.0.0").create()?;
-            env.fake_release().name("foo").version("0.12.0").create()?;
-            env.fake_release()
-                .name("foo")
-                .version("0.2.0")
-                .yanked(true)
-                .create()?;
-            env.fake_release()
-                .name("foo")
-                .version("0.2.0-alpha")
-                .create()?;
-            env.fake_release()
-                .name("foo")
-                .version("0.0.1")
-                .build_result_failed()
-                .binary(true)
-                .create()?;
+/// The minimum supported Rust version of a release as JSON, see [`Msrv`].
+pub(crate) async fn msrv_json_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let version = match match_version_axum(&pool, &name, Some(&req_version))
+        .await?
+        .assume_exact()?
+    {
+        MatchSemver::Exact((version, _)) | MatchSemver::Latest((version, _)) => version,
+        MatchSemver::Semver((version, _)) => {
+            return Ok(super::axum_cached_redirect(
+                &format!("/crate/{}/{}/msrv.json", name, version),
+                CachePolicy::ForeverInCdn,
+            )?
+            .into_response());
+        }
+    };
 
-            let details = CrateDetails::new(&mut *db.conn(), "foo", "0.2.0", "0.2.0", None)
-                .unwrap()
-                .unwrap();
-            assert_eq!(
-                details.releases,
-                vec![
-                    Release {
-                        version: semver::Version::parse("1.0.0")?,
-                        build_status: true,
-                        yanked: false,
-                        is_library: true,
-                        rustdoc_status: true,
-                        id: details.releases[0].id,
-                        target_name: "foo".to_owned(),
-                    },
-                    Release {
-                        version: semver::Version::parse("0.12.0")?,
-                        build_status: true,
+    let msrv = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let row = conn
+            .query_opt(
+                "SELECT releases.rust_version
+                 FROM releases
+                 INNER JOIN crates ON releases.crate_id = crates.id
+                 WHERE crates.name = $1 AND releases.version = $2",
+                &[&name, &version],
+            )?
+            .ok_or(AxumNope::VersionNotFound)?;
+        Ok(Msrv {
+            name,
+            version,
+            rust_version: row.get("rust_version"),
+        })
+    })
+    .await?;
+
+    Ok((
+        Extension(CachePolicy::NoStoreMustRevalidate),
+        [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+        Json(msrv),
+    )
+        .into_response())
+}
+
+/// Depth of the dependency graph returned by default
+pub(super) const DEPENDENCIES_DEFAULT_DEPTH: u32 = 1;
+/// Maximum depth of the dependency graph, deeper graphs grow too large to resolve per request
+pub(super) const DEPENDENCIES_MAX_DEPTH: u32 = 5;
+/// Maximum number of releases in the dependency graph, wide graphs can grow too large too
+pub(super) const DEPENDENCIES_MAX_NODES: usize = 500;
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct DependenciesParams {
+    depth: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct DependencyEdge {
+    name: String,
+    req: String,
+    kind: String,
+    /// Newest release on docs.rs matching `req`, `None` if there is none
+    version: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct DependencyNode {
+    name: String,
+    version: String,
+    /// Distance from the requested release
+    depth: u32,
+    /// `None` for nodes at the maximum depth, whose dependencies weren't resolved
+    dependencies: Option<Vec<DependencyEdge>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct DependencyGraph {
+    name: String,
+    version: String,
+    depth: u32,
+    /// Whether releases were left out after [`DEPENDENCIES_MAX_NODES`] were reached, they are
+    /// still listed as dependencies of the included releases
+    truncated: bool,
+    /// Every release in the graph once, in breadth-first order starting with the requested one
+    nodes: Vec<DependencyNode>,
+}
+
+/// A dependency of a release as name, version requirement and kind
+type Dependency = (String, String, String);
+
+/// The dependencies needed to build each of the `releases`, read in a single query.
+/// Dev-dependencies are skipped.
+fn release_dependencies(
+    conn: &mut impl GenericClient,
+    releases: &[(String, String)],
+) -> anyhow::Result<HashMap<(String, String), Vec<Dependency>>> {
+    let (names, versions): (Vec<_>, Vec<_>) = releases.iter().cloned().unzip();
+    let rows = conn.query(
+        "SELECT crates.name, releases.version, releases.dependencies
+         FROM releases
+         INNER JOIN crates ON releases.crate_id = crates.id
+         WHERE (crates.name, releases.version) IN (
+             SELECT * FROM UNNEST($1::TEXT[], $2::TEXT[])
+         )",
+        &[&names, &versions],
+    )?;
+
+    let mut dependencies = HashMap::new();
+    for row in rows {
+        let stored: Option<Value> = row.get(2);
+        let release_dependencies = stored
+            .as_ref()
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|dependency| {
+                // dependencies are stored as `[name, req, kind]`, older releases don't have a kind
+                let field = |idx: usize| dependency.get(idx).and_then(Value::as_str);
+                let kind = field(2).unwrap_or("normal");
+                if kind == "dev" {
+                    return None;
+                }
+                Some((field(0)?.to_owned(), field(1)?.to_owned(), kind.to_owned()))
+            })
+            .collect();
+        dependencies.insert((row.get(0), row.get(1)), release_dependencies);
+    }
+    Ok(dependencies)
+}
+
+/// Loads the non-yanked versions of the crates `names` into `versions_cache` in a single query,
+/// newest first.
+fn load_versions(
+    conn: &mut impl GenericClient,
+    versions_cache: &mut HashMap<String, Vec<Version>>,
+    names: &[String],
+) -> anyhow::Result<()> {
+    if names.is_empty() {
+        return Ok(());
+    }
+    for name in names {
+        // crates that aren't on docs.rs don't have any versions
+        versions_cache.entry(name.clone()).or_default();
+    }
+
+    let rows = conn.query(
+        "SELECT crates.name, releases.version
+         FROM releases
+         INNER JOIN crates ON releases.crate_id = crates.id
+         WHERE crates.name = ANY($1) AND NOT releases.yanked",
+        &[&names],
+    )?;
+    for row in rows {
+        if let (Some(versions), Ok(version)) = (
+            versions_cache.get_mut(row.get::<_, &str>(0)),
+            Version::parse(row.get(1)),
+        ) {
+            versions.push(version);
+        }
+    }
+    for name in names {
+        if let Some(versions) = versions_cache.get_mut(name) {
+            versions.sort_unstable_by(|a, b| b.cmp(a));
+        }
+    }
+    Ok(())
+}
+
+/// Newest non-yanked release of `name` matching `req`, preferring releases over pre-releases.
+/// The versions of `name` have to be loaded with [`load_versions`] before.
+fn resolve_dependency(
+    versions_cache: &HashMap<String, Vec<Version>>,
+    name: &str,
+    req: &str,
+) -> Option<String> {
+    let req = VersionReq::parse(req).ok()?;
+    versions_cache
+        .get(name)?
+        .iter()
+        .find(|version| req.matches(version))
+        .map(Version::to_string)
+}
+
+/// Resolves the dependencies of a release to the newest matching releases on docs.rs,
+/// walking them breadth-first up to `max_depth`.
+///
+/// Every level of the graph is read with a constant number of queries. Every release is only
+/// visited once, which also breaks dependency cycles, and at most [`DEPENDENCIES_MAX_NODES`]
+/// releases are visited. Dev-dependencies aren't followed since they aren't needed to build
+/// dependents.
+fn dependency_graph(
+    conn: &mut impl GenericClient,
+    name: String,
+    version: String,
+    max_depth: u32,
+) -> anyhow::Result<DependencyGraph> {
+    let mut versions_cache: HashMap<String, Vec<Version>> = HashMap::new();
+    let mut visited = HashSet::new();
+    let mut nodes = Vec::new();
+    let mut truncated = false;
+
+    visited.insert((name.clone(), version.clone()));
+    let mut level = vec![(name.clone(), version.clone())];
+    let mut depth = 0;
+    while !level.is_empty() {
+        if depth == max_depth {
+            nodes.extend(level.into_iter().map(|(name, version)| DependencyNode {
+                name,
+                version,
+                depth,
+                dependencies: None,
+            }));
+            break;
+        }
+
+        let mut dependencies = release_dependencies(conn, &level)?;
+        let unknown: HashSet<_> = dependencies
+            .values()
+            .flatten()
+            .map(|(dep_name, _, _)| dep_name)
+            .filter(|dep_name| !versions_cache.contains_key(*dep_name))
+            .cloned()
+            .collect();
+        load_versions(
+            conn,
+            &mut versions_cache,
+            &unknown.into_iter().collect::<Vec<_>>(),
+        )?;
+
+        let mut next_level = Vec::new();
+        for (node_name, node_version) in level {
+            let mut edges = Vec::new();
+            let node_dependencies = dependencies
+                .remove(&(node_name.clone(), node_version.clone()))
+                .unwrap_or_default();
+            for (dep_name, req, kind) in node_dependencies {
+                let resolved = resolve_dependency(&versions_cache, &dep_name, &req);
+                if let Some(resolved) = &resolved {
+                    let release = (dep_name.clone(), resolved.clone());
+                    if !visited.contains(&release) {
+                        if visited.len() < DEPENDENCIES_MAX_NODES {
+                            visited.insert(release.clone());
+                            next_level.push(release);
+                        } else {
+                            truncated = true;
+                        }
+                    }
+                }
+
+                edges.push(DependencyEdge {
+                    name: dep_name,
+                    req,
+                    kind,
+                    version: resolved,
+                });
+            }
+
+            nodes.push(DependencyNode {
+                name: node_name,
+                version: node_version,
+                depth,
+                dependencies: Some(edges),
+            });
+        }
+        level = next_level;
+        depth += 1;
+    }
+
+    Ok(DependencyGraph {
+        name,
+        version,
+        depth: max_depth,
+        truncated,
+        nodes,
+    })
+}
+
+pub(crate) async fn dependencies_json_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Query(params): Query<DependenciesParams>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let version = match match_version_axum(&pool, &name, Some(&req_version))
+        .await?
+        .assume_exact()?
+    {
+        MatchSemver::Exact((version, _)) | MatchSemver::Latest((version, _)) => version,
+        MatchSemver::Semver((version, _)) => {
+            let query = params
+                .depth
+                .map(|depth| format!("?depth={}", depth))
+                .unwrap_or_default();
+            return Ok(super::axum_cached_redirect(
+                &format!("/crate/{}/{}/dependencies.json{}", name, version, query),
+                CachePolicy::ForeverInCdn,
+            )?
+            .into_response());
+        }
+    };
+
+    let depth = params
+        .depth
+        .unwrap_or(DEPENDENCIES_DEFAULT_DEPTH)
+        .min(DEPENDENCIES_MAX_DEPTH);
+
+    let graph = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        dependency_graph(&mut *conn, name, version, depth)
+    })
+    .await?;
+
+    Ok((
+        Extension(CachePolicy::NoStoreMustRevalidate),
+        [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+        Json(graph),
+    )
+        .into_response())
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub(crate) struct VersionsParams {
+    /// Include pre-releases, defaults to `true`
+    prereleases: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct VersionJson {
+    version: String,
+    prerelease: bool,
+    yanked: bool,
+    has_docs: bool,
+}
+
+/// All versions of a crate, newest first by semver precedence, so `0.10.0` comes before `0.9.0`
+/// and pre-releases right after the release they precede.
+pub(crate) async fn versions_json_handler(
+    Path(name): Path<String>,
+    Query(params): Query<VersionsParams>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let releases = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let crate_id: i32 = conn
+            .query_opt("SELECT id FROM crates WHERE name = $1", &[&name])?
+            .ok_or(AxumNope::CrateNotFound)?
+            .get(0);
+        releases_for_crate(&mut *conn, crate_id)
+    })
+    .await?;
+
+    let include_prereleases = params.prereleases.unwrap_or(true);
+    let versions: Vec<_> = releases
+        .into_iter()
+        .filter(|release| include_prereleases || release.version.pre.is_empty())
+        .map(|release| VersionJson {
+            prerelease: !release.version.pre.is_empty(),
+            version: release.version.to_string(),
+            yanked: release.yanked,
+            has_docs: release.rustdoc_status,
+        })
+        .collect();
+
+    Ok((
+        Extension(CachePolicy::NoStoreMustRevalidate),
+        Json(versions),
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct CratesIoUrl {
+    name: String,
+    version: String,
+    url: String,
+}
+
+/// Redirects to the page of a release on crates.io, or returns its URL as JSON.
+///
+/// `latest` and semver requirements are resolved to the matching release first, so the link
+/// always points at a version that exists on crates.io.
+pub(crate) async fn crates_io_redirect_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+    RequestedFormat(format): RequestedFormat,
+) -> AxumResult<AxumResponse> {
+    let matched = match_version_axum(&pool, &name, Some(&req_version)).await?;
+    let name = matched.corrected_name.unwrap_or(name);
+    let (version, _) = matched.version.into_parts();
+    let url = format!("https://crates.io/crates/{}/{}", name, version);
+
+    // only cached shortly, `latest` and semver requirements change with new releases
+    let res = match format {
+        OutputFormat::Json => (
+            Extension(CachePolicy::ShortInCdn),
+            [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+            Json(CratesIoUrl { name, version, url }),
+        )
+            .into_response(),
+        OutputFormat::Html => (
+            StatusCode::FOUND,
+            [(LOCATION, url)],
+            Extension(CachePolicy::ShortInCdn),
+        )
+            .into_response(),
+    };
+    Ok(res)
+}
+
+/// Maximum number of entries in a single request to `/-/details-batch`
+pub(crate) const MAX_BATCH_DETAILS: usize = 100;
+
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct BatchDetailsEntry {
+    name: String,
+    /// Version or semver requirement, defaults to `latest`
+    version: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct CrateSummary {
+    name: String,
+    version: String,
+    description: Option<String>,
+    release_time: DateTime<Utc>,
+    build_status: bool,
+    rustdoc_status: bool,
+    yanked: bool,
+    target_name: String,
+    license: Option<String>,
+    repository_url: Option<String>,
+    documentation_url: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct BatchDetails {
+    name: String,
+    requested_version: String,
+    /// `None` if the release couldn't be found, see `error`
+    details: Option<CrateSummary>,
+    error: Option<String>,
+}
+
+/// Resolves the requested version of every entry and fetches the summaries of the matched
+/// releases in a single query.
+fn batch_details(
+    conn: &mut postgres::Client,
+    entries: Vec<BatchDetailsEntry>,
+) -> Result<Vec<BatchDetails>, anyhow::Error> {
+    let mut batch = Vec::with_capacity(entries.len());
+    let mut matched_releases = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let requested_version = entry.version.unwrap_or_else(|| "latest".into());
+        let (matched, error) = match match_version(conn, &entry.name, Some(&requested_version)) {
+            Ok(matched) => {
+                let name = matched.corrected_name.unwrap_or_else(|| entry.name.clone());
+                (Some((name, matched.version.into_parts().0)), None)
+            }
+            // database errors fail the whole request
+            Err(Nope::InternalServerError) => return Err(Nope::InternalServerError.into()),
+            Err(err) => (None, Some(err.to_string())),
+        };
+        batch.push(BatchDetails {
+            name: entry.name,
+            requested_version,
+            details: None,
+            error,
+        });
+        matched_releases.push(matched);
+    }
+
+    let (names, versions): (Vec<_>, Vec<_>) = matched_releases.iter().flatten().cloned().unzip();
+    let summaries: HashMap<_, _> = conn
+        .query(
+            "SELECT
+                crates.name,
+                releases.version,
+                releases.description,
+                releases.release_time,
+                releases.build_status,
+                releases.rustdoc_status,
+                releases.yanked,
+                releases.target_name,
+                releases.license,
+                releases.repository_url,
+                releases.documentation_url
+            FROM releases
+            INNER JOIN crates ON releases.crate_id = crates.id
+            WHERE (crates.name, releases.version) IN (
+                SELECT * FROM UNNEST($1::TEXT[], $2::TEXT[])
+            )",
+            &[&names, &versions],
+        )?
+        .into_iter()
+        .map(|row| {
+            let summary = CrateSummary {
+                name: row.get("name"),
+                version: row.get("version"),
+                description: row.get("description"),
+                release_time: row.get("release_time"),
+                build_status: row.get("build_status"),
+                rustdoc_status: row.get("rustdoc_status"),
+                yanked: row.get("yanked"),
+                target_name: row.get("target_name"),
+                license: row.get("license"),
+                repository_url: row.get("repository_url"),
+                documentation_url: row.get("documentation_url"),
+            };
+            ((summary.name.clone(), summary.version.clone()), summary)
+        })
+        .collect();
+
+    for (details, matched) in batch.iter_mut().zip(matched_releases) {
+        if let Some(release) = matched {
+            // the same release can be requested more than once
+            details.details = summaries.get(&release).cloned();
+            if details.details.is_none() {
+                details.error = Some(Nope::VersionNotFound.to_string());
+            }
+        }
+    }
+    Ok(batch)
+}
+
+/// The details of several releases in a single request, e.g. for dashboards about many crates.
+/// Every entry resolves `latest` and semver requirements on its own, entries that can't be found
+/// are reported with an `error` instead of failing the whole request.
+pub(crate) async fn details_batch_handler(
+    Extension(pool): Extension<Pool>,
+    Json(entries): Json<Vec<BatchDetailsEntry>>,
+) -> JsonAxumResult<impl IntoResponse> {
+    if entries.is_empty() {
+        return Err(
+            AxumNope::BadRequest("the batch must contain at least one crate".into()).into(),
+        );
+    }
+    if entries.len() > MAX_BATCH_DETAILS {
+        return Err(AxumNope::BadRequest(format!(
+            "the batch can contain at most {} crates",
+            MAX_BATCH_DETAILS
+        ))
+        .into());
+    }
+
+    let details = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        batch_details(&mut conn, entries)
+    })
+    .await?;
+
+    Ok((Extension(CachePolicy::NoCaching), Json(details)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docbuilder::{DocCoverage, DocSizes};
+    use crate::index::api::CrateOwner;
+    use crate::test::{
+        assert_cache_control, assert_redirect, assert_redirect_cached,
+        assert_redirect_cached_unchecked, assert_success, wrapper, TestDatabase,
+    };
+    use anyhow::{Context, Error};
+    use kuchiki::traits::TendrilSink;
+    use std::collections::HashMap;
+
+    fn assert_last_successful_build_equals(
+        db: &TestDatabase,
+        package: &str,
+        version: &str,
+        expected_last_successful_build: Option<&str>,
+    ) -> Result<(), Error> {
+        let details = CrateDetails::new(&mut *db.conn(), package, version, version, None)
+            .with_context(|| anyhow::anyhow!("could not fetch crate details"))?
+            .unwrap();
+
+        assert_eq!(
+            details.last_successful_build,
+            expected_last_successful_build.map(|s| s.to_string()),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_last_successful_build_when_last_releases_failed_or_yanked() {
+        wrapper(|env| {
+            let db = env.db();
+
+            env.fake_release().name("foo").version("0.0.1").create()?;
+            env.fake_release().name("foo").version("0.0.2").create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.0.3")
+                .build_result_failed()
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.0.4")
+                .yanked(true)
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.0.5")
+                .build_result_failed()
+                .yanked(true)
+                .create()?;
+
+            assert_last_successful_build_equals(db, "foo", "0.0.1", None)?;
+            assert_last_successful_build_equals(db, "foo", "0.0.2", None)?;
+            assert_last_successful_build_equals(db, "foo", "0.0.3", Some("0.0.2"))?;
+            assert_last_successful_build_equals(db, "foo", "0.0.4", None)?;
+            assert_last_successful_build_equals(db, "foo", "0.0.5", Some("0.0.2"))?;
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_last_successful_build_when_all_releases_failed_or_yanked() {
+        wrapper(|env| {
+            let db = env.db();
+
+            env.fake_release()
+                .name("foo")
+                .version("0.0.1")
+                .build_result_failed()
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.0.2")
+                .build_result_failed()
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.0.3")
+                .yanked(true)
+                .create()?;
+
+            assert_last_successful_build_equals(db, "foo", "0.0.1", None)?;
+            assert_last_successful_build_equals(db, "foo", "0.0.2", None)?;
+            assert_last_successful_build_equals(db, "foo", "0.0.3", None)?;
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_last_successful_build_with_intermittent_releases_failed_or_yanked() {
+        wrapper(|env| {
+            let db = env.db();
+
+            env.fake_release().name("foo").version("0.0.1").create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.0.2")
+                .build_result_failed()
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.0.3")
+                .yanked(true)
+                .create()?;
+            env.fake_release().name("foo").version("0.0.4").create()?;
+
+            assert_last_successful_build_equals(db, "foo", "0.0.1", None)?;
+            assert_last_successful_build_equals(db, "foo", "0.0.2", Some("0.0.4"))?;
+            assert_last_successful_build_equals(db, "foo", "0.0.3", None)?;
+            assert_last_successful_build_equals(db, "foo", "0.0.4", None)?;
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_releases_should_be_sorted() {
+        wrapper(|env| {
+            let db = env.db();
+
+            // Add new releases of 'foo' out-of-order since CrateDetails should sort them descending
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            env.fake_release().name("foo").version("0.1.1").create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.3.0")
+                .build_result_failed()
+                .create()?;
+            env.fake_release().name("foo").version("1.0.0").create()?;
+            env.fake_release().name("foo").version("0.12.0").create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.2.0")
+                .yanked(true)
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.2.0-alpha")
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.0.1")
+                .build_result_failed()
+                .binary(true)
+                .create()?;
+
+            let details = CrateDetails::new(&mut *db.conn(), "foo", "0.2.0", "0.2.0", None)
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                details.releases,
+                vec![
+                    Release {
+                        version: semver::Version::parse("1.0.0")?,
+                        build_status: true,
+                        yanked: false,
+                        is_library: true,
+                        rustdoc_status: true,
+                        id: details.releases[0].id,
+                        target_name: "foo".to_owned(),
+                    },
+                    Release {
+                        version: semver::Version::parse("0.12.0")?,
+                        build_status: true,
                         yanked: false,
                         is_library: true,
                         rustdoc_status: true,
@@ -605,472 +1536,1284 @@ mod tests {
     }
 
     #[test]
-    fn test_canonical_url() {
+    fn versions_json_is_sorted_by_semver() {
+        wrapper(|env| {
+            for version in ["0.9.0", "0.10.0-alpha.1", "0.10.0", "0.2.0", "1.0.0-rc.1"] {
+                env.fake_release().name("foo").version(version).create()?;
+            }
+            env.fake_release()
+                .name("foo")
+                .version("0.11.0")
+                .yanked(true)
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .build_result_failed()
+                .create()?;
+            let web = env.frontend();
+
+            let response = web.get("/crate/foo/versions.json").send()?;
+            assert!(response.status().is_success());
+            assert!(response
+                .headers()
+                .get("access-control-allow-origin")
+                .is_none());
+            let versions: Value = response.json()?;
+            let fields = |field: &str| -> Vec<Value> {
+                versions
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|version| version[field].clone())
+                    .collect()
+            };
+            assert_eq!(
+                fields("version"),
+                [
+                    "1.0.0-rc.1",
+                    "0.11.0",
+                    "0.10.0",
+                    "0.10.0-alpha.1",
+                    "0.9.0",
+                    "0.2.0",
+                    "0.1.0"
+                ]
+            );
+            assert_eq!(
+                fields("prerelease"),
+                [true, false, false, true, false, false, false]
+            );
+            assert_eq!(
+                fields("yanked"),
+                [false, true, false, false, false, false, false]
+            );
+            assert_eq!(
+                fields("has_docs"),
+                [true, true, true, true, true, true, false]
+            );
+
+            let versions: Value = web
+                .get("/crate/foo/versions.json?prereleases=false")
+                .send()?
+                .json()?;
+            assert_eq!(
+                versions
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|version| version["version"].as_str().unwrap())
+                    .collect::<Vec<_>>(),
+                ["0.11.0", "0.10.0", "0.9.0", "0.2.0", "0.1.0"]
+            );
+
+            assert_eq!(web.get("/crate/bar/versions.json").send()?.status(), 404);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn crates_io_redirect() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo_bar")
+                .version("0.1.0")
+                .create()?;
+            env.fake_release()
+                .name("foo_bar")
+                .version("0.2.0")
+                .create()?;
+            let web = env.frontend();
+
+            for (version, expected) in [("0.1.0", "0.1.0"), ("latest", "0.2.0"), ("^0.1", "0.1.0")]
+            {
+                assert_redirect_cached_unchecked(
+                    &format!("/crate/foo_bar/{}/crates-io-redirect", version),
+                    &format!("https://crates.io/crates/foo_bar/{}", expected),
+                    CachePolicy::ShortInCdn,
+                    web,
+                    &env.config(),
+                )?;
+            }
+            assert_redirect_cached_unchecked(
+                "/crate/foo-bar/latest/crates-io-redirect",
+                "https://crates.io/crates/foo_bar/0.2.0",
+                CachePolicy::ShortInCdn,
+                web,
+                &env.config(),
+            )?;
+
+            let response = web
+                .get("/crate/foo_bar/latest/crates-io-redirect?format=json")
+                .send()?;
+            assert_cache_control(&response, CachePolicy::ShortInCdn, &env.config());
+            let value: Value = response.json()?;
+            assert_eq!(
+                value,
+                serde_json::json!({
+                    "name": "foo_bar",
+                    "version": "0.2.0",
+                    "url": "https://crates.io/crates/foo_bar/0.2.0",
+                })
+            );
+
+            for path in [
+                "/crate/bar/latest/crates-io-redirect",
+                "/crate/foo_bar/0.3.0/crates-io-redirect",
+            ] {
+                assert_eq!(web.get_no_redirect(path).send()?.status(), 404);
+            }
+            let response = web
+                .get("/crate/bar/latest/crates-io-redirect?format=json")
+                .send()?;
+            assert_eq!(response.status(), 404);
+            let value: Value = response.json()?;
+            assert_eq!(value["error"]["code"], "crate_not_found");
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_canonical_url() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.0.1").create()?;
+            env.fake_release().name("foo").version("0.0.2").create()?;
+
+            let response = env.frontend().get("/crate/foo/0.0.1").send()?;
+            assert_cache_control(
+                &response,
+                CachePolicy::ForeverInCdnAndStaleInBrowser,
+                &env.config(),
+            );
+
+            assert!(response
+                .text()?
+                .contains("rel=\"canonical\" href=\"https://docs.rs/crate/foo/latest"));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_latest_version() {
+        wrapper(|env| {
+            let db = env.db();
+
+            env.fake_release().name("foo").version("0.0.1").create()?;
+            env.fake_release().name("foo").version("0.0.3").create()?;
+            env.fake_release().name("foo").version("0.0.2").create()?;
+
+            for version in &["0.0.1", "0.0.2", "0.0.3"] {
+                let details = CrateDetails::new(&mut *db.conn(), "foo", version, version, None)
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(
+                    details.latest_release().version,
+                    semver::Version::parse("0.0.3")?
+                );
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_latest_version_ignores_prerelease() {
+        wrapper(|env| {
+            let db = env.db();
+
+            env.fake_release().name("foo").version("0.0.1").create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.0.3-pre.1")
+                .create()?;
+            env.fake_release().name("foo").version("0.0.2").create()?;
+
+            for version in &["0.0.1", "0.0.2", "0.0.3-pre.1"] {
+                let details = CrateDetails::new(&mut *db.conn(), "foo", version, version, None)
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(
+                    details.latest_release().version,
+                    semver::Version::parse("0.0.2")?
+                );
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_latest_version_ignores_yanked() {
+        wrapper(|env| {
+            let db = env.db();
+
+            env.fake_release().name("foo").version("0.0.1").create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.0.3")
+                .yanked(true)
+                .create()?;
+            env.fake_release().name("foo").version("0.0.2").create()?;
+
+            for version in &["0.0.1", "0.0.2", "0.0.3"] {
+                let details = CrateDetails::new(&mut *db.conn(), "foo", version, version, None)
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(
+                    details.latest_release().version,
+                    semver::Version::parse("0.0.2")?
+                );
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_latest_version_only_yanked() {
+        wrapper(|env| {
+            let db = env.db();
+
+            env.fake_release()
+                .name("foo")
+                .version("0.0.1")
+                .yanked(true)
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.0.3")
+                .yanked(true)
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.0.2")
+                .yanked(true)
+                .create()?;
+
+            for version in &["0.0.1", "0.0.2", "0.0.3"] {
+                let details = CrateDetails::new(&mut *db.conn(), "foo", version, version, None)
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(
+                    details.latest_release().version,
+                    semver::Version::parse("0.0.3")?
+                );
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn releases_dropdowns_is_correct() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("binary")
+                .version("0.1.0")
+                .binary(true)
+                .create()?;
+
+            let page = kuchiki::parse_html()
+                .one(env.frontend().get("/crate/binary/0.1.0").send()?.text()?);
+            let warning = page.select_first("a.pure-menu-link.warn").unwrap();
+
+            assert_eq!(
+                warning
+                    .as_node()
+                    .as_element()
+                    .unwrap()
+                    .attributes
+                    .borrow()
+                    .get("title")
+                    .unwrap(),
+                "binary-0.1.0 is not a library"
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_updating_owners() {
+        wrapper(|env| {
+            let db = env.db();
+
+            env.fake_release()
+                .name("foo")
+                .version("0.0.1")
+                .add_owner(CrateOwner {
+                    login: "foobar".into(),
+                    avatar: "https://example.org/foobar".into(),
+                    name: "Foo Bar".into(),
+                    email: "foobar@example.org".into(),
+                })
+                .create()?;
+
+            let details = CrateDetails::new(&mut *db.conn(), "foo", "0.0.1", "0.0.1", None)
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                details.owners,
+                vec![("foobar".into(), "https://example.org/foobar".into())]
+            );
+
+            // Adding a new owner, and changing details on an existing owner
+            env.fake_release()
+                .name("foo")
+                .version("0.0.2")
+                .add_owner(CrateOwner {
+                    login: "foobar".into(),
+                    avatar: "https://example.org/foobarv2".into(),
+                    name: "Foo Bar".into(),
+                    email: "foobar@example.org".into(),
+                })
+                .add_owner(CrateOwner {
+                    login: "barfoo".into(),
+                    avatar: "https://example.org/barfoo".into(),
+                    name: "Bar Foo".into(),
+                    email: "foobar@example.org".into(),
+                })
+                .create()?;
+
+            let details = CrateDetails::new(&mut *db.conn(), "foo", "0.0.1", "0.0.1", None)
+                .unwrap()
+                .unwrap();
+            let mut owners = details.owners;
+            owners.sort();
+            assert_eq!(
+                owners,
+                vec![
+                    ("barfoo".into(), "https://example.org/barfoo".into()),
+                    ("foobar".into(), "https://example.org/foobarv2".into())
+                ]
+            );
+
+            // Removing an existing owner
+            env.fake_release()
+                .name("foo")
+                .version("0.0.3")
+                .add_owner(CrateOwner {
+                    login: "barfoo".into(),
+                    avatar: "https://example.org/barfoo".into(),
+                    name: "Bar Foo".into(),
+                    email: "foobar@example.org".into(),
+                })
+                .create()?;
+
+            let details = CrateDetails::new(&mut *db.conn(), "foo", "0.0.1", "0.0.1", None)
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                details.owners,
+                vec![("barfoo".into(), "https://example.org/barfoo".into())]
+            );
+
+            // Changing owner details on another of their crates applies the change to both
+            env.fake_release()
+                .name("bar")
+                .version("0.0.1")
+                .add_owner(CrateOwner {
+                    login: "barfoo".into(),
+                    avatar: "https://example.org/barfoov2".into(),
+                    name: "Bar Foo".into(),
+                    email: "foobar@example.org".into(),
+                })
+                .create()?;
+
+            let details = CrateDetails::new(&mut *db.conn(), "foo", "0.0.1", "0.0.1", None)
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                details.owners,
+                vec![("barfoo".into(), "https://example.org/barfoov2".into())]
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn feature_flags_report_empty() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("library")
+                .version("0.1.0")
+                .features(HashMap::new())
+                .create()?;
+
+            let page = kuchiki::parse_html().one(
+                env.frontend()
+                    .get("/crate/library/0.1.0/features")
+                    .send()?
+                    .text()?,
+            );
+            assert!(page.select_first(r#"p[data-id="empty-features"]"#).is_ok());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn feature_private_feature_flags_are_hidden() {
+        wrapper(|env| {
+            let features = [("_private".into(), Vec::new())]
+                .iter()
+                .cloned()
+                .collect::<HashMap<String, Vec<String>>>();
+            env.fake_release()
+                .name("library")
+                .version("0.1.0")
+                .features(features)
+                .create()?;
+
+            let page = kuchiki::parse_html().one(
+                env.frontend()
+                    .get("/crate/library/0.1.0/features")
+                    .send()?
+                    .text()?,
+            );
+            assert!(page.select_first(r#"p[data-id="empty-features"]"#).is_ok());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn feature_flags_without_default() {
+        wrapper(|env| {
+            let features = [("feature1".into(), Vec::new())]
+                .iter()
+                .cloned()
+                .collect::<HashMap<String, Vec<String>>>();
+            env.fake_release()
+                .name("library")
+                .version("0.1.0")
+                .features(features)
+                .create()?;
+
+            let page = kuchiki::parse_html().one(
+                env.frontend()
+                    .get("/crate/library/0.1.0/features")
+                    .send()?
+                    .text()?,
+            );
+            assert!(page.select_first(r#"p[data-id="empty-features"]"#).is_err());
+            let def_len = page
+                .select_first(r#"b[data-id="default-feature-len"]"#)
+                .unwrap();
+            assert_eq!(def_len.text_contents(), "0");
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn feature_flags_with_nested_default() {
+        wrapper(|env| {
+            let features = [
+                ("default".into(), vec!["feature1".into()]),
+                ("feature1".into(), vec!["feature2".into()]),
+                ("feature2".into(), Vec::new()),
+            ]
+            .iter()
+            .cloned()
+            .collect::<HashMap<String, Vec<String>>>();
+            env.fake_release()
+                .name("library")
+                .version("0.1.0")
+                .features(features)
+                .create()?;
+
+            let page = kuchiki::parse_html().one(
+                env.frontend()
+                    .get("/crate/library/0.1.0/features")
+                    .send()?
+                    .text()?,
+            );
+            assert!(page.select_first(r#"p[data-id="empty-features"]"#).is_err());
+            let def_len = page
+                .select_first(r#"b[data-id="default-feature-len"]"#)
+                .unwrap();
+            assert_eq!(def_len.text_contents(), "3");
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn feature_flags_report_null() {
+        wrapper(|env| {
+            let id = env
+                .fake_release()
+                .name("library")
+                .version("0.1.0")
+                .create()?;
+
+            env.db()
+                .conn()
+                .query("UPDATE releases SET features = NULL WHERE id = $1", &[&id])?;
+
+            let page = kuchiki::parse_html().one(
+                env.frontend()
+                    .get("/crate/library/0.1.0/features")
+                    .send()?
+                    .text()?,
+            );
+            assert!(page.select_first(r#"p[data-id="null-features"]"#).is_ok());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn platform_links_are_direct_and_without_nofollow() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("dummy")
+                .version("0.4.0")
+                .rustdoc_file("dummy/index.html")
+                .rustdoc_file("x86_64-pc-windows-msvc/dummy/index.html")
+                .default_target("x86_64-unknown-linux-gnu")
+                .add_target("x86_64-pc-windows-msvc")
+                .create()?;
+
+            let response = env.frontend().get("/crate/dummy/0.4.0").send()?;
+            assert!(response.status().is_success());
+
+            let platform_links: Vec<(String, String)> = kuchiki::parse_html()
+                .one(response.text()?)
+                .select(r#"a[aria-label="Platform"] + ul li a"#)
+                .expect("invalid selector")
+                .map(|el| {
+                    let attributes = el.attributes.borrow();
+                    let url = attributes.get("href").expect("href").to_string();
+                    let rel = attributes.get("rel").unwrap_or("").to_string();
+                    (url, rel)
+                })
+                .collect();
+
+            assert_eq!(platform_links.len(), 2);
+
+            for (url, rel) in platform_links {
+                assert!(!url.contains("/target-redirect/"));
+                assert_eq!(rel, "");
+            }
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn latest_url() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("dummy")
+                .version("0.4.0")
+                .rustdoc_file("dummy/index.html")
+                .rustdoc_file("x86_64-pc-windows-msvc/dummy/index.html")
+                .default_target("x86_64-unknown-linux-gnu")
+                .add_target("x86_64-pc-windows-msvc")
+                .create()?;
+            let web = env.frontend();
+
+            let resp = env.frontend().get("/crate/dummy/latest").send()?;
+            assert!(resp.status().is_success());
+            assert_cache_control(&resp, CachePolicy::ForeverInCdn, &env.config());
+            assert!(resp.url().as_str().ends_with("/crate/dummy/latest"));
+            let body = String::from_utf8(resp.bytes().unwrap().to_vec()).unwrap();
+            assert!(body.contains("<a href=\"/crate/dummy/latest/features\""));
+            assert!(body.contains("<a href=\"/crate/dummy/latest/builds\""));
+            assert!(body.contains("<a href=\"/crate/dummy/latest/source/\""));
+            assert!(body.contains("<a href=\"/crate/dummy/latest\""));
+
+            assert_redirect("/crate/dummy/latest/", "/crate/dummy/latest", web)?;
+            assert_redirect_cached(
+                "/crate/dummy",
+                "/crate/dummy/latest",
+                CachePolicy::ForeverInCdn,
+                web,
+                &env.config(),
+            )?;
+
+            let resp_json = env
+                .frontend()
+                .get("/crate/aquarelle/latest/builds.json")
+                .send()?;
+            assert!(resp_json
+                .url()
+                .as_str()
+                .ends_with("/crate/aquarelle/latest/builds.json"));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn coverage_json() {
         wrapper(|env| {
-            env.fake_release().name("foo").version("0.0.1").create()?;
-            env.fake_release().name("foo").version("0.0.2").create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .doc_coverage(DocCoverage {
+                    total_items: 8,
+                    documented_items: 6,
+                    total_items_needing_examples: 2,
+                    items_with_examples: 1,
+                })
+                .create()?;
 
-            let response = env.frontend().get("/crate/foo/0.0.1").send()?;
-            assert_cache_control(
-                &response,
-                CachePolicy::ForeverInCdnAndStaleInBrowser,
-                &env.config(),
+            let response = env
+                .frontend()
+                .get("/crate/foo/0.1.0/coverage.json")
+                .send()?;
+            assert!(response.status().is_success());
+            assert_cache_control(&response, CachePolicy::NoStoreMustRevalidate, &env.config());
+            let value: serde_json::Value = response.json()?;
+            assert_eq!(
+                value,
+                serde_json::json!({
+                    "total_items": 8,
+                    "documented_items": 6,
+                    "total_items_needing_examples": 2,
+                    "items_with_examples": 1,
+                    "percentage": 75.0,
+                })
             );
 
-            assert!(response
-                .text()?
-                .contains("rel=\"canonical\" href=\"https://docs.rs/crate/foo/latest"));
+            assert_redirect(
+                "/crate/foo/0.1/coverage.json",
+                "/crate/foo/0.1.0/coverage.json",
+                env.frontend(),
+            )?;
 
             Ok(())
         })
     }
 
     #[test]
-    fn test_latest_version() {
+    fn coverage_not_available() {
         wrapper(|env| {
-            let db = env.db();
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let web = env.frontend();
 
-            env.fake_release().name("foo").version("0.0.1").create()?;
-            env.fake_release().name("foo").version("0.0.3").create()?;
-            env.fake_release().name("foo").version("0.0.2").create()?;
+            let value: serde_json::Value =
+                web.get("/crate/foo/0.1.0/coverage.json").send()?.json()?;
+            assert_eq!(value["percentage"], serde_json::Value::Null);
+            assert_eq!(value["total_items"], serde_json::Value::Null);
 
-            for version in &["0.0.1", "0.0.2", "0.0.3"] {
-                let details = CrateDetails::new(&mut *db.conn(), "foo", version, version, None)
-                    .unwrap()
-                    .unwrap();
-                assert_eq!(
-                    details.latest_release().version,
-                    semver::Version::parse("0.0.3")?
-                );
-            }
+            let page = kuchiki::parse_html().one(web.get("/crate/foo/0.1.0").send()?.text()?);
+            assert!(page
+                .select(".pure-menu-item .documented-info")
+                .unwrap()
+                .any(|e| e.text_contents().contains("not available")));
+
+            let response = web.get("/crate/foo/0.2.0/coverage.json").send()?;
+            assert_eq!(response.status(), 404);
 
             Ok(())
         })
     }
 
     #[test]
-    fn test_latest_version_ignores_prerelease() {
+    fn sizes_json() {
         wrapper(|env| {
-            let db = env.db();
-
-            env.fake_release().name("foo").version("0.0.1").create()?;
             env.fake_release()
                 .name("foo")
-                .version("0.0.3-pre.1")
+                .version("0.1.0")
+                .doc_sizes(DocSizes {
+                    documentation: 123_456,
+                    sources: 7_890,
+                    search_index: 4_096,
+                })
                 .create()?;
-            env.fake_release().name("foo").version("0.0.2").create()?;
-
-            for version in &["0.0.1", "0.0.2", "0.0.3-pre.1"] {
-                let details = CrateDetails::new(&mut *db.conn(), "foo", version, version, None)
-                    .unwrap()
-                    .unwrap();
-                assert_eq!(
-                    details.latest_release().version,
-                    semver::Version::parse("0.0.2")?
-                );
-            }
-
-            Ok(())
-        })
-    }
+            env.fake_release().name("bar").version("0.1.0").create()?;
+            let web = env.frontend();
 
-    #[test]
-    fn test_latest_version_ignores_yanked() {
-        wrapper(|env| {
-            let db = env.db();
+            let response = web.get("/crate/foo/0.1.0/sizes.json").send()?;
+            assert!(response.status().is_success());
+            assert_cache_control(&response, CachePolicy::NoStoreMustRevalidate, &env.config());
+            let value: serde_json::Value = response.json()?;
+            assert_eq!(
+                value,
+                serde_json::json!({
+                    "documentation": 123_456,
+                    "sources": 7_890,
+                    "search_index": 4_096,
+                })
+            );
 
-            env.fake_release().name("foo").version("0.0.1").create()?;
-            env.fake_release()
-                .name("foo")
-                .version("0.0.3")
-                .yanked(true)
-                .create()?;
-            env.fake_release().name("foo").version("0.0.2").create()?;
+            // builds from before the sizes were recorded
+            let value: serde_json::Value = web.get("/crate/bar/0.1.0/sizes.json").send()?.json()?;
+            assert_eq!(
+                value,
+                serde_json::json!({
+                    "documentation": null,
+                    "sources": null,
+                    "search_index": null,
+                })
+            );
 
-            for version in &["0.0.1", "0.0.2", "0.0.3"] {
-                let details = CrateDetails::new(&mut *db.conn(), "foo", version, version, None)
-                    .unwrap()
-                    .unwrap();
-                assert_eq!(
-                    details.latest_release().version,
-                    semver::Version::parse("0.0.2")?
-                );
-            }
+            assert_redirect(
+                "/crate/foo/0.1/sizes.json",
+                "/crate/foo/0.1.0/sizes.json",
+                env.frontend(),
+            )?;
+            assert_eq!(web.get("/crate/foo/0.2.0/sizes.json").send()?.status(), 404);
 
             Ok(())
         })
     }
 
     #[test]
-    fn test_latest_version_only_yanked() {
+    fn readme_page() {
         wrapper(|env| {
-            let db = env.db();
-
-            env.fake_release()
-                .name("foo")
-                .version("0.0.1")
-                .yanked(true)
-                .create()?;
-            env.fake_release()
-                .name("foo")
-                .version("0.0.3")
-                .yanked(true)
-                .create()?;
             env.fake_release()
                 .name("foo")
-                .version("0.0.2")
-                .yanked(true)
+                .version("0.1.0")
+                .readme("# Hello\n\nsome *text*\n\n<script>alert(1)</script>\n")
                 .create()?;
+            let web = env.frontend();
 
-            for version in &["0.0.1", "0.0.2", "0.0.3"] {
-                let details = CrateDetails::new(&mut *db.conn(), "foo", version, version, None)
-                    .unwrap()
-                    .unwrap();
-                assert_eq!(
-                    details.latest_release().version,
-                    semver::Version::parse("0.0.3")?
-                );
-            }
+            let response = web.get("/crate/foo/0.1.0/readme").send()?;
+            assert!(response.status().is_success());
+            assert_cache_control(&response, CachePolicy::ForeverInCdn, &env.config());
+            let text = response.text()?;
+            assert!(text.contains("<h1>Hello</h1>"));
+            assert!(text.contains("<em>text</em>"));
+            assert!(!text.contains("<script>alert(1)</script>"));
+
+            assert_redirect("/crate/foo/0.1/readme", "/crate/foo/0.1.0/readme", web)?;
 
             Ok(())
         })
     }
 
     #[test]
-    fn releases_dropdowns_is_correct() {
+    fn readme_page_without_readme() {
         wrapper(|env| {
-            env.fake_release()
-                .name("binary")
-                .version("0.1.0")
-                .binary(true)
-                .create()?;
-
-            let page = kuchiki::parse_html()
-                .one(env.frontend().get("/crate/binary/0.1.0").send()?.text()?);
-            let warning = page.select_first("a.pure-menu-link.warn").unwrap();
+            env.fake_release().name("foo").version("0.1.0").create()?;
 
             assert_eq!(
-                warning
-                    .as_node()
-                    .as_element()
-                    .unwrap()
-                    .attributes
-                    .borrow()
-                    .get("title")
-                    .unwrap(),
-                "binary-0.1.0 is not a library"
+                env.frontend()
+                    .get("/crate/foo/0.1.0/readme")
+                    .send()?
+                    .status(),
+                404
             );
-
             Ok(())
-        });
+        })
     }
 
     #[test]
-    fn test_updating_owners() {
+    fn dependencies_json() {
         wrapper(|env| {
-            let db = env.db();
-
             env.fake_release()
                 .name("foo")
-                .version("0.0.1")
-                .add_owner(CrateOwner {
-                    login: "foobar".into(),
-                    avatar: "https://example.org/foobar".into(),
-                    name: "Foo Bar".into(),
-                    email: "foobar@example.org".into(),
-                })
+                .version("0.1.0")
+                .dependencies(&[
+                    ("bar", "^1.0", None),
+                    ("baz", "^0.2", Some("build")),
+                    ("test-helper", "^1", Some("dev")),
+                    ("not-on-docsrs", "^1", None),
+                ])
                 .create()?;
-
-            let details = CrateDetails::new(&mut *db.conn(), "foo", "0.0.1", "0.0.1", None)
-                .unwrap()
-                .unwrap();
-            assert_eq!(
-                details.owners,
-                vec![("foobar".into(), "https://example.org/foobar".into())]
-            );
-
-            // Adding a new owner, and changing details on an existing owner
             env.fake_release()
-                .name("foo")
-                .version("0.0.2")
-                .add_owner(CrateOwner {
-                    login: "foobar".into(),
-                    avatar: "https://example.org/foobarv2".into(),
-                    name: "Foo Bar".into(),
-                    email: "foobar@example.org".into(),
-                })
-                .add_owner(CrateOwner {
-                    login: "barfoo".into(),
-                    avatar: "https://example.org/barfoo".into(),
-                    name: "Bar Foo".into(),
-                    email: "foobar@example.org".into(),
-                })
+                .name("bar")
+                .version("1.0.0")
+                .dependencies(&[("baz", "^0.2", None)])
                 .create()?;
-
-            let details = CrateDetails::new(&mut *db.conn(), "foo", "0.0.1", "0.0.1", None)
-                .unwrap()
-                .unwrap();
-            let mut owners = details.owners;
-            owners.sort();
-            assert_eq!(
-                owners,
-                vec![
-                    ("barfoo".into(), "https://example.org/barfoo".into()),
-                    ("foobar".into(), "https://example.org/foobarv2".into())
-                ]
-            );
-
-            // Removing an existing owner
             env.fake_release()
-                .name("foo")
-                .version("0.0.3")
-                .add_owner(CrateOwner {
-                    login: "barfoo".into(),
-                    avatar: "https://example.org/barfoo".into(),
-                    name: "Bar Foo".into(),
-                    email: "foobar@example.org".into(),
-                })
+                .name("bar")
+                .version("1.1.0")
+                .dependencies(&[("baz", "^0.2", None)])
                 .create()?;
-
-            let details = CrateDetails::new(&mut *db.conn(), "foo", "0.0.1", "0.0.1", None)
-                .unwrap()
-                .unwrap();
-            assert_eq!(
-                details.owners,
-                vec![("barfoo".into(), "https://example.org/barfoo".into())]
-            );
-
-            // Changing owner details on another of their crates applies the change to both
             env.fake_release()
                 .name("bar")
-                .version("0.0.1")
-                .add_owner(CrateOwner {
-                    login: "barfoo".into(),
-                    avatar: "https://example.org/barfoov2".into(),
-                    name: "Bar Foo".into(),
-                    email: "foobar@example.org".into(),
-                })
+                .version("1.2.0")
+                .yanked(true)
+                .create()?;
+            // cycle back to foo
+            env.fake_release()
+                .name("baz")
+                .version("0.2.3")
+                .dependencies(&[("foo", "^0.1", None)])
+                .create()?;
+            env.fake_release()
+                .name("test-helper")
+                .version("1.0.0")
                 .create()?;
 
-            let details = CrateDetails::new(&mut *db.conn(), "foo", "0.0.1", "0.0.1", None)
+            let web = env.frontend();
+            let resp = web
+                .get("/crate/foo/0.1.0/dependencies.json?depth=3")
+                .send()?;
+            assert!(resp.status().is_success());
+            assert_cache_control(&resp, CachePolicy::NoStoreMustRevalidate, &env.config());
+            let value: serde_json::Value = resp.json()?;
+
+            assert_eq!(value["depth"], 3);
+            assert_eq!(value["truncated"], false);
+            let nodes: Vec<_> = value["nodes"]
+                .as_array()
                 .unwrap()
-                .unwrap();
+                .iter()
+                .map(|node| {
+                    (
+                        node["name"].as_str().unwrap(),
+                        node["version"].as_str().unwrap(),
+                        node["depth"].as_u64().unwrap(),
+                    )
+                })
+                .collect();
+            // every release shows up once, the cycle is broken at `foo`
             assert_eq!(
-                details.owners,
-                vec![("barfoo".into(), "https://example.org/barfoov2".into())]
+                nodes,
+                [
+                    ("foo", "0.1.0", 0),
+                    ("bar", "1.1.0", 1),
+                    ("baz", "0.2.3", 1)
+                ]
+            );
+            assert_eq!(
+                value["nodes"][0]["dependencies"],
+                serde_json::json!([
+                    {"name": "bar", "req": "^1.0", "kind": "normal", "version": "1.1.0"},
+                    {"name": "baz", "req": "^0.2", "kind": "build", "version": "0.2.3"},
+                    {"name": "not-on-docsrs", "req": "^1", "kind": "normal", "version": null},
+                ])
+            );
+            assert_eq!(
+                value["nodes"][2]["dependencies"],
+                serde_json::json!([{"name": "foo", "req": "^0.1", "kind": "normal", "version": "0.1.0"}])
             );
 
+            // the depth is limited
+            let value: serde_json::Value = web
+                .get("/crate/foo/0.1.0/dependencies.json")
+                .send()?
+                .json()?;
+            assert_eq!(value["depth"], 1);
+            assert_eq!(value["nodes"][1]["dependencies"], serde_json::Value::Null);
+
+            let value: serde_json::Value = web
+                .get("/crate/foo/0.1.0/dependencies.json?depth=1000")
+                .send()?
+                .json()?;
+            assert_eq!(value["depth"], DEPENDENCIES_MAX_DEPTH);
+
             Ok(())
         });
     }
 
     #[test]
-    fn feature_flags_report_empty() {
+    fn crate_details_accept_json() {
         wrapper(|env| {
             env.fake_release()
-                .name("library")
+                .name("foo")
                 .version("0.1.0")
-                .features(HashMap::new())
+                .description("a fake crate")
                 .create()?;
 
-            let page = kuchiki::parse_html().one(
-                env.frontend()
-                    .get("/crate/library/0.1.0/features")
-                    .send()?
-                    .text()?,
-            );
-            assert!(page.select_first(r#"p[data-id="empty-features"]"#).is_ok());
+            let resp = env
+                .frontend()
+                .get("/crate/foo/0.1.0")
+                .header("accept", "application/json")
+                .send()?;
+            assert!(resp.status().is_success());
+            assert!(resp
+                .headers()
+                .get_all("vary")
+                .iter()
+                .any(|value| value == "Accept"));
+            let value: serde_json::Value = resp.json()?;
+            assert_eq!(value.pointer("/name"), Some(&"foo".into()));
+            assert_eq!(value.pointer("/version"), Some(&"0.1.0".into()));
+            assert_eq!(value.pointer("/description"), Some(&"a fake crate".into()));
+
             Ok(())
         });
     }
 
     #[test]
-    fn feature_private_feature_flags_are_hidden() {
+    fn nightly_features() {
         wrapper(|env| {
-            let features = [("_private".into(), Vec::new())]
-                .iter()
-                .cloned()
-                .collect::<HashMap<String, Vec<String>>>();
             env.fake_release()
-                .name("library")
+                .name("foo")
                 .version("0.1.0")
-                .features(features)
+                .nightly_features(true)
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.2.0")
+                .nightly_features(false)
                 .create()?;
+            // built before the nightly features were recorded
+            env.fake_release().name("foo").version("0.3.0").create()?;
+            let web = env.frontend();
+
+            for (version, json, html) in [
+                ("0.1.0", serde_json::Value::Bool(true), "used"),
+                ("0.2.0", serde_json::Value::Bool(false), "not used"),
+                ("0.3.0", serde_json::Value::Null, "unknown"),
+            ] {
+                let url = format!("/crate/foo/{}", version);
+                let value: serde_json::Value =
+                    web.get(&format!("{}?format=json", url)).send()?.json()?;
+                assert_eq!(value["nightly_features"], json, "{}", version);
+
+                let page = kuchiki::parse_html().one(web.get(&url).send()?.text()?);
+                assert_eq!(
+                    page.select_first("#nightly-features")
+                        .unwrap()
+                        .text_contents(),
+                    html,
+                    "{}",
+                    version
+                );
+            }
 
-            let page = kuchiki::parse_html().one(
-                env.frontend()
-                    .get("/crate/library/0.1.0/features")
-                    .send()?
-                    .text()?,
-            );
-            assert!(page.select_first(r#"p[data-id="empty-features"]"#).is_ok());
             Ok(())
         });
     }
 
     #[test]
-    fn feature_flags_without_default() {
+    fn msrv() {
         wrapper(|env| {
-            let features = [("feature1".into(), Vec::new())]
-                .iter()
-                .cloned()
-                .collect::<HashMap<String, Vec<String>>>();
             env.fake_release()
-                .name("library")
+                .name("foo")
                 .version("0.1.0")
-                .features(features)
+                .rust_version("1.60")
                 .create()?;
+            env.fake_release().name("bar").version("0.1.0").create()?;
+            let web = env.frontend();
 
-            let page = kuchiki::parse_html().one(
-                env.frontend()
-                    .get("/crate/library/0.1.0/features")
-                    .send()?
-                    .text()?,
+            for (name, rust_version, html) in [
+                ("foo", serde_json::json!("1.60"), "1.60"),
+                ("bar", serde_json::Value::Null, "unspecified"),
+            ] {
+                let response = web
+                    .get(&format!("/crate/{}/0.1.0/msrv.json", name))
+                    .send()?;
+                assert!(response.status().is_success());
+                assert_cache_control(&response, CachePolicy::NoStoreMustRevalidate, &env.config());
+                let value: serde_json::Value = response.json()?;
+                assert_eq!(
+                    value,
+                    serde_json::json!({
+                        "name": name,
+                        "version": "0.1.0",
+                        "rust_version": rust_version,
+                    })
+                );
+
+                let url = format!("/crate/{}/0.1.0", name);
+                let value: serde_json::Value =
+                    web.get(&format!("{}?format=json", url)).send()?.json()?;
+                assert_eq!(value["rust_version"], rust_version, "{}", name);
+
+                let page = kuchiki::parse_html().one(web.get(&url).send()?.text()?);
+                assert_eq!(
+                    page.select_first("#rust-version").unwrap().text_contents(),
+                    html,
+                    "{}",
+                    name
+                );
+            }
+
+            assert_redirect(
+                "/crate/foo/0.1/msrv.json",
+                "/crate/foo/0.1.0/msrv.json",
+                web,
+            )?;
+            assert_eq!(
+                web.get("/crate/foo/0.2.0/msrv.json").send()?.status(),
+                StatusCode::NOT_FOUND
             );
-            assert!(page.select_first(r#"p[data-id="empty-features"]"#).is_err());
-            let def_len = page
-                .select_first(r#"b[data-id="default-feature-len"]"#)
-                .unwrap();
-            assert_eq!(def_len.text_contents(), "0");
+
             Ok(())
         });
     }
 
     #[test]
-    fn feature_flags_with_nested_default() {
+    fn dependency_snippet() {
         wrapper(|env| {
             let features = [
-                ("default".into(), vec!["feature1".into()]),
-                ("feature1".into(), vec!["feature2".into()]),
-                ("feature2".into(), Vec::new()),
+                ("default".into(), vec!["json".into()]),
+                ("json".into(), Vec::new()),
+                ("tls".into(), Vec::new()),
+                ("_private".into(), Vec::new()),
             ]
-            .iter()
-            .cloned()
+            .into_iter()
             .collect::<HashMap<String, Vec<String>>>();
             env.fake_release()
-                .name("library")
-                .version("0.1.0")
+                .name("foo-bar")
+                .version("1.2.3")
+                .features(features.clone())
+                .create()?;
+            env.fake_release()
+                .name("foo-bar")
+                .version("1.3.0")
                 .features(features)
                 .create()?;
+            let web = env.frontend();
 
-            let page = kuchiki::parse_html().one(
-                env.frontend()
-                    .get("/crate/library/0.1.0/features")
+            for (url, toml, cargo_add) in [
+                (
+                    "/crate/foo-bar/1.2.3",
+                    r#"foo-bar = "1.2.3""#,
+                    "cargo add foo-bar@1.2.3",
+                ),
+                (
+                    "/crate/foo-bar/latest",
+                    r#"foo-bar = "1.3.0""#,
+                    "cargo add foo-bar@1.3.0",
+                ),
+                (
+                    "/crate/foo-bar/1.2.3?enable=tls,json",
+                    r#"foo-bar = { version = "1.2.3", features = ["tls", "json"] }"#,
+                    "cargo add foo-bar@1.2.3 --features tls,json",
+                ),
+                // unknown and private features are ignored
+                (
+                    "/crate/foo-bar/1.2.3?enable=tls&enable=unknown&enable=_private",
+                    r#"foo-bar = { version = "1.2.3", features = ["tls"] }"#,
+                    "cargo add foo-bar@1.2.3 --features tls",
+                ),
+            ] {
+                let page = kuchiki::parse_html().one(web.get(url).send()?.text()?);
+                assert_eq!(
+                    page.select_first("#dependency-toml")
+                        .unwrap()
+                        .text_contents(),
+                    toml,
+                    "{}",
+                    url
+                );
+                assert_eq!(
+                    page.select_first("#dependency-cargo-add")
+                        .unwrap()
+                        .text_contents(),
+                    cargo_add,
+                    "{}",
+                    url
+                );
+
+                let separator = if url.contains('?') { '&' } else { '?' };
+                let value: serde_json::Value = web
+                    .get(&format!("{}{}format=json", url, separator))
                     .send()?
-                    .text()?,
+                    .json()?;
+                assert_eq!(value["dependency"]["toml"], toml, "{}", url);
+                assert_eq!(value["dependency"]["cargo_add"], cargo_add, "{}", url);
+            }
+
+            // the selection shows the public features, with the selected ones checked
+            let page = kuchiki::parse_html()
+                .one(web.get("/crate/foo-bar/1.2.3?enable=tls").send()?.text()?);
+            let checkboxes: Vec<_> = page
+                .select("#dependency-features input")
+                .unwrap()
+                .map(|input| {
+                    let attributes = input.attributes.borrow();
+                    (
+                        attributes.get("value").unwrap().to_owned(),
+                        attributes.contains("checked"),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                checkboxes,
+                vec![("json".to_owned(), false), ("tls".to_owned(), true)]
             );
-            assert!(page.select_first(r#"p[data-id="empty-features"]"#).is_err());
-            let def_len = page
-                .select_first(r#"b[data-id="default-feature-len"]"#)
-                .unwrap();
-            assert_eq!(def_len.text_contents(), "3");
+
             Ok(())
         });
     }
 
     #[test]
-    fn feature_flags_report_null() {
+    fn dependency_snippet_of_scoped_registry() {
+        let snippet = DependencySnippet::new("foo@internal", "0.1.0", vec!["tls".into()]);
+        assert_eq!(
+            snippet.toml,
+            r#"foo = { version = "0.1.0", features = ["tls"], registry = "internal" }"#
+        );
+        assert_eq!(
+            snippet.cargo_add,
+            "cargo add foo@0.1.0 --features tls --registry internal"
+        );
+    }
+
+    #[test]
+    fn server_timing_header() {
         wrapper(|env| {
-            let id = env
-                .fake_release()
-                .name("library")
+            env.override_config(|config| {
+                config.server_timing = true;
+            });
+            env.fake_release().name("foo").version("0.1.0").create()?;
+
+            let resp = env.frontend().get("/crate/foo/0.1.0").send()?;
+            assert!(resp.status().is_success());
+            let header = resp.headers()["server-timing"].to_str()?;
+            for step in ["match-version", "crate-details", "render"] {
+                assert!(header.contains(step), "{} missing in {}", step, header);
+            }
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn crate_details_redirect_to_docs() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.crate_details_redirect_to_docs = true;
+            });
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            env.fake_release()
+                .name("failed")
                 .version("0.1.0")
+                .build_result_failed()
                 .create()?;
+            let web = env.frontend();
 
-            env.db()
-                .conn()
-                .query("UPDATE releases SET features = NULL WHERE id = $1", &[&id])?;
+            assert_redirect_cached(
+                "/crate/foo",
+                "/foo/latest/foo/",
+                CachePolicy::ForeverInCdn,
+                web,
+                &env.config(),
+            )?;
+            assert_redirect_cached(
+                "/crate/failed",
+                "/crate/failed/latest",
+                CachePolicy::ForeverInCdn,
+                web,
+                &env.config(),
+            )?;
+            assert_success("/crate/foo/0.1.0", web)?;
+            assert_success("/crate/foo/latest", web)?;
+            Ok(())
+        });
+    }
 
-            let page = kuchiki::parse_html().one(
+    #[test]
+    fn crate_details_without_redirect_to_docs() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+
+            assert_redirect_cached(
+                "/crate/foo",
+                "/crate/foo/latest",
+                CachePolicy::ForeverInCdn,
+                env.frontend(),
+                &env.config(),
+            )?;
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn dependencies_json_redirects_semver() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+
+            assert_redirect_cached(
+                "/crate/foo/0.1/dependencies.json?depth=2",
+                "/crate/foo/0.1.0/dependencies.json?depth=2",
+                CachePolicy::ForeverInCdn,
+                env.frontend(),
+                &env.config(),
+            )?;
+            assert_eq!(
                 env.frontend()
-                    .get("/crate/library/0.1.0/features")
+                    .get("/crate/foo/0.2.0/dependencies.json")
                     .send()?
-                    .text()?,
+                    .status(),
+                404
             );
-            assert!(page.select_first(r#"p[data-id="null-features"]"#).is_ok());
             Ok(())
         });
     }
 
     #[test]
-    fn platform_links_are_direct_and_without_nofollow() {
+    fn details_batch() {
         wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
             env.fake_release()
-                .name("dummy")
-                .version("0.4.0")
-                .rustdoc_file("dummy/index.html")
-                .rustdoc_file("x86_64-pc-windows-msvc/dummy/index.html")
-                .default_target("x86_64-unknown-linux-gnu")
-                .add_target("x86_64-pc-windows-msvc")
+                .name("foo")
+                .version("0.2.0")
+                .description("newest foo")
                 .create()?;
+            env.fake_release()
+                .name("bar_baz")
+                .version("1.0.0")
+                .build_result_failed()
+                .create()?;
+            let web = env.frontend();
 
-            let response = env.frontend().get("/crate/dummy/0.4.0").send()?;
+            let response = web
+                .post("/-/details-batch")
+                .json(&serde_json::json!([
+                    { "name": "foo" },
+                    { "name": "foo", "version": "^0.1" },
+                    { "name": "bar-baz", "version": "1.0.0" },
+                    { "name": "foo", "version": "3.0.0" },
+                    { "name": "unknown" },
+                ]))
+                .send()?;
             assert!(response.status().is_success());
+            assert_cache_control(&response, CachePolicy::NoCaching, &env.config());
+            let value: Value = response.json()?;
+            let entries = value.as_array().unwrap();
+            assert_eq!(entries.len(), 5);
+
+            assert_eq!(entries[0]["requested_version"], "latest");
+            assert_eq!(entries[0]["details"]["version"], "0.2.0");
+            assert_eq!(entries[0]["details"]["description"], "newest foo");
+            assert_eq!(entries[0]["details"]["rustdoc_status"], true);
+            assert_eq!(entries[0]["error"], Value::Null);
+
+            assert_eq!(entries[1]["requested_version"], "^0.1");
+            assert_eq!(entries[1]["details"]["version"], "0.1.0");
+
+            assert_eq!(entries[2]["name"], "bar-baz");
+            assert_eq!(entries[2]["details"]["name"], "bar_baz");
+            assert_eq!(entries[2]["details"]["build_status"], false);
+
+            for entry in &entries[3..] {
+                assert_eq!(entry["details"], Value::Null);
+                assert!(entry["error"].is_string(), "{}", entry);
+            }
+            Ok(())
+        });
+    }
 
-            let platform_links: Vec<(String, String)> = kuchiki::parse_html()
-                .one(response.text()?)
-                .select(r#"a[aria-label="Platform"] + ul li a"#)
-                .expect("invalid selector")
-                .map(|el| {
-                    let attributes = el.attributes.borrow();
-                    let url = attributes.get("href").expect("href").to_string();
-                    let rel = attributes.get("rel").unwrap_or("").to_string();
-                    (url, rel)
-                })
+    #[test]
+    fn details_batch_is_bounded() {
+        wrapper(|env| {
+            let web = env.frontend();
+            let too_many: Vec<_> = (0..=MAX_BATCH_DETAILS)
+                .map(|i| serde_json::json!({ "name": format!("crate-{}", i) }))
                 .collect();
-
-            assert_eq!(platform_links.len(), 2);
-
-            for (url, rel) in platform_links {
-                assert!(!url.contains("/target-redirect/"));
-                assert_eq!(rel, "");
+            for body in [serde_json::json!([]), Value::Array(too_many)] {
+                let response = web.post("/-/details-batch").json(&body).send()?;
+                assert_eq!(response.status(), 400);
             }
-
             Ok(())
         });
     }
 
     #[test]
-    fn latest_url() {
+    fn details_batch_keeps_crate_with_that_name() {
         wrapper(|env| {
             env.fake_release()
-                .name("dummy")
-                .version("0.4.0")
-                .rustdoc_file("dummy/index.html")
-                .rustdoc_file("x86_64-pc-windows-msvc/dummy/index.html")
-                .default_target("x86_64-unknown-linux-gnu")
-                .add_target("x86_64-pc-windows-msvc")
+                .name("details-batch")
+                .version("0.1.0")
                 .create()?;
-            let web = env.frontend();
-
-            let resp = env.frontend().get("/crate/dummy/latest").send()?;
-            assert!(resp.status().is_success());
-            assert_cache_control(&resp, CachePolicy::ForeverInCdn, &env.config());
-            assert!(resp.url().as_str().ends_with("/crate/dummy/latest"));
-            let body = String::from_utf8(resp.bytes().unwrap().to_vec()).unwrap();
-            assert!(body.contains("<a href=\"/crate/dummy/latest/features\""));
-            assert!(body.contains("<a href=\"/crate/dummy/latest/builds\""));
-            assert!(body.contains("<a href=\"/crate/dummy/latest/source/\""));
-            assert!(body.contains("<a href=\"/crate/dummy/latest\""));
-
-            assert_redirect("/crate/dummy/latest/", "/crate/dummy/latest", web)?;
+            assert_success("/crate/details-batch/latest", env.frontend())?;
             assert_redirect_cached(
-                "/crate/dummy",
-                "/crate/dummy/latest",
+                "/crate/details-batch",
+                "/crate/details-batch/latest",
                 CachePolicy::ForeverInCdn,
-                web,
+                env.frontend(),
                 &env.config(),
             )?;
-
-            let resp_json = env
-                .frontend()
-                .get("/crate/aquarelle/latest/builds.json")
-                .send()?;
-            assert!(resp_json
-                .url()
-                .as_str()
-                .ends_with("/crate/aquarelle/latest/builds.json"));
-
             Ok(())
         });
     }
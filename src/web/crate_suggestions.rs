@@ -0,0 +1,344 @@
+//! "Did you mean" suggestions on the page of crates that don't exist, for typos like `tokoi`.
+//!
+//! The names of all crates are kept in a [BK-tree](https://en.wikipedia.org/wiki/BK-tree), so
+//! finding the close names only compares the requested name with a small part of them.
+
+use crate::{
+    db::Pool,
+    utils::spawn_blocking,
+    web::{error::error_code, page::web_page::DelayedTemplateRender},
+};
+use axum::{
+    http::Request as AxumHttpRequest, middleware::Next, response::Response as AxumResponse,
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// Most suggestions shown on a page
+const MAX_SUGGESTIONS: usize = 3;
+/// How long the loaded crate names are used before they are loaded again, so new crates are
+/// suggested too.
+const INDEX_MAX_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// Names differing only in case or `-` / `_` are the same crate.
+fn normalize(name: &str) -> String {
+    name.to_ascii_lowercase().replace('-', "_")
+}
+
+/// Most edits a name can be away from the requested one to be suggested, short names need
+/// closer matches so not every other short crate is suggested.
+fn max_distance(name: &str) -> usize {
+    if name.len() <= 3 {
+        1
+    } else {
+        2
+    }
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.as_bytes();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a) in a.bytes().enumerate() {
+        current[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[derive(Debug)]
+struct Node {
+    name: String,
+    normalized: String,
+    /// Children with their distance to this node
+    children: Vec<(usize, usize)>,
+}
+
+/// BK-tree of crate names, its nodes are stored in a `Vec` and refer to each other by index.
+#[derive(Debug, Default)]
+pub(crate) struct CrateNames {
+    nodes: Vec<Node>,
+}
+
+impl CrateNames {
+    pub(crate) fn new<I: IntoIterator<Item = String>>(names: I) -> Self {
+        let mut index = CrateNames::default();
+        for name in names {
+            index.insert(name);
+        }
+        index
+    }
+
+    fn insert(&mut self, name: String) {
+        let normalized = normalize(&name);
+        let new = self.nodes.len();
+
+        if !self.nodes.is_empty() {
+            let mut current = 0;
+            loop {
+                let distance = levenshtein(&self.nodes[current].normalized, &normalized);
+                if distance == 0 {
+                    return;
+                }
+                match self.nodes[current]
+                    .children
+                    .iter()
+                    .find(|(d, _)| *d == distance)
+                {
+                    Some((_, child)) => current = *child,
+                    None => {
+                        self.nodes[current].children.push((distance, new));
+                        break;
+                    }
+                }
+            }
+        }
+
+        self.nodes.push(Node {
+            name,
+            normalized,
+            children: Vec::new(),
+        });
+    }
+
+    /// The closest names to `name`, at most [`MAX_SUGGESTIONS`].
+    pub(crate) fn suggest(&self, name: &str) -> Vec<String> {
+        if self.nodes.is_empty() {
+            return Vec::new();
+        }
+        let normalized = normalize(name);
+        let max_distance = max_distance(&normalized);
+
+        let mut found = Vec::new();
+        let mut pending = vec![0];
+        while let Some(current) = pending.pop() {
+            let node = &self.nodes[current];
+            let distance = levenshtein(&node.normalized, &normalized);
+            // the name itself exists with another spelling, that's not a typo
+            if distance > 0 && distance <= max_distance {
+                found.push((distance, &node.name));
+            }
+            // the triangle inequality rules out all other children
+            pending.extend(
+                node.children
+                    .iter()
+                    .filter(|(d, _)| {
+                        distance.saturating_sub(*d) <= max_distance
+                            && d.saturating_sub(distance) <= max_distance
+                    })
+                    .map(|(_, child)| *child),
+            );
+        }
+
+        found.sort();
+        found
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, name)| name.clone())
+            .collect()
+    }
+}
+
+/// The [`CrateNames`] of all crates, loaded on the first request that needs them and again after
+/// [`INDEX_MAX_AGE`].
+#[derive(Debug, Default)]
+pub(crate) struct CrateNameIndex {
+    loaded: Mutex<Option<(Instant, Arc<CrateNames>)>>,
+    /// Held while the names are loaded, so a burst of 404s only runs the query once
+    loading: tokio::sync::Mutex<()>,
+}
+
+impl CrateNameIndex {
+    fn fresh(&self) -> Option<Arc<CrateNames>> {
+        match &*self.loaded.lock().unwrap() {
+            Some((loaded_at, names)) if loaded_at.elapsed() < INDEX_MAX_AGE => Some(names.clone()),
+            _ => None,
+        }
+    }
+
+    async fn get(&self, pool: Pool) -> anyhow::Result<Arc<CrateNames>> {
+        if let Some(names) = self.fresh() {
+            return Ok(names);
+        }
+
+        let _loading = self.loading.lock().await;
+        // another request might have loaded the names while this one was waiting
+        if let Some(names) = self.fresh() {
+            return Ok(names);
+        }
+
+        let names = spawn_blocking(move || {
+            let mut conn = pool.get()?;
+            let names = conn
+                .query("SELECT name FROM crates", &[])?
+                .into_iter()
+                .map(|row| row.get(0));
+            Ok(Arc::new(CrateNames::new(names)))
+        })
+        .await?;
+
+        *self.loaded.lock().unwrap() = Some((Instant::now(), names.clone()));
+        Ok(names)
+    }
+}
+
+/// The crate name in the path of a request, `/crate/:name/...` or `/:name/...`.
+fn requested_name(path: &str) -> Option<&str> {
+    let mut components = path.trim_start_matches('/').split('/');
+    let name = match components.next()? {
+        "crate" => components.next()?,
+        name => name,
+    };
+
+    let is_valid = !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if is_valid {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+/// Adds the names of existing crates close to the requested one to the page of
+/// [`AxumNope::CrateNotFound`](super::error::AxumNope::CrateNotFound) errors, as `suggestions`.
+pub(crate) async fn crate_suggestions_middleware<B>(
+    request: AxumHttpRequest<B>,
+    next: Next<B>,
+) -> AxumResponse {
+    let name = requested_name(request.uri().path()).map(str::to_owned);
+    let index = request.extensions().get::<Arc<CrateNameIndex>>().cloned();
+    let pool = request.extensions().get::<Pool>().cloned();
+
+    let mut response = next.run(request).await;
+    if error_code(&response) != Some("crate_not_found") {
+        return response;
+    }
+
+    let (name, index, pool) = match (name, index, pool) {
+        (Some(name), Some(index), Some(pool)) => (name, index, pool),
+        _ => return response,
+    };
+    // JSON errors don't render the page
+    if response
+        .extensions()
+        .get::<DelayedTemplateRender>()
+        .is_none()
+    {
+        return response;
+    }
+
+    let suggestions = match index.get(pool).await {
+        Ok(names) => names.suggest(&name),
+        Err(err) => {
+            warn!(?err, "could not load crate names for suggestions");
+            return response;
+        }
+    };
+    if let Some(render) = response.extensions_mut().get_mut::<DelayedTemplateRender>() {
+        render.context.insert("suggestions", &suggestions);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{levenshtein, requested_name, CrateNameIndex, CrateNames};
+    use crate::test::wrapper;
+    use kuchiki::traits::TendrilSink;
+    use std::sync::Arc;
+    use test_case::test_case;
+
+    #[test_case("tokio", "tokio", 0)]
+    #[test_case("tokio", "tokoi", 2)]
+    #[test_case("serde", "serde_json", 5)]
+    #[test_case("", "rand", 4)]
+    fn distance(a: &str, b: &str, expected: usize) {
+        assert_eq!(levenshtein(a, b), expected);
+        assert_eq!(levenshtein(b, a), expected);
+    }
+
+    #[test_case("/crate/tokoi/latest", Some("tokoi"))]
+    #[test_case("/tokoi/0.1.0/tokoi/", Some("tokoi"))]
+    #[test_case("/crate/", None)]
+    #[test_case("/crate/%20foo/latest", None)]
+    fn name_from_path(path: &str, expected: Option<&str>) {
+        assert_eq!(requested_name(path), expected);
+    }
+
+    #[test]
+    fn suggestions() {
+        let names = CrateNames::new(
+            [
+                "tokio",
+                "tokio-util",
+                "serde",
+                "serde_json",
+                "rand",
+                "rayon",
+                "Inflector",
+            ]
+            .map(String::from),
+        );
+
+        assert_eq!(names.suggest("tokoi"), ["tokio"]);
+        assert_eq!(names.suggest("serde-jsn"), ["serde_json"]);
+        assert_eq!(names.suggest("inflectr"), ["Inflector"]);
+        assert_eq!(names.suggest("rnd"), ["rand"]);
+        // existing crates with another spelling are not suggested
+        assert!(names.suggest("Tokio").is_empty());
+        assert!(names.suggest("qwertyuiop").is_empty());
+        assert!(CrateNames::default().suggest("tokio").is_empty());
+    }
+
+    #[test]
+    fn concurrent_requests_load_the_names_once() {
+        wrapper(|env| {
+            env.fake_release().name("tokio").version("1.0.0").create()?;
+            let index = CrateNameIndex::default();
+            let pool = env.db().pool();
+
+            let (first, second) = env
+                .runtime()
+                .block_on(async { tokio::join!(index.get(pool.clone()), index.get(pool)) });
+            assert!(Arc::ptr_eq(&first?, &second?));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn crate_not_found_page_suggests_similar_crates() {
+        wrapper(|env| {
+            env.fake_release().name("tokio").version("1.0.0").create()?;
+            env.fake_release().name("serde").version("1.0.0").create()?;
+            let web = env.frontend();
+
+            let response = web.get("/crate/tokoi/latest").send()?;
+            assert_eq!(response.status(), 404);
+            let page = kuchiki::parse_html().one(response.text()?);
+            let links: Vec<_> = page
+                .select(".crate-suggestions a")
+                .expect("invalid selector")
+                .map(|link| link.attributes.borrow().get("href").unwrap().to_owned())
+                .collect();
+            assert_eq!(links, ["/crate/tokio/latest"]);
+
+            let page =
+                kuchiki::parse_html().one(web.get("/crate/qwertyuiop/latest").send()?.text()?);
+            assert!(page.select_first(".crate-suggestions").is_err());
+
+            Ok(())
+        })
+    }
+}
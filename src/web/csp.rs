@@ -3,11 +3,18 @@ use axum::{
     http::Request as AxumHttpRequest, middleware::Next, response::Response as AxumResponse,
 };
 use iron::{AfterMiddleware, BeforeMiddleware, IronResult, Request, Response};
-use std::{fmt::Write, sync::Arc};
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 pub(super) struct Csp {
     nonce: String,
-    suppress: bool,
+    // the axum handlers only get a shared reference
+    suppress: AtomicBool,
 }
 
 impl Csp {
@@ -19,12 +26,12 @@ impl Csp {
 
         Self {
             nonce: base64::encode(random),
-            suppress: false,
+            suppress: AtomicBool::new(false),
         }
     }
 
-    pub(super) fn suppress(&mut self, suppress: bool) {
-        self.suppress = suppress;
+    pub(super) fn suppress(&self, suppress: bool) {
+        self.suppress.store(suppress, Ordering::Relaxed);
     }
 
     pub(super) fn nonce(&self) -> &str {
@@ -32,7 +39,7 @@ impl Csp {
     }
 
     fn render(&self, content_type: ContentType) -> Option<String> {
-        if self.suppress {
+        if self.suppress.load(Ordering::Relaxed) {
             return None;
         }
         let mut result = String::new();
@@ -199,7 +206,7 @@ mod tests {
 
     #[test]
     fn test_csp_suppressed() {
-        let mut csp = Csp::new();
+        let csp = Csp::new();
         csp.suppress(true);
 
         assert!(csp.render(ContentType::Other).is_none());
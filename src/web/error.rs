@@ -2,13 +2,34 @@ use std::borrow::Cow;
 
 use crate::{
     db::PoolError,
-    web::{page::WebPage, releases::Search, AxumErrorPage, ErrorPage},
+    web::{
+        output_format::{OutputFormat, RequestedFormat},
+        page::{
+            web_page::{DelayedTemplateRender, Translated},
+            WebPage,
+        },
+        releases::Search,
+        AxumErrorPage, ErrorPage,
+    },
 };
 use axum::{
-    http::StatusCode,
+    body::{boxed, Body},
+    http::{header::CONTENT_TYPE, HeaderValue, Request as AxumHttpRequest, StatusCode},
+    middleware::Next,
     response::{IntoResponse, Response as AxumResponse},
+    Json,
 };
 use iron::{status::Status, Handler, IronError, IronResult, Request, Response};
+use serde::Serialize;
+
+/// Shown for yanked versions whose documentation was deleted
+const GONE_MESSAGE: &str =
+    "this version of the crate was yanked and its documentation has been deleted";
+/// Shown for version requirements like `^1.x` that can't be parsed
+pub(crate) const INVALID_VERSION_RANGE_MESSAGE: &str =
+    "invalid version range, use semver requirements like `^1.2`, `~1.2.3` or `=1.2.3`";
+/// Shown for old releases that were built before the source code was stored
+const SOURCE_NOT_AVAILABLE_MESSAGE: &str = "the source code of this version is not available";
 
 #[derive(Debug, Copy, Clone, thiserror::Error)]
 pub enum Nope {
@@ -22,6 +43,10 @@ pub enum Nope {
     OwnerNotFound,
     #[error("Requested crate does not have specified version")]
     VersionNotFound,
+    #[error("Requested version was yanked and its documentation deleted")]
+    Gone,
+    #[error("Requested version range is invalid")]
+    InvalidVersionRange,
     #[error("Internal server error")]
     InternalServerError,
 }
@@ -36,6 +61,8 @@ impl From<Nope> for IronError {
             | Nope::CrateNotFound
             | Nope::OwnerNotFound
             | Nope::VersionNotFound => status::NotFound,
+            Nope::Gone => status::Gone,
+            Nope::InvalidVersionRange => status::BadRequest,
             Nope::InternalServerError => status::InternalServerError,
         };
 
@@ -93,6 +120,20 @@ impl Handler for Nope {
                 .into_response(req)
             }
 
+            Nope::Gone => ErrorPage {
+                title: "The requested version has been removed",
+                message: Some(GONE_MESSAGE.into()),
+                status: Status::Gone,
+            }
+            .into_response(req),
+
+            Nope::InvalidVersionRange => ErrorPage {
+                title: "The requested version range is invalid",
+                message: Some(INVALID_VERSION_RANGE_MESSAGE.into()),
+                status: Status::BadRequest,
+            }
+            .into_response(req),
+
             Nope::InternalServerError => {
                 // something went wrong, details should have been logged
                 ErrorPage {
@@ -125,25 +166,126 @@ pub enum AxumNope {
     OwnerNotFound,
     #[error("Requested crate does not have specified version")]
     VersionNotFound,
+    #[error("Requested version was yanked and its documentation deleted")]
+    Gone,
+    #[error("Source code of the requested version is not available")]
+    SourceNotAvailable,
     #[error("Search yielded no results")]
     NoResults,
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+    #[error("Missing or invalid authentication")]
+    Unauthorized,
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Request body too large")]
+    PayloadTooLarge,
+    #[error("Too many requests")]
+    TooManyRequests,
+    #[error("Request timed out")]
+    Timeout,
     #[error("Internal server error")]
     InternalServerError,
     #[error("internal error")]
     InternalError(anyhow::Error),
 }
 
+impl AxumNope {
+    /// Stable machine-readable identifier of the error, used in the JSON error responses.
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            AxumNope::ResourceNotFound => "resource_not_found",
+            AxumNope::BuildNotFound => "build_not_found",
+            AxumNope::CrateNotFound => "crate_not_found",
+            AxumNope::OwnerNotFound => "owner_not_found",
+            AxumNope::VersionNotFound => "version_not_found",
+            AxumNope::Gone => "gone",
+            AxumNope::SourceNotAvailable => "source_not_available",
+            AxumNope::NoResults => "no_results",
+            AxumNope::BadRequest(_) => "bad_request",
+            AxumNope::Unauthorized => "unauthorized",
+            AxumNope::Forbidden(_) => "forbidden",
+            AxumNope::Conflict(_) => "conflict",
+            AxumNope::PayloadTooLarge => "payload_too_large",
+            AxumNope::TooManyRequests => "too_many_requests",
+            AxumNope::Timeout => "timeout",
+            AxumNope::InternalServerError | AxumNope::InternalError(_) => "internal_server_error",
+        }
+    }
+
+    fn json_error(&self) -> (StatusCode, JsonError) {
+        let (status, message): (_, Cow<'static, str>) = match self {
+            AxumNope::ResourceNotFound => (StatusCode::NOT_FOUND, "no such resource".into()),
+            AxumNope::BuildNotFound => (StatusCode::NOT_FOUND, "no such build".into()),
+            AxumNope::CrateNotFound => (StatusCode::NOT_FOUND, "no such crate".into()),
+            AxumNope::OwnerNotFound => (StatusCode::NOT_FOUND, "no such owner".into()),
+            AxumNope::VersionNotFound => (
+                StatusCode::NOT_FOUND,
+                "no such version for this crate".into(),
+            ),
+            AxumNope::Gone => (StatusCode::GONE, GONE_MESSAGE.into()),
+            AxumNope::SourceNotAvailable => {
+                (StatusCode::NOT_FOUND, SOURCE_NOT_AVAILABLE_MESSAGE.into())
+            }
+            AxumNope::NoResults => (StatusCode::NOT_FOUND, "no results".into()),
+            AxumNope::BadRequest(message) => (StatusCode::BAD_REQUEST, message.clone().into()),
+            AxumNope::Unauthorized => (StatusCode::UNAUTHORIZED, "missing or invalid token".into()),
+            AxumNope::Forbidden(message) => (StatusCode::FORBIDDEN, message.clone().into()),
+            AxumNope::Conflict(message) => (StatusCode::CONFLICT, message.clone().into()),
+            AxumNope::PayloadTooLarge => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "the request body is too large".into(),
+            ),
+            AxumNope::TooManyRequests => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "too many requests, try again later".into(),
+            ),
+            AxumNope::Timeout => (
+                StatusCode::GATEWAY_TIMEOUT,
+                "the request took too long".into(),
+            ),
+            // details of internal errors are only reported, never shown in the JSON
+            AxumNope::InternalServerError | AxumNope::InternalError(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal server error".into(),
+            ),
+        };
+
+        (
+            status,
+            JsonError {
+                code: self.code(),
+                message,
+            },
+        )
+    }
+}
+
 impl IntoResponse for AxumNope {
     fn into_response(self) -> AxumResponse {
+        // kept around so `json_errors_middleware` can swap the page for JSON
+        let (_, json_error) = self.json_error();
+        let mut response = self.html_response();
+        response.extensions_mut().insert(json_error);
+        response
+    }
+}
+
+impl AxumNope {
+    fn html_response(self) -> AxumResponse {
         match self {
             AxumNope::ResourceNotFound => {
                 // user tried to navigate to a resource (doc page/file) that doesn't exist
-                AxumErrorPage {
-                    title: "Requested resource does not exist",
-                    message: "no such resource".into(),
-                    status: StatusCode::NOT_FOUND,
-                }
-                .into_response()
+                translated(
+                    AxumErrorPage {
+                        title: "Requested resource does not exist",
+                        message: "no such resource".into(),
+                        status: StatusCode::NOT_FOUND,
+                    },
+                    "error/not-found",
+                )
             }
 
             AxumNope::BuildNotFound => AxumErrorPage {
@@ -154,14 +296,16 @@ impl IntoResponse for AxumNope {
             .into_response(),
 
             AxumNope::CrateNotFound => {
-                // user tried to navigate to a crate that doesn't exist
-                // TODO: Display the attempted crate and a link to a search for said crate
-                AxumErrorPage {
-                    title: "The requested crate does not exist",
-                    message: "no such crate".into(),
-                    status: StatusCode::NOT_FOUND,
-                }
-                .into_response()
+                // user tried to navigate to a crate that doesn't exist, similar crates are
+                // suggested by `crate_suggestions_middleware`
+                translated(
+                    AxumErrorPage {
+                        title: "The requested crate does not exist",
+                        message: "no such crate".into(),
+                        status: StatusCode::NOT_FOUND,
+                    },
+                    "error/crate-not-found",
+                )
             }
 
             AxumNope::OwnerNotFound => AxumErrorPage {
@@ -181,6 +325,18 @@ impl IntoResponse for AxumNope {
                 }
                 .into_response()
             }
+            AxumNope::Gone => AxumErrorPage {
+                title: "The requested version has been removed",
+                message: GONE_MESSAGE.into(),
+                status: StatusCode::GONE,
+            }
+            .into_response(),
+            AxumNope::SourceNotAvailable => AxumErrorPage {
+                title: "The source code is not available",
+                message: SOURCE_NOT_AVAILABLE_MESSAGE.into(),
+                status: StatusCode::NOT_FOUND,
+            }
+            .into_response(),
             AxumNope::NoResults => {
                 // user did a search with no search terms
                 Search {
@@ -190,14 +346,58 @@ impl IntoResponse for AxumNope {
                 }
                 .into_response()
             }
+            AxumNope::BadRequest(message) => AxumErrorPage {
+                title: "Bad request",
+                message: message.into(),
+                status: StatusCode::BAD_REQUEST,
+            }
+            .into_response(),
+            AxumNope::Unauthorized => AxumErrorPage {
+                title: "Unauthorized",
+                message: "missing or invalid token".into(),
+                status: StatusCode::UNAUTHORIZED,
+            }
+            .into_response(),
+            AxumNope::Forbidden(message) => AxumErrorPage {
+                title: "Forbidden",
+                message: message.into(),
+                status: StatusCode::FORBIDDEN,
+            }
+            .into_response(),
+            AxumNope::Conflict(message) => AxumErrorPage {
+                title: "Conflict",
+                message: message.into(),
+                status: StatusCode::CONFLICT,
+            }
+            .into_response(),
+            AxumNope::PayloadTooLarge => AxumErrorPage {
+                title: "Payload too large",
+                message: "the request body is too large".into(),
+                status: StatusCode::PAYLOAD_TOO_LARGE,
+            }
+            .into_response(),
+            AxumNope::TooManyRequests => AxumErrorPage {
+                title: "Too many requests",
+                message: "too many requests, try again later".into(),
+                status: StatusCode::TOO_MANY_REQUESTS,
+            }
+            .into_response(),
+            AxumNope::Timeout => AxumErrorPage {
+                title: "Timeout",
+                message: "the request took too long".into(),
+                status: StatusCode::GATEWAY_TIMEOUT,
+            }
+            .into_response(),
             AxumNope::InternalServerError => {
                 // something went wrong, details should have been logged
-                AxumErrorPage {
-                    title: "Internal server error",
-                    message: "internal server error".into(),
-                    status: StatusCode::INTERNAL_SERVER_ERROR,
-                }
-                .into_response()
+                translated(
+                    AxumErrorPage {
+                        title: "Internal server error",
+                        message: "internal server error".into(),
+                        status: StatusCode::INTERNAL_SERVER_ERROR,
+                    },
+                    "error/internal-server-error",
+                )
             }
             AxumNope::InternalError(source) => {
                 let web_error = crate::web::AxumErrorPage {
@@ -208,12 +408,20 @@ impl IntoResponse for AxumNope {
 
                 crate::utils::report_error(&source);
 
-                web_error.into_response()
+                translated(web_error, "error/internal-server-error")
             }
         }
     }
 }
 
+/// Renders the common error pages in the language the client prefers, the translations are in
+/// `templates/error/`.
+fn translated(page: AxumErrorPage, template: &'static str) -> AxumResponse {
+    let mut response = page.into_response();
+    response.extensions_mut().insert(Translated(template));
+    response
+}
+
 impl From<anyhow::Error> for AxumNope {
     fn from(err: anyhow::Error) -> Self {
         match err.downcast::<AxumNope>() {
@@ -234,6 +442,10 @@ impl From<Nope> for AxumNope {
             Nope::CrateNotFound => AxumNope::CrateNotFound,
             Nope::OwnerNotFound => AxumNope::OwnerNotFound,
             Nope::VersionNotFound => AxumNope::VersionNotFound,
+            Nope::Gone => AxumNope::Gone,
+            Nope::InvalidVersionRange => {
+                AxumNope::BadRequest(INVALID_VERSION_RANGE_MESSAGE.to_string())
+            }
             Nope::InternalServerError => AxumNope::InternalServerError,
         }
     }
@@ -241,10 +453,235 @@ impl From<Nope> for AxumNope {
 
 pub(crate) type AxumResult<T> = Result<T, AxumNope>;
 
+/// Wrapper around [`AxumNope`] rendering the error as JSON instead of an HTML page,
+/// for use in the JSON API endpoints.
+#[derive(Debug)]
+pub(crate) struct JsonAxumNope(pub(crate) AxumNope);
+
+impl IntoResponse for JsonAxumNope {
+    fn into_response(self) -> AxumResponse {
+        if let AxumNope::InternalError(source) = &self.0 {
+            crate::utils::report_error(source);
+        }
+
+        let (status, error) = self.0.json_error();
+        (status, Json(JsonErrorEnvelope { error })).into_response()
+    }
+}
+
+/// Body of the JSON error responses, `{ "error": { "code": "...", "message": "..." } }`.
+#[derive(Debug, Serialize)]
+struct JsonErrorEnvelope {
+    error: JsonError,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct JsonError {
+    code: &'static str,
+    message: Cow<'static, str>,
+}
+
+/// The [`AxumNope::code`] of the error a response is the page of.
+pub(crate) fn error_code(response: &AxumResponse) -> Option<&'static str> {
+    response
+        .extensions()
+        .get::<JsonError>()
+        .map(|error| error.code)
+}
+
+/// Renders [`AxumNope`] errors of pages as JSON instead of HTML when the client asked for JSON
+/// with `?format=json` or its `Accept` header, see [`RequestedFormat`].
+pub(crate) async fn json_errors_middleware<B>(
+    request: AxumHttpRequest<B>,
+    next: Next<B>,
+) -> AxumResponse {
+    let (parts, body) = request.into_parts();
+    let wants_json = matches!(
+        RequestedFormat::from_parts(&parts),
+        Ok(RequestedFormat(OutputFormat::Json))
+    );
+    let response = next.run(AxumHttpRequest::from_parts(parts, body)).await;
+    if !wants_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let error = match parts.extensions.remove::<JsonError>() {
+        Some(error) => error,
+        None => return AxumResponse::from_parts(parts, body),
+    };
+
+    parts.extensions.remove::<DelayedTemplateRender>();
+    parts.extensions.remove::<Translated>();
+    parts
+        .headers
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    let body =
+        serde_json::to_vec(&JsonErrorEnvelope { error }).expect("could not serialize JSON error");
+    AxumResponse::from_parts(parts, boxed(Body::from(body)))
+}
+
+impl From<AxumNope> for JsonAxumNope {
+    fn from(err: AxumNope) -> Self {
+        JsonAxumNope(err)
+    }
+}
+
+impl From<Nope> for JsonAxumNope {
+    fn from(err: Nope) -> Self {
+        JsonAxumNope(err.into())
+    }
+}
+
+impl From<anyhow::Error> for JsonAxumNope {
+    fn from(err: anyhow::Error) -> Self {
+        JsonAxumNope(err.into())
+    }
+}
+
+pub(crate) type JsonAxumResult<T> = Result<T, JsonAxumNope>;
+
 #[cfg(test)]
 mod tests {
+    use super::AxumNope;
     use crate::test::wrapper;
+    use crate::web::{locale::TRANSLATED_LOCALES, page::TemplateData};
     use kuchiki::traits::TendrilSink;
+    use reqwest::StatusCode;
+    use serde_json::json;
+
+    #[test]
+    fn translated_error_pages_exist() {
+        wrapper(|env| {
+            let templates = TemplateData::new(&mut env.db().conn())?;
+            for name in [
+                "error/not-found",
+                "error/crate-not-found",
+                "error/internal-server-error",
+            ] {
+                for locale in TRANSLATED_LOCALES {
+                    let template = format!("{}.{}.html", name, locale);
+                    assert!(
+                        templates.templates.get_template(&template).is_ok(),
+                        "missing translation {}",
+                        template
+                    );
+                }
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn json_error_codes() {
+        assert_eq!(AxumNope::CrateNotFound.code(), "crate_not_found");
+        assert_eq!(AxumNope::BadRequest("invalid".into()).code(), "bad_request");
+        assert_eq!(
+            AxumNope::InternalError(anyhow::anyhow!("oops")).code(),
+            "internal_server_error"
+        );
+    }
+
+    #[test]
+    fn json_endpoint_not_found() {
+        wrapper(|env| {
+            let response = env
+                .frontend()
+                .get("/crate/crate-which-doesnt-exist/0.1.0/targets.json")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            assert_eq!(
+                response.json::<serde_json::Value>()?,
+                json!({"error": {
+                    "code": "version_not_found",
+                    "message": "no such version for this crate",
+                }})
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn json_errors_of_pages() {
+        wrapper(|env| {
+            let web = env.frontend();
+
+            for response in [
+                web.get("/crate/crate-which-doesnt-exist/latest?format=json")
+                    .send()?,
+                web.get("/crate/crate-which-doesnt-exist/latest")
+                    .header("Accept", "application/json")
+                    .send()?,
+            ] {
+                assert_eq!(response.status(), StatusCode::NOT_FOUND);
+                assert_eq!(response.headers()["content-type"], "application/json");
+                assert_eq!(
+                    response.json::<serde_json::Value>()?,
+                    json!({"error": {
+                        "code": "crate_not_found",
+                        "message": "no such crate",
+                    }})
+                );
+            }
+
+            let response = web
+                .get("/crate/foo/0.1.0/builds/42")
+                .header("Accept", "application/json")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            assert_eq!(
+                response.json::<serde_json::Value>()?["error"]["code"],
+                "build_not_found"
+            );
+
+            // browsers still get the HTML page
+            let response = web
+                .get("/crate/crate-which-doesnt-exist/latest")
+                .header("Accept", "text/html,*/*;q=0.8")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            assert!(response
+                .text()?
+                .contains("The requested crate does not exist"));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn translated_404_page() {
+        wrapper(|env| {
+            let web = env.frontend();
+
+            let response = web
+                .get("/crate/crate-which-doesnt-exist/latest")
+                .header("Accept-Language", "de-DE,de;q=0.9,en;q=0.8")
+                .send()?;
+            assert_eq!(response.status(), 404);
+            assert_eq!(response.headers()["vary"], "Accept-Language");
+            let page = kuchiki::parse_html().one(response.text()?);
+            assert_eq!(
+                page.select_first("#crate-title").unwrap().text_contents(),
+                "Die angeforderte Crate existiert nicht",
+            );
+            let html = page.select_first("html").unwrap();
+            assert_eq!(html.attributes.borrow().get("lang"), Some("de"));
+
+            let response = web
+                .get("/crate/crate-which-doesnt-exist/latest")
+                .header("Accept-Language", "en-US,de;q=0.5")
+                .send()?;
+            assert_eq!(response.headers()["vary"], "Accept-Language");
+            let page = kuchiki::parse_html().one(response.text()?);
+            assert_eq!(
+                page.select_first("#crate-title").unwrap().text_contents(),
+                "The requested crate does not exist",
+            );
+            let html = page.select_first("html").unwrap();
+            assert_eq!(html.attributes.borrow().get("lang"), Some("en"));
+
+            Ok(())
+        });
+    }
 
     #[test]
     fn check_404_page_content_crate() {
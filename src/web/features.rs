@@ -4,15 +4,23 @@ use crate::{
     db::Pool,
     impl_axum_webpage,
     utils::spawn_blocking,
-    web::{cache::CachePolicy, error::AxumResult, match_version_axum, MetaData},
+    web::{
+        base_url::BaseUrl,
+        cache::CachePolicy,
+        error::{AxumNope, AxumResult, JsonAxumResult},
+        match_version_axum,
+        output_format::{OutputFormat, RequestedFormat},
+        MetaData,
+    },
 };
 use anyhow::anyhow;
 use axum::{
-    extract::{Extension, Path},
+    extract::{Extension, Path, Query},
     response::IntoResponse,
+    Json,
 };
 use serde::Serialize;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 
 const DEFAULT_NAME: &str = "default";
 
@@ -21,6 +29,7 @@ struct FeaturesPage {
     metadata: MetaData,
     features: Option<Vec<Feature>>,
     default_len: usize,
+    enabled: Option<EnabledFeatures>,
     canonical_url: String,
     is_latest_url: bool,
 }
@@ -34,10 +43,50 @@ impl_axum_webpage! {
     },
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct FeaturesJson {
+    features: Option<Vec<Feature>>,
+    default_len: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    enabled: Option<EnabledFeatures>,
+}
+
+/// What a selection of features enables when the crate is used with them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+struct EnabledFeatures {
+    /// The features given with `?enable=`
+    selected: Vec<String>,
+    /// The selected features and all the features they enable transitively
+    features: BTreeSet<String>,
+    /// The optional dependencies enabled by these features
+    dependencies: BTreeSet<String>,
+}
+
+/// Collects the features given with `?enable=`, either comma separated or as repeated parameter
+/// like the checkboxes on the page submit them.
+pub(super) fn selected_features(query: &[(String, String)]) -> Vec<String> {
+    let mut selected: Vec<String> = Vec::new();
+    for (_, value) in query.iter().filter(|(key, _)| key == "enable") {
+        for name in value.split(',').map(str::trim) {
+            if !name.is_empty() && !selected.iter().any(|s| s == name) {
+                selected.push(name.to_owned());
+            }
+        }
+    }
+    selected
+}
+
+/// Shows the features of a release. With `?enable=foo,bar` it also shows everything enabling
+/// `foo` and `bar` enables, unknown feature names are a bad request.
 pub(crate) async fn build_features_handler(
     Path((name, req_version)): Path<(String, String)>,
     Extension(pool): Extension<Pool>,
+    BaseUrl(base_url): BaseUrl,
+    RequestedFormat(format): RequestedFormat,
+    Query(query): Query<Vec<(String, String)>>,
 ) -> AxumResult<impl IntoResponse> {
+    let selected = selected_features(&query);
+
     let (version, version_or_latest, is_latest_url) =
         match match_version_axum(&pool, &name, Some(&req_version))
             .await?
@@ -73,25 +122,239 @@ pub(crate) async fn build_features_handler(
     })
     .await?;
 
+    let raw: Option<Vec<Feature>> = row.get(0);
+
+    let mut enabled = None;
+    if !selected.is_empty() {
+        let graph = FeatureGraph::new(raw.clone().unwrap_or_default());
+        let unknown: Vec<&str> = selected
+            .iter()
+            .map(String::as_str)
+            .filter(|name| !graph.features.contains_key(*name))
+            .collect();
+        if !unknown.is_empty() {
+            return Err(AxumNope::BadRequest(format!(
+                "unknown features: {}",
+                unknown.join(", ")
+            )));
+        }
+
+        let mut enabled_features = graph.enabled(selected.iter().map(String::as_str));
+        enabled_features.selected = selected;
+        enabled = Some(enabled_features);
+    }
+
     let mut features = None;
     let mut default_len = 0;
 
-    if let Some(raw) = row.get(0) {
+    if let Some(raw) = raw {
         let result = order_features_and_count_default_len(raw);
         features = Some(result.0);
         default_len = result.1;
     }
 
+    if format == OutputFormat::Json {
+        return Ok((
+            Extension(if is_latest_url {
+                CachePolicy::ForeverInCdn
+            } else {
+                CachePolicy::ForeverInCdnAndStaleInBrowser
+            }),
+            Json(FeaturesJson {
+                features,
+                default_len,
+                enabled,
+            }),
+        )
+            .into_response());
+    }
+
     Ok(FeaturesPage {
         metadata,
         features,
         default_len,
+        enabled,
         is_latest_url,
-        canonical_url: format!("https://docs.rs/crate/{}/latest/features", &name),
+        canonical_url: format!("{}/crate/{}/latest/features", base_url, &name),
     }
     .into_response())
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct FeatureGraphJson {
+    features: Vec<FeatureJson>,
+}
+
+/// A feature of a release in `features.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct FeatureJson {
+    name: String,
+    /// The features and dependencies this feature enables, as written in `Cargo.toml`
+    enables: Vec<String>,
+    /// Whether the feature is enabled by `default`, directly or through other features
+    default: bool,
+    /// Whether this is the implicit feature of an optional dependency
+    implicit: bool,
+}
+
+/// The features of a release with the features they enable, for tooling that needs the
+/// complete feature graph. Unlike `features?format=json` this includes the private features.
+pub(crate) async fn features_json_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let (version, is_latest_url) = match match_version_axum(&pool, &name, Some(&req_version))
+        .await?
+        .assume_exact()?
+    {
+        MatchSemver::Exact((version, _)) => (version, false),
+        MatchSemver::Latest((version, _)) => (version, true),
+        MatchSemver::Semver((version, _)) => {
+            return Ok(super::axum_cached_redirect(
+                &format!("/crate/{}/{}/features.json", name, version),
+                CachePolicy::ForeverInCdn,
+            )?
+            .into_response());
+        }
+    };
+
+    let raw: Vec<Feature> = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let row = conn
+            .query_opt(
+                "SELECT releases.features FROM releases
+                 INNER JOIN crates ON crates.id = releases.crate_id
+                 WHERE crates.name = $1 AND releases.version = $2",
+                &[&name, &version],
+            )?
+            .ok_or(AxumNope::VersionNotFound)?;
+        // releases built before features were recorded
+        row.get::<_, Option<Vec<Feature>>>(0)
+            .ok_or_else(|| AxumNope::VersionNotFound.into())
+    })
+    .await?;
+
+    Ok((
+        Extension(if is_latest_url {
+            CachePolicy::ForeverInCdn
+        } else {
+            CachePolicy::ForeverInCdnAndStaleInBrowser
+        }),
+        Json(FeatureGraphJson {
+            features: features_json(raw),
+        }),
+    )
+        .into_response())
+}
+
+/// The declared features of a release merged with the implicit features of its optional
+/// dependencies.
+struct FeatureGraph {
+    /// The features with the features and dependencies they enable, sorted by name
+    features: BTreeMap<String, Vec<String>>,
+    implicit: HashSet<String>,
+    optional_dependencies: HashSet<String>,
+}
+
+impl FeatureGraph {
+    fn new(raw: Vec<Feature>) -> Self {
+        let mut features = BTreeMap::new();
+        let mut optional_dependencies = HashSet::new();
+        for feature in raw {
+            if feature.optional_dependency == Some(true) {
+                optional_dependencies.insert(feature.name);
+            } else {
+                features.insert(feature.name, feature.subfeatures);
+            }
+        }
+
+        // Cargo only adds the implicit feature when no feature uses the dependency with `dep:`
+        // syntax. Newer versions of cargo list it with the declared features already.
+        let mut implicit = HashSet::new();
+        for dependency in &optional_dependencies {
+            let dep_syntax = format!("dep:{}", dependency);
+            match features.get(dependency) {
+                Some(enables) => {
+                    if *enables == [dep_syntax] {
+                        implicit.insert(dependency.clone());
+                    }
+                }
+                None => {
+                    if !features
+                        .values()
+                        .any(|enables| enables.contains(&dep_syntax))
+                    {
+                        features.insert(dependency.clone(), vec![dep_syntax]);
+                        implicit.insert(dependency.clone());
+                    }
+                }
+            }
+        }
+
+        Self {
+            features,
+            implicit,
+            optional_dependencies,
+        }
+    }
+
+    /// The features transitively enabled by `selected`, including themselves, and the optional
+    /// dependencies they enable. Unknown features are ignored.
+    fn enabled<'a>(&self, selected: impl IntoIterator<Item = &'a str>) -> EnabledFeatures {
+        let mut enabled = EnabledFeatures::default();
+        let mut queue: VecDeque<&str> = selected.into_iter().collect();
+
+        while let Some(name) = queue.pop_front() {
+            let enables = match self.features.get(name) {
+                Some(enables) => enables,
+                None => continue,
+            };
+            if !enabled.features.insert(name.to_owned()) {
+                continue;
+            }
+            for entry in enables {
+                // `dep:foo` only enables the dependency, `foo?/bar` doesn't enable `foo` itself
+                let feature = match entry.split_once('/') {
+                    Some((dependency, _)) if dependency.ends_with('?') => continue,
+                    Some((dependency, _)) => {
+                        if self.optional_dependencies.contains(dependency) {
+                            enabled.dependencies.insert(dependency.to_owned());
+                        }
+                        dependency
+                    }
+                    None => match entry.strip_prefix("dep:") {
+                        Some(dependency) => {
+                            enabled.dependencies.insert(dependency.to_owned());
+                            continue;
+                        }
+                        None => entry.as_str(),
+                    },
+                };
+                queue.push_back(feature);
+            }
+        }
+        enabled
+    }
+}
+
+/// Merges the implicit features of optional dependencies into the declared features and marks
+/// the ones enabled by default, sorted by name.
+fn features_json(raw: Vec<Feature>) -> Vec<FeatureJson> {
+    let graph = FeatureGraph::new(raw);
+    let default = graph.enabled([DEFAULT_NAME]).features;
+    let implicit = graph.implicit;
+    graph
+        .features
+        .into_iter()
+        .map(|(name, enables)| FeatureJson {
+            default: default.contains(&name),
+            implicit: implicit.contains(&name),
+            name,
+            enables,
+        })
+        .collect()
+}
+
 fn order_features_and_count_default_len(raw: Vec<Feature>) -> (Vec<Feature>, usize) {
     let mut feature_map = get_feature_map(raw);
     let mut features = get_tree_structure_from_default(&mut feature_map);
@@ -134,6 +397,7 @@ mod tests {
     use super::*;
     use crate::db::types::Feature;
     use crate::test::{assert_cache_control, assert_redirect_cached, wrapper};
+    use kuchiki::traits::TendrilSink;
     use reqwest::StatusCode;
     use std::collections::HashMap;
 
@@ -253,6 +517,270 @@ mod tests {
         assert_eq!(features[1], non_default);
     }
 
+    #[test]
+    fn test_features_json_implicit_features() {
+        let raw = vec![
+            Feature::new(
+                DEFAULT_NAME.into(),
+                vec!["std".into(), "log".into(), "rand?/std".into()],
+                false,
+            ),
+            Feature::new("std".into(), vec!["_alloc".into()], false),
+            Feature::new("_alloc".into(), Vec::new(), false),
+            Feature::new("log".into(), vec!["dep:log".into()], false),
+            Feature::new("serde".into(), vec!["dep:serde_crate".into()], false),
+            Feature::new("log".into(), Vec::new(), true),
+            Feature::new("rand".into(), Vec::new(), true),
+            Feature::new("serde_crate".into(), Vec::new(), true),
+        ];
+
+        let feature = |name: &str, enables: &[&str], default: bool, implicit: bool| FeatureJson {
+            name: name.into(),
+            enables: enables.iter().map(|&s| s.into()).collect(),
+            default,
+            implicit,
+        };
+        assert_eq!(
+            features_json(raw),
+            vec![
+                feature("_alloc", &[], true, false),
+                feature("default", &["std", "log", "rand?/std"], true, false),
+                feature("log", &["dep:log"], true, true),
+                feature("rand", &["dep:rand"], false, true),
+                feature("serde", &["dep:serde_crate"], false, false),
+                feature("std", &["_alloc"], true, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_enabled_features_are_transitive() {
+        let graph = FeatureGraph::new(vec![
+            Feature::new(DEFAULT_NAME.into(), vec!["std".into()], false),
+            Feature::new("std".into(), vec!["alloc".into()], false),
+            Feature::new("alloc".into(), Vec::new(), false),
+            Feature::new(
+                "full".into(),
+                vec!["std".into(), "serde".into(), "rand?/std".into()],
+                false,
+            ),
+            Feature::new("serde".into(), vec!["dep:serde_crate".into()], false),
+            Feature::new("log".into(), vec!["tracing/log".into()], false),
+            Feature::new("serde_crate".into(), Vec::new(), true),
+            Feature::new("rand".into(), Vec::new(), true),
+            Feature::new("tracing".into(), Vec::new(), true),
+        ]);
+
+        let enabled = graph.enabled(["full"]);
+        assert_eq!(
+            enabled.features.iter().collect::<Vec<_>>(),
+            ["alloc", "full", "serde", "std"]
+        );
+        assert_eq!(
+            enabled.dependencies.iter().collect::<Vec<_>>(),
+            ["serde_crate"]
+        );
+
+        let enabled = graph.enabled(["log", "rand"]);
+        assert_eq!(
+            enabled.features.iter().collect::<Vec<_>>(),
+            ["log", "rand", "tracing"]
+        );
+        assert_eq!(
+            enabled.dependencies.iter().collect::<Vec<_>>(),
+            ["rand", "tracing"]
+        );
+    }
+
+    #[test]
+    fn test_selected_features() {
+        let query = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|&(key, value)| (key.into(), value.into()))
+                .collect()
+        };
+        assert_eq!(
+            selected_features(&query(&[("enable", "foo, bar,,foo")])),
+            ["foo", "bar"]
+        );
+        assert_eq!(
+            selected_features(&query(&[
+                ("enable", "foo"),
+                ("format", "json"),
+                ("enable", "baz")
+            ])),
+            ["foo", "baz"]
+        );
+        assert!(selected_features(&query(&[("format", "json")])).is_empty());
+    }
+
+    #[test]
+    fn enable_features() {
+        wrapper(|env| {
+            let features = [
+                ("default".into(), vec!["std".into()]),
+                ("std".into(), vec!["alloc".into()]),
+                ("alloc".into(), Vec::new()),
+                ("full".into(), vec!["std".into(), "dep:serde".into()]),
+            ]
+            .iter()
+            .cloned()
+            .collect::<HashMap<String, Vec<String>>>();
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .features(features)
+                .optional_dependency("serde")
+                .create()?;
+            let web = env.frontend();
+
+            let value: serde_json::Value = web
+                .get("/crate/foo/0.1.0/features?format=json&enable=full")
+                .send()?
+                .json()?;
+            assert_eq!(
+                value["enabled"],
+                serde_json::json!({
+                    "selected": ["full"],
+                    "features": ["alloc", "full", "std"],
+                    "dependencies": ["serde"],
+                })
+            );
+
+            let resp = web
+                .get("/crate/foo/0.1.0/features?enable=std&enable=full")
+                .send()?;
+            assert!(resp.status().is_success());
+            let page = kuchiki::parse_html().one(resp.text()?);
+            let enabled: Vec<_> = page
+                .select("h3.enabled")
+                .unwrap()
+                .map(|h3| h3.text_contents())
+                .collect();
+            assert_eq!(enabled.len(), 3);
+            let checked: Vec<_> = page
+                .select("[data-id=enable-features] input[checked]")
+                .unwrap()
+                .map(|input| input.attributes.borrow().get("value").unwrap().to_owned())
+                .collect();
+            assert_eq!(checked.len(), 2);
+            assert!(page.select_first("[data-id=enabled-features]").is_ok());
+
+            // without a selection nothing is marked
+            let page =
+                kuchiki::parse_html().one(web.get("/crate/foo/0.1.0/features").send()?.text()?);
+            assert!(page.select_first("h3.enabled").is_err());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn enable_unknown_features() {
+        wrapper(|env| {
+            let features = [("std".into(), Vec::new())]
+                .iter()
+                .cloned()
+                .collect::<HashMap<String, Vec<String>>>();
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .features(features)
+                .create()?;
+            let web = env.frontend();
+
+            let resp = web
+                .get("/crate/foo/0.1.0/features?enable=std,nope,missing")
+                .send()?;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+            let body = resp.text()?;
+            assert!(body.contains("unknown features: nope, missing"), "{}", body);
+
+            let value: serde_json::Value = web
+                .get("/crate/foo/0.1.0/features?format=json")
+                .send()?
+                .json()?;
+            assert!(value.get("enabled").is_none());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn features_json_endpoint() {
+        wrapper(|env| {
+            let features = [
+                ("default".into(), vec!["std".into()]),
+                ("std".into(), Vec::new()),
+                ("serde".into(), vec!["dep:serde_json".into()]),
+            ]
+            .iter()
+            .cloned()
+            .collect::<HashMap<String, Vec<String>>>();
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .features(features)
+                .optional_dependency("rand")
+                .optional_dependency("serde_json")
+                .create()?;
+            let web = env.frontend();
+
+            let resp = web.get("/crate/foo/0.1.0/features.json").send()?;
+            assert!(resp.status().is_success());
+            assert_cache_control(
+                &resp,
+                CachePolicy::ForeverInCdnAndStaleInBrowser,
+                &env.config(),
+            );
+            let value: serde_json::Value = resp.json()?;
+            assert_eq!(
+                value,
+                serde_json::json!({
+                    "features": [
+                        { "name": "default", "enables": ["std"], "default": true, "implicit": false },
+                        { "name": "rand", "enables": ["dep:rand"], "default": false, "implicit": true },
+                        { "name": "serde", "enables": ["dep:serde_json"], "default": false, "implicit": false },
+                        { "name": "std", "enables": [], "default": true, "implicit": false },
+                    ]
+                })
+            );
+
+            assert_redirect_cached(
+                "/crate/foo/0.1/features.json",
+                "/crate/foo/0.1.0/features.json",
+                CachePolicy::ForeverInCdn,
+                web,
+                &env.config(),
+            )?;
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn features_json_without_features() {
+        wrapper(|env| {
+            let id = env.fake_release().name("foo").version("0.1.0").create()?;
+            env.db()
+                .conn()
+                .execute("UPDATE releases SET features = NULL WHERE id = $1", &[&id])?;
+
+            let resp = env
+                .frontend()
+                .get("/crate/foo/0.1.0/features.json")
+                .send()?;
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+            assert!(resp.headers()["content-type"]
+                .to_str()?
+                .starts_with("application/json"));
+            let value: serde_json::Value = resp.json()?;
+            assert_eq!(
+                value.pointer("/error/code"),
+                Some(&"version_not_found".into())
+            );
+            Ok(())
+        });
+    }
+
     #[test]
     fn semver_redirect() {
         wrapper(|env| {
@@ -320,6 +848,47 @@ mod tests {
         });
     }
 
+    #[test]
+    fn format_query_overrides_accept_header() {
+        wrapper(|env| {
+            let features = [
+                ("default".into(), vec!["feature1".into()]),
+                ("feature1".into(), Vec::new()),
+            ]
+            .iter()
+            .cloned()
+            .collect::<HashMap<String, Vec<String>>>();
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .features(features)
+                .create()?;
+            let web = env.frontend();
+
+            let resp = web
+                .get("/crate/foo/0.1.0/features?format=json")
+                .header("accept", "text/html")
+                .send()?;
+            assert!(resp.status().is_success());
+            let value: serde_json::Value = resp.json()?;
+            assert_eq!(value.pointer("/default_len"), Some(&2.into()));
+            assert_eq!(value.pointer("/features/0/name"), Some(&"default".into()));
+
+            let resp = web
+                .get("/crate/foo/0.1.0/features?format=html")
+                .header("accept", "application/json")
+                .send()?;
+            assert!(resp.status().is_success());
+            assert!(resp.headers()["content-type"]
+                .to_str()?
+                .starts_with("text/html"));
+
+            let resp = web.get("/crate/foo/0.1.0/features?format=xml").send()?;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+            Ok(())
+        });
+    }
+
     #[test]
     fn crate_version_not_found() {
         wrapper(|env| {
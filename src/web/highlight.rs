@@ -1,11 +1,13 @@
 use crate::error::Result;
 use once_cell::sync::Lazy;
 use syntect::{
-    html::{ClassStyle, ClassedHTMLGenerator},
-    parsing::{SyntaxReference, SyntaxSet},
+    html::{line_tokens_to_classed_spans, ClassStyle, ClassedHTMLGenerator},
+    parsing::{ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet},
     util::LinesWithEndings,
 };
 
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "syntax-" };
+
 static SYNTAXES: Lazy<SyntaxSet> = Lazy::new(|| {
     static SYNTAX_DATA: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/syntect.packdump"));
 
@@ -22,11 +24,8 @@ static SYNTAXES: Lazy<SyntaxSet> = Lazy::new(|| {
 });
 
 fn try_with_syntax(syntax: &SyntaxReference, code: &str) -> Result<String> {
-    let mut html_generator = ClassedHTMLGenerator::new_with_class_style(
-        syntax,
-        &SYNTAXES,
-        ClassStyle::SpacedPrefixed { prefix: "syntax-" },
-    );
+    let mut html_generator =
+        ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAXES, CLASS_STYLE);
 
     for line in LinesWithEndings::from(code) {
         html_generator.parse_html_for_line_which_includes_newline(line)?;
@@ -60,9 +59,114 @@ pub fn with_lang(lang: Option<&str>, code: &str) -> String {
     }
 }
 
+/// A part of the highlighted code, see [`chunks_with_lang`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct HighlightedChunk {
+    /// Number of lines of the code in this chunk
+    pub(crate) lines: usize,
+    pub(crate) html: String,
+}
+
+/// Highlights `code` lazily in chunks of up to `lines_per_chunk` lines, so large files can be
+/// sent while they are still being highlighted.
+///
+/// Each chunk is valid HTML on its own: the spans still open at the end of a chunk are closed,
+/// and opened again at the start of the next one.
+pub(crate) fn chunks_with_lang<'a>(
+    lang: Option<&str>,
+    code: &'a str,
+    lines_per_chunk: usize,
+) -> HighlightedChunks<'a> {
+    HighlightedChunks {
+        lines: LinesWithEndings::from(code),
+        parse_state: ParseState::new(select_syntax(lang, code)),
+        scope_stack: ScopeStack::new(),
+        lines_per_chunk,
+        failed: false,
+    }
+}
+
+pub(crate) struct HighlightedChunks<'a> {
+    lines: LinesWithEndings<'a>,
+    parse_state: ParseState,
+    scope_stack: ScopeStack,
+    lines_per_chunk: usize,
+    /// After an error the rest of the code is only escaped
+    failed: bool,
+}
+
+impl HighlightedChunks<'_> {
+    fn highlight_line(&mut self, line: &str) -> Result<(String, isize)> {
+        let ops = self.parse_state.parse_line(line, &SYNTAXES)?;
+        Ok(line_tokens_to_classed_spans(
+            line,
+            &ops,
+            CLASS_STYLE,
+            &mut self.scope_stack,
+        )?)
+    }
+}
+
+/// The opening tag `line_tokens_to_classed_spans` uses for `scope`.
+fn open_span(html: &mut String, scope: &Scope) {
+    html.push_str("<span class=\"");
+    for (i, atom) in scope.build_string().split('.').enumerate() {
+        if i != 0 {
+            html.push(' ');
+        }
+        html.push_str("syntax-");
+        html.push_str(atom);
+    }
+    html.push_str("\">");
+}
+
+impl Iterator for HighlightedChunks<'_> {
+    type Item = HighlightedChunk;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut html = String::new();
+        let mut open_spans = self.scope_stack.len() as isize;
+        for scope in self.scope_stack.as_slice() {
+            open_span(&mut html, scope);
+        }
+
+        let mut lines = 0;
+        while lines < self.lines_per_chunk {
+            let line = match self.lines.next() {
+                Some(line) => line,
+                None => break,
+            };
+            lines += 1;
+
+            if !self.failed {
+                match self.highlight_line(line) {
+                    Ok((highlighted, delta)) => {
+                        html.push_str(&highlighted);
+                        open_spans += delta;
+                        continue;
+                    }
+                    Err(err) => {
+                        log::error!("failed while highlighting code: {err:?}");
+                        self.failed = true;
+                    }
+                }
+            }
+            html.push_str(&tera::escape_html(line));
+        }
+
+        if lines == 0 {
+            return None;
+        }
+        for _ in 0..open_spans {
+            html.push_str("</span>");
+        }
+        Some(HighlightedChunk { lines, html })
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::select_syntax;
+    use super::{chunks_with_lang, select_syntax, try_with_lang};
 
     #[test]
     fn custom_filetypes() {
@@ -78,4 +182,52 @@ mod tests {
 
         assert_eq!(select_syntax(Some(".rustfmt.toml"), "").name, toml.name);
     }
+
+    #[test]
+    fn chunks_match_highlighting_everything() {
+        let code = "/// docs\nfn main() {\n    let s = \"a\n    multiline string\";\n}\n";
+        let chunks: Vec<_> = chunks_with_lang(Some("rs"), code, 3).collect();
+
+        assert_eq!(
+            chunks.iter().map(|chunk| chunk.lines).collect::<Vec<_>>(),
+            [3, 2]
+        );
+        // the string continues in the next chunk
+        assert!(chunks[1]
+            .html
+            .starts_with("<span class=\"syntax-source syntax-rust\">"));
+        let continued = &chunks[1].html[..chunks[1].html.find("multiline").unwrap()];
+        assert!(continued.contains("syntax-string"));
+
+        let strip_tags = |html: &str| {
+            let mut text = String::new();
+            let mut in_tag = false;
+            for c in html.chars() {
+                match c {
+                    '<' => in_tag = true,
+                    '>' => in_tag = false,
+                    c if !in_tag => text.push(c),
+                    _ => {}
+                }
+            }
+            text
+        };
+        let chunked: String = chunks.iter().map(|chunk| strip_tags(&chunk.html)).collect();
+        assert_eq!(
+            chunked,
+            strip_tags(&try_with_lang(Some("rs"), code).unwrap())
+        );
+
+        for chunk in &chunks {
+            assert_eq!(
+                chunk.html.matches("<span").count(),
+                chunk.html.matches("</span>").count()
+            );
+        }
+    }
+
+    #[test]
+    fn chunks_of_empty_code() {
+        assert_eq!(chunks_with_lang(Some("rs"), "", 10).count(), 0);
+    }
 }
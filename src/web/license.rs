@@ -0,0 +1,349 @@
+//! Shows the license of a release, the SPDX expression from the manifest together with the
+//! license files shipped in its source.
+
+use crate::{
+    db::Pool,
+    impl_axum_webpage,
+    storage::PathNotFoundError,
+    utils::spawn_blocking,
+    web::{
+        cache::CachePolicy,
+        error::{AxumNope, AxumResult},
+        match_version_axum,
+        output_format::{OutputFormat, RequestedFormat},
+        MatchSemver, MetaData,
+    },
+    Storage,
+};
+use axum::{
+    extract::{Extension, Path},
+    response::{IntoResponse, Response as AxumResponse},
+    Json,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Prefixes of the names of license files, like `LICENSE-MIT` or `COPYING.txt`. The files are
+/// only looked up in the root of the source and compared case-insensitively.
+const LICENSE_FILE_PREFIXES: &[&str] = &["license", "licence", "copying", "unlicense"];
+
+/// Returns the paths of the license files in the source file list stored in `releases.files`,
+/// sorted by name. `license_file` is the `license-file` field of the manifest, it's included
+/// wherever it is in the source.
+fn find_license_files(files: &Value, license_file: Option<&str>) -> Vec<String> {
+    // cargo packages license files from outside of the package into its root
+    let license_file = license_file.map(|path| {
+        let path = path.trim_start_matches("./");
+        if path.starts_with("../") {
+            path.rsplit('/').next().unwrap_or(path)
+        } else {
+            path
+        }
+    });
+
+    let mut paths: Vec<String> = files
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|file| file.as_array()?.get(1)?.as_str())
+        .filter(|path| {
+            Some(*path) == license_file
+                || !path.contains('/')
+                    && LICENSE_FILE_PREFIXES.iter().any(|prefix| {
+                        path.get(..prefix.len())
+                            .map_or(false, |start| start.eq_ignore_ascii_case(prefix))
+                    })
+        })
+        .map(str::to_owned)
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct LicenseFile {
+    name: String,
+    text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct License {
+    /// The `license` field of the manifest, `None` for crates using a `license-file` only
+    spdx: Option<String>,
+    files: Vec<LicenseFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct LicensePage {
+    metadata: MetaData,
+    #[serde(flatten)]
+    license: License,
+    is_latest_url: bool,
+}
+
+impl_axum_webpage! {
+    LicensePage = "crate/license.html",
+    cache_policy = |page| license_cache_policy(page.is_latest_url),
+}
+
+/// Pages of exact versions can also be cached by browsers, the CDN is purged when the included
+/// files change.
+fn license_cache_policy(is_latest_url: bool) -> CachePolicy {
+    if is_latest_url {
+        CachePolicy::ForeverInCdn
+    } else {
+        CachePolicy::ForeverInCdnAndStaleInBrowser
+    }
+}
+
+pub(crate) async fn license_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+    Extension(storage): Extension<Arc<Storage>>,
+    RequestedFormat(format): RequestedFormat,
+) -> AxumResult<AxumResponse> {
+    let (version, version_or_latest, is_latest_url) =
+        match match_version_axum(&pool, &name, Some(&req_version))
+            .await?
+            .assume_exact()?
+        {
+            MatchSemver::Exact((version, _)) => (version.clone(), version, false),
+            MatchSemver::Latest((version, _)) => (version, "latest".to_string(), true),
+            MatchSemver::Semver((version, _)) => {
+                return Ok(super::axum_cached_redirect(
+                    &format!("/crate/{}/{}/license", name, version),
+                    CachePolicy::ForeverInCdn,
+                )?
+                .into_response());
+            }
+        };
+
+    let page = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let row = conn
+            .query_opt(
+                "SELECT releases.license, releases.license_file, releases.files,
+                    releases.archive_storage
+                 FROM releases
+                 INNER JOIN crates ON releases.crate_id = crates.id
+                 WHERE crates.name = $1 AND releases.version = $2",
+                &[&name, &version],
+            )?
+            .ok_or(AxumNope::VersionNotFound)?;
+
+        // releases built before the source code was stored don't have a file list
+        let license_file: Option<String> = row.get("license_file");
+        let paths = row
+            .get::<_, Option<Value>>("files")
+            .map(|files| find_license_files(&files, license_file.as_deref()))
+            .unwrap_or_default();
+
+        let mut files = Vec::with_capacity(paths.len());
+        for path in paths {
+            match storage.fetch_source_file(&name, &version, &path, row.get("archive_storage")) {
+                Ok(blob) => files.push(LicenseFile {
+                    name: path,
+                    text: String::from_utf8_lossy(&blob.content).into_owned(),
+                }),
+                Err(err) if err.is::<PathNotFoundError>() => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(LicensePage {
+            metadata: MetaData::from_crate(&mut conn, &name, &version, &version_or_latest)?,
+            license: License {
+                spdx: row.get("license"),
+                files,
+            },
+            is_latest_url,
+        })
+    })
+    .await?;
+
+    if format == OutputFormat::Json {
+        return Ok((
+            Extension(license_cache_policy(is_latest_url)),
+            Json(page.license),
+        )
+            .into_response());
+    }
+    Ok(page.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_license_files;
+    use crate::test::{assert_cache_control, assert_redirect_cached, wrapper};
+    use crate::web::cache::CachePolicy;
+    use kuchiki::traits::TendrilSink;
+    use serde_json::json;
+    use test_case::test_case;
+
+    #[test_case(&["src/lib.rs", "LICENSE"], &["LICENSE"])]
+    #[test_case(
+        &["LICENSE-MIT", "src/lib.rs", "LICENSE-APACHE"],
+        &["LICENSE-APACHE", "LICENSE-MIT"];
+        "dual license"
+    )]
+    #[test_case(&["License.md", "COPYING", "UNLICENSE"], &["COPYING", "License.md", "UNLICENSE"]; "case insensitive")]
+    #[test_case(&["vendor/LICENSE", "src/license.rs", "LICENSE"], &["LICENSE"]; "only root")]
+    #[test_case(&["README.md"], &[]; "missing")]
+    fn find_license_files_in_files(paths: &[&str], expected: &[&str]) {
+        let files = json!(paths
+            .iter()
+            .map(|path| json!(["text/plain", path]))
+            .collect::<Vec<_>>());
+        assert_eq!(find_license_files(&files, None), expected);
+    }
+
+    #[test_case("legal/terms.txt", &["LICENSE", "legal/terms.txt"])]
+    #[test_case("./legal/terms.txt", &["LICENSE", "legal/terms.txt"]; "relative")]
+    #[test_case("../TERMS", &["LICENSE", "TERMS"]; "outside of the package")]
+    #[test_case("LICENSE", &["LICENSE"]; "not duplicated")]
+    #[test_case("legal/missing.txt", &["LICENSE"]; "missing")]
+    fn find_license_files_with_license_file(license_file: &str, expected: &[&str]) {
+        let files = json!([
+            ["text/plain", "LICENSE"],
+            ["text/plain", "TERMS"],
+            ["text/plain", "legal/terms.txt"],
+            ["text/plain", "src/lib.rs"],
+        ]);
+        assert_eq!(find_license_files(&files, Some(license_file)), expected);
+    }
+
+    #[test]
+    fn dual_license() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .license(Some("MIT OR Apache-2.0"))
+                .source_file("LICENSE-MIT", b"MIT License <Copyright>")
+                .source_file("LICENSE-APACHE", b"Apache License\nVersion 2.0")
+                .source_file("src/lib.rs", b"")
+                .create()?;
+            let web = env.frontend();
+
+            let response = web.get("/crate/foo/0.1.0/license?format=json").send()?;
+            assert!(response.status().is_success());
+            assert_cache_control(
+                &response,
+                CachePolicy::ForeverInCdnAndStaleInBrowser,
+                &env.config(),
+            );
+            assert_eq!(
+                response.json::<serde_json::Value>()?,
+                json!({
+                    "spdx": "MIT OR Apache-2.0",
+                    "files": [
+                        {"name": "LICENSE-APACHE", "text": "Apache License\nVersion 2.0"},
+                        {"name": "LICENSE-MIT", "text": "MIT License <Copyright>"},
+                    ],
+                })
+            );
+
+            let response = web.get("/crate/foo/0.1.0/license").send()?;
+            assert!(response.status().is_success());
+            assert_cache_control(
+                &response,
+                CachePolicy::ForeverInCdnAndStaleInBrowser,
+                &env.config(),
+            );
+            let page = kuchiki::parse_html().one(response.text()?);
+            assert_eq!(
+                page.select_first("[data-id=spdx]").unwrap().text_contents(),
+                "MIT OR Apache-2.0"
+            );
+            let texts: Vec<_> = page
+                .select("pre.license-text")
+                .unwrap()
+                .map(|pre| pre.text_contents())
+                .collect();
+            assert_eq!(
+                texts,
+                ["Apache License\nVersion 2.0", "MIT License <Copyright>"]
+            );
+
+            let response = web.get("/crate/foo/latest/license").send()?;
+            assert!(response.status().is_success());
+            assert_cache_control(&response, CachePolicy::ForeverInCdn, &env.config());
+
+            assert_redirect_cached(
+                "/crate/foo/0.1/license",
+                "/crate/foo/0.1.0/license",
+                CachePolicy::ForeverInCdn,
+                web,
+                &env.config(),
+            )?;
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn only_spdx_or_only_files() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .license(Some("MIT"))
+                .source_file("src/lib.rs", b"")
+                .create()?;
+            env.fake_release()
+                .name("bar")
+                .version("0.1.0")
+                .license(None)
+                .source_file("LICENSE.txt", b"All rights reserved")
+                .create()?;
+            let web = env.frontend();
+
+            let value: serde_json::Value = web
+                .get("/crate/foo/0.1.0/license")
+                .header("accept", "application/json")
+                .send()?
+                .json()?;
+            assert_eq!(value, json!({"spdx": "MIT", "files": []}));
+            let page =
+                kuchiki::parse_html().one(web.get("/crate/foo/0.1.0/license").send()?.text()?);
+            assert!(page.select_first("[data-id=no-files]").is_ok());
+
+            let value: serde_json::Value = web
+                .get("/crate/bar/0.1.0/license?format=json")
+                .send()?
+                .json()?;
+            assert_eq!(
+                value,
+                json!({
+                    "spdx": null,
+                    "files": [{"name": "LICENSE.txt", "text": "All rights reserved"}],
+                })
+            );
+            let page =
+                kuchiki::parse_html().one(web.get("/crate/bar/0.1.0/license").send()?.text()?);
+            assert!(page.select_first("[data-id=no-spdx]").is_ok());
+
+            env.fake_release()
+                .name("baz")
+                .version("0.1.0")
+                .license(None)
+                .license_file("legal/terms.txt")
+                .source_file("legal/terms.txt", b"Custom terms")
+                .create()?;
+            let value: serde_json::Value = web
+                .get("/crate/baz/0.1.0/license?format=json")
+                .send()?
+                .json()?;
+            assert_eq!(
+                value,
+                json!({
+                    "spdx": null,
+                    "files": [{"name": "legal/terms.txt", "text": "Custom terms"}],
+                })
+            );
+
+            assert_eq!(web.get("/crate/foo/0.2.0/license").send()?.status(), 404);
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,61 @@
+//! Picking a translation of a page based on the `Accept-Language` header, see [`negotiate`].
+
+/// Locales pages can be translated to, besides the default english.
+///
+/// Translations are template variants named `<template>.<locale>.html`, see
+/// [`Translated`](super::page::web_page::Translated).
+pub(crate) const TRANSLATED_LOCALES: &[&str] = &["de", "es", "fr"];
+
+/// Returns the translation the client prefers according to its `Accept-Language` header, or
+/// `None` when the english default should be used.
+///
+/// Regional variants fall back to their language, so `de-AT` gets the `de` translation.
+pub(crate) fn negotiate(accept_language: &str) -> Option<&'static str> {
+    let mut languages: Vec<(String, f32)> = accept_language
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';').map(str::trim);
+            let language = parts.next().filter(|language| !language.is_empty())?;
+            let quality = parts
+                .filter_map(|param| param.strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            Some((language.to_ascii_lowercase(), quality))
+        })
+        .filter(|(_, quality)| *quality > 0.0)
+        .collect();
+    // stable sort, languages with the same quality keep the order of the header
+    languages.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+
+    for (language, _) in languages {
+        let primary = language.split('-').next().unwrap_or_default();
+        if primary == "en" || primary == "*" {
+            return None;
+        }
+        if let Some(locale) = TRANSLATED_LOCALES.iter().find(|&&locale| locale == primary) {
+            return Some(locale);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::negotiate;
+    use test_case::test_case;
+
+    #[test_case("de", Some("de"); "exact")]
+    #[test_case("de-AT,de;q=0.9", Some("de"); "region")]
+    #[test_case("FR-fr", Some("fr"); "uppercase")]
+    #[test_case("en-US,en;q=0.9,de;q=0.8", None; "english first")]
+    #[test_case("de;q=0.5, fr", Some("fr"); "ranked")]
+    #[test_case("es, fr", Some("es"); "tie")]
+    #[test_case("ja, fr;q=0.5", Some("fr"); "untranslated first")]
+    #[test_case("ja", None; "untranslated")]
+    #[test_case("*", None; "wildcard")]
+    #[test_case("de;q=0, es;q=0.1", Some("es"); "refused")]
+    #[test_case("", None; "empty")]
+    fn accept_language(header: &str, expected: Option<&str>) {
+        assert_eq!(negotiate(header), expected);
+    }
+}
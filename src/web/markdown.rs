@@ -80,7 +80,7 @@ pub fn render(text: &str) -> String {
 
 #[cfg(test)]
 mod test {
-    use super::render_with_highlighter;
+    use super::{render, render_with_highlighter};
     use indoc::indoc;
     use std::cell::RefCell;
 
@@ -115,4 +115,19 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn raw_html_and_dangerous_links_are_removed() {
+        let output = render(indoc! {"
+            <script>alert(1)</script>
+
+            <img src=x onerror=alert(1)>
+
+            [link](javascript:alert(1))
+        "});
+
+        assert!(!output.contains("<script"));
+        assert!(!output.contains("onerror"));
+        assert!(!output.contains("javascript:"));
+    }
 }
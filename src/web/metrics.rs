@@ -1,4 +1,9 @@
-use crate::{db::Pool, utils::spawn_blocking, web::error::AxumResult, BuildQueue, Metrics};
+use crate::{
+    db::Pool,
+    utils::spawn_blocking,
+    web::{client_ip::ClientIp, error::AxumResult},
+    BuildQueue, Metrics,
+};
 use anyhow::Context as _;
 use axum::{
     body::Body,
@@ -20,6 +25,7 @@ use std::{
 };
 #[cfg(test)]
 use tracing::debug;
+use tracing::Instrument;
 
 pub(super) async fn metrics_handler(
     Extension(pool): Extension<Pool>,
@@ -78,8 +84,15 @@ pub(crate) async fn request_recorder<B>(
         .expect("metrics missing in request extensions")
         .clone();
 
+    let client_ip = ClientIp::from_request_data(request.extensions(), request.headers())
+        .map(|ClientIp(ip)| ip.to_string())
+        .unwrap_or_default();
+
     let start = Instant::now();
-    let result = next.run(request).await;
+    let result = next
+        .run(request)
+        .instrument(tracing::info_span!("request", route = %route_name, %client_ip))
+        .await;
     let resp_time = duration_to_seconds(start.elapsed());
 
     metrics
@@ -90,6 +103,7 @@ pub(crate) async fn request_recorder<B>(
         .response_time
         .with_label_values(&[&route_name])
         .observe(resp_time);
+    metrics.recent_response_times.record(&route_name, resp_time);
 
     result
 }
@@ -123,6 +137,9 @@ impl iron::Handler for RequestRecorder {
             .response_time
             .with_label_values(&[&self.route_name])
             .observe(resp_time);
+        metrics
+            .recent_response_times
+            .record(&self.route_name, resp_time);
 
         result
     }
@@ -133,9 +150,38 @@ struct RenderingTime {
     step: &'static str,
 }
 
+/// Durations of the steps of handling a request, sent to browsers in a `Server-Timing` header
+/// when `DOCSRS_SERVER_TIMING` is enabled.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct ServerTiming {
+    entries: Vec<(&'static str, Duration)>,
+}
+
+impl ServerTiming {
+    pub(crate) fn record(&mut self, step: &'static str, duration: Duration) {
+        self.entries.push((step, duration));
+    }
+
+    pub(crate) fn header_value(&self) -> String {
+        self.entries
+            .iter()
+            .map(|(step, duration)| {
+                format!(
+                    "{};desc=\"{}\";dur={:.1}",
+                    step.replace(' ', "-"),
+                    step,
+                    duration.as_secs_f64() * 1000.0
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
 pub(crate) struct RenderingTimesRecorder<'a> {
     metric: &'a HistogramVec,
     current: Option<RenderingTime>,
+    server_timing: ServerTiming,
 }
 
 impl<'a> RenderingTimesRecorder<'a> {
@@ -143,9 +189,16 @@ impl<'a> RenderingTimesRecorder<'a> {
         Self {
             metric,
             current: None,
+            server_timing: ServerTiming::default(),
         }
     }
 
+    /// Finishes the current step and returns the durations of all steps so far.
+    pub(crate) fn server_timing(&mut self) -> ServerTiming {
+        self.record_current();
+        self.server_timing.clone()
+    }
+
     pub(crate) fn step(&mut self, step: &'static str) {
         self.record_current();
         self.current = Some(RenderingTime {
@@ -162,9 +215,11 @@ impl<'a> RenderingTimesRecorder<'a> {
                 current.step,
                 current.start.elapsed()
             );
+            let elapsed = current.start.elapsed();
             self.metric
                 .with_label_values(&[current.step])
-                .observe(duration_to_seconds(current.start.elapsed()));
+                .observe(duration_to_seconds(elapsed));
+            self.server_timing.record(current.step, elapsed);
         }
     }
 }
@@ -282,6 +337,81 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_response_time_percentiles() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let frontend = env.frontend();
+            for _ in 0..3 {
+                frontend.get("/crate/foo/0.1.0").send()?;
+            }
+
+            let families = env.metrics().gather(&env.pool()?, &env.build_queue())?;
+            let percentiles = families
+                .iter()
+                .find(|family| family.get_name() == "docsrs_response_time_percentiles")
+                .unwrap();
+            let mut quantiles: Vec<_> = percentiles
+                .get_metric()
+                .iter()
+                .filter(|metric| {
+                    metric.get_label().iter().any(|label| {
+                        label.get_name() == "route" && label.get_value() == "/crate/:name/:version"
+                    })
+                })
+                .map(|metric| {
+                    assert!(metric.get_gauge().get_value() > 0.0);
+                    metric
+                        .get_label()
+                        .iter()
+                        .find(|label| label.get_name() == "quantile")
+                        .unwrap()
+                        .get_value()
+                        .to_owned()
+                })
+                .collect();
+            quantiles.sort();
+            assert_eq!(quantiles, ["0.5", "0.9", "0.99"]);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_build_queue_gauges() {
+        wrapper(|env| {
+            env.fake_release().name("built").version("0.1.0").create()?;
+            let queue = env.build_queue();
+            queue.add_crate("foo", "0.1.0", 0, None)?;
+            queue.add_crate("bar", "0.1.0", 0, None)?;
+            env.db().conn().execute(
+                "UPDATE queue SET queued_at = NOW() - INTERVAL '10 minutes' WHERE name = 'foo'",
+                &[],
+            )?;
+
+            let gauge = |name: &str| -> anyhow::Result<f64> {
+                let families = env.metrics().gather(&env.pool()?, &queue)?;
+                Ok(families
+                    .iter()
+                    .find(|family| family.get_name() == name)
+                    .unwrap_or_else(|| panic!("missing metric {}", name))
+                    .get_metric()[0]
+                    .get_gauge()
+                    .get_value())
+            };
+            assert_eq!(gauge("docsrs_queued_crates_count")?, 2.0);
+            let age = gauge("docsrs_queue_oldest_crate_age")?;
+            assert!((600.0..660.0).contains(&age), "{}", age);
+            // the fake build finished on this server just now
+            assert_eq!(gauge("docsrs_active_builders")?, 1.0);
+
+            env.db().conn().execute("DELETE FROM queue", &[])?;
+            assert_eq!(gauge("docsrs_queue_oldest_crate_age")?, 0.0);
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_metrics_page_success() {
         wrapper(|env| {
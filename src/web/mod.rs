@@ -6,7 +6,7 @@ use crate::utils::get_correct_docsrs_style_file;
 use crate::utils::{report_error, spawn_blocking};
 use anyhow::{anyhow, bail, Context as _, Result};
 use serde_json::Value;
-use tracing::{info, instrument};
+use tracing::{info, instrument, warn};
 
 /// ctry! (cratesfyitry) is extremely similar to try! and itry!
 /// except it returns an error page response instead of plain Err.
@@ -72,27 +72,54 @@ macro_rules! extension {
     }};
 }
 
+mod all_items;
+mod auth;
+mod base_url;
+mod book;
 mod build_details;
 mod builds;
 pub(crate) mod cache;
+pub(crate) mod changelog;
+mod client_ip;
+mod compare;
+mod cors;
 pub(crate) mod crate_details;
+mod crate_suggestions;
 mod csp;
 pub(crate) mod error;
 mod extensions;
 mod features;
 mod file;
 mod highlight;
+mod license;
+mod locale;
 mod markdown;
 pub(crate) mod metrics;
+mod openapi;
+mod output_format;
+mod previews;
+mod purge;
+mod rate_limit;
 mod releases;
-mod routes;
+mod reports;
+pub(crate) mod routes;
 mod rustdoc;
+pub(crate) mod rustdoc_assets;
+pub(crate) mod scoped_urls;
+mod search_index;
+mod short_links;
 mod sitemap;
 mod source;
 mod statics;
 mod strangler;
+mod webhooks;
 
-use crate::{db::Pool, impl_axum_webpage, impl_webpage, Context};
+use crate::{
+    db::Pool,
+    impl_axum_webpage, impl_webpage,
+    storage::{Blob, PathNotFoundError},
+    Context, Storage,
+};
 use anyhow::Error;
 use axum::{
     extract::Extension,
@@ -119,16 +146,30 @@ use router::{NoRoute, TrailingSlash};
 use semver::{Version, VersionReq};
 use serde::Serialize;
 use std::borrow::Borrow;
-use std::{borrow::Cow, net::SocketAddr, sync::Arc};
+use std::{
+    borrow::Cow,
+    future::Future,
+    net::{SocketAddr, TcpListener},
+    sync::Arc,
+    time::Duration,
+};
 use strangler::StranglerService;
 use tower::ServiceBuilder;
-use tower_http::trace::TraceLayer;
+use tower_http::{
+    compression::{
+        predicate::{NotForContentType, Predicate, SizeAbove},
+        CompressionLayer,
+    },
+    trace::TraceLayer,
+};
 use url::form_urlencoded;
 
 /// Duration of static files for staticfile and DatabaseFileHandler (in seconds)
 const STATIC_FILE_CACHE_DURATION: u64 = 60 * 60 * 24 * 30 * 12; // 12 months
 
 const DEFAULT_BIND: &str = "0.0.0.0:3000";
+/// Responses smaller than this aren't worth compressing
+const COMPRESSION_MIN_SIZE: u16 = 1024;
 
 pub(crate) struct MainHandler {
     shared_resource_handler: Box<dyn Handler>,
@@ -151,10 +192,10 @@ impl MainHandler {
     pub(crate) fn new(
         template_data: Arc<TemplateData>,
         context: &dyn Context,
+        routes: routes::Routes,
     ) -> Result<MainHandler, Error> {
         let inject_extensions = InjectExtensions::new(context, template_data)?;
 
-        let routes = routes::build_routes();
         let shared_resources = Self::chain(
             inject_extensions.clone(),
             rustdoc::LegacySharedResourceHandler,
@@ -314,9 +355,17 @@ impl MatchSemver {
 /// `version` may be an exact version number or loose semver version requirement. The return value
 /// will indicate whether the given version exactly matched a version number from the database.
 ///
+/// `latest` (like `*` and `newest`) resolves to the newest stable release, or the newest
+/// pre-release when a crate has no stable releases. `latest-prerelease` resolves to the newest
+/// release including pre-releases.
+///
+/// Release channels configured with `database channel set` (like `stable`) resolve to the version
+/// they point to. Unknown channels are treated like any other invalid version requirement.
+///
 /// This function will also check for crates where dashes in the name (`-`) have been replaced with
 /// underscores (`_`) and vice-versa. The return value will indicate whether the crate name has
 /// been matched exactly, or if there has been a "correction" in the name that matched instead.
+#[instrument(skip(conn))]
 fn match_version(
     conn: &mut Client,
     name: &str,
@@ -349,10 +398,6 @@ fn match_version(
     let releases = crate_details::releases_for_crate(conn, crate_id)
         .expect("error fetching releases for crate");
 
-    if releases.is_empty() {
-        return Err(Nope::CrateNotFound);
-    }
-
     // version is an Option<&str> from router::Router::get, need to decode first.
     // Any encoding errors we treat as _any version_.
     use iron::url::percent_encoding::percent_decode;
@@ -373,29 +418,109 @@ fn match_version(
                 target_name: release.target_name.clone(),
             });
         }
+
+        // yanked releases that were deleted afterwards are gone for good
+        let purged = conn
+            .query_opt(
+                "SELECT 1 FROM purged_releases WHERE crate_id = $1 AND version = $2",
+                &[&crate_id, &parsed_req_version.to_string()],
+            )
+            .map_err(|err| {
+                report_error(&Error::from(err).context("error looking up purged releases"));
+                Nope::InternalServerError
+            })?;
+        if purged.is_some() {
+            return Err(Nope::Gone);
+        }
     }
 
-    // Now try to match with semver, treat `newest` and `latest` as `*`
-    let req_semver = if req_version == "newest" || req_version == "latest" {
-        VersionReq::STAR
-    } else {
+    if releases.is_empty() {
+        return Err(Nope::CrateNotFound);
+    }
+
+    // Now try to match with semver, treat `newest`, `latest` and `latest-prerelease` as `*`
+    let include_prereleases = req_version == "latest-prerelease";
+    let is_explicit_range = !matches!(
+        req_version.as_ref(),
+        "*" | "newest" | "latest" | "latest-prerelease"
+    );
+
+    // release channels like `stable` are never valid version requirements,
+    // so only look them up when the requested version isn't one.
+    if is_explicit_range && VersionReq::parse(&req_version).is_err() {
+        let channel_version = crate::db::channels::channel_version(
+            conn,
+            corrected_name.as_deref().unwrap_or(name),
+            &req_version,
+        )
+        .map_err(|err| {
+            report_error(&err.context("error looking up the release channel"));
+            Nope::InternalServerError
+        })?;
+
+        if let Some(channel_version) = channel_version {
+            return releases
+                .iter()
+                .find(|release| release.version.to_string() == channel_version)
+                .map(|release| MatchVersion {
+                    corrected_name: corrected_name.clone(),
+                    version: MatchSemver::Semver((release.version.to_string(), release.id)),
+                    rustdoc_status: release.rustdoc_status,
+                    target_name: release.target_name.clone(),
+                })
+                .ok_or(Nope::VersionNotFound);
+        }
+    }
+
+    let req_semver = if is_explicit_range {
         VersionReq::parse(&req_version).map_err(|err| {
             info!(
                 "could not parse version requirement \"{}\": {:?}",
                 req_version, err
             );
-            Nope::VersionNotFound
+            // only complain about the syntax when the user obviously tried to give a range
+            if req_version.starts_with(['^', '~', '=', '<', '>', '*']) || req_version.contains(',')
+            {
+                Nope::InvalidVersionRange
+            } else {
+                Nope::VersionNotFound
+            }
         })?
+    } else {
+        VersionReq::STAR
     };
 
     // starting here, we only look at non-yanked releases
     let releases: Vec<_> = releases.iter().filter(|r| !r.yanked).collect();
 
+    // semver `*` does not match pre-releases, `releases` is sorted newest version first.
+    if include_prereleases {
+        return releases
+            .first()
+            .map(|release| MatchVersion {
+                corrected_name: corrected_name.clone(),
+                version: MatchSemver::Semver((release.version.to_string(), release.id)),
+                rustdoc_status: release.rustdoc_status,
+                target_name: release.target_name.clone(),
+            })
+            .ok_or(Nope::VersionNotFound);
+    }
+
     // try to match the version in all un-yanked releases.
-    if let Some(release) = releases
+    // Explicit ranges like `^1.2` prefer the newest matching release that has documentation,
+    // while `latest` always points to the newest release so failed builds stay visible.
+    let mut matching = releases
         .iter()
-        .find(|release| req_semver.matches(&release.version))
-    {
+        .filter(|release| req_semver.matches(&release.version));
+    let matched = if is_explicit_range {
+        matching
+            .clone()
+            .find(|release| release.rustdoc_status)
+            .or_else(|| matching.next())
+    } else {
+        matching.next()
+    };
+    if let Some(release) = matched {
         return Ok(MatchVersion {
             corrected_name,
             version: if input_version == Some("latest") {
@@ -416,7 +541,11 @@ fn match_version(
             .first()
             .map(|release| MatchVersion {
                 corrected_name: corrected_name.clone(),
-                version: MatchSemver::Semver((release.version.to_string(), release.id)),
+                version: if input_version == Some("latest") {
+                    MatchSemver::Latest((release.version.to_string(), release.id))
+                } else {
+                    MatchSemver::Semver((release.version.to_string(), release.id))
+                },
                 rustdoc_status: release.rustdoc_status,
                 target_name: release.target_name.clone(),
             })
@@ -449,29 +578,118 @@ async fn match_version_axum(
     .await
 }
 
+/// The documentation of a release, for the JSON endpoints serving what rustdoc generated for it.
+struct RustdocRelease {
+    name: String,
+    version: String,
+    archive_storage: bool,
+    /// The name of the library, the directory of the pages of the default target
+    target_name: String,
+    doc_rustc_version: String,
+}
+
+impl RustdocRelease {
+    /// Loads a release, failing with `VersionNotFound` when it doesn't exist and with
+    /// `ResourceNotFound` when it has no documentation.
+    fn load(conn: &mut Client, name: &str, version: &str) -> Result<Self> {
+        let row = conn
+            .query_opt(
+                "SELECT releases.rustdoc_status,
+                    releases.archive_storage,
+                    releases.target_name,
+                    releases.doc_rustc_version
+                 FROM releases
+                 INNER JOIN crates ON releases.crate_id = crates.id
+                 WHERE crates.name = $1 AND releases.version = $2",
+                &[&name, &version],
+            )?
+            .ok_or(error::AxumNope::VersionNotFound)?;
+        if !row.get::<_, bool>("rustdoc_status") {
+            return Err(error::AxumNope::ResourceNotFound.into());
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            version: version.to_owned(),
+            archive_storage: row.get("archive_storage"),
+            target_name: row.get("target_name"),
+            doc_rustc_version: row.get("doc_rustc_version"),
+        })
+    }
+
+    /// Fetches a file of the documentation, failing with `ResourceNotFound` when it doesn't exist.
+    fn fetch(&self, storage: &Storage, path: &str) -> Result<Blob> {
+        storage
+            .fetch_rustdoc_file(&self.name, &self.version, path, self.archive_storage, None)
+            .map_err(Self::not_found)
+    }
+
+    /// Fetches a file like [`RustdocRelease::fetch`], but still compressed.
+    fn fetch_compressed(&self, storage: &Storage, path: &str) -> Result<Blob> {
+        storage
+            .fetch_compressed_rustdoc_file(&self.name, &self.version, path, self.archive_storage)
+            .map_err(Self::not_found)
+    }
+
+    fn not_found(err: Error) -> Error {
+        if err.downcast_ref::<PathNotFoundError>().is_some() {
+            error::AxumNope::ResourceNotFound.into()
+        } else {
+            err
+        }
+    }
+}
+
 #[instrument(skip_all)]
 pub(crate) fn build_axum_app(
     context: &dyn Context,
     template_data: Arc<TemplateData>,
+    router: AxumRouter,
 ) -> Result<AxumRouter, Error> {
-    Ok(routes::build_axum_routes().layer(
+    Ok(router.layer(
         // It’s recommended to use tower::ServiceBuilder to apply multiple middleware at once,
         // instead of calling Router::layer repeatedly:
         ServiceBuilder::new()
             .layer(TraceLayer::new_for_http())
             .layer(sentry_tower::NewSentryLayer::new_from_top())
             .layer(sentry_tower::SentryHttpLayer::with_transaction())
+            // responses that already have a `Content-Encoding` are never compressed again,
+            // images, fonts and zip archives are compressed formats already. Not compressing the
+            // archives also keeps their byte ranges intact.
+            .layer(
+                CompressionLayer::new().compress_when(
+                    SizeAbove::new(COMPRESSION_MIN_SIZE)
+                        .and(NotForContentType::GRPC)
+                        .and(NotForContentType::IMAGES)
+                        .and(NotForContentType::const_new("font/"))
+                        .and(NotForContentType::const_new("application/zip")),
+                ),
+            )
             .layer(Extension(context.pool()?))
             .layer(Extension(context.build_queue()?))
+            .layer(Extension(context.cdn()?))
             .layer(Extension(context.metrics()?))
             .layer(Extension(context.config()?))
             .layer(Extension(context.storage()?))
             .layer(Extension(context.repository_stats_updater()?))
             .layer(Extension(template_data))
+            .layer(Extension(Arc::new(
+                crate_suggestions::CrateNameIndex::default(),
+            )))
+            .layer(Extension(Arc::new(
+                search_index::SearchIndexCache::default(),
+            )))
+            .layer(Extension(Arc::new(
+                releases::DocumentedCratesCount::default(),
+            )))
             .layer(middleware::from_fn(csp::csp_middleware))
             .layer(middleware::from_fn(
                 page::web_page::render_templates_middleware,
             ))
+            .layer(middleware::from_fn(
+                crate_suggestions::crate_suggestions_middleware,
+            ))
+            .layer(middleware::from_fn(error::json_errors_middleware))
             .layer(middleware::from_fn(cache::cache_middleware)),
     ))
 }
@@ -487,9 +705,10 @@ pub(crate) fn build_strangler_service(addr: SocketAddr) -> Result<StranglerServi
 pub(crate) fn start_iron_server(
     context: &dyn Context,
     template_data: Arc<TemplateData>,
+    routes: routes::Routes,
     threads: Option<usize>,
 ) -> Result<Listening, Error> {
-    let mut iron = Iron::new(MainHandler::new(template_data, context)?);
+    let mut iron = Iron::new(MainHandler::new(template_data, context, routes)?);
     if let Some(threads) = threads {
         iron.threads = threads;
     }
@@ -500,7 +719,8 @@ pub(crate) fn start_iron_server(
 pub fn start_web_server(addr: Option<&str>, context: &dyn Context) -> Result<(), Error> {
     let template_data = Arc::new(TemplateData::new(&mut *context.pool()?.get()?)?);
 
-    let iron_server = start_iron_server(context, template_data.clone(), None)?;
+    let (axum_router, iron_routes) = routes::build_routes();
+    let iron_server = start_iron_server(context, template_data.clone(), iron_routes, None)?;
 
     let axum_addr: SocketAddr = addr.unwrap_or(DEFAULT_BIND).parse()?;
 
@@ -510,20 +730,86 @@ pub fn start_web_server(addr: Option<&str>, context: &dyn Context) -> Result<(),
         axum_addr.port()
     );
 
-    context.runtime()?.block_on(async {
-        axum::Server::bind(&axum_addr)
-            .serve(
-                build_axum_app(context, template_data)?
-                    .fallback_service(build_strangler_service(iron_server.socket)?)
-                    .into_make_service(),
-            )
-            .await?;
-        Ok::<(), Error>(())
-    })?;
+    let app = build_axum_app(context, template_data, axum_router)?
+        .fallback_service(build_strangler_service(iron_server.socket)?);
+    let app = scoped_urls::serve_scoped_urls(app, &context.config()?);
+    let grace_period = Duration::from_secs(context.config()?.shutdown_grace_period);
+
+    context.runtime()?.block_on(serve_with_graceful_shutdown(
+        TcpListener::bind(axum_addr)?,
+        app,
+        shutdown_signal(),
+        grace_period,
+    ))?;
 
     Ok(())
 }
 
+/// Serves `app` on `listener` until `signal` resolves.
+///
+/// The server then stops accepting new connections, and waits up to `grace_period` for
+/// in-flight requests (including slow rustdoc renders) to complete before returning, so
+/// deploys don't drop requests.
+pub(crate) async fn serve_with_graceful_shutdown(
+    listener: TcpListener,
+    app: AxumRouter,
+    signal: impl Future<Output = ()>,
+    grace_period: Duration,
+) -> Result<(), Error> {
+    let (draining_tx, draining_rx) = tokio::sync::oneshot::channel::<()>();
+
+    let server = axum::Server::from_tcp(listener)?
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async move {
+            signal.await;
+            info!(?grace_period, "draining in-flight requests");
+            let _ = draining_tx.send(());
+        });
+
+    let deadline = async move {
+        match draining_rx.await {
+            Ok(()) => tokio::time::sleep(grace_period).await,
+            // the server stopped without being signaled, its error is returned below
+            Err(_) => std::future::pending().await,
+        }
+    };
+
+    tokio::select! {
+        result = server => {
+            result?;
+            info!("finished draining in-flight requests");
+        }
+        _ = deadline => {
+            warn!(?grace_period, "grace period elapsed, dropping remaining in-flight requests");
+        }
+    }
+    Ok(())
+}
+
+/// Resolves when the process receives `SIGTERM` or Ctrl+C.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install the Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install the SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
 /// Converts Timespec to nice readable relative time string
 fn duration_to_str(init: DateTime<Utc>) -> String {
     let now = Utc::now();
@@ -602,6 +888,16 @@ fn axum_cached_redirect(
     Ok(resp)
 }
 
+/// A `301 Moved Permanently` redirect, for URLs that always point to the same location.
+fn axum_permanent_redirect(
+    url: &str,
+    cache_policy: cache::CachePolicy,
+) -> Result<impl IntoResponse, Error> {
+    let mut resp = axum_cached_redirect(url, cache_policy)?.into_response();
+    *resp.status_mut() = StatusCode::MOVED_PERMANENTLY;
+    Ok(resp)
+}
+
 fn redirect_base(req: &Request) -> String {
     // Try to get the scheme from CloudFront first, and then from iron
     let scheme = req
@@ -772,6 +1068,81 @@ mod test {
     use serde_json::json;
     use test_case::test_case;
 
+    /// Router with a `/slow` route taking `duration`, the returned channel resolves once a
+    /// request reached it.
+    fn slow_app(duration: Duration) -> (AxumRouter, tokio::sync::oneshot::Receiver<()>) {
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+        let started_tx = Arc::new(std::sync::Mutex::new(Some(started_tx)));
+        let app = AxumRouter::new().route(
+            "/slow",
+            axum::routing::get(move || async move {
+                if let Some(started_tx) = started_tx.lock().unwrap().take() {
+                    let _ = started_tx.send(());
+                }
+                tokio::time::sleep(duration).await;
+                "done"
+            }),
+        );
+        (app, started_rx)
+    }
+
+    #[tokio::test]
+    async fn graceful_shutdown_completes_in_flight_requests() {
+        let (app, started_rx) = slow_app(Duration::from_millis(500));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/slow", listener.local_addr().unwrap());
+
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(serve_with_graceful_shutdown(
+            listener,
+            app,
+            async move {
+                shutdown_rx.await.ok();
+            },
+            Duration::from_secs(10),
+        ));
+
+        let request = tokio::spawn({
+            let url = url.clone();
+            async move { reqwest::get(url).await?.text().await }
+        });
+        started_rx.await.unwrap();
+        shutdown_tx.send(()).unwrap();
+
+        assert_eq!(request.await.unwrap().unwrap(), "done");
+        server.await.unwrap().unwrap();
+        // no new connections are accepted after the shutdown
+        assert!(reqwest::get(url).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn graceful_shutdown_grace_period() {
+        let (app, started_rx) = slow_app(Duration::from_secs(60));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/slow", listener.local_addr().unwrap());
+
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(serve_with_graceful_shutdown(
+            listener,
+            app,
+            async move {
+                shutdown_rx.await.ok();
+            },
+            Duration::from_millis(100),
+        ));
+
+        let request = tokio::spawn(reqwest::get(url));
+        started_rx.await.unwrap();
+        shutdown_tx.send(()).unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("server didn't stop after the grace period")
+            .unwrap()
+            .unwrap();
+        request.abort();
+    }
+
     fn release(version: &str, env: &TestEnvironment) -> i32 {
         env.fake_release()
             .name("foo")
@@ -1084,6 +1455,72 @@ mod test {
         })
     }
 
+    #[test]
+    fn release_channels() {
+        wrapper(|env| {
+            let db = env.db();
+            let web = env.frontend();
+
+            release("1.0.0", env);
+            release("2.0.0", env);
+            crate::db::channels::set_channel(
+                &mut db.conn(),
+                &env.config(),
+                &env.cdn(),
+                "foo",
+                "stable",
+                "1.0.0",
+            )?;
+            // points to a version that was never released
+            crate::db::channels::set_channel(
+                &mut db.conn(),
+                &env.config(),
+                &env.cdn(),
+                "foo",
+                "lts",
+                "0.9.0",
+            )?;
+
+            let matched = match_version(&mut db.conn(), "foo", Some("stable"))?.version;
+            assert!(matches!(matched, MatchSemver::Semver(_)));
+            assert_eq!(matched.into_parts().0, "1.0.0");
+            assert_redirect("/crate/foo/stable", "/crate/foo/1.0.0", web)?;
+
+            for unknown in ["/crate/foo/beta", "/crate/foo/lts"] {
+                assert_eq!(
+                    web.get(unknown).send()?.status(),
+                    StatusCode::NOT_FOUND,
+                    "{}",
+                    unknown
+                );
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test_case(&["0.1.0", "0.2.0"], "0.2.0", "0.2.0"; "stable only")]
+    #[test_case(&["0.1.0-alpha", "0.1.0-beta"], "0.1.0-beta", "0.1.0-beta"; "prerelease only")]
+    #[test_case(&["0.1.0", "0.2.0-alpha", "0.1.1"], "0.1.1", "0.2.0-alpha"; "mixed")]
+    fn latest_and_latest_prerelease(versions: &[&str], latest: &str, latest_prerelease: &str) {
+        wrapper(|env| {
+            for version in versions {
+                release(version, env);
+            }
+            let mut conn = env.db().conn();
+
+            let matched = match_version(&mut conn, "foo", Some("latest"))?.version;
+            assert!(matches!(matched, MatchSemver::Latest(_)));
+            assert_eq!(matched.into_parts().0, latest);
+
+            let matched = match_version(&mut conn, "foo", Some("latest-prerelease"))?.version;
+            assert!(matches!(matched, MatchSemver::Semver(_)));
+            assert_eq!(matched.into_parts().0, latest_prerelease);
+
+            Ok(())
+        })
+    }
+
     #[test]
     // vaguely related to https://github.com/rust-lang/docs.rs/issues/395
     fn metadata_has_no_effect() {
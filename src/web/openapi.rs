@@ -0,0 +1,1438 @@
+//! OpenAPI 3 description of the JSON endpoints, served at `/-/openapi.json`.
+//!
+//! The document is built from the typed structs below and serialized once on first use. Every
+//! route added with `json_api_route` has to be described here, which is checked by a test.
+
+use super::{
+    builds::STALE_BUILDS_LIMIT,
+    cache::CachePolicy,
+    crate_details::{
+        DEPENDENCIES_DEFAULT_DEPTH, DEPENDENCIES_MAX_DEPTH, DEPENDENCIES_MAX_NODES,
+        MAX_BATCH_DETAILS,
+    },
+    releases::{MAX_CHECKED_RELEASES, RELEASES_IN_DASHBOARD},
+    reports::REPORTS_LIMIT,
+    search_index::SEARCH_RESULTS,
+};
+use crate::docbuilder::BuildFailure;
+use axum::{
+    extract::Extension,
+    http::header::{ACCESS_CONTROL_ALLOW_ORIGIN, CONTENT_TYPE},
+    response::IntoResponse,
+};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const OPENAPI_VERSION: &str = "3.0.3";
+const BEARER_AUTH: &str = "bearerAuth";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct OpenApi {
+    openapi: String,
+    info: Info,
+    paths: BTreeMap<String, PathItem>,
+    components: Components,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Info {
+    title: String,
+    description: String,
+    version: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct PathItem {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    get: Option<Operation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    post: Option<Operation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    delete: Option<Operation>,
+}
+
+impl PathItem {
+    fn operations(&self) -> impl Iterator<Item = &Operation> {
+        self.get.iter().chain(&self.post).chain(&self.delete)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Operation {
+    operation_id: String,
+    summary: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    parameters: Vec<Parameter>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    request_body: Option<RequestBody>,
+    responses: BTreeMap<String, Response>,
+    /// Alternative security requirements, each mapping a security scheme to its scopes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    security: Vec<BTreeMap<String, Vec<String>>>,
+}
+
+impl Operation {
+    fn new(operation_id: &str, summary: &str) -> Self {
+        Self {
+            operation_id: operation_id.to_owned(),
+            summary: summary.to_owned(),
+            parameters: Vec::new(),
+            request_body: None,
+            responses: BTreeMap::new(),
+            security: Vec::new(),
+        }
+    }
+
+    fn parameter(mut self, parameter: Parameter) -> Self {
+        self.parameters.push(parameter);
+        self
+    }
+
+    fn request_body(self, schema: Schema) -> Self {
+        self.request_body_of_type(mime::APPLICATION_JSON.as_ref(), schema)
+    }
+
+    fn request_body_of_type(mut self, content_type: &str, schema: Schema) -> Self {
+        self.request_body = Some(RequestBody {
+            required: true,
+            content: BTreeMap::from([(content_type.to_owned(), MediaType { schema })]),
+        });
+        self
+    }
+
+    fn response(mut self, status: u16, description: &str, schema: Option<Schema>) -> Self {
+        self.responses.insert(
+            status.to_string(),
+            Response {
+                description: description.to_owned(),
+                content: schema.map(json_content).unwrap_or_default(),
+            },
+        );
+        self
+    }
+
+    fn error(self, status: u16, description: &str) -> Self {
+        self.response(status, description, Some(Schema::reference("Error")))
+    }
+
+    fn bearer_auth(mut self) -> Self {
+        self.security
+            .push(BTreeMap::from([(BEARER_AUTH.to_owned(), Vec::new())]));
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ParameterLocation {
+    Path,
+    Query,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Parameter {
+    name: String,
+    #[serde(rename = "in")]
+    location: ParameterLocation,
+    description: String,
+    required: bool,
+    schema: Schema,
+}
+
+impl Parameter {
+    fn path(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            location: ParameterLocation::Path,
+            description: description.to_owned(),
+            required: true,
+            schema: Schema::string(),
+        }
+    }
+
+    fn query(name: &str, description: &str, schema: Schema) -> Self {
+        Self {
+            name: name.to_owned(),
+            location: ParameterLocation::Query,
+            description: description.to_owned(),
+            required: false,
+            schema,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct RequestBody {
+    required: bool,
+    content: BTreeMap<String, MediaType>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Response {
+    description: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    content: BTreeMap<String, MediaType>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct MediaType {
+    schema: Schema,
+}
+
+fn json_content(schema: Schema) -> BTreeMap<String, MediaType> {
+    BTreeMap::from([(mime::APPLICATION_JSON.to_string(), MediaType { schema })])
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Components {
+    schemas: BTreeMap<String, Schema>,
+    security_schemes: BTreeMap<String, SecurityScheme>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SecurityScheme {
+    #[serde(rename = "type")]
+    kind: String,
+    scheme: String,
+    description: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SchemaType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    Array,
+    Object,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct Schema {
+    #[serde(rename = "$ref", default, skip_serializing_if = "Option::is_none")]
+    reference: Option<String>,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    kind: Option<SchemaType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    format: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    nullable: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    minimum: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    maximum: Option<u32>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    properties: BTreeMap<String, Schema>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    required: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    items: Option<Box<Schema>>,
+    #[serde(rename = "enum", default, skip_serializing_if = "Vec::is_empty")]
+    values: Vec<String>,
+}
+
+impl Schema {
+    fn of_type(kind: SchemaType, format: Option<&str>) -> Self {
+        Self {
+            kind: Some(kind),
+            format: format.map(str::to_owned),
+            ..Default::default()
+        }
+    }
+
+    fn string() -> Self {
+        Self::of_type(SchemaType::String, None)
+    }
+
+    /// A string that is one of the given values.
+    fn enumeration<'a>(values: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            values: values.into_iter().map(str::to_owned).collect(),
+            ..Self::string()
+        }
+    }
+
+    fn date_time() -> Self {
+        Self::of_type(SchemaType::String, Some("date-time"))
+    }
+
+    fn binary() -> Self {
+        Self::of_type(SchemaType::String, Some("binary"))
+    }
+
+    fn int32() -> Self {
+        Self::of_type(SchemaType::Integer, Some("int32"))
+    }
+
+    fn int64() -> Self {
+        Self::of_type(SchemaType::Integer, Some("int64"))
+    }
+
+    fn float() -> Self {
+        Self::of_type(SchemaType::Number, Some("float"))
+    }
+
+    fn boolean() -> Self {
+        Self::of_type(SchemaType::Boolean, None)
+    }
+
+    fn array(items: Schema) -> Self {
+        Self {
+            items: Some(Box::new(items)),
+            ..Self::of_type(SchemaType::Array, None)
+        }
+    }
+
+    /// An object with all of the given properties, use [`Schema::nullable`] for optional values.
+    fn object(properties: Vec<(&str, Schema)>) -> Self {
+        Self {
+            required: properties
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            properties: properties
+                .into_iter()
+                .map(|(name, schema)| (name.to_owned(), schema))
+                .collect(),
+            ..Self::of_type(SchemaType::Object, None)
+        }
+    }
+
+    fn reference(name: &str) -> Self {
+        Self {
+            reference: Some(format!("#/components/schemas/{}", name)),
+            ..Default::default()
+        }
+    }
+
+    fn nullable(self) -> Self {
+        Self {
+            nullable: true,
+            ..self
+        }
+    }
+
+    fn describe(self, description: &str) -> Self {
+        Self {
+            description: Some(description.to_owned()),
+            ..self
+        }
+    }
+
+    fn range(self, minimum: u32, maximum: u32) -> Self {
+        Self {
+            minimum: Some(minimum),
+            maximum: Some(maximum),
+            ..self
+        }
+    }
+}
+
+/// The categories of [`BuildFailure`], `null` for successful builds.
+fn failure_category() -> Schema {
+    Schema::enumeration(BuildFailure::ALL.iter().map(BuildFailure::as_str)).nullable()
+}
+
+fn schemas() -> BTreeMap<String, Schema> {
+    BTreeMap::from([
+        (
+            "Error".to_owned(),
+            Schema::object(vec![(
+                "error",
+                Schema::object(vec![
+                    ("code", Schema::string()),
+                    ("message", Schema::string()),
+                ]),
+            )]),
+        ),
+        (
+            "Build".to_owned(),
+            Schema::object(vec![
+                ("id", Schema::int32()),
+                ("rustc_version", Schema::string()),
+                ("docsrs_version", Schema::string()),
+                ("build_status", Schema::boolean()),
+                ("build_time", Schema::date_time()),
+                (
+                    "failure_category",
+                    failure_category().describe(
+                        "Likely cause of a failed build, `docs-too-large` when the documentation \
+                         exceeded the size limit and was rejected",
+                    ),
+                ),
+            ]),
+        ),
+        (
+            "BuildTarget".to_owned(),
+            Schema::object(vec![
+                ("target", Schema::string()),
+                ("successful", Schema::boolean()),
+                ("default", Schema::boolean()),
+            ]),
+        ),
+        (
+            "CrateVersion".to_owned(),
+            Schema::object(vec![
+                ("version", Schema::string()),
+                ("prerelease", Schema::boolean()),
+                ("yanked", Schema::boolean()),
+                (
+                    "has_docs",
+                    Schema::boolean().describe("Whether the documentation was built"),
+                ),
+            ]),
+        ),
+        (
+            "Coverage".to_owned(),
+            Schema::object(vec![
+                ("total_items", Schema::int32().nullable()),
+                ("documented_items", Schema::int32().nullable()),
+                ("total_items_needing_examples", Schema::int32().nullable()),
+                ("items_with_examples", Schema::int32().nullable()),
+                (
+                    "percentage",
+                    Schema::float()
+                        .nullable()
+                        .describe("Percentage of documented items"),
+                ),
+            ])
+            .describe("All fields are `null` for releases built before the coverage was recorded"),
+        ),
+        (
+            "Asset".to_owned(),
+            Schema::object(vec![
+                ("url", Schema::string()),
+                (
+                    "kind",
+                    Schema::string().describe("`stylesheet`, `script`, `font` or `other`"),
+                ),
+            ]),
+        ),
+        (
+            "Sizes".to_owned(),
+            Schema::object(vec![
+                (
+                    "documentation",
+                    Schema::int64()
+                        .nullable()
+                        .describe("Bytes of the HTML pages generated by rustdoc for all targets"),
+                ),
+                (
+                    "sources",
+                    Schema::int64()
+                        .nullable()
+                        .describe("Bytes of the files in the source archive"),
+                ),
+                (
+                    "search_index",
+                    Schema::int64()
+                        .nullable()
+                        .describe("Bytes of the search indexes generated by rustdoc"),
+                ),
+            ])
+            .describe("All fields are `null` for releases built before the sizes were recorded"),
+        ),
+        (
+            "Feature".to_owned(),
+            Schema::object(vec![
+                ("name", Schema::string()),
+                (
+                    "enables",
+                    Schema::array(Schema::string())
+                        .describe("Features and dependencies enabled by the feature"),
+                ),
+                (
+                    "default",
+                    Schema::boolean()
+                        .describe("Whether `default` enables the feature, directly or indirectly"),
+                ),
+                (
+                    "implicit",
+                    Schema::boolean()
+                        .describe("Whether this is the feature of an optional dependency"),
+                ),
+            ]),
+        ),
+        (
+            "DependencyEdge".to_owned(),
+            Schema::object(vec![
+                ("name", Schema::string()),
+                ("req", Schema::string()),
+                ("kind", Schema::string()),
+                (
+                    "version",
+                    Schema::string()
+                        .nullable()
+                        .describe("Newest release on docs.rs matching `req`"),
+                ),
+            ]),
+        ),
+        (
+            "DependencyNode".to_owned(),
+            Schema::object(vec![
+                ("name", Schema::string()),
+                ("version", Schema::string()),
+                (
+                    "depth",
+                    Schema::int32().describe("Distance from the requested release"),
+                ),
+                (
+                    "dependencies",
+                    Schema::array(Schema::reference("DependencyEdge"))
+                        .nullable()
+                        .describe("`null` for nodes at the maximum depth"),
+                ),
+            ]),
+        ),
+        (
+            "DependencyGraph".to_owned(),
+            Schema::object(vec![
+                ("name", Schema::string()),
+                ("version", Schema::string()),
+                ("depth", Schema::int32()),
+                (
+                    "truncated",
+                    Schema::boolean().describe(&format!(
+                        "Whether releases were left out of `nodes` after {} were included",
+                        DEPENDENCIES_MAX_NODES
+                    )),
+                ),
+                (
+                    "nodes",
+                    Schema::array(Schema::reference("DependencyNode")).describe(
+                        "Every release in the graph once, breadth-first from the requested one",
+                    ),
+                ),
+            ]),
+        ),
+        (
+            "QueueEntry".to_owned(),
+            Schema::object(vec![
+                ("name", Schema::string()),
+                ("version", Schema::string()),
+                ("priority", Schema::int32()),
+                ("registry", Schema::string().nullable()),
+                (
+                    "estimated_wait",
+                    Schema::int64()
+                        .nullable()
+                        .describe("Estimated seconds until the build is finished"),
+                ),
+            ]),
+        ),
+        (
+            "BuildQueue".to_owned(),
+            Schema::object(vec![
+                ("queue", Schema::array(Schema::reference("QueueEntry"))),
+                (
+                    "average_build_duration",
+                    Schema::int64()
+                        .nullable()
+                        .describe("Average seconds a build took recently"),
+                ),
+                ("active_builders", Schema::int64()),
+            ]),
+        ),
+        (
+            "Release".to_owned(),
+            Schema::object(vec![
+                ("name", Schema::string()),
+                ("version", Schema::string()),
+                ("description", Schema::string().nullable()),
+                ("target_name", Schema::string().nullable()),
+                ("rustdoc_status", Schema::boolean()),
+                ("build_time", Schema::date_time()),
+                ("stars", Schema::int32()),
+                (
+                    "failure_category",
+                    failure_category().describe("Likely cause of a failed build"),
+                ),
+            ]),
+        ),
+        (
+            "Dashboard".to_owned(),
+            Schema::object(vec![
+                (
+                    "recent_releases",
+                    Schema::array(Schema::reference("Release")),
+                ),
+                (
+                    "recent_failures",
+                    Schema::array(Schema::reference("Release")),
+                ),
+                (
+                    "most_starred",
+                    Schema::array(Schema::reference("Release"))
+                        .describe("Latest releases of the crates with the most stars"),
+                ),
+                (
+                    "failures_by_stars",
+                    Schema::array(Schema::reference("Release"))
+                        .describe("Failed latest releases of the crates with the most stars"),
+                ),
+                (
+                    "queue_length",
+                    Schema::int64().describe("Releases waiting in the build queue or being built"),
+                ),
+            ]),
+        ),
+        (
+            "CheckedRelease".to_owned(),
+            Schema::object(vec![
+                ("crate", Schema::string()),
+                (
+                    "requested_version",
+                    Schema::string().describe("Version or semver requirement as requested"),
+                ),
+                (
+                    "version",
+                    Schema::string()
+                        .nullable()
+                        .describe("The matched version, `null` if the lookup failed"),
+                ),
+                (
+                    "build_status",
+                    Schema::boolean()
+                        .nullable()
+                        .describe("Whether the latest build succeeded, `null` if not built yet"),
+                ),
+                (
+                    "error",
+                    Schema::string()
+                        .nullable()
+                        .describe("Why the release couldn't be checked, e.g. an unknown crate"),
+                ),
+            ]),
+        ),
+        (
+            "BatchDetailsEntry".to_owned(),
+            Schema::object(vec![
+                ("name", Schema::string()),
+                (
+                    "version",
+                    Schema::string()
+                        .nullable()
+                        .describe("Version or semver requirement, defaults to `latest`"),
+                ),
+            ]),
+        ),
+        (
+            "CrateSummary".to_owned(),
+            Schema::object(vec![
+                ("name", Schema::string()),
+                ("version", Schema::string()),
+                ("description", Schema::string().nullable()),
+                ("release_time", Schema::date_time()),
+                ("build_status", Schema::boolean()),
+                ("rustdoc_status", Schema::boolean()),
+                ("yanked", Schema::boolean()),
+                ("target_name", Schema::string()),
+                ("license", Schema::string().nullable()),
+                ("repository_url", Schema::string().nullable()),
+                ("documentation_url", Schema::string().nullable()),
+            ]),
+        ),
+        (
+            "BatchDetails".to_owned(),
+            Schema::object(vec![
+                ("name", Schema::string()),
+                (
+                    "requested_version",
+                    Schema::string().describe("Version or semver requirement as requested"),
+                ),
+                (
+                    "details",
+                    Schema::reference("CrateSummary")
+                        .nullable()
+                        .describe("Details of the matched release, `null` if the lookup failed"),
+                ),
+                (
+                    "error",
+                    Schema::string()
+                        .nullable()
+                        .describe("Why the release couldn't be found"),
+                ),
+            ]),
+        ),
+        (
+            "AboutStats".to_owned(),
+            Schema::object(vec![
+                ("crates", Schema::int64()),
+                ("releases", Schema::int64()),
+                ("successful_builds", Schema::int64()),
+                (
+                    "storage_bytes",
+                    Schema::int64()
+                        .nullable()
+                        .describe("Approximate size of the stored files, if known"),
+                ),
+            ]),
+        ),
+        (
+            "QueuedRebuild".to_owned(),
+            Schema::object(vec![(
+                "position",
+                Schema::int64().describe("Position in the build queue, starting at 1"),
+            )]),
+        ),
+        (
+            "ShieldsEndpoint".to_owned(),
+            Schema::object(vec![
+                ("schemaVersion", Schema::int32()),
+                ("label", Schema::string()),
+                (
+                    "message",
+                    Schema::string().describe("`passing`, `failing` or `unknown`"),
+                ),
+                ("color", Schema::string()),
+            ]),
+        ),
+        (
+            "ReportParams".to_owned(),
+            Schema::object(vec![
+                (
+                    "reason",
+                    Schema::string().describe("What's broken, up to 1000 characters"),
+                ),
+                (
+                    "path",
+                    Schema::string()
+                        .nullable()
+                        .describe("The page with broken docs, up to 500 characters"),
+                ),
+            ]),
+        ),
+        (
+            "Item".to_owned(),
+            Schema::object(vec![
+                (
+                    "kind",
+                    Schema::string().describe("Kind of the item, like `struct` or `macro`"),
+                ),
+                (
+                    "path",
+                    Schema::string().describe("Path of the item, starting with the crate"),
+                ),
+                ("url", Schema::string()),
+                (
+                    "stability",
+                    Schema::string().describe("`stable`, `unstable` or `deprecated`"),
+                ),
+            ]),
+        ),
+        (
+            "StaleBuild".to_owned(),
+            Schema::object(vec![
+                ("name", Schema::string()),
+                ("version", Schema::string()),
+                ("rustc_version", Schema::string()),
+                ("build_time", Schema::date_time()),
+            ]),
+        ),
+        (
+            "StaleBuilds".to_owned(),
+            Schema::object(vec![
+                (
+                    "current_rustc_version",
+                    Schema::string()
+                        .nullable()
+                        .describe("The toolchain docs.rs is currently building with"),
+                ),
+                ("releases", Schema::array(Schema::reference("StaleBuild"))),
+            ]),
+        ),
+        (
+            "Report".to_owned(),
+            Schema::object(vec![
+                ("id", Schema::int32()),
+                ("name", Schema::string()),
+                ("version", Schema::string()),
+                ("reason", Schema::string()),
+                ("path", Schema::string().nullable()),
+                ("created_at", Schema::date_time()),
+            ]),
+        ),
+        (
+            "ShortLink".to_owned(),
+            Schema::object(vec![
+                ("token", Schema::string()),
+                ("url", Schema::string().describe("The short link")),
+                (
+                    "target",
+                    Schema::string().describe("Path of the page the link redirects to"),
+                ),
+                ("expires_at", Schema::date_time().nullable()),
+            ]),
+        ),
+        (
+            "Preview".to_owned(),
+            Schema::object(vec![
+                ("token", Schema::string()),
+                ("expires_at", Schema::date_time()),
+                (
+                    "url",
+                    Schema::string().describe("Where the preview is served"),
+                ),
+            ]),
+        ),
+        (
+            "WebhookParams".to_owned(),
+            Schema::object(vec![("url", Schema::string().describe("An https url"))]),
+        ),
+        (
+            "Webhook".to_owned(),
+            Schema::object(vec![
+                ("id", Schema::int32()),
+                ("url", Schema::string()),
+                (
+                    "secret",
+                    Schema::string()
+                        .describe("Key of the HMAC-SHA256 signatures, only returned once"),
+                ),
+            ]),
+        ),
+    ])
+}
+
+fn crate_name() -> Parameter {
+    Parameter::path("name", "Name of the crate")
+}
+
+fn crate_version() -> Parameter {
+    Parameter::path(
+        "version",
+        "Exact version, `latest` for the newest stable release or a semver requirement \
+         redirecting to the matching version, `latest-prerelease` also matches pre-releases",
+    )
+}
+
+/// A `GET` endpoint for a release, which redirects semver requirements to the matching version.
+fn release_endpoint(operation_id: &str, summary: &str, schema: Schema) -> Operation {
+    Operation::new(operation_id, summary)
+        .parameter(crate_name())
+        .parameter(crate_version())
+        .response(200, "Success", Some(schema))
+        .response(
+            302,
+            "Redirect to the version matching the requirement",
+            None,
+        )
+        .error(404, "Crate or version not found")
+}
+
+fn path<'a>(paths: &'a mut BTreeMap<String, PathItem>, path: &str) -> &'a mut PathItem {
+    paths.entry(path.to_owned()).or_default()
+}
+
+fn document() -> OpenApi {
+    let mut paths = BTreeMap::new();
+
+    path(&mut paths, "/crate/{name}/{version}/builds.json").get = Some(release_endpoint(
+        "getBuilds",
+        "Builds of a release, newest first",
+        Schema::array(Schema::reference("Build")),
+    ));
+    path(&mut paths, "/crate/{name}/{version}/targets.json").get = Some(release_endpoint(
+        "getTargets",
+        "Targets documentation was built for, starting with the default target",
+        Schema::array(Schema::reference("BuildTarget")),
+    ));
+    path(&mut paths, "/crate/{name}/{version}/coverage.json").get = Some(release_endpoint(
+        "getCoverage",
+        "Documentation coverage of a release",
+        Schema::reference("Coverage"),
+    ));
+    path(&mut paths, "/crate/{name}/{version}/sizes.json").get = Some(release_endpoint(
+        "getSizes",
+        "Sizes of the documentation, sources and search index of a release, before compression",
+        Schema::reference("Sizes"),
+    ));
+    path(&mut paths, "/crate/{name}/{version}/msrv.json").get = Some(release_endpoint(
+        "getMsrv",
+        "Minimum supported Rust version of a release, from `rust-version` in its manifest",
+        Schema::object(vec![
+            ("name", Schema::string()),
+            ("version", Schema::string()),
+            (
+                "rust_version",
+                Schema::string().nullable().describe(
+                    "`null` when the manifest doesn't specify it, or for releases built before \
+                     it was recorded",
+                ),
+            ),
+        ]),
+    ));
+    path(
+        &mut paths,
+        "/crate/{name}/{version}/builds/{id}/environment.json",
+    )
+    .get = Some(
+        Operation::new(
+            "getBuildEnvironment",
+            "Toolchain and configuration a build ran with",
+        )
+        .parameter(crate_name())
+        .parameter(Parameter::path("version", "Exact version"))
+        .parameter(Parameter::path("id", "Id of a build of this release"))
+        .response(
+            200,
+            "Success",
+            Some(
+                Schema::object(vec![
+                    ("id", Schema::int32()),
+                    ("rustc_version", Schema::string()),
+                    ("docsrs_version", Schema::string()),
+                    ("cargo_version", Schema::string().nullable()),
+                    ("target", Schema::string().nullable()),
+                    (
+                        "cfgs",
+                        Schema::array(Schema::string())
+                            .nullable()
+                            .describe("`--cfg` flags passed to rustc and rustdoc, like `docsrs`"),
+                    ),
+                    ("build_time", Schema::date_time()),
+                ])
+                .describe("Fields that weren't recorded for older builds are `null`"),
+            ),
+        )
+        .error(404, "Build not found for this release"),
+    );
+    path(&mut paths, "/crate/{name}/{version}/assets.json").get = Some(release_endpoint(
+        "getAssets",
+        "Rustdoc static files the documentation of a release loads",
+        Schema::object(vec![("assets", Schema::array(Schema::reference("Asset")))]),
+    ));
+    path(&mut paths, "/crate/{name}/{version}/features.json").get = Some(release_endpoint(
+        "getFeatures",
+        "Features of a release with the features they enable, sorted by name",
+        Schema::object(vec![(
+            "features",
+            Schema::array(Schema::reference("Feature")),
+        )]),
+    ));
+    path(&mut paths, "/crate/{name}/{version}/search").get = Some(
+        release_endpoint(
+            "searchItems",
+            "Items of a release whose name contains the query, best matches first",
+            Schema::array(Schema::object(vec![
+                (
+                    "kind",
+                    Schema::string().describe("Kind of the item, like `struct` or `method`"),
+                ),
+                (
+                    "path",
+                    Schema::string().describe("Path of the item, starting with the crate"),
+                ),
+            ])),
+        )
+        .parameter(Parameter::query(
+            "q",
+            &format!(
+                "Name or part of the name of the items, at most {} items are returned",
+                SEARCH_RESULTS
+            ),
+            Schema::string(),
+        ))
+        .error(400, "Missing query"),
+    );
+    path(&mut paths, "/crate/{name}/{version}/search-index.json").get = Some(release_endpoint(
+        "getSearchIndex",
+        "Search index rustdoc generated for the default target of a release",
+        Schema::of_type(SchemaType::Object, None)
+            .describe("The index as rustdoc stores it, its format changes between versions"),
+    ));
+    path(&mut paths, "/crate/{name}/{version}/all.json").get = Some(release_endpoint(
+        "getAllItems",
+        "Items of the default target of a release, as listed on its `all.html` page",
+        Schema::array(Schema::reference("Item")),
+    ));
+    path(&mut paths, "/crate/{name}/{version}/dependencies.json").get = Some(
+        release_endpoint(
+            "getDependencies",
+            "Dependencies of a release resolved to the newest matching releases on docs.rs",
+            Schema::reference("DependencyGraph"),
+        )
+        .parameter(Parameter::query(
+            "depth",
+            &format!(
+                "Depth of the dependency graph, defaults to {}",
+                DEPENDENCIES_DEFAULT_DEPTH
+            ),
+            Schema::int32().range(0, DEPENDENCIES_MAX_DEPTH),
+        )),
+    );
+    path(&mut paths, "/crate/{name}/versions.json").get = Some(
+        Operation::new(
+            "getVersions",
+            "Versions of a crate, newest first by semver precedence",
+        )
+        .parameter(crate_name())
+        .parameter(Parameter::query(
+            "prereleases",
+            "Include pre-releases, defaults to `true`",
+            Schema::boolean(),
+        ))
+        .response(
+            200,
+            "Success",
+            Some(Schema::array(Schema::reference("CrateVersion"))),
+        )
+        .error(404, "Crate not found"),
+    );
+    path(&mut paths, "/{name}/status.json").get = Some(
+        Operation::new(
+            "getStatus",
+            "Status of the latest build as shields.io endpoint badge",
+        )
+        .parameter(crate_name())
+        .parameter(Parameter::query(
+            "version",
+            "Version or semver requirement, defaults to `latest`",
+            Schema::string(),
+        ))
+        .parameter(Parameter::query(
+            "label",
+            "Label of the badge, defaults to `docs`",
+            Schema::string(),
+        ))
+        .response(200, "Success", Some(Schema::reference("ShieldsEndpoint"))),
+    );
+    path(&mut paths, "/releases/queue.json").get = Some(
+        Operation::new("getBuildQueue", "Crates waiting to be built").response(
+            200,
+            "Success",
+            Some(Schema::reference("BuildQueue")),
+        ),
+    );
+    path(&mut paths, "/releases/recent-failures.json").get = Some(
+        Operation::new(
+            "getRecentFailures",
+            "Recent failed builds with the likely cause of every failure",
+        )
+        .parameter(Parameter::query(
+            "page",
+            "Page of the list, starting at 1",
+            Schema::int64(),
+        ))
+        .response(
+            200,
+            "Success",
+            Some(Schema::array(Schema::reference("Release"))),
+        )
+        .error(400, "Invalid page"),
+    );
+    path(&mut paths, "/releases/dashboard.json").get = Some(
+        Operation::new(
+            "getDashboard",
+            "Recent releases and failures, the crates with the most stars and the queue length",
+        )
+        .parameter(Parameter::query(
+            "page",
+            "Page of every list, starting at 1",
+            Schema::int64(),
+        ))
+        .parameter(Parameter::query(
+            "limit",
+            &format!(
+                "Releases in every list, defaults to {}",
+                RELEASES_IN_DASHBOARD
+            ),
+            Schema::int64().range(1, RELEASES_IN_DASHBOARD as u32),
+        ))
+        .response(200, "Success", Some(Schema::reference("Dashboard")))
+        .error(400, "Invalid page or limit"),
+    );
+    path(&mut paths, "/releases/check").get = Some(
+        Operation::new(
+            "checkReleases",
+            "Doc build status of several releases, e.g. the dependencies of a project",
+        )
+        .parameter(Parameter::query(
+            "crates",
+            &format!(
+                "Comma separated `name@version` entries, at most {}. The version defaults to \
+                 `latest` and may be a semver requirement",
+                MAX_CHECKED_RELEASES
+            ),
+            Schema::string(),
+        ))
+        .response(
+            200,
+            "Success, with an `error` for every entry that wasn't found",
+            Some(Schema::array(Schema::reference("CheckedRelease"))),
+        )
+        .error(400, "No or too many entries"),
+    );
+    path(&mut paths, "/about/stats.json").get = Some(
+        Operation::new("getStats", "Number of hosted crates, releases and builds").response(
+            200,
+            "Success",
+            Some(Schema::reference("AboutStats")),
+        ),
+    );
+    path(&mut paths, "/crate/{name}/{version}/rebuild").post = Some(
+        Operation::new("rebuild", "Queue a rebuild of a release")
+            .parameter(crate_name())
+            .parameter(Parameter::path("version", "Exact version"))
+            .bearer_auth()
+            .response(
+                202,
+                "Rebuild queued",
+                Some(Schema::reference("QueuedRebuild")),
+            )
+            .error(401, "Missing or invalid rebuild token")
+            .error(404, "Version not found")
+            .error(409, "Release is already queued or being built")
+            .error(
+                429,
+                "Too many rebuilds queued with this token in the last hour",
+            ),
+    );
+    path(&mut paths, "/-/stale-builds.json").get = Some(
+        Operation::new(
+            "getStaleBuilds",
+            "Releases built with an older toolchain than the current one, oldest builds first",
+        )
+        .parameter(Parameter::query(
+            "limit",
+            &format!("Most releases returned, defaults to {}", STALE_BUILDS_LIMIT),
+            Schema::int64().range(1, STALE_BUILDS_LIMIT as u32),
+        ))
+        .bearer_auth()
+        .response(200, "Success", Some(Schema::reference("StaleBuilds")))
+        .error(400, "Invalid limit")
+        .error(401, "Missing or invalid rebuild token"),
+    );
+    path(&mut paths, "/-/admin/purge/{name}/{version}").post = Some(
+        Operation::new("purgeRelease", "Purge the pages of a release from the CDN")
+            .parameter(crate_name())
+            .parameter(Parameter::path("version", "Exact version"))
+            .bearer_auth()
+            .response(
+                200,
+                "Success",
+                Some(Schema::object(vec![(
+                    "paths",
+                    Schema::array(Schema::string())
+                        .describe("The invalidated path patterns, of all configured distributions"),
+                )])),
+            )
+            .error(401, "Missing or invalid purge token")
+            .error(404, "Version not found"),
+    );
+    path(&mut paths, "/-/details-batch").post = Some(
+        Operation::new(
+            "getCrateDetailsBatch",
+            "Details of several releases, e.g. for dashboards about many crates",
+        )
+        .request_body(
+            Schema::array(Schema::reference("BatchDetailsEntry"))
+                .describe(&format!("At most {} entries", MAX_BATCH_DETAILS)),
+        )
+        .response(
+            200,
+            "Success, with an `error` for every entry that wasn't found",
+            Some(Schema::array(Schema::reference("BatchDetails"))),
+        )
+        .error(400, "No or too many entries"),
+    );
+    path(&mut paths, "/crate/{name}/{version}/report").post = Some(
+        Operation::new(
+            "reportBrokenDocs",
+            "Report broken documentation of a release",
+        )
+        .parameter(crate_name())
+        .parameter(Parameter::path("version", "Exact version"))
+        .request_body(Schema::reference("ReportParams"))
+        .response(204, "Report stored", None)
+        .error(400, "Empty or too long reason or path")
+        .error(404, "Version not found")
+        .error(429, "Too many reports from this client, try again later"),
+    );
+    path(&mut paths, "/-/reports.json").get = Some(
+        Operation::new("getReports", "Most recent reports of broken docs")
+            .parameter(Parameter::query(
+                "crate",
+                "Only list the reports of this crate",
+                Schema::string(),
+            ))
+            .parameter(Parameter::query(
+                "limit",
+                &format!("Most reports returned, defaults to {}", REPORTS_LIMIT),
+                Schema::int64().range(1, REPORTS_LIMIT as u32),
+            ))
+            .bearer_auth()
+            .response(
+                200,
+                "Success",
+                Some(Schema::array(Schema::reference("Report"))),
+            )
+            .error(400, "Invalid limit")
+            .error(401, "Missing or invalid report token"),
+    );
+    path(&mut paths, "/-/short-links").post = Some(
+        Operation::new(
+            "createShortLink",
+            "Create a short link to a page on docs.rs",
+        )
+        .request_body(Schema::object(vec![
+            (
+                "url",
+                Schema::string().describe("Absolute url on docs.rs or path of the page"),
+            ),
+            (
+                "expires_at",
+                Schema::date_time()
+                    .nullable()
+                    .describe("When the link stops working, never by default"),
+            ),
+        ]))
+        .bearer_auth()
+        .response(
+            201,
+            "Short link created",
+            Some(Schema::reference("ShortLink")),
+        )
+        .error(400, "Url not on docs.rs or expiry in the past")
+        .error(401, "Missing or invalid short link token"),
+    );
+    path(&mut paths, "/-/previews").post = Some(
+        Operation::new(
+            "uploadPreview",
+            "Upload rustdoc output to preview it, until it expires",
+        )
+        .request_body_of_type(
+            "application/zip",
+            Schema::binary()
+                .describe("Zip file of the rustdoc output with stored or bzip2 compressed entries"),
+        )
+        .bearer_auth()
+        .response(201, "Preview uploaded", Some(Schema::reference("Preview")))
+        .error(400, "Empty or invalid zip file")
+        .error(401, "Missing or invalid preview token")
+        .error(413, "Zip file too large"),
+    );
+    path(&mut paths, "/-/openapi.json").get = Some(
+        Operation::new("getOpenApi", "This description of the JSON endpoints").response(
+            200,
+            "Success",
+            Some(Schema::of_type(SchemaType::Object, None).describe("An OpenAPI 3 document")),
+        ),
+    );
+    path(&mut paths, "/crate/{name}/webhooks").post = Some(
+        Operation::new(
+            "registerWebhook",
+            "Register a webhook notified about finished builds",
+        )
+        .parameter(crate_name())
+        .request_body(Schema::reference("WebhookParams"))
+        .bearer_auth()
+        .response(
+            201,
+            "Webhook registered",
+            Some(Schema::reference("Webhook")),
+        )
+        .error(400, "Invalid webhook url")
+        .error(401, "Missing or invalid crates.io token")
+        .error(403, "Token user isn't an owner of the crate")
+        .error(404, "Crate not found")
+        .error(409, "Webhook url is already registered"),
+    );
+    path(&mut paths, "/crate/{name}/webhooks").delete = Some(
+        Operation::new("unregisterWebhook", "Remove a webhook")
+            .parameter(crate_name())
+            .request_body(Schema::reference("WebhookParams"))
+            .bearer_auth()
+            .response(204, "Webhook removed", None)
+            .error(401, "Missing or invalid crates.io token")
+            .error(403, "Token user isn't an owner of the crate")
+            .error(404, "Crate or webhook not found"),
+    );
+
+    OpenApi {
+        openapi: OPENAPI_VERSION.to_owned(),
+        info: Info {
+            title: "docs.rs".to_owned(),
+            description: "JSON endpoints of docs.rs".to_owned(),
+            version: crate::BUILD_VERSION.to_owned(),
+        },
+        paths,
+        components: Components {
+            schemas: schemas(),
+            security_schemes: BTreeMap::from([(
+                BEARER_AUTH.to_owned(),
+                SecurityScheme {
+                    kind: "http".to_owned(),
+                    scheme: "bearer".to_owned(),
+                    description: "One of the configured tokens of the endpoint, or a crates.io \
+                                  API token of a crate owner for webhooks"
+                        .to_owned(),
+                },
+            )]),
+        },
+    }
+}
+
+static OPENAPI_JSON: Lazy<String> =
+    Lazy::new(|| serde_json::to_string(&document()).expect("openapi document is serializable"));
+
+pub(crate) async fn openapi_handler() -> impl IntoResponse {
+    (
+        Extension(CachePolicy::NoStoreMustRevalidate),
+        [
+            (CONTENT_TYPE, mime::APPLICATION_JSON.as_ref()),
+            (ACCESS_CONTROL_ALLOW_ORIGIN, "*"),
+        ],
+        OPENAPI_JSON.as_str(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{assert_cache_control, wrapper};
+    use crate::web::routes::axum_route_infos;
+    use std::collections::BTreeSet;
+
+    /// The path with every templated segment replaced, to compare axum's `:name` and `*path`
+    /// with OpenAPI's `{name}`.
+    fn path_pattern(path: &str) -> String {
+        path.split('/')
+            .map(|segment| {
+                if segment.starts_with(':')
+                    || segment.starts_with('*')
+                    || (segment.starts_with('{') && segment.ends_with('}'))
+                {
+                    "{}"
+                } else {
+                    segment
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    #[test]
+    fn every_json_api_route_is_documented() {
+        let documented: BTreeSet<_> = document()
+            .paths
+            .keys()
+            .map(|path| path_pattern(path))
+            .collect();
+        let routes = axum_route_infos();
+
+        for route in routes.iter().filter(|route| route.json_api) {
+            assert!(
+                documented.contains(&path_pattern(&route.path)),
+                "{} isn't described in the OpenAPI document",
+                route.path
+            );
+        }
+
+        let json_api_routes: BTreeSet<_> = routes
+            .iter()
+            .filter(|route| route.json_api)
+            .map(|route| path_pattern(&route.path))
+            .collect();
+        for path in &documented {
+            assert!(
+                json_api_routes.contains(path),
+                "{} is described but isn't a JSON API route",
+                path
+            );
+        }
+    }
+
+    fn references(schema: &Schema) -> Vec<&str> {
+        schema
+            .reference
+            .as_deref()
+            .into_iter()
+            .chain(schema.properties.values().flat_map(references))
+            .chain(schema.items.iter().flat_map(|items| references(items)))
+            .collect()
+    }
+
+    #[test]
+    fn serves_valid_openapi_document() {
+        wrapper(|env| {
+            let web = env.frontend();
+
+            let response = web.get("/-/openapi.json").send()?;
+            assert!(response.status().is_success());
+            assert_cache_control(&response, CachePolicy::NoStoreMustRevalidate, &env.config());
+            assert_eq!(response.headers()["content-type"], "application/json");
+            let document: OpenApi = response.json()?;
+
+            assert!(document.openapi.starts_with("3."));
+            assert!(!document.paths.is_empty());
+
+            let schemas = &document.components.schemas;
+            for schema in schemas.values() {
+                for reference in references(schema) {
+                    let name = reference.strip_prefix("#/components/schemas/").unwrap();
+                    assert!(schemas.contains_key(name), "unknown schema {}", reference);
+                }
+            }
+
+            let mut operation_ids = Vec::new();
+            for (path, item) in &document.paths {
+                let mut operations = item.operations().peekable();
+                assert!(operations.peek().is_some(), "{} has no operations", path);
+
+                for operation in operations {
+                    operation_ids.push(operation.operation_id.as_str());
+                    assert!(!operation.responses.is_empty());
+
+                    // every templated segment of the path is described as path parameter
+                    let templated: Vec<_> = path
+                        .split('/')
+                        .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+                        .collect();
+                    let path_parameters: Vec<_> = operation
+                        .parameters
+                        .iter()
+                        .filter(|param| param.location == ParameterLocation::Path)
+                        .map(|param| {
+                            assert!(param.required);
+                            param.name.as_str()
+                        })
+                        .collect();
+                    assert_eq!(templated, path_parameters, "{}", path);
+
+                    let bodies = operation.request_body.iter().map(|body| &body.content);
+                    let contents = operation
+                        .responses
+                        .values()
+                        .map(|response| &response.content)
+                        .chain(bodies);
+                    for media in contents.flat_map(BTreeMap::values) {
+                        for reference in references(&media.schema) {
+                            let name = reference.strip_prefix("#/components/schemas/").unwrap();
+                            assert!(schemas.contains_key(name), "unknown schema {}", reference);
+                        }
+                    }
+
+                    for requirement in &operation.security {
+                        for scheme in requirement.keys() {
+                            assert!(document.components.security_schemes.contains_key(scheme));
+                        }
+                    }
+                }
+            }
+
+            let total = operation_ids.len();
+            operation_ids.sort_unstable();
+            operation_ids.dedup();
+            assert_eq!(operation_ids.len(), total, "operation ids must be unique");
+
+            Ok(())
+        })
+    }
+}
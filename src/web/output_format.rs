@@ -0,0 +1,137 @@
+//! Selecting between the HTML and JSON representation of pages, see [`RequestedFormat`].
+
+use super::error::AxumNope;
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{
+        header::{ACCEPT, VARY},
+        request::Parts,
+        HeaderValue, Request,
+    },
+    middleware::Next,
+    response::Response as AxumResponse,
+};
+use serde::Deserialize;
+use url::form_urlencoded;
+
+/// Output format of the pages that can also be fetched as JSON, see `?format=`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum OutputFormat {
+    Html,
+    Json,
+}
+
+impl OutputFormat {
+    fn from_query_value(value: &str) -> Option<Self> {
+        match value {
+            "html" => Some(OutputFormat::Html),
+            "json" => Some(OutputFormat::Json),
+            _ => None,
+        }
+    }
+
+    /// Picks the format the client prefers according to its `Accept` header. JSON is only used
+    /// when it's explicitly ranked above HTML, browsers and wildcards get HTML.
+    fn from_accept_header(accept: &str) -> Self {
+        let mut html_quality = 0.0;
+        let mut json_quality = 0.0;
+
+        for entry in accept.split(',') {
+            let mut parts = entry.split(';').map(str::trim);
+            let media_type = parts.next().unwrap_or_default().to_ascii_lowercase();
+            let quality = parts
+                .filter_map(|param| param.strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            match media_type.as_str() {
+                "application/json" => json_quality = f32::max(json_quality, quality),
+                "text/html" | "text/*" | "*/*" => html_quality = f32::max(html_quality, quality),
+                _ => {}
+            }
+        }
+
+        if json_quality > html_quality {
+            OutputFormat::Json
+        } else {
+            OutputFormat::Html
+        }
+    }
+}
+
+/// The format a page should be rendered in.
+///
+/// The `?format=` query parameter takes precedence so responses can be inspected in a browser,
+/// otherwise the `Accept` header decides. Routes using this need [`vary_accept_middleware`] so
+/// both representations are cached separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RequestedFormat(pub(crate) OutputFormat);
+
+impl RequestedFormat {
+    pub(crate) fn from_parts(parts: &Parts) -> Result<Self, AxumNope> {
+        if let Some(query) = parts.uri.query() {
+            if let Some((_, value)) =
+                form_urlencoded::parse(query.as_bytes()).find(|(key, _)| key == "format")
+            {
+                return OutputFormat::from_query_value(&value)
+                    .map(RequestedFormat)
+                    .ok_or_else(|| {
+                        AxumNope::BadRequest(format!(
+                            "unknown format `{}`, use `html` or `json`",
+                            value
+                        ))
+                    });
+            }
+        }
+
+        Ok(RequestedFormat(
+            parts
+                .headers
+                .get(ACCEPT)
+                .and_then(|accept| accept.to_str().ok())
+                .map_or(OutputFormat::Html, OutputFormat::from_accept_header),
+        ))
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RequestedFormat
+where
+    S: Send + Sync,
+{
+    type Rejection = AxumNope;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Self::from_parts(parts)
+    }
+}
+
+/// Adds `Vary: Accept` to responses of routes using [`RequestedFormat`].
+pub(crate) async fn vary_accept_middleware<B>(request: Request<B>, next: Next<B>) -> AxumResponse {
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .append(VARY, HeaderValue::from_static("Accept"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OutputFormat;
+    use test_case::test_case;
+
+    #[test_case("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8", OutputFormat::Html; "browser")]
+    #[test_case("*/*", OutputFormat::Html; "wildcard")]
+    #[test_case("application/json", OutputFormat::Json; "json")]
+    #[test_case("application/json, */*;q=0.5", OutputFormat::Json; "json with fallback")]
+    #[test_case("text/html;q=0.5, application/json;q=0.9", OutputFormat::Json; "ranked json")]
+    #[test_case("application/json;q=0.5, text/html", OutputFormat::Html; "ranked html")]
+    #[test_case("application/json, text/html", OutputFormat::Html; "tie")]
+    #[test_case("APPLICATION/JSON", OutputFormat::Json; "uppercase")]
+    #[test_case("image/png", OutputFormat::Html; "unrelated")]
+    fn accept_header(accept: &str, expected: OutputFormat) {
+        assert_eq!(OutputFormat::from_accept_header(accept), expected);
+    }
+}
@@ -102,6 +102,9 @@ fn load_templates(conn: &mut Client) -> Result<Tera> {
     tera.register_filter("fab", IconType::Brand);
     tera.register_filter("highlight", Highlight);
 
+    // Custom testers
+    tera.register_tester("prerelease", prerelease);
+
     Ok(tera)
 }
 
@@ -193,6 +196,16 @@ fn dbg(value: &Value, _args: &HashMap<String, Value>) -> TeraResult<Value> {
     Ok(value.clone())
 }
 
+/// Whether the value is the version of a pre-release. Build metadata like in `1.0.0+build-1`
+/// can contain a `-` too, so the version is parsed.
+#[allow(clippy::unnecessary_wraps)]
+fn prerelease(value: Option<&Value>, _args: &[Value]) -> TeraResult<bool> {
+    Ok(value
+        .and_then(Value::as_str)
+        .and_then(|version| semver::Version::parse(version).ok())
+        .map_or(false, |version| !version.pre.is_empty()))
+}
+
 /// Dedent a string by removing all leading whitespace
 #[allow(clippy::unnecessary_wraps)]
 fn dedent(value: &Value, args: &HashMap<String, Value>) -> TeraResult<Value> {
@@ -338,6 +351,25 @@ impl tera::Filter for Highlight {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_prerelease() {
+        for (version, expected) in [
+            ("1.0.0", false),
+            ("1.0.0-alpha.1", true),
+            ("1.0.0+build-1", false),
+            ("1.0.0-rc.1+build-1", true),
+            ("not-a-version", false),
+        ] {
+            assert_eq!(
+                prerelease(Some(&Value::String(version.into())), &[]).unwrap(),
+                expected,
+                "{}",
+                version
+            );
+        }
+        assert!(!prerelease(None, &[]).unwrap());
+    }
+
     #[test]
     fn test_templates_are_valid() {
         crate::test::wrapper(|env| {
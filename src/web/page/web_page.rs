@@ -2,17 +2,24 @@ use super::TemplateData;
 use crate::{
     ctry,
     utils::spawn_blocking,
-    web::{cache::CachePolicy, csp::Csp, error::AxumNope},
+    web::{cache::CachePolicy, csp::Csp, error::AxumNope, locale, metrics::ServerTiming},
+    Config,
 };
 use anyhow::Error;
 use axum::{
-    body::{boxed, Body},
+    body::{boxed, Body, BoxBody, Bytes, StreamBody},
     http::Request as AxumRequest,
     middleware::Next,
     response::{IntoResponse, Response as AxumResponse},
 };
-use futures_util::future::{BoxFuture, FutureExt};
-use http::header::CONTENT_LENGTH;
+use futures_util::{
+    future::{self, BoxFuture, FutureExt},
+    stream::{self, StreamExt},
+};
+use http::{
+    header::{ACCEPT_LANGUAGE, CONTENT_LENGTH, VARY},
+    HeaderValue,
+};
 use iron::{
     headers::{ContentType, Link, LinkValue, RelationType},
     response::Response,
@@ -20,8 +27,9 @@ use iron::{
     IronResult, Request,
 };
 use serde::Serialize;
-use std::{borrow::Cow, sync::Arc};
+use std::{borrow::Cow, convert::Infallible, sync::Arc, time::Instant};
 use tera::Context;
+use tokio::sync::mpsc;
 
 /// When making using a custom status, use a closure that coerces to a `fn(&Self) -> Status`
 #[macro_export]
@@ -245,20 +253,70 @@ pub(crate) struct DelayedTemplateRender {
     pub cpu_intensive_rendering: bool,
 }
 
+/// adding this to the axum response extensions next to
+/// [`DelayedTemplateRender`] renders the translation of the page
+/// the client prefers, `<template>.<locale>.html`, instead of the
+/// english template. See [`locale::negotiate`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Translated(pub(crate) &'static str);
+
+/// Placed by templates where the content of [`StreamedContent`] goes, available in the context
+/// as `streamed_content_marker`.
+const STREAMED_CONTENT_MARKER: &str = "<!-- streamed content -->";
+
+/// adding this to the axum response extensions next to
+/// [`DelayedTemplateRender`] sends the rendered page up to the
+/// `streamed_content_marker` right away, followed by the chunks
+/// from the receiver as they arrive and the rest of the page.
+pub(crate) struct StreamedContent(pub(crate) mpsc::Receiver<String>);
+
+/// The body of a page with [`StreamedContent`], or `None` when the page doesn't contain the
+/// marker.
+fn streamed_body(rendered: &str, chunks: mpsc::Receiver<String>) -> Option<BoxBody> {
+    let (before, after) = rendered.split_once(STREAMED_CONTENT_MARKER)?;
+    let (before, after) = (before.to_owned(), after.to_owned());
+
+    let chunks = stream::unfold(chunks, |mut chunks| async move {
+        chunks.recv().await.map(|chunk| (chunk, chunks))
+    });
+    let body = stream::once(future::ready(before))
+        .chain(chunks)
+        .chain(stream::once(future::ready(after)))
+        .map(|part| Ok::<_, Infallible>(Bytes::from(part)));
+
+    Some(boxed(StreamBody::new(body)))
+}
+
 fn render_response(
     mut response: AxumResponse,
     templates: Arc<TemplateData>,
     csp_nonce: String,
+    accept_language: Option<String>,
 ) -> BoxFuture<'static, AxumResponse> {
     async move {
         if let Some(render) = response.extensions_mut().remove::<DelayedTemplateRender>() {
             let DelayedTemplateRender {
-                template,
+                mut template,
                 mut context,
                 cpu_intensive_rendering,
             } = render;
             context.insert("csp_nonce", &csp_nonce);
 
+            let streamed_content = response.extensions_mut().remove::<StreamedContent>();
+            if streamed_content.is_some() {
+                context.insert("streamed_content_marker", STREAMED_CONTENT_MARKER);
+            }
+
+            if let Some(Translated(name)) = response.extensions_mut().remove::<Translated>() {
+                if let Some(locale) = accept_language.as_deref().and_then(locale::negotiate) {
+                    template = format!("{}.{}.html", name, locale);
+                    context.insert("locale", locale);
+                }
+                response
+                    .headers_mut()
+                    .append(VARY, HeaderValue::from_static("Accept-Language"));
+            }
+
             let rendered = if cpu_intensive_rendering {
                 spawn_blocking({
                     let templates = templates.clone();
@@ -283,11 +341,19 @@ fn render_response(
                             AxumNope::InternalError(err).into_response(),
                             templates,
                             csp_nonce,
+                            accept_language,
                         )
                         .await;
                     }
                 }
             };
+            if let Some(StreamedContent(chunks)) = streamed_content {
+                if let Some(body) = streamed_body(&rendered, chunks) {
+                    *response.body_mut() = body;
+                    return response;
+                }
+            }
+
             let content_length = rendered.len();
             *response.body_mut() = boxed(Body::from(rendered));
             response
@@ -318,7 +384,63 @@ pub(crate) async fn render_templates_middleware<B>(
         .nonce()
         .to_owned();
 
+    let server_timing_enabled = req
+        .extensions()
+        .get::<Arc<Config>>()
+        .map_or(false, |config| config.server_timing);
+
+    let accept_language = req
+        .headers()
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map(ToOwned::to_owned);
+
     let response = next.run(req).await;
 
-    render_response(response, templates, csp_nonce).await
+    let server_timing = response.extensions().get::<ServerTiming>().cloned();
+    let start = Instant::now();
+    let mut response = render_response(response, templates, csp_nonce, accept_language).await;
+
+    if let Some(mut server_timing) = server_timing.filter(|_| server_timing_enabled) {
+        server_timing.record("render", start.elapsed());
+        if let Ok(value) = HeaderValue::from_str(&server_timing.header_value()) {
+            response.headers_mut().insert("server-timing", value);
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{streamed_body, STREAMED_CONTENT_MARKER};
+    use hyper::body::HttpBody;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn streamed_body_sends_page_before_content() {
+        let (sender, receiver) = mpsc::channel(1);
+        let page = format!("<main>{}</main>", STREAMED_CONTENT_MARKER);
+        let mut body = streamed_body(&page, receiver).unwrap();
+
+        // nothing was produced yet, but the start of the page is already there
+        let first = body.data().await.unwrap().unwrap();
+        assert_eq!(first, "<main>");
+
+        sender.send("first chunk".into()).await.unwrap();
+        assert_eq!(body.data().await.unwrap().unwrap(), "first chunk");
+        sender.send("second chunk".into()).await.unwrap();
+        drop(sender);
+
+        let mut rest = Vec::new();
+        while let Some(data) = body.data().await {
+            rest.push(data.unwrap());
+        }
+        assert_eq!(rest, ["second chunk", "</main>"]);
+    }
+
+    #[test]
+    fn streamed_body_without_marker() {
+        let (_sender, receiver) = mpsc::channel(1);
+        assert!(streamed_body("<main></main>", receiver).is_none());
+    }
 }
@@ -0,0 +1,252 @@
+//! Endpoints to upload and serve documentation previews, see [`crate::previews`].
+//!
+//! Uploads are authenticated with one of the `DOCSRS_PREVIEW_TOKENS`, the previews are served to
+//! anyone knowing their token.
+
+use crate::{
+    db::Pool,
+    previews::{self, Preview},
+    storage::{PathNotFoundError, Storage},
+    utils::spawn_blocking,
+    web::{
+        auth::TokenName,
+        cache::CachePolicy,
+        error::{AxumNope, AxumResult, JsonAxumResult},
+        file::File,
+    },
+    Config,
+};
+use axum::{
+    body::Bytes,
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::instrument;
+
+/// Previews are never indexed by search engines
+const ROBOTS_HEADER: (&str, &str) = ("x-robots-tag", "noindex, nofollow");
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct UploadedPreview {
+    #[serde(flatten)]
+    preview: Preview,
+    url: String,
+}
+
+/// Uploads a preview, the body is a zip file of the rustdoc output with stored or bzip2
+/// compressed entries, like `cd target/doc && zip -r -Z bzip2 ../doc.zip .`.
+///
+/// Responds with `201 Created` and the URL of the preview. The upload was authenticated with one
+/// of the `DOCSRS_PREVIEW_TOKENS` before its body was read.
+pub(crate) async fn upload_preview_handler(
+    Extension(TokenName(created_by)): Extension<TokenName>,
+    Extension(config): Extension<Arc<Config>>,
+    Extension(pool): Extension<Pool>,
+    Extension(storage): Extension<Arc<Storage>>,
+    body: Bytes,
+) -> JsonAxumResult<impl IntoResponse> {
+    if body.is_empty() {
+        return Err(AxumNope::BadRequest("the preview can't be empty".into()).into());
+    }
+
+    // the request is cancelled after the `write_request_timeout`, the blocking task isn't
+    let deadline = Instant::now() + Duration::from_secs(config.write_request_timeout);
+    let preview = spawn_blocking({
+        let ttl = Duration::from_secs(config.preview_ttl);
+        move || {
+            let mut conn = pool.get()?;
+            previews::create(&mut conn, &storage, &body, &created_by, ttl, deadline).map_err(
+                |err| {
+                    if err.downcast_ref::<zip::result::ZipError>().is_some() {
+                        AxumNope::BadRequest(format!("invalid zip file: {}", err)).into()
+                    } else {
+                        err
+                    }
+                },
+            )
+        }
+    })
+    .await?;
+
+    let url = format!("{}/-/preview/{}/", config.public_base_url, preview.token);
+    Ok((
+        StatusCode::CREATED,
+        Extension(CachePolicy::NoCaching),
+        Json(UploadedPreview { preview, url }),
+    ))
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct PreviewHandlerParams {
+    token: String,
+    #[serde(default)]
+    path: String,
+}
+
+/// Serves the files of a preview like the documentation of a release, until it expires.
+///
+/// Previews are uploaded as they are and served from our origin, so unlike the documentation of
+/// releases they keep the CSP, which blocks their scripts and styles.
+#[instrument(skip(pool, storage))]
+pub(crate) async fn preview_handler(
+    Path(PreviewHandlerParams { token, path }): Path<PreviewHandlerParams>,
+    Extension(pool): Extension<Pool>,
+    Extension(storage): Extension<Arc<Storage>>,
+) -> AxumResult<impl IntoResponse> {
+    let mut path = path;
+    if path.is_empty() || path.ends_with('/') {
+        path.push_str("index.html");
+    }
+
+    let blob = spawn_blocking(move || {
+        if !previews::is_available(&mut *pool.get()?, &token)? {
+            return Err(AxumNope::ResourceNotFound.into());
+        }
+
+        match storage.fetch_preview_file(&token, &path) {
+            Ok(blob) => Ok(blob),
+            Err(err) if err.downcast_ref::<PathNotFoundError>().is_some() => {
+                Err(AxumNope::ResourceNotFound.into())
+            }
+            Err(err) => Err(err),
+        }
+    })
+    .await?;
+
+    // the preview is gone when it expires
+    Ok((
+        Extension(CachePolicy::NoCaching),
+        [ROBOTS_HEADER],
+        File(blob),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::previews::zip_file;
+    use crate::test::{assert_cache_control, wrapper};
+    use crate::web::cache::CachePolicy;
+    use reqwest::StatusCode;
+    use serde_json::Value;
+
+    #[test]
+    fn upload_and_serve_preview() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.preview_tokens = vec!["admin:secret".parse().unwrap()];
+            });
+            let web = env.frontend();
+            let zip = zip_file(&[
+                ("foo/index.html", &b"<html>foo docs</html>"[..]),
+                ("foo/struct.Foo.html", &b"<html>struct Foo</html>"[..]),
+                ("static.files/main.js", &b"// main"[..]),
+            ]);
+
+            let response = web.post("/-/previews").body(zip.clone()).send()?;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+            let response = web
+                .post("/-/previews")
+                .bearer_auth("secret")
+                .body(zip)
+                .send()?;
+            assert_eq!(response.status(), StatusCode::CREATED);
+            let preview: Value = response.json()?;
+            let token = preview["token"].as_str().unwrap();
+            assert_eq!(
+                preview["url"],
+                format!("https://docs.rs/-/preview/{}/", token)
+            );
+
+            let response = web.get(&format!("/-/preview/{}/foo/", token)).send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_cache_control(&response, CachePolicy::NoCaching, &env.config());
+            assert_eq!(response.headers()["x-robots-tag"], "noindex, nofollow");
+            assert!(response.headers()["content-security-policy"]
+                .to_str()?
+                .starts_with("default-src 'none'"));
+            assert_eq!(response.text()?, "<html>foo docs</html>");
+
+            let response = web
+                .get(&format!("/-/preview/{}/static.files/main.js", token))
+                .send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            for path in [
+                format!("/-/preview/{}/foo/struct.Bar.html", token),
+                "/-/preview/0123456789abcdef/foo/".to_string(),
+            ] {
+                assert_eq!(web.get(&path).send()?.status(), StatusCode::NOT_FOUND);
+            }
+
+            let response = web
+                .post("/-/previews")
+                .bearer_auth("secret")
+                .body("not a zip file")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn uploads_are_authenticated_before_reading_the_body() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.preview_tokens = vec!["admin:secret".parse().unwrap()];
+                config.max_preview_size = 10;
+            });
+            let web = env.frontend();
+            let body = vec![0; 100];
+
+            let response = web.post("/-/previews").body(body.clone()).send()?;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+            let response = web
+                .post("/-/previews")
+                .bearer_auth("secret")
+                .body(body)
+                .send()?;
+            assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn expired_preview_is_not_found() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.preview_tokens = vec!["admin:secret".parse().unwrap()];
+            });
+            let web = env.frontend();
+
+            let preview: Value = web
+                .post("/-/previews")
+                .bearer_auth("secret")
+                .body(zip_file(&[("foo/index.html", &b"<html>foo</html>"[..])]))
+                .send()?
+                .json()?;
+            let token = preview["token"].as_str().unwrap();
+            let path = format!("/-/preview/{}/foo/index.html", token);
+            assert_eq!(web.get(&path).send()?.status(), StatusCode::OK);
+
+            env.db().conn().execute(
+                "UPDATE doc_previews SET expires_at = NOW() - INTERVAL '1 second'",
+                &[],
+            )?;
+            // expired previews are gone before the daemon deletes them
+            assert_eq!(web.get(&path).send()?.status(), StatusCode::NOT_FOUND);
+
+            crate::previews::delete_expired(&mut env.db().conn(), &env.storage(), &env.config())?;
+            assert_eq!(web.get(&path).send()?.status(), StatusCode::NOT_FOUND);
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,188 @@
+//! Purges the CDN caches of a release, for when a rebuild or a manual fix left stale pages behind.
+//!
+//! Purges are authenticated with one of the `DOCSRS_CDN_PURGE_TOKENS` and recorded in
+//! `cdn_purges`, together with the error if the CDN rejected them.
+
+use crate::{
+    cdn::{self, CdnBackend},
+    db::Pool,
+    utils::spawn_blocking,
+    web::{
+        auth::authenticate_named_token,
+        cache::CachePolicy,
+        error::{AxumNope, JsonAxumResult},
+    },
+    Config,
+};
+use axum::{
+    extract::{Extension, Path},
+    http::HeaderMap,
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use tracing::info;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Purge {
+    /// The invalidated path patterns, of all configured distributions
+    paths: Vec<String>,
+}
+
+/// Purges the pages of a release from the CDN.
+pub(crate) async fn purge_handler(
+    Path((name, version)): Path<(String, String)>,
+    headers: HeaderMap,
+    Extension(config): Extension<Arc<Config>>,
+    Extension(cdn): Extension<Arc<CdnBackend>>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let requested_by = authenticate_named_token(&headers, &config.cdn_purge_tokens)?;
+
+    let paths = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        // like rebuilds, only exact versions can be purged
+        let release = conn.query_opt(
+            "SELECT 1
+             FROM releases
+             INNER JOIN crates ON releases.crate_id = crates.id
+             WHERE crates.name = $1 AND releases.version = $2",
+            &[&name, &version],
+        )?;
+        if release.is_none() {
+            return Err(AxumNope::VersionNotFound.into());
+        }
+
+        let result = cdn::invalidate_release(&config, &*cdn, &name, &version);
+        let (paths, error) = match &result {
+            Ok(paths) => (paths.clone(), None),
+            Err(err) => (Vec::new(), Some(format!("{:?}", err))),
+        };
+        conn.execute(
+            "INSERT INTO cdn_purges (name, version, paths, requested_by, error)
+             VALUES ($1, $2, $3, $4, $5)",
+            &[&name, &version, &paths, &requested_by, &error],
+        )?;
+        let paths = result?;
+        info!(%name, %version, %requested_by, ?paths, "purged CDN");
+        Ok(paths)
+    })
+    .await?;
+
+    Ok((Extension(CachePolicy::NoCaching), Json(Purge { paths })))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cdn::CdnBackend;
+    use crate::test::{assert_cache_control, wrapper};
+    use crate::web::cache::CachePolicy;
+    use reqwest::StatusCode;
+    use serde_json::json;
+
+    #[test]
+    fn purge_release() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.cdn_purge_tokens = vec!["admin:secret".parse().unwrap()];
+                config.cloudfront_distribution_id_web = Some("distribution_id_web".into());
+                config.cloudfront_distribution_id_static = Some("distribution_id_static".into());
+            });
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let web = env.frontend();
+
+            let response = web
+                .post("/-/admin/purge/foo/0.1.0")
+                .bearer_auth("secret")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_cache_control(&response, CachePolicy::NoCaching, &env.config());
+            assert_eq!(
+                response.json::<serde_json::Value>()?,
+                json!({
+                    "paths": [
+                        "/foo/0.1.0/*",
+                        "/foo/0.1.0",
+                        "/foo/latest/*",
+                        "/foo/latest",
+                        "/crate/foo/0.1.0/*",
+                        "/crate/foo/0.1.0",
+                        "/crate/foo/latest/*",
+                        "/crate/foo/latest",
+                        "/rustdoc/foo/0.1.0/*",
+                    ],
+                })
+            );
+
+            if let CdnBackend::Dummy(ref invalidation_requests) = *env.cdn() {
+                let ir = invalidation_requests.lock().unwrap();
+                assert_eq!(
+                    *ir,
+                    [
+                        ("distribution_id_web".into(), "/foo/0.1.0/*".into()),
+                        ("distribution_id_web".into(), "/foo/0.1.0".into()),
+                        ("distribution_id_web".into(), "/foo/latest/*".into()),
+                        ("distribution_id_web".into(), "/foo/latest".into()),
+                        ("distribution_id_web".into(), "/crate/foo/0.1.0/*".into()),
+                        ("distribution_id_web".into(), "/crate/foo/0.1.0".into()),
+                        ("distribution_id_web".into(), "/crate/foo/latest/*".into()),
+                        ("distribution_id_web".into(), "/crate/foo/latest".into()),
+                        (
+                            "distribution_id_static".into(),
+                            "/rustdoc/foo/0.1.0/*".into()
+                        ),
+                    ]
+                );
+            } else {
+                panic!("tests have to use the dummy CDN");
+            }
+
+            let row = env.db().conn().query_one(
+                "SELECT name, version, array_length(paths, 1), requested_by, error
+                 FROM cdn_purges",
+                &[],
+            )?;
+            assert_eq!(row.get::<_, String>(0), "foo");
+            assert_eq!(row.get::<_, String>(1), "0.1.0");
+            assert_eq!(row.get::<_, i32>(2), 9);
+            assert_eq!(row.get::<_, String>(3), "admin");
+            assert_eq!(row.get::<_, Option<String>>(4), None);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn purge_requires_token_and_release() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.cdn_purge_tokens = vec!["admin:secret".parse().unwrap()];
+            });
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let web = env.frontend();
+
+            let response = web.post("/-/admin/purge/foo/0.1.0").send()?;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            let response = web
+                .post("/-/admin/purge/foo/0.1.0")
+                .bearer_auth("wrong")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+            let response = web
+                .post("/-/admin/purge/foo/0.2.0")
+                .bearer_auth("secret")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            let purges: i64 = env
+                .db()
+                .conn()
+                .query_one("SELECT COUNT(*) FROM cdn_purges", &[])?
+                .get(0);
+            assert_eq!(purges, 0);
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,64 @@
+//! In-memory rate limiting of the endpoints that can be abused to cause load.
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+pub(crate) const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Counts the requests of each key, e.g. a client IP or the name of a token, in fixed windows of
+/// [`RATE_LIMIT_WINDOW`].
+///
+/// The counts are only kept in memory, they start over when the server restarts.
+#[derive(Debug)]
+pub(crate) struct RateLimiter<K> {
+    windows: Mutex<HashMap<K, (Instant, u32)>>,
+}
+
+impl<K> Default for RateLimiter<K> {
+    fn default() -> Self {
+        Self {
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+    /// Records a request of `key`, returns whether it's within the `limit`.
+    pub(crate) fn check(&self, key: K, limit: u32, now: Instant) -> bool {
+        let mut windows = self.windows.lock().unwrap();
+        // forget about keys whose window is over, so the map doesn't grow forever
+        windows.retain(|_, (start, _)| now.duration_since(*start) < RATE_LIMIT_WINDOW);
+
+        let (_, count) = windows.entry(key).or_insert((now, 0));
+        if *count >= limit {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RateLimiter, RATE_LIMIT_WINDOW};
+    use std::{net::IpAddr, time::Instant};
+
+    #[test]
+    fn rate_limiter() {
+        let limiter = RateLimiter::<IpAddr>::default();
+        let now = Instant::now();
+        let (a, b) = ("192.0.2.1".parse().unwrap(), "192.0.2.2".parse().unwrap());
+
+        assert!(limiter.check(a, 2, now));
+        assert!(limiter.check(a, 2, now));
+        assert!(!limiter.check(a, 2, now));
+        assert!(limiter.check(b, 2, now));
+
+        assert!(limiter.check(a, 2, now + RATE_LIMIT_WINDOW));
+        assert_eq!(limiter.windows.lock().unwrap().len(), 1);
+    }
+}
@@ -4,12 +4,16 @@ use crate::{
     build_queue::QueuedCrate,
     cdn::{self, CrateInvalidation},
     db::Pool,
+    docbuilder::BuildFailure,
     impl_axum_webpage,
     utils::{report_error, spawn_blocking},
     web::{
         axum_parse_uri_with_params, axum_redirect,
-        error::{AxumNope, AxumResult},
-        match_version_axum,
+        base_url::BaseUrl,
+        cache::CachePolicy,
+        error::{AxumNope, AxumResult, JsonAxumNope, JsonAxumResult, Nope},
+        match_version, match_version_axum,
+        output_format::{OutputFormat, RequestedFormat},
     },
     BuildQueue, Config, Metrics,
 };
@@ -17,13 +21,15 @@ use anyhow::{anyhow, Context as _, Result};
 use axum::{
     extract::{Extension, Path, Query},
     response::{IntoResponse, Response as AxumResponse},
+    Json,
 };
 use chrono::{DateTime, NaiveDate, Utc};
 use postgres::Client;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
 use std::str;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tracing::{debug, warn};
 use url::form_urlencoded;
 
@@ -33,6 +39,20 @@ const RELEASES_IN_HOME: i64 = 15;
 const RELEASES_IN_RELEASES: i64 = 30;
 /// Releases in recent releases feed
 const RELEASES_IN_FEED: i64 = 150;
+/// Days after their first release crates are listed in `/releases/new-crates`
+const NEW_CRATES_DAYS: i32 = 30;
+/// Crate names returned by the search suggestions
+const SEARCH_SUGGESTIONS: i64 = 10;
+/// Maximum number of crates in a single watch list
+const WATCH_MAX_CRATES: usize = 50;
+/// Crate names in a page of `/crates`
+const CRATES_IN_LIST: i64 = 100;
+/// How long the number of documented crates is reused, see [`DocumentedCratesCount`]
+const DOCUMENTED_CRATES_MAX_AGE: Duration = Duration::from_secs(10 * 60);
+/// Maximum number of releases in each list of `/releases/dashboard.json`
+pub(crate) const RELEASES_IN_DASHBOARD: i64 = 30;
+/// Maximum number of entries checked by a single request to `/releases/check`
+pub(crate) const MAX_CHECKED_RELEASES: usize = 100;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Release {
@@ -43,6 +63,8 @@ pub struct Release {
     rustdoc_status: bool,
     pub(crate) build_time: DateTime<Utc>,
     stars: i32,
+    /// The likely cause of a failed build, `None` for successful builds
+    failure_category: Option<BuildFailure>,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -83,7 +105,9 @@ pub(crate) fn get_releases(
             releases.target_name,
             releases.rustdoc_status,
             builds.build_time,
-            repositories.stars
+            repositories.stars,
+            builds.failure_category,
+            builds.build_status
         FROM crates
         {1}
         INNER JOIN builds ON releases.id = builds.rid
@@ -113,6 +137,7 @@ pub(crate) fn get_releases(
             rustdoc_status: row.get(4),
             build_time: row.get(5),
             stars: row.get::<_, Option<i32>>(6).unwrap_or(0),
+            failure_category: BuildFailure::from_column(row.get(7), row.get(8)),
         })
         .collect())
 }
@@ -124,10 +149,124 @@ struct SearchResult {
     pub next_page: Option<String>,
 }
 
+/// `owner:` and `keyword:` qualifiers in a search query, narrowing the results
+/// to crates having all of the given owners and keywords.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct SearchQualifiers {
+    /// lowercase owner logins
+    owners: Vec<String>,
+    /// keyword slugs
+    keywords: Vec<String>,
+}
+
+impl SearchQualifiers {
+    /// Splits the qualifiers off a search query, returns them with the remaining free-text
+    /// search term.
+    fn parse(query: &str) -> (String, Self) {
+        let mut qualifiers = Self::default();
+        let mut term = Vec::new();
+        for word in query.split_whitespace() {
+            match word.split_once(':') {
+                Some(("owner", owner)) if !owner.is_empty() => {
+                    qualifiers.owners.push(owner.to_lowercase())
+                }
+                Some(("keyword", keyword)) if !keyword.is_empty() => {
+                    qualifiers.keywords.push(slug::slugify(keyword))
+                }
+                _ => term.push(word),
+            }
+        }
+        (term.join(" "), qualifiers)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.owners.is_empty() && self.keywords.is_empty()
+    }
+
+    /// Formats the qualifiers back into a search query, following `term`.
+    fn to_query(&self, term: &str) -> String {
+        let owners = self.owners.iter().map(|owner| format!("owner:{}", owner));
+        let keywords = self
+            .keywords
+            .iter()
+            .map(|keyword| format!("keyword:{}", keyword));
+        Some(term.to_owned())
+            .filter(|term| !term.is_empty())
+            .into_iter()
+            .chain(owners)
+            .chain(keywords)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Get the latest releases of the crates with the given names, or of all crates when `names`
+/// is `None`, which match the qualifiers.
+///
+/// Without a `limit` all matching releases are returned.
+fn get_qualified_releases(
+    conn: &mut Client,
+    names: Option<&[String]>,
+    qualifiers: &SearchQualifiers,
+    limit: Option<i64>,
+) -> Result<Vec<Release>> {
+    Ok(conn
+        .query(
+            "SELECT DISTINCT ON (crates.name)
+                 crates.name,
+                 releases.version,
+                 releases.description,
+                 builds.build_time,
+                 releases.target_name,
+                 releases.rustdoc_status,
+                 repositories.stars
+
+             FROM crates
+             INNER JOIN releases ON crates.latest_version_id = releases.id
+             INNER JOIN builds ON releases.id = builds.rid
+             LEFT JOIN repositories ON releases.repository_id = repositories.id
+
+             WHERE
+                 ($1::TEXT[] IS NULL OR crates.name = ANY($1)) AND
+                 $2::TEXT[] <@ ARRAY(
+                     SELECT LOWER(owners.login)
+                     FROM owner_rels
+                     INNER JOIN owners ON owner_rels.oid = owners.id
+                     WHERE owner_rels.cid = crates.id
+                 ) AND
+                 $3::TEXT[] <@ ARRAY(
+                     SELECT keywords.slug::TEXT
+                     FROM keyword_rels
+                     INNER JOIN keywords ON keyword_rels.kid = keywords.id
+                     WHERE keyword_rels.rid = releases.id
+                 )
+             ORDER BY crates.name, builds.build_time DESC
+             LIMIT $4",
+            &[&names, &qualifiers.owners, &qualifiers.keywords, &limit],
+        )?
+        .into_iter()
+        .map(|row| Release {
+            name: row.get("name"),
+            version: row.get("version"),
+            description: row.get("description"),
+            build_time: row.get("build_time"),
+            target_name: row.get("target_name"),
+            rustdoc_status: row.get("rustdoc_status"),
+            stars: row.get::<_, Option<i32>>("stars").unwrap_or(0),
+            failure_category: None,
+        })
+        .collect())
+}
+
 /// Get the search results for a crate search query
 ///
 /// This delegates to the crates.io search API.
-async fn get_search_results(pool: Pool, query_params: &str) -> Result<SearchResult, anyhow::Error> {
+/// Qualifiers are applied to the returned crates using the owners and keywords docs.rs knows.
+async fn get_search_results(
+    pool: Pool,
+    query_params: &str,
+    qualifiers: SearchQualifiers,
+) -> Result<SearchResult, anyhow::Error> {
     #[derive(Deserialize)]
     struct CratesIoSearchResult {
         crates: Vec<CratesIoCrate>,
@@ -204,43 +343,12 @@ async fn get_search_results(pool: Pool, query_params: &str) -> Result<SearchResu
         let names = names.clone();
         move || {
             let mut conn = pool.get()?;
-            Ok(conn
-                .query(
-                    "SELECT
-                         crates.name,
-                         releases.version,
-                         releases.description,
-                         builds.build_time,
-                         releases.target_name,
-                         releases.rustdoc_status,
-                         repositories.stars
-
-                     FROM crates
-                     INNER JOIN releases ON crates.latest_version_id = releases.id
-                     INNER JOIN builds ON releases.id = builds.rid
-                     LEFT JOIN repositories ON releases.repository_id = repositories.id
-
-                     WHERE crates.name = ANY($1)",
-                    &[&*names],
-                )?
-                .into_iter()
-                .map(|row| {
-                    let stars: Option<_> = row.get("stars");
-                    let name: String = row.get("name");
-                    (
-                        name.clone(),
-                        Release {
-                            name,
-                            version: row.get("version"),
-                            description: row.get("description"),
-                            build_time: row.get("build_time"),
-                            target_name: row.get("target_name"),
-                            rustdoc_status: row.get("rustdoc_status"),
-                            stars: stars.unwrap_or(0),
-                        },
-                    )
-                })
-                .collect())
+            Ok(
+                get_qualified_releases(&mut conn, Some(names.as_slice()), &qualifiers, None)?
+                    .into_iter()
+                    .map(|release| (release.name.clone(), release))
+                    .collect(),
+            )
         }
     })
     .await?;
@@ -260,6 +368,53 @@ async fn get_search_results(pool: Pool, query_params: &str) -> Result<SearchResu
     })
 }
 
+/// The first releases of crates published in the last [`NEW_CRATES_DAYS`] days, newest first.
+/// New versions of crates published before aren't included.
+///
+/// `build_time` of the releases is the time they were published.
+fn get_new_crates(conn: &mut Client, page: i64, limit: i64) -> Result<Vec<Release>> {
+    let offset = (page - 1) * limit;
+
+    Ok(conn
+        .query(
+            "SELECT crates.name,
+                releases.version,
+                releases.description,
+                releases.target_name,
+                releases.rustdoc_status,
+                releases.release_time,
+                repositories.stars
+            FROM releases
+            INNER JOIN crates ON crates.id = releases.crate_id
+            LEFT JOIN repositories ON releases.repository_id = repositories.id
+            WHERE
+                releases.release_time >= NOW() - make_interval(days => $1) AND
+                -- only the first publication of a crate
+                NOT EXISTS (
+                    SELECT 1
+                    FROM releases AS earlier
+                    WHERE
+                        earlier.crate_id = releases.crate_id AND
+                        (earlier.release_time, earlier.id) < (releases.release_time, releases.id)
+                )
+            ORDER BY releases.release_time DESC, crates.name
+            LIMIT $2 OFFSET $3",
+            &[&NEW_CRATES_DAYS, &limit, &offset],
+        )?
+        .into_iter()
+        .map(|row| Release {
+            name: row.get(0),
+            version: row.get(1),
+            description: row.get(2),
+            target_name: row.get(3),
+            rustdoc_status: row.get(4),
+            build_time: row.get(5),
+            stars: row.get::<_, Option<i32>>(6).unwrap_or(0),
+            failure_category: None,
+        })
+        .collect())
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 struct HomePage {
     recent_releases: Vec<Release>,
@@ -282,6 +437,10 @@ pub(crate) async fn home_page(Extension(pool): Extension<Pool>) -> AxumResult<im
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 struct ReleaseFeed {
     recent_releases: Vec<Release>,
+    base_url: String,
+    subtitle: &'static str,
+    /// The path the feed is served at
+    path: &'static str,
 }
 
 impl_axum_webpage! {
@@ -291,6 +450,7 @@ impl_axum_webpage! {
 
 pub(crate) async fn releases_feed_handler(
     Extension(pool): Extension<Pool>,
+    BaseUrl(base_url): BaseUrl,
 ) -> AxumResult<impl IntoResponse> {
     let recent_releases = spawn_blocking(move || {
         let mut conn = pool.get()?;
@@ -298,7 +458,31 @@ pub(crate) async fn releases_feed_handler(
     })
     .await?;
 
-    Ok(ReleaseFeed { recent_releases })
+    Ok(ReleaseFeed {
+        recent_releases,
+        base_url,
+        subtitle: "Recent Rust crates",
+        path: "/releases/feed",
+    })
+}
+
+/// The Atom feed of `/releases/new-crates`.
+pub(crate) async fn new_crates_feed_handler(
+    Extension(pool): Extension<Pool>,
+    BaseUrl(base_url): BaseUrl,
+) -> AxumResult<impl IntoResponse> {
+    let recent_releases = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        get_new_crates(&mut conn, 1, RELEASES_IN_FEED)
+    })
+    .await?;
+
+    Ok(ReleaseFeed {
+        recent_releases,
+        base_url,
+        subtitle: "New Rust crates",
+        path: "/releases/new-crates/feed",
+    })
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
@@ -324,6 +508,7 @@ pub(crate) enum ReleaseType {
     RecentFailures,
     Failures,
     Search,
+    NewCrates,
 }
 
 pub(crate) async fn releases_handler(
@@ -350,6 +535,9 @@ pub(crate) async fn releases_handler(
         ReleaseType::Search => {
             panic!("The search page has special requirements and cannot use this handler",)
         }
+        ReleaseType::NewCrates => {
+            panic!("The new crates page has special requirements and cannot use this handler",)
+        }
     };
 
     let releases = spawn_blocking(move || {
@@ -388,6 +576,41 @@ pub(crate) async fn recent_releases_handler(
     releases_handler(pool, page.map(|p| p.0), ReleaseType::Recent).await
 }
 
+/// Lists crates by their first release in the last [`NEW_CRATES_DAYS`] days, unlike
+/// `/releases` which lists every new version.
+pub(crate) async fn new_crates_handler(
+    page: Option<Path<i64>>,
+    Extension(pool): Extension<Pool>,
+) -> AxumResult<impl IntoResponse> {
+    let page_number = page.map_or(1, |p| p.0).max(1);
+    // the offset of the page has to fit into the query
+    if (page_number - 1)
+        .checked_mul(RELEASES_IN_RELEASES)
+        .is_none()
+    {
+        return Err(AxumNope::BadRequest("`page` is too large".into()));
+    }
+
+    let releases = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        get_new_crates(&mut conn, page_number, RELEASES_IN_RELEASES)
+    })
+    .await?;
+
+    Ok(ViewReleases {
+        show_next_page: releases.len() == RELEASES_IN_RELEASES as usize,
+        show_previous_page: page_number != 1,
+        releases,
+        description: format!(
+            "Crates first published in the last {} days",
+            NEW_CRATES_DAYS
+        ),
+        release_type: ReleaseType::NewCrates,
+        page_number,
+        owner: None,
+    })
+}
+
 pub(crate) async fn releases_by_stars_handler(
     page: Option<Path<i64>>,
     Extension(pool): Extension<Pool>,
@@ -402,6 +625,36 @@ pub(crate) async fn releases_recent_failures_handler(
     releases_handler(pool, page.map(|p| p.0), ReleaseType::RecentFailures).await
 }
 
+#[derive(Deserialize, Debug)]
+pub(crate) struct RecentFailuresParams {
+    page: Option<i64>,
+}
+
+/// The releases of `/releases/recent-failures` with the likely cause of every failure.
+pub(crate) async fn recent_failures_json_handler(
+    Extension(pool): Extension<Pool>,
+    Query(params): Query<RecentFailuresParams>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let page = params.page.unwrap_or(1);
+    if page < 1 {
+        return Err(AxumNope::BadRequest("`page` has to be at least 1".into()).into());
+    }
+
+    let releases = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        get_releases(
+            &mut conn,
+            page,
+            RELEASES_IN_RELEASES,
+            Order::RecentFailures,
+            false,
+        )
+    })
+    .await?;
+
+    Ok((Extension(CachePolicy::ShortInCdn), Json(releases)))
+}
+
 pub(crate) async fn releases_failures_by_stars_handler(
     page: Option<Path<i64>>,
     Extension(pool): Extension<Pool>,
@@ -409,130 +662,813 @@ pub(crate) async fn releases_failures_by_stars_handler(
     releases_handler(pool, page.map(|p| p.0), ReleaseType::Failures).await
 }
 
-pub(crate) async fn owner_handler(Path(owner): Path<String>) -> AxumResult<impl IntoResponse> {
-    axum_redirect(format!(
-        "https://crates.io/users/{}",
-        owner.strip_prefix('@').unwrap_or(&owner)
-    ))
-    .map_err(|_| AxumNope::OwnerNotFound)
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct OwnerPathParams {
+    owner: String,
+    page: Option<i64>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
-pub(super) struct Search {
-    pub(super) title: String,
-    #[serde(rename = "releases")]
-    pub(super) results: Vec<Release>,
-    pub(super) search_query: Option<String>,
-    pub(super) previous_page_link: Option<String>,
-    pub(super) next_page_link: Option<String>,
-    /// This should always be `ReleaseType::Search`
-    pub(super) release_type: ReleaseType,
-    #[serde(skip)]
-    pub(super) status: http::StatusCode,
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum OwnerKind {
+    User,
+    /// A GitHub team, their login looks like `github:org:team`
+    Team,
 }
 
-impl Default for Search {
-    fn default() -> Self {
-        Self {
-            title: String::default(),
-            results: Vec::default(),
-            search_query: None,
-            previous_page_link: None,
-            next_page_link: None,
-            release_type: ReleaseType::Search,
-            status: http::StatusCode::OK,
-        }
+/// An owner as stored from the registry's metadata, with totals over their crates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Owner {
+    login: String,
+    /// The display name, falls back to the login
+    name: String,
+    /// `None` when the registry didn't give us an avatar
+    avatar: Option<String>,
+    kind: OwnerKind,
+    profile_url: String,
+    crates: i64,
+    total_downloads: i64,
+}
+
+impl Owner {
+    fn get(conn: &mut Client, login: &str) -> Result<Option<Owner>> {
+        let row = match conn.query_opt(
+            "SELECT id, login, name, avatar FROM owners WHERE LOWER(login) = LOWER($1)",
+            &[&login],
+        )? {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+        let id: i32 = row.get("id");
+        let login: String = row.get("login");
+
+        let totals = conn.query_one(
+            "SELECT
+                COUNT(DISTINCT crates.id),
+                COALESCE(SUM(releases.downloads), 0)::BIGINT
+             FROM owner_rels
+             INNER JOIN crates ON crates.id = owner_rels.cid
+             LEFT JOIN releases ON releases.crate_id = crates.id
+             WHERE owner_rels.oid = $1",
+            &[&id],
+        )?;
+
+        let non_empty = |value: Option<String>| value.filter(|value| !value.is_empty());
+        let (kind, profile_url) = if login.starts_with("github:") {
+            (
+                OwnerKind::Team,
+                format!("https://crates.io/teams/{}", login),
+            )
+        } else {
+            (
+                OwnerKind::User,
+                format!("https://crates.io/users/{}", login),
+            )
+        };
+
+        Ok(Some(Owner {
+            name: non_empty(row.get("name")).unwrap_or_else(|| login.clone()),
+            avatar: non_empty(row.get("avatar")),
+            kind,
+            profile_url,
+            crates: totals.get(0),
+            total_downloads: totals.get(1),
+            login,
+        }))
     }
 }
 
-async fn redirect_to_random_crate(
-    config: Arc<Config>,
-    metrics: Arc<Metrics>,
-    pool: Pool,
-) -> AxumResult<impl IntoResponse> {
-    // We try to find a random crate and redirect to it.
-    //
-    // The query is efficient, but relies on a static factor which depends
-    // on the amount of crates with > 100 GH stars over the amount of all crates.
-    //
-    // If random-crate-searches end up being empty, increase that value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct OwnerJson {
+    #[serde(flatten)]
+    owner: Owner,
+    page: i64,
+    releases: Vec<Release>,
+}
 
-    let row = spawn_blocking({
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct OwnerPage {
+    owner: Owner,
+    releases: Vec<Release>,
+    release_type: &'static str,
+    show_next_page: bool,
+    show_previous_page: bool,
+    page_number: i64,
+}
+
+impl_axum_webpage! {
+    OwnerPage = "releases/owner.html",
+}
+
+/// The latest releases of the crates of an owner, most starred first.
+///
+/// Owners we don't know are redirected to their profile on crates.io.
+pub(crate) async fn owner_handler(
+    Path(params): Path<OwnerPathParams>,
+    Extension(pool): Extension<Pool>,
+    RequestedFormat(format): RequestedFormat,
+) -> AxumResult<AxumResponse> {
+    let page_number = params.page.unwrap_or(1).max(1);
+    // the offset of the page has to fit into the query
+    if (page_number - 1)
+        .checked_mul(RELEASES_IN_RELEASES)
+        .is_none()
+    {
+        return Err(AxumNope::BadRequest("`page` is too large".into()));
+    }
+    let login = params
+        .owner
+        .strip_prefix('@')
+        .unwrap_or(&params.owner)
+        .to_owned();
+
+    let owner_and_releases = spawn_blocking({
+        let login = login.clone();
         move || {
             let mut conn = pool.get()?;
-            Ok(conn.query_opt(
-                "WITH params AS (
-                    -- get maximum possible id-value in crates-table
-                    SELECT last_value AS max_id FROM crates_id_seq
-                )
-                SELECT
-                    crates.name,
-                    releases.version,
-                    releases.target_name
-                FROM (
-                    -- generate random numbers in the ID-range.
-                    SELECT DISTINCT 1 + trunc(random() * params.max_id)::INTEGER AS id
-                    FROM params, generate_series(1, $1)
-                ) AS r
-                INNER JOIN crates ON r.id = crates.id
-                INNER JOIN releases ON crates.latest_version_id = releases.id
-                INNER JOIN repositories ON releases.repository_id = repositories.id
-                WHERE
-                    releases.rustdoc_status = TRUE AND
-                    repositories.stars >= 100
-                LIMIT 1",
-                &[&(config.random_crate_search_view_size as i32)],
-            )?)
+            let owner = match Owner::get(&mut conn, &login)? {
+                Some(owner) => owner,
+                None => return Ok(None),
+            };
+
+            let releases = conn
+                .query(
+                    "SELECT crates.name,
+                        releases.version,
+                        releases.description,
+                        releases.target_name,
+                        releases.rustdoc_status,
+                        releases.release_time,
+                        repositories.stars
+                    FROM owner_rels
+                    INNER JOIN owners ON owners.id = owner_rels.oid
+                    INNER JOIN crates ON crates.id = owner_rels.cid
+                    INNER JOIN releases ON releases.id = crates.latest_version_id
+                    LEFT JOIN repositories ON releases.repository_id = repositories.id
+                    WHERE owners.login = $1
+                    ORDER BY repositories.stars DESC NULLS LAST, crates.name
+                    LIMIT $2 OFFSET $3",
+                    &[
+                        &owner.login,
+                        &RELEASES_IN_RELEASES,
+                        &((page_number - 1) * RELEASES_IN_RELEASES),
+                    ],
+                )?
+                .into_iter()
+                .map(|row| Release {
+                    name: row.get(0),
+                    version: row.get(1),
+                    description: row.get(2),
+                    target_name: row.get(3),
+                    rustdoc_status: row.get(4),
+                    build_time: row.get(5),
+                    stars: row.get::<_, Option<i32>>(6).unwrap_or(0),
+                    failure_category: None,
+                })
+                .collect::<Vec<_>>();
+
+            Ok(Some((owner, releases)))
         }
     })
     .await?;
 
-    if let Some(row) = row {
-        let name: String = row.get("name");
-        let version: String = row.get("version");
-        let target_name: String = row.get("target_name");
+    let (owner, releases) = match (owner_and_releases, format) {
+        (Some(owner_and_releases), _) => owner_and_releases,
+        (None, OutputFormat::Json) => {
+            return Ok(JsonAxumNope(AxumNope::OwnerNotFound).into_response())
+        }
+        (None, OutputFormat::Html) => {
+            return Ok(axum_redirect(format!("https://crates.io/users/{}", login))
+                .map_err(|_| AxumNope::OwnerNotFound)?
+                .into_response())
+        }
+    };
 
-        metrics.im_feeling_lucky_searches.inc();
+    Ok(match format {
+        OutputFormat::Json => (
+            Extension(CachePolicy::NoCaching),
+            Json(OwnerJson {
+                owner,
+                page: page_number,
+                releases,
+            }),
+        )
+            .into_response(),
+        OutputFormat::Html => OwnerPage {
+            show_next_page: releases.len() == RELEASES_IN_RELEASES as usize,
+            show_previous_page: page_number != 1,
+            owner,
+            releases,
+            release_type: "owner",
+            page_number,
+        }
+        .into_response(),
+    })
+}
 
-        Ok(axum_redirect(format!(
-            "/{}/{}/{}/",
-            name, version, target_name
-        ))?)
-    } else {
-        report_error(&anyhow!("found no result in random crate search"));
-        Err(AxumNope::NoResults)
-    }
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct WatchParams {
+    /// Comma separated list of crate names
+    #[serde(default)]
+    crates: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct WatchJson {
+    releases: Vec<Release>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct WatchPage {
+    releases: Vec<Release>,
+    description: String,
+    release_type: &'static str,
+    show_next_page: bool,
+    show_previous_page: bool,
+    page_number: i64,
 }
 
 impl_axum_webpage! {
-    Search = "releases/search_results.html",
-    status = |search| search.status,
+    WatchPage = "releases/releases.html",
 }
 
-pub(crate) async fn search_handler(
+/// Recent releases of a list of crates given in the URL, so users can bookmark their own
+/// dashboard without needing an account.
+pub(crate) async fn watch_handler(
     Extension(pool): Extension<Pool>,
-    Extension(config): Extension<Arc<Config>>,
-    Extension(metrics): Extension<Arc<Metrics>>,
-    Query(mut params): Query<HashMap<String, String>>,
+    Query(params): Query<WatchParams>,
+    RequestedFormat(format): RequestedFormat,
 ) -> AxumResult<AxumResponse> {
-    let query = params
-        .get("query")
-        .map(|q| q.to_string())
-        .unwrap_or_else(|| "".to_string());
-
-    // check if I am feeling lucky button pressed and redirect user to crate page
-    // if there is a match. Also check for paths to items within crates.
-    if params.remove("i-am-feeling-lucky").is_some() || query.contains("::") {
-        // redirect to a random crate if query is empty
-        if query.is_empty() {
-            return Ok(redirect_to_random_crate(config, metrics, pool)
-                .await?
-                .into_response());
-        }
-
-        let mut queries = BTreeMap::new();
+    let mut crates: Vec<String> = params
+        .crates
+        .split(',')
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty())
+        .collect();
+    // the same crate can be given with different case or `-` and `_`, like `normalize_crate_name`
+    // in the database treats them
+    let normalize = |name: &String| name.to_lowercase().replace('_', "-");
+    crates.sort_by_key(normalize);
+    crates.dedup_by_key(|name| normalize(name));
+
+    let error = if crates.is_empty() {
+        Some("no crates given, use `?crates=first,second`".to_string())
+    } else if crates.len() > WATCH_MAX_CRATES {
+        Some(format!(
+            "too many crates, at most {} can be watched at once",
+            WATCH_MAX_CRATES
+        ))
+    } else {
+        None
+    };
+    if let Some(message) = error {
+        return Ok(match format {
+            OutputFormat::Json => JsonAxumNope(AxumNope::BadRequest(message)).into_response(),
+            OutputFormat::Html => AxumNope::BadRequest(message).into_response(),
+        });
+    }
 
-        let krate = match query.split_once("::") {
+    let crate_count = crates.len();
+    let releases = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        Ok(conn
+            .query(
+                "SELECT crates.name,
+                    releases.version,
+                    releases.description,
+                    releases.target_name,
+                    releases.rustdoc_status,
+                    builds.build_time,
+                    repositories.stars
+                FROM crates
+                INNER JOIN releases ON crates.id = releases.crate_id
+                INNER JOIN builds ON releases.id = builds.rid
+                LEFT JOIN repositories ON releases.repository_id = repositories.id
+                WHERE normalize_crate_name(crates.name) IN (
+                    SELECT normalize_crate_name(name) FROM UNNEST($1::TEXT[]) AS name
+                )
+                ORDER BY builds.build_time DESC
+                LIMIT $2",
+                &[&crates, &RELEASES_IN_RELEASES],
+            )?
+            .into_iter()
+            .map(|row| Release {
+                name: row.get(0),
+                version: row.get(1),
+                description: row.get(2),
+                target_name: row.get(3),
+                rustdoc_status: row.get(4),
+                build_time: row.get(5),
+                stars: row.get::<_, Option<i32>>(6).unwrap_or(0),
+                failure_category: None,
+            })
+            .collect::<Vec<_>>())
+    })
+    .await?;
+
+    Ok(match format {
+        OutputFormat::Json => (
+            Extension(CachePolicy::NoCaching),
+            Json(WatchJson { releases }),
+        )
+            .into_response(),
+        OutputFormat::Html => WatchPage {
+            releases,
+            description: format!(
+                "Recent releases of {} watched crate{}",
+                crate_count,
+                if crate_count == 1 { "" } else { "s" }
+            ),
+            release_type: "watch",
+            show_next_page: false,
+            show_previous_page: false,
+            page_number: 1,
+        }
+        .into_response(),
+    })
+}
+
+/// Turns the slug of a crates.io category into something readable, e.g.
+/// `development-tools::testing` into `Development tools › Testing`.
+fn category_display_name(slug: &str) -> String {
+    slug.split("::")
+        .map(|part| {
+            let part = part.replace('-', " ");
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" › ")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Category {
+    slug: String,
+    name: String,
+    /// Number of crates with documentation whose latest release is in this category
+    crates: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct CategoriesPage {
+    categories: Vec<Category>,
+}
+
+impl_axum_webpage! {
+    CategoriesPage = "releases/categories.html",
+}
+
+pub(crate) async fn categories_handler(
+    Extension(pool): Extension<Pool>,
+    RequestedFormat(format): RequestedFormat,
+) -> AxumResult<AxumResponse> {
+    let categories = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        Ok(conn
+            .query(
+                "SELECT categories.slug, COUNT(releases.id)
+                FROM categories
+                INNER JOIN category_rels ON categories.id = category_rels.cid
+                INNER JOIN crates ON crates.latest_version_id = category_rels.rid
+                INNER JOIN releases ON releases.id = crates.latest_version_id
+                WHERE releases.rustdoc_status = TRUE
+                GROUP BY categories.slug
+                ORDER BY categories.slug",
+                &[],
+            )?
+            .into_iter()
+            .map(|row| {
+                let slug: String = row.get(0);
+                Category {
+                    name: category_display_name(&slug),
+                    slug,
+                    crates: row.get(1),
+                }
+            })
+            .collect::<Vec<_>>())
+    })
+    .await?;
+
+    Ok(match format {
+        OutputFormat::Json => (
+            Extension(CachePolicy::NoCaching),
+            Json(CategoriesPage { categories }),
+        )
+            .into_response(),
+        OutputFormat::Html => CategoriesPage { categories }.into_response(),
+    })
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct CategoryParams {
+    page: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct CategoryJson {
+    slug: String,
+    name: String,
+    page: i64,
+    releases: Vec<Release>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct CategoryPage {
+    releases: Vec<Release>,
+    title: String,
+    description: String,
+    release_type: &'static str,
+    slug: String,
+    show_next_page: bool,
+    show_previous_page: bool,
+    page_number: i64,
+}
+
+impl_axum_webpage! {
+    CategoryPage = "releases/category.html",
+}
+
+/// Documented crates in a crates.io category, most downloaded first.
+pub(crate) async fn category_handler(
+    Path(slug): Path<String>,
+    Extension(pool): Extension<Pool>,
+    Query(params): Query<CategoryParams>,
+    RequestedFormat(format): RequestedFormat,
+) -> AxumResult<AxumResponse> {
+    let page_number = params.page.unwrap_or(1).max(1);
+    // the offset of the page has to fit into the query
+    if (page_number - 1)
+        .checked_mul(RELEASES_IN_RELEASES)
+        .is_none()
+    {
+        return Err(AxumNope::BadRequest("`page` is too large".into()));
+    }
+
+    let releases = spawn_blocking({
+        let slug = slug.clone();
+        move || {
+            let mut conn = pool.get()?;
+            if conn
+                .query_opt("SELECT id FROM categories WHERE slug = $1", &[&slug])?
+                .is_none()
+            {
+                return Ok(None);
+            }
+
+            Ok(Some(
+                conn.query(
+                    "SELECT crates.name,
+                        releases.version,
+                        releases.description,
+                        releases.target_name,
+                        releases.rustdoc_status,
+                        releases.release_time,
+                        repositories.stars
+                    FROM categories
+                    INNER JOIN category_rels ON categories.id = category_rels.cid
+                    INNER JOIN crates ON crates.latest_version_id = category_rels.rid
+                    INNER JOIN releases ON releases.id = crates.latest_version_id
+                    LEFT JOIN repositories ON releases.repository_id = repositories.id
+                    WHERE categories.slug = $1 AND releases.rustdoc_status = TRUE
+                    ORDER BY releases.downloads DESC, crates.name
+                    LIMIT $2 OFFSET $3",
+                    &[
+                        &slug,
+                        &RELEASES_IN_RELEASES,
+                        &((page_number - 1) * RELEASES_IN_RELEASES),
+                    ],
+                )?
+                .into_iter()
+                .map(|row| Release {
+                    name: row.get(0),
+                    version: row.get(1),
+                    description: row.get(2),
+                    target_name: row.get(3),
+                    rustdoc_status: row.get(4),
+                    build_time: row.get(5),
+                    stars: row.get::<_, Option<i32>>(6).unwrap_or(0),
+                    failure_category: None,
+                })
+                .collect::<Vec<_>>(),
+            ))
+        }
+    })
+    .await?;
+
+    let releases = match (releases, format) {
+        (Some(releases), _) => releases,
+        (None, OutputFormat::Json) => {
+            return Ok(JsonAxumNope(AxumNope::ResourceNotFound).into_response())
+        }
+        (None, OutputFormat::Html) => return Err(AxumNope::ResourceNotFound),
+    };
+
+    let name = category_display_name(&slug);
+    Ok(match format {
+        OutputFormat::Json => (
+            Extension(CachePolicy::NoCaching),
+            Json(CategoryJson {
+                slug,
+                name,
+                page: page_number,
+                releases,
+            }),
+        )
+            .into_response(),
+        OutputFormat::Html => CategoryPage {
+            show_next_page: releases.len() == RELEASES_IN_RELEASES as usize,
+            show_previous_page: page_number != 1,
+            releases,
+            description: format!("Documented crates in the category `{}`", slug),
+            title: name,
+            release_type: "categories",
+            slug,
+            page_number,
+        }
+        .into_response(),
+    })
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct CratesParams {
+    /// Only list crates whose name comes after this one
+    after: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct CratesJson {
+    crates: Vec<String>,
+    /// Number of crates with documentation on all pages
+    total: i64,
+    /// Link to the next page, `None` on the last one
+    next: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct CratesPage {
+    crates: Vec<String>,
+    total: i64,
+    next: Option<String>,
+    is_first_page: bool,
+}
+
+impl_axum_webpage! {
+    CratesPage = "releases/crates.html",
+}
+
+/// Crates with at least one documented release
+const DOCUMENTED_CRATE: &str = "EXISTS (
+    SELECT 1 FROM releases
+    WHERE releases.crate_id = crates.id AND releases.rustdoc_status = TRUE
+)";
+
+/// The number of documented crates shown on `/releases/crates`, counted at most every
+/// [`DOCUMENTED_CRATES_MAX_AGE`] since it has to check every crate.
+#[derive(Debug, Default)]
+pub(crate) struct DocumentedCratesCount {
+    counted: Mutex<Option<(Instant, i64)>>,
+}
+
+impl DocumentedCratesCount {
+    fn get(&self, conn: &mut Client) -> Result<i64> {
+        if let Some((counted_at, count)) = *self.counted.lock().unwrap() {
+            if counted_at.elapsed() < DOCUMENTED_CRATES_MAX_AGE {
+                return Ok(count);
+            }
+        }
+
+        let count: i64 = conn
+            .query_one(
+                format!("SELECT COUNT(*) FROM crates WHERE {}", DOCUMENTED_CRATE).as_str(),
+                &[],
+            )?
+            .get(0);
+        *self.counted.lock().unwrap() = Some((Instant::now(), count));
+        Ok(count)
+    }
+}
+
+/// All crates with at least one documented release, in alphabetical order.
+///
+/// The pages are selected by the last crate name of the previous page instead of an offset, so
+/// the query stays fast for the later pages too.
+pub(crate) async fn crates_handler(
+    Extension(pool): Extension<Pool>,
+    Extension(count): Extension<Arc<DocumentedCratesCount>>,
+    Query(params): Query<CratesParams>,
+    RequestedFormat(format): RequestedFormat,
+) -> AxumResult<AxumResponse> {
+    let limit = params.limit.unwrap_or(CRATES_IN_LIST);
+    if !(1..=CRATES_IN_LIST).contains(&limit) {
+        return Err(AxumNope::BadRequest(format!(
+            "`limit` has to be between 1 and {}",
+            CRATES_IN_LIST
+        )));
+    }
+
+    let (mut crates, total) = spawn_blocking({
+        let after = params.after.clone();
+        move || {
+            let mut conn = pool.get()?;
+            // one more than needed, to know whether there is a next page
+            let crates: Vec<String> = conn
+                .query(
+                    format!(
+                        "SELECT crates.name
+                         FROM crates
+                         WHERE ($1::TEXT IS NULL OR crates.name > $1) AND {}
+                         ORDER BY crates.name
+                         LIMIT $2",
+                        DOCUMENTED_CRATE
+                    )
+                    .as_str(),
+                    &[&after, &(limit + 1)],
+                )?
+                .into_iter()
+                .map(|row| row.get(0))
+                .collect();
+            Ok((crates, count.get(&mut conn)?))
+        }
+    })
+    .await?;
+
+    let next = if crates.len() as i64 > limit {
+        crates.truncate(limit as usize);
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        query.append_pair("after", crates.last().unwrap());
+        if params.limit.is_some() {
+            query.append_pair("limit", &limit.to_string());
+        }
+        if format == OutputFormat::Json {
+            query.append_pair("format", "json");
+        }
+        Some(format!("/releases/crates?{}", query.finish()))
+    } else {
+        None
+    };
+
+    Ok(match format {
+        OutputFormat::Json => (
+            Extension(CachePolicy::NoCaching),
+            Json(CratesJson {
+                crates,
+                total,
+                next,
+            }),
+        )
+            .into_response(),
+        OutputFormat::Html => CratesPage {
+            crates,
+            total,
+            next,
+            is_first_page: params.after.is_none(),
+        }
+        .into_response(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct Search {
+    pub(super) title: String,
+    #[serde(rename = "releases")]
+    pub(super) results: Vec<Release>,
+    pub(super) search_query: Option<String>,
+    pub(super) previous_page_link: Option<String>,
+    pub(super) next_page_link: Option<String>,
+    /// This should always be `ReleaseType::Search`
+    pub(super) release_type: ReleaseType,
+    #[serde(skip)]
+    pub(super) status: http::StatusCode,
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self {
+            title: String::default(),
+            results: Vec::default(),
+            search_query: None,
+            previous_page_link: None,
+            next_page_link: None,
+            release_type: ReleaseType::Search,
+            status: http::StatusCode::OK,
+        }
+    }
+}
+
+async fn redirect_to_random_crate(
+    config: Arc<Config>,
+    metrics: Arc<Metrics>,
+    pool: Pool,
+) -> AxumResult<impl IntoResponse> {
+    // We try to find a random crate and redirect to it.
+    //
+    // The query is efficient, but relies on a static factor which depends
+    // on the amount of crates with > 100 GH stars over the amount of all crates.
+    //
+    // If random-crate-searches end up being empty, increase that value.
+
+    let row = spawn_blocking({
+        move || {
+            let mut conn = pool.get()?;
+            Ok(conn.query_opt(
+                "WITH params AS (
+                    -- get maximum possible id-value in crates-table
+                    SELECT last_value AS max_id FROM crates_id_seq
+                )
+                SELECT
+                    crates.name,
+                    releases.version,
+                    releases.target_name
+                FROM (
+                    -- generate random numbers in the ID-range.
+                    SELECT DISTINCT 1 + trunc(random() * params.max_id)::INTEGER AS id
+                    FROM params, generate_series(1, $1)
+                ) AS r
+                INNER JOIN crates ON r.id = crates.id
+                INNER JOIN releases ON crates.latest_version_id = releases.id
+                INNER JOIN repositories ON releases.repository_id = repositories.id
+                WHERE
+                    releases.rustdoc_status = TRUE AND
+                    repositories.stars >= 100
+                LIMIT 1",
+                &[&(config.random_crate_search_view_size as i32)],
+            )?)
+        }
+    })
+    .await?;
+
+    if let Some(row) = row {
+        let name: String = row.get("name");
+        let version: String = row.get("version");
+        let target_name: String = row.get("target_name");
+
+        metrics.im_feeling_lucky_searches.inc();
+
+        Ok(axum_redirect(format!(
+            "/{}/{}/{}/",
+            name, version, target_name
+        ))?)
+    } else {
+        report_error(&anyhow!("found no result in random crate search"));
+        Err(AxumNope::NoResults)
+    }
+}
+
+impl_axum_webpage! {
+    Search = "releases/search_results.html",
+    status = |search| search.status,
+}
+
+/// Redirects a bare crate name to the documentation of its latest release.
+///
+/// When the latest release has no documentation we redirect to the crate details page
+/// instead, which explains why.
+pub(crate) async fn crate_docs_redirect_handler(
+    Path(name): Path<String>,
+    Extension(pool): Extension<Pool>,
+) -> AxumResult<impl IntoResponse> {
+    let matchver = match_version_axum(&pool, &name, None).await?;
+    let (version, _) = matchver.version.into_parts();
+    let krate = matchver.corrected_name.unwrap_or(name);
+
+    // the latest release changes over time, so this has to be a temporary redirect
+    let uri = if matchver.rustdoc_status {
+        format!("/{}/{}/{}/", krate, version, matchver.target_name)
+    } else {
+        format!("/crate/{}/{}", krate, version)
+    };
+
+    Ok(axum_redirect(uri)?)
+}
+
+pub(crate) async fn search_handler(
+    Extension(pool): Extension<Pool>,
+    Extension(config): Extension<Arc<Config>>,
+    Extension(metrics): Extension<Arc<Metrics>>,
+    Query(mut params): Query<HashMap<String, String>>,
+) -> AxumResult<AxumResponse> {
+    let query = params
+        .get("query")
+        .map(|q| q.to_string())
+        .unwrap_or_else(|| "".to_string());
+
+    // check if I am feeling lucky button pressed and redirect user to crate page
+    // if there is a match. Also check for paths to items within crates.
+    if params.remove("i-am-feeling-lucky").is_some() || query.contains("::") {
+        // redirect to a random crate if query is empty
+        if query.is_empty() {
+            return Ok(redirect_to_random_crate(config, metrics, pool)
+                .await?
+                .into_response());
+        }
+
+        let mut queries = BTreeMap::new();
+
+        let krate = match query.split_once("::") {
             Some((krate, query)) => {
                 queries.insert("search".into(), query.into());
                 krate
@@ -562,6 +1498,10 @@ pub(crate) async fn search_handler(
         }
     }
 
+    // qualifiers aren't part of the crates.io pagination arguments, so pagination links keep
+    // them in the `query`.
+    let (term, qualifiers) = SearchQualifiers::parse(&query);
+
     let search_result = if let Some(paginate) = params.get("paginate") {
         let decoded = base64::decode(paginate.as_bytes()).map_err(|e| {
             warn!(
@@ -586,19 +1526,33 @@ pub(crate) async fn search_handler(
             return Err(AxumNope::NoResults);
         }
 
-        get_search_results(pool, &query_params).await?
-    } else if !query.is_empty() {
+        get_search_results(pool, &query_params, qualifiers.clone()).await?
+    } else if !term.is_empty() {
         let query_params: String = form_urlencoded::Serializer::new(String::new())
-            .append_pair("q", &query)
+            .append_pair("q", &term)
             .append_pair("per_page", &RELEASES_IN_RELEASES.to_string())
             .finish();
 
-        get_search_results(pool, &format!("?{}", &query_params)).await?
+        get_search_results(pool, &format!("?{}", &query_params), qualifiers.clone()).await?
+    } else if !qualifiers.is_empty() {
+        // without a search term there's nothing to ask crates.io for
+        let qualifiers = qualifiers.clone();
+        let results = spawn_blocking(move || {
+            let mut conn = pool.get()?;
+            get_qualified_releases(&mut conn, None, &qualifiers, Some(RELEASES_IN_RELEASES))
+        })
+        .await?;
+        SearchResult {
+            results,
+            executed_query: None,
+            prev_page: None,
+            next_page: None,
+        }
     } else {
         return Err(AxumNope::NoResults);
     };
 
-    let executed_query = search_result.executed_query.unwrap_or_default();
+    let executed_query = qualifiers.to_query(&search_result.executed_query.unwrap_or_default());
 
     let title = if search_result.results.is_empty() {
         format!("No results found for '{}'", executed_query)
@@ -606,24 +1560,84 @@ pub(crate) async fn search_handler(
         format!("Search results for '{}'", executed_query)
     };
 
+    let page_link = |params: String| {
+        let mut link = format!("/releases/search?paginate={}", base64::encode(params));
+        if !qualifiers.is_empty() {
+            link.push('&');
+            link.push_str(
+                &form_urlencoded::Serializer::new(String::new())
+                    .append_pair("query", &qualifiers.to_query(""))
+                    .finish(),
+            );
+        }
+        link
+    };
+
     Ok(Search {
         title,
         results: search_result.results,
         search_query: Some(executed_query),
-        next_page_link: search_result
-            .next_page
-            .map(|params| format!("/releases/search?paginate={}", base64::encode(params))),
-        previous_page_link: search_result
-            .prev_page
-            .map(|params| format!("/releases/search?paginate={}", base64::encode(params))),
+        next_page_link: search_result.next_page.map(page_link),
+        previous_page_link: search_result.prev_page.map(page_link),
         ..Default::default()
     }
     .into_response())
 }
 
+/// Returns crate names starting with `prefix`, the most downloaded ones first.
+///
+/// Names are compared normalized, so `-` and `_` are interchangeable, matching the
+/// `crates_normalized_name_prefix_idx` index.
+fn get_search_suggestions(conn: &mut Client, prefix: &str) -> Result<Vec<String>> {
+    let prefix = prefix.trim().to_lowercase().replace('_', "-");
+    // crate names only contain alphanumerics, `-` and `_`, so anything else can't match.
+    // This also means we don't have to escape any `LIKE` wildcards.
+    if prefix.is_empty()
+        || !prefix
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Ok(Vec::new());
+    }
+
+    Ok(conn
+        .query(
+            "SELECT crates.name
+             FROM crates
+             INNER JOIN releases ON crates.latest_version_id = releases.id
+             WHERE normalize_crate_name(crates.name) LIKE $1 || '%'
+             ORDER BY releases.downloads DESC, crates.name
+             LIMIT $2",
+            &[&prefix, &SEARCH_SUGGESTIONS],
+        )?
+        .into_iter()
+        .map(|row| row.get(0))
+        .collect())
+}
+
+pub(crate) async fn search_suggest_handler(
+    Extension(pool): Extension<Pool>,
+    Query(params): Query<HashMap<String, String>>,
+) -> AxumResult<impl IntoResponse> {
+    let query = params.get("q").cloned().unwrap_or_default();
+
+    let suggestions = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        get_search_suggestions(&mut conn, &query)
+    })
+    .await?;
+
+    Ok((Extension(CachePolicy::NoCaching), Json(suggestions)))
+}
+
+/// Number of days shown in the release activity by default
+const ACTIVITY_DEFAULT_DAYS: i32 = 30;
+/// Maximum number of days that can be requested from the release activity
+const ACTIVITY_MAX_DAYS: i32 = 365;
+
 #[derive(Debug, Clone, PartialEq, Serialize)]
 struct ReleaseActivity {
-    description: &'static str,
+    description: String,
     dates: Vec<String>,
     counts: Vec<i64>,
     failures: Vec<i64>,
@@ -633,9 +1647,33 @@ impl_axum_webpage! {
     ReleaseActivity = "releases/activity.html",
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ActivityParams {
+    days: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct ActivityCount {
+    date: NaiveDate,
+    count: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct ActivityJson {
+    releases: Vec<ActivityCount>,
+    failures: Vec<ActivityCount>,
+}
+
 pub(crate) async fn activity_handler(
     Extension(pool): Extension<Pool>,
-) -> AxumResult<impl IntoResponse> {
+    Query(params): Query<ActivityParams>,
+    RequestedFormat(format): RequestedFormat,
+) -> AxumResult<AxumResponse> {
+    let days = params
+        .days
+        .unwrap_or(ACTIVITY_DEFAULT_DAYS)
+        .clamp(1, ACTIVITY_MAX_DAYS);
+
     let data = spawn_blocking(move ||  {
         let mut conn = pool.get()?;
         Ok(conn.query(
@@ -643,7 +1681,7 @@ pub(crate) async fn activity_handler(
             WITH dates AS (
                 -- we need this series so that days in the statistic that don't have any releases are included
                 SELECT generate_series(
-                        CURRENT_DATE - INTERVAL '30 days',
+                        CURRENT_DATE - make_interval(days => $1),
                         CURRENT_DATE - INTERVAL '1 day',
                         '1 day'::interval
                     )::date AS date_
@@ -656,7 +1694,7 @@ pub(crate) async fn activity_handler(
                 FROM
                     releases
                 WHERE
-                    release_time >= CURRENT_DATE - INTERVAL '30 days' AND
+                    release_time >= CURRENT_DATE - make_interval(days => $1) AND
                     release_time < CURRENT_DATE
                 GROUP BY
                     release_time::date
@@ -672,28 +1710,60 @@ pub(crate) async fn activity_handler(
             ORDER BY
                 dates.date_
             ",
-            &[],
+            &[&days],
         )?.into_iter()
             .map(|row| (row.get(0), row.get(1), row.get(2)))
             .collect::<Vec<(NaiveDate, i64, i64)>>()
             )
     }).await?;
 
-    Ok(ReleaseActivity {
-        description: "Monthly release activity",
-        dates: data
-            .iter()
-            .map(|&d| d.0.format("%d %b").to_string())
-            .collect(),
-        counts: data.iter().map(|&d| d.1).collect(),
-        failures: data.iter().map(|&d| d.2).collect(),
+    Ok(match format {
+        OutputFormat::Json => (
+            Extension(CachePolicy::NoCaching),
+            Json(ActivityJson {
+                releases: data
+                    .iter()
+                    .map(|&(date, count, _)| ActivityCount { date, count })
+                    .collect(),
+                failures: data
+                    .iter()
+                    .map(|&(date, _, count)| ActivityCount { date, count })
+                    .collect(),
+            }),
+        )
+            .into_response(),
+        OutputFormat::Html => ReleaseActivity {
+            description: if days == ACTIVITY_DEFAULT_DAYS {
+                "Monthly release activity".to_string()
+            } else {
+                format!("Release activity of the last {} days", days)
+            },
+            dates: data
+                .iter()
+                .map(|&d| d.0.format("%d %b").to_string())
+                .collect(),
+            counts: data.iter().map(|&d| d.1).collect(),
+            failures: data.iter().map(|&d| d.2).collect(),
+        }
+        .into_response(),
     })
 }
 
+/// Number of recent builds needed before wait times in the queue are estimated
+const QUEUE_ESTIMATE_MIN_SAMPLES: i64 = 5;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct QueueEntry {
+    #[serde(flatten)]
+    krate: QueuedCrate,
+    /// Estimated seconds until the build is finished, `None` without enough recent builds
+    estimated_wait: Option<i64>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize)]
 struct BuildQueuePage {
     description: &'static str,
-    queue: Vec<QueuedCrate>,
+    queue: Vec<QueueEntry>,
     active_deployments: Vec<CrateInvalidation>,
 }
 
@@ -701,20 +1771,78 @@ impl_axum_webpage! {
     BuildQueuePage = "releases/build_queue.html",
 }
 
-pub(crate) async fn build_queue_handler(
-    Extension(build_queue): Extension<Arc<BuildQueue>>,
-    Extension(pool): Extension<Pool>,
-) -> AxumResult<impl IntoResponse> {
-    let (queue, active_deployments) = spawn_blocking(move || {
-        let mut queue = build_queue.queued_crates()?;
-        for krate in queue.iter_mut() {
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct BuildQueueJson {
+    queue: Vec<QueueEntry>,
+    /// Average seconds a build took recently, `None` without enough recent builds
+    average_build_duration: Option<i64>,
+    active_builders: i64,
+}
+
+/// Estimates how long a build takes.
+///
+/// We don't store when builds start, so the duration is the time between two builds finishing on
+/// the same server during the last day. Gaps over an hour are the builder idling and are skipped.
+fn build_duration_estimate(conn: &mut Client) -> Result<Option<f64>> {
+    let row = conn.query_one(
+        "WITH durations AS (
+            SELECT build_time - LAG(build_time) OVER (
+                PARTITION BY build_server ORDER BY build_time
+            ) AS duration
+            FROM builds
+            WHERE build_time > NOW() - INTERVAL '1 day'
+        )
+        SELECT
+            COUNT(*) AS samples,
+            AVG(EXTRACT(EPOCH FROM duration))::FLOAT8 AS average
+        FROM durations
+        WHERE duration < INTERVAL '1 hour'",
+        &[],
+    )?;
+    let samples: i64 = row.get("samples");
+    let average: Option<f64> = row.get("average");
+
+    Ok(average.filter(|_| samples >= QUEUE_ESTIMATE_MIN_SAMPLES))
+}
+
+/// Fetches the queue in build order with the estimated wait times.
+fn queue_with_estimates(
+    build_queue: &BuildQueue,
+    conn: &mut Client,
+) -> Result<(Vec<QueueEntry>, Option<f64>, i64)> {
+    let average_duration = build_duration_estimate(conn)?;
+    let active_builders = build_queue.active_builders()?;
+
+    let queue = build_queue
+        .queued_crates()?
+        .into_iter()
+        .enumerate()
+        .map(|(position, mut krate)| {
             // The priority here is inverted: in the database if a crate has a higher priority it
             // will be built after everything else, which is counter-intuitive for people not
             // familiar with docs.rs's inner workings.
             krate.priority = -krate.priority;
-        }
 
+            // every builder takes one crate from the front of the queue at a time
+            let rounds = position as i64 / active_builders.max(1) + 1;
+            QueueEntry {
+                krate,
+                estimated_wait: average_duration
+                    .map(|duration| (duration * rounds as f64).round() as i64),
+            }
+        })
+        .collect();
+
+    Ok((queue, average_duration, active_builders))
+}
+
+pub(crate) async fn build_queue_handler(
+    Extension(build_queue): Extension<Arc<BuildQueue>>,
+    Extension(pool): Extension<Pool>,
+) -> AxumResult<impl IntoResponse> {
+    let (queue, active_deployments) = spawn_blocking(move || {
         let mut conn = pool.get()?;
+        let (queue, _, _) = queue_with_estimates(&build_queue, &mut conn)?;
         Ok((queue, cdn::active_crate_invalidations(&mut conn)?))
     })
     .await?;
@@ -726,13 +1854,195 @@ pub(crate) async fn build_queue_handler(
     })
 }
 
+pub(crate) async fn build_queue_json_handler(
+    Extension(build_queue): Extension<Arc<BuildQueue>>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let (queue, average_duration, active_builders) = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        queue_with_estimates(&build_queue, &mut conn)
+    })
+    .await?;
+
+    Ok((
+        Extension(CachePolicy::NoCaching),
+        Json(BuildQueueJson {
+            queue,
+            average_build_duration: average_duration.map(|duration| duration.round() as i64),
+            active_builders,
+        }),
+    ))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct DashboardParams {
+    page: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct DashboardJson {
+    recent_releases: Vec<Release>,
+    recent_failures: Vec<Release>,
+    most_starred: Vec<Release>,
+    failures_by_stars: Vec<Release>,
+    /// Number of releases waiting in the build queue or being built
+    queue_length: usize,
+}
+
+/// The lists of the `/releases` pages and the length of the build queue in a single response,
+/// for status dashboards. `page` and `limit` apply to every list.
+pub(crate) async fn dashboard_json_handler(
+    Extension(pool): Extension<Pool>,
+    Extension(build_queue): Extension<Arc<BuildQueue>>,
+    Query(params): Query<DashboardParams>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let page = params.page.unwrap_or(1);
+    if page < 1 {
+        return Err(AxumNope::BadRequest("`page` has to be at least 1".into()).into());
+    }
+    let limit = params.limit.unwrap_or(RELEASES_IN_DASHBOARD);
+    if !(1..=RELEASES_IN_DASHBOARD).contains(&limit) {
+        return Err(AxumNope::BadRequest(format!(
+            "`limit` has to be between 1 and {}",
+            RELEASES_IN_DASHBOARD
+        ))
+        .into());
+    }
+    // the offset of the page has to fit into the query
+    if (page - 1).checked_mul(limit).is_none() {
+        return Err(AxumNope::BadRequest("`page` is too large".into()).into());
+    }
+
+    let dashboard = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let mut releases =
+            |order, latest_only| get_releases(&mut conn, page, limit, order, latest_only);
+
+        Ok(DashboardJson {
+            recent_releases: releases(Order::ReleaseTime, false)?,
+            recent_failures: releases(Order::RecentFailures, false)?,
+            most_starred: releases(Order::GithubStars, true)?,
+            failures_by_stars: releases(Order::FailuresByGithubStars, true)?,
+            queue_length: build_queue.pending_count()?,
+        })
+    })
+    .await?;
+
+    Ok((Extension(CachePolicy::ShortInCdn), Json(dashboard)))
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct CheckParams {
+    crates: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct CheckedRelease {
+    #[serde(rename = "crate")]
+    name: String,
+    /// The version or semver requirement as given in the request
+    requested_version: String,
+    /// The matched version, `None` if the lookup failed
+    version: Option<String>,
+    /// Status of the latest build of the matched version, `None` if it wasn't built yet
+    build_status: Option<bool>,
+    error: Option<String>,
+}
+
+/// Parses the `crates` parameter of `/releases/check`, a comma separated list of
+/// `name@version` entries. The version defaults to `latest` and may be a semver requirement.
+fn parse_checked_releases(crates: &str) -> Vec<(String, String)> {
+    crates
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once('@') {
+            Some((name, version)) => (name.trim().to_owned(), version.trim().to_owned()),
+            None => (entry.to_owned(), "latest".to_owned()),
+        })
+        .collect()
+}
+
+fn check_release(
+    conn: &mut Client,
+    name: String,
+    requested_version: String,
+) -> Result<CheckedRelease> {
+    let mut checked = CheckedRelease {
+        name,
+        requested_version,
+        version: None,
+        build_status: None,
+        error: None,
+    };
+
+    let (version, id) = match match_version(conn, &checked.name, Some(&checked.requested_version))
+        .and_then(|matched| matched.assume_exact())
+    {
+        Ok(matched) => matched.into_parts(),
+        // database errors fail the whole request
+        Err(Nope::InternalServerError) => return Err(Nope::InternalServerError.into()),
+        Err(err) => {
+            checked.error = Some(err.to_string());
+            return Ok(checked);
+        }
+    };
+
+    checked.build_status = conn
+        .query_opt(
+            "SELECT build_status
+             FROM builds
+             WHERE rid = $1
+             ORDER BY build_time DESC
+             LIMIT 1",
+            &[&id],
+        )
+        .context("could not load build status")?
+        .map(|row| row.get(0));
+    checked.version = Some(version);
+    Ok(checked)
+}
+
+/// The doc build status of several releases in a single request, e.g. for all dependencies of
+/// a project. Entries that can't be found are reported with an `error` instead of failing the
+/// whole request.
+pub(crate) async fn check_handler(
+    Extension(pool): Extension<Pool>,
+    Query(params): Query<CheckParams>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let releases = parse_checked_releases(params.crates.as_deref().unwrap_or_default());
+    if releases.is_empty() {
+        return Err(AxumNope::BadRequest("`crates` must list at least one crate".into()).into());
+    }
+    if releases.len() > MAX_CHECKED_RELEASES {
+        return Err(AxumNope::BadRequest(format!(
+            "`crates` can list at most {} crates",
+            MAX_CHECKED_RELEASES
+        ))
+        .into());
+    }
+
+    let checked = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        releases
+            .into_iter()
+            .map(|(name, version)| check_release(&mut conn, name, version))
+            .collect::<Result<Vec<_>>>()
+    })
+    .await?;
+
+    // build statuses change with every build, so they can only be cached shortly
+    Ok((Extension(CachePolicy::ShortInCdn), Json(checked)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::index::api::CrateOwner;
     use crate::test::{
-        assert_redirect, assert_redirect_unchecked, assert_success, wrapper, FakeBuild,
-        TestFrontend,
+        assert_cache_control, assert_redirect, assert_redirect_unchecked, assert_success, wrapper,
+        FakeBuild, TestFrontend,
     };
     use anyhow::Error;
     use chrono::{Duration, TimeZone};
@@ -777,6 +2087,62 @@ mod tests {
         })
     }
 
+    #[test]
+    fn search_suggest() {
+        wrapper(|env| {
+            env.fake_release().name("serde").create()?;
+            env.fake_release().name("serde_json").create()?;
+            env.fake_release().name("serde-yaml").create()?;
+            env.fake_release().name("sered").create()?;
+            env.fake_release().name("tokio").create()?;
+            env.db().conn().execute(
+                "UPDATE releases SET downloads = 100
+                 FROM crates
+                 WHERE crates.id = releases.crate_id AND crates.name = 'serde_json'",
+                &[],
+            )?;
+
+            let response = env
+                .frontend()
+                .get("/releases/search/suggest?q=Serde_")
+                .send()?;
+            assert_cache_control(&response, CachePolicy::NoCaching, &env.config());
+            let suggestions: Vec<String> = response.json()?;
+            assert_eq!(suggestions, vec!["serde_json", "serde-yaml"]);
+
+            let suggestions: Vec<String> = env
+                .frontend()
+                .get("/releases/search/suggest?q=ser")
+                .send()?
+                .json()?;
+            assert_eq!(
+                suggestions,
+                vec!["serde_json", "serde", "serde-yaml", "sered"]
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test_case("")]
+    #[test_case("%20%20")]
+    #[test_case("s%25")]
+    fn search_suggest_invalid_query(query: &str) {
+        wrapper(|env| {
+            env.fake_release().name("serde").create()?;
+
+            let response = env
+                .frontend()
+                .get(&format!("/releases/search/suggest?q={}", query))
+                .send()?;
+            assert!(response.status().is_success());
+            let suggestions: Vec<String> = response.json()?;
+            assert!(suggestions.is_empty());
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn search_im_feeling_lucky_with_query_redirect_to_crate_page() {
         wrapper(|env| {
@@ -797,17 +2163,59 @@ mod tests {
     }
 
     #[test]
-    fn search_im_feeling_lucky_with_query_redirect_to_docs() {
+    fn search_im_feeling_lucky_with_query_redirect_to_docs() {
+        wrapper(|env| {
+            let web = env.frontend();
+            env.fake_release().name("some_random_crate").create()?;
+            env.fake_release().name("some_other_crate").create()?;
+
+            assert_redirect(
+                "/releases/search?query=some_random_crate&i-am-feeling-lucky=1",
+                "/some_random_crate/1.0.0/some_random_crate/",
+                web,
+            )?;
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn crate_docs_redirect() {
+        wrapper(|env| {
+            let web = env.frontend();
+            env.fake_release()
+                .name("some_crate")
+                .version("0.1.0")
+                .create()?;
+            env.fake_release()
+                .name("some_crate")
+                .version("0.2.0")
+                .create()?;
+
+            assert_redirect("/~/some_crate", "/some_crate/0.2.0/some_crate/", web)?;
+            assert_redirect("/~/some-crate", "/some_crate/0.2.0/some_crate/", web)?;
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn crate_docs_redirect_without_docs() {
+        wrapper(|env| {
+            let web = env.frontend();
+            env.fake_release()
+                .name("some_crate")
+                .build_result_failed()
+                .create()?;
+
+            assert_redirect("/~/some_crate", "/crate/some_crate/1.0.0", web)?;
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn crate_docs_redirect_unknown_crate() {
         wrapper(|env| {
-            let web = env.frontend();
-            env.fake_release().name("some_random_crate").create()?;
-            env.fake_release().name("some_other_crate").create()?;
-
-            assert_redirect(
-                "/releases/search?query=some_random_crate&i-am-feeling-lucky=1",
-                "/some_random_crate/1.0.0/some_random_crate/",
-                web,
-            )?;
+            let response = env.frontend().get("/~/some_crate").send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
             Ok(())
         })
     }
@@ -1115,6 +2523,183 @@ mod tests {
         })
     }
 
+    #[test_case("serde", "serde", &[], &[]; "no qualifiers")]
+    #[test_case(
+        "owner:dtolnay serde json",
+        "serde json",
+        &["dtolnay"],
+        &[];
+        "owner"
+    )]
+    #[test_case(
+        "parser keyword:Command-Line owner:Foo owner:bar",
+        "parser",
+        &["foo", "bar"],
+        &["command-line"];
+        "combined"
+    )]
+    #[test_case("keyword:parser", "", &[], &["parser"]; "only qualifiers")]
+    #[test_case("owner: keyword:", "owner: keyword:", &[], &[]; "empty qualifiers")]
+    #[test_case("std::fmt", "std::fmt", &[], &[]; "path")]
+    fn parse_search_qualifiers(query: &str, term: &str, owners: &[&str], keywords: &[&str]) {
+        let (parsed_term, qualifiers) = SearchQualifiers::parse(query);
+        assert_eq!(parsed_term, term);
+        assert_eq!(qualifiers.owners, owners);
+        assert_eq!(qualifiers.keywords, keywords);
+    }
+
+    #[test]
+    fn search_qualifiers_to_query() {
+        let (term, qualifiers) = SearchQualifiers::parse("keyword:cli owner:foo parser");
+        assert_eq!(qualifiers.to_query(&term), "parser owner:foo keyword:cli");
+        assert_eq!(qualifiers.to_query(""), "owner:foo keyword:cli");
+    }
+
+    fn owner(login: &str) -> CrateOwner {
+        CrateOwner {
+            login: login.into(),
+            avatar: format!("https://example.org/{}", login),
+            name: login.into(),
+            email: format!("{}@example.org", login),
+        }
+    }
+
+    fn mock_crates_io_search(query: &str, names: &[&str]) -> mockito::Mock {
+        mock("GET", "/api/v1/crates")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("q".into(), query.into()),
+                Matcher::UrlEncoded("per_page".into(), "30".into()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({
+                    "crates": names.iter().map(|name| json!({ "name": name })).collect::<Vec<_>>(),
+                    "meta": {
+                        "next_page": "?q=parser&page=2",
+                        "prev_page": null,
+                    }
+                })
+                .to_string(),
+            )
+            .create()
+    }
+
+    #[test]
+    fn search_with_qualifiers() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("parser_a")
+                .add_owner(owner("foo"))
+                .keywords(vec!["cli".into(), "parsing".into()])
+                .create()?;
+            env.fake_release()
+                .name("parser_b")
+                .add_owner(owner("foo"))
+                .add_owner(owner("bar"))
+                .keywords(vec!["parsing".into()])
+                .create()?;
+            env.fake_release()
+                .name("parser_c")
+                .add_owner(owner("bar"))
+                .keywords(vec!["cli".into()])
+                .create()?;
+            let web = env.frontend();
+            let _m = mock_crates_io_search("parser", &["parser_c", "parser_b", "parser_a"]);
+
+            for (query, expected) in [
+                ("parser", &["parser_c", "parser_b", "parser_a"][..]),
+                ("parser owner:Foo", &["parser_b", "parser_a"]),
+                ("owner:foo parser keyword:cli", &["parser_a"]),
+                ("parser owner:foo owner:bar", &["parser_b"]),
+                ("parser keyword:cli keyword:parsing", &["parser_a"]),
+                ("parser owner:bar keyword:cli keyword:parsing", &[]),
+                ("parser owner:unknown", &[]),
+            ] {
+                let links = get_release_links(
+                    &format!(
+                        "/releases/search?{}",
+                        form_urlencoded::Serializer::new(String::new())
+                            .append_pair("query", query)
+                            .finish()
+                    ),
+                    web,
+                )?;
+                let expected: Vec<_> = expected
+                    .iter()
+                    .map(|name| format!("/{0}/1.0.0/{0}/", name))
+                    .collect();
+                assert_eq!(links, expected, "{}", query);
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn search_with_qualifiers_keeps_them_in_pagination() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("parser_a")
+                .add_owner(owner("foo"))
+                .create()?;
+            let web = env.frontend();
+            let _m = mock_crates_io_search("parser", &["parser_a"]);
+
+            let response = web
+                .get("/releases/search?query=parser+owner%3Afoo")
+                .send()?;
+            assert!(response.status().is_success());
+            let page = kuchiki::parse_html().one(response.text()?);
+            assert_eq!(
+                page.select_first("title").unwrap().text_contents(),
+                "Search results for 'parser owner:foo'"
+            );
+
+            let next_page = format!(
+                "/releases/search?paginate={}&query=owner%3Afoo",
+                base64::encode("?q=parser&page=2")
+            );
+            assert!(page
+                .select("a")
+                .unwrap()
+                .any(|el| el.attributes.borrow().get("href") == Some(next_page.as_str())));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn search_only_qualifiers() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("parser_b")
+                .add_owner(owner("foo"))
+                .keywords(vec!["cli".into()])
+                .create()?;
+            env.fake_release()
+                .name("parser_a")
+                .add_owner(owner("foo"))
+                .create()?;
+            env.fake_release()
+                .name("parser_c")
+                .add_owner(owner("bar"))
+                .keywords(vec!["cli".into()])
+                .create()?;
+            let web = env.frontend();
+
+            // without a search term crates.io isn't asked at all
+            assert_eq!(
+                get_release_links("/releases/search?query=owner%3Afoo", web)?,
+                vec!["/parser_a/1.0.0/parser_a/", "/parser_b/1.0.0/parser_b/"]
+            );
+            assert_eq!(
+                get_release_links("/releases/search?query=owner%3Afoo+keyword%3Acli", web)?,
+                vec!["/parser_b/1.0.0/parser_b/"]
+            );
+            assert!(get_release_links("/releases/search?query=keyword%3Aunknown", web)?.is_empty());
+            Ok(())
+        })
+    }
+
     fn get_release_links(path: &str, web: &TestFrontend) -> Result<Vec<String>, Error> {
         let response = web.get(path).send()?;
         assert!(response.status().is_success());
@@ -1348,6 +2933,75 @@ mod tests {
         })
     }
 
+    #[test]
+    fn release_activity_json() {
+        wrapper(|env| {
+            let web = env.frontend();
+
+            env.fake_release()
+                .name("some_random_crate_yesterday")
+                .release_time(Utc::now() - Duration::days(1))
+                .create()?;
+            env.fake_release()
+                .name("some_random_crate_that_failed_yesterday")
+                .build_result_failed()
+                .release_time(Utc::now() - Duration::days(1))
+                .create()?;
+
+            let response = web.get("/releases/activity?format=json").send()?;
+            assert!(response.status().is_success());
+            let value: serde_json::Value = response.json()?;
+
+            let releases = value["releases"].as_array().unwrap();
+            let failures = value["failures"].as_array().unwrap();
+            assert_eq!(releases.len(), 30);
+            assert_eq!(failures.len(), 30);
+
+            let yesterday = (Utc::now() - Duration::days(1))
+                .date_naive()
+                .format("%Y-%m-%d")
+                .to_string();
+            assert_eq!(
+                releases.last().unwrap(),
+                &serde_json::json!({ "date": yesterday, "count": 2 })
+            );
+            assert_eq!(
+                failures.last().unwrap(),
+                &serde_json::json!({ "date": yesterday, "count": 1 })
+            );
+            assert_eq!(releases[0]["count"], 0);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn release_activity_days() {
+        wrapper(|env| {
+            let web = env.frontend();
+
+            for (days, expected) in &[("7", 7), ("365", 365), ("10000", 365), ("0", 1)] {
+                let value: serde_json::Value = web
+                    .get(&format!("/releases/activity?format=json&days={}", days))
+                    .send()?
+                    .json()?;
+                assert_eq!(value["releases"].as_array().unwrap().len(), *expected);
+            }
+
+            let response = web.get("/releases/activity?days=7").send()?;
+            assert!(response.status().is_success());
+            let empty_data = format!("data: [{}]", vec!["0"; 7].join(","));
+            assert_eq!(response.text()?.matches(&empty_data).count(), 2);
+
+            assert_eq!(
+                web.get("/releases/activity?format=csv").send()?.status(),
+                400
+            );
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn release_feed() {
         wrapper(|env| {
@@ -1363,6 +3017,66 @@ mod tests {
         })
     }
 
+    #[test]
+    fn new_crates() {
+        wrapper(|env| {
+            let days_ago = |days| Utc::now() - Duration::days(days);
+            for (name, version, published) in [
+                ("brand-new", "0.1.0", days_ago(2)),
+                // new versions of crates published before
+                ("grown", "0.1.0", days_ago(100)),
+                ("grown", "0.2.0", days_ago(1)),
+                ("old", "1.0.0", days_ago(100)),
+                // only the first version of new crates
+                ("two-versions", "0.1.0", days_ago(5)),
+                ("two-versions", "0.2.0", days_ago(1)),
+            ] {
+                env.fake_release()
+                    .name(name)
+                    .version(version)
+                    .release_time(published)
+                    .create()?;
+            }
+            let web = env.frontend();
+            let expected = vec!["brand-new-0.1.0", "two-versions-0.1.0"];
+
+            let page = kuchiki::parse_html().one(web.get("/releases/new-crates").send()?.text()?);
+            let listed: Vec<_> = page
+                .select(".recent-releases-container li .name")
+                .unwrap()
+                .map(|node| node.text_contents().trim().to_owned())
+                .collect();
+            assert_eq!(listed, expected);
+
+            let response = web.get("/releases/new-crates/feed").send()?;
+            assert!(response.status().is_success());
+            let feed = response.text()?;
+            assert!(feed.contains("/releases/new-crates/feed\" rel=\"self\""));
+            let titles: Vec<_> = feed
+                .split("<title>")
+                .skip(2)
+                .filter_map(|entry| entry.split_once("</title>"))
+                .map(|(title, _)| title)
+                .collect();
+            assert_eq!(titles, expected);
+
+            // the feed of all releases still has the new versions
+            assert!(web
+                .get("/releases/feed")
+                .send()?
+                .text()?
+                .contains("<title>grown-0.2.0</title>"));
+
+            assert_eq!(
+                web.get(&format!("/releases/new-crates/{}", i64::MAX))
+                    .send()?
+                    .status(),
+                400
+            );
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_deployment_queue() {
         wrapper(|env| {
@@ -1388,59 +3102,321 @@ mod tests {
                 .expect("missing list items")
                 .collect::<Vec<_>>();
 
-            assert_eq!(items.len(), 1);
-            let a = items[0].as_node().select_first("a").expect("missing link");
+            assert_eq!(items.len(), 1);
+            let a = items[0].as_node().select_first("a").expect("missing link");
+
+            assert!(a.text_contents().contains("krate_2"));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_releases_queue() {
+        wrapper(|env| {
+            let queue = env.build_queue();
+            let web = env.frontend();
+
+            let empty = kuchiki::parse_html().one(web.get("/releases/queue").send()?.text()?);
+            assert!(empty
+                .select(".queue-list > strong")
+                .expect("missing heading")
+                .any(|el| el.text_contents().contains("nothing")));
+
+            assert!(!empty
+                .select(".release > strong")
+                .expect("missing heading")
+                .any(|el| el.text_contents().contains("active CDN deployments")));
+
+            queue.add_crate("foo", "1.0.0", 0, None)?;
+            queue.add_crate("bar", "0.1.0", -10, None)?;
+            queue.add_crate("baz", "0.0.1", 10, None)?;
+
+            let full = kuchiki::parse_html().one(web.get("/releases/queue").send()?.text()?);
+            let items = full
+                .select(".queue-list > li")
+                .expect("missing list items")
+                .collect::<Vec<_>>();
+
+            assert_eq!(items.len(), 3);
+            let expected = [
+                ("bar", "0.1.0", Some(10)),
+                ("foo", "1.0.0", None),
+                ("baz", "0.0.1", Some(-10)),
+            ];
+            for (li, expected) in items.iter().zip(&expected) {
+                let a = li.as_node().select_first("a").expect("missing link");
+                assert!(a.text_contents().contains(expected.0));
+                assert!(a.text_contents().contains(expected.1));
+
+                if let Some(priority) = expected.2 {
+                    assert!(li
+                        .text_contents()
+                        .contains(&format!("priority: {}", priority)));
+                }
+            }
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_dashboard_json() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .github_stats("ghost/foo", 10, 10, 10)
+                .create()?;
+            env.fake_release()
+                .name("bar")
+                .version("0.1.0")
+                .github_stats("ghost/bar", 20, 20, 20)
+                .build_result_failed()
+                .create()?;
+            env.build_queue().add_crate("baz", "1.0.0", 0, None)?;
+            let web = env.frontend();
+
+            let response = web.get("/releases/dashboard.json").send()?;
+            assert!(response.status().is_success());
+            assert_cache_control(&response, CachePolicy::ShortInCdn, &env.config());
+            let value: serde_json::Value = response.json()?;
+            let names = |key: &str| -> Vec<String> {
+                value[key]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|release| release["name"].as_str().unwrap().to_owned())
+                    .collect()
+            };
+            assert_eq!(names("recent_releases").len(), 2);
+            assert_eq!(names("recent_failures"), ["bar"]);
+            assert_eq!(names("most_starred"), ["bar", "foo"]);
+            assert_eq!(names("failures_by_stars"), ["bar"]);
+            assert_eq!(value["queue_length"], 1);
+
+            let value: serde_json::Value = web
+                .get("/releases/dashboard.json?limit=1&page=2")
+                .send()?
+                .json()?;
+            assert_eq!(value["most_starred"][0]["name"], "foo");
+            assert_eq!(value["recent_failures"], json!([]));
+
+            for query in [
+                "limit=0",
+                "limit=1000",
+                "page=0",
+                "page=9223372036854775807",
+            ] {
+                let response = web
+                    .get(&format!("/releases/dashboard.json?{}", query))
+                    .send()?;
+                assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn recent_failures_with_category() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .builds(vec![FakeBuild::default()
+                    .failure(BuildFailure::Timeout)
+                    .build_time(Utc.with_ymd_and_hms(2020, 1, 2, 0, 0, 0).unwrap())])
+                .create()?;
+            env.fake_release()
+                .name("bar")
+                .version("0.1.0")
+                .builds(vec![FakeBuild::default()
+                    .failure(BuildFailure::MissingDependency)
+                    .build_time(Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap())])
+                .create()?;
+            // failed before the category was recorded
+            env.fake_release()
+                .name("baz")
+                .version("0.1.0")
+                .build_result_failed()
+                .create()?;
+            env.fake_release().name("qux").version("0.1.0").create()?;
+            let web = env.frontend();
+
+            let response = web.get("/releases/recent-failures.json").send()?;
+            assert!(response.status().is_success());
+            assert_cache_control(&response, CachePolicy::ShortInCdn, &env.config());
+            let value: serde_json::Value = response.json()?;
+            let categories: Vec<_> = value
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|release| (release["name"].clone(), release["failure_category"].clone()))
+                .collect();
+            assert_eq!(
+                categories,
+                [
+                    (json!("baz"), json!("other")),
+                    (json!("foo"), json!("timeout")),
+                    (json!("bar"), json!("missing-dependency")),
+                ]
+            );
+
+            let page =
+                kuchiki::parse_html().one(web.get("/releases/recent-failures").send()?.text()?);
+            let categories: Vec<_> = page
+                .select(".recent-releases-container .failure-category")
+                .unwrap()
+                .map(|node| node.text_contents().trim().to_owned())
+                .collect();
+            assert_eq!(categories, ["other", "timeout", "missing dependency"]);
+
+            let response = web.get("/releases/recent-failures.json?page=0").send()?;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn check_releases_with_unknown_entries() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("1.0.3").create()?;
+            env.fake_release()
+                .name("bar")
+                .version("2.1.0")
+                .build_result_failed()
+                .create()?;
+            let web = env.frontend();
+
+            let response = web
+                .get("/releases/check?crates=foo@1.0,bar@2.1.0,baz@1.0,foo@2,foo,foo@%3E%3Dx")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_cache_control(&response, CachePolicy::ShortInCdn, &env.config());
+            assert_eq!(
+                response.json::<serde_json::Value>()?,
+                json!([
+                    {
+                        "crate": "foo",
+                        "requested_version": "1.0",
+                        "version": "1.0.3",
+                        "build_status": true,
+                        "error": null,
+                    },
+                    {
+                        "crate": "bar",
+                        "requested_version": "2.1.0",
+                        "version": "2.1.0",
+                        "build_status": false,
+                        "error": null,
+                    },
+                    {
+                        "crate": "baz",
+                        "requested_version": "1.0",
+                        "version": null,
+                        "build_status": null,
+                        "error": "Requested crate not found",
+                    },
+                    {
+                        "crate": "foo",
+                        "requested_version": "2",
+                        "version": null,
+                        "build_status": null,
+                        "error": "Requested crate does not have specified version",
+                    },
+                    {
+                        "crate": "foo",
+                        "requested_version": "latest",
+                        "version": "1.0.3",
+                        "build_status": true,
+                        "error": null,
+                    },
+                    {
+                        "crate": "foo",
+                        "requested_version": ">=x",
+                        "version": null,
+                        "build_status": null,
+                        "error": "Requested version range is invalid",
+                    },
+                ])
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn check_releases_bounds_entries() {
+        wrapper(|env| {
+            let web = env.frontend();
 
-            assert!(a.text_contents().contains("krate_2"));
+            let too_many = vec!["foo@1.0"; MAX_CHECKED_RELEASES + 1].join(",");
+            for query in ["", "crates=", "crates=,,", &format!("crates={}", too_many)] {
+                let response = web.get(&format!("/releases/check?{}", query)).send()?;
+                assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+            }
 
+            let at_most = vec!["foo@1.0"; MAX_CHECKED_RELEASES].join(",");
+            let response = web
+                .get(&format!("/releases/check?crates={}", at_most))
+                .send()?;
+            assert_eq!(response.status(), StatusCode::OK);
             Ok(())
-        });
+        })
     }
 
     #[test]
-    fn test_releases_queue() {
+    fn test_releases_queue_estimates() {
         wrapper(|env| {
             let queue = env.build_queue();
             let web = env.frontend();
 
-            let empty = kuchiki::parse_html().one(web.get("/releases/queue").send()?.text()?);
-            assert!(empty
-                .select(".queue-list > strong")
-                .expect("missing heading")
-                .any(|el| el.text_contents().contains("nothing")));
-
-            assert!(!empty
-                .select(".release > strong")
-                .expect("missing heading")
-                .any(|el| el.text_contents().contains("active CDN deployments")));
-
             queue.add_crate("foo", "1.0.0", 0, None)?;
-            queue.add_crate("bar", "0.1.0", -10, None)?;
-            queue.add_crate("baz", "0.0.1", 10, None)?;
-
-            let full = kuchiki::parse_html().one(web.get("/releases/queue").send()?.text()?);
-            let items = full
-                .select(".queue-list > li")
-                .expect("missing list items")
-                .collect::<Vec<_>>();
+            queue.add_crate("bar", "0.1.0", 0, None)?;
+
+            // not enough recent builds to estimate anything
+            let page = kuchiki::parse_html().one(web.get("/releases/queue").send()?.text()?);
+            assert!(page
+                .select(".queue-list > li .estimated-wait")
+                .unwrap()
+                .all(|el| el.text_contents().contains("unknown")));
+            let value: serde_json::Value = web.get("/releases/queue.json").send()?.json()?;
+            assert_eq!(value["average_build_duration"], serde_json::Value::Null);
+            assert_eq!(value["queue"][0]["estimated_wait"], serde_json::Value::Null);
+
+            // a build every 2 minutes on a single server
+            env.fake_release()
+                .name("built")
+                .version("0.1.0")
+                .builds(
+                    (0..6)
+                        .map(|i| {
+                            FakeBuild::default().build_time(Utc::now() - Duration::minutes(2 * i))
+                        })
+                        .collect(),
+                )
+                .create()?;
 
-            assert_eq!(items.len(), 3);
-            let expected = [
-                ("bar", "0.1.0", Some(10)),
-                ("foo", "1.0.0", None),
-                ("baz", "0.0.1", Some(-10)),
-            ];
-            for (li, expected) in items.iter().zip(&expected) {
-                let a = li.as_node().select_first("a").expect("missing link");
-                assert!(a.text_contents().contains(expected.0));
-                assert!(a.text_contents().contains(expected.1));
+            let response = web.get("/releases/queue.json").send()?;
+            assert_cache_control(&response, CachePolicy::NoCaching, &env.config());
+            let value: serde_json::Value = response.json()?;
+            assert_eq!(value["average_build_duration"], 120);
+            assert_eq!(value["active_builders"], 1);
+            assert_eq!(
+                value["queue"],
+                json!([
+                    {"name": "foo", "version": "1.0.0", "priority": 0, "registry": null, "estimated_wait": 120},
+                    {"name": "bar", "version": "0.1.0", "priority": 0, "registry": null, "estimated_wait": 240},
+                ])
+            );
 
-                if let Some(priority) = expected.2 {
-                    assert!(li
-                        .text_contents()
-                        .contains(&format!("priority: {}", priority)));
-                }
-            }
+            let page = kuchiki::parse_html().one(web.get("/releases/queue").send()?.text()?);
+            let estimates: Vec<_> = page
+                .select(".queue-list > li .estimated-wait")
+                .unwrap()
+                .map(|el| el.text_contents())
+                .collect();
+            assert!(estimates[0].contains("~2 minutes"));
+            assert!(estimates[1].contains("~4 minutes"));
 
             Ok(())
         });
@@ -1511,10 +3487,12 @@ mod tests {
                     .zip(
                         [
                             "Recent",
+                            "New Crates",
                             "Stars",
                             "Recent Failures",
                             "Failures By Stars",
                             "Activity",
+                            "Categories",
                             "Queue",
                         ]
                         .iter(),
@@ -1536,10 +3514,12 @@ mod tests {
 
             for url in &[
                 "/releases",
+                "/releases/new-crates",
                 "/releases/stars",
                 "/releases/recent-failures",
                 "/releases/failures",
                 "/releases/activity",
+                "/releases/categories",
                 "/releases/queue",
             ] {
                 tester(url);
@@ -1549,6 +3529,279 @@ mod tests {
         });
     }
 
+    #[test]
+    fn watched_releases() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .release_time(Utc.with_ymd_and_hms(2020, 4, 16, 4, 33, 50).unwrap())
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.2.0")
+                .release_time(Utc.with_ymd_and_hms(2020, 6, 16, 4, 33, 50).unwrap())
+                .create()?;
+            env.fake_release()
+                .name("bar_baz")
+                .version("1.0.0")
+                .release_time(Utc.with_ymd_and_hms(2020, 5, 16, 4, 33, 50).unwrap())
+                .build_result_failed()
+                .create()?;
+            env.fake_release()
+                .name("unwatched")
+                .version("1.0.0")
+                .release_time(Utc.with_ymd_and_hms(2020, 7, 16, 4, 33, 50).unwrap())
+                .create()?;
+
+            let web = env.frontend();
+            assert_eq!(
+                get_release_links("/releases/watch?crates=foo,%20bar-baz,foo", web)?,
+                ["/foo/0.2.0/foo/", "/crate/bar_baz/1.0.0", "/foo/0.1.0/foo/",]
+            );
+            // names are deduplicated like crate names are normalized
+            assert!(web
+                .get("/releases/watch?crates=foo,bar-baz,FOO,bar_baz")
+                .send()?
+                .text()?
+                .contains("Recent releases of 2 watched crates"));
+
+            let response = web
+                .get("/releases/watch?crates=foo,bar_baz&format=json")
+                .send()?;
+            assert!(response.status().is_success());
+            let value: serde_json::Value = response.json()?;
+            let releases: Vec<_> = value["releases"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|release| {
+                    (
+                        release["name"].as_str().unwrap(),
+                        release["version"].as_str().unwrap(),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                releases,
+                [("foo", "0.2.0"), ("bar_baz", "1.0.0"), ("foo", "0.1.0")]
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn watched_releases_invalid_lists() {
+        wrapper(|env| {
+            let web = env.frontend();
+
+            assert_eq!(web.get("/releases/watch").send()?.status(), 400);
+            assert_eq!(
+                web.get("/releases/watch?crates=,%20,").send()?.status(),
+                400
+            );
+
+            let too_many = (0..=WATCH_MAX_CRATES)
+                .map(|i| format!("crate{}", i))
+                .collect::<Vec<_>>()
+                .join(",");
+            let response = web
+                .get(&format!("/releases/watch?crates={}&format=json", too_many))
+                .send()?;
+            assert_eq!(response.status(), 400);
+            assert!(response.json::<serde_json::Value>()?["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("too many crates"));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn category_names() {
+        assert_eq!(category_display_name("parsing"), "Parsing");
+        assert_eq!(
+            category_display_name("command-line-utilities"),
+            "Command line utilities"
+        );
+        assert_eq!(
+            category_display_name("development-tools::testing"),
+            "Development tools › Testing"
+        );
+    }
+
+    #[test]
+    fn categories() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .categories(vec!["parsing".into(), "encoding".into()])
+                .create()?;
+            env.fake_release()
+                .name("bar")
+                .version("0.1.0")
+                .categories(vec!["parsing".into()])
+                .create()?;
+            env.fake_release()
+                .name("baz")
+                .version("0.1.0")
+                .categories(vec!["parsing".into()])
+                .build_result_failed()
+                .create()?;
+            // only the latest release counts
+            env.fake_release()
+                .name("old")
+                .version("0.1.0")
+                .categories(vec!["parsing".into()])
+                .create()?;
+            env.fake_release().name("old").version("0.2.0").create()?;
+            env.db().conn().execute(
+                "UPDATE releases SET downloads = 100
+                 FROM crates
+                 WHERE crates.id = releases.crate_id AND crates.name = 'bar'",
+                &[],
+            )?;
+
+            let web = env.frontend();
+            assert_eq!(
+                get_release_links("/releases/categories", web)?,
+                [
+                    "/releases/categories/encoding",
+                    "/releases/categories/parsing"
+                ]
+            );
+            assert_eq!(
+                get_release_links("/releases/categories/parsing", web)?,
+                ["/bar/0.1.0/bar/", "/foo/0.1.0/foo/"]
+            );
+
+            let value: serde_json::Value =
+                web.get("/releases/categories?format=json").send()?.json()?;
+            assert_eq!(
+                value["categories"],
+                serde_json::json!([
+                    {"slug": "encoding", "name": "Encoding", "crates": 1},
+                    {"slug": "parsing", "name": "Parsing", "crates": 2},
+                ])
+            );
+
+            let value: serde_json::Value = web
+                .get("/releases/categories/parsing?format=json&page=2")
+                .send()?
+                .json()?;
+            assert_eq!(value["name"], "Parsing");
+            assert_eq!(value["page"], 2);
+            assert_eq!(value["releases"], serde_json::json!([]));
+
+            assert_eq!(
+                web.get("/releases/categories/unknown").send()?.status(),
+                404
+            );
+            let response = web.get("/releases/categories/unknown?format=json").send()?;
+            assert_eq!(response.status(), 404);
+            assert_eq!(
+                response.json::<serde_json::Value>()?["error"]["code"],
+                "resource_not_found"
+            );
+
+            assert_eq!(
+                web.get(&format!("/releases/categories/parsing?page={}", i64::MAX))
+                    .send()?
+                    .status(),
+                400
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn crates_list() {
+        wrapper(|env| {
+            for name in ["d", "b", "a", "c"] {
+                env.fake_release().name(name).version("0.1.0").create()?;
+            }
+            // crates without any documented release aren't listed
+            env.fake_release()
+                .name("failed")
+                .version("0.1.0")
+                .build_result_failed()
+                .create()?;
+            env.fake_release().name("e").version("0.1.0").create()?;
+            env.fake_release()
+                .name("e")
+                .version("0.2.0")
+                .build_result_failed()
+                .create()?;
+
+            let web = env.frontend();
+            let page = |path: &str| -> Result<serde_json::Value, Error> {
+                let response = web.get(path).send()?;
+                assert!(response.status().is_success());
+                Ok(response.json()?)
+            };
+
+            assert_eq!(
+                page("/releases/crates?format=json&limit=2")?,
+                serde_json::json!({
+                    "crates": ["a", "b"],
+                    "total": 5,
+                    "next": "/releases/crates?after=b&limit=2&format=json",
+                })
+            );
+            assert_eq!(
+                page("/releases/crates?after=b&limit=2&format=json")?,
+                serde_json::json!({
+                    "crates": ["c", "d"],
+                    "total": 5,
+                    "next": "/releases/crates?after=d&limit=2&format=json",
+                })
+            );
+            let last = page("/releases/crates?after=d&limit=2&format=json")?;
+            assert_eq!(last["crates"], serde_json::json!(["e"]));
+            assert_eq!(last["next"], serde_json::Value::Null);
+            let exact = page("/releases/crates?after=a&limit=4&format=json")?;
+            assert_eq!(exact["crates"], serde_json::json!(["b", "c", "d", "e"]));
+            assert_eq!(exact["next"], serde_json::Value::Null);
+            assert_eq!(
+                page("/releases/crates?after=e&format=json")?["crates"],
+                serde_json::json!([])
+            );
+
+            assert_eq!(
+                get_release_links("/releases/crates", web)?,
+                [
+                    "/crate/a/latest",
+                    "/crate/b/latest",
+                    "/crate/c/latest",
+                    "/crate/d/latest",
+                    "/crate/e/latest"
+                ]
+            );
+            let html =
+                kuchiki::parse_html().one(web.get("/releases/crates?limit=3").send()?.text()?);
+            let next = html.select_first(".pagination a").unwrap();
+            assert_eq!(
+                next.attributes.borrow().get("href"),
+                Some("/releases/crates?after=c&limit=3")
+            );
+
+            for limit in ["0", "101"] {
+                let response = web
+                    .get(&format!("/releases/crates?format=json&limit={}", limit))
+                    .send()?;
+                assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+                assert_eq!(
+                    response.json::<serde_json::Value>()?["error"]["code"],
+                    "bad_request"
+                );
+            }
+            Ok(())
+        })
+    }
+
     #[test]
     fn check_owner_releases_redirect() {
         wrapper(|env| {
@@ -1558,4 +3811,153 @@ mod tests {
             Ok(())
         });
     }
+
+    #[test]
+    fn owner_page() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .add_owner(owner("someone"))
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.2.0")
+                .add_owner(owner("someone"))
+                .create()?;
+            env.fake_release()
+                .name("bar")
+                .version("1.0.0")
+                .add_owner(owner("someone"))
+                .add_owner(owner("other"))
+                .create()?;
+            env.fake_release()
+                .name("baz")
+                .version("1.0.0")
+                .add_owner(owner("other"))
+                .create()?;
+            env.db()
+                .conn()
+                .execute("UPDATE releases SET downloads = 100", &[])?;
+
+            let web = env.frontend();
+
+            let page = kuchiki::parse_html().one(web.get("/releases/someone").send()?.text()?);
+            assert_eq!(
+                page.select_first("h1 img.owner-avatar")
+                    .unwrap()
+                    .attributes
+                    .borrow()
+                    .get("src"),
+                Some("https://example.org/someone")
+            );
+            assert!(page
+                .select_first(".description")
+                .unwrap()
+                .text_contents()
+                .contains("2 crates, 300 downloads"));
+            assert_eq!(
+                get_release_links("/releases/@someone", web)?,
+                ["/bar/1.0.0/bar/", "/foo/0.2.0/foo/"]
+            );
+
+            let json: serde_json::Value =
+                web.get("/releases/someone?format=json").send()?.json()?;
+            assert_eq!(json["login"], "someone");
+            assert_eq!(json["name"], "someone");
+            assert_eq!(json["avatar"], "https://example.org/someone");
+            assert_eq!(json["kind"], "user");
+            assert_eq!(json["profile_url"], "https://crates.io/users/someone");
+            assert_eq!(json["crates"], 2);
+            assert_eq!(json["total_downloads"], 300);
+            assert_eq!(json["releases"].as_array().unwrap().len(), 2);
+
+            let response = web.get("/releases/nobody?format=json").send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            let json: serde_json::Value = response.json()?;
+            assert_eq!(json["error"]["code"], "owner_not_found");
+
+            assert_eq!(
+                web.get(&format!("/releases/someone/{}", i64::MAX))
+                    .send()?
+                    .status(),
+                StatusCode::BAD_REQUEST
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn owner_page_for_team_without_metadata() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .add_owner(CrateOwner {
+                    login: "github:org:team".into(),
+                    avatar: "".into(),
+                    name: "".into(),
+                    email: "".into(),
+                })
+                .create()?;
+
+            let web = env.frontend();
+
+            let json: serde_json::Value = web
+                .get("/releases/github:org:team?format=json")
+                .send()?
+                .json()?;
+            assert_eq!(json["name"], "github:org:team");
+            assert_eq!(json["avatar"], serde_json::Value::Null);
+            assert_eq!(json["kind"], "team");
+            assert_eq!(
+                json["profile_url"],
+                "https://crates.io/teams/github:org:team"
+            );
+
+            let page =
+                kuchiki::parse_html().one(web.get("/releases/github:org:team").send()?.text()?);
+            assert!(page.select_first("h1 img").is_err());
+            assert!(page.select_first("h1 .owner-avatar").is_ok());
+            assert!(page
+                .select_first(".description")
+                .unwrap()
+                .text_contents()
+                .starts_with("Team github:org:team"));
+            Ok(())
+        });
+    }
+
+    #[test_case("/releases/crates")]
+    #[test_case("/releases/categories")]
+    #[test_case("/releases/categories/parsing")]
+    #[test_case("/releases/someone")]
+    #[test_case("/releases/watch?crates=foo")]
+    #[test_case("/releases/activity")]
+    fn lists_accept_json(path: &str) {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .add_owner(owner("someone"))
+                .categories(vec!["parsing".into()])
+                .create()?;
+            let web = env.frontend();
+
+            let response = web.get(path).header("accept", "application/json").send()?;
+            assert!(response.status().is_success());
+            assert_eq!(response.headers()["content-type"], "application/json");
+            assert!(response
+                .headers()
+                .get_all("vary")
+                .iter()
+                .any(|value| value == "Accept"));
+
+            let separator = if path.contains('?') { '&' } else { '?' };
+            let response = web
+                .get(&format!("{}{}format=xml", path, separator))
+                .send()?;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            Ok(())
+        });
+    }
 }
@@ -0,0 +1,291 @@
+//! Reports of broken docs sent by users, so rendering bugs can be flagged without opening a
+//! GitHub issue.
+//!
+//! Anyone can send a report, rate limited per client IP with `DOCSRS_REPORTS_PER_HOUR`. They are
+//! reviewed at `/-/reports.json`, authenticated with one of the `DOCSRS_REPORT_TOKENS`.
+
+use crate::{
+    db::Pool,
+    utils::spawn_blocking,
+    web::{
+        auth::authenticate_named_token,
+        cache::CachePolicy,
+        client_ip::ClientIp,
+        error::{AxumNope, JsonAxumResult},
+        rate_limit::RateLimiter,
+    },
+    Config,
+};
+use axum::{
+    extract::{Extension, Path, Query},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{net::IpAddr, sync::Arc, time::Instant};
+use tracing::info;
+
+/// Longest accepted reason, in characters
+const MAX_REASON_LEN: usize = 1000;
+/// Longest accepted path, in characters
+const MAX_PATH_LEN: usize = 500;
+/// Most reports returned by `/-/reports.json` at once
+pub(super) const REPORTS_LIMIT: i64 = 100;
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ReportParams {
+    reason: String,
+    /// The page with broken docs, relative to the docs of the release
+    path: Option<String>,
+}
+
+impl ReportParams {
+    /// Returns the trimmed reason and path, or why they are invalid.
+    fn validate(self) -> Result<(String, Option<String>), AxumNope> {
+        let reason = self.reason.trim();
+        if reason.is_empty() {
+            return Err(AxumNope::BadRequest("`reason` can't be empty".into()));
+        }
+        if reason.chars().count() > MAX_REASON_LEN {
+            return Err(AxumNope::BadRequest(format!(
+                "`reason` can't be longer than {} characters",
+                MAX_REASON_LEN
+            )));
+        }
+
+        let path = self
+            .path
+            .as_deref()
+            .map(str::trim)
+            .filter(|path| !path.is_empty());
+        if let Some(path) = path {
+            if path.chars().count() > MAX_PATH_LEN {
+                return Err(AxumNope::BadRequest(format!(
+                    "`path` can't be longer than {} characters",
+                    MAX_PATH_LEN
+                )));
+            }
+        }
+
+        Ok((reason.to_owned(), path.map(str::to_owned)))
+    }
+}
+
+/// Stores a report of broken docs for a release, responds with `204 No Content`.
+pub(crate) async fn report_handler(
+    Path((name, version)): Path<(String, String)>,
+    client_ip: ClientIp,
+    Extension(config): Extension<Arc<Config>>,
+    Extension(pool): Extension<Pool>,
+    Extension(rate_limiter): Extension<Arc<RateLimiter<IpAddr>>>,
+    Json(params): Json<ReportParams>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let (reason, path) = params.validate()?;
+
+    if !rate_limiter.check(client_ip.0, config.reports_per_hour, Instant::now()) {
+        return Err(AxumNope::TooManyRequests.into());
+    }
+
+    spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        // only exact versions can be reported, the docs of a semver requirement can change
+        let inserted = conn.execute(
+            "INSERT INTO doc_reports (rid, reason, path)
+             SELECT releases.id, $3, $4
+             FROM releases
+             INNER JOIN crates ON releases.crate_id = crates.id
+             WHERE crates.name = $1 AND releases.version = $2",
+            &[&name, &version, &reason, &path],
+        )?;
+        if inserted == 0 {
+            return Err(AxumNope::VersionNotFound.into());
+        }
+        info!(%name, %version, ?path, "received report of broken docs");
+        Ok(())
+    })
+    .await?;
+
+    Ok((StatusCode::NO_CONTENT, Extension(CachePolicy::NoCaching)))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ReportsParams {
+    /// Only list the reports of this crate
+    #[serde(rename = "crate")]
+    krate: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Report {
+    id: i32,
+    name: String,
+    version: String,
+    reason: String,
+    path: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+/// Lists the most recent reports of broken docs, authenticated with one of the
+/// `DOCSRS_REPORT_TOKENS` given as bearer token.
+pub(crate) async fn reports_json_handler(
+    headers: HeaderMap,
+    Query(params): Query<ReportsParams>,
+    Extension(config): Extension<Arc<Config>>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
+    authenticate_named_token(&headers, &config.report_tokens)?;
+
+    let limit = params.limit.unwrap_or(REPORTS_LIMIT);
+    if !(1..=REPORTS_LIMIT).contains(&limit) {
+        return Err(AxumNope::BadRequest(format!(
+            "`limit` has to be between 1 and {}",
+            REPORTS_LIMIT
+        ))
+        .into());
+    }
+
+    let reports = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        Ok(conn
+            .query(
+                "SELECT doc_reports.id,
+                    crates.name,
+                    releases.version,
+                    doc_reports.reason,
+                    doc_reports.path,
+                    doc_reports.created_at
+                 FROM doc_reports
+                 INNER JOIN releases ON releases.id = doc_reports.rid
+                 INNER JOIN crates ON crates.id = releases.crate_id
+                 WHERE $1::TEXT IS NULL OR crates.name = $1
+                 ORDER BY doc_reports.created_at DESC, doc_reports.id DESC
+                 LIMIT $2",
+                &[&params.krate, &limit],
+            )?
+            .into_iter()
+            .map(|row| Report {
+                id: row.get("id"),
+                name: row.get("name"),
+                version: row.get("version"),
+                reason: row.get("reason"),
+                path: row.get("path"),
+                created_at: row.get("created_at"),
+            })
+            .collect::<Vec<_>>())
+    })
+    .await?;
+
+    Ok((Extension(CachePolicy::NoCaching), Json(reports)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReportParams;
+    use crate::test::{assert_cache_control, wrapper};
+    use crate::web::cache::CachePolicy;
+    use reqwest::StatusCode;
+    use serde_json::{json, Value};
+    use test_case::test_case;
+
+    #[test_case("  broken  ", Some(" /foo/index.html "), Ok(("broken", Some("/foo/index.html"))))]
+    #[test_case("broken", Some(""), Ok(("broken", None)); "empty path")]
+    #[test_case(" ", None, Err(()); "empty reason")]
+    #[test_case(&"a".repeat(1001), None, Err(()); "long reason")]
+    #[test_case("broken", Some("a".repeat(501).as_str()), Err(()); "long path")]
+    fn validate_params(
+        reason: &str,
+        path: Option<&str>,
+        expected: Result<(&str, Option<&str>), ()>,
+    ) {
+        let params = ReportParams {
+            reason: reason.into(),
+            path: path.map(Into::into),
+        };
+        assert_eq!(
+            params
+                .validate()
+                .as_ref()
+                .map(|(reason, path)| (reason.as_str(), path.as_deref()))
+                .map_err(|_| ()),
+            expected
+        );
+    }
+
+    #[test]
+    fn send_and_review_reports() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.report_tokens = vec!["admin:secret".parse().unwrap()];
+            });
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let web = env.frontend();
+
+            let response = web
+                .post("/crate/foo/0.1.0/report")
+                .json(&json!({ "reason": "the sidebar is empty", "path": "/foo/struct.Foo.html" }))
+                .send()?;
+            assert_eq!(response.status(), StatusCode::NO_CONTENT);
+            assert_cache_control(&response, CachePolicy::NoCaching, &env.config());
+
+            let response = web
+                .post("/crate/foo/0.1.0/report")
+                .json(&json!({ "reason": "" }))
+                .send()?;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+            for path in ["/crate/foo/0.2.0/report", "/crate/foo/0.1/report"] {
+                let response = web.post(path).json(&json!({ "reason": "broken" })).send()?;
+                assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            }
+
+            assert_eq!(
+                web.get("/-/reports.json").send()?.status(),
+                StatusCode::UNAUTHORIZED
+            );
+            let response = web.get("/-/reports.json").bearer_auth("secret").send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_cache_control(&response, CachePolicy::NoCaching, &env.config());
+            let reports: Value = response.json()?;
+            let reports = reports.as_array().unwrap();
+            assert_eq!(reports.len(), 1);
+            assert_eq!(reports[0]["name"], "foo");
+            assert_eq!(reports[0]["version"], "0.1.0");
+            assert_eq!(reports[0]["reason"], "the sidebar is empty");
+            assert_eq!(reports[0]["path"], "/foo/struct.Foo.html");
+
+            let reports: Value = web
+                .get("/-/reports.json?crate=bar")
+                .bearer_auth("secret")
+                .send()?
+                .json()?;
+            assert_eq!(reports, json!([]));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn reports_are_rate_limited() {
+        wrapper(|env| {
+            env.override_config(|config| config.reports_per_hour = 2);
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let web = env.frontend();
+
+            let report = || {
+                web.post("/crate/foo/0.1.0/report")
+                    .json(&json!({ "reason": "broken" }))
+                    .send()
+            };
+            assert_eq!(report()?.status(), StatusCode::NO_CONTENT);
+            assert_eq!(report()?.status(), StatusCode::NO_CONTENT);
+
+            let response = report()?;
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+            let error: Value = response.json()?;
+            assert_eq!(error["error"]["code"], "too_many_requests");
+            Ok(())
+        })
+    }
+}
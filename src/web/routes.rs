@@ -1,22 +1,38 @@
 use super::{
-    cache::CachePolicy, error::AxumNope, metrics::request_recorder, metrics::RequestRecorder,
+    auth::require_named_token,
+    cache::CachePolicy,
+    cors::{cors_middleware, preflight_handler},
+    error::{AxumNope, JsonAxumNope},
+    metrics::request_recorder,
+    metrics::RequestRecorder,
+    output_format::vary_accept_middleware,
+    rate_limit::RateLimiter,
 };
+use crate::{config::NamedToken, Config};
 use axum::{
+    body::Body,
+    extract::Extension,
     handler::Handler as AxumHandler,
-    http::Request as AxumHttpRequest,
+    http::{header::CONTENT_LENGTH, Request as AxumHttpRequest},
     middleware::{self, Next},
-    response::{IntoResponse, Redirect},
-    routing::get,
+    response::{IntoResponse, Redirect, Response as AxumResponse},
     routing::MethodRouter,
-    Router as AxumRouter,
+    routing::{delete, get, post},
+    Json, Router as AxumRouter,
 };
 use axum_extra::routing::RouterExt;
+use hyper::body::HttpBody as _;
 use iron::middleware::Handler;
 use router::Router as IronRouter;
-use std::{collections::HashSet, convert::Infallible};
-use tracing::{debug, instrument};
-
-const INTERNAL_PREFIXES: &[&str] = &["-", "about", "crate", "releases", "sitemap.xml"];
+use serde::Serialize;
+use std::{
+    collections::{BTreeSet, HashSet},
+    convert::Infallible,
+    net::IpAddr,
+    sync::Arc,
+    time::Duration,
+};
+use tracing::{debug, instrument, warn};
 
 #[instrument(skip_all)]
 fn get_static<H, T, S, B>(handler: H) -> MethodRouter<S, B, Infallible>
@@ -44,6 +60,138 @@ where
     }))
 }
 
+/// Makes a JSON API route callable from the `cors_allowed_origins`, answering preflight requests
+/// and adding the CORS headers, see [`cors_middleware`].
+fn json_api<S, B>(method_router: MethodRouter<S, B, Infallible>) -> MethodRouter<S, B, Infallible>
+where
+    B: Send + 'static + hyper::body::HttpBody,
+    S: Clone + Send + Sync + 'static,
+{
+    method_router
+        .options(preflight_handler)
+        .route_layer(middleware::from_fn(cors_middleware))
+}
+
+#[instrument(skip_all)]
+fn post_internal<H, T, S>(handler: H) -> MethodRouter<S, Body, Infallible>
+where
+    H: AxumHandler<T, S, Body>,
+    T: 'static,
+    S: Clone + Send + Sync + 'static,
+{
+    post(handler)
+        .route_layer(middleware::from_fn(|request, next| async {
+            request_recorder(request, next, None).await
+        }))
+        .route_layer(middleware::from_fn(write_limits_middleware))
+}
+
+/// Like [`post_internal`], for uploads whose body can be up to `max_body_size` instead of the
+/// `max_request_body_size`. The uploads are authenticated with one of the `tokens` before their
+/// body is read, see [`require_named_token`].
+#[instrument(skip_all)]
+fn post_upload_internal<H, T, S>(
+    handler: H,
+    max_body_size: fn(&Config) -> usize,
+    tokens: fn(&Config) -> &[NamedToken],
+) -> MethodRouter<S, Body, Infallible>
+where
+    H: AxumHandler<T, S, Body>,
+    T: 'static,
+    S: Clone + Send + Sync + 'static,
+{
+    post(handler)
+        .route_layer(middleware::from_fn(|request, next| async {
+            request_recorder(request, next, None).await
+        }))
+        .route_layer(middleware::from_fn(move |request, next| {
+            write_limits(request, next, max_body_size)
+        }))
+        .route_layer(middleware::from_fn(move |request, next| {
+            require_named_token(request, next, tokens)
+        }))
+}
+
+#[instrument(skip_all)]
+fn delete_internal<H, T, S>(handler: H) -> MethodRouter<S, Body, Infallible>
+where
+    H: AxumHandler<T, S, Body>,
+    T: 'static,
+    S: Clone + Send + Sync + 'static,
+{
+    delete(handler)
+        .route_layer(middleware::from_fn(|request, next| async {
+            request_recorder(request, next, None).await
+        }))
+        .route_layer(middleware::from_fn(write_limits_middleware))
+}
+
+/// Protects endpoints changing data. Request bodies larger than `max_request_body_size` are
+/// rejected with `413 Payload Too Large`, handlers taking longer than `write_request_timeout`
+/// are cancelled with `504 Gateway Timeout`.
+///
+/// Cancelling only drops the future of the handler. Work it started with `spawn_blocking` keeps
+/// running until it's done, so handlers doing long blocking work have to check a deadline
+/// themselves, like the preview upload does.
+#[instrument(skip_all)]
+async fn write_limits_middleware(request: AxumHttpRequest<Body>, next: Next<Body>) -> AxumResponse {
+    write_limits(request, next, |config| config.max_request_body_size).await
+}
+
+async fn write_limits(
+    request: AxumHttpRequest<Body>,
+    next: Next<Body>,
+    max_body_size: fn(&Config) -> usize,
+) -> AxumResponse {
+    let config = request
+        .extensions()
+        .get::<Arc<Config>>()
+        .cloned()
+        .expect("missing config extension in request");
+    let max_body_size = max_body_size(&config);
+
+    let too_large = request
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok()?.parse::<usize>().ok())
+        .map_or(false, |length| length > max_body_size);
+    if too_large {
+        return JsonAxumNope(AxumNope::PayloadTooLarge).into_response();
+    }
+
+    // the length header is optional, so the body is read with the limit before the handler
+    // gets it
+    let (parts, mut body) = request.into_parts();
+    let mut content = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                return JsonAxumNope(AxumNope::BadRequest(format!(
+                    "failed to read the request body: {}",
+                    err
+                )))
+                .into_response()
+            }
+        };
+        if content.len() + chunk.len() > max_body_size {
+            return JsonAxumNope(AxumNope::PayloadTooLarge).into_response();
+        }
+        content.extend_from_slice(&chunk);
+    }
+    let request = AxumHttpRequest::from_parts(parts, Body::from(content));
+
+    let timeout = Duration::from_secs(config.write_request_timeout);
+    match tokio::time::timeout(timeout, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => {
+            // blocking work of the handler might still be running, see `write_limits_middleware`
+            warn!(?timeout, "cancelled a request taking too long");
+            JsonAxumNope(AxumNope::Timeout).into_response()
+        }
+    }
+}
+
 #[instrument(skip_all)]
 fn get_rustdoc<H, T, S, B>(handler: H) -> MethodRouter<S, B, Infallible>
 where
@@ -59,15 +207,23 @@ where
         .layer(middleware::from_fn(block_blacklisted_prefixes_middleware))
 }
 
+/// The first path segments of all internal pages, see [`AxumRoutes`].
+#[derive(Debug)]
+struct PagePrefixes(HashSet<String>);
+
 #[instrument(skip_all)]
 async fn block_blacklisted_prefixes_middleware<B>(
     request: AxumHttpRequest<B>,
     next: Next<B>,
 ) -> impl IntoResponse {
+    let page_prefixes = request
+        .extensions()
+        .get::<Arc<PagePrefixes>>()
+        .expect("page prefixes request extension not found")
+        .clone();
+
     if let Some(first_component) = request.uri().path().trim_matches('/').split('/').next() {
-        if !first_component.is_empty()
-            && (INTERNAL_PREFIXES.binary_search(&first_component).is_ok())
-        {
+        if !first_component.is_empty() && page_prefixes.0.contains(first_component) {
             debug!(
                 first_component = first_component,
                 "blocking blacklisted prefix"
@@ -79,8 +235,73 @@ async fn block_blacklisted_prefixes_middleware<B>(
     next.run(request).await
 }
 
-pub(super) fn build_axum_routes() -> AxumRouter {
-    AxumRouter::new()
+/// Builds the axum router and the iron routes of the rustdoc pages axum doesn't serve yet.
+///
+/// Both block the page prefixes of the axum routes for rustdoc pages, so the axum routes are only
+/// built once here and their prefixes are passed on to iron.
+pub(crate) fn build_routes() -> (AxumRouter, Routes) {
+    let AxumRoutes {
+        router,
+        page_prefixes,
+        routes,
+    } = axum_routes();
+
+    let iron_routes = build_iron_routes(&page_prefixes);
+    let route_table = RouteTable {
+        routes,
+        page_prefixes: page_prefixes.iter().cloned().collect(),
+        iron_routes: iron_routes.patterns(),
+    };
+
+    let router = router
+        .layer(Extension(Arc::new(PagePrefixes(page_prefixes))))
+        .layer(Extension(Arc::new(route_table)));
+
+    (router, iron_routes)
+}
+
+/// A route registered in the axum router, see [`AxumRoutes`].
+#[derive(Debug, Clone, Serialize)]
+pub(super) struct RouteInfo {
+    pub(super) path: String,
+    /// Whether the path with or without a trailing slash redirects here
+    trailing_slash_redirect: bool,
+    /// Whether this is a JSON API route, which are described in the OpenAPI document
+    pub(super) json_api: bool,
+}
+
+/// The routes actually registered in the routers, served at `/-/routes.json` when
+/// `expose_route_table` is enabled.
+///
+/// Cache policies are picked by the handlers for each response, so they can't be listed here.
+#[derive(Debug, Serialize)]
+struct RouteTable {
+    routes: Vec<RouteInfo>,
+    /// Path prefixes blocked for rustdoc pages
+    page_prefixes: BTreeSet<String>,
+    /// Patterns of the rustdoc pages still served by iron
+    iron_routes: Vec<String>,
+}
+
+async fn route_table_handler(
+    Extension(config): Extension<Arc<Config>>,
+    Extension(route_table): Extension<Arc<RouteTable>>,
+) -> Result<impl IntoResponse, JsonAxumNope> {
+    if !config.expose_route_table {
+        return Err(JsonAxumNope(AxumNope::ResourceNotFound));
+    }
+
+    Ok((Extension(CachePolicy::NoCaching), Json(route_table)))
+}
+
+/// The routes registered in the axum router.
+#[cfg(test)]
+pub(super) fn axum_route_infos() -> Vec<RouteInfo> {
+    axum_routes().routes
+}
+
+fn axum_routes() -> AxumRoutes {
+    AxumRoutes::new()
         // Well known resources, robots.txt and favicon.ico support redirection, the sitemap.xml
         // must live at the site root:
         //   https://developers.google.com/search/reference/robots_txt#handling-http-result-codes
@@ -105,10 +326,23 @@ pub(super) fn build_axum_routes() -> AxumRouter {
             "/sitemap.xml",
             get_internal(super::sitemap::sitemapindex_handler),
         )
+        .route(
+            "/sitemap.txt",
+            get_internal(super::sitemap::sitemap_txt_handler),
+        )
         .route_with_tsr(
             "/-/sitemap/:letter/sitemap.xml",
             get_internal(super::sitemap::sitemap_handler),
         )
+        // the segment holds the number of the part, the router wants the same name as above
+        .route(
+            "/-/sitemap/:letter/sitemap.txt",
+            get_internal(super::sitemap::sitemap_txt_part_handler),
+        )
+        .route_with_tsr(
+            "/-/sitemap/:letter/:part",
+            get_internal(super::sitemap::sitemap_part_handler),
+        )
         .route_with_tsr(
             "/about/builds",
             get_internal(super::sitemap::about_builds_handler),
@@ -117,6 +351,10 @@ pub(super) fn build_axum_routes() -> AxumRouter {
             "/about/metrics",
             get_internal(super::metrics::metrics_handler),
         )
+        .json_api_route(
+            "/about/stats.json",
+            get_internal(super::sitemap::about_stats_json_handler),
+        )
         .route_with_tsr("/about", get_internal(super::sitemap::about_handler))
         .route_with_tsr(
             "/about/:subpage",
@@ -131,6 +369,14 @@ pub(super) fn build_axum_routes() -> AxumRouter {
             "/releases/recent/:page",
             get_internal(super::releases::recent_releases_handler),
         )
+        .route_with_tsr(
+            "/releases/new-crates",
+            get_internal(super::releases::new_crates_handler),
+        )
+        .route_with_tsr(
+            "/releases/new-crates/:page",
+            get_internal(super::releases::new_crates_handler),
+        )
         .route_with_tsr(
             "/releases/stars",
             get_internal(super::releases::releases_by_stars_handler),
@@ -155,61 +401,275 @@ pub(super) fn build_axum_routes() -> AxumRouter {
             "/releases/failures/:page",
             get_internal(super::releases::releases_failures_by_stars_handler),
         )
+        .route_with_tsr(
+            "/releases/crates",
+            get_internal(super::releases::crates_handler)
+                .route_layer(middleware::from_fn(vary_accept_middleware)),
+        )
+        .route_with_tsr(
+            "/releases/categories",
+            get_internal(super::releases::categories_handler)
+                .route_layer(middleware::from_fn(vary_accept_middleware)),
+        )
+        .route_with_tsr(
+            "/releases/categories/:slug",
+            get_internal(super::releases::category_handler)
+                .route_layer(middleware::from_fn(vary_accept_middleware)),
+        )
         .route_with_tsr(
             "/crate/:name",
-            get_internal(super::crate_details::crate_details_handler),
+            get_internal(super::crate_details::crate_details_handler)
+                .route_layer(middleware::from_fn(vary_accept_middleware)),
+        )
+        .route_with_tsr(
+            "/crate/:name/compare",
+            get_internal(super::compare::compare_releases_handler)
+                .route_layer(middleware::from_fn(vary_accept_middleware)),
+        )
+        .json_api_route(
+            "/crate/:name/versions.json",
+            get_internal(super::crate_details::versions_json_handler),
         )
         .route_with_tsr(
             "/crate/:name/:version",
-            get_internal(super::crate_details::crate_details_handler),
+            get_internal(super::crate_details::crate_details_handler)
+                .route_layer(middleware::from_fn(vary_accept_middleware)),
+        )
+        .route(
+            "/crate/:name/:version/crates-io-redirect",
+            get_internal(super::crate_details::crates_io_redirect_handler)
+                .route_layer(middleware::from_fn(vary_accept_middleware)),
         )
         .route_with_tsr(
             "/releases/feed",
             get_static(super::releases::releases_feed_handler),
         )
+        .route_with_tsr(
+            "/releases/new-crates/feed",
+            get_static(super::releases::new_crates_feed_handler),
+        )
         .route_with_tsr(
             "/releases/:owner",
-            get_internal(super::releases::owner_handler),
+            get_internal(super::releases::owner_handler)
+                .route_layer(middleware::from_fn(vary_accept_middleware)),
         )
         .route_with_tsr(
             "/releases/:owner/:page",
-            get_internal(super::releases::owner_handler),
+            get_internal(super::releases::owner_handler)
+                .route_layer(middleware::from_fn(vary_accept_middleware)),
+        )
+        .route_with_tsr(
+            "/releases/watch",
+            get_internal(super::releases::watch_handler)
+                .route_layer(middleware::from_fn(vary_accept_middleware)),
         )
         .route_with_tsr(
             "/releases/activity",
-            get_internal(super::releases::activity_handler),
+            get_internal(super::releases::activity_handler)
+                .route_layer(middleware::from_fn(vary_accept_middleware)),
         )
         .route_with_tsr(
             "/releases/search",
             get_internal(super::releases::search_handler),
         )
+        .route_with_tsr(
+            "/releases/search/suggest",
+            get_internal(super::releases::search_suggest_handler),
+        )
+        .route_with_tsr(
+            "/~/:name",
+            get_internal(super::releases::crate_docs_redirect_handler),
+        )
         .route_with_tsr(
             "/releases/queue",
             get_internal(super::releases::build_queue_handler),
         )
+        .json_api_route(
+            "/-/openapi.json",
+            get_internal(super::openapi::openapi_handler),
+        )
+        .route("/-/routes.json", get_internal(route_table_handler))
+        .json_api_route(
+            "/releases/queue.json",
+            get_internal(super::releases::build_queue_json_handler),
+        )
+        .json_api_route(
+            "/releases/recent-failures.json",
+            get_internal(super::releases::recent_failures_json_handler),
+        )
+        .json_api_route(
+            "/releases/dashboard.json",
+            get_internal(super::releases::dashboard_json_handler),
+        )
+        .json_api_route(
+            "/releases/check",
+            get_internal(super::releases::check_handler),
+        )
+        .json_api_route(
+            "/-/stale-builds.json",
+            get_internal(super::builds::stale_builds_json_handler),
+        )
         .route_with_tsr(
             "/crate/:name/:version/builds",
-            get_internal(super::builds::build_list_handler),
+            get_internal(super::builds::build_list_handler)
+                .route_layer(middleware::from_fn(vary_accept_middleware)),
         )
-        .route(
+        .route_with_tsr(
+            "/crate/:name/builds/matrix",
+            get_internal(super::builds::build_matrix_handler)
+                .route_layer(middleware::from_fn(vary_accept_middleware)),
+        )
+        .json_api_route(
             "/crate/:name/:version/builds.json",
             get_static(super::builds::build_list_json_handler),
         )
+        .json_api_route(
+            "/crate/:name/:version/targets.json",
+            get_internal(super::builds::build_targets_json_handler),
+        )
+        .route_with_tsr(
+            "/crate/:name/:version/readme",
+            get_internal(super::crate_details::readme_handler),
+        )
+        .route_with_tsr(
+            "/crate/:name/:version/changelog",
+            get_internal(super::changelog::changelog_handler),
+        )
+        .route_with_tsr(
+            "/crate/:name/:version/license",
+            get_internal(super::license::license_handler)
+                .route_layer(middleware::from_fn(vary_accept_middleware))
+                .route_layer(middleware::from_fn(cors_middleware)),
+        )
+        .json_api_route(
+            "/crate/:name/:version/coverage.json",
+            get_internal(super::crate_details::coverage_json_handler),
+        )
+        .json_api_route(
+            "/crate/:name/:version/sizes.json",
+            get_internal(super::crate_details::sizes_json_handler),
+        )
+        .json_api_route(
+            "/crate/:name/:version/msrv.json",
+            get_internal(super::crate_details::msrv_json_handler),
+        )
+        .json_api_route(
+            "/crate/:name/:version/features.json",
+            get_internal(super::features::features_json_handler),
+        )
+        .json_api_route(
+            "/crate/:name/:version/dependencies.json",
+            get_internal(super::crate_details::dependencies_json_handler),
+        )
+        .json_api_route(
+            "/crate/:name/:version/search-index.json",
+            get_internal(super::search_index::search_index_handler),
+        )
+        .json_api_route(
+            "/crate/:name/:version/search",
+            get_internal(super::search_index::search_handler),
+        )
+        .json_api_route(
+            "/crate/:name/:version/all.json",
+            get_internal(super::all_items::all_items_handler),
+        )
+        .json_api_route(
+            "/crate/:name/:version/assets.json",
+            get_internal(super::rustdoc_assets::assets_handler),
+        )
+        .json_api_route(
+            "/crate/:name/:version/rebuild",
+            post_internal(super::builds::rebuild_handler)
+                .layer(Extension(Arc::new(RateLimiter::<String>::default()))),
+        )
+        .json_api_route(
+            "/crate/:name/:version/report",
+            post_internal(super::reports::report_handler)
+                .layer(Extension(Arc::new(RateLimiter::<IpAddr>::default()))),
+        )
+        .json_api_route(
+            "/-/reports.json",
+            get_internal(super::reports::reports_json_handler),
+        )
+        .json_api_route(
+            "/crate/:name/webhooks",
+            post_internal(super::webhooks::register_webhook_handler)
+                .merge(delete_internal(super::webhooks::unregister_webhook_handler)),
+        )
+        .json_api_route(
+            "/-/admin/purge/:name/:version",
+            post_internal(super::purge::purge_handler),
+        )
+        .json_api_route(
+            "/-/details-batch",
+            post_internal(super::crate_details::details_batch_handler),
+        )
+        .json_api_route(
+            "/-/short-links",
+            post_internal(super::short_links::create_short_link_handler),
+        )
+        .json_api_route(
+            "/-/previews",
+            post_upload_internal(
+                super::previews::upload_preview_handler,
+                |config| config.max_preview_size,
+                |config| config.preview_tokens.as_slice(),
+            ),
+        )
+        .route_with_tsr(
+            "/-/preview/:token/",
+            get_internal(super::previews::preview_handler),
+        )
+        .route(
+            "/-/preview/:token/*path",
+            get_internal(super::previews::preview_handler),
+        )
+        .route_with_tsr(
+            "/-/s/:token",
+            get_internal(super::short_links::short_link_handler),
+        )
         .route_with_tsr(
             "/crate/:name/:version/builds/:id",
-            get_internal(super::build_details::build_details_handler),
+            get_internal(super::build_details::build_details_handler)
+                .route_layer(middleware::from_fn(vary_accept_middleware)),
+        )
+        .json_api_route(
+            "/crate/:name/:version/builds/:id/environment.json",
+            get_internal(super::build_details::build_environment_json_handler),
         )
         .route_with_tsr(
             "/crate/:name/:version/features",
-            get_internal(super::features::build_features_handler),
+            get_internal(super::features::build_features_handler)
+                .route_layer(middleware::from_fn(vary_accept_middleware))
+                .route_layer(middleware::from_fn(cors_middleware)),
+        )
+        .route(
+            "/crate/:name/:version/Cargo.toml",
+            get_internal(super::source::cargo_toml_handler),
+        )
+        .route(
+            "/crate/:name/:version/source.zip",
+            get_internal(super::source::source_archive_handler),
         )
         .route_with_tsr(
             "/crate/:name/:version/source/",
-            get_internal(super::source::source_browser_handler),
+            get_internal(super::source::source_browser_handler)
+                .route_layer(middleware::from_fn(vary_accept_middleware))
+                .route_layer(middleware::from_fn(cors_middleware)),
         )
         .route(
             "/crate/:name/:version/source/*path",
-            get_internal(super::source::source_browser_handler),
+            get_internal(super::source::source_browser_handler)
+                .route_layer(middleware::from_fn(vary_accept_middleware))
+                .route_layer(middleware::from_fn(cors_middleware)),
+        )
+        .route_with_tsr(
+            "/crate/:name/:version/book/",
+            get_internal(super::book::book_handler),
+        )
+        .route(
+            "/crate/:name/:version/book/*path",
+            get_internal(super::book::book_handler),
         )
         .route(
             "/-/rustdoc.static/*path",
@@ -235,14 +695,22 @@ pub(super) fn build_axum_routes() -> AxumRouter {
             "/crate/:name/:version/target-redirect/*path",
             get_internal(super::rustdoc::target_redirect_handler),
         )
+        .route(
+            "/crate/:name/item-latest/*path",
+            get_internal(super::rustdoc::item_latest_handler),
+        )
         .route(
             "/:crate/badge.svg",
             get_rustdoc(super::rustdoc::badge_handler),
         )
+        .json_api_route(
+            "/:crate/status.json",
+            get_rustdoc(super::rustdoc::status_json_handler),
+        )
 }
 
 // REFACTOR: Break this into smaller initialization functions
-pub(super) fn build_routes() -> Routes {
+fn build_iron_routes(page_prefixes: &HashSet<String>) -> Routes {
     let mut routes = Routes::new();
 
     routes.rustdoc_page("/:crate", super::rustdoc::rustdoc_redirector_handler);
@@ -280,17 +748,82 @@ pub(super) fn build_routes() -> Routes {
         super::rustdoc::rustdoc_html_server_handler,
     );
 
-    for prefix in INTERNAL_PREFIXES {
+    for prefix in page_prefixes {
         routes.add_internal_page_prefix(prefix);
     }
 
     routes
 }
 
+/// Wrapper around axum's Router, collecting the static first path segments of all the routes
+/// registered through it. Like the page prefixes of iron's `Routes`, these are blacklisted for
+/// rustdoc pages, so a crate named "about" can't hijack /about/0.1.0/index.html.
+struct AxumRoutes {
+    router: AxumRouter,
+    page_prefixes: HashSet<String>,
+    routes: Vec<RouteInfo>,
+}
+
+impl AxumRoutes {
+    fn new() -> Self {
+        Self {
+            router: AxumRouter::new(),
+            page_prefixes: HashSet::new(),
+            routes: Vec::new(),
+        }
+    }
+
+    fn add_page_prefix(&mut self, path: &str) {
+        if let Some(first_component) = path.trim_start_matches('/').split('/').next() {
+            if !first_component.is_empty()
+                && !first_component.starts_with(':')
+                && !first_component.starts_with('*')
+            {
+                self.page_prefixes.insert(first_component.to_owned());
+            }
+        }
+    }
+
+    fn route(mut self, path: &str, method_router: MethodRouter) -> Self {
+        self.add_page_prefix(path);
+        self.routes.push(RouteInfo {
+            path: path.to_owned(),
+            trailing_slash_redirect: false,
+            json_api: false,
+        });
+        self.router = self.router.route(path, method_router);
+        self
+    }
+
+    fn route_with_tsr(mut self, path: &str, method_router: MethodRouter) -> Self {
+        self.add_page_prefix(path);
+        self.routes.push(RouteInfo {
+            path: path.to_owned(),
+            trailing_slash_redirect: true,
+            json_api: false,
+        });
+        self.router = self.router.route_with_tsr(path, method_router);
+        self
+    }
+
+    /// Adds a JSON API route, see [`json_api`]. These have to be described in the OpenAPI
+    /// document.
+    fn json_api_route(mut self, path: &str, method_router: MethodRouter) -> Self {
+        self.add_page_prefix(path);
+        self.routes.push(RouteInfo {
+            path: path.to_owned(),
+            trailing_slash_redirect: false,
+            json_api: true,
+        });
+        self.router = self.router.route(path, json_api(method_router));
+        self
+    }
+}
+
 /// This wrapper class aids the construction of iron's Router, with docs.rs-specific additions to
-/// it. Routes are supposed to be added by the build_routes function, which calls methods in this
-/// struct depending on the type of route being added.
-pub(super) struct Routes {
+/// it. Routes are supposed to be added by the build_iron_routes function, which calls methods in
+/// this struct depending on the type of route being added.
+pub(crate) struct Routes {
     /// Normal GET routes.
     get: Vec<(String, Box<dyn Handler>)>,
     /// GET routes serving rustdoc content. The BlockBlacklistedPrefixes middleware is added
@@ -314,6 +847,15 @@ impl Routes {
         self.page_prefixes.clone()
     }
 
+    /// The patterns of all the registered routes.
+    fn patterns(&self) -> Vec<String> {
+        self.get
+            .iter()
+            .chain(self.rustdoc_get.iter())
+            .map(|(pattern, _)| pattern.clone())
+            .collect()
+    }
+
     pub(super) fn add_internal_page_prefix<P: AsRef<str>>(&mut self, prefix: P) {
         self.page_prefixes.insert(prefix.as_ref().to_string());
     }
@@ -406,9 +948,96 @@ fn calculate_id(pattern: &str) -> String {
 
 #[cfg(test)]
 mod tests {
+    use super::{axum_routes, post_internal};
     use crate::test::*;
     use crate::web::cache::CachePolicy;
+    use axum::{body::Body, extract::Extension, http::Request, Router as AxumRouter};
     use reqwest::StatusCode;
+    use std::time::Duration;
+    use tower::ServiceExt as _;
+
+    #[test]
+    fn page_prefixes_are_derived_from_routes() {
+        let page_prefixes = axum_routes().page_prefixes;
+
+        for prefix in &[
+            "-",
+            "about",
+            "crate",
+            "releases",
+            "sitemap.xml",
+            "robots.txt",
+            "favicon.ico",
+            "opensearch.xml",
+        ] {
+            assert!(
+                page_prefixes.contains(*prefix),
+                "{} is missing from the page prefixes",
+                prefix
+            );
+        }
+
+        for prefix in &page_prefixes {
+            assert!(!prefix.is_empty());
+            assert!(!prefix.starts_with(':'));
+            assert!(!prefix.starts_with('*'));
+        }
+    }
+
+    #[test]
+    fn route_table_is_disabled_by_default() {
+        wrapper(|env| {
+            let response = env.frontend().get("/-/routes.json").send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn route_table() {
+        wrapper(|env| {
+            env.override_config(|config| config.expose_route_table = true);
+            let web = env.frontend();
+            let response = web.get("/-/routes.json").send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_cache_control(&response, CachePolicy::NoCaching, &env.config());
+            let table: serde_json::Value = response.json()?;
+
+            let routes = table["routes"].as_array().unwrap();
+            assert_eq!(routes.len(), axum_routes().routes.len());
+            assert!(routes.contains(&serde_json::json!({
+                "path": "/crate/:name",
+                "trailing_slash_redirect": true,
+                "json_api": false,
+            })));
+            assert!(routes.contains(&serde_json::json!({
+                "path": "/-/routes.json",
+                "trailing_slash_redirect": false,
+                "json_api": false,
+            })));
+            assert!(routes.contains(&serde_json::json!({
+                "path": "/crate/:name/versions.json",
+                "trailing_slash_redirect": false,
+                "json_api": true,
+            })));
+
+            let page_prefixes: Vec<_> = table["page_prefixes"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|prefix| prefix.as_str().unwrap())
+                .collect();
+            for prefix in axum_routes().page_prefixes {
+                assert!(page_prefixes.contains(&prefix.as_str()));
+            }
+
+            assert!(table["iron_routes"]
+                .as_array()
+                .unwrap()
+                .contains(&serde_json::json!("/:crate/:version/:target/*.html")));
+            Ok(())
+        })
+    }
 
     #[test]
     fn test_root_redirects() {
@@ -468,4 +1097,68 @@ mod tests {
             Ok(())
         })
     }
+
+    #[test]
+    fn write_requests_with_large_bodies_are_rejected() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.short_link_tokens = vec!["admin:secret".parse().unwrap()];
+                config.max_request_body_size = 64;
+            });
+            let web = env.frontend();
+            let body = serde_json::json!({ "url": format!("/{}", "a".repeat(100)) }).to_string();
+
+            let response = web
+                .post("/-/short-links")
+                .bearer_auth("secret")
+                .header("content-type", "application/json")
+                .body(body.clone())
+                .send()?;
+            assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+            // without a `Content-Length` header
+            let response = web
+                .post("/-/short-links")
+                .bearer_auth("secret")
+                .header("content-type", "application/json")
+                .body(reqwest::blocking::Body::new(std::io::Cursor::new(body)))
+                .send()?;
+            assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+            let response = web
+                .post("/-/short-links")
+                .bearer_auth("secret")
+                .json(&serde_json::json!({ "url": "/crate/foo" }))
+                .send()?;
+            assert_eq!(response.status(), StatusCode::CREATED);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn slow_write_requests_time_out() {
+        wrapper(|env| {
+            env.override_config(|config| config.write_request_timeout = 1);
+            let app = AxumRouter::new()
+                .route(
+                    "/slow",
+                    post_internal(|| async {
+                        tokio::time::sleep(Duration::from_secs(60)).await;
+                        "done"
+                    }),
+                )
+                .layer(Extension(env.config()))
+                .layer(Extension(env.metrics()));
+
+            let response = env.runtime().block_on(
+                app.oneshot(
+                    Request::post("/slow")
+                        .body(Body::empty())
+                        .expect("invalid request"),
+                ),
+            )?;
+            assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+            Ok(())
+        })
+    }
 }
@@ -2,11 +2,14 @@
 
 use crate::{
     db::Pool,
+    docbuilder::BuildFailure,
+    impl_webpage,
     repositories::RepositoryStatsUpdater,
     storage::{rustdoc_archive_path, PathNotFoundError},
     utils::{self, spawn_blocking},
     web::{
         axum_cached_redirect,
+        base_url::BaseUrl,
         cache::CachePolicy,
         crate_details::CrateDetails,
         csp::Csp,
@@ -14,18 +17,21 @@ use crate::{
         file::File,
         match_version, match_version_axum,
         metrics::RenderingTimesRecorder,
+        page::WebPage,
         parse_url_with_params, redirect, redirect_base, MatchSemver, MetaData,
     },
-    Config, Metrics, Storage, RUSTDOC_STATIC_STORAGE_PREFIX,
+    BuildQueue, Config, Metrics, Storage, RUSTDOC_STATIC_STORAGE_PREFIX,
 };
 use anyhow::{anyhow, Context as _};
 use axum::{
     extract::{Extension, Path, Query},
     http::StatusCode,
     response::IntoResponse,
+    Json,
 };
+use chrono::{DateTime, Utc};
 use iron::{
-    headers::{Link, LinkValue, RelationType},
+    headers::{ETag, EntityTag, IfNoneMatch, Link, LinkValue, RelationType},
     modifiers::Redirect,
     status,
     url::percent_encoding::percent_decode,
@@ -36,12 +42,13 @@ use once_cell::sync::Lazy;
 use router::Router;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
     fmt::Write,
+    hash::{Hash, Hasher},
     path,
     sync::Arc,
 };
-use tracing::{debug, instrument};
+use tracing::{debug, instrument, warn};
 
 static DOC_RUST_LANG_ORG_REDIRECTS: Lazy<HashMap<&str, &str>> = Lazy::new(|| {
     HashMap::from([
@@ -56,6 +63,12 @@ static DOC_RUST_LANG_ORG_REDIRECTS: Lazy<HashMap<&str, &str>> = Lazy::new(|| {
     ])
 });
 
+/// Themes which can be requested with `?theme=` on rustdoc pages
+const RUSTDOC_THEMES: &[&str] = &["light", "dark", "ayu"];
+/// Most releases checked for the page by [`item_latest_handler`], every check can download the
+/// index of an archive
+const ITEM_LATEST_MAX_RELEASES: usize = 10;
+
 fn ico_handler(req: &mut Request) -> IronResult<Response> {
     if let Some(&"favicon.ico") = req.url.path().last() {
         // if we're looking for exactly "favicon.ico", we need to defer to the handler that
@@ -73,6 +86,80 @@ fn ico_handler(req: &mut Request) -> IronResult<Response> {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct BuildInProgressPage {
+    name: String,
+    version: String,
+    /// The position in the build queue, `1` while the docs are being built
+    position: usize,
+}
+
+impl_webpage! {
+    BuildInProgressPage = "crate/build_in_progress.html",
+    status = |_| status::Accepted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct DocsTooLargePage {
+    name: String,
+    version: String,
+    /// The build which rejected the documentation
+    build_id: i32,
+}
+
+impl_webpage! {
+    DocsTooLargePage = "crate/docs_too_large.html",
+}
+
+/// Returns the id of the newest build of a release when it rejected the documentation for
+/// exceeding the size limit.
+fn docs_too_large_build(
+    conn: &mut postgres::Client,
+    release_id: i32,
+) -> anyhow::Result<Option<i32>> {
+    Ok(conn
+        .query_opt(
+            "SELECT id, failure_category
+             FROM builds
+             WHERE rid = $1
+             ORDER BY build_time DESC, id DESC
+             LIMIT 1",
+            &[&release_id],
+        )?
+        .filter(|row| {
+            row.get::<_, Option<&str>>("failure_category")
+                == Some(BuildFailure::DocsTooLarge.as_str())
+        })
+        .map(|row| row.get("id")))
+}
+
+/// Called when no release matches the request. Renders a page telling the docs will be available
+/// soon when the requested version is waiting in the build queue, or returns `err` otherwise.
+///
+/// Any queued version of the crate matches when the request isn't for an exact version.
+fn build_in_progress_or(
+    req: &Request,
+    name: &str,
+    req_version: Option<&str>,
+    err: Nope,
+) -> IronResult<Response> {
+    let version = req_version.filter(|version| semver::Version::parse(version).is_ok());
+    let queued = ctry!(
+        req,
+        extension!(req, BuildQueue).queued_position(name, version)
+    );
+
+    match queued {
+        Some((version, position)) => BuildInProgressPage {
+            name: name.to_string(),
+            version,
+            position,
+        }
+        .into_response(req),
+        None => Err(err.into()),
+    }
+}
+
 /// Handler called for `/:crate` and `/:crate/:version` URLs. Automatically redirects to the docs
 /// or crate details page based on whether the given crate version was successfully built.
 pub fn rustdoc_redirector_handler(req: &mut Request) -> IronResult<Response> {
@@ -180,13 +267,20 @@ pub fn rustdoc_redirector_handler(req: &mut Request) -> IronResult<Response> {
     // it doesn't matter if the version that was given was exact or not, since we're redirecting
     // anyway
     rendering_time.step("match version");
-    let v = match_version(&mut conn, &crate_name, req_version)?;
+    let v = match match_version(&mut conn, &crate_name, req_version) {
+        Ok(v) => v,
+        Err(err) if matches!(err, Nope::CrateNotFound | Nope::VersionNotFound) => {
+            return build_in_progress_or(req, &crate_name, req_version, err);
+        }
+        Err(err) => return Err(err.into()),
+    };
     if let Some(new_name) = v.corrected_name {
         // `match_version` checked against -/_ typos, so if we have a name here we should
         // use that instead
         crate_name = new_name;
     }
     let (mut version, id) = v.version.into_parts();
+    let exact_version = version.clone();
 
     if let None | Some("latest") = req_version {
         version = "latest".to_string()
@@ -195,44 +289,113 @@ pub fn rustdoc_redirector_handler(req: &mut Request) -> IronResult<Response> {
     // get target name and whether it has docs
     // FIXME: This is a bit inefficient but allowing us to use less code in general
     rendering_time.step("fetch release doc status");
-    let (target_name, has_docs): (String, bool) = {
+    let (target_name, has_docs, is_library, archive_storage): (String, bool, bool, bool) = {
         let rows = ctry!(
             req,
             conn.query(
-                "SELECT target_name, rustdoc_status
+                "SELECT target_name, rustdoc_status, is_library, archive_storage
                  FROM releases
                  WHERE releases.id = $1",
                 &[&id]
             ),
         );
 
-        (rows[0].get(0), rows[0].get(1))
+        (
+            rows[0].get(0),
+            rows[0].get(1),
+            rows[0].get(2),
+            rows[0].get(3),
+        )
     };
 
     if target == Some("index.html") || target == Some(&target_name) {
         target = None;
     }
 
-    if has_docs {
-        rendering_time.step("redirect to doc");
+    let storage = extension!(req, Storage);
+    let landing = ctry!(
+        req,
+        DocsLanding::select(is_library, has_docs, || {
+            storage.rustdoc_file_exists(
+                &crate_name,
+                &exact_version,
+                &format!("{target_name}/index.html"),
+                archive_storage,
+            )
+        }),
+    );
 
-        let base = redirect_base(req);
-        let url_str = if let Some(target) = target {
-            format!("{base}/{crate_name}/{version}/{target}/{target_name}/")
-        } else {
-            format!("{base}/{crate_name}/{version}/{target_name}/")
-        };
+    if landing == DocsLanding::CrateDetails {
+        if let Some(build_id) = ctry!(req, docs_too_large_build(&mut conn, id)) {
+            return DocsTooLargePage {
+                name: crate_name,
+                version: exact_version,
+                build_id,
+            }
+            .into_response(req);
+        }
 
-        let cache = if version == "latest" {
-            CachePolicy::ForeverInCdn
-        } else {
-            CachePolicy::ForeverInCdnAndStaleInBrowser
-        };
+        rendering_time.step("redirect to crate");
+        return redirect_to_crate(req, &crate_name, &version);
+    }
 
-        redirect_to_doc(req, url_str, cache, path_in_crate.as_deref())
+    rendering_time.step("redirect to doc");
+
+    let doc_path = if let Some(target) = target {
+        format!("/{crate_name}/{version}/{target}/{target_name}/")
     } else {
-        rendering_time.step("redirect to crate");
-        redirect_to_crate(req, &crate_name, &version)
+        format!("/{crate_name}/{version}/{target_name}/")
+    };
+    if doc_path == req.url.as_ref().path() {
+        // redirecting to the requested page again would loop
+        warn!(path = %doc_path, "detected redirect loop");
+        return redirect_to_crate(req, &crate_name, &version);
+    }
+    let url_str = format!("{}{doc_path}", redirect_base(req));
+
+    let cache = if version == "latest" {
+        CachePolicy::ForeverInCdn
+    } else {
+        CachePolicy::ForeverInCdnAndStaleInBrowser
+    };
+
+    redirect_to_doc(req, url_str, cache, path_in_crate.as_deref())
+}
+
+/// The page `/:crate/:version` redirects to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DocsLanding {
+    /// The docs of the library target
+    Lib,
+    /// The docs of the first binary target, for releases without library
+    Bin,
+    /// The crate details page, for releases without any docs
+    CrateDetails,
+}
+
+impl DocsLanding {
+    /// Selects the landing page in this order of preference:
+    ///
+    /// 1. the library target
+    /// 2. the first binary target, when there are docs for it
+    /// 3. the crate details page
+    ///
+    /// The `target_name` of a release without library is the name of its first binary target,
+    /// `bin_has_docs` has to check whether that one was documented.
+    fn select(
+        is_library: bool,
+        has_docs: bool,
+        bin_has_docs: impl FnOnce() -> anyhow::Result<bool>,
+    ) -> anyhow::Result<Self> {
+        Ok(if !has_docs {
+            DocsLanding::CrateDetails
+        } else if is_library {
+            DocsLanding::Lib
+        } else if bin_has_docs()? {
+            DocsLanding::Bin
+        } else {
+            DocsLanding::CrateDetails
+        })
     }
 }
 
@@ -250,6 +413,10 @@ struct RustdocPage {
     // true if the URL specifies a version using the string "latest."
     is_latest_url: bool,
     is_prerelease: bool,
+    // true if search engines shouldn't index the page, see `Config::noindex_unstable_versions`
+    noindex: bool,
+    // the theme requested with `?theme=`, `None` for unknown themes
+    theme: Option<&'static str>,
     krate: CrateDetails,
     metadata: MetaData,
 }
@@ -276,13 +443,22 @@ impl RustdocPage {
 
         let is_latest_url = self.is_latest_url;
         let canonical_url = self.canonical_url.clone();
+        let theme = self.theme;
 
         // Build the page of documentation
         let ctx = ctry!(req, tera::Context::from_serialize(self));
         let config = extension!(req, Config);
         // Extract the head and body of the rustdoc file so that we can insert it into our own html
         // while logging OOM errors from html rewriting
-        let html = match utils::rewrite_lol(rustdoc_html, max_parse_memory, ctx, templates) {
+        let html = match utils::rewrite_lol(
+            rustdoc_html,
+            max_parse_memory,
+            ctx,
+            templates,
+            config.rustdoc_header_html.as_deref(),
+            config.rustdoc_footer_html.as_deref(),
+            theme,
+        ) {
             Err(RewritingError::MemoryLimitExceeded(..)) => {
                 metrics.html_rewrite_ooms.inc();
 
@@ -303,15 +479,37 @@ impl RustdocPage {
 
         response.headers.set(Link::new(vec![link_value]));
 
-        response.extensions.insert::<CachePolicy>(if is_latest_url {
-            CachePolicy::ForeverInCdn
-        } else {
-            CachePolicy::ForeverInCdnAndStaleInBrowser
-        });
+        response
+            .extensions
+            .insert::<CachePolicy>(page_cache_policy(is_latest_url));
         Ok(response)
     }
 }
 
+/// `/latest/` pages change with new releases, they can't be cached in browsers.
+fn page_cache_policy(is_latest_url: bool) -> CachePolicy {
+    if is_latest_url {
+        CachePolicy::ForeverInCdn
+    } else {
+        CachePolicy::ForeverInCdnAndStaleInBrowser
+    }
+}
+
+/// The `ETag` of a rustdoc page of a release built at `build_time`. Since docs.rs renders its
+/// own header and footer into the pages, it also changes with every version of docs.rs, and
+/// with the yanked and latest version warnings of the header.
+fn rustdoc_page_etag(build_time: DateTime<Utc>, yanked: bool, latest_version: &str) -> EntityTag {
+    let mut hasher = DefaultHasher::new();
+    crate::BUILD_VERSION.hash(&mut hasher);
+    yanked.hash(&mut hasher);
+    latest_version.hash(&mut hasher);
+    EntityTag::weak(format!(
+        "{:x}-{:x}",
+        build_time.timestamp(),
+        hasher.finish()
+    ))
+}
+
 /// Serves documentation generated by rustdoc.
 ///
 /// This includes all HTML files for an individual crate, as well as the `search-index.js`, which is
@@ -371,7 +569,13 @@ pub fn rustdoc_html_server_handler(req: &mut Request) -> IronResult<Response> {
     // * If both the name and the version are an exact match, return the version of the crate.
     // * If there is an exact match, but the requested crate name was corrected (dashes vs. underscores), redirect to the corrected name.
     // * If there is a semver (but not exact) match, redirect to the exact version.
-    let release_found = match_version(&mut conn, &name, url_version)?;
+    let release_found = match match_version(&mut conn, &name, url_version) {
+        Ok(release_found) => release_found,
+        Err(err) if matches!(err, Nope::CrateNotFound | Nope::VersionNotFound) => {
+            return build_in_progress_or(req, &name, url_version, err);
+        }
+        Err(err) => return Err(err.into()),
+    };
 
     let (version, version_or_latest, is_latest_url) = match release_found.version {
         MatchSemver::Exact((version, _)) => {
@@ -440,6 +644,19 @@ pub fn rustdoc_html_server_handler(req: &mut Request) -> IronResult<Response> {
         req_path.push("index.html");
     }
     let mut path = ctry!(req, percent_decode(path.as_bytes()).decode_utf8());
+    let is_page = path.ends_with(".html") || !path.rsplit('/').next().unwrap_or("").contains('.');
+
+    // Explain why there are no docs instead of serving a 404, or leftovers of an older build
+    if is_page && !krate.rustdoc_status {
+        if let Some(build_id) = ctry!(req, docs_too_large_build(&mut conn, krate.release_id)) {
+            return DocsTooLargePage {
+                name,
+                version,
+                build_id,
+            }
+            .into_response(req);
+        }
+    }
 
     // Attempt to load the file from the database
     let blob = match storage.fetch_rustdoc_file(
@@ -447,7 +664,7 @@ pub fn rustdoc_html_server_handler(req: &mut Request) -> IronResult<Response> {
         &version,
         &path,
         krate.archive_storage,
-        &mut rendering_time,
+        Some(&mut rendering_time),
     ) {
         Ok(file) => file,
         Err(err) => {
@@ -479,6 +696,28 @@ pub fn rustdoc_html_server_handler(req: &mut Request) -> IronResult<Response> {
                     &req_path,
                     CachePolicy::ForeverInCdn,
                 )
+            } else if is_page
+                && krate.rustdoc_status
+                && req_path[..] != [krate.target_name.as_str(), "index.html"][..]
+                && ctry!(
+                    req,
+                    storage.rustdoc_file_exists(
+                        &name,
+                        &version,
+                        &format!("{}/index.html", krate.target_name),
+                        krate.archive_storage
+                    )
+                )
+            {
+                // The page doesn't exist in this build, most likely this is a stale deep link
+                // from before the docs were restructured. Send the user to the crate root
+                // instead of showing a dead end.
+                redirect(
+                    &name,
+                    &version_or_latest,
+                    &[krate.target_name.as_str(), ""],
+                    CachePolicy::ForeverInCdn,
+                )
             } else {
                 Err(Nope::ResourceNotFound.into())
             };
@@ -492,7 +731,11 @@ pub fn rustdoc_html_server_handler(req: &mut Request) -> IronResult<Response> {
         // default asset caching behaviour is `Cache::ForeverInCdnAndBrowser`.
         // This is an edge-case when we serve invocation specific static assets under `/latest/`:
         // https://github.com/rust-lang/docs.rs/issues/1593
-        return Ok(File(blob).serve());
+        let mut response = File(blob).serve();
+        if config.server_timing {
+            set_server_timing(&mut response, &mut rendering_time);
+        }
+        return Ok(response);
     }
 
     rendering_time.step("find latest path");
@@ -502,6 +745,28 @@ pub fn rustdoc_html_server_handler(req: &mut Request) -> IronResult<Response> {
     // Get the latest version of the crate
     let latest_version = latest_release.version.to_string();
     let is_latest_version = latest_version == version;
+
+    // The html of a build only changes with the pages docs.rs renders around it, clients
+    // revalidating a page they got from the newest build and the current deploy don't need it
+    // again.
+    let etag = krate
+        .last_build_time
+        .map(|build_time| rustdoc_page_etag(build_time, krate.yanked, &latest_version));
+    if let (Some(etag), Some(if_none_match)) = (&etag, req.headers.get::<IfNoneMatch>()) {
+        let matches = match if_none_match {
+            IfNoneMatch::Any => true,
+            IfNoneMatch::Items(tags) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        };
+        if matches {
+            let mut response = Response::with(status::NotModified);
+            response.headers.set(ETag(etag.clone()));
+            response
+                .extensions
+                .insert::<CachePolicy>(page_cache_policy(is_latest_url));
+            return Ok(response);
+        }
+    }
+
     let is_prerelease = !(semver::Version::parse(&version)
         .with_context(|| {
             format!(
@@ -562,14 +827,15 @@ pub fn rustdoc_html_server_handler(req: &mut Request) -> IronResult<Response> {
 
     let latest_path = format!("/crate/{}/latest{}{}", name, target_redirect, query_string);
 
-    // Set the canonical URL for search engines to the `/latest/` page on docs.rs.
+    // Set the canonical URL for search engines to the `/latest/` page on the public host.
     // Note: The URL this points to may not exist. For instance, if we're rendering
     // `struct Foo` in version 0.1.0 of a crate, and version 0.2.0 of that crate removes
     // `struct Foo`, this will point at a 404. That's fine: search engines will crawl
     // the target and will not canonicalize to a URL that doesn't exist.
     // Don't include index.html in the canonical URL.
     let canonical_url = format!(
-        "https://docs.rs/{}/latest/{}",
+        "{}/{}/latest/{}",
+        BaseUrl::from_iron(req).0,
         name,
         inner_path.replace("index.html", "")
     );
@@ -584,8 +850,21 @@ pub fn rustdoc_html_server_handler(req: &mut Request) -> IronResult<Response> {
         format!("{}/", target)
     };
 
+    let server_timing = config.server_timing;
+    let preload_links = if config.early_hints {
+        super::rustdoc_assets::preload_links(&krate.preload_assets)
+    } else {
+        None
+    };
+    let noindex = config.noindex_unstable_versions && (is_prerelease || krate.yanked);
+    let theme = req
+        .url
+        .as_ref()
+        .query_pairs()
+        .find(|(key, _)| key == "theme")
+        .and_then(|(_, value)| RUSTDOC_THEMES.iter().copied().find(|theme| *theme == value));
     rendering_time.step("rewrite html");
-    RustdocPage {
+    let mut response = RustdocPage {
         latest_path,
         canonical_url,
         permalink_path,
@@ -595,10 +874,31 @@ pub fn rustdoc_html_server_handler(req: &mut Request) -> IronResult<Response> {
         is_latest_version,
         is_latest_url,
         is_prerelease,
+        noindex,
+        theme,
         metadata: krate.metadata.clone(),
         krate,
     }
-    .into_response(&blob.content, config.max_parse_memory, req, &path)
+    .into_response(&blob.content, config.max_parse_memory, req, &path)?;
+    if let Some(etag) = etag {
+        response.headers.set(ETag(etag));
+    }
+    if let Some(preload_links) = preload_links {
+        response
+            .headers
+            .append_raw("Link", preload_links.into_bytes());
+    }
+    if server_timing {
+        set_server_timing(&mut response, &mut rendering_time);
+    }
+    Ok(response)
+}
+
+fn set_server_timing(response: &mut Response, rendering_time: &mut RenderingTimesRecorder) {
+    response.headers.set_raw(
+        "Server-Timing",
+        vec![rendering_time.server_timing().header_value().into_bytes()],
+    );
 }
 
 /// Checks whether the given path exists.
@@ -700,53 +1000,184 @@ pub(crate) async fn target_redirect_handler(
     // default target,
     // and add `/index.html` if we request
     // a folder.
-    let storage_location_for_path = {
-        let mut pieces: Vec<_> = req_path.split('/').map(str::to_owned).collect();
-
-        if let Some(target) = pieces.first() {
-            if target == &crate_details.metadata.default_target {
-                pieces.remove(0);
-            }
+    let mut pieces: Vec<_> = req_path.split('/').map(str::to_owned).collect();
+    let target = match pieces.first() {
+        Some(target)
+            if target == &crate_details.metadata.default_target
+                || crate_details.metadata.doc_targets.contains(target) =>
+        {
+            Some(pieces.remove(0))
         }
-
-        if let Some(last) = pieces.last_mut() {
-            if last.is_empty() {
-                *last = "index.html".to_string();
-            }
+        _ => None,
+    };
+    if let Some(last) = pieces.last_mut() {
+        if last.is_empty() {
+            *last = "index.html".to_string();
         }
+    }
+    // the path inside the docs of a single target
+    let inner_path = pieces.join("/");
 
-        pieces.join("/")
+    let storage_location = |target: Option<&str>| match target {
+        Some(target) if target != crate_details.metadata.default_target => {
+            format!("{target}/{inner_path}")
+        }
+        _ => inner_path.clone(),
     };
+    let storage_location_for_path = storage_location(target.as_deref());
+    // other targets the page could exist for, if it's gated to a specific platform
+    let other_locations: Vec<_> = crate_details
+        .metadata
+        .doc_targets
+        .iter()
+        .filter(|other| Some(*other) != target.as_ref())
+        .map(|other| storage_location(Some(other)))
+        .filter(|location| *location != storage_location_for_path)
+        .collect();
 
-    let redirect_path = if spawn_blocking({
+    let (exists, exists_for_other_target) = spawn_blocking({
         let name = name.clone();
+        let version = version.clone();
         let file_path = storage_location_for_path.clone();
+        let archive_storage = crate_details.archive_storage;
         move || {
-            storage.rustdoc_file_exists(&name, &version, &file_path, crate_details.archive_storage)
+            if storage.rustdoc_file_exists(&name, &version, &file_path, archive_storage)? {
+                return Ok((true, false));
+            }
+            for location in &other_locations {
+                if storage.rustdoc_file_exists(&name, &version, location, archive_storage)? {
+                    return Ok((false, true));
+                }
+            }
+            Ok((false, false))
         }
     })
-    .await?
-    {
+    .await?;
+
+    let redirect_path = if exists {
         // Simple case: page exists in the other target & version, so just change these
         storage_location_for_path
+    } else if exists_for_other_target {
+        // The page is gated to other platforms, like `std::os::windows` when switching to linux.
+        // Searching for it won't find anything, so go to the root of the crate instead.
+        match target {
+            Some(target) if target != crate_details.metadata.default_target => {
+                format!("{target}/{}/", crate_details.target_name)
+            }
+            _ => format!("{}/", crate_details.target_name),
+        }
     } else {
         let pieces: Vec<_> = storage_location_for_path.split('/').collect();
         path_for_version(&pieces, &crate_details)
     };
 
+    let cache_policy = if is_latest_url {
+        CachePolicy::ForeverInCdn
+    } else {
+        CachePolicy::ForeverInCdnAndStaleInBrowser
+    };
+
+    // The rustdoc pages send missing pages back here, a redirect to the page that was just
+    // requested would loop. Show the details of the release instead.
+    if !exists {
+        let redirect_location = match redirect_path.split_once('?') {
+            Some((location, _)) => location,
+            None => &redirect_path,
+        };
+        let redirect_location = if redirect_location.is_empty() || redirect_location.ends_with('/')
+        {
+            format!("{redirect_location}index.html")
+        } else {
+            redirect_location.to_owned()
+        };
+        if redirect_location == storage_location_for_path {
+            warn!(%name, %version, path = %req_path, "detected redirect loop");
+            return Ok(axum_cached_redirect(
+                &format!("/crate/{name}/{version_or_latest}"),
+                cache_policy,
+            )?);
+        }
+    }
+
     Ok(axum_cached_redirect(
         &format!("/{name}/{version_or_latest}/{redirect_path}"),
-        if is_latest_url {
-            CachePolicy::ForeverInCdn
-        } else {
-            CachePolicy::ForeverInCdnAndStaleInBrowser
-        },
+        cache_policy,
     )?)
 }
 
+/// Redirects to the newest release whose documentation still has the page at `path`, e.g. an item
+/// that was removed or renamed in later releases, so users of an old version's page can find the
+/// current equivalent.
+///
+/// Releases are checked newest first by semver, skipping yanked releases, pre-releases and
+/// releases without docs, up to [`ITEM_LATEST_MAX_RELEASES`] of them. If none of them has the
+/// page, redirect to the root of the latest docs.
+///
+/// Found pages are cached until the crate is invalidated by its next build. The redirect to the
+/// root is only cached shortly, the page might only be missing from the checked releases.
+pub(crate) async fn item_latest_handler(
+    Path((name, path)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+    Extension(storage): Extension<Arc<Storage>>,
+) -> AxumResult<impl IntoResponse> {
+    let path = path.trim_start_matches('/').to_owned();
+    let storage_path = if path.is_empty() || path.ends_with('/') {
+        format!("{path}index.html")
+    } else {
+        path.clone()
+    };
+
+    let redirect = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let crate_id: i32 = conn
+            .query_opt("SELECT id FROM crates WHERE name = $1", &[&name])?
+            .ok_or(AxumNope::CrateNotFound)?
+            .get(0);
+
+        let mut releases: Vec<(semver::Version, String, bool)> = conn
+            .query(
+                "SELECT version, target_name, archive_storage
+                 FROM releases
+                 WHERE crate_id = $1 AND rustdoc_status AND NOT yanked",
+                &[&crate_id],
+            )?
+            .into_iter()
+            .filter_map(|row| {
+                let version = semver::Version::parse(row.get("version")).ok()?;
+                Some((version, row.get("target_name"), row.get("archive_storage")))
+            })
+            .filter(|(version, _, _)| version.pre.is_empty())
+            .collect();
+        releases.sort_unstable_by(|a, b| b.0.cmp(&a.0));
+
+        for (version, _, archive_storage) in releases.iter().take(ITEM_LATEST_MAX_RELEASES) {
+            let version = version.to_string();
+            if storage.rustdoc_file_exists(&name, &version, &storage_path, *archive_storage)? {
+                return Ok((
+                    format!("/{name}/{version}/{path}"),
+                    CachePolicy::ForeverInCdn,
+                ));
+            }
+        }
+        let redirect = match releases.first() {
+            Some((_, target_name, _)) => format!("/{name}/latest/{target_name}/"),
+            // no release has docs at all
+            None => format!("/crate/{name}/latest"),
+        };
+        Ok((redirect, CachePolicy::ShortInCdn))
+    })
+    .await?;
+
+    Ok(axum_cached_redirect(&redirect.0, redirect.1)?)
+}
+
 #[derive(Deserialize, Debug)]
 pub(crate) struct BadgeQueryParams {
     version: Option<String>,
+    /// passed on to shields.io
+    style: Option<String>,
+    /// passed on to shields.io
+    label: Option<String>,
 }
 
 #[instrument]
@@ -756,16 +1187,96 @@ pub(crate) async fn badge_handler(
 ) -> AxumResult<impl IntoResponse> {
     let version = query.version.unwrap_or_else(|| "latest".to_string());
 
-    let url = url::Url::parse(&format!(
+    let mut url = url::Url::parse(&format!(
         "https://img.shields.io/docsrs/{}/{}",
         name, version
     ))
     .context("could not parse URL")?;
+    if query.style.is_some() || query.label.is_some() {
+        let mut pairs = url.query_pairs_mut();
+        if let Some(style) = &query.style {
+            pairs.append_pair("style", style);
+        }
+        if let Some(label) = &query.label {
+            pairs.append_pair("label", label);
+        }
+    }
 
+    // The redirect only depends on the url, which caches already use as key including the
+    // query, so there's no request header to list in `Vary`.
+    // It's only cached shortly so the CDN picks up changes to the badge variants quickly.
     Ok((
         StatusCode::MOVED_PERMANENTLY,
         [(http::header::LOCATION, url.to_string())],
-        Extension(CachePolicy::ForeverInCdnAndBrowser),
+        Extension(CachePolicy::ShortInCdn),
+    ))
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct StatusQueryParams {
+    version: Option<String>,
+    label: Option<String>,
+}
+
+/// The JSON schema of shields.io's endpoint badges, see <https://shields.io/endpoint>.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ShieldsEndpoint {
+    schema_version: u8,
+    label: String,
+    message: &'static str,
+    color: &'static str,
+}
+
+/// Status of the latest build of a release as shields.io endpoint JSON, for custom badges.
+///
+/// Unknown crates and versions, and releases without builds are reported as `unknown` instead of
+/// an error, so the badge still renders. Database errors are still errors.
+#[instrument(skip(pool))]
+pub(crate) async fn status_json_handler(
+    Path(name): Path<String>,
+    Query(query): Query<StatusQueryParams>,
+    Extension(pool): Extension<Pool>,
+) -> AxumResult<impl IntoResponse> {
+    let version = query.version.unwrap_or_else(|| "latest".to_string());
+
+    let build_status: Option<bool> = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let id = match match_version(&mut conn, &name, Some(&version)) {
+            Ok(matched) => matched.version.into_parts().1,
+            // database errors aren't an unknown status, they were already reported
+            Err(Nope::InternalServerError) => return Err(Nope::InternalServerError.into()),
+            // unknown, purged or invalid versions
+            Err(_) => return Ok(None),
+        };
+        Ok(conn
+            .query_opt(
+                "SELECT build_status
+                 FROM builds
+                 WHERE rid = $1
+                 ORDER BY build_time DESC
+                 LIMIT 1",
+                &[&id],
+            )?
+            .map(|row| row.get(0)))
+    })
+    .await?;
+
+    let (message, color) = match build_status {
+        Some(true) => ("passing", "brightgreen"),
+        Some(false) => ("failing", "red"),
+        None => ("unknown", "lightgrey"),
+    };
+
+    // the status changes with every build, so it can only be cached shortly
+    Ok((
+        Extension(CachePolicy::ShortInCdn),
+        Json(ShieldsEndpoint {
+            schema_version: 1,
+            label: query.label.unwrap_or_else(|| "docs".to_string()),
+            message,
+            color,
+        }),
     ))
 }
 
@@ -876,8 +1387,10 @@ impl Handler for LegacySharedResourceHandler {
 
 #[cfg(test)]
 mod test {
-    use crate::{test::*, web::cache::CachePolicy, Config};
+    use super::DocsLanding;
+    use crate::{docbuilder::BuildFailure, test::*, web::cache::CachePolicy, Config};
     use anyhow::Context;
+    use chrono::TimeZone;
     use kuchiki::traits::TendrilSink;
     use reqwest::{blocking::ClientBuilder, redirect, StatusCode};
     use std::collections::BTreeMap;
@@ -1068,6 +1581,78 @@ mod test {
         });
     }
 
+    #[test_case(true)]
+    #[test_case(false)]
+    fn wasm_default_target(archive_storage: bool) {
+        wrapper(|env| {
+            env.fake_release()
+                .name("dummy")
+                .version("0.1.0")
+                .archive_storage(archive_storage)
+                .rustdoc_file("dummy/index.html")
+                .rustdoc_file("dummy/struct.Dummy.html")
+                .rustdoc_file("dummy/fn.wasm_only.html")
+                .rustdoc_file("x86_64-unknown-linux-gnu/dummy/index.html")
+                .rustdoc_file("x86_64-unknown-linux-gnu/dummy/struct.Dummy.html")
+                .default_target("wasm32-unknown-unknown")
+                .add_target("x86_64-unknown-linux-gnu")
+                .create()?;
+            let web = env.frontend();
+
+            // the docs of the default target are served from the root like any other
+            let base = "/dummy/0.1.0/dummy/";
+            assert_success(base, web)?;
+            assert_redirect("/dummy/0.1.0/", base, web)?;
+            assert_redirect("/dummy/0.1.0/wasm32-unknown-unknown/dummy/", base, web)?;
+            assert_redirect(
+                "/dummy/0.1.0/wasm32-unknown-unknown/dummy/struct.Dummy.html",
+                "/dummy/0.1.0/dummy/struct.Dummy.html",
+                web,
+            )?;
+            assert_success("/dummy/0.1.0/x86_64-unknown-linux-gnu/dummy/", web)?;
+
+            let page = kuchiki::parse_html().one(web.get(base).send()?.text()?);
+            let platforms: Vec<_> = page
+                .select(r#"a[aria-label="Platform"] + ul li a"#)
+                .expect("invalid selector")
+                .map(|el| el.text_contents())
+                .collect();
+            assert_eq!(
+                platforms,
+                ["wasm32-unknown-unknown", "x86_64-unknown-linux-gnu"]
+            );
+
+            // switching between the targets
+            assert_redirect(
+                "/crate/dummy/0.1.0/target-redirect/x86_64-unknown-linux-gnu/dummy/struct.Dummy.html",
+                "/dummy/0.1.0/x86_64-unknown-linux-gnu/dummy/struct.Dummy.html",
+                web,
+            )?;
+            assert_redirect(
+                "/crate/dummy/0.1.0/target-redirect/wasm32-unknown-unknown/dummy/struct.Dummy.html",
+                "/dummy/0.1.0/dummy/struct.Dummy.html",
+                web,
+            )?;
+            // items only available on wasm go to the crate root of the other target
+            assert_redirect(
+                "/crate/dummy/0.1.0/target-redirect/x86_64-unknown-linux-gnu/dummy/fn.wasm_only.html",
+                "/dummy/0.1.0/x86_64-unknown-linux-gnu/dummy/",
+                web,
+            )?;
+
+            let targets: serde_json::Value =
+                web.get("/crate/dummy/0.1.0/targets.json").send()?.json()?;
+            assert_eq!(
+                targets,
+                serde_json::json!([
+                    {"target": "wasm32-unknown-unknown", "successful": true, "default": true},
+                    {"target": "x86_64-unknown-linux-gnu", "successful": true, "default": false},
+                ])
+            );
+            Ok(())
+        });
+    }
+
     #[test]
     fn latest_url() {
         wrapper(|env| {
@@ -1089,6 +1674,221 @@ mod test {
         })
     }
 
+    #[test]
+    fn prerelease_banner_and_latest_prerelease() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("dummy")
+                .version("0.1.0-alpha")
+                .rustdoc_file("dummy/index.html")
+                .create()?;
+            let web = env.frontend();
+
+            // only pre-releases, `latest` shows the newest one with a banner
+            let page = kuchiki::parse_html().one(web.get("/dummy/latest/dummy/").send()?.text()?);
+            assert!(page.select_first("[data-id=prerelease-banner]").is_ok());
+
+            env.fake_release()
+                .name("dummy")
+                .version("0.1.0")
+                .rustdoc_file("dummy/index.html")
+                .create()?;
+            env.fake_release()
+                .name("dummy")
+                .version("0.2.0-beta")
+                .rustdoc_file("dummy/index.html")
+                .create()?;
+
+            let page = kuchiki::parse_html().one(web.get("/dummy/latest/dummy/").send()?.text()?);
+            assert!(page.select_first("[data-id=prerelease-banner]").is_err());
+            assert_eq!(
+                page.select_first("a.crate-name .title")
+                    .unwrap()
+                    .text_contents(),
+                "dummy-0.1.0"
+            );
+
+            assert_redirect_cached(
+                "/dummy/latest-prerelease/dummy/",
+                "/dummy/0.2.0-beta/dummy/",
+                CachePolicy::ForeverInCdn,
+                web,
+                &env.config(),
+            )?;
+            Ok(())
+        })
+    }
+
+    #[test_case(true; "noindex enabled")]
+    #[test_case(false; "noindex disabled")]
+    fn noindex_unstable_versions(noindex_unstable_versions: bool) {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.noindex_unstable_versions = noindex_unstable_versions;
+            });
+            env.fake_release()
+                .name("dummy")
+                .version("0.1.0")
+                .rustdoc_file("dummy/index.html")
+                .create()?;
+            env.fake_release()
+                .name("dummy")
+                .version("0.2.0-alpha")
+                .rustdoc_file("dummy/index.html")
+                .create()?;
+            env.fake_release()
+                .name("dummy")
+                .version("0.0.1")
+                .yanked(true)
+                .rustdoc_file("dummy/index.html")
+                .create()?;
+            let web = env.frontend();
+
+            let has_noindex = |path: &str| -> Result<bool, anyhow::Error> {
+                let page = kuchiki::parse_html().one(web.get(path).send()?.text()?);
+                Ok(page
+                    .select_first(r#"head meta[name="robots"][content="noindex"]"#)
+                    .is_ok())
+            };
+            assert!(!has_noindex("/dummy/0.1.0/dummy/")?);
+            assert!(!has_noindex("/dummy/latest/dummy/")?);
+            assert_eq!(
+                has_noindex("/dummy/0.2.0-alpha/dummy/")?,
+                noindex_unstable_versions
+            );
+            assert_eq!(
+                has_noindex("/dummy/0.0.1/dummy/")?,
+                noindex_unstable_versions
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn server_timing_header() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.server_timing = true;
+            });
+
+            env.fake_release()
+                .name("dummy")
+                .version("0.1.0")
+                .archive_storage(true)
+                .rustdoc_file("dummy/index.html")
+                .create()?;
+
+            let resp = env.frontend().get("/dummy/0.1.0/dummy/").send()?;
+            assert!(resp.status().is_success());
+            let header = resp.headers()["server-timing"].to_str()?;
+            for step in [
+                "match-version",
+                "crate-details",
+                "range-request",
+                "rewrite-html",
+            ] {
+                assert!(header.contains(step), "{} missing in {}", step, header);
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn if_none_match_build_and_docsrs_version() {
+        wrapper(|env| {
+            let build_time = chrono::Utc.with_ymd_and_hms(2022, 6, 1, 12, 30, 0).unwrap();
+            env.fake_release()
+                .name("dummy")
+                .version("0.1.0")
+                .rustdoc_file("dummy/index.html")
+                .builds(vec![FakeBuild::default().build_time(build_time)])
+                .create()?;
+            let web = env.frontend();
+
+            let resp = web.get("/dummy/0.1.0/dummy/").send()?;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let etag = resp.headers()["etag"].to_str()?.to_owned();
+            assert_eq!(
+                etag,
+                rustdoc_page_etag(build_time, false, "0.1.0").to_string()
+            );
+
+            let resp = web
+                .get("/dummy/0.1.0/dummy/")
+                .header("if-none-match", &etag)
+                .send()?;
+            assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+            assert_eq!(resp.headers()["etag"], etag.as_str());
+            assert_cache_control(
+                &resp,
+                CachePolicy::ForeverInCdnAndStaleInBrowser,
+                &env.config(),
+            );
+
+            // pages of an older build, or rendered by another version of docs.rs
+            let other_build = chrono::Utc
+                .with_ymd_and_hms(2022, 5, 31, 12, 30, 0)
+                .unwrap();
+            for other in [
+                rustdoc_page_etag(other_build, false, "0.1.0").to_string(),
+                format!("W/\"{:x}-0\"", build_time.timestamp()),
+            ] {
+                let resp = web
+                    .get("/dummy/0.1.0/dummy/")
+                    .header("if-none-match", &other)
+                    .send()?;
+                assert_eq!(resp.status(), StatusCode::OK, "{}", other);
+            }
+
+            // the build time alone isn't enough anymore
+            let resp = web
+                .get("/dummy/0.1.0/dummy/")
+                .header("if-modified-since", "Wed, 01 Jun 2022 12:30:00 GMT")
+                .send()?;
+            assert_eq!(resp.status(), StatusCode::OK);
+
+            // the header warns about yanked releases and newer versions without a rebuild
+            env.db()
+                .conn()
+                .execute("UPDATE releases SET yanked = TRUE", &[])?;
+            let resp = web
+                .get("/dummy/0.1.0/dummy/")
+                .header("if-none-match", &etag)
+                .send()?;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let etag = resp.headers()["etag"].to_str()?.to_owned();
+
+            env.fake_release()
+                .name("dummy")
+                .version("0.2.0")
+                .rustdoc_file("dummy/index.html")
+                .create()?;
+            let resp = web
+                .get("/dummy/0.1.0/dummy/")
+                .header("if-none-match", &etag)
+                .send()?;
+            assert_eq!(resp.status(), StatusCode::OK);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn no_server_timing_header_by_default() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("dummy")
+                .version("0.1.0")
+                .archive_storage(true)
+                .rustdoc_file("dummy/index.html")
+                .create()?;
+
+            let resp = env.frontend().get("/dummy/0.1.0/dummy/").send()?;
+            assert!(resp.status().is_success());
+            assert!(resp.headers().get("server-timing").is_none());
+            Ok(())
+        })
+    }
+
     #[test]
     fn cache_headers_on_version() {
         wrapper(|env| {
@@ -1122,6 +1922,110 @@ mod test {
         })
     }
 
+    #[test_case(true)]
+    #[test_case(false)]
+    fn item_latest_finds_newest_release_with_item(archive_storage: bool) {
+        wrapper(|env| {
+            env.fake_release()
+                .name("dummy")
+                .version("0.1.0")
+                .archive_storage(archive_storage)
+                .rustdoc_file("dummy/struct.Removed.html")
+                .rustdoc_file("dummy/struct.Kept.html")
+                .create()?;
+            env.fake_release()
+                .name("dummy")
+                .version("0.2.0")
+                .archive_storage(archive_storage)
+                .rustdoc_file("dummy/struct.Removed.html")
+                .rustdoc_file("dummy/struct.Kept.html")
+                .create()?;
+            env.fake_release()
+                .name("dummy")
+                .version("0.3.0")
+                .archive_storage(archive_storage)
+                .rustdoc_file("dummy/struct.Kept.html")
+                .create()?;
+            // neither yanked releases nor pre-releases are linked
+            env.fake_release()
+                .name("dummy")
+                .version("0.4.0-alpha.1")
+                .archive_storage(archive_storage)
+                .rustdoc_file("dummy/struct.Removed.html")
+                .create()?;
+            env.fake_release()
+                .name("dummy")
+                .version("0.2.1")
+                .archive_storage(archive_storage)
+                .rustdoc_file("dummy/struct.Removed.html")
+                .yanked(true)
+                .create()?;
+            let web = env.frontend();
+
+            for (path, expected, cache_policy) in [
+                (
+                    "dummy/struct.Kept.html",
+                    "/dummy/0.3.0/dummy/struct.Kept.html",
+                    CachePolicy::ForeverInCdn,
+                ),
+                (
+                    "dummy/struct.Removed.html",
+                    "/dummy/0.2.0/dummy/struct.Removed.html",
+                    CachePolicy::ForeverInCdn,
+                ),
+                (
+                    "dummy/struct.Unknown.html",
+                    "/dummy/latest/dummy/",
+                    CachePolicy::ShortInCdn,
+                ),
+                ("dummy/", "/dummy/0.3.0/dummy/", CachePolicy::ForeverInCdn),
+            ] {
+                assert_redirect_cached(
+                    &format!("/crate/dummy/item-latest/{}", path),
+                    expected,
+                    cache_policy,
+                    web,
+                    &env.config(),
+                )?;
+            }
+
+            assert_eq!(
+                web.get("/crate/unknown/item-latest/unknown/")
+                    .send()?
+                    .status(),
+                StatusCode::NOT_FOUND
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn item_latest_checks_limited_releases() {
+        wrapper(|env| {
+            // only the oldest release has the page, and it's too old to be checked
+            env.fake_release()
+                .name("dummy")
+                .version("0.0.1")
+                .rustdoc_file("dummy/struct.Removed.html")
+                .create()?;
+            for minor in 1..=ITEM_LATEST_MAX_RELEASES {
+                env.fake_release()
+                    .name("dummy")
+                    .version(&format!("0.{}.0", minor))
+                    .create()?;
+            }
+
+            assert_redirect_cached(
+                "/crate/dummy/item-latest/dummy/struct.Removed.html",
+                "/dummy/latest/dummy/",
+                CachePolicy::ShortInCdn,
+                env.frontend(),
+                &env.config(),
+            )?;
+            Ok(())
+        })
+    }
+
     #[test_case(true)]
     #[test_case(false)]
     fn go_to_latest_version(archive_storage: bool) {
@@ -1230,6 +2134,78 @@ mod test {
         })
     }
 
+    #[test_case(true, true, true, DocsLanding::Lib)]
+    #[test_case(true, true, false, DocsLanding::Lib; "lib without bins")]
+    #[test_case(false, true, true, DocsLanding::Bin)]
+    #[test_case(false, true, false, DocsLanding::CrateDetails; "bin without docs")]
+    #[test_case(true, false, true, DocsLanding::CrateDetails; "failed build")]
+    fn docs_landing_preference(
+        is_library: bool,
+        has_docs: bool,
+        bin_has_docs: bool,
+        expected: DocsLanding,
+    ) {
+        assert_eq!(
+            DocsLanding::select(is_library, has_docs, || Ok(bin_has_docs)).unwrap(),
+            expected
+        );
+    }
+
+    #[test_case(true)]
+    #[test_case(false)]
+    fn redirect_to_lib_bin_or_crate_details(archive_storage: bool) {
+        wrapper(|env| {
+            env.fake_release()
+                .name("lib_only")
+                .version("0.1.0")
+                .archive_storage(archive_storage)
+                .create()?;
+            env.fake_release()
+                .name("bin_only")
+                .version("0.1.0")
+                .archive_storage(archive_storage)
+                .binary(true)
+                .create()?;
+            env.fake_release()
+                .name("documented_bin")
+                .version("0.1.0")
+                .archive_storage(archive_storage)
+                .binary(true)
+                .has_docs(true)
+                .rustdoc_file("documented_bin/index.html")
+                .create()?;
+            env.fake_release()
+                .name("undocumented_bin")
+                .version("0.1.0")
+                .archive_storage(archive_storage)
+                .binary(true)
+                .has_docs(true)
+                .create()?;
+            env.fake_release()
+                .name("lib_and_bin")
+                .version("0.1.0")
+                .archive_storage(archive_storage)
+                .add_bin_target("cli")
+                .create()?;
+            let web = env.frontend();
+
+            assert_redirect("/lib_only/0.1.0", "/lib_only/0.1.0/lib_only/", web)?;
+            assert_redirect("/bin_only/0.1.0", "/crate/bin_only/0.1.0", web)?;
+            assert_redirect(
+                "/documented_bin/0.1.0",
+                "/documented_bin/0.1.0/documented_bin/",
+                web,
+            )?;
+            assert_redirect(
+                "/undocumented_bin/0.1.0",
+                "/crate/undocumented_bin/0.1.0",
+                web,
+            )?;
+            assert_redirect("/lib_and_bin/0.1.0", "/lib_and_bin/0.1.0/lib_and_bin/", web)?;
+            Ok(())
+        })
+    }
+
     #[test_case(true)]
     #[test_case(false)]
     fn redirect_latest_goes_to_crate_if_build_failed(archive_storage: bool) {
@@ -1315,22 +2291,58 @@ mod test {
             env.fake_release()
                 .name("dummy")
                 .version("0.1.0")
-                .archive_storage(archive_storage)
+                .archive_storage(archive_storage)
+                .rustdoc_file("dummy/index.html")
+                .yanked(true)
+                .create()?;
+
+            assert!(has_yanked_warning("/dummy/0.1.0/dummy/", web)?);
+
+            env.fake_release()
+                .name("dummy")
+                .version("0.2.0")
+                .archive_storage(archive_storage)
+                .rustdoc_file("dummy/index.html")
+                .yanked(true)
+                .create()?;
+
+            assert!(has_yanked_warning("/dummy/0.1.0/dummy/", web)?);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn yanked_banner_follows_yanked_flag() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("dummy")
+                .version("0.1.0")
                 .rustdoc_file("dummy/index.html")
-                .yanked(true)
                 .create()?;
+            let web = env.frontend();
 
-            assert!(has_yanked_warning("/dummy/0.1.0/dummy/", web)?);
+            let set_yanked = |yanked: bool| {
+                env.db()
+                    .conn()
+                    .execute("UPDATE releases SET yanked = $1", &[&yanked])
+            };
+            let check = |yanked: bool| -> Result<(), anyhow::Error> {
+                let page =
+                    kuchiki::parse_html().one(web.get("/dummy/0.1.0/dummy/").send()?.text()?);
+                assert_eq!(page.select_first("#yanked-banner").is_ok(), yanked);
 
-            env.fake_release()
-                .name("dummy")
-                .version("0.2.0")
-                .archive_storage(archive_storage)
-                .rustdoc_file("dummy/index.html")
-                .yanked(true)
-                .create()?;
+                let details: serde_json::Value =
+                    web.get("/crate/dummy/0.1.0?format=json").send()?.json()?;
+                assert_eq!(details["yanked"], yanked);
+                Ok(())
+            };
 
-            assert!(has_yanked_warning("/dummy/0.1.0/dummy/", web)?);
+            check(false)?;
+            set_yanked(true)?;
+            check(true)?;
+            set_yanked(false)?;
+            check(false)?;
 
             Ok(())
         })
@@ -1366,11 +2378,7 @@ mod test {
             }
             println!("({} -> {})", last_url, current_url);
             assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
-            assert_cache_control(
-                &response,
-                CachePolicy::ForeverInCdnAndBrowser,
-                &env.config(),
-            );
+            assert_cache_control(&response, CachePolicy::ShortInCdn, &env.config());
             assert_eq!(
                 current_url.as_str(),
                 "https://img.shields.io/docsrs/zstd/latest"
@@ -1503,6 +2511,82 @@ mod test {
         })
     }
 
+    #[test]
+    fn badge_variants() {
+        wrapper(|env| {
+            let web = env.frontend();
+
+            let mut locations = Vec::new();
+            for url in [
+                "/foo/badge.svg",
+                "/foo/badge.svg?style=flat-square",
+                "/foo/badge.svg?style=for-the-badge",
+                "/foo/badge.svg?style=flat-square&label=docs%20%26%20more&color=red",
+            ] {
+                let response = web.get_no_redirect(url).send()?;
+                assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY, "{}", url);
+                assert_cache_control(&response, CachePolicy::ShortInCdn, &env.config());
+                locations.push(response.headers()["location"].to_str()?.to_owned());
+            }
+
+            assert_eq!(
+                locations,
+                [
+                    "https://img.shields.io/docsrs/foo/latest",
+                    "https://img.shields.io/docsrs/foo/latest?style=flat-square",
+                    "https://img.shields.io/docsrs/foo/latest?style=for-the-badge",
+                    "https://img.shields.io/docsrs/foo/latest?style=flat-square&label=docs+%26+more",
+                ]
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn status_json() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.2.0")
+                .build_result_failed()
+                .create()?;
+            let web = env.frontend();
+
+            let status = |path: &str| -> Result<serde_json::Value, anyhow::Error> {
+                let response = web.get(path).send()?;
+                assert_eq!(response.status(), StatusCode::OK);
+                assert_cache_control(&response, CachePolicy::ShortInCdn, &env.config());
+                Ok(response.json()?)
+            };
+
+            assert_eq!(
+                status("/foo/status.json")?,
+                serde_json::json!({
+                    "schemaVersion": 1,
+                    "label": "docs",
+                    "message": "failing",
+                    "color": "red",
+                })
+            );
+            assert_eq!(
+                status("/foo/status.json?version=0.1.0&label=documentation")?,
+                serde_json::json!({
+                    "schemaVersion": 1,
+                    "label": "documentation",
+                    "message": "passing",
+                    "color": "brightgreen",
+                })
+            );
+            for path in ["/foo/status.json?version=0.3.0", "/bar/status.json"] {
+                let status = status(path)?;
+                assert_eq!(status["message"], "unknown");
+                assert_eq!(status["color"], "lightgrey");
+            }
+            Ok(())
+        })
+    }
+
     #[test]
     fn nonexistent_crate_404s() {
         wrapper(|env| {
@@ -1819,7 +2903,7 @@ mod test {
                 &[
                     (
                         "x86_64-pc-windows-msvc",
-                        "/dummy/0.4.0/x86_64-pc-windows-msvc/dummy/?search=DefaultOnly",
+                        "/dummy/0.4.0/x86_64-pc-windows-msvc/dummy/",
                     ),
                     (
                         "x86_64-unknown-linux-gnu",
@@ -1866,10 +2950,7 @@ mod test {
                         "x86_64-pc-windows-msvc",
                         "/dummy/0.4.0/x86_64-pc-windows-msvc/dummy/struct.WindowsOnly.html",
                     ),
-                    (
-                        "x86_64-unknown-linux-gnu",
-                        "/dummy/0.4.0/dummy/?search=WindowsOnly",
-                    ),
+                    ("x86_64-unknown-linux-gnu", "/dummy/0.4.0/dummy/"),
                 ],
             )?;
 
@@ -1891,6 +2972,29 @@ mod test {
         })
     }
 
+    #[test]
+    fn target_redirect_loop_redirects_to_crate_details() {
+        wrapper(|env| {
+            let id = env.fake_release().name("foo").version("0.1.0").create()?;
+            // `foo-cli/` looks like a target to the rustdoc pages, they send it back to
+            // `target-redirect` which can't find the page either.
+            env.db().conn().execute(
+                "UPDATE releases SET target_name = 'foo-cli' WHERE id = $1",
+                &[&id],
+            )?;
+            let web = env.frontend();
+
+            assert_redirect_cached(
+                "/crate/foo/0.1.0/target-redirect/foo-cli/index.html",
+                "/crate/foo/0.1.0",
+                CachePolicy::ForeverInCdnAndStaleInBrowser,
+                web,
+                &env.config(),
+            )?;
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_redirect_to_latest_302() {
         wrapper(|env| {
@@ -1940,6 +3044,101 @@ mod test {
         })
     }
 
+    #[test]
+    fn deleted_yanked_version_is_gone() {
+        wrapper(|env| {
+            env.fake_release().name("dummy").version("0.1.0").create()?;
+            env.fake_release()
+                .name("dummy")
+                .version("0.2.0")
+                .yanked(true)
+                .create()?;
+            env.fake_release()
+                .name("dummy")
+                .version("0.3.0")
+                .yanked(true)
+                .create()?;
+            let web = env.frontend();
+
+            crate::db::delete_version(env, "dummy", "0.2.0")?;
+
+            for path in &[
+                "/dummy/0.2.0/dummy/",
+                "/dummy/0.2.0/dummy/struct.Foo.html",
+                "/crate/dummy/0.2.0",
+            ] {
+                let response = web.get(path).send()?;
+                assert_eq!(response.status(), StatusCode::GONE, "{}", path);
+                assert!(response.text()?.contains("has been removed"));
+            }
+
+            // yanked versions which weren't deleted are still served, with a warning
+            let response = web.get("/dummy/0.3.0/dummy/").send()?;
+            assert!(response.status().is_success());
+            assert!(response.text()?.contains("This release has been yanked"));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn version_range_redirects() {
+        wrapper(|env| {
+            for version in &["1.1.0", "1.2.0", "1.2.5", "2.0.0"] {
+                env.fake_release().name("dummy").version(version).create()?;
+            }
+            env.fake_release()
+                .name("dummy")
+                .version("1.3.0")
+                .build_result_failed()
+                .create()?;
+            let web = env.frontend();
+
+            // caret, skipping the newest matching release without docs
+            assert_redirect("/dummy/%5E1.2/dummy/", "/dummy/1.2.5/dummy/", web)?;
+            assert_redirect("/dummy/%5E1/dummy/", "/dummy/1.2.5/dummy/", web)?;
+            // tilde
+            assert_redirect("/dummy/~1.2.0/dummy/", "/dummy/1.2.5/dummy/", web)?;
+            assert_redirect("/dummy/~1.1/dummy/", "/dummy/1.1.0/dummy/", web)?;
+            // exact match
+            assert_redirect("/dummy/=1.2.0/dummy/", "/dummy/1.2.0/dummy/", web)?;
+            // the newest release is still used when no match has docs
+            assert_redirect("/crate/dummy/=1.3.0", "/crate/dummy/1.3.0", web)?;
+
+            assert_eq!(
+                web.get("/dummy/%5E3/dummy/").send()?.status(),
+                StatusCode::NOT_FOUND
+            );
+            for path in &[
+                "/dummy/%5E1.a/dummy/",
+                "/dummy/~/dummy/",
+                "/crate/dummy/%3E=1,%20%3C",
+            ] {
+                let response = web.get(path).send()?;
+                assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", path);
+                assert!(response.text()?.contains("invalid version range"));
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn deleted_version_without_yank_is_not_found() {
+        wrapper(|env| {
+            env.fake_release().name("dummy").version("0.1.0").create()?;
+            env.fake_release().name("dummy").version("0.2.0").create()?;
+
+            crate::db::delete_version(env, "dummy", "0.2.0")?;
+
+            assert_eq!(
+                env.frontend().get("/dummy/0.2.0/dummy/").send()?.status(),
+                StatusCode::NOT_FOUND
+            );
+            Ok(())
+        })
+    }
+
     #[test_case(true)]
     #[test_case(false)]
     fn test_no_trailing_target_slash(archive_storage: bool) {
@@ -1976,6 +3175,61 @@ mod test {
         })
     }
 
+    #[test_case(true)]
+    #[test_case(false)]
+    fn target_redirect_platform_gated_module(archive_storage: bool) {
+        wrapper(|env| {
+            env.fake_release()
+                .name("dummy")
+                .version("0.1.0")
+                .archive_storage(archive_storage)
+                .rustdoc_file("dummy/index.html")
+                .rustdoc_file("dummy/os/index.html")
+                .rustdoc_file("dummy/os/unix/index.html")
+                .rustdoc_file("dummy/os/unix/fn.getuid.html")
+                .rustdoc_file("x86_64-pc-windows-msvc/dummy/index.html")
+                .rustdoc_file("x86_64-pc-windows-msvc/dummy/os/index.html")
+                .rustdoc_file("x86_64-pc-windows-msvc/dummy/os/windows/index.html")
+                .default_target("x86_64-unknown-linux-gnu")
+                .add_target("x86_64-pc-windows-msvc")
+                .create()?;
+            let web = env.frontend();
+
+            // modules existing on both platforms are kept
+            assert_redirect(
+                "/crate/dummy/0.1.0/target-redirect/x86_64-pc-windows-msvc/dummy/os/index.html",
+                "/dummy/0.1.0/x86_64-pc-windows-msvc/dummy/os/index.html",
+                web,
+            )?;
+
+            // platform-gated modules and items go to the root of the other platform
+            assert_redirect(
+                "/crate/dummy/0.1.0/target-redirect/x86_64-pc-windows-msvc/dummy/os/unix/index.html",
+                "/dummy/0.1.0/x86_64-pc-windows-msvc/dummy/",
+                web,
+            )?;
+            assert_redirect(
+                "/crate/dummy/0.1.0/target-redirect/x86_64-pc-windows-msvc/dummy/os/unix/fn.getuid.html",
+                "/dummy/0.1.0/x86_64-pc-windows-msvc/dummy/",
+                web,
+            )?;
+            assert_redirect(
+                "/crate/dummy/latest/target-redirect/x86_64-unknown-linux-gnu/dummy/os/windows/index.html",
+                "/dummy/latest/dummy/",
+                web,
+            )?;
+
+            // pages missing on every platform still fall back to a search
+            assert_redirect(
+                "/crate/dummy/0.1.0/target-redirect/x86_64-pc-windows-msvc/dummy/os/struct.Removed.html",
+                "/dummy/0.1.0/x86_64-pc-windows-msvc/dummy/?search=Removed",
+                web,
+            )?;
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_redirect_crate_coloncolon_path() {
         wrapper(|env| {
@@ -2303,7 +3557,70 @@ mod test {
                 "/winapi/0.3.9/winapi/macro.ENUM.html",
                 env.frontend(),
             )?;
-            assert_not_found("/winapi/0.3.9/winapi/struct.not_here.html", env.frontend())?;
+            assert_redirect(
+                "/winapi/0.3.9/winapi/struct.not_here.html",
+                "/winapi/0.3.9/winapi/",
+                env.frontend(),
+            )?;
+
+            Ok(())
+        })
+    }
+
+    #[test_case(true)]
+    #[test_case(false)]
+    fn test_missing_page_in_built_crate_redirects_to_index(archive_storage: bool) {
+        wrapper(|env| {
+            env.fake_release()
+                .name("dummy")
+                .version("0.1.0")
+                .archive_storage(archive_storage)
+                .rustdoc_file("dummy/struct.Foo.html")
+                .create()?;
+            let web = env.frontend();
+
+            assert_redirect(
+                "/dummy/0.1.0/dummy/old_module/struct.Foo.html",
+                "/dummy/0.1.0/dummy/",
+                web,
+            )?;
+            assert_redirect("/dummy/latest/dummy/removed/", "/dummy/latest/dummy/", web)?;
+
+            // missing assets are still a 404
+            assert_eq!(
+                web.get("/dummy/0.1.0/dummy/missing.js").send()?.status(),
+                StatusCode::NOT_FOUND
+            );
+            // as is a missing version
+            assert_eq!(
+                web.get("/dummy/0.2.0/dummy/struct.Foo.html")
+                    .send()?
+                    .status(),
+                StatusCode::NOT_FOUND
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test_case(true)]
+    #[test_case(false)]
+    fn test_missing_page_in_failed_build_404s(archive_storage: bool) {
+        wrapper(|env| {
+            env.fake_release()
+                .name("dummy")
+                .version("0.1.0")
+                .archive_storage(archive_storage)
+                .build_result_failed()
+                .create()?;
+
+            assert_eq!(
+                env.frontend()
+                    .get("/dummy/0.1.0/dummy/struct.Foo.html")
+                    .send()?
+                    .status(),
+                StatusCode::NOT_FOUND
+            );
 
             Ok(())
         })
@@ -2519,4 +3836,115 @@ mod test {
             Ok(())
         });
     }
+
+    #[test]
+    fn queued_release_shows_build_in_progress() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let queue = env.build_queue();
+            queue.add_crate("bar", "1.0.0", 0, None)?;
+            queue.add_crate("foo", "0.2.0", 0, None)?;
+            let web = env.frontend();
+
+            for url in ["/foo/0.2.0/foo/", "/foo/0.2.0", "/bar", "/bar/latest/bar/"] {
+                let response = web.get(url).send()?;
+                assert_eq!(response.status(), StatusCode::ACCEPTED, "{}", url);
+                assert_cache_control(&response, CachePolicy::NoCaching, &env.config());
+            }
+            let page = kuchiki::parse_html().one(web.get("/foo/0.2.0/foo/").send()?.text()?);
+            assert_eq!(
+                page.select_first("#crate-title").unwrap().text_contents(),
+                "foo 0.2.0"
+            );
+            assert!(page
+                .select_first("[data-id=queue-position]")
+                .unwrap()
+                .text_contents()
+                .contains("position 2"));
+
+            for url in ["/foo/0.3.0/foo/", "/baz/latest/baz/", "/baz"] {
+                assert_eq!(
+                    web.get(url).send()?.status(),
+                    StatusCode::NOT_FOUND,
+                    "{}",
+                    url
+                );
+            }
+
+            // once built, the docs are served normally
+            queue.process_next_crate(|_| Ok(()))?;
+            queue.process_next_crate(|krate| {
+                env.fake_release()
+                    .name(&krate.name)
+                    .version(&krate.version)
+                    .create()?;
+                Ok(())
+            })?;
+            assert_success("/foo/0.2.0/foo/", web)?;
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn docs_too_large_shows_explanation() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .has_docs(false)
+                .builds(vec![FakeBuild::default()
+                    .successful(false)
+                    .failure(BuildFailure::DocsTooLarge)])
+                .create()?;
+            env.fake_release()
+                .name("bar")
+                .version("0.1.0")
+                .build_result_failed()
+                .create()?;
+            let build_id: i32 = env
+                .db()
+                .conn()
+                .query_one(
+                    "SELECT id FROM builds WHERE failure_category = $1",
+                    &[&BuildFailure::DocsTooLarge.as_str()],
+                )?
+                .get(0);
+            let web = env.frontend();
+
+            for url in [
+                "/foo/0.1.0",
+                "/foo/0.1.0/foo/",
+                "/foo/latest/foo/struct.Foo.html",
+            ] {
+                let response = web.get(url).send()?;
+                assert_eq!(response.status(), StatusCode::OK, "{}", url);
+                let page = kuchiki::parse_html().one(response.text()?);
+                let link = page
+                    .select_first("[data-id=docs-too-large] a")
+                    .expect("missing build log link");
+                assert_eq!(
+                    link.attributes.borrow().get("href").unwrap(),
+                    format!("/crate/foo/0.1.0/builds/{}", build_id)
+                );
+            }
+
+            // other failures don't get the explanation
+            assert_redirect("/bar/0.1.0", "/crate/bar/0.1.0", web)?;
+            assert_eq!(
+                web.get("/bar/0.1.0/bar/").send()?.status(),
+                StatusCode::NOT_FOUND
+            );
+
+            let builds: serde_json::Value =
+                web.get("/crate/foo/0.1.0/builds.json").send()?.json()?;
+            assert_eq!(builds[0]["failure_category"], "docs-too-large");
+            let build: serde_json::Value = web
+                .get(&format!("/crate/foo/0.1.0/builds/{}?format=json", build_id))
+                .send()?
+                .json()?;
+            assert_eq!(build["failure_category"], "docs-too-large");
+
+            Ok(())
+        })
+    }
 }
@@ -0,0 +1,448 @@
+//! Lists the rustdoc static files the documentation of a release loads, so embedders can use the
+//! same stylesheets and scripts as the pages on docs.rs.
+
+use crate::{
+    db::Pool,
+    utils::spawn_blocking,
+    web::{
+        cache::CachePolicy, error::JsonAxumResult, match_version_axum, MatchSemver, RustdocRelease,
+    },
+    Storage,
+};
+use anyhow::Context as _;
+use axum::{
+    extract::{Extension, Path},
+    response::IntoResponse,
+    Json,
+};
+use lol_html::{element, errors::RewritingError};
+use serde::Serialize;
+use std::{cell::RefCell, sync::Arc};
+
+/// The path rustdoc is told to load its static files from with `--static-root-path`
+const RUSTDOC_STATIC_PATH: &str = "/-/rustdoc.static/";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum AssetKind {
+    Stylesheet,
+    Script,
+    Font,
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Asset {
+    url: String,
+    kind: AssetKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct AssetsJson {
+    assets: Vec<Asset>,
+}
+
+impl AssetKind {
+    /// The kind of a file rustdoc loads itself, like the search script, by its extension.
+    fn from_file_name(name: &str) -> Self {
+        if name.ends_with(".css") {
+            AssetKind::Stylesheet
+        } else if name.ends_with(".js") {
+            AssetKind::Script
+        } else if name.ends_with(".woff2") || name.ends_with(".woff") {
+            AssetKind::Font
+        } else {
+            AssetKind::Other
+        }
+    }
+}
+
+/// Parses the static files below [`RUSTDOC_STATIC_PATH`] a rustdoc page references, in the order
+/// they appear, without duplicates.
+///
+/// When `on_demand` is set, this includes the files the page doesn't load right away: the ones
+/// rustdoc loads on demand, which it lists on the `rustdoc-vars` element, and the fallbacks in
+/// `<noscript>`. Releases documented before docs.rs used `--static-root-path` reference their
+/// files relatively and have none.
+fn parse_assets(html: &[u8], on_demand: bool) -> Result<Vec<Asset>, RewritingError> {
+    use lol_html::{html_content::Element, HtmlRewriter, Settings};
+
+    let assets = RefCell::new(Vec::<Asset>::new());
+    let noscript = RefCell::new(Vec::<String>::new());
+    let add = |url: String, kind: AssetKind| {
+        let mut assets = assets.borrow_mut();
+        if url.starts_with(RUSTDOC_STATIC_PATH) && !assets.iter().any(|asset| asset.url == url) {
+            assets.push(Asset { url, kind });
+        }
+    };
+
+    let mut element_content_handlers = vec![
+        element!("noscript link[href]", |link: &mut Element| {
+            if !on_demand {
+                noscript
+                    .borrow_mut()
+                    .push(link.get_attribute("href").unwrap_or_default());
+            }
+            Ok(())
+        }),
+        element!("link[href]", |link: &mut Element| {
+            let rel = link.get_attribute("rel").unwrap_or_default();
+            let kind = if rel.split_whitespace().any(|rel| rel == "stylesheet") {
+                AssetKind::Stylesheet
+            } else if link.get_attribute("as").as_deref() == Some("font") {
+                AssetKind::Font
+            } else {
+                AssetKind::Other
+            };
+            add(link.get_attribute("href").unwrap_or_default(), kind);
+            Ok(())
+        }),
+        element!("script[src]", |script: &mut Element| {
+            add(
+                script.get_attribute("src").unwrap_or_default(),
+                AssetKind::Script,
+            );
+            Ok(())
+        }),
+    ];
+    if on_demand {
+        element_content_handlers.push(element!("[data-static-root-path]", |vars: &mut Element| {
+            let root = vars
+                .get_attribute("data-static-root-path")
+                .unwrap_or_default();
+            for attribute in vars.attributes() {
+                let name = attribute.name();
+                if name.starts_with("data-") && (name.ends_with("-js") || name.ends_with("-css")) {
+                    let file = attribute.value();
+                    add(
+                        format!("{}{}", root, file),
+                        AssetKind::from_file_name(&file),
+                    );
+                }
+            }
+            Ok(())
+        }));
+    }
+
+    let settings = Settings {
+        element_content_handlers,
+        ..Settings::default()
+    };
+    let mut rewriter = HtmlRewriter::new(settings, |_: &[u8]| {});
+    rewriter.write(html)?;
+    rewriter.end()?;
+
+    let noscript = noscript.into_inner();
+    let mut assets = assets.into_inner();
+    assets.retain(|asset| !noscript.contains(&asset.url));
+    Ok(assets)
+}
+
+/// The stylesheets, scripts and fonts a rustdoc page loads right away.
+///
+/// All pages of a release reference the same static files, so this is parsed once from the root
+/// page of the default target when the release is built, and stored with it.
+pub(crate) fn preload_assets(html: &[u8]) -> Result<Vec<String>, RewritingError> {
+    Ok(parse_assets(html, false)?
+        .into_iter()
+        .filter(|asset| asset.kind != AssetKind::Other)
+        .map(|asset| asset.url)
+        .collect())
+}
+
+/// The value of a `Link` header preloading the stored [`preload_assets`] of a release, `None` if
+/// there are none, like for releases built before they were stored.
+pub(super) fn preload_links(assets: &[String]) -> Option<String> {
+    let links: Vec<String> = assets
+        .iter()
+        .filter_map(|url| {
+            let destination = match AssetKind::from_file_name(url) {
+                AssetKind::Stylesheet => "style",
+                AssetKind::Script => "script",
+                // fonts are always fetched in CORS mode
+                AssetKind::Font => "font; crossorigin",
+                AssetKind::Other => return None,
+            };
+            Some(format!("<{}>; rel=preload; as={}", url, destination))
+        })
+        .collect();
+    if links.is_empty() {
+        None
+    } else {
+        Some(links.join(", "))
+    }
+}
+
+/// Serves the rustdoc static files referenced by the root page of the default target of a
+/// release.
+pub(crate) async fn assets_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+    Extension(storage): Extension<Arc<Storage>>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let version = match match_version_axum(&pool, &name, Some(&req_version))
+        .await?
+        .assume_exact()?
+    {
+        MatchSemver::Exact((version, _)) | MatchSemver::Latest((version, _)) => version,
+        MatchSemver::Semver((version, _)) => {
+            return Ok(super::axum_cached_redirect(
+                &format!("/crate/{}/{}/assets.json", name, version),
+                CachePolicy::ForeverInCdn,
+            )?
+            .into_response());
+        }
+    };
+
+    let assets = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let release = RustdocRelease::load(&mut conn, &name, &version)?;
+        let path = format!("{}/index.html", release.target_name);
+        let content = release.fetch(&storage, &path)?.content;
+
+        parse_assets(&content, true)
+            .with_context(|| format!("failed to parse {} of {} {}", path, name, version))
+    })
+    .await?;
+
+    Ok((
+        Extension(CachePolicy::ForeverInCdn),
+        Json(AssetsJson { assets }),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_assets, preload_assets, preload_links, Asset, AssetKind};
+    use crate::{
+        test::{assert_cache_control, assert_redirect_cached, wrapper},
+        web::cache::CachePolicy,
+    };
+    use kuchiki::traits::TendrilSink;
+    use reqwest::StatusCode;
+    use test_case::test_case;
+
+    const INDEX_HTML: &str = r#"<!DOCTYPE html><html><head>
+<link rel="preload" as="font" type="font/woff2" crossorigin href="/-/rustdoc.static/SourceSerif4-Regular-46f98efaafac5295.ttf.woff2">
+<link rel="stylesheet" href="/-/rustdoc.static/normalize-76eba96aa4d2e634.css">
+<link rel="stylesheet" href="/-/rustdoc.static/rustdoc-cb6f1f67f1bcd037.css">
+<meta name="rustdoc-vars" data-root-path="../" data-static-root-path="/-/rustdoc.static/" data-current-crate="foo" data-search-js="search-8be46b629f5f14a8.js" data-settings-js="settings-74424d7eec62a23e.js" data-settings-css="settings-8c76f75bfb6bd192.css">
+<script src="/-/rustdoc.static/storage-62ce34ea385b278a.js"></script>
+<script defer src="../crates.js"></script>
+<noscript><link rel="stylesheet" href="/-/rustdoc.static/noscript-cffde32267a19fd6.css"></noscript>
+<link rel="icon" href="/-/rustdoc.static/favicon-32x32-422f7d1d52889060.png">
+</head><body><script src="/-/rustdoc.static/storage-62ce34ea385b278a.js"></script></body></html>"#;
+
+    fn asset(url: &str, kind: AssetKind) -> Asset {
+        Asset {
+            url: url.into(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn parse_referenced_assets() {
+        assert_eq!(
+            parse_assets(INDEX_HTML.as_bytes(), true).unwrap(),
+            vec![
+                asset(
+                    "/-/rustdoc.static/SourceSerif4-Regular-46f98efaafac5295.ttf.woff2",
+                    AssetKind::Font
+                ),
+                asset(
+                    "/-/rustdoc.static/normalize-76eba96aa4d2e634.css",
+                    AssetKind::Stylesheet
+                ),
+                asset(
+                    "/-/rustdoc.static/rustdoc-cb6f1f67f1bcd037.css",
+                    AssetKind::Stylesheet
+                ),
+                asset(
+                    "/-/rustdoc.static/search-8be46b629f5f14a8.js",
+                    AssetKind::Script
+                ),
+                asset(
+                    "/-/rustdoc.static/settings-74424d7eec62a23e.js",
+                    AssetKind::Script
+                ),
+                asset(
+                    "/-/rustdoc.static/settings-8c76f75bfb6bd192.css",
+                    AssetKind::Stylesheet
+                ),
+                asset(
+                    "/-/rustdoc.static/storage-62ce34ea385b278a.js",
+                    AssetKind::Script
+                ),
+                asset(
+                    "/-/rustdoc.static/noscript-cffde32267a19fd6.css",
+                    AssetKind::Stylesheet
+                ),
+                asset(
+                    "/-/rustdoc.static/favicon-32x32-422f7d1d52889060.png",
+                    AssetKind::Other
+                ),
+            ]
+        );
+
+        // relative references of old releases
+        assert!(parse_assets(
+            br#"<link rel="stylesheet" href="../rustdoc-20200101.css">"#,
+            true
+        )
+        .unwrap()
+        .is_empty());
+    }
+
+    #[test]
+    fn assets_match_served_page() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .rustdoc_file_with("foo/index.html", INDEX_HTML.as_bytes())
+                .create()?;
+            let web = env.frontend();
+
+            let response = web.get("/crate/foo/latest/assets.json").send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_cache_control(&response, CachePolicy::ForeverInCdn, &env.config());
+            let value: serde_json::Value = response.json()?;
+            let assets: Vec<&str> = value["assets"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|asset| asset["url"].as_str().unwrap())
+                .collect();
+
+            // every `<link>` and `<script>` of the served page below the static path is listed
+            let html = web.get("/foo/0.1.0/foo/").send()?.text()?;
+            let page = kuchiki::parse_html().one(html.as_str());
+            let mut referenced: Vec<String> = page
+                .select("link[href], script[src]")
+                .unwrap()
+                .filter_map(|element| {
+                    let attributes = element.attributes.borrow();
+                    attributes
+                        .get("href")
+                        .or_else(|| attributes.get("src"))
+                        .map(str::to_owned)
+                })
+                .filter(|url| url.starts_with("/-/rustdoc.static/"))
+                .collect();
+            referenced.sort();
+            referenced.dedup();
+            assert!(!referenced.is_empty());
+            for url in &referenced {
+                assert!(assets.contains(&url.as_str()), "{} is missing", url);
+            }
+            // the files rustdoc loads on demand are only named on the page
+            for url in &assets {
+                let file = url.trim_start_matches("/-/rustdoc.static/");
+                assert!(html.contains(file), "{} is not referenced", url);
+            }
+
+            assert_redirect_cached(
+                "/crate/foo/0.1/assets.json",
+                "/crate/foo/0.1.0/assets.json",
+                CachePolicy::ForeverInCdn,
+                web,
+                &env.config(),
+            )?;
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn preload_links_of_page() {
+        let assets = preload_assets(INDEX_HTML.as_bytes()).unwrap();
+        // neither the files rustdoc loads on demand nor the `<noscript>` fallbacks
+        assert_eq!(
+            assets,
+            vec![
+                "/-/rustdoc.static/SourceSerif4-Regular-46f98efaafac5295.ttf.woff2",
+                "/-/rustdoc.static/normalize-76eba96aa4d2e634.css",
+                "/-/rustdoc.static/rustdoc-cb6f1f67f1bcd037.css",
+                "/-/rustdoc.static/storage-62ce34ea385b278a.js",
+            ]
+        );
+        assert_eq!(
+            preload_links(&assets).unwrap(),
+            "</-/rustdoc.static/SourceSerif4-Regular-46f98efaafac5295.ttf.woff2>; rel=preload; as=font; crossorigin, \
+             </-/rustdoc.static/normalize-76eba96aa4d2e634.css>; rel=preload; as=style, \
+             </-/rustdoc.static/rustdoc-cb6f1f67f1bcd037.css>; rel=preload; as=style, \
+             </-/rustdoc.static/storage-62ce34ea385b278a.js>; rel=preload; as=script"
+        );
+        assert!(
+            preload_assets(br#"<link rel="stylesheet" href="../rustdoc-20200101.css">"#)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(preload_links(&[]), None);
+    }
+
+    #[test_case(true)]
+    #[test_case(false)]
+    fn early_hints(enabled: bool) {
+        wrapper(|env| {
+            env.override_config(|config| config.early_hints = enabled);
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .rustdoc_file_with("foo/index.html", INDEX_HTML.as_bytes())
+                .rustdoc_file("foo/struct.Foo.html")
+                .create()?;
+            let web = env.frontend();
+            let preloads = |response: reqwest::blocking::Response| -> Vec<String> {
+                assert_eq!(response.status(), StatusCode::OK);
+                response
+                    .headers()
+                    .get_all("link")
+                    .iter()
+                    .map(|link| link.to_str().unwrap().to_owned())
+                    .filter(|link| link.contains("rel=preload"))
+                    .collect()
+            };
+
+            let links = preloads(web.get("/foo/0.1.0/foo/").send()?);
+            if enabled {
+                assert_eq!(links.len(), 1);
+                assert!(links[0].contains(
+                    "</-/rustdoc.static/rustdoc-cb6f1f67f1bcd037.css>; rel=preload; as=style"
+                ));
+                // the files rustdoc loads on demand aren't needed for the first paint
+                assert!(!links[0].contains("search-8be46b629f5f14a8.js"));
+                assert!(!links[0].contains("noscript-cffde32267a19fd6.css"));
+            } else {
+                assert!(links.is_empty());
+            }
+
+            // other pages of the release get the links stored for the root page
+            let links = preloads(web.get("/foo/0.1.0/foo/struct.Foo.html").send()?);
+            assert_eq!(links.len(), usize::from(enabled));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn assets_not_found() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .build_result_failed()
+                .create()?;
+            let web = env.frontend();
+
+            for url in [
+                "/crate/foo/0.1.0/assets.json",
+                "/crate/foo/0.2.0/assets.json",
+            ] {
+                let response = web.get(url).send()?;
+                assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", url);
+                assert_eq!(response.headers()["content-type"], "application/json");
+            }
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,330 @@
+//! URLs of crates from scoped registries, see [`crate::utils::scoped_registry`].
+//!
+//! `/registry/:registry/crate/:name/...` and `/registry/:registry/:name/...` are served by the
+//! handlers of the unscoped URLs, with the scoped name `<name>@<registry>` in place of the crate
+//! name, when `:registry` is one of the configured scoped registries. Redirects to scoped names,
+//! and the links to them in the pages of scoped URLs, are turned back into scoped URLs.
+//!
+//! This wraps the whole app including the iron fallback, the requests are rewritten before
+//! they are routed. The pages of scoped URLs aren't compressed, their links are rewritten in
+//! the response body.
+
+use crate::{
+    utils::scoped_registry::{scoped_name, split_scoped_name},
+    Config,
+};
+use axum::{
+    body::{boxed, Body, Full},
+    http::{
+        header::{ACCEPT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, LOCATION},
+        HeaderValue, Request as AxumHttpRequest, Uri,
+    },
+    response::Response as AxumResponse,
+    Router as AxumRouter,
+};
+use once_cell::sync::Lazy;
+use regex::bytes::{Captures, Regex};
+use std::{convert::Infallible, sync::Arc};
+use tower::{service_fn, ServiceExt};
+use tracing::warn;
+
+/// Wraps `app` to serve the scoped URLs of the scoped registries in `config`, see the module
+/// docs.
+pub(crate) fn serve_scoped_urls(app: AxumRouter, config: &Config) -> AxumRouter {
+    let registries: Arc<Vec<String>> = Arc::new(
+        config
+            .scoped_registries
+            .iter()
+            .map(|registry| registry.name.clone())
+            .collect(),
+    );
+    AxumRouter::new().fallback_service(service_fn(move |request| {
+        let app = app.clone();
+        let registries = registries.clone();
+        async move { Ok::<_, Infallible>(serve(app, &registries, request).await) }
+    }))
+}
+
+async fn serve(
+    app: AxumRouter,
+    registries: &[String],
+    mut request: AxumHttpRequest<Body>,
+) -> AxumResponse {
+    let scoped = match internal_path(request.uri().path(), registries) {
+        Some(path) => {
+            let path_and_query = match request.uri().query() {
+                Some(query) => format!("{}?{}", path, query),
+                None => path,
+            };
+            if let Ok(uri) = path_and_query.parse() {
+                *request.uri_mut() = uri;
+            }
+            // the links in the body are rewritten below
+            request.headers_mut().remove(ACCEPT_ENCODING);
+            true
+        }
+        None => false,
+    };
+
+    let response = match app.oneshot(request).await {
+        Ok(response) => response,
+        Err(err) => match err {},
+    };
+    let response = scope_location(response, registries);
+    if scoped {
+        scope_links(response, registries).await
+    } else {
+        response
+    }
+}
+
+fn scope_location(mut response: AxumResponse, registries: &[String]) -> AxumResponse {
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .and_then(|location| location.parse::<Uri>().ok())
+        .and_then(|location| {
+            let path = scoped_path(location.path(), registries)?;
+            let path_and_query = match location.query() {
+                Some(query) => format!("{}?{}", path, query),
+                None => path,
+            };
+            let mut parts = location.into_parts();
+            parts.path_and_query = Some(path_and_query.parse().ok()?);
+            Uri::from_parts(parts).ok()
+        })
+        .and_then(|location| HeaderValue::from_str(&location.to_string()).ok());
+
+    if let Some(location) = location {
+        response.headers_mut().insert(LOCATION, location);
+    }
+    response
+}
+
+/// Rewrites the links to scoped names in HTML, JSON and XML bodies to scoped URLs.
+async fn scope_links(response: AxumResponse, registries: &[String]) -> AxumResponse {
+    static SCOPED_LINK: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"/(crate/)?([\w-]+)@([\w-]+)\b").unwrap());
+
+    let is_text = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .map_or(false, |content_type| {
+            content_type.starts_with("text/")
+                || content_type.contains("json")
+                || content_type.contains("xml")
+        });
+    if !is_text {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(err) => {
+            warn!(?err, "failed to read the body of a scoped page");
+            return AxumResponse::from_parts(parts, boxed(Body::empty()));
+        }
+    };
+    let body = SCOPED_LINK.replace_all(&body, |captures: &Captures| {
+        if !registries
+            .iter()
+            .any(|registry| registry.as_bytes() == &captures[3])
+        {
+            return captures[0].to_vec();
+        }
+        let prefix = captures.get(1).map_or(&b""[..], |prefix| prefix.as_bytes());
+        [&b"/registry/"[..], &captures[3], b"/", prefix, &captures[2]].concat()
+    });
+
+    parts.headers.remove(CONTENT_LENGTH);
+    AxumResponse::from_parts(parts, boxed(Full::from(body.into_owned())))
+}
+
+/// The unscoped path serving the scoped path `path`, `None` when it isn't a scoped path of one
+/// of `registries`.
+fn internal_path(path: &str, registries: &[String]) -> Option<String> {
+    let (registry, rest) = path.strip_prefix("/registry/")?.split_once('/')?;
+    if !registries.iter().any(|configured| configured == registry) {
+        return None;
+    }
+
+    let (prefix, rest) = match rest.strip_prefix("crate/") {
+        Some(rest) => ("/crate/", rest),
+        None => ("/", rest),
+    };
+    let (name, rest) = match rest.split_once('/') {
+        Some((name, rest)) => (name, Some(rest)),
+        None => (rest, None),
+    };
+    if name.is_empty() || split_scoped_name(name).1.is_some() {
+        return None;
+    }
+
+    let name = scoped_name(registry, name);
+    Some(match rest {
+        Some(rest) => format!("{}{}/{}", prefix, name, rest),
+        None => format!("{}{}", prefix, name),
+    })
+}
+
+/// The scoped path of `path` when it's the path of a crate from a scoped registry.
+fn scoped_path(path: &str, registries: &[String]) -> Option<String> {
+    let (prefix, rest) = match path.strip_prefix("/crate/") {
+        Some(rest) => ("crate/", rest),
+        None => ("", path.strip_prefix('/')?),
+    };
+    let (name, rest) = match rest.split_once('/') {
+        Some((name, rest)) => (name, Some(rest)),
+        None => (rest, None),
+    };
+    let (name, registry) = match split_scoped_name(name) {
+        (name, Some(registry)) if registries.iter().any(|configured| configured == registry) => {
+            (name, registry)
+        }
+        _ => return None,
+    };
+
+    Some(match rest {
+        Some(rest) => format!("/registry/{}/{}{}/{}", registry, prefix, name, rest),
+        None => format!("/registry/{}/{}{}", registry, prefix, name),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::*;
+    use axum::http::StatusCode;
+    use test_case::test_case;
+
+    #[test_case("/registry/internal/crate/foo", Some("/crate/foo@internal"))]
+    #[test_case(
+        "/registry/internal/crate/foo/1.0.0/builds",
+        Some("/crate/foo@internal/1.0.0/builds")
+    )]
+    #[test_case(
+        "/registry/internal/foo/latest/foo/",
+        Some("/foo@internal/latest/foo/")
+    )]
+    #[test_case("/registry/internal/foo", Some("/foo@internal"))]
+    #[test_case("/registry/internal/", None)]
+    #[test_case("/registry/internal/foo@other/1.0.0", None)]
+    #[test_case("/registry/a.b/foo", None)]
+    #[test_case("/registry/other/foo", None)]
+    #[test_case("/registry/latest/registry/", None)]
+    #[test_case("/crate/foo/1.0.0", None)]
+    fn internal_paths(path: &str, expected: Option<&str>) {
+        let registries = ["internal".to_string()];
+        assert_eq!(internal_path(path, &registries).as_deref(), expected);
+        // scoped paths are the inverse
+        if let Some(internal) = expected {
+            assert_eq!(scoped_path(internal, &registries).as_deref(), Some(path));
+        }
+    }
+
+    #[test]
+    fn unscoped_paths_stay() {
+        let registries = ["internal".to_string()];
+        assert_eq!(scoped_path("/crate/foo/1.0.0", &registries), None);
+        assert_eq!(scoped_path("/foo/1.0.0/foo/", &registries), None);
+        assert_eq!(scoped_path("/crate/foo@other/1.0.0", &registries), None);
+        assert_eq!(scoped_path("/", &registries), None);
+    }
+
+    #[test]
+    fn crate_named_registry() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("registry")
+                .version("0.1.0")
+                .create()?;
+            let web = env.frontend();
+
+            assert_success("/registry/latest/registry/", web)?;
+            assert_success("/crate/registry/latest", web)?;
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn scoped_and_unscoped_resolution() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.scoped_registries =
+                    vec!["internal=https://git.example.com/index".parse().unwrap()];
+            });
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .rustdoc_file_with(
+                    "foo/index.html",
+                    b"<html><head></head><body>default registry</body></html>",
+                )
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .registry("internal")
+                .version("1.0.0")
+                .rustdoc_file_with(
+                    "foo/index.html",
+                    b"<html><head></head><body>internal registry</body></html>",
+                )
+                .create()?;
+            let web = env.frontend();
+
+            assert!(web
+                .get("/foo/0.1.0/foo/")
+                .send()?
+                .text()?
+                .contains("default registry"));
+            assert!(web
+                .get("/registry/internal/foo/1.0.0/foo/")
+                .send()?
+                .text()?
+                .contains("internal registry"));
+            assert_success("/registry/internal/crate/foo/1.0.0", web)?;
+
+            // links in the pages stay within the registry too
+            let page = web
+                .get("/registry/internal/crate/foo/1.0.0")
+                .send()?
+                .text()?;
+            assert!(page.contains("\"/registry/internal/crate/foo/1.0.0"));
+            assert!(!page.contains("/crate/foo@internal"));
+
+            // redirects stay within the registry
+            assert_redirect(
+                "/registry/internal/crate/foo/1",
+                "/registry/internal/crate/foo/1.0.0",
+                web,
+            )?;
+            assert_redirect(
+                "/registry/internal/foo/1.0.0",
+                "/registry/internal/foo/1.0.0/foo/",
+                web,
+            )?;
+            assert_redirect("/foo/0.1.0", "/foo/0.1.0/foo/", web)?;
+
+            // the releases of one registry aren't visible in the other
+            for url in [
+                "/foo/1.0.0/foo/",
+                "/crate/foo/1.0.0",
+                "/registry/internal/foo/0.1.0/foo/",
+                "/registry/internal/crate/foo/0.1.0",
+                "/registry/other/crate/foo/0.1.0",
+            ] {
+                assert_eq!(
+                    web.get(url).send()?.status(),
+                    StatusCode::NOT_FOUND,
+                    "{}",
+                    url
+                );
+            }
+
+            Ok(())
+        })
+    }
+}
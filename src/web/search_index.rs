@@ -0,0 +1,770 @@
+//! Serves the search index rustdoc generated for a release as JSON, so external tools can use it
+//! without evaluating the `search-index.js`, and searches the items in it.
+
+use crate::{
+    db::Pool,
+    storage::Blob,
+    utils::{parse_rustc_version, report_error, spawn_blocking},
+    web::{
+        cache::CachePolicy,
+        error::{AxumNope, AxumResult, JsonAxumResult},
+        match_version_axum, MatchSemver, RustdocRelease,
+    },
+    Config, Storage,
+};
+use anyhow::{Context as _, Result};
+use axum::{
+    body::{Bytes, StreamBody},
+    extract::{Extension, Path, Query},
+    http::header::CONTENT_TYPE,
+    response::IntoResponse,
+    Json,
+};
+use futures_util::stream;
+use postgres::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::VecDeque,
+    io::{self, BufReader, Read},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc;
+use url::form_urlencoded;
+
+/// rustdoc stores the search index as JSON in the string literal passed to this call
+const JSON_PARSE_CALL: &[u8] = b"JSON.parse('";
+/// Size of the chunks the unescaped index is streamed in
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Unescapes the JSON search index in the single-quoted string literal of a `search-index.js`
+/// while it's read, so the index can be streamed to the client without holding it in memory.
+///
+/// rustdoc only escapes backslashes and single quotes, and separates crates with line
+/// continuations.
+struct UnescapedIndex<R> {
+    bytes: io::Bytes<BufReader<R>>,
+    done: bool,
+}
+
+impl<R: Read> UnescapedIndex<R> {
+    /// Skips to the start of the literal.
+    ///
+    /// Indices generated before rustdoc started using `JSON.parse` assign plain JS objects
+    /// instead, those return `None`.
+    fn new(reader: R) -> io::Result<Option<Self>> {
+        let mut bytes = BufReader::new(reader).bytes();
+        let mut matched = 0;
+        while matched < JSON_PARSE_CALL.len() {
+            let byte = match bytes.next().transpose()? {
+                Some(byte) => byte,
+                None => return Ok(None),
+            };
+            matched = if byte == JSON_PARSE_CALL[matched] {
+                matched + 1
+            } else if byte == JSON_PARSE_CALL[0] {
+                1
+            } else {
+                0
+            };
+        }
+        Ok(Some(Self { bytes, done: false }))
+    }
+
+    fn next_byte(&mut self) -> io::Result<u8> {
+        self.bytes
+            .next()
+            .transpose()?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unterminated search index"))
+    }
+}
+
+impl<R: Read> Read for UnescapedIndex<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut written = 0;
+        while written < buf.len() && !self.done {
+            let byte = match self.next_byte()? {
+                b'\'' => {
+                    self.done = true;
+                    break;
+                }
+                b'\\' => match self.next_byte()? {
+                    b'\n' => continue,
+                    escaped => escaped,
+                },
+                byte => byte,
+            };
+            buf[written] = byte;
+            written += 1;
+        }
+        Ok(written)
+    }
+}
+
+/// The search index of a release, still compressed like it's stored.
+struct SearchIndex {
+    /// The name of the library of the release, the key of its items in the index
+    target_name: String,
+    blob: Blob,
+    max_size: usize,
+}
+
+impl SearchIndex {
+    /// The JSON of the index, decompressed and unescaped while it's read.
+    fn json(&self) -> Result<UnescapedIndex<impl Read + '_>> {
+        let reader = self.blob.decompressing_reader(self.max_size)?;
+        Ok(UnescapedIndex::new(reader)?.ok_or(AxumNope::ResourceNotFound)?)
+    }
+}
+
+/// Loads the search index of the default target of a release, failing with `ResourceNotFound`
+/// when the release has no documentation or no index in a supported format.
+fn load_search_index(
+    conn: &mut Client,
+    storage: &Storage,
+    config: &Config,
+    name: &str,
+    version: &str,
+) -> Result<SearchIndex> {
+    let release = RustdocRelease::load(conn, name, version)?;
+
+    // the index is named after the `--resource-suffix` passed to rustdoc
+    let path = match parse_rustc_version(&release.doc_rustc_version) {
+        Ok(suffix) => format!("search-index-{}.js", suffix),
+        Err(_) => return Err(AxumNope::ResourceNotFound.into()),
+    };
+    let index = SearchIndex {
+        blob: release.fetch_compressed(storage, &path)?,
+        target_name: release.target_name,
+        max_size: config.max_file_size,
+    };
+    // fail before the response started
+    index.json()?;
+    Ok(index)
+}
+
+/// Serves the search index of the default target of a release.
+///
+/// The stored `search-index.js` is decompressed and unescaped while it's streamed to the client.
+/// Errors after the response started, like an unterminated index, abort it.
+pub(crate) async fn search_index_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> AxumResult<impl IntoResponse> {
+    let version = match match_version_axum(&pool, &name, Some(&req_version))
+        .await?
+        .assume_exact()?
+    {
+        MatchSemver::Exact((version, _)) | MatchSemver::Latest((version, _)) => version,
+        MatchSemver::Semver((version, _)) => {
+            return Ok(super::axum_cached_redirect(
+                &format!("/crate/{}/{}/search-index.json", name, version),
+                CachePolicy::ForeverInCdn,
+            )?
+            .into_response());
+        }
+    };
+
+    let index = spawn_blocking({
+        let (name, version) = (name.clone(), version.clone());
+        move || {
+            let mut conn = pool.get()?;
+            load_search_index(&mut conn, &storage, &config, &name, &version)
+        }
+    })
+    .await?;
+
+    // only a few chunks are prepared ahead of what the client received
+    let (sender, receiver) = mpsc::channel(2);
+    tokio::task::spawn_blocking(move || {
+        let result = index.json().and_then(|mut json| loop {
+            let mut chunk = vec![0; CHUNK_SIZE];
+            let read = json.read(&mut chunk)?;
+            if read == 0 {
+                return Ok(());
+            }
+            chunk.truncate(read);
+            if sender.blocking_send(Ok(Bytes::from(chunk))).is_err() {
+                // the client went away
+                return Ok(());
+            }
+        });
+        if let Err(err) = result {
+            report_error(&err.context(format!(
+                "failed to stream the search index of {} {}",
+                name, version
+            )));
+            let _ = sender.blocking_send(Err(io::Error::new(
+                io::ErrorKind::Other,
+                "failed to read the search index",
+            )));
+        }
+    });
+    let chunks = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+
+    Ok((
+        Extension(CachePolicy::ForeverInCdn),
+        [(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())],
+        StreamBody::new(chunks),
+    )
+        .into_response())
+}
+
+/// Names of rustdoc's item kinds, indexed by the numbers it stores in the search index
+const ITEM_KINDS: &[&str] = &[
+    "mod",
+    "externcrate",
+    "import",
+    "struct",
+    "enum",
+    "fn",
+    "type",
+    "static",
+    "trait",
+    "impl",
+    "tymethod",
+    "method",
+    "structfield",
+    "variant",
+    "macro",
+    "primitive",
+    "associatedtype",
+    "constant",
+    "associatedconstant",
+    "union",
+    "foreigntype",
+    "keyword",
+    "opaque",
+    "attr",
+    "derive",
+    "traitalias",
+];
+/// Most items returned by a single search
+pub(crate) const SEARCH_RESULTS: usize = 50;
+
+/// An item of the search index
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct IndexItem {
+    /// The kind of the item as rustdoc names its pages, like `struct` or `method`
+    kind: String,
+    /// The full path of the item, starting with the name of the crate
+    path: String,
+    #[serde(skip)]
+    name: String,
+}
+
+/// Lists the items of a single crate of the search index.
+///
+/// rustdoc stores the items column-wise: their names in `n`, their kinds in `t` (an array of
+/// numbers, or a string of letters since 1.70) and their modules in `q`. Modules are only stored
+/// when they change, either as empty strings for unchanged ones or as `[index, path]` pairs.
+/// Methods and fields refer to their parent in `p` through `i`. Parts in formats other than
+/// these are skipped.
+fn index_items(crate_index: &Value, target_name: &str) -> Vec<IndexItem> {
+    let names = match crate_index["n"].as_array() {
+        Some(names) => names,
+        None => return Vec::new(),
+    };
+    let kinds: Vec<Option<usize>> = match &crate_index["t"] {
+        Value::Array(kinds) => kinds
+            .iter()
+            .map(|kind| kind.as_u64().map(|kind| kind as usize))
+            .collect(),
+        Value::String(kinds) => kinds
+            .bytes()
+            .map(|kind| kind.checked_sub(b'A').map(usize::from))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let mut modules = vec![target_name.to_owned(); names.len()];
+    match crate_index["q"].as_array() {
+        Some(paths) if paths.iter().all(Value::is_string) => {
+            let mut module = target_name;
+            for (index, path) in paths.iter().enumerate().take(names.len()) {
+                let path = path.as_str().unwrap_or_default();
+                if !path.is_empty() {
+                    module = path;
+                }
+                modules[index] = module.to_owned();
+            }
+        }
+        Some(paths) => {
+            for pair in paths {
+                if let (Some(start), Some(path)) = (pair[0].as_u64(), pair[1].as_str()) {
+                    for module in modules.iter_mut().skip(start as usize) {
+                        *module = path.to_owned();
+                    }
+                }
+            }
+        }
+        None => {}
+    }
+
+    let parents: Vec<&str> = crate_index["p"]
+        .as_array()
+        .map(|parents| {
+            parents
+                .iter()
+                .map(|parent| parent[1].as_str().unwrap_or_default())
+                .collect()
+        })
+        .unwrap_or_default();
+    let parent_of = |index: usize| {
+        // parents are counted from 1, 0 means no parent
+        crate_index["i"][index]
+            .as_u64()
+            .and_then(|parent| parent.checked_sub(1))
+            .and_then(|parent| parents.get(parent as usize).copied())
+            .filter(|parent| !parent.is_empty())
+    };
+
+    names
+        .iter()
+        .enumerate()
+        .filter_map(|(index, name)| {
+            let name = name.as_str().filter(|name| !name.is_empty())?;
+            let kind = kinds
+                .get(index)
+                .copied()
+                .flatten()
+                .and_then(|kind| ITEM_KINDS.get(kind))
+                .copied()
+                .unwrap_or("unknown");
+            let path = match parent_of(index) {
+                Some(parent) => format!("{}::{}::{}", modules[index], parent, name),
+                None => format!("{}::{}", modules[index], name),
+            };
+            Some(IndexItem {
+                kind: kind.to_owned(),
+                path,
+                name: name.to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// Finds the items whose name contains `query`, ignoring case. Exact matches come first, then
+/// names starting with the query, then the other matches, each ordered by the length of their
+/// path so items closer to the crate root rank higher.
+fn search_items(items: &[IndexItem], query: &str) -> Vec<IndexItem> {
+    let query = query.to_lowercase();
+    let mut found: Vec<(u8, &IndexItem)> = items
+        .iter()
+        .filter_map(|item| {
+            let name = item.name.to_lowercase();
+            let rank = if name == query {
+                0
+            } else if name.starts_with(&query) {
+                1
+            } else if name.contains(&query) {
+                2
+            } else {
+                return None;
+            };
+            Some((rank, item))
+        })
+        .collect();
+    found.sort_by(|(rank, item), (other_rank, other)| {
+        (rank, item.path.len(), &item.path).cmp(&(other_rank, other.path.len(), &other.path))
+    });
+    found.dedup_by(|(_, item), (_, other)| item == other);
+    found
+        .into_iter()
+        .take(SEARCH_RESULTS)
+        .map(|(_, item)| item.clone())
+        .collect()
+}
+
+/// How many releases the items of their search index are kept for
+const CACHED_INDICES: usize = 16;
+/// How long the items of an index are used before it's loaded again, so rebuilt releases are
+/// searched in their new index.
+const CACHED_INDEX_MAX_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// The items of the search indices searched last, so searching a release again doesn't load and
+/// parse its index again.
+#[derive(Debug, Default)]
+pub(crate) struct SearchIndexCache {
+    recent: Mutex<VecDeque<(String, String, Instant, Arc<Vec<IndexItem>>)>>,
+}
+
+impl SearchIndexCache {
+    /// The items of the search index of a release, loaded with [`load_search_index`] when they
+    /// aren't cached.
+    fn items(
+        &self,
+        conn: &mut Client,
+        storage: &Storage,
+        config: &Config,
+        name: &str,
+        version: &str,
+    ) -> Result<Arc<Vec<IndexItem>>> {
+        {
+            let mut recent = self.recent.lock().unwrap();
+            recent.retain(|(_, _, loaded_at, _)| loaded_at.elapsed() < CACHED_INDEX_MAX_AGE);
+            if let Some(position) = recent
+                .iter()
+                .position(|(cached_name, cached_version, _, _)| {
+                    cached_name == name && cached_version == version
+                })
+            {
+                let entry = recent.remove(position).unwrap();
+                let items = entry.3.clone();
+                recent.push_front(entry);
+                return Ok(items);
+            }
+        }
+
+        let index = load_search_index(conn, storage, config, name, version)?;
+        let json: Value = serde_json::from_reader(index.json()?)
+            .with_context(|| format!("invalid search index of {} {}", name, version))?;
+        let crate_index = json
+            .get(&index.target_name)
+            .ok_or(AxumNope::ResourceNotFound)?;
+        let items = Arc::new(index_items(crate_index, &index.target_name));
+
+        let mut recent = self.recent.lock().unwrap();
+        recent.push_front((
+            name.to_owned(),
+            version.to_owned(),
+            Instant::now(),
+            items.clone(),
+        ));
+        recent.truncate(CACHED_INDICES);
+        Ok(items)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SearchParams {
+    q: Option<String>,
+}
+
+/// Searches the items of the default target of a release by name, in its search index.
+pub(crate) async fn search_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Query(params): Query<SearchParams>,
+    Extension(pool): Extension<Pool>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(config): Extension<Arc<Config>>,
+    Extension(cache): Extension<Arc<SearchIndexCache>>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let query = params.q.unwrap_or_default().trim().to_owned();
+    if query.is_empty() {
+        return Err(AxumNope::BadRequest("missing search query `q`".into()).into());
+    }
+
+    let version = match match_version_axum(&pool, &name, Some(&req_version))
+        .await?
+        .assume_exact()?
+    {
+        MatchSemver::Exact((version, _)) | MatchSemver::Latest((version, _)) => version,
+        MatchSemver::Semver((version, _)) => {
+            let query = form_urlencoded::Serializer::new(String::new())
+                .append_pair("q", &query)
+                .finish();
+            return Ok(super::axum_cached_redirect(
+                &format!("/crate/{}/{}/search?{}", name, version, query),
+                CachePolicy::ForeverInCdn,
+            )?
+            .into_response());
+        }
+    };
+
+    let found = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let items = cache.items(&mut conn, &storage, &config, &name, &version)?;
+        Ok(search_items(&items, &query))
+    })
+    .await?;
+
+    Ok((Extension(CachePolicy::ForeverInCdn), Json(found)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{UnescapedIndex, SEARCH_RESULTS};
+    use crate::{
+        test::{assert_cache_control, assert_redirect_cached, wrapper},
+        web::cache::CachePolicy,
+    };
+    use reqwest::StatusCode;
+    use serde_json::json;
+    use std::io::Read;
+    use test_case::test_case;
+
+    const SEARCH_INDEX: &str = r#"var searchIndex = JSON.parse('{\
+"foo":{"doc":"It\'s \\"quoted\\"","t":[0]},\
+"bar":{"doc":"","t":[]}\
+}');
+if (typeof window !== 'undefined' && window.initSearch) {window.initSearch(searchIndex)};
+if (typeof exports !== 'undefined') {exports.searchIndex = searchIndex};
+"#;
+    /// The index file of the fake releases, named after their `doc_rustc_version`
+    const SEARCH_INDEX_PATH: &str = "search-index-19700101-2.0.0-nightly-000000000.js";
+
+    fn unescape(content: &str) -> Option<std::io::Result<String>> {
+        let mut index = UnescapedIndex::new(content.as_bytes()).unwrap()?;
+        let mut json = String::new();
+        Some(index.read_to_string(&mut json).map(|_| json))
+    }
+
+    #[test]
+    fn unescape_search_index() {
+        let json: serde_json::Value =
+            serde_json::from_str(&unescape(SEARCH_INDEX).unwrap().unwrap()).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "foo": {"doc": "It's \"quoted\"", "t": [0]},
+                "bar": {"doc": "", "t": []},
+            })
+        );
+    }
+
+    #[test]
+    fn unescape_in_small_reads() {
+        let content = r#"JSON.JSON.parse('{"doc":"\'\\\\\
+"}')"#;
+        let mut index = UnescapedIndex::new(content.as_bytes()).unwrap().unwrap();
+        let mut json = Vec::new();
+        let mut chunk = [0; 2];
+        loop {
+            let read = index.read(&mut chunk).unwrap();
+            if read == 0 {
+                break;
+            }
+            json.extend_from_slice(&chunk[..read]);
+        }
+
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["doc"], "'\\");
+    }
+
+    #[test]
+    fn unsupported_search_index() {
+        assert!(
+            unescape("var searchIndex = {};\nsearchIndex[\"foo\"] = {\"doc\":\"\"};").is_none()
+        );
+
+        let err = unescape("var searchIndex = JSON.parse('{\"doc\":\"unterminated")
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test_case(true; "archive storage")]
+    #[test_case(false; "file storage")]
+    fn search_index_json(archive_storage: bool) {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .archive_storage(archive_storage)
+                .rustdoc_file_with(SEARCH_INDEX_PATH, SEARCH_INDEX.as_bytes())
+                .create()?;
+            let web = env.frontend();
+
+            for url in [
+                "/crate/foo/0.1.0/search-index.json",
+                "/crate/foo/latest/search-index.json",
+            ] {
+                let response = web.get(url).send()?;
+                assert_eq!(response.status(), StatusCode::OK);
+                assert_cache_control(&response, CachePolicy::ForeverInCdn, &env.config());
+                assert_eq!(response.headers()["content-type"], "application/json");
+                let json: serde_json::Value = response.json()?;
+                assert_eq!(json["foo"]["doc"], "It's \"quoted\"");
+            }
+
+            assert_redirect_cached(
+                "/crate/foo/0.1/search-index.json",
+                "/crate/foo/0.1.0/search-index.json",
+                CachePolicy::ForeverInCdn,
+                web,
+                &env.config(),
+            )?;
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn search_index_json_not_found() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            env.fake_release()
+                .name("bar")
+                .version("0.1.0")
+                .build_result_failed()
+                .create()?;
+            let web = env.frontend();
+
+            for url in [
+                "/crate/foo/0.1.0/search-index.json",
+                "/crate/bar/0.1.0/search-index.json",
+                "/crate/foo/0.2.0/search-index.json",
+                "/crate/baz/0.1.0/search-index.json",
+            ] {
+                assert_eq!(
+                    web.get(url).send()?.status(),
+                    StatusCode::NOT_FOUND,
+                    "{}",
+                    url
+                );
+            }
+            Ok(())
+        });
+    }
+
+    /// Escapes `index` like rustdoc does for its `search-index.js`
+    fn search_index_js(index: serde_json::Value) -> String {
+        format!(
+            "var searchIndex = JSON.parse('{}');\n",
+            index.to_string().replace('\\', "\\\\").replace('\'', "\\'")
+        )
+    }
+
+    #[test_case(
+        json!([3, 5, 5, 3, 0, 5, 11]),
+        json!(["foo", "", "", "", "", "foo::parser", ""]);
+        "before 1.70"
+    )]
+    #[test_case(
+        json!("DFFDAFL"),
+        json!([[0, "foo"], [5, "foo::parser"]]);
+        "since 1.70"
+    )]
+    fn search_in_crate(kinds: serde_json::Value, modules: serde_json::Value) {
+        wrapper(|env| {
+            let index = search_index_js(json!({
+                "foo": {
+                    "doc": "",
+                    "n": ["Parser", "parse", "parse_all", "Config", "parser", "sparse", "parse_str"],
+                    "t": kinds,
+                    "q": modules,
+                    "i": [0, 0, 0, 0, 0, 0, 1],
+                    "p": [[3, "Config"]],
+                },
+                "dependency": {"doc": "", "n": ["parse"], "t": [5], "q": ["dependency"]},
+            }));
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .rustdoc_file_with(SEARCH_INDEX_PATH, index.as_bytes())
+                .create()?;
+            let web = env.frontend();
+
+            let response = web.get("/crate/foo/0.1.0/search?q=parse").send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_cache_control(&response, CachePolicy::ForeverInCdn, &env.config());
+            assert_eq!(
+                response.json::<serde_json::Value>()?,
+                json!([
+                    {"kind": "fn", "path": "foo::parse"},
+                    {"kind": "struct", "path": "foo::Parser"},
+                    {"kind": "mod", "path": "foo::parser"},
+                    {"kind": "fn", "path": "foo::parse_all"},
+                    {"kind": "method", "path": "foo::Config::parse_str"},
+                    {"kind": "fn", "path": "foo::parser::sparse"},
+                ])
+            );
+
+            let found: serde_json::Value = web
+                .get("/crate/foo/latest/search?q=CONFIG")
+                .send()?
+                .json()?;
+            assert_eq!(found, json!([{"kind": "struct", "path": "foo::Config"}]));
+
+            let found: serde_json::Value = web
+                .get("/crate/foo/0.1.0/search?q=nothing")
+                .send()?
+                .json()?;
+            assert_eq!(found, json!([]));
+
+            assert_redirect_cached(
+                "/crate/foo/0.1/search?q=parse+all",
+                "/crate/foo/0.1.0/search?q=parse+all",
+                CachePolicy::ForeverInCdn,
+                web,
+                &env.config(),
+            )?;
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn search_reuses_the_loaded_index() {
+        wrapper(|env| {
+            let index = search_index_js(json!({
+                "foo": {"doc": "", "n": ["Parser"], "t": [3], "q": ["foo"]},
+            }));
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .rustdoc_file_with(SEARCH_INDEX_PATH, index.as_bytes())
+                .create()?;
+            let web = env.frontend();
+
+            let expected = json!([{"kind": "struct", "path": "foo::Parser"}]);
+            let found: serde_json::Value =
+                web.get("/crate/foo/0.1.0/search?q=parser").send()?.json()?;
+            assert_eq!(found, expected);
+
+            // the index isn't loaded again for the next query
+            env.storage().delete_prefix("rustdoc/foo/")?;
+            let found: serde_json::Value =
+                web.get("/crate/foo/0.1.0/search?q=pars").send()?.json()?;
+            assert_eq!(found, expected);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn search_items_are_limited() {
+        let items: Vec<_> = (0..SEARCH_RESULTS * 2)
+            .map(|index| super::IndexItem {
+                kind: "fn".into(),
+                path: format!("foo::item_{}", index),
+                name: format!("item_{}", index),
+            })
+            .collect();
+        assert_eq!(super::search_items(&items, "item").len(), SEARCH_RESULTS);
+    }
+
+    #[test]
+    fn search_items_not_found() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let web = env.frontend();
+
+            assert_eq!(
+                web.get("/crate/foo/0.1.0/search").send()?.status(),
+                StatusCode::BAD_REQUEST
+            );
+            assert_eq!(
+                web.get("/crate/foo/0.1.0/search?q=").send()?.status(),
+                StatusCode::BAD_REQUEST
+            );
+            for url in [
+                // no search index
+                "/crate/foo/0.1.0/search?q=foo",
+                "/crate/foo/0.2.0/search?q=foo",
+                "/crate/bar/0.1.0/search?q=foo",
+            ] {
+                assert_eq!(
+                    web.get(url).send()?.status(),
+                    StatusCode::NOT_FOUND,
+                    "{}",
+                    url
+                );
+            }
+            Ok(())
+        });
+    }
+}
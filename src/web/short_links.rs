@@ -0,0 +1,350 @@
+//! Short links at `/-/s/:token` redirecting to docs.rs urls, for places with length limits.
+//!
+//! Links are created through an endpoint authenticated with one of the
+//! `DOCSRS_SHORT_LINK_TOKENS`, and can optionally expire.
+
+use crate::{
+    db::Pool,
+    utils::spawn_blocking,
+    web::{
+        auth::authenticate_named_token,
+        cache::CachePolicy,
+        error::{AxumNope, AxumResult, JsonAxumResult},
+    },
+    Config,
+};
+use anyhow::{bail, Context as _, Result};
+use axum::{
+    extract::{Extension, Path},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+
+const TOKEN_ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+/// Length of generated tokens, 62^10 possible tokens make collisions very unlikely
+const TOKEN_LEN: usize = 10;
+/// How often a new token is generated when the previous one is already taken
+const TOKEN_ATTEMPTS: usize = 3;
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct CreateShortLinkParams {
+    url: String,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct ShortLink {
+    token: String,
+    url: String,
+    target: String,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// Generates a random token of [`TOKEN_LEN`] alphanumeric characters.
+fn generate_token() -> Result<String> {
+    let mut token = String::with_capacity(TOKEN_LEN);
+    let mut random = [0u8; 32];
+    while token.len() < TOKEN_LEN {
+        getrandom::getrandom(&mut random).context("failed to generate a short link token")?;
+        let missing = TOKEN_LEN - token.len();
+        token.extend(
+            random
+                .iter()
+                // skip bytes which would make some characters more likely than others
+                .filter(|&&byte| (byte as usize) < TOKEN_ALPHABET.len() * 4)
+                .map(|&byte| TOKEN_ALPHABET[byte as usize % TOKEN_ALPHABET.len()] as char)
+                .take(missing),
+        );
+    }
+    Ok(token)
+}
+
+/// Returns the path and query of `url` on docs.rs, which is stored as target of a short link.
+///
+/// Absolute urls have to be on the public host, short links can't redirect to other sites.
+fn link_target(url: &str, public_base_url: &str) -> Result<String, AxumNope> {
+    let invalid = || {
+        AxumNope::BadRequest(format!(
+            "invalid url `{}`, short links can only point to {}",
+            url, public_base_url
+        ))
+    };
+
+    let target = match url.strip_prefix(public_base_url) {
+        Some(path) if path.is_empty() => "/",
+        Some(path) if path.starts_with('/') => path,
+        Some(_) => return Err(invalid()),
+        None if url.starts_with('/') => url,
+        None => return Err(invalid()),
+    };
+
+    // protocol relative urls would leave docs.rs, and browsers remove tabs and newlines from
+    // urls, so `/\t/example.com` would be one too
+    if target.starts_with("//")
+        || target.contains('\\')
+        || target
+            .chars()
+            .any(|c| c.is_ascii_control() || c.is_ascii_whitespace())
+    {
+        return Err(invalid());
+    }
+    Ok(target.to_owned())
+}
+
+fn insert_short_link(
+    conn: &mut postgres::Client,
+    target: &str,
+    expires_at: Option<DateTime<Utc>>,
+    created_by: &str,
+) -> Result<String> {
+    for _ in 0..TOKEN_ATTEMPTS {
+        let token = generate_token()?;
+        let inserted = conn.execute(
+            "INSERT INTO short_links (token, target, created_by, expires_at)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (token) DO NOTHING",
+            &[&token, &target, &created_by, &expires_at],
+        )?;
+        if inserted == 1 {
+            return Ok(token);
+        }
+    }
+    bail!(
+        "failed to generate an unused short link token in {} attempts",
+        TOKEN_ATTEMPTS
+    );
+}
+
+/// Creates a short link, authenticated with one of the `DOCSRS_SHORT_LINK_TOKENS` given as
+/// bearer token.
+pub(crate) async fn create_short_link_handler(
+    headers: HeaderMap,
+    Extension(config): Extension<Arc<Config>>,
+    Extension(pool): Extension<Pool>,
+    Json(params): Json<CreateShortLinkParams>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let created_by = authenticate_named_token(&headers, &config.short_link_tokens)?;
+    let target = link_target(&params.url, &config.public_base_url)?;
+    if let Some(expires_at) = params.expires_at {
+        if expires_at <= Utc::now() {
+            return Err(AxumNope::BadRequest("`expires_at` has to be in the future".into()).into());
+        }
+    }
+
+    let token = spawn_blocking({
+        let target = target.clone();
+        move || {
+            let mut conn = pool.get()?;
+            let token = insert_short_link(&mut conn, &target, params.expires_at, &created_by)?;
+            info!(%token, %target, %created_by, "created short link");
+            Ok(token)
+        }
+    })
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Extension(CachePolicy::NoCaching),
+        Json(ShortLink {
+            url: format!("{}/-/s/{}", config.public_base_url, token),
+            token,
+            target,
+            expires_at: params.expires_at,
+        }),
+    ))
+}
+
+pub(crate) async fn short_link_handler(
+    Path(token): Path<String>,
+    Extension(pool): Extension<Pool>,
+) -> AxumResult<impl IntoResponse> {
+    let (target, expires_at): (String, Option<DateTime<Utc>>) = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let row = conn
+            .query_opt(
+                "SELECT target, expires_at
+                 FROM short_links
+                 WHERE token = $1 AND (expires_at IS NULL OR expires_at > NOW())",
+                &[&token],
+            )?
+            .ok_or(AxumNope::ResourceNotFound)?;
+        Ok((row.get(0), row.get(1)))
+    })
+    .await?;
+
+    // expiring links must not be served from caches after they expired
+    let cache_policy = if expires_at.is_some() {
+        CachePolicy::NoCaching
+    } else {
+        CachePolicy::ForeverInCdnAndBrowser
+    };
+    Ok(super::axum_permanent_redirect(&target, cache_policy)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_token, link_target, TOKEN_LEN};
+    use crate::test::{assert_cache_control, wrapper};
+    use crate::web::cache::CachePolicy;
+    use reqwest::StatusCode;
+    use serde_json::json;
+    use test_case::test_case;
+
+    #[test]
+    fn generated_tokens() {
+        let token = generate_token().unwrap();
+        assert_eq!(token.len(), TOKEN_LEN);
+        assert!(token.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(token, generate_token().unwrap());
+    }
+
+    #[test_case("/serde/latest/serde/", Some("/serde/latest/serde/"))]
+    #[test_case(
+        "https://docs.rs/crate/serde/1.0.0?x=y",
+        Some("/crate/serde/1.0.0?x=y")
+    )]
+    #[test_case("https://docs.rs", Some("/"))]
+    #[test_case("https://docs.rs.evil.com/", None)]
+    #[test_case("https://example.com/", None)]
+    #[test_case("//example.com/", None)]
+    #[test_case("/\\example.com/", None)]
+    #[test_case("/\t/example.com/", None; "tab")]
+    #[test_case("/\n/example.com/", None; "newline")]
+    #[test_case("https://docs.rs/\r/example.com/", None; "carriage return")]
+    #[test_case("/ /example.com/", None; "space")]
+    #[test_case("serde", None)]
+    fn link_targets(url: &str, expected: Option<&str>) {
+        assert_eq!(
+            link_target(url, "https://docs.rs").ok().as_deref(),
+            expected
+        );
+    }
+
+    #[test]
+    fn create_and_resolve_short_link() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.short_link_tokens = vec!["admin:secret".parse().unwrap()];
+            });
+            let web = env.frontend();
+
+            let response = web
+                .post("/-/short-links")
+                .bearer_auth("secret")
+                .json(&json!({ "url": "https://docs.rs/serde/latest/serde/" }))
+                .send()?;
+            assert_eq!(response.status(), StatusCode::CREATED);
+            let link: serde_json::Value = response.json()?;
+            let token = link["token"].as_str().unwrap();
+            assert_eq!(link["url"], format!("https://docs.rs/-/s/{}", token));
+            assert_eq!(link["target"], "/serde/latest/serde/");
+
+            let created_by: String = env
+                .db()
+                .conn()
+                .query_one("SELECT created_by FROM short_links", &[])?
+                .get(0);
+            assert_eq!(created_by, "admin");
+
+            let response = web.get_no_redirect(&format!("/-/s/{}", token)).send()?;
+            assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+            assert_eq!(response.headers()["location"], "/serde/latest/serde/");
+            assert_cache_control(
+                &response,
+                CachePolicy::ForeverInCdnAndBrowser,
+                &env.config(),
+            );
+
+            assert_eq!(
+                web.get_no_redirect("/-/s/unknown").send()?.status(),
+                StatusCode::NOT_FOUND
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn expiring_short_link() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.short_link_tokens = vec!["admin:secret".parse().unwrap()];
+            });
+            let web = env.frontend();
+
+            let response = web
+                .post("/-/short-links")
+                .bearer_auth("secret")
+                .json(&json!({
+                    "url": "/crate/serde/latest",
+                    "expires_at": "2999-01-01T00:00:00Z",
+                }))
+                .send()?;
+            assert_eq!(response.status(), StatusCode::CREATED);
+            let link: serde_json::Value = response.json()?;
+            let token = link["token"].as_str().unwrap();
+
+            let response = web.get_no_redirect(&format!("/-/s/{}", token)).send()?;
+            assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+            assert_cache_control(&response, CachePolicy::NoCaching, &env.config());
+
+            env.db().conn().execute(
+                "UPDATE short_links SET expires_at = NOW() - INTERVAL '1 minute'",
+                &[],
+            )?;
+            let response = web.get_no_redirect(&format!("/-/s/{}", token)).send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            let response = web
+                .post("/-/short-links")
+                .bearer_auth("secret")
+                .json(&json!({
+                    "url": "/crate/serde/latest",
+                    "expires_at": "2000-01-01T00:00:00Z",
+                }))
+                .send()?;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn create_short_link_requires_token() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.short_link_tokens = vec!["admin:secret".parse().unwrap()];
+            });
+            let web = env.frontend();
+            let body = json!({ "url": "/crate/serde/latest" });
+
+            let response = web.post("/-/short-links").json(&body).send()?;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+            let response = web
+                .post("/-/short-links")
+                .bearer_auth("wrong")
+                .json(&body)
+                .send()?;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+            let response = web
+                .post("/-/short-links")
+                .bearer_auth("secret")
+                .json(&json!({ "url": "https://example.com/" }))
+                .send()?;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+            let count: i64 = env
+                .db()
+                .conn()
+                .query_one("SELECT COUNT(*) FROM short_links", &[])?
+                .get(0);
+            assert_eq!(count, 0);
+            Ok(())
+        })
+    }
+}
@@ -4,22 +4,84 @@ use crate::{
     impl_axum_webpage,
     utils::{get_config, spawn_blocking, ConfigName},
     web::{
+        base_url::BaseUrl,
+        cache::CachePolicy,
         error::{AxumNope, AxumResult},
         AxumErrorPage,
     },
+    Config, Storage,
 };
+use anyhow::Result;
 use axum::{
     extract::{Extension, Path},
-    http::StatusCode,
-    response::IntoResponse,
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response as AxumResponse},
+    Json,
 };
 use chrono::{DateTime, TimeZone, Utc};
-use serde::Serialize;
+use postgres::Client;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+
+/// Most URLs a single sitemap may contain, see <https://www.sitemaps.org/protocol.html>
+const SITEMAP_MAX_URLS: usize = 50_000;
+/// Largest uncompressed size of a single sitemap in bytes
+const SITEMAP_MAX_SIZE: usize = 50 * 1024 * 1024;
+/// Every release is listed with its crate root and its `all.html`
+const SITEMAP_URLS_PER_RELEASE: usize = 2;
+/// Upper bound for the size of a `<url>` entry without the base url. Crate and target names
+/// are at most 255 characters each, everything else is markup, the path and the timestamp.
+const SITEMAP_MAX_URL_SIZE: usize = 2 * 255 + 200;
+/// Upper bound for the size of the `<urlset>` around the entries
+const SITEMAP_MAX_HEADER_SIZE: usize = 200;
+
+/// The number of releases that fit into a single sitemap without exceeding any of its limits
+fn releases_per_sitemap(base_url: &str) -> usize {
+    let max_release_size = SITEMAP_URLS_PER_RELEASE * (base_url.len() + SITEMAP_MAX_URL_SIZE);
+    (SITEMAP_MAX_URLS / SITEMAP_URLS_PER_RELEASE)
+        .min((SITEMAP_MAX_SIZE - SITEMAP_MAX_HEADER_SIZE) / max_release_size)
+}
+
+/// The number of sitemaps needed for the given number of releases, at least one
+fn sitemap_parts(releases: usize, base_url: &str) -> usize {
+    ((releases + releases_per_sitemap(base_url) - 1) / releases_per_sitemap(base_url)).max(1)
+}
+
+fn validate_letter(letter: &str) -> AxumResult<()> {
+    let mut chars = letter.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) if ch.is_ascii_lowercase() => Ok(()),
+        _ => Err(AxumNope::ResourceNotFound),
+    }
+}
+
+/// Counts the releases listed in the sitemaps, of crates starting with the given prefix.
+/// With `stable_only`, pre-releases and yanked releases are left out.
+fn count_releases(conn: &mut Client, prefix: &str, stable_only: bool) -> Result<usize> {
+    let count: i64 = conn
+        .query_one(
+            "SELECT COUNT(*)
+             FROM (
+                SELECT DISTINCT crates.name, releases.target_name
+                FROM crates
+                INNER JOIN releases ON releases.crate_id = crates.id
+                WHERE
+                    rustdoc_status = true AND
+                    crates.name ILIKE $1 AND
+                    (NOT $2 OR (NOT releases.yanked AND releases.version !~ '^[^+]*-'))
+             ) AS sitemap_releases",
+            &[&format!("{}%", prefix), &stable_only],
+        )?
+        .get(0);
+    Ok(count as usize)
+}
 
 /// sitemap index
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 struct SitemapIndexXml {
-    sitemaps: Vec<char>,
+    /// Paths of the sitemaps relative to `/-/sitemap/`
+    sitemaps: Vec<String>,
+    base_url: String,
 }
 
 impl_axum_webpage! {
@@ -27,10 +89,49 @@ impl_axum_webpage! {
     content_type = "application/xml",
 }
 
-pub(crate) async fn sitemapindex_handler() -> impl IntoResponse {
-    let sitemaps: Vec<char> = ('a'..='z').collect();
+/// Lists the sitemaps of all letters. Letters with too many releases for a single sitemap are
+/// split into numbered parts, which are listed here directly since sitemap indexes can't be
+/// nested.
+pub(crate) async fn sitemapindex_handler(
+    Extension(pool): Extension<Pool>,
+    Extension(config): Extension<Arc<Config>>,
+    BaseUrl(base_url): BaseUrl,
+) -> AxumResult<impl IntoResponse> {
+    let stable_only = config.noindex_unstable_versions;
+    let counts: HashMap<String, usize> = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        Ok(conn
+            .query(
+                "SELECT LOWER(LEFT(name, 1)), COUNT(*)
+                 FROM (
+                    SELECT DISTINCT crates.name, releases.target_name
+                    FROM crates
+                    INNER JOIN releases ON releases.crate_id = crates.id
+                    WHERE
+                        rustdoc_status = true AND
+                        (NOT $1 OR (NOT releases.yanked AND releases.version !~ '^[^+]*-'))
+                 ) AS sitemap_releases
+                 GROUP BY 1",
+                &[&stable_only],
+            )?
+            .into_iter()
+            .map(|row| (row.get(0), row.get::<_, i64>(1) as usize))
+            .collect())
+    })
+    .await?;
+
+    let mut sitemaps = Vec::new();
+    for letter in 'a'..='z' {
+        let releases = counts.get(&letter.to_string()).copied().unwrap_or(0);
+        let parts = sitemap_parts(releases, &base_url);
+        if parts == 1 {
+            sitemaps.push(format!("{}/sitemap.xml", letter));
+        } else {
+            sitemaps.extend((1..=parts).map(|part| format!("{}/sitemap-{}.xml", letter, part)));
+        }
+    }
 
-    SitemapIndexXml { sitemaps }
+    Ok(SitemapIndexXml { sitemaps, base_url })
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
@@ -44,6 +145,7 @@ struct SitemapRow {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 struct SitemapXml {
     releases: Vec<SitemapRow>,
+    base_url: String,
 }
 
 impl_axum_webpage! {
@@ -51,51 +153,194 @@ impl_axum_webpage! {
     content_type = "application/xml",
 }
 
+/// Fetches one page of the releases in the sitemaps, of crates starting with the given prefix,
+/// ordered by name. With `stable_only`, pre-releases and yanked releases are left out.
+fn sitemap_releases(
+    conn: &mut Client,
+    prefix: &str,
+    stable_only: bool,
+    offset: usize,
+    limit: usize,
+) -> Result<Vec<SitemapRow>> {
+    let query = conn.query(
+        "SELECT crates.name,
+                releases.target_name,
+                MAX(releases.release_time) as release_time
+         FROM crates
+         INNER JOIN releases ON releases.crate_id = crates.id
+         WHERE 
+            rustdoc_status = true AND 
+            crates.name ILIKE $1 AND
+            (NOT $2 OR (NOT releases.yanked AND releases.version !~ '^[^+]*-'))
+         GROUP BY crates.name, releases.target_name
+         ORDER BY crates.name, releases.target_name
+         OFFSET $3
+         LIMIT $4",
+        &[
+            &format!("{}%", prefix),
+            &stable_only,
+            &(offset as i64),
+            &(limit as i64),
+        ],
+    )?;
+
+    Ok(query
+        .into_iter()
+        .map(|row| SitemapRow {
+            crate_name: row.get("name"),
+            target_name: row.get("target_name"),
+            last_modified: row
+                .get::<_, DateTime<Utc>>("release_time")
+                // On Aug 27 2022 we added `<link rel="canonical">` to all pages,
+                // so they should all get recrawled if they haven't been since then.
+                .max(Utc.with_ymd_and_hms(2022, 8, 28, 0, 0, 0).unwrap())
+                .format("%+")
+                .to_string(),
+        })
+        .collect())
+}
+
+/// The sitemap of a letter, or an index of its parts when it has too many releases for a
+/// single sitemap.
 pub(crate) async fn sitemap_handler(
     Path(letter): Path<String>,
     Extension(pool): Extension<Pool>,
+    Extension(config): Extension<Arc<Config>>,
+    BaseUrl(base_url): BaseUrl,
+) -> AxumResult<AxumResponse> {
+    validate_letter(&letter)?;
+    let letter = letter.chars().next().unwrap();
+
+    let limit = releases_per_sitemap(&base_url);
+    let stable_only = config.noindex_unstable_versions;
+    let (parts, releases) = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let prefix = letter.to_string();
+        let parts = sitemap_parts(count_releases(&mut conn, &prefix, stable_only)?, &base_url);
+        let releases = if parts == 1 {
+            sitemap_releases(&mut conn, &prefix, stable_only, 0, limit)?
+        } else {
+            Vec::new()
+        };
+        Ok((parts, releases))
+    })
+    .await?;
+
+    Ok(if parts == 1 {
+        SitemapXml { releases, base_url }.into_response()
+    } else {
+        SitemapIndexXml {
+            sitemaps: (1..=parts)
+                .map(|part| format!("{}/sitemap-{}.xml", letter, part))
+                .collect(),
+            base_url,
+        }
+        .into_response()
+    })
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct SitemapPartParams {
+    letter: String,
+    /// `sitemap-{n}.xml`, starting at 1
+    part: String,
+}
+
+/// A numbered part of the sitemap of a letter
+pub(crate) async fn sitemap_part_handler(
+    Path(params): Path<SitemapPartParams>,
+    Extension(pool): Extension<Pool>,
+    Extension(config): Extension<Arc<Config>>,
+    BaseUrl(base_url): BaseUrl,
 ) -> AxumResult<impl IntoResponse> {
-    if letter.len() != 1 {
-        return Err(AxumNope::ResourceNotFound);
-    } else if let Some(ch) = letter.chars().next() {
-        if !(ch.is_ascii_lowercase()) {
-            return Err(AxumNope::ResourceNotFound);
+    validate_letter(&params.letter)?;
+    let letter = params.letter.chars().next().unwrap();
+    let part: usize = params
+        .part
+        .strip_prefix("sitemap-")
+        .and_then(|part| part.strip_suffix(".xml"))
+        .and_then(|part| part.parse().ok())
+        .filter(|part| *part >= 1)
+        .ok_or(AxumNope::ResourceNotFound)?;
+
+    let limit = releases_per_sitemap(&base_url);
+    let stable_only = config.noindex_unstable_versions;
+    let releases = spawn_blocking({
+        let base_url = base_url.clone();
+        move || {
+            let mut conn = pool.get()?;
+            let prefix = letter.to_string();
+            let releases = count_releases(&mut conn, &prefix, stable_only)?;
+            if part > sitemap_parts(releases, &base_url) {
+                return Err(AxumNope::ResourceNotFound.into());
+            }
+            sitemap_releases(&mut conn, &prefix, stable_only, (part - 1) * limit, limit)
         }
-    }
-    let releases = spawn_blocking(move || {
-        let mut conn = pool.get()?;
-        let query = conn.query(
-            "SELECT crates.name,
-                    releases.target_name,
-                    MAX(releases.release_time) as release_time
-             FROM crates
-             INNER JOIN releases ON releases.crate_id = crates.id
-             WHERE 
-                rustdoc_status = true AND 
-                crates.name ILIKE $1 
-             GROUP BY crates.name, releases.target_name
-             ",
-            &[&format!("{}%", letter)],
-        )?;
+    })
+    .await?;
 
-        Ok(query
-            .into_iter()
-            .map(|row| SitemapRow {
-                crate_name: row.get("name"),
-                target_name: row.get("target_name"),
-                last_modified: row
-                    .get::<_, DateTime<Utc>>("release_time")
-                    // On Aug 27 2022 we added `<link rel="canonical">` to all pages,
-                    // so they should all get recrawled if they haven't been since then.
-                    .max(Utc.with_ymd_and_hms(2022, 8, 28, 0, 0, 0).unwrap())
-                    .format("%+")
-                    .to_string(),
-            })
-            .collect())
+    Ok(SitemapXml { releases, base_url })
+}
+
+/// A part of the plaintext sitemap, with one URL per line and the same URLs as the XML
+/// sitemaps. Since there is no plaintext sitemap index, `/sitemap.txt` holds the first part
+/// and the others are served at `/-/sitemap/{n}/sitemap.txt`, starting at 2.
+async fn sitemap_txt(
+    pool: Pool,
+    config: &Config,
+    base_url: String,
+    part: usize,
+) -> AxumResult<AxumResponse> {
+    let limit = releases_per_sitemap(&base_url);
+    let stable_only = config.noindex_unstable_versions;
+    let releases = spawn_blocking({
+        let base_url = base_url.clone();
+        move || {
+            let mut conn = pool.get()?;
+            let releases = count_releases(&mut conn, "", stable_only)?;
+            if part > sitemap_parts(releases, &base_url) {
+                return Err(AxumNope::ResourceNotFound.into());
+            }
+            sitemap_releases(&mut conn, "", stable_only, (part - 1) * limit, limit)
+        }
     })
     .await?;
 
-    Ok(SitemapXml { releases })
+    let mut body = String::new();
+    for release in releases {
+        let root = format!(
+            "{}/{}/latest/{}/",
+            base_url, release.crate_name, release.target_name
+        );
+        body.push_str(&format!("{}\n{}all.html\n", root, root));
+    }
+
+    Ok(([(CONTENT_TYPE, mime::TEXT_PLAIN_UTF_8.as_ref())], body).into_response())
+}
+
+/// The first part of the plaintext sitemap
+pub(crate) async fn sitemap_txt_handler(
+    Extension(pool): Extension<Pool>,
+    Extension(config): Extension<Arc<Config>>,
+    BaseUrl(base_url): BaseUrl,
+) -> AxumResult<AxumResponse> {
+    sitemap_txt(pool, &config, base_url, 1).await
+}
+
+/// A numbered part of the plaintext sitemap
+pub(crate) async fn sitemap_txt_part_handler(
+    Path(part): Path<String>,
+    Extension(pool): Extension<Pool>,
+    Extension(config): Extension<Arc<Config>>,
+    BaseUrl(base_url): BaseUrl,
+) -> AxumResult<AxumResponse> {
+    let part = part
+        .parse::<usize>()
+        .ok()
+        .filter(|part| *part >= 2)
+        .ok_or(AxumNope::ResourceNotFound)?;
+
+    sitemap_txt(pool, &config, base_url, part).await
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
@@ -126,6 +371,41 @@ pub(crate) async fn about_builds_handler(
     })
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct AboutStats {
+    crates: i64,
+    releases: i64,
+    successful_builds: i64,
+    /// `None` when the storage backend can't tell cheaply
+    storage_bytes: Option<u64>,
+}
+
+/// Aggregate numbers about the documentation hosted here, for status pages.
+pub(crate) async fn about_stats_json_handler(
+    Extension(pool): Extension<Pool>,
+    Extension(storage): Extension<Arc<Storage>>,
+) -> AxumResult<impl IntoResponse> {
+    let stats = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let row = conn.query_one(
+            "SELECT
+                (SELECT COUNT(*) FROM crates) AS crates,
+                (SELECT COUNT(*) FROM releases) AS releases,
+                (SELECT COUNT(*) FROM builds WHERE build_status) AS successful_builds",
+            &[],
+        )?;
+        Ok(AboutStats {
+            crates: row.get("crates"),
+            releases: row.get("releases"),
+            successful_builds: row.get("successful_builds"),
+            storage_bytes: storage.approximate_size()?,
+        })
+    })
+    .await?;
+
+    Ok((Extension(CachePolicy::ShortInCdn), Json(stats)))
+}
+
 #[derive(Serialize)]
 struct AboutPage<'a> {
     #[serde(skip)]
@@ -165,8 +445,10 @@ pub(crate) async fn about_handler(subpage: Option<Path<String>>) -> AxumResult<i
 
 #[cfg(test)]
 mod tests {
-    use crate::test::{assert_success, wrapper};
+    use crate::test::{assert_cache_control, assert_redirect_unchecked, assert_success, wrapper};
+    use crate::web::cache::CachePolicy;
     use reqwest::StatusCode;
+    use test_case::test_case;
 
     #[test]
     fn sitemap_index() {
@@ -233,6 +515,199 @@ mod tests {
         })
     }
 
+    #[test_case(true; "noindex enabled")]
+    #[test_case(false; "noindex disabled")]
+    fn sitemap_unstable_versions(noindex_unstable_versions: bool) {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.noindex_unstable_versions = noindex_unstable_versions;
+            });
+            env.fake_release()
+                .name("stable_crate")
+                .version("1.0.0")
+                .create()?;
+            env.fake_release()
+                .name("prerelease_crate")
+                .version("1.0.0-alpha")
+                .create()?;
+            env.fake_release()
+                .name("yanked_crate")
+                .version("1.0.0")
+                .yanked(true)
+                .create()?;
+            let web = env.frontend();
+
+            let sitemap = web.get("/sitemap.txt").send()?.text()?;
+            assert!(sitemap.contains("/stable_crate/latest/"));
+            for name in ["prerelease_crate", "yanked_crate"] {
+                assert_eq!(
+                    !sitemap.contains(&format!("/{}/latest/", name)),
+                    noindex_unstable_versions,
+                    "{}",
+                    name
+                );
+            }
+
+            let sitemap = web.get("/-/sitemap/p/sitemap.xml").send()?.text()?;
+            assert_eq!(
+                !sitemap.contains("/prerelease_crate/latest/"),
+                noindex_unstable_versions
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn sitemap_txt() {
+        wrapper(|env| {
+            env.fake_release().name("some_random_crate").create()?;
+            env.fake_release()
+                .name("other_crate")
+                .version("0.2.0")
+                .create()?;
+            env.fake_release()
+                .name("some_random_crate_that_failed")
+                .build_result_failed()
+                .create()?;
+            let web = env.frontend();
+
+            let response = web.get("/sitemap.txt").send()?;
+            assert!(response.status().is_success());
+            assert_eq!(
+                response.headers()["content-type"],
+                "text/plain; charset=utf-8"
+            );
+            let base_url = env.config().public_base_url.clone();
+            let expected: Vec<String> = ["other_crate", "some_random_crate"]
+                .iter()
+                .flat_map(|name| {
+                    [
+                        format!("{}/{}/latest/{}/", base_url, name, name),
+                        format!("{}/{}/latest/{}/all.html", base_url, name, name),
+                    ]
+                })
+                .collect();
+            assert_eq!(response.text()?.lines().collect::<Vec<_>>(), expected);
+
+            // everything fits into the first part
+            for invalid in [
+                "/-/sitemap/1/sitemap.txt",
+                "/-/sitemap/2/sitemap.txt",
+                "/-/sitemap/x/sitemap.txt",
+            ] {
+                assert_eq!(
+                    web.get(invalid).send()?.status(),
+                    StatusCode::NOT_FOUND,
+                    "{}",
+                    invalid
+                );
+            }
+
+            // crates named like sitemaps have their root page
+            assert_redirect_unchecked("/sitemap-rs", "/sitemap-rs/", web)?;
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn sitemap_large_letter_is_split() {
+        wrapper(|env| {
+            // one release more than fits into a single sitemap
+            let releases = (super::SITEMAP_MAX_URLS / super::SITEMAP_URLS_PER_RELEASE + 1) as i32;
+            let mut conn = env.db().conn();
+            conn.execute(
+                "INSERT INTO crates (name)
+                 SELECT 'zz-synthetic-' || LPAD(i::TEXT, 6, '0') FROM generate_series(1, $1) AS i",
+                &[&releases],
+            )?;
+            conn.execute(
+                "INSERT INTO releases (
+                    crate_id, version, release_time, target_name, rustdoc_status,
+                    doc_rustc_version, default_target, doc_targets
+                 )
+                 SELECT id, '1.0.0', NOW(), REPLACE(name, '-', '_'), true,
+                    'rustc 1.66.0', 'x86_64-unknown-linux-gnu', '[]'
+                 FROM crates
+                 WHERE name LIKE 'zz-synthetic-%'",
+                &[],
+            )?;
+            drop(conn);
+
+            let web = env.frontend();
+
+            let index = web.get("/sitemap.xml").send()?.text()?;
+            assert!(index.contains("/-/sitemap/a/sitemap.xml"));
+            assert!(!index.contains("/-/sitemap/z/sitemap.xml"));
+            assert!(index.contains("/-/sitemap/z/sitemap-1.xml"));
+            assert!(index.contains("/-/sitemap/z/sitemap-2.xml"));
+            assert!(!index.contains("/-/sitemap/z/sitemap-3.xml"));
+
+            let letter_index = web.get("/-/sitemap/z/sitemap.xml").send()?;
+            assert!(letter_index.status().is_success());
+            let letter_index = letter_index.text()?;
+            assert!(letter_index.contains("<sitemapindex"));
+            assert!(letter_index.contains("/-/sitemap/z/sitemap-1.xml"));
+            assert!(letter_index.contains("/-/sitemap/z/sitemap-2.xml"));
+
+            let first = web.get("/-/sitemap/z/sitemap-1.xml").send()?.text()?;
+            assert_eq!(first.matches("<url>").count(), super::SITEMAP_MAX_URLS,);
+            assert!(first.len() <= super::SITEMAP_MAX_SIZE);
+            assert!(first.contains("/zz-synthetic-000001/latest/"));
+
+            // the last release ends up alone in the second part
+            let second = web.get("/-/sitemap/z/sitemap-2.xml").send()?.text()?;
+            assert_eq!(
+                second.matches("<url>").count(),
+                super::SITEMAP_URLS_PER_RELEASE
+            );
+            assert!(second.contains(&format!("/zz-synthetic-{:06}/latest/", releases)));
+
+            // the plaintext sitemap is split the same way
+            let first = web.get("/sitemap.txt").send()?.text()?;
+            assert_eq!(first.lines().count(), super::SITEMAP_MAX_URLS);
+            assert!(first.contains("/zz-synthetic-000001/latest/"));
+            let second = web.get("/-/sitemap/2/sitemap.txt").send()?;
+            assert!(second.status().is_success());
+            assert_eq!(
+                second.text()?.lines().collect::<Vec<_>>(),
+                [
+                    format!(
+                        "{}/zz-synthetic-{:06}/latest/zz_synthetic_{:06}/",
+                        env.config().public_base_url,
+                        releases,
+                        releases
+                    ),
+                    format!(
+                        "{}/zz-synthetic-{:06}/latest/zz_synthetic_{:06}/all.html",
+                        env.config().public_base_url,
+                        releases,
+                        releases
+                    ),
+                ]
+            );
+            assert_eq!(
+                web.get("/-/sitemap/3/sitemap.txt").send()?.status(),
+                StatusCode::NOT_FOUND
+            );
+
+            for invalid in [
+                "sitemap-0.xml",
+                "sitemap-3.xml",
+                "sitemap-x.xml",
+                "other.xml",
+            ] {
+                assert_eq!(
+                    web.get(&format!("/-/sitemap/z/{}", invalid))
+                        .send()?
+                        .status(),
+                    StatusCode::NOT_FOUND,
+                );
+            }
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn sitemap_max_age() {
         wrapper(|env| {
@@ -274,6 +749,31 @@ mod tests {
         })
     }
 
+    #[test]
+    fn about_stats() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            env.fake_release().name("foo").version("0.2.0").create()?;
+            env.fake_release()
+                .name("bar")
+                .version("0.1.0")
+                .build_result_failed()
+                .create()?;
+
+            let response = env.frontend().get("/about/stats.json").send()?;
+            assert!(response.status().is_success());
+            assert_cache_control(&response, CachePolicy::ShortInCdn, &env.config());
+
+            let stats: serde_json::Value = response.json()?;
+            assert_eq!(stats["crates"], 2);
+            assert_eq!(stats["releases"], 3);
+            assert_eq!(stats["successful_builds"], 2);
+            // the test environment stores files in the database
+            assert!(stats["storage_bytes"].as_u64().unwrap() > 0);
+            Ok(())
+        })
+    }
+
     #[test]
     fn robots_txt() {
         wrapper(|env| {
@@ -2,18 +2,57 @@ use super::{error::AxumResult, match_version_axum};
 use crate::{
     db::Pool,
     impl_axum_webpage,
-    utils::{get_correct_docsrs_style_file, spawn_blocking},
-    web::{cache::CachePolicy, error::AxumNope, file::File as DbFile, MatchSemver, MetaData},
+    storage::{source_archive_path, FileMetadata, FileRange, PathNotFoundError},
+    utils::{get_correct_docsrs_style_file, report_error, spawn_blocking},
+    web::{
+        base_url::BaseUrl,
+        cache::CachePolicy,
+        error::{AxumNope, JsonAxumNope},
+        highlight,
+        output_format::{OutputFormat, RequestedFormat},
+        page::web_page::StreamedContent,
+        MatchSemver, MetaData,
+    },
     Storage,
 };
-use anyhow::Result;
-use axum::{extract::Path, response::IntoResponse, Extension};
+use anyhow::{anyhow, Context as _, Result};
+use axum::{
+    body::{boxed, Bytes, Empty, StreamBody},
+    extract::{Path, Query},
+    http::{
+        header::{
+            ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG,
+            IF_RANGE, LAST_MODIFIED, RANGE,
+        },
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::IntoResponse,
+    Extension, Json,
+};
+use futures_util::stream;
+use httpdate::fmt_http_date;
 use postgres::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::{cmp::Ordering, sync::Arc};
+use std::{cmp::Ordering, fmt::Write as _, io, sync::Arc, time::SystemTime};
+use tokio::sync::mpsc;
 use tracing::instrument;
 
+/// Lines of source code highlighted and sent to the client at once
+const SOURCE_CHUNK_LINES: usize = 500;
+/// Bytes at the start of a file searched for NUL bytes, the same amount git checks
+const BINARY_SNIFF_LENGTH: usize = 8000;
+/// Size of the parts source archives are read from storage in while they are sent
+const ARCHIVE_CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// Whether a source file is binary instead of text, judged by its content so text files with
+/// unusual extensions are still shown. Like git, files with a NUL byte near the start are binary,
+/// and so is everything that isn't valid UTF-8.
+fn is_binary(content: &[u8]) -> bool {
+    content[..content.len().min(BINARY_SNIFF_LENGTH)].contains(&0)
+        || std::str::from_utf8(content).is_err()
+}
+
 /// A source file's name and mime type
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Serialize)]
 struct File {
@@ -164,7 +203,10 @@ struct SourcePage {
     file_list: FileList,
     show_parent_link: bool,
     file: Option<File>,
-    file_content: Option<String>,
+    /// The content itself is streamed, see [`stream_highlighted_source`]
+    has_file_content: bool,
+    /// The file is binary, only a link to the raw file is shown
+    is_binary: bool,
     canonical_url: String,
     is_latest_url: bool,
 }
@@ -172,12 +214,44 @@ struct SourcePage {
 impl_axum_webpage! {
     SourcePage = "crate/source.html",
     canonical_url = |page| Some(page.canonical_url.clone()),
-    cache_policy = |page| if page.is_latest_url {
+    cache_policy = |page| source_cache_policy(page.is_latest_url),
+    cpu_intensive_rendering = true,
+}
+
+/// Pages under `/latest/` change with every release, so browsers must not cache them.
+fn source_cache_policy(is_latest_url: bool) -> CachePolicy {
+    if is_latest_url {
         CachePolicy::ForeverInCdn
     } else {
         CachePolicy::ForeverInCdnAndStaleInBrowser
-    },
-    cpu_intensive_rendering = true,
+    }
+}
+
+/// Shown instead of the source browser for releases that were built before the source code of
+/// crates was stored.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct SourceNotAvailablePage {
+    metadata: MetaData,
+}
+
+impl_axum_webpage! {
+    SourceNotAvailablePage = "crate/source_not_available.html",
+    status = |_| StatusCode::NOT_FOUND,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct SourceJson {
+    name: String,
+    version: String,
+    path: String,
+    files: Vec<File>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct SourceBrowserQuery {
+    /// Serve the file itself instead of the source browser page
+    #[serde(default)]
+    raw: bool,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -195,8 +269,11 @@ pub(crate) async fn source_browser_handler(
         version,
         path,
     }): Path<SourceBrowserHandlerParams>,
+    Query(query): Query<SourceBrowserQuery>,
+    RequestedFormat(format): RequestedFormat,
     Extension(storage): Extension<Arc<Storage>>,
     Extension(pool): Extension<Pool>,
+    BaseUrl(base_url): BaseUrl,
 ) -> AxumResult<impl IntoResponse> {
     let v = match_version_axum(&pool, &name, Some(&version)).await?;
 
@@ -217,58 +294,95 @@ pub(crate) async fn source_browser_handler(
         }
     };
 
-    let blob = spawn_blocking({
+    let (source_available, blob) = spawn_blocking({
         let pool = pool.clone();
         let path = path.clone();
         let name = name.clone();
         let version = version.clone();
         move || {
             let mut conn = pool.get()?;
-            let archive_storage: bool = conn
-                .query_one(
-                    "SELECT archive_storage
-                     FROM releases
-                     INNER JOIN crates ON releases.crate_id = crates.id
-                     WHERE
-                         name = $1 AND
-                         version = $2",
-                    &[&name, &version],
-                )?
-                .get::<_, bool>(0);
+            // releases built before the source code was stored don't have a file list
+            let row = conn.query_one(
+                "SELECT archive_storage,
+                        COALESCE(json_typeof(releases.files) = 'array', false)
+                 FROM releases
+                 INNER JOIN crates ON releases.crate_id = crates.id
+                 WHERE
+                     name = $1 AND
+                     version = $2",
+                &[&name, &version],
+            )?;
+            let archive_storage: bool = row.get(0);
+            let source_available: bool = row.get(1);
 
             // try to get actual file first
-            // skip if request is a directory
-            Ok(if !path.ends_with('/') {
+            // skip if request is a directory or only the file list is needed
+            let blob = if source_available && format == OutputFormat::Html && !path.ends_with('/') {
                 storage
                     .fetch_source_file(&name, &version, &path, archive_storage)
                     .ok()
             } else {
                 None
-            })
+            };
+            Ok((source_available, blob))
         }
     })
     .await?;
 
-    let (file, file_content) = if let Some(blob) = blob {
-        let is_text = blob.mime.starts_with("text") || blob.mime == "application/json";
-        // serve the file with DatabaseFileHandler if file isn't text and not empty
-        if !is_text && !blob.is_empty() {
-            return Ok(DbFile(blob).into_response());
-        } else if is_text && !blob.is_empty() {
-            let path = blob
-                .path
-                .rsplit_once('/')
-                .map(|(_, path)| path)
-                .unwrap_or(&blob.path);
-            (
-                Some(File::from_path_and_mime(path, &blob.mime)),
-                String::from_utf8(blob.content).ok(),
+    if !source_available {
+        return Ok(match format {
+            OutputFormat::Json => JsonAxumNope(AxumNope::SourceNotAvailable).into_response(),
+            OutputFormat::Html => {
+                let metadata = spawn_blocking(move || {
+                    let mut conn = pool.get()?;
+                    MetaData::from_crate(&mut conn, &name, &version, &version_or_latest)
+                })
+                .await?;
+                SourceNotAvailablePage { metadata }.into_response()
+            }
+        });
+    }
+
+    let (file, file_content, is_binary) = if let Some(blob) = blob {
+        let is_binary = is_binary(&blob.content);
+        if query.raw {
+            let content_type = if is_binary {
+                mime::APPLICATION_OCTET_STREAM
+            } else {
+                mime::TEXT_PLAIN_UTF_8
+            };
+            return Ok((
+                Extension(source_cache_policy(is_latest_url)),
+                [(CONTENT_TYPE, content_type.as_ref())],
+                blob.content,
             )
+                .into_response());
+        }
+
+        let path = blob
+            .path
+            .rsplit_once('/')
+            .map(|(_, path)| path)
+            .unwrap_or(&blob.path);
+        if blob.is_empty() {
+            (None, None, false)
+        } else if is_binary {
+            let file = File::from_path_and_mime(path, mime::APPLICATION_OCTET_STREAM.as_ref());
+            (Some(file), None, true)
         } else {
-            (None, None)
+            // text files with an unknown or binary extension are still shown as plain text
+            let mime = if blob.mime.starts_with("text") || blob.mime == "application/json" {
+                blob.mime.as_str()
+            } else {
+                mime::TEXT_PLAIN.as_ref()
+            };
+            let file = File::from_path_and_mime(path, mime);
+            (Some(file), String::from_utf8(blob.content).ok(), false)
         }
+    } else if query.raw {
+        return Err(AxumNope::ResourceNotFound);
     } else {
-        (None, None)
+        (None, None, false)
     };
 
     let current_folder = if let Some(last_slash_pos) = path.rfind('/') {
@@ -279,6 +393,7 @@ pub(crate) async fn source_browser_handler(
 
     let file_list = spawn_blocking({
         let name = name.clone();
+        let version = version.clone();
         let current_folder = current_folder.to_string();
         move || {
             let mut conn = pool.get()?;
@@ -291,22 +406,380 @@ pub(crate) async fn source_browser_handler(
             )
         }
     })
-    .await?
-    .ok_or(AxumNope::ResourceNotFound)?;
+    .await?;
 
-    Ok(SourcePage {
+    if format == OutputFormat::Json {
+        return Ok(match file_list {
+            Some(file_list) => (
+                Extension(source_cache_policy(is_latest_url)),
+                Json(SourceJson {
+                    name,
+                    version,
+                    path: current_folder.to_string(),
+                    files: file_list.files,
+                }),
+            )
+                .into_response(),
+            None => JsonAxumNope(AxumNope::ResourceNotFound).into_response(),
+        });
+    }
+    let file_list = file_list.ok_or(AxumNope::ResourceNotFound)?;
+
+    let lang = file.as_ref().map(|file| file.name.clone());
+    let mut response = SourcePage {
         file_list,
         show_parent_link: !current_folder.is_empty(),
         file,
-        file_content,
-        canonical_url: format!("https://docs.rs/crate/{}/latest/source/{}", name, path),
+        has_file_content: file_content.is_some(),
+        is_binary,
+        canonical_url: format!("{}/crate/{}/latest/source/{}", base_url, name, path),
         is_latest_url,
     }
-    .into_response())
+    .into_response();
+    if let Some(file_content) = file_content {
+        response
+            .extensions_mut()
+            .insert(stream_highlighted_source(lang, file_content));
+    }
+    Ok(response)
+}
+
+/// Highlights the source code in a background thread, sending it in chunks of
+/// [`SOURCE_CHUNK_LINES`] so the browser can start showing large files before all of it is
+/// highlighted.
+///
+/// Every chunk has its own column of line numbers, linking to the `#L<line>` anchors.
+fn stream_highlighted_source(lang: Option<String>, code: String) -> StreamedContent {
+    // only a few chunks are highlighted ahead of what the client received
+    let (sender, receiver) = mpsc::channel(2);
+
+    tokio::task::spawn_blocking(move || {
+        let mut first_line = 1;
+        for chunk in highlight::chunks_with_lang(lang.as_deref(), &code, SOURCE_CHUNK_LINES) {
+            let mut html = String::from(r#"<div class="source-chunk"><pre class="line-numbers">"#);
+            for line in first_line..first_line + chunk.lines {
+                writeln!(html, r##"<a id="L{0}" href="#L{0}">{0}</a>"##, line).unwrap();
+            }
+            html.push_str("</pre><pre><code>");
+            html.push_str(&chunk.html);
+            html.push_str("</code></pre></div>");
+            first_line += chunk.lines;
+
+            if sender.blocking_send(html).is_err() {
+                // the client went away
+                break;
+            }
+        }
+    });
+
+    StreamedContent(receiver)
+}
+
+/// Returns the path of the crate's manifest in the source file list stored in `releases.files`.
+///
+/// Packaged crates have it at the root, but sources from other places might keep the crate in a
+/// subdirectory of a workspace, then the manifest closest to the root is used.
+fn find_manifest(files: &Value) -> Option<String> {
+    files
+        .as_array()?
+        .iter()
+        .filter_map(|file| file.as_array()?.get(1)?.as_str())
+        .filter(|path| *path == "Cargo.toml" || path.ends_with("/Cargo.toml"))
+        .min_by_key(|path| path.matches('/').count())
+        .map(str::to_owned)
+}
+
+/// Serves the `Cargo.toml` the crate was published with from the stored source.
+#[instrument(skip(pool, storage))]
+pub(crate) async fn cargo_toml_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(pool): Extension<Pool>,
+) -> AxumResult<impl IntoResponse> {
+    let version = match match_version_axum(&pool, &name, Some(&req_version))
+        .await?
+        .assume_exact()?
+    {
+        MatchSemver::Exact((version, _)) | MatchSemver::Latest((version, _)) => version,
+        MatchSemver::Semver((version, _)) => {
+            return Ok(super::axum_cached_redirect(
+                &format!("/crate/{}/{}/Cargo.toml", name, version),
+                CachePolicy::ForeverInCdn,
+            )?
+            .into_response());
+        }
+    };
+
+    let content = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let row = conn
+            .query_opt(
+                "SELECT releases.archive_storage, releases.files
+                 FROM releases
+                 INNER JOIN crates ON releases.crate_id = crates.id
+                 WHERE crates.name = $1 AND releases.version = $2",
+                &[&name, &version],
+            )?
+            .ok_or(AxumNope::VersionNotFound)?;
+
+        // releases built before the source code was stored don't have a file list
+        let path = row
+            .get::<_, Option<Value>>("files")
+            .as_ref()
+            .and_then(find_manifest)
+            .ok_or(AxumNope::ResourceNotFound)?;
+
+        match storage.fetch_source_file(&name, &version, &path, row.get("archive_storage")) {
+            Ok(blob) => Ok(blob.content),
+            Err(err) if err.is::<PathNotFoundError>() => Err(AxumNope::ResourceNotFound.into()),
+            Err(err) => Err(err),
+        }
+    })
+    .await?;
+
+    Ok((
+        Extension(CachePolicy::ForeverInCdn),
+        [(CONTENT_TYPE, mime::TEXT_PLAIN_UTF_8.as_ref())],
+        content,
+    )
+        .into_response())
+}
+
+/// The bytes of a file requested with the `Range` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RequestedRange {
+    /// Without a `Range` header or with one we don't support, like multiple ranges, the whole
+    /// file is served.
+    Full,
+    Partial(FileRange),
+    /// The range starts after the end of the file.
+    Unsatisfiable,
+}
+
+/// Parses a single byte range like `bytes=0-99`, `bytes=100-` or `bytes=-100` (the last 100
+/// bytes) of a file with `size` bytes. Ranges ending after the end of the file are shortened.
+fn requested_range(header: Option<&HeaderValue>, size: u64) -> RequestedRange {
+    let parse = || {
+        let spec = header?.to_str().ok()?.strip_prefix("bytes=")?.trim();
+        let (start, end) = spec.split_once('-')?;
+        let last = size.checked_sub(1);
+
+        Some(if start.is_empty() {
+            let len: u64 = end.parse().ok()?;
+            match last {
+                Some(last) if len > 0 => RequestedRange::Partial(size.saturating_sub(len)..=last),
+                _ => RequestedRange::Unsatisfiable,
+            }
+        } else {
+            let start: u64 = start.parse().ok()?;
+            let end: Option<u64> = if end.is_empty() {
+                None
+            } else {
+                Some(end.parse().ok().filter(|end| *end >= start)?)
+            };
+            match last {
+                Some(last) if start <= last => {
+                    RequestedRange::Partial(start..=end.map_or(last, |end| end.min(last)))
+                }
+                _ => RequestedRange::Unsatisfiable,
+            }
+        })
+    };
+    parse().unwrap_or(RequestedRange::Full)
+}
+
+/// The `ETag` of a stored file, it changes when the file is stored again, like when it's
+/// recompressed.
+fn file_etag(metadata: &FileMetadata) -> String {
+    format!(
+        "\"{:x}-{:x}\"",
+        metadata.size,
+        metadata.date_updated.timestamp_millis()
+    )
+}
+
+/// Whether a range may be served for the `If-Range` header, which has to name the current
+/// `etag` or `last_modified` date of the file when it's set. Weak tags never match.
+fn if_range_matches(header: Option<&HeaderValue>, etag: &str, last_modified: &str) -> bool {
+    let header = match header {
+        Some(header) => header.to_str().unwrap_or_default().trim(),
+        None => return true,
+    };
+    if header.starts_with('"') || header.starts_with("W/") {
+        header == etag
+    } else {
+        httpdate::parse_http_date(header).map_or(false, |date| fmt_http_date(date) == last_modified)
+    }
+}
+
+/// Reads `range` of a stored file in parts of [`ARCHIVE_CHUNK_SIZE`] bytes in a background
+/// thread, sending them as they are read. Reading stops with an error when the file is stored
+/// again in between, the parts have to come from the file described by `metadata`.
+fn stream_file_range(
+    storage: Arc<Storage>,
+    path: String,
+    range: FileRange,
+    metadata: FileMetadata,
+) -> mpsc::Receiver<io::Result<Bytes>> {
+    // only a few parts are read ahead of what the client received
+    let (sender, receiver) = mpsc::channel(2);
+
+    tokio::task::spawn_blocking(move || {
+        let mut start = *range.start();
+        while start <= *range.end() {
+            let end = (*range.end()).min(start + ARCHIVE_CHUNK_SIZE - 1);
+            let part = storage
+                .get_range(&path, ARCHIVE_CHUNK_SIZE as usize, start..=end, None)
+                .and_then(|blob| {
+                    if blob.date_updated == metadata.date_updated {
+                        Ok(Bytes::from(blob.content))
+                    } else {
+                        Err(anyhow!("{} was stored again while it was sent", path))
+                    }
+                });
+            let part = part.map_err(|err| {
+                report_error(&err);
+                io::Error::new(io::ErrorKind::Other, "failed to read the archive")
+            });
+            let failed = part.is_err();
+            // the client went away when the response can't be sent anymore
+            if sender.blocking_send(part).is_err() || failed {
+                break;
+            }
+            start = end + 1;
+        }
+    });
+
+    receiver
+}
+
+/// Serves the archive with the source code of a release. Single byte ranges are supported so
+/// interrupted downloads can be resumed, with `If-Range` to make sure the parts come from the
+/// same archive.
+///
+/// The archive is read from storage in parts while it's sent. Only complete archives are cached,
+/// CDNs would serve cached ranges for other ranges.
+#[instrument(skip(pool, storage))]
+pub(crate) async fn source_archive_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(pool): Extension<Pool>,
+    headers: HeaderMap,
+) -> AxumResult<impl IntoResponse> {
+    let version = match match_version_axum(&pool, &name, Some(&req_version))
+        .await?
+        .assume_exact()?
+    {
+        MatchSemver::Exact((version, _)) => version,
+        // the ranges of a download have to come from the same archive
+        MatchSemver::Latest((version, _)) | MatchSemver::Semver((version, _)) => {
+            return Ok(super::axum_cached_redirect(
+                &format!("/crate/{}/{}/source.zip", name, version),
+                CachePolicy::ForeverInCdn,
+            )?
+            .into_response());
+        }
+    };
+
+    let path = source_archive_path(&name, &version);
+    let metadata = spawn_blocking({
+        let (name, version, path) = (name.clone(), version.clone(), path.clone());
+        let storage = storage.clone();
+        move || {
+            let mut conn = pool.get()?;
+            let archive_storage: bool = conn
+                .query_opt(
+                    "SELECT releases.archive_storage
+                     FROM releases
+                     INNER JOIN crates ON releases.crate_id = crates.id
+                     WHERE crates.name = $1 AND releases.version = $2",
+                    &[&name, &version],
+                )?
+                .ok_or(AxumNope::VersionNotFound)?
+                .get(0);
+            // releases stored before the archives were introduced only have single files
+            if !archive_storage {
+                return Err(AxumNope::ResourceNotFound.into());
+            }
+
+            match storage.get_metadata(&path) {
+                Ok(metadata) => Ok(metadata),
+                Err(err) if err.is::<PathNotFoundError>() => Err(AxumNope::ResourceNotFound.into()),
+                Err(err) => Err(err),
+            }
+        }
+    })
+    .await?;
+
+    let size = metadata.size;
+    let etag = file_etag(&metadata);
+    let last_modified = fmt_http_date(SystemTime::from(metadata.date_updated));
+    let requested = if if_range_matches(headers.get(IF_RANGE), &etag, &last_modified) {
+        requested_range(headers.get(RANGE), size)
+    } else {
+        RequestedRange::Full
+    };
+    let (status, cache_policy, range) = match requested {
+        RequestedRange::Full => (
+            StatusCode::OK,
+            source_cache_policy(false),
+            size.checked_sub(1).map(|last| 0..=last),
+        ),
+        RequestedRange::Partial(range) => (
+            StatusCode::PARTIAL_CONTENT,
+            CachePolicy::NoCaching,
+            Some(range),
+        ),
+        RequestedRange::Unsatisfiable => {
+            return Ok((
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                Extension(CachePolicy::NoCaching),
+                [(CONTENT_RANGE, format!("bytes */{}", size))],
+            )
+                .into_response());
+        }
+    };
+
+    let (length, body) = match range.clone() {
+        Some(range) => (
+            range.end() - range.start() + 1,
+            boxed(StreamBody::new(stream::unfold(
+                stream_file_range(storage, path, range, metadata),
+                |mut parts| async move { parts.recv().await.map(|part| (part, parts)) },
+            ))),
+        ),
+        None => (0, boxed(Empty::new())),
+    };
+    let mut response = (
+        status,
+        Extension(cache_policy),
+        [
+            (CONTENT_TYPE, "application/zip".to_owned()),
+            (CONTENT_LENGTH, length.to_string()),
+            (ACCEPT_RANGES, "bytes".to_owned()),
+            (ETAG, etag),
+            (LAST_MODIFIED, last_modified),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}-{}.zip\"", name, version),
+            ),
+        ],
+        body,
+    )
+        .into_response();
+    if let (StatusCode::PARTIAL_CONTENT, Some(range)) = (status, range) {
+        response.headers_mut().insert(
+            CONTENT_RANGE,
+            HeaderValue::try_from(format!("bytes {}-{}/{}", range.start(), range.end(), size))
+                .context("invalid content range")?,
+        );
+    }
+    Ok(response)
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{requested_range, RequestedRange};
     use crate::test::*;
     use crate::web::cache::CachePolicy;
     use kuchiki::traits::TendrilSink;
@@ -534,6 +1007,37 @@ mod tests {
         });
     }
 
+    #[test]
+    fn large_files_are_streamed_in_chunks() {
+        wrapper(|env| {
+            let code: String = (0..1200)
+                .map(|i| format!("const C{i}: u32 = {i};\n"))
+                .collect();
+            env.fake_release()
+                .name("fake")
+                .version("0.1.0")
+                .source_file("big.rs", code.as_bytes())
+                .create()?;
+
+            let web = env.frontend();
+
+            let response = web.get("/crate/fake/0.1.0/source/big.rs").send()?;
+            assert!(response.status().is_success());
+            let text = response.text()?;
+
+            assert_eq!(text.matches(r#"<div class="source-chunk">"#).count(), 3);
+            let first = text.find(r#"id="L1""#).unwrap();
+            let chunk_start = text.find(r#"id="L501""#).unwrap();
+            let last = text.find(r#"id="L1200""#).unwrap();
+            assert!(first < chunk_start && chunk_start < last);
+            assert!(!text.contains(r#"id="L1201""#));
+            assert!(text.contains(r#"<span class="syntax-source syntax-rust">"#));
+            assert!(text.trim_end().ends_with("</html>"));
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn json_is_served_as_rendered_html() {
         wrapper(|env| {
@@ -564,6 +1068,71 @@ mod tests {
         });
     }
 
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x01\0\0\0\x01\x08\x06\0\0\0";
+
+    #[test_case("logo.png", PNG, "application/octet-stream")]
+    #[test_case("src/lib.rs", "pub fn föö() {}".as_bytes(), "text/plain; charset=utf-8")]
+    #[test_case("data/input.bin", "übersetzung: ja".as_bytes(), "text/plain; charset=utf-8")]
+    fn raw_files_by_content(path: &str, content: &[u8], content_type: &str) {
+        wrapper(|env| {
+            env.fake_release()
+                .name("fake")
+                .version("0.1.0")
+                .source_file(path, content)
+                .create()?;
+
+            let response = env
+                .frontend()
+                .get(&format!("/crate/fake/0.1.0/source/{}?raw=true", path))
+                .send()?;
+            assert!(response.status().is_success());
+            assert_eq!(response.headers()["content-type"], content_type);
+            assert_eq!(response.bytes()?, content);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn binary_files_are_not_rendered() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("fake")
+                .version("0.1.0")
+                .source_file("logo.png", PNG)
+                .source_file("data/input.bin", "übersetzung: ja".as_bytes())
+                .create()?;
+            let web = env.frontend();
+
+            let page = kuchiki::parse_html().one(
+                web.get("/crate/fake/0.1.0/source/logo.png")
+                    .send()?
+                    .text()?,
+            );
+            let placeholder = page.select_first("#source-code.binary-file").unwrap();
+            assert!(placeholder.text_contents().contains("Binary file"));
+            assert!(page.select_first("#source-code .source-chunk").is_err());
+
+            // text files are shown whatever their extension
+            let page = kuchiki::parse_html().one(
+                web.get("/crate/fake/0.1.0/source/data/input.bin")
+                    .send()?
+                    .text()?,
+            );
+            assert!(page.select_first("#source-code.binary-file").is_err());
+            assert!(page
+                .select_first("#source-code")
+                .unwrap()
+                .text_contents()
+                .contains("übersetzung"));
+
+            let response = web
+                .get("/crate/fake/0.1.0/source/missing.rs?raw=true")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            Ok(())
+        })
+    }
+
     #[test]
     fn root_file_list() {
         wrapper(|env| {
@@ -622,4 +1191,329 @@ mod tests {
             Ok(())
         });
     }
+
+    #[test_case(true)]
+    #[test_case(false)]
+    fn source_not_available(archive_storage: bool) {
+        wrapper(|env| {
+            let release_id = env
+                .fake_release()
+                .archive_storage(archive_storage)
+                .name("fake")
+                .version("0.1.0")
+                .source_file("src/lib.rs", b"pub fn foo() {}")
+                .create()?;
+            // releases built before the source code was stored
+            env.db().conn().execute(
+                "UPDATE releases SET files = NULL WHERE id = $1",
+                &[&release_id],
+            )?;
+
+            let web = env.frontend();
+            for path in [
+                "/crate/fake/0.1.0/source/",
+                "/crate/fake/0.1.0/source/src/lib.rs",
+            ] {
+                let response = web.get(path).send()?;
+                assert_eq!(response.status(), StatusCode::NOT_FOUND);
+                let page = kuchiki::parse_html().one(response.text()?);
+                let message = page.select_first("#source-not-available").unwrap();
+                assert!(message.text_contents().contains("not available"));
+                let link = message.as_node().select_first("a").unwrap();
+                assert_eq!(
+                    link.attributes.borrow().get("href"),
+                    Some("/fake/0.1.0/fake/")
+                );
+            }
+
+            let response = web.get("/crate/fake/0.1.0/source/?format=json").send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            assert_eq!(
+                response.json::<serde_json::Value>()?,
+                serde_json::json!({"error": {
+                    "code": "source_not_available",
+                    "message": "the source code of this version is not available",
+                }}),
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn file_list_json() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("fake")
+                .version("0.1.0")
+                .source_file("src/lib.rs", b"pub fn foo() {}")
+                .source_file("README.md", b"hello")
+                .create()?;
+
+            let response = env
+                .frontend()
+                .get("/crate/fake/0.1.0/source/src/?format=json")
+                .send()?;
+            assert!(response.status().is_success());
+            assert_cache_control(
+                &response,
+                CachePolicy::ForeverInCdnAndStaleInBrowser,
+                &env.config(),
+            );
+            assert_eq!(
+                response.json::<serde_json::Value>()?,
+                serde_json::json!({
+                    "name": "fake",
+                    "version": "0.1.0",
+                    "path": "src/",
+                    "files": [{ "name": "lib.rs", "mime": "text/rust" }],
+                }),
+            );
+
+            let response = env
+                .frontend()
+                .get("/crate/fake/0.1.0/source/src/")
+                .header("accept", "application/json")
+                .send()?;
+            assert!(response.status().is_success());
+            assert!(response
+                .headers()
+                .get_all("vary")
+                .iter()
+                .any(|value| value == "Accept"));
+            assert_eq!(response.json::<serde_json::Value>()?["path"], "src/");
+
+            let response = env
+                .frontend()
+                .get("/crate/fake/0.1.0/source/src/?format=xml")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            Ok(())
+        });
+    }
+
+    #[test_case(&["src/lib.rs", "Cargo.toml"], Some("Cargo.toml"))]
+    #[test_case(
+        &["foo/Cargo.toml", "Cargo.toml.orig", "foo/bar/Cargo.toml"],
+        Some("foo/Cargo.toml");
+        "workspace"
+    )]
+    #[test_case(&["src/lib.rs", "NotCargo.toml"], None; "missing")]
+    fn find_manifest_in_files(paths: &[&str], expected: Option<&str>) {
+        let files = serde_json::json!(paths
+            .iter()
+            .map(|path| serde_json::json!(["text/toml", path]))
+            .collect::<Vec<_>>());
+        assert_eq!(super::find_manifest(&files).as_deref(), expected);
+    }
+
+    #[test_case(true)]
+    #[test_case(false)]
+    fn cargo_toml(archive_storage: bool) {
+        wrapper(|env| {
+            env.fake_release()
+                .archive_storage(archive_storage)
+                .name("fake")
+                .version("0.1.0")
+                .source_file("Cargo.toml", b"[package]\nname = \"fake\"\n")
+                .source_file("src/lib.rs", b"pub fn foo() {}")
+                .create()?;
+            let web = env.frontend();
+
+            let response = web.get("/crate/fake/0.1.0/Cargo.toml").send()?;
+            assert!(response.status().is_success());
+            assert_cache_control(&response, CachePolicy::ForeverInCdn, &env.config());
+            assert_eq!(
+                response.headers()["content-type"],
+                "text/plain; charset=utf-8"
+            );
+            assert_eq!(response.text()?, "[package]\nname = \"fake\"\n");
+
+            assert_redirect_cached(
+                "/crate/fake/0.1/Cargo.toml",
+                "/crate/fake/0.1.0/Cargo.toml",
+                CachePolicy::ForeverInCdn,
+                web,
+                &env.config(),
+            )?;
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn cargo_toml_not_found() {
+        wrapper(|env| {
+            let release_id = env
+                .fake_release()
+                .name("fake")
+                .version("0.1.0")
+                .source_file("Cargo.toml", b"[package]")
+                .create()?;
+            env.fake_release()
+                .name("fake")
+                .version("0.2.0")
+                .source_file("src/lib.rs", b"pub fn foo() {}")
+                .create()?;
+            let web = env.frontend();
+
+            assert_eq!(
+                web.get("/crate/fake/0.2.0/Cargo.toml").send()?.status(),
+                StatusCode::NOT_FOUND
+            );
+
+            // releases built before the source code was stored
+            env.db().conn().execute(
+                "UPDATE releases SET files = NULL WHERE id = $1",
+                &[&release_id],
+            )?;
+            assert_eq!(
+                web.get("/crate/fake/0.1.0/Cargo.toml").send()?.status(),
+                StatusCode::NOT_FOUND
+            );
+            Ok(())
+        });
+    }
+
+    #[test_case(None, RequestedRange::Full)]
+    #[test_case(Some("bytes=0-9"), RequestedRange::Partial(0..=9))]
+    #[test_case(Some("bytes=90-"), RequestedRange::Partial(90..=99))]
+    #[test_case(Some("bytes=-10"), RequestedRange::Partial(90..=99))]
+    #[test_case(Some("bytes=-200"), RequestedRange::Partial(0..=99))]
+    #[test_case(Some("bytes=50-200"), RequestedRange::Partial(50..=99))]
+    #[test_case(Some("bytes=100-"), RequestedRange::Unsatisfiable)]
+    #[test_case(Some("bytes=-0"), RequestedRange::Unsatisfiable)]
+    #[test_case(Some("bytes=9-0"), RequestedRange::Full)]
+    #[test_case(Some("bytes=0-9,20-29"), RequestedRange::Full)]
+    #[test_case(Some("items=0-9"), RequestedRange::Full)]
+    fn requested_ranges(header: Option<&str>, expected: RequestedRange) {
+        let header = header.map(axum::http::HeaderValue::from_static);
+        assert_eq!(requested_range(header.as_ref(), 100), expected);
+    }
+
+    #[test]
+    fn source_archive_ranges() {
+        wrapper(|env| {
+            env.fake_release()
+                .archive_storage(true)
+                .name("fake")
+                .version("0.1.0")
+                .source_file("src/lib.rs", b"pub fn foo() {}")
+                .create()?;
+            let web = env.frontend();
+
+            let response = web.get("/crate/fake/0.1.0/source.zip").send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_cache_control(
+                &response,
+                CachePolicy::ForeverInCdnAndStaleInBrowser,
+                &env.config(),
+            );
+            assert_eq!(response.headers()["accept-ranges"], "bytes");
+            assert_eq!(response.headers()["content-type"], "application/zip");
+            let etag = response.headers()["etag"].to_str()?.to_owned();
+            let last_modified = response.headers()["last-modified"].to_str()?.to_owned();
+            let archive = response.bytes()?.to_vec();
+            let size = archive.len();
+            assert!(size > 20);
+
+            for (range, start) in [
+                ("bytes=0-9".to_owned(), 0),
+                // open-ended, resuming a download
+                (format!("bytes={}-", size - 10), size - 10),
+            ] {
+                let response = web
+                    .get("/crate/fake/0.1.0/source.zip")
+                    .header("range", &range)
+                    .send()?;
+                assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT, "{}", range);
+                assert_cache_control(&response, CachePolicy::NoCaching, &env.config());
+                assert_eq!(
+                    response.headers()["content-range"],
+                    format!("bytes {}-{}/{}", start, start + 9, size).as_str(),
+                    "{}",
+                    range
+                );
+                assert_eq!(response.headers()["accept-ranges"], "bytes");
+                assert_eq!(
+                    response.bytes()?.as_ref(),
+                    &archive[start..start + 10],
+                    "{}",
+                    range
+                );
+            }
+
+            let response = web
+                .get("/crate/fake/0.1.0/source.zip")
+                .header("range", format!("bytes={}-", size))
+                .send()?;
+            assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+            assert_cache_control(&response, CachePolicy::NoCaching, &env.config());
+            assert_eq!(
+                response.headers()["content-range"],
+                format!("bytes */{}", size).as_str()
+            );
+
+            // ranges are only served for the archive named by `If-Range`
+            for if_range in [etag.as_str(), last_modified.as_str()] {
+                let response = web
+                    .get("/crate/fake/0.1.0/source.zip")
+                    .header("range", "bytes=0-9")
+                    .header("if-range", if_range)
+                    .send()?;
+                assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+                assert_eq!(response.bytes()?.as_ref(), &archive[..10]);
+            }
+            for if_range in [
+                "\"0-0\"",
+                &format!("W/{}", etag),
+                "Thu, 01 Jan 1970 00:00:00 GMT",
+            ] {
+                let response = web
+                    .get("/crate/fake/0.1.0/source.zip")
+                    .header("range", "bytes=0-9")
+                    .header("if-range", if_range)
+                    .send()?;
+                assert_eq!(response.status(), StatusCode::OK, "{}", if_range);
+                assert_cache_control(
+                    &response,
+                    CachePolicy::ForeverInCdnAndStaleInBrowser,
+                    &env.config(),
+                );
+                assert_eq!(response.bytes()?.as_ref(), archive.as_slice());
+            }
+
+            assert_redirect_cached(
+                "/crate/fake/0.1/source.zip",
+                "/crate/fake/0.1.0/source.zip",
+                CachePolicy::ForeverInCdn,
+                web,
+                &env.config(),
+            )?;
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn source_archive_not_found() {
+        wrapper(|env| {
+            env.fake_release()
+                .archive_storage(false)
+                .name("fake")
+                .version("0.1.0")
+                .create()?;
+            let web = env.frontend();
+
+            for url in [
+                "/crate/fake/0.1.0/source.zip",
+                "/crate/fake/0.2.0/source.zip",
+            ] {
+                assert_eq!(
+                    web.get(url).send()?.status(),
+                    StatusCode::NOT_FOUND,
+                    "{}",
+                    url
+                );
+            }
+            Ok(())
+        });
+    }
 }
@@ -1,8 +1,9 @@
 use super::{
+    base_url::BaseUrl,
     cache::CachePolicy,
     error::{AxumNope, AxumResult},
 };
-use crate::utils::report_error;
+use crate::{impl_axum_webpage, utils::report_error, Config};
 use anyhow::Context;
 use axum::{
     extract::{Extension, Path},
@@ -16,7 +17,8 @@ use chrono::prelude::*;
 use httpdate::fmt_http_date;
 use mime::Mime;
 use mime_guess::MimeGuess;
-use std::{ffi::OsStr, path, time::SystemTime};
+use serde::Serialize;
+use std::{ffi::OsStr, path, sync::Arc, time::SystemTime};
 use tokio::fs;
 
 const VENDORED_CSS: &str = include_str!(concat!(env!("OUT_DIR"), "/vendored.css"));
@@ -26,7 +28,23 @@ const RUSTDOC_2021_12_05_CSS: &str =
     include_str!(concat!(env!("OUT_DIR"), "/rustdoc-2021-12-05.css"));
 const STATIC_SEARCH_PATHS: &[&str] = &["static", "vendor"];
 
-pub(crate) async fn static_handler(Path(path): Path<String>) -> AxumResult<impl IntoResponse> {
+/// The OpenSearch description, which has to contain absolute URLs of the public host
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct OpenSearchXml {
+    base_url: String,
+}
+
+impl_axum_webpage! {
+    OpenSearchXml = "core/opensearch.xml",
+    content_type = "application/opensearchdescription+xml",
+    cache_policy = |_| CachePolicy::ForeverInCdn,
+}
+
+pub(crate) async fn static_handler(
+    Path(path): Path<String>,
+    BaseUrl(base_url): BaseUrl,
+    Extension(config): Extension<Arc<Config>>,
+) -> AxumResult<impl IntoResponse> {
     let text_css: Mime = "text/css".parse().unwrap();
 
     Ok(match path.as_str() {
@@ -34,36 +52,77 @@ pub(crate) async fn static_handler(Path(path): Path<String>) -> AxumResult<impl
         "style.css" => build_response(STYLE_CSS, text_css),
         "rustdoc.css" => build_response(RUSTDOC_CSS, text_css),
         "rustdoc-2021-12-05.css" => build_response(RUSTDOC_2021_12_05_CSS, text_css),
-        file => match serve_file(file).await {
+        "opensearch.xml" => OpenSearchXml { base_url }.into_response(),
+        file => match serve_file(file, config.static_directory_index).await {
             Ok(response) => response.into_response(),
             Err(err) => return Err(err),
         },
     })
 }
 
-async fn serve_file(file: &str) -> AxumResult<impl IntoResponse> {
+async fn serve_file(file: &str, directory_index: bool) -> AxumResult<Response> {
+    serve_file_from(STATIC_SEARCH_PATHS, file, directory_index).await
+}
+
+/// Serves `file` from the first of the `roots` containing it.
+///
+/// With `directory_index`, directories are served by their `index.html`, otherwise they are not
+/// found like files outside the roots.
+async fn serve_file_from<P: AsRef<path::Path>>(
+    roots: &[P],
+    file: &str,
+    directory_index: bool,
+) -> AxumResult<Response> {
+    // Only plain relative paths are allowed, anything with `..` or starting at the filesystem
+    // root can't be a static file.
+    let relative = path::Path::new(file);
+    if !relative
+        .components()
+        .all(|component| matches!(component, path::Component::Normal(_)))
+    {
+        return Err(AxumNope::ResourceNotFound);
+    }
+
     // Find the first path that actually exists
-    let path = STATIC_SEARCH_PATHS
+    let path = roots
         .iter()
         .find_map(|root| {
-            let path = path::Path::new(root).join(file);
+            let path = root.as_ref().join(relative);
             if !path.exists() {
                 return None;
             }
 
-            // Prevent accessing static files outside the root. This could happen if the path
-            // contains `/` or `..`. The check doesn't outright prevent those strings to be present
-            // to allow accessing files in subdirectories.
+            // Prevent accessing static files outside the root. This could still happen through
+            // symlinks, which aren't resolved by the check above.
             let canonical_path = std::fs::canonicalize(path).ok()?;
             let canonical_root = std::fs::canonicalize(root).ok()?;
-            if canonical_path.starts_with(canonical_root) {
-                Some(canonical_path)
+            if canonical_path.starts_with(&canonical_root) {
+                Some((canonical_path, canonical_root))
             } else {
                 None
             }
         })
         .ok_or(AxumNope::ResourceNotFound)?;
 
+    let path = match path {
+        (path, root) if path.is_dir() => {
+            if !directory_index {
+                return Err(AxumNope::ResourceNotFound);
+            }
+            // relative links in the index only work with a trailing slash
+            if !file.ends_with('/') {
+                return Ok(super::axum_redirect(format!("/-/static/{}/", file))?.into_response());
+            }
+            let index = std::fs::canonicalize(path.join("index.html"))
+                .map_err(|_| AxumNope::ResourceNotFound)?;
+            if !index.starts_with(root) || !index.is_file() {
+                return Err(AxumNope::ResourceNotFound);
+            }
+            index
+        }
+        (path, _) => path,
+    };
+
     let contents = fs::read(&path)
         .await
         .with_context(|| format!("failed to read static file {}", path.display()))
@@ -75,19 +134,16 @@ async fn serve_file(file: &str) -> AxumResult<impl IntoResponse> {
     // If we can detect the file's mime type, set it
     // MimeGuess misses a lot of the file types we need, so there's a small wrapper
     // around it
-    let content_type: Mime = if file == "opensearch.xml" {
-        "application/opensearchdescription+xml".parse().unwrap()
-    } else {
-        path.extension()
-            .and_then(OsStr::to_str)
-            .and_then(|ext| match ext {
-                "eot" => Some("application/vnd.ms-fontobject".parse().unwrap()),
-                "woff2" => Some("application/font-woff2".parse().unwrap()),
-                "ttf" => Some("application/x-font-ttf".parse().unwrap()),
-                _ => MimeGuess::from_path(&path).first(),
-            })
-            .unwrap_or(mime::APPLICATION_OCTET_STREAM)
-    };
+    let content_type: Mime = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .and_then(|ext| match ext {
+            "eot" => Some("application/vnd.ms-fontobject".parse().unwrap()),
+            "woff2" => Some("application/font-woff2".parse().unwrap()),
+            "ttf" => Some("application/x-font-ttf".parse().unwrap()),
+            _ => MimeGuess::from_path(&path).first(),
+        })
+        .unwrap_or(mime::APPLICATION_OCTET_STREAM);
 
     Ok(build_response(contents, content_type))
 }
@@ -111,7 +167,7 @@ where
 
 #[cfg(test)]
 mod tests {
-    use super::{serve_file, STATIC_SEARCH_PATHS, STYLE_CSS, VENDORED_CSS};
+    use super::{serve_file, serve_file_from, STATIC_SEARCH_PATHS, STYLE_CSS, VENDORED_CSS};
     use crate::{
         test::{assert_cache_control, wrapper},
         web::{cache::CachePolicy, error::AxumNope},
@@ -180,6 +236,24 @@ mod tests {
         });
     }
 
+    #[test]
+    fn opensearch_xml() {
+        wrapper(|env| {
+            let resp = env.frontend().get("/-/static/opensearch.xml").send()?;
+            assert!(resp.status().is_success());
+            assert_cache_control(&resp, CachePolicy::ForeverInCdn, &env.config());
+            assert_eq!(
+                resp.headers().get("Content-Type"),
+                Some(&"application/opensearchdescription+xml".parse().unwrap()),
+            );
+            assert!(resp
+                .text()?
+                .contains("template=\"https://docs.rs/releases/search?query={searchTerms}\""));
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn static_files() {
         wrapper(|env| {
@@ -246,6 +320,58 @@ mod tests {
         });
     }
 
+    #[test]
+    fn static_directory() {
+        wrapper(|env| {
+            let web = env.frontend();
+            for path in ["/-/static/chartjs", "/-/static/chartjs/"] {
+                let response = web.get(path).send()?;
+                assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", path);
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn static_directory_without_index() {
+        wrapper(|env| {
+            env.override_config(|config| config.static_directory_index = true);
+            let response = env.frontend().get("/-/static/chartjs/").send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            Ok(())
+        });
+    }
+
+    #[tokio::test]
+    async fn directory_index() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("site/docs")).unwrap();
+        fs::write(root.path().join("site/index.html"), "<h1>site</h1>").unwrap();
+        fs::write(root.path().join("site/docs/index.html"), "<h1>docs</h1>").unwrap();
+        let roots = [root.path()];
+
+        for (file, expected) in [("site/", "<h1>site</h1>"), ("site/docs/", "<h1>docs</h1>")] {
+            let response = serve_file_from(&roots, file, true).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["content-type"], "text/html");
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            assert_eq!(body, expected);
+        }
+
+        let response = serve_file_from(&roots, "site/docs", true).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(response.headers()["location"], "/-/static/site/docs/");
+
+        assert!(matches!(
+            serve_file_from(&roots, "site/", false).await,
+            Err(AxumNope::ResourceNotFound)
+        ));
+        assert!(matches!(
+            serve_file_from(&roots, "site/docs/../", true).await,
+            Err(AxumNope::ResourceNotFound)
+        ));
+    }
+
     #[tokio::test]
     async fn directory_traversal() {
         const PATHS: &[&str] = &[
@@ -264,7 +390,10 @@ mod tests {
             // serve the file also includes protection for path traversal, in the event we switch
             // to a framework that doesn't include builtin protection in the future.
             assert!(
-                matches!(serve_file(path).await, Err(AxumNope::ResourceNotFound)),
+                matches!(
+                    serve_file(path, true).await,
+                    Err(AxumNope::ResourceNotFound)
+                ),
                 "{} did not return a 404",
                 path
             );
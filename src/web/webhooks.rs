@@ -0,0 +1,348 @@
+//! Endpoints for crate owners to manage their build notification webhooks, see
+//! [`crate::webhooks`].
+//!
+//! Owners authenticate with a crates.io API token as bearer token, which is checked against the
+//! crates owned by its user on crates.io.
+
+use crate::{
+    db::Pool,
+    utils::{spawn_blocking, APP_USER_AGENT},
+    web::{
+        auth::bearer_token,
+        cache::CachePolicy,
+        error::{AxumNope, JsonAxumResult},
+    },
+    webhooks::{self, Webhook},
+};
+use anyhow::Result;
+use axum::{
+    extract::{Extension, Path},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use once_cell::sync::Lazy;
+use reqwest::{
+    header::{HeaderValue, ACCEPT, USER_AGENT},
+    Client as HttpClient,
+};
+use serde::Deserialize;
+use tracing::info;
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct WebhookParams {
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CratesIoMe {
+    user: CratesIoUser,
+    owned_crates: Vec<CratesIoOwnedCrate>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CratesIoUser {
+    login: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CratesIoOwnedCrate {
+    name: String,
+}
+
+static HTTP_CLIENT: Lazy<HttpClient> = Lazy::new(|| {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(USER_AGENT, HeaderValue::from_static(APP_USER_AGENT));
+    headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+    HttpClient::builder()
+        .default_headers(headers)
+        .build()
+        .unwrap()
+});
+
+/// Checks that the bearer token is a crates.io API token of an owner of the crate, and returns
+/// the login of its user.
+async fn authenticate_owner(headers: &HeaderMap, name: &str) -> Result<String, AxumNope> {
+    let token = bearer_token(headers)?;
+
+    #[cfg(not(test))]
+    let host = "https://crates.io";
+    #[cfg(test)]
+    let host = mockito::server_url();
+
+    let response = HTTP_CLIENT
+        .get(format!("{}/api/v1/me", host))
+        .header(AUTHORIZATION, token)
+        .send()
+        .await
+        .map_err(anyhow::Error::from)?;
+    if response.status().is_client_error() {
+        return Err(AxumNope::Unauthorized);
+    }
+    let me: CratesIoMe = response
+        .error_for_status()
+        .map_err(anyhow::Error::from)?
+        .json()
+        .await
+        .map_err(anyhow::Error::from)?;
+
+    if me.owned_crates.iter().any(|krate| krate.name == name) {
+        Ok(me.user.login)
+    } else {
+        Err(AxumNope::Forbidden(format!(
+            "{} is not an owner of {}",
+            me.user.login, name
+        )))
+    }
+}
+
+fn validate_url(url: &str) -> Result<(), AxumNope> {
+    match url::Url::parse(url) {
+        Ok(parsed) if parsed.scheme() == "https" && parsed.host().is_some() => Ok(()),
+        _ => Err(AxumNope::BadRequest(format!(
+            "invalid webhook url `{}`, only https urls are supported",
+            url
+        ))),
+    }
+}
+
+fn crate_id(pool: &Pool, name: &str) -> Result<i32> {
+    let mut conn = pool.get()?;
+    Ok(conn
+        .query_opt("SELECT id FROM crates WHERE name = $1", &[&name])?
+        .ok_or(AxumNope::CrateNotFound)?
+        .get(0))
+}
+
+/// Registers a webhook notified about every finished build of the crate. The response contains
+/// the secret used to sign the payloads, it isn't shown again.
+pub(crate) async fn register_webhook_handler(
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    Extension(pool): Extension<Pool>,
+    Json(params): Json<WebhookParams>,
+) -> JsonAxumResult<impl IntoResponse> {
+    validate_url(&params.url)?;
+    let login = authenticate_owner(&headers, &name).await?;
+    let crate_id = spawn_blocking({
+        let pool = pool.clone();
+        let name = name.clone();
+        move || crate_id(&pool, &name)
+    })
+    .await?;
+    spawn_blocking({
+        let url = params.url.clone();
+        move || {
+            webhooks::check_public_url(&url)
+                .map_err(|err| AxumNope::BadRequest(format!("invalid webhook url: {}", err)).into())
+        }
+    })
+    .await?;
+
+    let webhook: Webhook = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let webhook = webhooks::register(&mut conn, crate_id, &params.url, &login)?
+            .ok_or_else(|| AxumNope::Conflict(format!("{} is already registered", params.url)))?;
+        info!(%name, url = %params.url, %login, "registered webhook");
+        Ok(webhook)
+    })
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Extension(CachePolicy::NoCaching),
+        Json(webhook),
+    ))
+}
+
+pub(crate) async fn unregister_webhook_handler(
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    Extension(pool): Extension<Pool>,
+    Json(params): Json<WebhookParams>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let login = authenticate_owner(&headers, &name).await?;
+    let crate_id = spawn_blocking({
+        let pool = pool.clone();
+        let name = name.clone();
+        move || crate_id(&pool, &name)
+    })
+    .await?;
+
+    spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        if !webhooks::unregister(&mut conn, crate_id, &params.url)? {
+            return Err(AxumNope::ResourceNotFound.into());
+        }
+        info!(%name, url = %params.url, %login, "unregistered webhook");
+        Ok(())
+    })
+    .await?;
+
+    Ok((StatusCode::NO_CONTENT, Extension(CachePolicy::NoCaching)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test::{assert_cache_control, wrapper};
+    use crate::web::cache::CachePolicy;
+    use mockito::{mock, Mock};
+    use reqwest::StatusCode;
+    use serde_json::json;
+
+    fn mock_crates_io_user(token: &str, login: &str, owned_crates: &[&str]) -> Mock {
+        mock("GET", "/api/v1/me")
+            .match_header("authorization", token)
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({
+                    "user": { "login": login },
+                    "owned_crates": owned_crates
+                        .iter()
+                        .map(|name| json!({ "name": name }))
+                        .collect::<Vec<_>>(),
+                })
+                .to_string(),
+            )
+            .create()
+    }
+
+    #[test]
+    fn register_and_unregister_webhook() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let _m = mock_crates_io_user("owner-token", "owner", &["foo"]);
+            let web = env.frontend();
+            let body = json!({ "url": "https://93.184.216.34/hook" });
+
+            let response = web
+                .post("/crate/foo/webhooks")
+                .bearer_auth("owner-token")
+                .json(&body)
+                .send()?;
+            assert_eq!(response.status(), StatusCode::CREATED);
+            assert_cache_control(&response, CachePolicy::NoCaching, &env.config());
+            let webhook: serde_json::Value = response.json()?;
+            assert_eq!(webhook["url"], "https://93.184.216.34/hook");
+            assert_eq!(webhook["secret"].as_str().unwrap().len(), 64);
+
+            let created_by: String = env
+                .db()
+                .conn()
+                .query_one("SELECT created_by FROM webhooks", &[])?
+                .get(0);
+            assert_eq!(created_by, "owner");
+
+            let response = web
+                .post("/crate/foo/webhooks")
+                .bearer_auth("owner-token")
+                .json(&body)
+                .send()?;
+            assert_eq!(response.status(), StatusCode::CONFLICT);
+
+            let response = web
+                .delete("/crate/foo/webhooks")
+                .bearer_auth("owner-token")
+                .json(&body)
+                .send()?;
+            assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+            let response = web
+                .delete("/crate/foo/webhooks")
+                .bearer_auth("owner-token")
+                .json(&body)
+                .send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn webhooks_require_an_owner() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let _other = mock_crates_io_user("other-token", "other", &["bar"]);
+            let _invalid = mock("GET", "/api/v1/me")
+                .match_header("authorization", "invalid-token")
+                .with_status(403)
+                .create();
+            let web = env.frontend();
+            let body = json!({ "url": "https://example.com/hook" });
+
+            let response = web.post("/crate/foo/webhooks").json(&body).send()?;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+            let response = web
+                .post("/crate/foo/webhooks")
+                .bearer_auth("invalid-token")
+                .json(&body)
+                .send()?;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+            let response = web
+                .post("/crate/foo/webhooks")
+                .bearer_auth("other-token")
+                .json(&body)
+                .send()?;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+            let count: i64 = env
+                .db()
+                .conn()
+                .query_one("SELECT COUNT(*) FROM webhooks", &[])?
+                .get(0);
+            assert_eq!(count, 0);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn register_webhook_validation() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let _m = mock_crates_io_user("owner-token", "owner", &["foo", "unknown"]);
+            let web = env.frontend();
+
+            for url in ["http://example.com/hook", "ftp://example.com", "not a url"] {
+                let response = web
+                    .post("/crate/foo/webhooks")
+                    .bearer_auth("owner-token")
+                    .json(&json!({ "url": url }))
+                    .send()?;
+                assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", url);
+            }
+
+            // webhooks can't reach internal services
+            for url in [
+                "https://127.0.0.1/hook",
+                "https://localhost/hook",
+                "https://10.0.0.1/hook",
+                "https://169.254.169.254/latest/meta-data",
+                "https://[::1]/hook",
+            ] {
+                let response = web
+                    .post("/crate/foo/webhooks")
+                    .bearer_auth("owner-token")
+                    .json(&json!({ "url": url }))
+                    .send()?;
+                assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", url);
+            }
+
+            let response = web
+                .post("/crate/unknown/webhooks")
+                .bearer_auth("owner-token")
+                .json(&json!({ "url": "https://93.184.216.34/hook" }))
+                .send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            // the crate isn't looked up before authenticating
+            let response = web
+                .post("/crate/unknown/webhooks")
+                .json(&json!({ "url": "https://93.184.216.34/hook" }))
+                .send()?;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            Ok(())
+        })
+    }
+}
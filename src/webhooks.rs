@@ -0,0 +1,474 @@
+//! Build notification webhooks.
+//!
+//! Crate owners can register URLs that get a signed `POST` whenever a build of their crate
+//! finishes. Notifications are queued in the database when the build is stored and delivered by
+//! the daemon, failed deliveries are retried with an exponential backoff until
+//! `DOCSRS_WEBHOOK_MAX_ATTEMPTS` is reached, after which they're kept as dead letters.
+
+use crate::{utils::APP_USER_AGENT, Config};
+use anyhow::{bail, Context as _, Result};
+use hmac::{Hmac, Mac};
+use postgres::Client;
+use reqwest::{
+    blocking::Client as HttpClient,
+    header::{CONTENT_TYPE, USER_AGENT},
+};
+use serde::Serialize;
+use sha2::Sha256;
+use std::{
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    time::Duration,
+};
+use tracing::{debug, warn};
+use url::{Host, Url};
+
+/// Header with the HMAC-SHA256 of the payload, keyed with the secret of the webhook
+pub(crate) const SIGNATURE_HEADER: &str = "x-docsrs-signature";
+/// Header with the ID of the delivery, which stays the same across retries
+pub(crate) const DELIVERY_HEADER: &str = "x-docsrs-delivery";
+/// Deliveries attempted in a single run of the delivery loop
+const DELIVERY_BATCH_SIZE: i64 = 100;
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+/// The backoff between attempts doubles until it reaches `2^MAX_BACKOFF_EXPONENT` minutes
+const MAX_BACKOFF_EXPONENT: u32 = 10;
+
+/// A registered webhook, the secret is only shown when registering it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct Webhook {
+    pub(crate) id: i32,
+    pub(crate) url: String,
+    pub(crate) secret: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum BuildStatus {
+    Success,
+    Failure,
+}
+
+/// The payload sent to webhooks after a build
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct BuildNotification {
+    #[serde(rename = "crate")]
+    pub(crate) krate: String,
+    pub(crate) version: String,
+    pub(crate) build_id: i32,
+    pub(crate) status: BuildStatus,
+    pub(crate) log_url: String,
+}
+
+/// Registers a webhook for a crate with a new random secret. Returns `None` when the URL is
+/// already registered for the crate.
+pub(crate) fn register(
+    conn: &mut Client,
+    crate_id: i32,
+    url: &str,
+    created_by: &str,
+) -> Result<Option<Webhook>> {
+    let mut secret = [0u8; 32];
+    getrandom::getrandom(&mut secret).context("failed to generate a webhook secret")?;
+    let secret = hex::encode(secret);
+
+    Ok(conn
+        .query_opt(
+            "INSERT INTO webhooks (crate_id, url, secret, created_by)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (crate_id, url) DO NOTHING
+             RETURNING id",
+            &[&crate_id, &url, &secret, &created_by],
+        )?
+        .map(|row| Webhook {
+            id: row.get(0),
+            url: url.to_owned(),
+            secret,
+        }))
+}
+
+/// Removes a webhook and its pending deliveries, returns whether it was registered.
+pub(crate) fn unregister(conn: &mut Client, crate_id: i32, url: &str) -> Result<bool> {
+    Ok(conn.execute(
+        "DELETE FROM webhooks WHERE crate_id = $1 AND url = $2",
+        &[&crate_id, &url],
+    )? > 0)
+}
+
+/// Queues a notification about a finished build for every webhook of the crate.
+pub(crate) fn queue_build_notifications(
+    conn: &mut Client,
+    config: &Config,
+    name: &str,
+    version: &str,
+    build_id: i32,
+    successful: bool,
+) -> Result<u64> {
+    let payload = serde_json::to_string(&BuildNotification {
+        krate: name.to_owned(),
+        version: version.to_owned(),
+        build_id,
+        status: if successful {
+            BuildStatus::Success
+        } else {
+            BuildStatus::Failure
+        },
+        log_url: format!(
+            "{}/crate/{}/{}/builds/{}",
+            config.public_base_url, name, version, build_id
+        ),
+    })?;
+
+    Ok(conn.execute(
+        "INSERT INTO webhook_deliveries (webhook_id, payload)
+         SELECT webhooks.id, $2
+         FROM webhooks
+         INNER JOIN crates ON crates.id = webhooks.crate_id
+         WHERE crates.name = $1",
+        &[&name, &payload],
+    )?)
+}
+
+/// The hex encoded HMAC-SHA256 of the payload
+pub(crate) fn sign(secret: &str, payload: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(payload);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Whether `ip` is reachable on the internet, and not one of our own or a private address.
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // shared address space, RFC 6598
+                || (a == 100 && (64..128).contains(&b))
+                || a == 0
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(ip));
+            }
+            let segments = ip.segments();
+            let first = segments[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // NAT64 addresses embedding an IPv4 address, 64:ff9b::/96
+                || segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+                // unique local addresses, fc00::/7
+                || (first & 0xfe00) == 0xfc00
+                // link-local addresses, fe80::/10
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Resolves the host of a webhook URL and checks that all its addresses are public, so webhooks
+/// can't be used to send requests to our internal services. Returns the checked addresses.
+fn resolve_public_url(url: &Url) -> Result<Vec<SocketAddr>> {
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addrs: Vec<SocketAddr> = match url.host() {
+        Some(Host::Ipv4(ip)) => vec![(ip, port).into()],
+        Some(Host::Ipv6(ip)) => vec![(ip, port).into()],
+        Some(Host::Domain(domain)) => (domain, port)
+            .to_socket_addrs()
+            .with_context(|| format!("could not resolve {}", domain))?
+            .collect(),
+        None => bail!("{} has no host", url),
+    };
+    if addrs.is_empty() {
+        bail!("{} doesn't resolve to any address", url);
+    }
+    #[cfg(test)]
+    if url.origin() == Url::parse(&mockito::server_url())?.origin() {
+        return Ok(addrs);
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+        bail!("{} resolves to the non-public address {}", url, addr.ip());
+    }
+    Ok(addrs)
+}
+
+/// Checks that a webhook URL only resolves to public addresses, see [`resolve_public_url`].
+pub(crate) fn check_public_url(url: &str) -> Result<()> {
+    resolve_public_url(&Url::parse(url)?)?;
+    Ok(())
+}
+
+/// A client sending requests to `url` only to the addresses `resolve_public_url` checked.
+/// Resolving the host again when sending could give other addresses, e.g. with DNS rebinding.
+fn pinned_http_client(url: &Url) -> Result<HttpClient> {
+    let addrs = resolve_public_url(url)?;
+    let mut builder = HttpClient::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        // redirects could lead to addresses `resolve_public_url` rejects
+        .redirect(reqwest::redirect::Policy::none());
+    if let Some(Host::Domain(domain)) = url.host() {
+        builder = builder.resolve_to_addrs(domain, &addrs);
+    }
+    Ok(builder.build()?)
+}
+
+fn deliver(url: &str, secret: &str, id: i32, payload: &str) -> Result<()> {
+    let url = Url::parse(url)?;
+    pinned_http_client(&url)?
+        .post(url)
+        .header(USER_AGENT, APP_USER_AGENT)
+        .header(CONTENT_TYPE, "application/json")
+        .header(
+            SIGNATURE_HEADER,
+            format!("sha256={}", sign(secret, payload.as_bytes())),
+        )
+        .header(DELIVERY_HEADER, id.to_string())
+        .body(payload.to_owned())
+        .send()?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Attempts all deliveries that are due, returns how many of them succeeded.
+pub(crate) fn deliver_pending(conn: &mut Client, config: &Config) -> Result<usize> {
+    let pending = conn.query(
+        "SELECT webhook_deliveries.id,
+                webhooks.url,
+                webhooks.secret,
+                webhook_deliveries.payload,
+                webhook_deliveries.attempts
+         FROM webhook_deliveries
+         INNER JOIN webhooks ON webhooks.id = webhook_deliveries.webhook_id
+         WHERE
+            webhook_deliveries.dead_at IS NULL AND
+            webhook_deliveries.next_attempt <= NOW()
+         ORDER BY webhook_deliveries.next_attempt
+         LIMIT $1",
+        &[&DELIVERY_BATCH_SIZE],
+    )?;
+
+    let mut delivered = 0;
+    for row in pending {
+        let id: i32 = row.get(0);
+        let url: String = row.get(1);
+        let attempts = row.get::<_, i32>(4) + 1;
+
+        match deliver(&url, row.get(2), id, row.get(3)) {
+            Ok(()) => {
+                debug!(id, %url, attempts, "delivered webhook");
+                conn.execute("DELETE FROM webhook_deliveries WHERE id = $1", &[&id])?;
+                delivered += 1;
+            }
+            Err(err) if attempts >= i32::from(config.webhook_max_attempts) => {
+                warn!(id, %url, attempts, "giving up on webhook delivery: {:?}", err);
+                conn.execute(
+                    "UPDATE webhook_deliveries
+                     SET attempts = $2, last_error = $3, dead_at = NOW()
+                     WHERE id = $1",
+                    &[&id, &attempts, &format!("{:?}", err)],
+                )?;
+            }
+            Err(err) => {
+                debug!(id, %url, attempts, "webhook delivery failed: {:?}", err);
+                let backoff_minutes = 2i32.pow((attempts as u32).min(MAX_BACKOFF_EXPONENT));
+                conn.execute(
+                    "UPDATE webhook_deliveries
+                     SET
+                        attempts = $2,
+                        last_error = $3,
+                        next_attempt = NOW() + make_interval(mins => $4)
+                     WHERE id = $1",
+                    &[&id, &attempts, &format!("{:?}", err), &backoff_minutes],
+                )?;
+            }
+        }
+    }
+
+    Ok(delivered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::wrapper;
+    use mockito::mock;
+
+    fn crate_id(conn: &mut Client, name: &str) -> Result<i32> {
+        Ok(conn
+            .query_one("SELECT id FROM crates WHERE name = $1", &[&name])?
+            .get(0))
+    }
+
+    #[test]
+    fn register_and_unregister() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let mut conn = env.db().conn();
+            let id = crate_id(&mut conn, "foo")?;
+
+            let webhook = register(&mut conn, id, "https://example.com/hook", "owner")?.unwrap();
+            assert_eq!(webhook.url, "https://example.com/hook");
+            assert_eq!(webhook.secret.len(), 64);
+
+            // registering the same url twice doesn't change the secret
+            assert!(register(&mut conn, id, "https://example.com/hook", "owner")?.is_none());
+
+            assert!(unregister(&mut conn, id, "https://example.com/hook")?);
+            assert!(!unregister(&mut conn, id, "https://example.com/hook")?);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn public_ips() {
+        for ip in ["93.184.216.34", "2606:2800:220:1:248:1893:25c8:1946"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "224.0.0.1",
+            "239.255.255.250",
+            "64:ff9b::7f00:1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn non_public_urls_are_rejected() {
+        for url in [
+            "https://127.0.0.1/hook",
+            "https://[::1]/hook",
+            "https://localhost/hook",
+            "https://169.254.169.254/latest/meta-data",
+        ] {
+            assert!(check_public_url(url).is_err(), "{}", url);
+        }
+        assert!(check_public_url("https://93.184.216.34/hook").is_ok());
+    }
+
+    #[test]
+    fn signature() {
+        // test vector from RFC 4231, test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn deliver_build_notification() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let mut conn = env.db().conn();
+            let id = crate_id(&mut conn, "foo")?;
+            let webhook = register(
+                &mut conn,
+                id,
+                &format!("{}/hook", mockito::server_url()),
+                "owner",
+            )?
+            .unwrap();
+
+            assert_eq!(
+                queue_build_notifications(&mut conn, &env.config(), "foo", "0.1.0", 42, false)?,
+                1
+            );
+            let payload = format!(
+                r#"{{"crate":"foo","version":"0.1.0","build_id":42,"status":"failure","log_url":"{}/crate/foo/0.1.0/builds/42"}}"#,
+                env.config().public_base_url
+            );
+
+            let _m = mock("POST", "/hook")
+                .match_header(
+                    SIGNATURE_HEADER,
+                    format!("sha256={}", sign(&webhook.secret, payload.as_bytes())).as_str(),
+                )
+                .match_body(payload.as_str())
+                .with_status(200)
+                .expect(1)
+                .create();
+
+            assert_eq!(deliver_pending(&mut conn, &env.config())?, 1);
+            _m.assert();
+
+            let remaining: i64 = conn
+                .query_one("SELECT COUNT(*) FROM webhook_deliveries", &[])?
+                .get(0);
+            assert_eq!(remaining, 0);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn failed_deliveries_are_retried_then_dead_lettered() {
+        wrapper(|env| {
+            env.override_config(|config| config.webhook_max_attempts = 2);
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let mut conn = env.db().conn();
+            let id = crate_id(&mut conn, "foo")?;
+            register(
+                &mut conn,
+                id,
+                &format!("{}/failing-hook", mockito::server_url()),
+                "owner",
+            )?;
+            queue_build_notifications(&mut conn, &env.config(), "foo", "0.1.0", 1, true)?;
+
+            let _m = mock("POST", "/failing-hook").with_status(500).create();
+
+            assert_eq!(deliver_pending(&mut conn, &env.config())?, 0);
+            let row = conn.query_one(
+                "SELECT attempts, next_attempt > NOW(), dead_at IS NULL FROM webhook_deliveries",
+                &[],
+            )?;
+            assert_eq!(row.get::<_, i32>(0), 1);
+            assert!(row.get::<_, bool>(1));
+            assert!(row.get::<_, bool>(2));
+
+            // not due yet
+            assert_eq!(deliver_pending(&mut conn, &env.config())?, 0);
+            assert_eq!(
+                conn.query_one("SELECT attempts FROM webhook_deliveries", &[])?
+                    .get::<_, i32>(0),
+                1
+            );
+
+            conn.execute("UPDATE webhook_deliveries SET next_attempt = NOW()", &[])?;
+            assert_eq!(deliver_pending(&mut conn, &env.config())?, 0);
+            let row = conn.query_one(
+                "SELECT attempts, dead_at IS NOT NULL, last_error FROM webhook_deliveries",
+                &[],
+            )?;
+            assert_eq!(row.get::<_, i32>(0), 2);
+            assert!(row.get::<_, bool>(1));
+            assert!(row.get::<_, String>(2).contains("500"));
+
+            // dead letters aren't attempted again
+            conn.execute("UPDATE webhook_deliveries SET next_attempt = NOW()", &[])?;
+            assert_eq!(deliver_pending(&mut conn, &env.config())?, 0);
+            assert_eq!(
+                conn.query_one("SELECT attempts FROM webhook_deliveries", &[])?
+                    .get::<_, i32>(0),
+                2
+            );
+            Ok(())
+        })
+    }
+}